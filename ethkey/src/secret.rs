@@ -15,6 +15,7 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::fmt;
+use std::ptr;
 use std::ops::Deref;
 use std::str::FromStr;
 use rustc_hex::ToHex;
@@ -173,6 +174,15 @@ impl Deref for Secret {
 	}
 }
 
+impl Drop for Secret {
+	fn drop(&mut self) {
+		// volatile writes are not optimized out, so secret is not left in memory after drop
+		for byte in self.inner.iter_mut() {
+			unsafe { ptr::write_volatile(byte, 0) };
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use std::str::FromStr;
@@ -242,6 +242,28 @@ usage! {
 				"Clean the database",
 			}
 		}
+
+		CMD cmd_secretstore
+		{
+			"Manage key shares, stored by the SecretStore on this system",
+
+			CMD cmd_secretstore_list {
+				"List ids of stored keys",
+			}
+
+			CMD cmd_secretstore_show
+			{
+				"Show metadata of stored key share",
+
+				ARG arg_secretstore_show_key: (Option<String>) = None,
+				"<KEY>",
+				"Key id",
+			}
+
+			CMD cmd_secretstore_verify {
+				"Verify that all stored key shares are readable and consistent",
+			}
+		}
 	}
 	{
 		// Flags and arguments
@@ -563,18 +585,38 @@ usage! {
 			"--no-acl-check",
 			"Disable ACL check (useful for test environments).",
 
+			FLAG flag_secretstore_health: (bool) = false, or |c: &Config| otry!(c.secretstore).health.clone(),
+			"--secretstore-health",
+			"Enable unauthenticated GET /health endpoint of Secret Store HTTP API, reporting this node view of the cluster.",
+
+			ARG arg_secretstore_contract: (String) = "none", or |c: &Config| otry!(c.secretstore).service_contract.clone(),
+			"--secretstore-contract=[SOURCE]",
+			"Secret Store Service contract address source: none, registry (contract address is read from registry) or address. Key generation and document key store requests, emitted by the contract, are processed by the Secret Store.",
+
 			ARG arg_secretstore_nodes: (String) = "", or |c: &Config| otry!(c.secretstore).nodes.as_ref().map(|vec| vec.join(",")),
 			"--secretstore-nodes=[NODES]",
-			"Comma-separated list of other secret store cluster nodes in form NODE_PUBLIC_KEY_IN_HEX@NODE_IP_ADDR:NODE_PORT.",
+			"Comma-separated list of other secret store cluster nodes in form NODE_PUBLIC_KEY_IN_HEX@NODE_ADDR:NODE_PORT. NODE_ADDR is an IPv4 address, an IPv6 address in square brackets or a hostname, which is re-resolved on reconnect.",
+
+			ARG arg_secretstore_read_only_nodes: (String) = "", or |c: &Config| otry!(c.secretstore).read_only_nodes.as_ref().map(|vec| vec.join(",")),
+			"--secretstore-read-only-nodes=[NODES]",
+			"Comma-separated list of public keys (in hex) of read-only secret store cluster nodes. These nodes hold key shares and take part in decryption and signing, but are not allowed to start share recovery and refresh sessions.",
 
 			ARG arg_secretstore_interface: (String) = "local", or |c: &Config| otry!(c.secretstore).interface.clone(),
 			"--secretstore-interface=[IP]",
-			"Specify the hostname portion for listening to Secret Store Key Server internal requests, IP should be an interface's IP address, or local.",
+			"Specify the hostname portion for listening to Secret Store Key Server internal requests, IP should be an interface's IPv4 or IPv6 address, a hostname, or local.",
 
 			ARG arg_secretstore_port: (u16) = 8083u16, or |c: &Config| otry!(c.secretstore).port.clone(),
 			"--secretstore-port=[PORT]",
 			"Specify the port portion for listening to Secret Store Key Server internal requests.",
 
+			ARG arg_secretstore_external_address: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).external_address.clone(),
+			"--secretstore-external-address=[ADDR:PORT]",
+			"Address (IP or hostname and port) of this node, which is reachable by other Secret Store cluster nodes. Use it when node is behind NAT. Reachability of this address is periodically checked and reported by the health endpoint.",
+
+			FLAG flag_secretstore_upnp: (bool) = false, or |c: &Config| otry!(c.secretstore).upnp.clone(),
+			"--secretstore-upnp",
+			"Map Secret Store Key Server internal port on the gateway using UPnP and use resulting address as the external address of this node. Ignored when --secretstore-external-address is specified.",
+
 			ARG arg_secretstore_http_interface: (String) = "local", or |c: &Config| otry!(c.secretstore).http_interface.clone(),
 			"--secretstore-http-interface=[IP]",
 			"Specify the hostname portion for listening to Secret Store Key Server HTTP requests, IP should be an interface's IP address, or local.",
@@ -583,6 +625,34 @@ usage! {
 			"--secretstore-http-port=[PORT]",
 			"Specify the port portion for listening to Secret Store Key Server HTTP requests.",
 
+			ARG arg_secretstore_http_api_keys: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).http_api_keys.clone(),
+			"--secretstore-http-api-keys=[PATH]",
+			"Require every Secret Store HTTP request (except for the health-check request) to carry one of API keys, listed in given file (one key per line), in the 'Authorization: Bearer KEY' header.",
+
+			ARG arg_secretstore_http_cors: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).http_cors.clone(),
+			"--secretstore-http-cors=[URL]",
+			"Comma-separated list of origins, which are allowed to make cross-origin requests to Secret Store HTTP API (\"*\" - any origin). Cross-origin requests are refused by default.",
+
+			ARG arg_secretstore_http_hosts: (String) = "all", or |c: &Config| otry!(c.secretstore).http_hosts.as_ref().map(|vec| vec.join(",")),
+			"--secretstore-http-hosts=[HOSTS]",
+			"List of allowed Host header values of Secret Store HTTP requests. Special options: \"all\", \"none\".",
+
+			ARG arg_secretstore_http_tls_cert: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).http_tls_cert.clone(),
+			"--secretstore-http-tls-cert=[PATH]",
+			"Serve Secret Store HTTP API over TLS, using certificate (optionally followed by intermediate certificates) from given PEM file. Requires --secretstore-http-tls-key.",
+
+			ARG arg_secretstore_http_tls_key: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).http_tls_key.clone(),
+			"--secretstore-http-tls-key=[PATH]",
+			"PEM file with the private key of Secret Store HTTP API TLS certificate.",
+
+			ARG arg_secretstore_http_tls_client_ca: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).http_tls_client_ca.clone(),
+			"--secretstore-http-tls-client-ca=[PATH]",
+			"Require every Secret Store HTTP API client to present TLS certificate, signed by one of CA certificates from given PEM file.",
+
+			ARG arg_secretstore_storage: (String) = "db", or |c: &Config| otry!(c.secretstore).storage.clone(),
+			"--secretstore-storage=[BACKEND]",
+			"Specify where key shares are stored: db - in the database in the SecretStore data directory, file - in the single encrypted file in the SecretStore data directory, memory - in memory only (key shares are lost on restart).",
+
 			ARG arg_secretstore_path: (String) = "$BASE/secretstore", or |c: &Config| otry!(c.secretstore).path.clone(),
 			"--secretstore-path=[PATH]",
 			"Specify directory where Secret Store should save its data..",
@@ -591,6 +661,90 @@ usage! {
 			"--secretstore-secret=[SECRET]",
 			"Hex-encoded secret key of this node.",
 
+			ARG arg_secretstore_audit_log: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).audit_log.clone(),
+			"--secretstore-audit-log=[PATH]",
+			"Append records of all key generation, retrieval and signing requests, served by this node, to given file. Records are available through the SecretStore HTTP API.",
+
+			ARG arg_secretstore_requester_sessions_per_minute: (Option<usize>) = None, or |c: &Config| otry!(c.secretstore).requester_sessions_per_minute.clone(),
+			"--secretstore-requester-sessions-per-minute=[NUM]",
+			"Maximal number of decryption and signing sessions a single requester can start on this node within a minute.",
+
+			ARG arg_secretstore_requester_concurrent_sessions: (Option<usize>) = None, or |c: &Config| otry!(c.secretstore).requester_concurrent_sessions.clone(),
+			"--secretstore-requester-concurrent-sessions=[NUM]",
+			"Maximal number of concurrent decryption and signing sessions a single requester can start on this node.",
+
+			ARG arg_secretstore_sessions_per_minute: (Option<usize>) = None, or |c: &Config| otry!(c.secretstore).sessions_per_minute.clone(),
+			"--secretstore-sessions-per-minute=[NUM]",
+			"Maximal number of decryption and signing sessions all requesters can start on this node within a minute.",
+
+			ARG arg_secretstore_concurrent_sessions: (Option<usize>) = None, or |c: &Config| otry!(c.secretstore).concurrent_sessions.clone(),
+			"--secretstore-concurrent-sessions=[NUM]",
+			"Maximal number of concurrent decryption and signing sessions all requesters can start on this node.",
+
+			ARG arg_secretstore_rekeyings_per_minute: (Option<usize>) = None, or |c: &Config| otry!(c.secretstore).rekeyings_per_minute.clone(),
+			"--secretstore-rekeyings-per-minute=[NUM]",
+			"Re-generate document keys, access to which has been revoked in ACL, starting at most NUM re-keyings within a minute. Owners must re-encrypt documents with the new keys. Disabled by default.",
+
+			ARG arg_secretstore_admin_messages_share: (Option<usize>) = None, or |c: &Config| otry!(c.secretstore).admin_messages_share.clone(),
+			"--secretstore-admin-messages-share=[PERCENT]",
+			"Maximal share of share recovery, refresh and move messages in messages, processed by this node, while decryption and signing sessions are running. Admin messages above this share are delayed. Not limited by default.",
+
+			ARG arg_secretstore_removed_keys_retention: (Option<u64>) = None, or |c: &Config| otry!(c.secretstore).removed_keys_retention.clone(),
+			"--secretstore-removed-keys-retention=[SECONDS]",
+			"Keep key shares, removed from this node by share move and key deletion sessions, for given number of SECONDS, so that they could be restored. 7 days by default.",
+
+			ARG arg_secretstore_decryption_cache_ttl: (Option<u64>) = None, or |c: &Config| otry!(c.secretstore).decryption_cache_ttl.clone(),
+			"--secretstore-decryption-cache-ttl=[SECONDS]",
+			"Cache results of document key shadow decryption for given number of SECONDS. Cached result is only returned to the same requester, while the requester has access to the key. Disabled by default.",
+
+			ARG arg_secretstore_max_session_traffic: (Option<u64>) = None, or |c: &Config| otry!(c.secretstore).max_session_traffic.clone(),
+			"--secretstore-max-session-traffic=[BYTES]",
+			"Abort session, which has sent and received more than BYTES of messages on this node. Not limited by default.",
+
+			ARG arg_secretstore_session_journal: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).session_journal.clone(),
+			"--secretstore-session-journal=[PATH]",
+			"Record every message, sent and processed by sessions on this node, together with sessions start and removal, to files in given directory. Every session journal is stored in a separate file, named by the session id. Disabled by default.",
+
+			ARG arg_secretstore_backup_path: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).backup_path.clone(),
+			"--secretstore-backup-path=[PATH]",
+			"Directory, where key storage backups are written to and restored from. Backup files are referred by their name within this directory. Backups are disabled by default.",
+
+			ARG arg_secretstore_session_timeouts: (String) = "", or |c: &Config| otry!(c.secretstore).session_timeouts.as_ref().map(|vec| vec.join(",")),
+			"--secretstore-session-timeouts=[TIMEOUTS]",
+			"Comma-separated list of session idle timeouts in form SESSION_KIND:SECONDS. SESSION_KIND is one of: generation, encryption, decryption, signing, share_recovery, share_refresh. Session is stopped when no messages have been received within its idle timeout (60 seconds by default).",
+
+			ARG arg_secretstore_session_total_timeout: (Option<u64>) = None, or |c: &Config| otry!(c.secretstore).session_total_timeout.clone(),
+			"--secretstore-session-total-timeout=[SECS]",
+			"Maximal duration of secret store session of any kind (600 seconds by default).",
+
+			ARG arg_secretstore_peer_rate_limits: (String) = "", or |c: &Config| otry!(c.secretstore).peer_rate_limits.as_ref().map(|vec| vec.join(",")),
+			"--secretstore-peer-rate-limits=[LIMITS]",
+			"Comma-separated list of limits of messages, received from every other key server, in form MESSAGE_CLASS:MESSAGES_PER_SECOND. MESSAGE_CLASS is one of: session_initialization, session_error, session, cluster. Key server, which exceeds any limit, is disconnected for --secretstore-peer-throttle-time seconds. Defaults are session_initialization:50,session_error:20,session:2000,cluster:50.",
+
+			ARG arg_secretstore_peer_throttle_time: (Option<u64>) = None, or |c: &Config| otry!(c.secretstore).peer_throttle_time.clone(),
+			"--secretstore-peer-throttle-time=[SECS]",
+			"Time, during which key server, which has exceeded messages rate limit, is disconnected (60 seconds by default).",
+
+			FLAG flag_secretstore_deterministic_node_selection: (bool) = false, or |c: &Config| otry!(c.secretstore).deterministic_node_selection.clone(),
+			"--secretstore-deterministic-node-selection",
+			"Select key servers for decryption sessions in fixed order, instead of preferring key servers with lowest round-trip time and load. Useful for testing.",
+
+			ARG arg_secretstore_message_retries: (Option<usize>) = None, or |c: &Config| otry!(c.secretstore).message_retries.clone(),
+			"--secretstore-message-retries=[NUM]",
+			"Maximal number of times a session message, which can not be processed yet (i.e. while key storage is being backed up), is retried before it is dropped. Messages are retried every 100 milliseconds until processed by default.",
+
+			ARG arg_secretstore_acl_file: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).acl_file.clone(),
+			"--secretstore-acl-file=[PATH]",
+			"Read ACL from given JSON file instead of the on-chain ACL contract. The file maps requester addresses to lists of accessible key ids (\"*\" - all keys), optionally with expiry timestamps, and is reloaded when changed.",
+
+			ARG arg_secretstore_acl_service: (Option<String>) = None, or |c: &Config| otry!(c.secretstore).acl_service.clone(),
+			"--secretstore-acl-service=[URL]",
+			"Ask HTTP policy service at given URL whether the requester can access the key, instead of reading permissions from the on-chain ACL contract. Ignored when --secretstore-acl-file is specified.",
+
+			ARG arg_secretstore_acl_cache_ttl: (Option<u64>) = None, or |c: &Config| otry!(c.secretstore).acl_cache_ttl.clone(),
+			"--secretstore-acl-cache-ttl=[SECONDS]",
+			"Cache decisions of ACL policy service for given number of SECONDS. 60 seconds by default.",
+
 		["Sealing/Mining options"]
 			FLAG flag_force_sealing: (bool) = false, or |c: &Config| otry!(c.mining).force_sealing.clone(),
 			"--force-sealing",
@@ -1088,13 +1242,46 @@ struct SecretStore {
 	disable: Option<bool>,
 	disable_http: Option<bool>,
 	disable_acl_check: Option<bool>,
+	health: Option<bool>,
+	service_contract: Option<String>,
 	self_secret: Option<String>,
 	nodes: Option<Vec<String>>,
+	read_only_nodes: Option<Vec<String>>,
 	interface: Option<String>,
 	port: Option<u16>,
+	external_address: Option<String>,
+	upnp: Option<bool>,
 	http_interface: Option<String>,
 	http_port: Option<u16>,
+	http_api_keys: Option<String>,
+	http_cors: Option<String>,
+	http_hosts: Option<Vec<String>>,
+	http_tls_cert: Option<String>,
+	http_tls_key: Option<String>,
+	http_tls_client_ca: Option<String>,
 	path: Option<String>,
+	storage: Option<String>,
+	acl_file: Option<String>,
+	acl_service: Option<String>,
+	acl_cache_ttl: Option<u64>,
+	audit_log: Option<String>,
+	requester_sessions_per_minute: Option<usize>,
+	requester_concurrent_sessions: Option<usize>,
+	sessions_per_minute: Option<usize>,
+	concurrent_sessions: Option<usize>,
+	rekeyings_per_minute: Option<usize>,
+	admin_messages_share: Option<usize>,
+	removed_keys_retention: Option<u64>,
+	decryption_cache_ttl: Option<u64>,
+	max_session_traffic: Option<u64>,
+	session_journal: Option<String>,
+	backup_path: Option<String>,
+	session_timeouts: Option<Vec<String>>,
+	session_total_timeout: Option<u64>,
+	peer_rate_limits: Option<Vec<String>>,
+	peer_throttle_time: Option<u64>,
+	deterministic_node_selection: Option<bool>,
+	message_retries: Option<usize>,
 }
 
 #[derive(Default, Debug, PartialEq, Deserialize)]
@@ -1335,6 +1522,10 @@ mod tests {
 			cmd_tools_hash: false,
 			cmd_db: false,
 			cmd_db_kill: false,
+			cmd_secretstore: false,
+			cmd_secretstore_list: false,
+			cmd_secretstore_show: false,
+			cmd_secretstore_verify: false,
 
 			// Arguments
 			arg_daemon_pid_file: None,
@@ -1347,6 +1538,7 @@ mod tests {
 			arg_snapshot_file: None,
 			arg_restore_file: None,
 			arg_tools_hash_file: None,
+			arg_secretstore_show_key: None,
 
 			arg_account_new_password: None,
 			arg_signer_sign_password: None,
@@ -1444,13 +1636,46 @@ mod tests {
 			flag_no_secretstore: false,
 			flag_no_secretstore_http: false,
 			flag_no_secretstore_acl_check: false,
+			flag_secretstore_health: false,
+			arg_secretstore_contract: "none".into(),
 			arg_secretstore_secret: None,
+			arg_secretstore_acl_file: None,
+			arg_secretstore_acl_service: None,
+			arg_secretstore_acl_cache_ttl: None,
+			arg_secretstore_audit_log: None,
+			arg_secretstore_requester_sessions_per_minute: None,
+			arg_secretstore_requester_concurrent_sessions: None,
+			arg_secretstore_sessions_per_minute: None,
+			arg_secretstore_concurrent_sessions: None,
+			arg_secretstore_rekeyings_per_minute: None,
+			arg_secretstore_admin_messages_share: None,
+			arg_secretstore_removed_keys_retention: None,
+			arg_secretstore_decryption_cache_ttl: None,
+			arg_secretstore_max_session_traffic: None,
+			arg_secretstore_session_journal: None,
+			arg_secretstore_backup_path: None,
+			arg_secretstore_session_timeouts: "".into(),
+			arg_secretstore_session_total_timeout: None,
+			arg_secretstore_peer_rate_limits: "".into(),
+			arg_secretstore_peer_throttle_time: None,
+			flag_secretstore_deterministic_node_selection: false,
+			arg_secretstore_message_retries: None,
 			arg_secretstore_nodes: "".into(),
+			arg_secretstore_read_only_nodes: "".into(),
 			arg_secretstore_interface: "local".into(),
 			arg_secretstore_port: 8083u16,
+			arg_secretstore_external_address: None,
+			flag_secretstore_upnp: false,
 			arg_secretstore_http_interface: "local".into(),
 			arg_secretstore_http_port: 8082u16,
+			arg_secretstore_http_api_keys: None,
+			arg_secretstore_http_cors: None,
+			arg_secretstore_http_hosts: "all".into(),
+			arg_secretstore_http_tls_cert: None,
+			arg_secretstore_http_tls_key: None,
+			arg_secretstore_http_tls_client_ca: None,
 			arg_secretstore_path: "$HOME/.parity/secretstore".into(),
+			arg_secretstore_storage: "db".into(),
 
 			// IPFS
 			flag_ipfs_api: false,
@@ -1683,13 +1908,46 @@ mod tests {
 				disable: None,
 				disable_http: None,
 				disable_acl_check: None,
+				health: None,
+				service_contract: None,
 				self_secret: None,
 				nodes: None,
+				read_only_nodes: None,
 				interface: None,
 				port: Some(8083),
+				external_address: None,
+				upnp: None,
 				http_interface: None,
 				http_port: Some(8082),
+				http_api_keys: None,
+				http_cors: None,
+				http_hosts: None,
+				http_tls_cert: None,
+				http_tls_key: None,
+				http_tls_client_ca: None,
 				path: None,
+				storage: None,
+				acl_file: None,
+				acl_service: None,
+				acl_cache_ttl: None,
+				audit_log: None,
+				requester_sessions_per_minute: None,
+				requester_concurrent_sessions: None,
+				sessions_per_minute: None,
+				concurrent_sessions: None,
+				rekeyings_per_minute: None,
+				admin_messages_share: None,
+				removed_keys_retention: None,
+				decryption_cache_ttl: None,
+				max_session_traffic: None,
+				session_journal: None,
+				backup_path: None,
+				session_timeouts: None,
+				session_total_timeout: None,
+				peer_rate_limits: None,
+				peer_throttle_time: None,
+				deterministic_node_selection: None,
+				message_retries: None,
 			}),
 			ipfs: Some(Ipfs {
 				enable: Some(false),
@@ -24,7 +24,7 @@ use std::str::FromStr;
 use cli::{Args, ArgsError};
 use hash::keccak;
 use bigint::prelude::U256;
-use bigint::hash::H256;
+use bigint::hash::{H256, clean_0x};
 use util::{version_data, Address};
 use bytes::Bytes;
 use util::journaldb::Algorithm;
@@ -46,7 +46,8 @@ use ethcore_logger::Config as LogConfig;
 use dir::{self, Directories, default_hypervisor_path, default_local_path, default_data_path};
 use dapps::Configuration as DappsConfiguration;
 use ipfs::Configuration as IpfsConfiguration;
-use secretstore::{Configuration as SecretStoreConfiguration, NodeSecretKey};
+use secretstore::{Configuration as SecretStoreConfiguration, NodeSecretKey, KeyStorageBackend, ContractAddress as SecretStoreContractAddress,
+	SecretStoreCmd, SecretStoreAction, HttpTlsConfiguration as SecretStoreHttpTlsConfiguration};
 use updater::{UpdatePolicy, UpdateFilter, ReleaseTrack};
 use run::RunCmd;
 use blockchain::{BlockchainCmd, ImportBlockchain, ExportBlockchain, KillBlockchain, ExportState, DataFormat};
@@ -80,6 +81,7 @@ pub enum Cmd {
 	},
 	Snapshot(SnapshotCommand),
 	Hash(Option<String>),
+	SecretStore(SecretStoreCmd),
 }
 
 pub struct Execute {
@@ -190,6 +192,26 @@ impl Configuration {
 				dirs: dirs,
 				pruning: pruning,
 			}))
+		} else if self.args.cmd_secretstore {
+			let action = if self.args.cmd_secretstore_list {
+				SecretStoreAction::List
+			} else if self.args.cmd_secretstore_show {
+				let key_id = self.args.arg_secretstore_show_key.clone().expect("CLI argument is required; qed");
+				SecretStoreAction::Show(clean_0x(&key_id).parse().map_err(|_| format!("Invalid key id: {:?}", key_id))?)
+			} else if self.args.cmd_secretstore_verify {
+				SecretStoreAction::Verify
+			} else {
+				unreachable!();
+			};
+			Cmd::SecretStore(SecretStoreCmd {
+				key_storage: self.secretstore_storage()?,
+				path: dirs.secretstore,
+				self_secret: self.secretstore_self_secret()?,
+				keys_path: dirs.keys,
+				spec: spec,
+				password_files: self.args.arg_password.clone(),
+				action: action,
+			})
 		} else if self.args.cmd_account {
 			let account_cmd = if self.args.cmd_account_new {
 				let new_acc = NewAccount {
@@ -619,13 +641,44 @@ impl Configuration {
 			enabled: self.secretstore_enabled(),
 			http_enabled: self.secretstore_http_enabled(),
 			acl_check_enabled: self.secretstore_acl_check_enabled(),
+			acl_file: self.args.arg_secretstore_acl_file.clone(),
+			acl_service: self.args.arg_secretstore_acl_service.clone(),
+			acl_cache_ttl: self.args.arg_secretstore_acl_cache_ttl,
+			audit_log: self.args.arg_secretstore_audit_log.clone(),
+			health_check_enabled: self.args.flag_secretstore_health,
+			service_contract_address: self.secretstore_service_contract_address()?,
+			requester_sessions_per_minute: self.args.arg_secretstore_requester_sessions_per_minute,
+			requester_concurrent_sessions: self.args.arg_secretstore_requester_concurrent_sessions,
+			sessions_per_minute: self.args.arg_secretstore_sessions_per_minute,
+			concurrent_sessions: self.args.arg_secretstore_concurrent_sessions,
+			rekeyings_per_minute: self.args.arg_secretstore_rekeyings_per_minute,
+			admin_messages_share: self.secretstore_admin_messages_share()?,
+			removed_keys_retention: self.args.arg_secretstore_removed_keys_retention,
+			decryption_cache_ttl: self.args.arg_secretstore_decryption_cache_ttl,
+			max_session_traffic: self.args.arg_secretstore_max_session_traffic,
+			session_journal: self.args.arg_secretstore_session_journal.clone(),
+			backup_path: self.args.arg_secretstore_backup_path.clone(),
+			session_timeouts: self.secretstore_session_timeouts()?,
+			session_total_timeout: self.args.arg_secretstore_session_total_timeout,
+			peer_rate_limits: self.secretstore_peer_rate_limits()?,
+			peer_throttle_time: self.args.arg_secretstore_peer_throttle_time,
+			deterministic_node_selection: self.args.flag_secretstore_deterministic_node_selection,
+			message_retries: self.args.arg_secretstore_message_retries,
 			self_secret: self.secretstore_self_secret()?,
 			nodes: self.secretstore_nodes()?,
+			read_only_nodes: self.secretstore_read_only_nodes()?,
 			interface: self.secretstore_interface(),
 			port: self.args.arg_ports_shift + self.args.arg_secretstore_port,
+			external_address: self.secretstore_external_address()?,
+			upnp_enabled: self.args.flag_secretstore_upnp,
 			http_interface: self.secretstore_http_interface(),
 			http_port: self.args.arg_ports_shift + self.args.arg_secretstore_http_port,
+			http_api_keys: self.args.arg_secretstore_http_api_keys.clone(),
+			http_cors: Self::cors(self.args.arg_secretstore_http_cors.as_ref()),
+			http_hosts: Self::parse_hosts(&self.args.arg_secretstore_http_hosts),
+			http_tls: self.secretstore_http_tls()?,
 			data_path: self.directories().secretstore,
+			key_storage: self.secretstore_storage()?,
 		})
 	}
 
@@ -1037,6 +1090,23 @@ impl Configuration {
 		}
 	}
 
+	fn secretstore_service_contract_address(&self) -> Result<Option<SecretStoreContractAddress>, String> {
+		Ok(match self.args.arg_secretstore_contract.as_ref() {
+			"none" => None,
+			"registry" => Some(SecretStoreContractAddress::Registry),
+			a => Some(SecretStoreContractAddress::Address(to_address(Some(a.into()))?)),
+		})
+	}
+
+	fn secretstore_storage(&self) -> Result<KeyStorageBackend, String> {
+		match self.args.arg_secretstore_storage.as_str() {
+			"db" => Ok(KeyStorageBackend::Database),
+			"file" => Ok(KeyStorageBackend::File),
+			"memory" => Ok(KeyStorageBackend::Memory),
+			s => Err(format!("Invalid secret store storage: {}. Must be one of: db, file, memory", s)),
+		}
+	}
+
 	fn secretstore_nodes(&self) -> Result<BTreeMap<Public, (String, u16)>, String> {
 		let mut nodes = BTreeMap::new();
 		for node in self.args.arg_secretstore_nodes.split(',').filter(|n| n != &"") {
@@ -1045,22 +1115,103 @@ impl Configuration {
 				return Err(format!("Invalid secret store node: {}", node));
 			}
 
-			let ip_and_port: Vec<_> = public_and_addr[1].split(':').collect();
-			if ip_and_port.len() != 2 {
-				return Err(format!("Invalid secret store node: {}", node));
-			}
-
+			let (ip, port) = split_secretstore_address(public_and_addr[1])
+				.ok_or_else(|| format!("Invalid secret store node: {}", node))?;
 			let public = public_and_addr[0].parse()
 				.map_err(|e| format!("Invalid public key in secret store node: {}. Error: {:?}", public_and_addr[0], e))?;
-			let port = ip_and_port[1].parse()
-				.map_err(|e| format!("Invalid port in secret store node: {}. Error: {:?}", ip_and_port[1], e))?;
+			let port = port.parse()
+				.map_err(|e| format!("Invalid port in secret store node: {}. Error: {:?}", port, e))?;
 
-			nodes.insert(public, (ip_and_port[0].into(), port));
+			nodes.insert(public, (ip.into(), port));
 		}
 
 		Ok(nodes)
 	}
 
+	fn secretstore_admin_messages_share(&self) -> Result<Option<usize>, String> {
+		match self.args.arg_secretstore_admin_messages_share {
+			Some(share) if share > 100 => Err(format!("Invalid secret store admin messages share: {}. Must be in range 0..100", share)),
+			share => Ok(share),
+		}
+	}
+
+	fn secretstore_external_address(&self) -> Result<Option<(String, u16)>, String> {
+		let address = match self.args.arg_secretstore_external_address {
+			Some(ref address) => address,
+			None => return Ok(None),
+		};
+
+		let (ip, port) = split_secretstore_address(address)
+			.ok_or_else(|| format!("Invalid secret store external address: {}", address))?;
+		let port = port.parse()
+			.map_err(|e| format!("Invalid port in secret store external address: {}. Error: {:?}", port, e))?;
+
+		Ok(Some((ip.into(), port)))
+	}
+
+	fn secretstore_http_tls(&self) -> Result<Option<SecretStoreHttpTlsConfiguration>, String> {
+		match (self.args.arg_secretstore_http_tls_cert.as_ref(), self.args.arg_secretstore_http_tls_key.as_ref()) {
+			(Some(certificate), Some(private_key)) => Ok(Some(SecretStoreHttpTlsConfiguration {
+				certificate: certificate.clone(),
+				private_key: private_key.clone(),
+				client_ca: self.args.arg_secretstore_http_tls_client_ca.clone(),
+			})),
+			(None, None) if self.args.arg_secretstore_http_tls_client_ca.is_some() =>
+				Err("--secretstore-http-tls-client-ca requires --secretstore-http-tls-cert and --secretstore-http-tls-key".into()),
+			(None, None) => Ok(None),
+			_ => Err("Both --secretstore-http-tls-cert and --secretstore-http-tls-key are required to enable secret store HTTP API TLS".into()),
+		}
+	}
+
+	fn secretstore_session_timeouts(&self) -> Result<BTreeMap<String, u64>, String> {
+		let mut timeouts = BTreeMap::new();
+		for timeout in self.args.arg_secretstore_session_timeouts.split(',').filter(|t| t != &"") {
+			let kind_and_timeout: Vec<_> = timeout.split(':').collect();
+			if kind_and_timeout.len() != 2 {
+				return Err(format!("Invalid secret store session timeout: {}", timeout));
+			}
+
+			match kind_and_timeout[0] {
+				"generation" | "encryption" | "decryption" | "signing" | "share_recovery" | "share_refresh" => (),
+				kind => return Err(format!("Invalid secret store session kind: {}. Must be one of: generation, encryption, decryption, signing, share_recovery, share_refresh", kind)),
+			}
+			let seconds = kind_and_timeout[1].parse()
+				.map_err(|e| format!("Invalid secret store session timeout: {}. Error: {:?}", kind_and_timeout[1], e))?;
+
+			timeouts.insert(kind_and_timeout[0].into(), seconds);
+		}
+
+		Ok(timeouts)
+	}
+
+	fn secretstore_peer_rate_limits(&self) -> Result<BTreeMap<String, u64>, String> {
+		let mut limits = BTreeMap::new();
+		for limit in self.args.arg_secretstore_peer_rate_limits.split(',').filter(|l| l != &"") {
+			let class_and_limit: Vec<_> = limit.split(':').collect();
+			if class_and_limit.len() != 2 {
+				return Err(format!("Invalid secret store peer rate limit: {}", limit));
+			}
+
+			match class_and_limit[0] {
+				"session_initialization" | "session_error" | "session" | "cluster" => (),
+				class => return Err(format!("Invalid secret store message class: {}. Must be one of: session_initialization, session_error, session, cluster", class)),
+			}
+			let messages_per_second = class_and_limit[1].parse()
+				.map_err(|e| format!("Invalid secret store peer rate limit: {}. Error: {:?}", class_and_limit[1], e))?;
+
+			limits.insert(class_and_limit[0].into(), messages_per_second);
+		}
+
+		Ok(limits)
+	}
+
+	fn secretstore_read_only_nodes(&self) -> Result<Vec<Public>, String> {
+		self.args.arg_secretstore_read_only_nodes.split(',').filter(|n| n != &"")
+			.map(|node| node.parse()
+				.map_err(|e| format!("Invalid public key of secret store read-only node: {}. Error: {:?}", node, e)))
+			.collect()
+	}
+
 	fn stratum_interface(&self) -> String {
 		self.interface(&self.args.arg_stratum_interface)
 	}
@@ -1119,6 +1270,22 @@ impl Configuration {
 	}
 }
 
+/// Split secret store node address into host and port parts.
+/// IPv6 addresses are enclosed in square brackets: [::1]:8083
+fn split_secretstore_address(address: &str) -> Option<(&str, &str)> {
+	let ip_and_port: Vec<_> = address.rsplitn(2, ':').collect();
+	if ip_and_port.len() != 2 {
+		return None;
+	}
+	let (port, ip) = (ip_and_port[0], ip_and_port[1]);
+	let ip = if ip.starts_with('[') && ip.ends_with(']') { &ip[1..ip.len() - 1] } else { ip };
+	if ip.is_empty() || (ip.contains(':') && !ip_and_port[1].starts_with('[')) {
+		return None;
+	}
+
+	Some((ip, port))
+}
+
 #[cfg(test)]
 mod tests {
 	use std::io::Write;
@@ -1189,6 +1356,79 @@ mod tests {
 		));
 	}
 
+	#[test]
+	fn test_command_secretstore_show() {
+		let args = vec!["parity", "secretstore", "show", "0x0000000000000000000000000000000000000000000000000000000000000001"];
+		let conf = parse(&args);
+		assert_eq!(conf.into_command().unwrap().cmd, Cmd::SecretStore(SecretStoreCmd {
+			key_storage: KeyStorageBackend::Database,
+			path: Directories::default().secretstore,
+			self_secret: None,
+			keys_path: Directories::default().keys,
+			spec: SpecType::default(),
+			password_files: vec![],
+			action: SecretStoreAction::Show(H256::from(1)),
+		}));
+	}
+
+	#[test]
+	fn test_secretstore_storage() {
+		let conf = parse(&["parity", "--secretstore-storage", "file"]);
+		assert_eq!(conf.secretstore_storage(), Ok(KeyStorageBackend::File));
+		let conf = parse(&["parity", "--secretstore-storage", "sql"]);
+		assert!(conf.secretstore_storage().is_err());
+	}
+
+	#[test]
+	fn test_secretstore_nodes() {
+		let public = "a6ea1a0e7f734f5ec1e0aaaf660fefb6e5a337ce529f6d5e6fa41a0ad9c6179dd1bcd5fd1121ec1fd54ad563a744b0cbc16706d1e4fc9bee80ea8738fe426320";
+		let conf = parse(&["parity", "--secretstore-nodes", &format!("{}@127.0.0.1:3333,{}@[::1]:4444,{}@ss-0.secretstore:5555", public, public, public)]);
+		let nodes = conf.secretstore_nodes().unwrap();
+		assert_eq!(nodes.values().next(), Some(&("ss-0.secretstore".to_owned(), 5555)));
+		let conf = parse(&["parity", "--secretstore-nodes", &format!("{}@[::1]:4444", public)]);
+		assert_eq!(conf.secretstore_nodes().unwrap().values().next(), Some(&("::1".to_owned(), 4444)));
+		let conf = parse(&["parity", "--secretstore-nodes", &format!("{}@::1:4444", public)]);
+		assert!(conf.secretstore_nodes().is_err());
+	}
+
+	#[test]
+	fn test_secretstore_external_address() {
+		let conf = parse(&["parity"]);
+		assert_eq!(conf.secretstore_external_address(), Ok(None));
+		let conf = parse(&["parity", "--secretstore-external-address", "[2001:db8::1]:8083"]);
+		assert_eq!(conf.secretstore_external_address(), Ok(Some(("2001:db8::1".to_owned(), 8083))));
+		let conf = parse(&["parity", "--secretstore-external-address", "203.0.113.1"]);
+		assert!(conf.secretstore_external_address().is_err());
+	}
+
+	#[test]
+	fn test_secretstore_http_tls() {
+		let conf = parse(&["parity"]);
+		assert_eq!(conf.secretstore_http_tls(), Ok(None));
+		let conf = parse(&["parity", "--secretstore-http-tls-cert", "cert.pem", "--secretstore-http-tls-key", "key.pem"]);
+		assert_eq!(conf.secretstore_http_tls(), Ok(Some(SecretStoreHttpTlsConfiguration {
+			certificate: "cert.pem".into(),
+			private_key: "key.pem".into(),
+			client_ca: None,
+		})));
+		let conf = parse(&["parity", "--secretstore-http-tls-cert", "cert.pem"]);
+		assert!(conf.secretstore_http_tls().is_err());
+		let conf = parse(&["parity", "--secretstore-http-tls-client-ca", "ca.pem"]);
+		assert!(conf.secretstore_http_tls().is_err());
+	}
+
+	#[test]
+	fn test_secretstore_peer_rate_limits() {
+		let conf = parse(&["parity"]);
+		assert_eq!(conf.secretstore_peer_rate_limits(), Ok(BTreeMap::new()));
+		let conf = parse(&["parity", "--secretstore-peer-rate-limits", "session_initialization:10,cluster:5"]);
+		assert_eq!(conf.secretstore_peer_rate_limits(), Ok(vec![("cluster".to_owned(), 5), ("session_initialization".to_owned(), 10)].into_iter().collect()));
+		let conf = parse(&["parity", "--secretstore-peer-rate-limits", "generation:10"]);
+		assert!(conf.secretstore_peer_rate_limits().is_err());
+		let conf = parse(&["parity", "--secretstore-peer-rate-limits", "session:many"]);
+		assert!(conf.secretstore_peer_rate_limits().is_err());
+	}
+
 	#[test]
 	fn test_command_account_import() {
 		let args = vec!["parity", "account", "import", "my_dir", "another_dir"];
@@ -16,11 +16,13 @@
 
 use std::collections::BTreeMap;
 use std::sync::Arc;
+use bigint::hash::H256;
 use dir::default_data_path;
 use ethcore::account_provider::AccountProvider;
 use ethcore::client::Client;
 use ethkey::{Secret, Public};
 use helpers::replace_home;
+use params::SpecType;
 use util::Address;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -41,20 +43,143 @@ pub struct Configuration {
 	pub http_enabled: bool,
 	/// Is ACL check enabled.
 	pub acl_check_enabled: bool,
+	/// Path to the ACL file. If None, on-chain ACL contract is used.
+	pub acl_file: Option<String>,
+	/// URL of the ACL policy service. If None, on-chain ACL contract is used.
+	pub acl_service: Option<String>,
+	/// Time (seconds) ACL policy service decisions are cached. If None, default value is used.
+	pub acl_cache_ttl: Option<u64>,
+	/// Path to the audit log file. If None, audit log is disabled.
+	pub audit_log: Option<String>,
+	/// Is unauthenticated health-check endpoint enabled.
+	pub health_check_enabled: bool,
+	/// Service contract address. If None, service contract is not watched.
+	pub service_contract_address: Option<ContractAddress>,
+	/// Max sessions single requester can start within a minute.
+	pub requester_sessions_per_minute: Option<usize>,
+	/// Max concurrent sessions of single requester.
+	pub requester_concurrent_sessions: Option<usize>,
+	/// Max sessions all requesters can start within a minute.
+	pub sessions_per_minute: Option<usize>,
+	/// Max concurrent sessions of all requesters.
+	pub concurrent_sessions: Option<usize>,
+	/// Max document re-keyings after ACL revocation within a minute. If None, documents are not re-keyed.
+	pub rekeyings_per_minute: Option<usize>,
+	/// Max share (percents) of admin sessions messages, while user sessions are running. If None, not limited.
+	pub admin_messages_share: Option<usize>,
+	/// Time (seconds) removed key shares are kept before purged. If None, default value is used.
+	pub removed_keys_retention: Option<u64>,
+	/// Time (seconds) shadow decryption results are cached. If None, results are not cached.
+	pub decryption_cache_ttl: Option<u64>,
+	/// Max bytes sent && received by single session. If None, not limited.
+	pub max_session_traffic: Option<u64>,
+	/// Path to the session journal directory. If None, session journal is disabled.
+	pub session_journal: Option<String>,
+	/// Path to the key storage backups directory. If None, backups are disabled.
+	pub backup_path: Option<String>,
+	/// Idle timeouts (seconds) of sessions by session kind.
+	pub session_timeouts: BTreeMap<String, u64>,
+	/// Max duration (seconds) of any session.
+	pub session_total_timeout: Option<u64>,
+	/// Limits (messages per second) of messages, received from other nodes, by message class.
+	pub peer_rate_limits: BTreeMap<String, u64>,
+	/// Time (seconds), during which node, which has exceeded messages rate limit, is disconnected.
+	pub peer_throttle_time: Option<u64>,
+	/// Select nodes for decryption sessions in their natural order.
+	pub deterministic_node_selection: bool,
+	/// Max number of retries of session message, which can not be processed yet.
+	pub message_retries: Option<usize>,
 	/// This node secret.
 	pub self_secret: Option<NodeSecretKey>,
 	/// Other nodes IDs + addresses.
 	pub nodes: BTreeMap<Public, (String, u16)>,
+	/// IDs of nodes, which are not allowed to start share administration sessions.
+	pub read_only_nodes: Vec<Public>,
 	/// Interface to listen to
 	pub interface: String,
 	/// Port to listen to
 	pub port: u16,
+	/// Address of this node, which is reachable by other nodes.
+	pub external_address: Option<(String, u16)>,
+	/// Is UPnP mapping of the listener port enabled.
+	pub upnp_enabled: bool,
 	/// Interface to listen to
 	pub http_interface: String,
 	/// Port to listen to
 	pub http_port: u16,
+	/// Path to the HTTP API keys file. If None, HTTP requests are not authenticated.
+	pub http_api_keys: Option<String>,
+	/// Origins allowed to make cross-origin HTTP requests. If None, cross-origin requests are refused.
+	pub http_cors: Option<Vec<String>>,
+	/// Allowed Host header values of HTTP requests. If None, any Host is accepted.
+	pub http_hosts: Option<Vec<String>>,
+	/// HTTP API TLS configuration. If None, TLS is disabled.
+	pub http_tls: Option<HttpTlsConfiguration>,
 	/// Data directory path for secret store
 	pub data_path: String,
+	/// Key shares storage backend.
+	pub key_storage: KeyStorageBackend,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+/// Secret store contract address.
+pub enum ContractAddress {
+	/// Contract address is read from registry.
+	Registry,
+	/// Contract address is specified.
+	Address(Address),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+/// Secret store HTTP API TLS configuration.
+pub struct HttpTlsConfiguration {
+	/// Path to the certificate (chain) PEM file.
+	pub certificate: String,
+	/// Path to the private key PEM file.
+	pub private_key: String,
+	/// Path to the PEM file with CA certificates of clients. If None, client certificates are not required.
+	pub client_ca: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+/// Key shares storage backend.
+pub enum KeyStorageBackend {
+	/// Stored in the database in secret store data directory.
+	Database,
+	/// Stored in the single encrypted file in secret store data directory.
+	File,
+	/// Kept in memory only.
+	Memory,
+}
+
+/// Secret store key shares storage command
+#[derive(Debug, PartialEq)]
+pub struct SecretStoreCmd {
+	/// Key shares storage backend.
+	pub key_storage: KeyStorageBackend,
+	/// Data directory path for secret store.
+	pub path: String,
+	/// This node secret.
+	pub self_secret: Option<NodeSecretKey>,
+	/// Keys directory path (when this node secret is stored as account in key store).
+	pub keys_path: String,
+	/// Spec, which keys directory is used for.
+	pub spec: SpecType,
+	/// Password files to unlock this node account in key store.
+	pub password_files: Vec<String>,
+	/// Action to perform.
+	pub action: SecretStoreAction,
+}
+
+/// Secret store key shares storage action
+#[derive(Debug, PartialEq)]
+pub enum SecretStoreAction {
+	/// List ids of all stored keys.
+	List,
+	/// Show metadata of stored key share.
+	Show(H256),
+	/// Check that all stored key shares are readable && consistent.
+	Verify,
 }
 
 /// Secret store dependencies
@@ -69,7 +194,7 @@ pub struct Dependencies<'a> {
 
 #[cfg(not(feature = "secretstore"))]
 mod server {
-	use super::{Configuration, Dependencies};
+	use super::{Configuration, Dependencies, SecretStoreCmd};
 
 	/// Noop key server implementation
 	pub struct KeyServer;
@@ -80,15 +205,28 @@ mod server {
 			Ok(KeyServer)
 		}
 	}
+
+	/// Execute key shares storage command
+	pub fn execute(_cmd: SecretStoreCmd) -> Result<String, String> {
+		Err("SecretStore is not supported by this build".into())
+	}
 }
 
 #[cfg(feature="secretstore")]
 mod server {
 	use std::sync::Arc;
+	use std::path::PathBuf;
 	use ethcore_secretstore;
+	use ethcore::account_provider::{AccountProvider, AccountProviderSettings};
+	use ethcore::ethstore::EthStore;
+	use ethcore::ethstore::dir::RootDiskDirectory;
 	use ethkey::KeyPair;
+	use helpers::passwords_from_files;
+	use params::SpecType;
+	use util::Address;
 	use ansi_term::Colour::Red;
-	use super::{Configuration, Dependencies, NodeSecretKey};
+	use bigint::hash::H256;
+	use super::{Configuration, Dependencies, NodeSecretKey, KeyStorageBackend, ContractAddress, SecretStoreCmd, SecretStoreAction};
 
 	/// Key server
 	pub struct KeyServer {
@@ -105,24 +243,8 @@ mod server {
 			let self_secret: Arc<ethcore_secretstore::NodeKeyPair> = match conf.self_secret.take() {
 				Some(NodeSecretKey::Plain(secret)) => Arc::new(ethcore_secretstore::PlainNodeKeyPair::new(
 					KeyPair::from_secret(secret).map_err(|e| format!("invalid secret: {}", e))?)),
-				Some(NodeSecretKey::KeyStore(account)) => {
-					// Check if account exists
-					if !deps.account_provider.has_account(account.clone()).unwrap_or(false) {
-						return Err(format!("Account {} passed as secret store node key is not found", account));
-					}
-
-					// Check if any passwords have been read from the password file(s)
-					if deps.accounts_passwords.is_empty() {
-						return Err(format!("No password found for the secret store node account {}", account));
-					}
-
-					// Attempt to sign in the engine signer.
-					let password = deps.accounts_passwords.iter()
-						.find(|p| deps.account_provider.sign(account.clone(), Some((*p).clone()), Default::default()).is_ok())
-						.ok_or(format!("No valid password for the secret store node account {}", account))?;
-					Arc::new(ethcore_secretstore::KeyStoreNodeKeyPair::new(deps.account_provider, account, password.clone())
-						.map_err(|e| format!("{}", e))?)
-				},
+				Some(NodeSecretKey::KeyStore(account)) =>
+					Arc::new(keystore_node_key_pair(deps.account_provider, account, deps.accounts_passwords)?),
 				None => return Err("self secret is required when using secretstore".into()),
 			};
 
@@ -133,18 +255,71 @@ mod server {
 					port: conf.http_port,
 				}) } else { None },
 				data_path: conf.data_path.clone(),
+				key_storage: key_storage_backend(&conf.key_storage),
 				acl_check_enabled: conf.acl_check_enabled,
+				acl_file: conf.acl_file.clone(),
+				acl_service: conf.acl_service.clone(),
+				acl_cache_ttl: conf.acl_cache_ttl,
+				audit_log: conf.audit_log.clone(),
+				health_check_enabled: conf.health_check_enabled,
+				http_api_keys: conf.http_api_keys.clone(),
+				http_cors: conf.http_cors.clone(),
+				http_hosts: conf.http_hosts.clone(),
+				http_tls_certificate: conf.http_tls.as_ref().map(|tls| tls.certificate.clone()),
+				http_tls_private_key: conf.http_tls.as_ref().map(|tls| tls.private_key.clone()),
+				http_tls_client_ca: conf.http_tls.as_ref().and_then(|tls| tls.client_ca.clone()),
+				rekeyings_per_minute: conf.rekeyings_per_minute,
+				service_contract_address: conf.service_contract_address.clone().map(|address| match address {
+					ContractAddress::Registry => ethcore_secretstore::ContractAddress::Registry,
+					ContractAddress::Address(address) => ethcore_secretstore::ContractAddress::Address(address),
+				}),
 				cluster_config: ethcore_secretstore::ClusterConfiguration {
 					threads: 4,
 					listener_address: ethcore_secretstore::NodeAddress {
 						address: conf.interface.clone(),
 						port: conf.port,
 					},
+					external_address: conf.external_address.map(|(ip, port)| ethcore_secretstore::NodeAddress {
+						address: ip,
+						port: port,
+					}),
+					upnp_enabled: conf.upnp_enabled,
+					admin_messages_share: conf.admin_messages_share,
+					removed_keys_retention: conf.removed_keys_retention,
+					decryption_cache_ttl: conf.decryption_cache_ttl,
+					max_session_traffic: conf.max_session_traffic,
+					session_journal: conf.session_journal.clone(),
+					backup_path: conf.backup_path.clone(),
 					nodes: conf.nodes.into_iter().map(|(p, (ip, port))| (p, ethcore_secretstore::NodeAddress {
 						address: ip,
 						port: port,
 					})).collect(),
 					allow_connecting_to_higher_nodes: true,
+					rate_limits: ethcore_secretstore::SessionsRateLimits {
+						requester_sessions_per_minute: conf.requester_sessions_per_minute,
+						requester_concurrent_sessions: conf.requester_concurrent_sessions,
+						sessions_per_minute: conf.sessions_per_minute,
+						concurrent_sessions: conf.concurrent_sessions,
+					},
+					peer_rate_limits: ethcore_secretstore::PeerRateLimits {
+						session_initializations: conf.peer_rate_limits.get("session_initialization").cloned(),
+						session_errors: conf.peer_rate_limits.get("session_error").cloned(),
+						session_messages: conf.peer_rate_limits.get("session").cloned(),
+						cluster_messages: conf.peer_rate_limits.get("cluster").cloned(),
+						throttle_time: conf.peer_throttle_time,
+					},
+					deterministic_nodes_selection: conf.deterministic_node_selection,
+					read_only_nodes: conf.read_only_nodes,
+					timeouts: ethcore_secretstore::ClusterTimeouts {
+						generation_idle_timeout: conf.session_timeouts.get("generation").cloned(),
+						encryption_idle_timeout: conf.session_timeouts.get("encryption").cloned(),
+						decryption_idle_timeout: conf.session_timeouts.get("decryption").cloned(),
+						signing_idle_timeout: conf.session_timeouts.get("signing").cloned(),
+						share_recovery_idle_timeout: conf.session_timeouts.get("share_recovery").cloned(),
+						share_refresh_idle_timeout: conf.session_timeouts.get("share_refresh").cloned(),
+						total_timeout: conf.session_total_timeout,
+						message_retries: conf.message_retries,
+					},
 				},
 			};
 
@@ -158,9 +333,117 @@ mod server {
 			})
 		}
 	}
+
+	/// Execute key shares storage command
+	pub fn execute(cmd: SecretStoreCmd) -> Result<String, String> {
+		let key_storage = open_key_storage(&cmd)?;
+		match cmd.action {
+			SecretStoreAction::List => {
+				let documents = key_storage.documents().map_err(|e| format!("Error reading key storage: {}", e))?;
+				Ok(documents.into_iter()
+					.map(|d| format!("0x{:?}", d))
+					.collect::<Vec<_>>()
+					.join("\n"))
+			},
+			SecretStoreAction::Show(key_id) => {
+				let key_share = key_storage.get(&key_id).map_err(|e| format!("Error reading key 0x{:?}: {}", key_id, e))?;
+				let versions = key_storage.versions(&key_id).map_err(|e| format!("Error reading key 0x{:?}: {}", key_id, e))?;
+
+				let mut result = vec![
+					format!("author: 0x{:?}", key_share.author),
+					format!("threshold: {}", key_share.threshold),
+					format!("common point: {}", key_share.common_point.map(|p| format!("0x{:?}", p)).unwrap_or_else(|| "none".into())),
+					format!("encrypted point: {}", key_share.encrypted_point.map(|p| format!("0x{:?}", p)).unwrap_or_else(|| "none".into())),
+					format!("versions: {}", versions.iter().map(|v| format!("0x{:?}", v)).collect::<Vec<_>>().join(", ")),
+					"holders:".into(),
+				];
+				result.extend(key_share.id_numbers.keys().map(|n| format!("  0x{:?}", n)));
+				Ok(result.join("\n"))
+			},
+			SecretStoreAction::Verify => {
+				let documents = key_storage.documents().map_err(|e| format!("Error reading key storage: {}", e))?;
+				let errors: Vec<_> = documents.iter()
+					.filter_map(|d| verify_key(&*key_storage, d).err().map(|e| format!("0x{:?}: {}", d, e)))
+					.collect();
+				if !errors.is_empty() {
+					return Err(errors.join("\n"));
+				}
+
+				Ok(format!("{} key(s) verified", documents.len()))
+			},
+		}
+	}
+
+	fn key_storage_backend(key_storage: &KeyStorageBackend) -> ethcore_secretstore::KeyStorageBackend {
+		match *key_storage {
+			KeyStorageBackend::Database => ethcore_secretstore::KeyStorageBackend::Database,
+			KeyStorageBackend::File => ethcore_secretstore::KeyStorageBackend::File,
+			KeyStorageBackend::Memory => ethcore_secretstore::KeyStorageBackend::Memory,
+		}
+	}
+
+	fn open_key_storage(cmd: &SecretStoreCmd) -> Result<Arc<ethcore_secretstore::KeyStorage>, String> {
+		// key shares are encrypted with the key, derived from node secret
+		let self_key_pair: Box<ethcore_secretstore::NodeKeyPair> = match cmd.self_secret {
+			Some(NodeSecretKey::Plain(ref secret)) => Box::new(ethcore_secretstore::PlainNodeKeyPair::new(
+				KeyPair::from_secret(secret.clone()).map_err(|e| format!("invalid secret: {}", e))?)),
+			Some(NodeSecretKey::KeyStore(ref account)) => {
+				let account_provider = open_account_provider(&cmd.keys_path, &cmd.spec)?;
+				let passwords = passwords_from_files(&cmd.password_files)?;
+				Box::new(keystore_node_key_pair(Arc::new(account_provider), account.clone(), &passwords)?)
+			},
+			None => return Err("self secret is required to open key storage".into()),
+		};
+
+		ethcore_secretstore::open_key_storage(&key_storage_backend(&cmd.key_storage), &cmd.path, &*self_key_pair)
+			.map_err(|e| format!("Error opening key storage at {}: {}", cmd.path, e))
+	}
+
+	fn open_account_provider(keys_path: &str, spec: &SpecType) -> Result<AccountProvider, String> {
+		let spec = spec.spec(&::std::env::temp_dir())?;
+		let mut path = PathBuf::from(keys_path);
+		path.push(spec.data_dir);
+		let dir = Box::new(RootDiskDirectory::at(path));
+		let secret_store = Box::new(EthStore::open(dir).map_err(|e| format!("Could not open keys store: {}", e))?);
+		Ok(AccountProvider::new(secret_store, AccountProviderSettings::default()))
+	}
+
+	fn keystore_node_key_pair(account_provider: Arc<AccountProvider>, account: Address, passwords: &[String]) -> Result<ethcore_secretstore::KeyStoreNodeKeyPair, String> {
+		// Check if account exists
+		if !account_provider.has_account(account.clone()).unwrap_or(false) {
+			return Err(format!("Account {} passed as secret store node key is not found", account));
+		}
+
+		// Check if any passwords have been read from the password file(s)
+		if passwords.is_empty() {
+			return Err(format!("No password found for the secret store node account {}", account));
+		}
+
+		// Attempt to sign in the engine signer.
+		let password = passwords.iter()
+			.find(|p| account_provider.sign(account.clone(), Some((*p).clone()), Default::default()).is_ok())
+			.ok_or(format!("No valid password for the secret store node account {}", account))?;
+		ethcore_secretstore::KeyStoreNodeKeyPair::new(account_provider, account, password.clone())
+			.map_err(|e| format!("{}", e))
+	}
+
+	fn verify_key(key_storage: &ethcore_secretstore::KeyStorage, key_id: &H256) -> Result<(), String> {
+		let versions = key_storage.versions(key_id).map_err(|e| format!("{}", e))?;
+		for version in versions {
+			let key_share = key_storage.get_version(key_id, &version).map_err(|e| format!("version 0x{:?}: {}", version, e))?;
+			if key_share.version() != version {
+				return Err(format!("version 0x{:?}: version does not match key holders", version));
+			}
+			if key_share.threshold >= key_share.id_numbers.len() {
+				return Err(format!("version 0x{:?}: threshold {} requires more than {} key holders", version, key_share.threshold, key_share.id_numbers.len()));
+			}
+		}
+
+		Ok(())
+	}
 }
 
-pub use self::server::KeyServer;
+pub use self::server::{KeyServer, execute};
 
 impl Default for Configuration {
 	fn default() -> Self {
@@ -169,13 +452,44 @@ impl Default for Configuration {
 			enabled: true,
 			http_enabled: true,
 			acl_check_enabled: true,
+			acl_file: None,
+			acl_service: None,
+			acl_cache_ttl: None,
+			audit_log: None,
+			health_check_enabled: false,
+			service_contract_address: None,
+			requester_sessions_per_minute: None,
+			requester_concurrent_sessions: None,
+			sessions_per_minute: None,
+			concurrent_sessions: None,
+			rekeyings_per_minute: None,
+			admin_messages_share: None,
+			removed_keys_retention: None,
+			decryption_cache_ttl: None,
+			max_session_traffic: None,
+			session_journal: None,
+			backup_path: None,
+			session_timeouts: BTreeMap::new(),
+			session_total_timeout: None,
+			peer_rate_limits: BTreeMap::new(),
+			peer_throttle_time: None,
+			deterministic_node_selection: false,
+			message_retries: None,
 			self_secret: None,
 			nodes: BTreeMap::new(),
+			read_only_nodes: Vec::new(),
 			interface: "127.0.0.1".to_owned(),
 			port: 8083,
+			external_address: None,
+			upnp_enabled: false,
 			http_interface: "127.0.0.1".to_owned(),
 			http_port: 8082,
+			http_api_keys: None,
+			http_cors: None,
+			http_hosts: None,
+			http_tls: None,
 			data_path: replace_home(&data_dir, "$BASE/secretstore"),
+			key_storage: KeyStorageBackend::Database,
 		}
 	}
 }
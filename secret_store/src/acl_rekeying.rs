@@ -0,0 +1,219 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use parking_lot::{Mutex, Condvar};
+use acl_storage::AclStorage;
+use key_server_cluster::{ClusterClient, Error as ClusterError};
+use key_server_set::KeyServerSet;
+use service_contract_listener::is_processed_by_this_key_server;
+use traits::NodeKeyPair;
+use types::all::ServerKeyId;
+
+/// Interval between checks for revoked documents (in seconds).
+const REVOCATIONS_CHECK_INTERVAL: u64 = 5;
+/// Interval of re-keyings rate limit (in seconds).
+const REKEYINGS_LIMIT_INTERVAL: u64 = 60;
+
+/// ACL re-keying policy. When access to the document is revoked in the ACL, document key is re-generated,
+/// so that revoked requesters could not use previously retrieved document key to decrypt documents,
+/// encrypted after revocation. Documents must be re-encrypted with the new key by their owners.
+/// Every document is re-keyed by the single key server of the current set, selected by the server key id.
+pub struct AclRekeying {
+	/// Shared re-keying data.
+	data: Arc<AclRekeyingData>,
+	/// Service thread handle.
+	service_handle: Option<thread::JoinHandle<()>>,
+}
+
+/// ACL re-keying data.
+struct AclRekeyingData {
+	/// ACL storage.
+	acl_storage: Arc<AclStorage>,
+	/// Cluster client.
+	cluster: Arc<ClusterClient>,
+	/// This node key pair.
+	self_key_pair: Arc<NodeKeyPair>,
+	/// Key servers set.
+	key_server_set: Arc<KeyServerSet>,
+	/// Documents, waiting for re-keying.
+	queue: Mutex<RekeyingQueue>,
+	/// Is shutdown requested.
+	is_shutdown: Mutex<bool>,
+	/// Shutdown event.
+	shutdown_event: Condvar,
+}
+
+/// Rate-limited queue of documents, waiting for re-keying.
+struct RekeyingQueue {
+	/// Max number of re-keyings, started within a minute.
+	rekeyings_per_minute: usize,
+	/// Documents, waiting for re-keying.
+	documents: VecDeque<ServerKeyId>,
+	/// Start times of re-keyings, started within the last minute.
+	recent_rekeyings: VecDeque<Instant>,
+}
+
+impl AclRekeying {
+	pub fn new(acl_storage: Arc<AclStorage>, cluster: Arc<ClusterClient>, self_key_pair: Arc<NodeKeyPair>, key_server_set: Arc<KeyServerSet>, rekeyings_per_minute: usize) -> Self {
+		let data = Arc::new(AclRekeyingData {
+			acl_storage: acl_storage,
+			cluster: cluster,
+			self_key_pair: self_key_pair,
+			key_server_set: key_server_set,
+			queue: Mutex::new(RekeyingQueue::new(rekeyings_per_minute)),
+			is_shutdown: Mutex::new(false),
+			shutdown_event: Condvar::new(),
+		});
+
+		let service_thread_data = data.clone();
+		let service_handle = thread::spawn(move || AclRekeying::run_service_thread(service_thread_data));
+		AclRekeying {
+			data: data,
+			service_handle: Some(service_handle),
+		}
+	}
+
+	fn run_service_thread(data: Arc<AclRekeyingData>) {
+		loop {
+			{
+				let mut is_shutdown = data.is_shutdown.lock();
+				if !*is_shutdown {
+					data.shutdown_event.wait_for(&mut is_shutdown, Duration::from_secs(REVOCATIONS_CHECK_INTERVAL));
+				}
+				if *is_shutdown {
+					break;
+				}
+			}
+
+			data.rekey_revoked_documents();
+		}
+	}
+}
+
+impl Drop for AclRekeying {
+	fn drop(&mut self) {
+		if let Some(service_handle) = self.service_handle.take() {
+			*self.data.is_shutdown.lock() = true;
+			self.data.shutdown_event.notify_all();
+			// ignore error as we are already closing
+			let _ = service_handle.join();
+		}
+	}
+}
+
+impl AclRekeyingData {
+	/// Enqueue revoked documents && re-key as much of them, as rate limit allows.
+	fn rekey_revoked_documents(&self) {
+		let revoked_documents = self.acl_storage.take_revoked_documents();
+		if !revoked_documents.is_empty() {
+			let key_servers = self.key_server_set.get().into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+			let mut queue = self.queue.lock();
+			for document in revoked_documents {
+				if is_processed_by_this_key_server(&key_servers, self.self_key_pair.public(), &document) {
+					queue.push(document);
+				}
+			}
+		}
+
+		loop {
+			let document = match self.queue.lock().pop(Instant::now()) {
+				Some(document) => document,
+				None => break,
+			};
+
+			match self.cluster.new_rekeying_session(document.clone()).and_then(|session| session.wait(None)) {
+				Ok(()) => info!(target: "secretstore", "{}: document key {} has been re-keyed after ACL revocation",
+					self.self_key_pair.public(), document),
+				// only server key is generated => nothing to re-key
+				Err(ClusterError::NotStartedSessionId) => (),
+				Err(error) => warn!(target: "secretstore", "{}: document key {} re-keying after ACL revocation has failed with: {}",
+					self.self_key_pair.public(), document, error),
+			}
+		}
+	}
+}
+
+impl RekeyingQueue {
+	/// Create new queue.
+	pub fn new(rekeyings_per_minute: usize) -> Self {
+		RekeyingQueue {
+			rekeyings_per_minute: rekeyings_per_minute,
+			documents: VecDeque::new(),
+			recent_rekeyings: VecDeque::new(),
+		}
+	}
+
+	/// Enqueue document for re-keying.
+	pub fn push(&mut self, document: ServerKeyId) {
+		if !self.documents.contains(&document) {
+			self.documents.push_back(document);
+		}
+	}
+
+	/// Dequeue document for re-keying, if rate limit allows.
+	pub fn pop(&mut self, now: Instant) -> Option<ServerKeyId> {
+		while self.recent_rekeyings.front().map(|t| now.duration_since(*t) >= Duration::from_secs(REKEYINGS_LIMIT_INTERVAL)).unwrap_or(false) {
+			self.recent_rekeyings.pop_front();
+		}
+		if self.recent_rekeyings.len() >= self.rekeyings_per_minute {
+			return None;
+		}
+
+		let document = self.documents.pop_front();
+		if document.is_some() {
+			self.recent_rekeyings.push_back(now);
+		}
+		document
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	use super::RekeyingQueue;
+
+	#[test]
+	fn rekeying_queue_ignores_duplicate_documents() {
+		let mut queue = RekeyingQueue::new(10);
+		queue.push(1.into());
+		queue.push(2.into());
+		queue.push(1.into());
+
+		let now = Instant::now();
+		assert_eq!(queue.pop(now), Some(1.into()));
+		assert_eq!(queue.pop(now), Some(2.into()));
+		assert_eq!(queue.pop(now), None);
+	}
+
+	#[test]
+	fn rekeying_queue_is_rate_limited() {
+		let mut queue = RekeyingQueue::new(2);
+		queue.push(1.into());
+		queue.push(2.into());
+		queue.push(3.into());
+
+		let now = Instant::now();
+		assert_eq!(queue.pop(now), Some(1.into()));
+		assert_eq!(queue.pop(now + Duration::from_secs(30)), Some(2.into()));
+		assert_eq!(queue.pop(now + Duration::from_secs(59)), None);
+		assert_eq!(queue.pop(now + Duration::from_secs(60)), Some(3.into()));
+		assert_eq!(queue.pop(now + Duration::from_secs(60)), None);
+	}
+}
@@ -14,24 +14,49 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::fs;
+use std::mem;
+use std::io::Read;
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::path::PathBuf;
 use std::collections::{HashMap, HashSet};
 use futures::{future, Future};
+use hyper::Client as HttpClient;
+use hyper::header::ContentType;
+use hyper::status::StatusCode as HttpStatusCode;
 use parking_lot::{Mutex, RwLock};
+use serde_json;
 use ethkey::public_to_address;
+use ethcore::filter::Filter;
 use ethcore::client::{Client, BlockChainClient, BlockId, ChainNotify};
 use native_contracts::SecretStoreAclStorage;
 use bigint::hash::H256;
 use util::Address;
 use bytes::Bytes;
+use serialization::{SerializablePublic, SerializableH256};
 use types::all::{Error, ServerKeyId, Public};
 
 const ACL_CHECKER_CONTRACT_REGISTRY_NAME: &'static str = "secretstore_acl_checker";
+/// When there are more blocks in the import queue, the chain is considered not synced.
+const MAX_SYNCED_QUEUE_SIZE: usize = 3;
+/// Default time (in seconds) during which decisions of HTTP policy service are cached.
+const DEFAULT_HTTP_ACL_CACHE_TTL: u64 = 60;
+/// Max number of cached decisions of HTTP policy service.
+const MAX_HTTP_ACL_CACHE_SIZE: usize = 64 * 1024;
+/// Timeout (in seconds) of single request to HTTP policy service.
+const HTTP_ACL_REQUEST_TIMEOUT: u64 = 10;
+/// Max size of HTTP policy service response.
+const MAX_HTTP_ACL_RESPONSE_SIZE: u64 = 4 * 1024;
 
 /// ACL storage of Secret Store
 pub trait AclStorage: Send + Sync {
 	/// Check if requestor with `public` key can access document with hash `document`
 	fn check(&self, public: &Public, document: &ServerKeyId) -> Result<bool, Error>;
+	/// Take documents, access to which has been revoked from some requester since previous call.
+	fn take_revoked_documents(&self) -> Vec<ServerKeyId> {
+		Vec::new()
+	}
 }
 
 /// On-chain ACL storage implementation.
@@ -48,12 +73,111 @@ struct CachedContract {
 	contract_addr: Option<Address>,
 	/// Contract at given address.
 	contract: Option<SecretStoreAclStorage>,
+	/// Cached permissions, read from the contract at given address.
+	permissions: HashMap<(Address, ServerKeyId), bool>,
+	/// Documents, access to which has been revoked since they have been taken last time.
+	revoked: Vec<ServerKeyId>,
+}
+
+/// File-based ACL storage implementation.
+/// The file is a JSON object, mapping requester address to the list of grants. Every grant is either
+/// a key id, or an object { "key": key id, "expires": unix timestamp }. The "*" key id grants access
+/// to all keys. The file is reloaded when modified.
+pub struct FileAclStorage {
+	/// Path to the ACL file.
+	path: PathBuf,
+	/// Permissions, read from the file.
+	acl: RwLock<FileAcl>,
+	/// Documents, access to which has been revoked since they have been taken last time.
+	revoked: Mutex<Vec<ServerKeyId>>,
+}
+
+/// Permissions, read from ACL file.
+#[derive(Debug, Default)]
+struct FileAcl {
+	/// File modification time at the moment of reading.
+	modified: Option<SystemTime>,
+	/// Requester address => accessible documents.
+	permissions: HashMap<Address, DocumentsPermission>,
+}
+
+/// Documents, accessible by the requester.
+#[derive(Debug, Default, PartialEq)]
+struct DocumentsPermission {
+	/// If Some, requester can access all documents until given expiry time.
+	all: Option<Expiry>,
+	/// Requester can access these documents until given expiry time.
+	documents: HashMap<ServerKeyId, Expiry>,
+}
+
+/// Grant expiry time (seconds since unix epoch). None if grant never expires.
+type Expiry = Option<u64>;
+
+/// Single grant, as it is stored in the ACL file.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SerializableGrant {
+	/// Grant without expiry.
+	Key(String),
+	/// Grant, which expires at given time.
+	ExpiringKey {
+		/// Key id.
+		key: String,
+		/// Expiry time (seconds since unix epoch).
+		expires: u64,
+	},
+}
+
+/// ACL storage, which delegates access decisions to the external HTTP policy service.
+/// Service is called with POST request with body { "requester": public, "address": address, "key": key id }
+/// and must respond with { "allowed": true|false }. Decisions are cached for configured time. When cached
+/// decision, which has permitted access, expires, it is re-checked && document is reported as revoked if
+/// access is now denied.
+pub struct HttpAclStorage {
+	/// Policy service URL.
+	url: String,
+	/// Time during which decisions are cached.
+	cache_ttl: Duration,
+	/// HTTP client.
+	client: HttpClient,
+	/// Cached decisions.
+	decisions: Mutex<HashMap<(Public, ServerKeyId), CachedDecision>>,
+	/// Documents, access to which has been revoked since they have been taken last time.
+	revoked: Mutex<Vec<ServerKeyId>>,
+}
+
+/// Cached decision of HTTP policy service.
+#[derive(Debug, Clone, Copy)]
+struct CachedDecision {
+	/// Is access permitted.
+	is_permitted: bool,
+	/// Decision is cached until this time.
+	expires: Instant,
+}
+
+/// Request to HTTP policy service.
+#[derive(Debug, Serialize)]
+struct SerializableAclRequest {
+	/// Requester public key.
+	requester: SerializablePublic,
+	/// Requester address.
+	address: String,
+	/// Key id.
+	key: SerializableH256,
+}
+
+/// Response of HTTP policy service.
+#[derive(Debug, Deserialize)]
+struct SerializableAclResponse {
+	/// Is access permitted.
+	allowed: bool,
 }
 
 /// Dummy ACL storage implementation (check always passed).
 #[derive(Default, Debug)]
 pub struct DummyAclStorage {
 	prohibited: RwLock<HashMap<Public, HashSet<ServerKeyId>>>,
+	revoked: Mutex<Vec<ServerKeyId>>,
 }
 
 impl OnChainAclStorage {
@@ -70,12 +194,16 @@ impl AclStorage for OnChainAclStorage {
 	fn check(&self, public: &Public, document: &ServerKeyId) -> Result<bool, Error> {
 		self.contract.lock().check(public, document)
 	}
+
+	fn take_revoked_documents(&self) -> Vec<ServerKeyId> {
+		mem::replace(&mut self.contract.lock().revoked, Vec::new())
+	}
 }
 
 impl ChainNotify for OnChainAclStorage {
 	fn new_blocks(&self, _imported: Vec<H256>, _invalid: Vec<H256>, enacted: Vec<H256>, retracted: Vec<H256>, _sealed: Vec<H256>, _proposed: Vec<Bytes>, _duration: u64) {
 		if !enacted.is_empty() || !retracted.is_empty() {
-			self.contract.lock().update()
+			self.contract.lock().update(enacted, retracted)
 		}
 	}
 }
@@ -86,10 +214,12 @@ impl CachedContract {
 			client: Arc::downgrade(client),
 			contract_addr: None,
 			contract: None,
+			permissions: HashMap::new(),
+			revoked: Vec::new(),
 		}
 	}
 
-	pub fn update(&mut self) {
+	pub fn update(&mut self, enacted: Vec<H256>, retracted: Vec<H256>) {
 		if let Some(client) = self.client.upgrade() {
 			let new_contract_addr = client.registry_address(ACL_CHECKER_CONTRACT_REGISTRY_NAME.to_owned());
 			if self.contract_addr.as_ref() != new_contract_addr.as_ref() {
@@ -100,28 +230,308 @@ impl CachedContract {
 				});
 
 				self.contract_addr = new_contract_addr;
+				self.permissions.clear();
+				return;
+			}
+
+			// any event, emitted by the contract, could change permissions => just drop the whole cache
+			let is_contract_changed = self.contract_addr.is_some() && enacted.iter()
+				.chain(retracted.iter())
+				.any(|block_hash| !client.logs(Filter {
+					from_block: BlockId::Hash(block_hash.clone()),
+					to_block: BlockId::Hash(block_hash.clone()),
+					address: self.contract_addr.clone().map(|a| vec![a]),
+					topics: vec![None, None, None, None],
+					limit: Some(1),
+				}).is_empty());
+			if is_contract_changed {
+				trace!(target: "secretstore", "ACL checker contract has emitted event. Dropping {} cached permissions", self.permissions.len());
+				let permitted: Vec<_> = self.permissions.drain()
+					.filter(|&(_, is_permitted)| is_permitted)
+					.map(|(key, _)| key)
+					.collect();
+
+				// access is denied while the chain is syncing => only check for revocations when synced
+				if is_synced(&*client) {
+					for (address, document) in permitted {
+						if let Ok(false) = self.check_address(address, &document) {
+							trace!(target: "secretstore", "Access to {} has been revoked in ACL checker contract", document);
+							self.revoked.push(document);
+						}
+					}
+				}
 			}
 		}
 	}
 
 	pub fn check(&mut self, public: &Public, document: &ServerKeyId) -> Result<bool, Error> {
+		self.check_address(public_to_address(&public), document)
+	}
+
+	fn check_address(&mut self, address: Address, document: &ServerKeyId) -> Result<bool, Error> {
+		if let Some(is_permitted) = self.permissions.get(&(address.clone(), document.clone())) {
+			return Ok(*is_permitted);
+		}
+
+		let client = self.client.upgrade().ok_or(Error::Internal("Calling contract without client".into()))?;
 		match self.contract.as_ref() {
+			// when the chain is not yet synced, the contract state could be outdated => deny access until synced
+			Some(_) if !is_synced(&*client) => {
+				trace!(target: "secretstore", "Denying access of {} to {}: chain is not synced", address, document);
+				Ok(false)
+			},
 			Some(contract) => {
-				let address = public_to_address(&public);
-				let do_call = |a, d| future::done(
-					self.client
-						.upgrade()
-						.ok_or("Calling contract without client".into())
-						.and_then(|c| c.call_contract(BlockId::Latest, a, d)));
-				contract.check_permissions(do_call, address, document.clone())
+				let do_call = |a, d| future::done(client.call_contract(BlockId::Latest, a, d));
+				let is_permitted = contract.check_permissions(do_call, address.clone(), document.clone())
 					.map_err(|err| Error::Internal(err))
-					.wait()
+					.wait()?;
+				self.permissions.insert((address, document.clone()), is_permitted);
+				Ok(is_permitted)
 			},
 			None => Err(Error::Internal("ACL checker contract is not configured".to_owned())),
 		}
 	}
 }
 
+/// Check if client has (almost) imported all known blocks.
+pub fn is_synced(client: &Client) -> bool {
+	let queue_info = client.queue_info();
+	queue_info.unverified_queue_size + queue_info.verified_queue_size <= MAX_SYNCED_QUEUE_SIZE
+}
+
+impl FileAclStorage {
+	/// Read ACL from given file.
+	pub fn new(path: &str) -> Result<Self, Error> {
+		let path = PathBuf::from(path);
+		let acl = FileAcl::read(&path)?;
+		Ok(FileAclStorage {
+			path: path,
+			acl: RwLock::new(acl),
+			revoked: Mutex::new(Vec::new()),
+		})
+	}
+
+	/// Reload ACL if file has been modified since it has been read.
+	fn reload_if_modified(&self) {
+		let modified = file_modified(&self.path);
+		if modified.is_some() && modified == self.acl.read().modified {
+			return;
+		}
+
+		match FileAcl::read(&self.path) {
+			Ok(acl) => {
+				trace!(target: "secretstore", "Reloaded ACL file {}", self.path.display());
+				let mut old_acl = self.acl.write();
+				self.revoked.lock().extend(old_acl.revoked_documents(&acl));
+				*old_acl = acl;
+			},
+			Err(err) => warn!(target: "secretstore", "Error reloading ACL file {}: {}. Using previous version", self.path.display(), err),
+		}
+	}
+}
+
+impl AclStorage for FileAclStorage {
+	fn check(&self, public: &Public, document: &ServerKeyId) -> Result<bool, Error> {
+		self.reload_if_modified();
+
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+		Ok(self.acl.read().permissions.get(&public_to_address(public))
+			.map(|permission| permission.all.iter().chain(permission.documents.get(document))
+				.any(|expiry| expiry.map(|expiry| now < expiry).unwrap_or(true)))
+			.unwrap_or(false))
+	}
+
+	fn take_revoked_documents(&self) -> Vec<ServerKeyId> {
+		self.reload_if_modified();
+		mem::replace(&mut *self.revoked.lock(), Vec::new())
+	}
+}
+
+impl FileAcl {
+	pub fn read(path: &PathBuf) -> Result<Self, Error> {
+		let modified = file_modified(path);
+		let file = fs::File::open(path).map_err(|e| Error::Internal(format!("error opening ACL file: {}", e)))?;
+		let raw_permissions: HashMap<String, Vec<SerializableGrant>> = serde_json::from_reader(file)
+			.map_err(|e| Error::Internal(format!("error reading ACL file: {}", e)))?;
+		let mut permissions = HashMap::new();
+		for (requester, grants) in raw_permissions {
+			let requester: Address = clean_0x(&requester).parse()
+				.map_err(|_| Error::Internal(format!("invalid requester address in ACL file: {}", requester)))?;
+			let mut permission = DocumentsPermission::default();
+			for grant in grants {
+				let (key, expiry) = match grant {
+					SerializableGrant::Key(key) => (key, None),
+					SerializableGrant::ExpiringKey { key, expires } => (key, Some(expires)),
+				};
+				if key == "*" {
+					permission.all = Some(max_expiry(permission.all, expiry));
+				} else {
+					let key: ServerKeyId = clean_0x(&key).parse()
+						.map_err(|_| Error::Internal(format!("invalid key id in ACL file: {}", key)))?;
+					let previous_expiry = permission.documents.get(&key).cloned();
+					permission.documents.insert(key, max_expiry(previous_expiry, expiry));
+				}
+			}
+			permissions.insert(requester, permission);
+		}
+
+		Ok(FileAcl {
+			modified: modified,
+			permissions: permissions,
+		})
+	}
+
+	/// Get documents, which have been explicitly granted to some requester in this ACL, but are not granted in the new one.
+	/// Expiration of grants is not considered as revocation. Revocation of "*" grant could not be tracked, since
+	/// it is unknown which documents have been accessed using this grant.
+	pub fn revoked_documents(&self, new_acl: &FileAcl) -> Vec<ServerKeyId> {
+		let mut revoked = Vec::new();
+		for (requester, permission) in &self.permissions {
+			let new_permission = new_acl.permissions.get(requester);
+			for document in permission.documents.keys() {
+				let is_granted = new_permission
+					.map(|p| p.all.is_some() || p.documents.contains_key(document))
+					.unwrap_or(false);
+				if !is_granted && !revoked.contains(document) {
+					revoked.push(document.clone());
+				}
+			}
+		}
+		revoked
+	}
+}
+
+fn file_modified(path: &PathBuf) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Select the latest of two grants expiry times.
+fn max_expiry(previous: Option<Expiry>, expiry: Expiry) -> Expiry {
+	match (previous, expiry) {
+		(None, expiry) => expiry,
+		(Some(None), _) | (_, None) => None,
+		(Some(Some(previous)), Some(expiry)) => Some(::std::cmp::max(previous, expiry)),
+	}
+}
+
+fn clean_0x(s: &str) -> &str {
+	if s.starts_with("0x") { &s[2..] } else { s }
+}
+
+impl HttpAclStorage {
+	/// Create ACL storage, calling policy service at given URL. If cache TTL is None, default value is used.
+	pub fn new(url: String, cache_ttl: Option<u64>) -> Self {
+		let mut client = HttpClient::new();
+		client.set_read_timeout(Some(Duration::from_secs(HTTP_ACL_REQUEST_TIMEOUT)));
+		client.set_write_timeout(Some(Duration::from_secs(HTTP_ACL_REQUEST_TIMEOUT)));
+
+		HttpAclStorage {
+			url: url,
+			cache_ttl: Duration::from_secs(cache_ttl.unwrap_or(DEFAULT_HTTP_ACL_CACHE_TTL)),
+			client: client,
+			decisions: Mutex::new(HashMap::new()),
+			revoked: Mutex::new(Vec::new()),
+		}
+	}
+
+	/// Ask policy service if requester can access the document.
+	fn request(&self, public: &Public, document: &ServerKeyId) -> Result<bool, Error> {
+		let request = serde_json::to_string(&SerializableAclRequest {
+			requester: public.clone().into(),
+			address: format!("0x{:?}", public_to_address(public)),
+			key: document.clone().into(),
+		}).map_err(|e| Error::Serde(format!("{}", e)))?;
+
+		let response = self.client.post(&self.url)
+			.header(ContentType::json())
+			.body(&request)
+			.send()
+			.map_err(|e| Error::Internal(format!("error calling ACL policy service: {}", e)))?;
+		if response.status != HttpStatusCode::Ok {
+			return Err(Error::Internal(format!("ACL policy service has responded with {}", response.status)));
+		}
+
+		let response: SerializableAclResponse = serde_json::from_reader(response.take(MAX_HTTP_ACL_RESPONSE_SIZE))
+			.map_err(|e| Error::Internal(format!("invalid ACL policy service response: {}", e)))?;
+		Ok(response.allowed)
+	}
+
+	/// Cache the decision.
+	fn cache(&self, public: Public, document: ServerKeyId, is_permitted: bool) {
+		let mut decisions = self.decisions.lock();
+		if decisions.len() >= MAX_HTTP_ACL_CACHE_SIZE {
+			// expired decisions, which have permitted access, are kept for revocation check
+			let now = Instant::now();
+			decisions.retain(|_, decision| decision.is_permitted || decision.expires > now);
+			if decisions.len() >= MAX_HTTP_ACL_CACHE_SIZE {
+				return;
+			}
+		}
+
+		decisions.insert((public, document), CachedDecision {
+			is_permitted: is_permitted,
+			expires: Instant::now() + self.cache_ttl,
+		});
+	}
+}
+
+impl AclStorage for HttpAclStorage {
+	fn check(&self, public: &Public, document: &ServerKeyId) -> Result<bool, Error> {
+		let cached_decision = self.decisions.lock().get(&(public.clone(), document.clone())).cloned();
+		match cached_decision {
+			Some(decision) if decision.expires > Instant::now() => Ok(decision.is_permitted),
+			_ => {
+				let is_permitted = self.request(public, document)?;
+				if !is_permitted && cached_decision.map(|d| d.is_permitted).unwrap_or(false) {
+					trace!(target: "secretstore", "Access to {} has been revoked by ACL policy service", document);
+					self.revoked.lock().push(document.clone());
+				}
+				self.cache(public.clone(), document.clone(), is_permitted);
+				Ok(is_permitted)
+			},
+		}
+	}
+
+	fn take_revoked_documents(&self) -> Vec<ServerKeyId> {
+		// re-check expired decisions, which have permitted access
+		let now = Instant::now();
+		let expired: Vec<_> = {
+			let mut decisions = self.decisions.lock();
+			let expired = decisions.iter()
+				.filter(|&(_, decision)| decision.is_permitted && decision.expires <= now)
+				.map(|(key, _)| key.clone())
+				.collect::<Vec<_>>();
+			for key in &expired {
+				decisions.remove(key);
+			}
+			expired
+		};
+		for (public, document) in expired {
+			match self.request(&public, &document) {
+				Ok(is_permitted) => {
+					if !is_permitted {
+						trace!(target: "secretstore", "Access to {} has been revoked by ACL policy service", document);
+						self.revoked.lock().push(document.clone());
+					}
+					self.cache(public, document, is_permitted);
+				},
+				Err(err) => {
+					warn!(target: "secretstore", "Error re-checking access to {}: {}", document, err);
+					// keep expired decision to retry later
+					self.decisions.lock().insert((public, document), CachedDecision {
+						is_permitted: true,
+						expires: now,
+					});
+				},
+			}
+		}
+
+		let mut revoked = mem::replace(&mut *self.revoked.lock(), Vec::new());
+		revoked.dedup();
+		revoked
+	}
+}
+
 impl DummyAclStorage {
 	/// Prohibit given requestor access to given documents
 	#[cfg(test)]
@@ -129,7 +539,8 @@ impl DummyAclStorage {
 		self.prohibited.write()
 			.entry(public)
 			.or_insert_with(Default::default)
-			.insert(document);
+			.insert(document.clone());
+		self.revoked.lock().push(document);
 	}
 }
 
@@ -140,4 +551,151 @@ impl AclStorage for DummyAclStorage {
 			.map(|docs| !docs.contains(document))
 			.unwrap_or(true))
 	}
+
+	fn take_revoked_documents(&self) -> Vec<ServerKeyId> {
+		mem::replace(&mut *self.revoked.lock(), Vec::new())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+	use std::io::{Read, Write};
+	use devtools::RandomTempPath;
+	use ethkey::{Random, Generator, public_to_address};
+	use bigint::hash::H256;
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+	use hyper::server::{Server as HttpServer, Request as HttpRequest, Response as HttpResponse, Listening as HttpListening};
+	use super::{AclStorage, FileAclStorage, FileAcl, HttpAclStorage};
+
+	/// Start policy service, permitting access to the document 1 while `allowed` is true.
+	fn start_policy_service(allowed: Arc<AtomicBool>, requests: Arc<AtomicUsize>) -> HttpListening {
+		HttpServer::http("127.0.0.1:0").unwrap().handle(move |mut req: HttpRequest, res: HttpResponse| {
+			let mut body = String::new();
+			req.read_to_string(&mut body).unwrap();
+			requests.fetch_add(1, Ordering::SeqCst);
+			let is_allowed = allowed.load(Ordering::SeqCst)
+				&& body.contains("\"0x0000000000000000000000000000000000000000000000000000000000000001\"");
+			res.send(format!(r#"{{"allowed": {}}}"#, is_allowed).as_bytes()).unwrap();
+		}).unwrap()
+	}
+
+	#[test]
+	fn file_acl_storage_checks_permissions() {
+		let all = Random.generate().unwrap();
+		let some = Random.generate().unwrap();
+		let none = Random.generate().unwrap();
+		let path = RandomTempPath::create_dir();
+		let acl_path = path.as_path().join("acl.json");
+		fs::File::create(&acl_path).unwrap().write_all(format!(r#"{{
+			"0x{:?}": ["*"],
+			"{:?}": ["0x0000000000000000000000000000000000000000000000000000000000000001"]
+		}}"#, public_to_address(all.public()), public_to_address(some.public())).as_bytes()).unwrap();
+
+		let acl_storage = FileAclStorage::new(acl_path.to_str().unwrap()).unwrap();
+		assert_eq!(acl_storage.check(all.public(), &H256::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(all.public(), &H256::from(2)), Ok(true));
+		assert_eq!(acl_storage.check(some.public(), &H256::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(some.public(), &H256::from(2)), Ok(false));
+		assert_eq!(acl_storage.check(none.public(), &H256::from(1)), Ok(false));
+	}
+
+	#[test]
+	fn file_acl_storage_checks_grants_expiry() {
+		let requester = Random.generate().unwrap();
+		let path = RandomTempPath::create_dir();
+		let acl_path = path.as_path().join("acl.json");
+		fs::File::create(&acl_path).unwrap().write_all(format!(r#"{{
+			"{:?}": [
+				{{ "key": "0000000000000000000000000000000000000000000000000000000000000001", "expires": 1 }},
+				{{ "key": "0000000000000000000000000000000000000000000000000000000000000002", "expires": 1 }},
+				"0000000000000000000000000000000000000000000000000000000000000002",
+				{{ "key": "0000000000000000000000000000000000000000000000000000000000000003", "expires": 18446744073709551615 }}
+			]
+		}}"#, public_to_address(requester.public())).as_bytes()).unwrap();
+
+		let acl_storage = FileAclStorage::new(acl_path.to_str().unwrap()).unwrap();
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(1)), Ok(false));
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(2)), Ok(true));
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(3)), Ok(true));
+	}
+
+	#[test]
+	fn file_acl_reports_revoked_documents() {
+		let revoked = Random.generate().unwrap();
+		let kept = Random.generate().unwrap();
+		let path = RandomTempPath::create_dir();
+		let old_acl_path = path.as_path().join("old_acl.json");
+		fs::File::create(&old_acl_path).unwrap().write_all(format!(r#"{{
+			"{:?}": ["0000000000000000000000000000000000000000000000000000000000000001", "0000000000000000000000000000000000000000000000000000000000000002"],
+			"{:?}": ["0000000000000000000000000000000000000000000000000000000000000003"]
+		}}"#, public_to_address(revoked.public()), public_to_address(kept.public())).as_bytes()).unwrap();
+		let new_acl_path = path.as_path().join("new_acl.json");
+		fs::File::create(&new_acl_path).unwrap().write_all(format!(r#"{{
+			"{:?}": ["0000000000000000000000000000000000000000000000000000000000000002"],
+			"{:?}": ["*"]
+		}}"#, public_to_address(revoked.public()), public_to_address(kept.public())).as_bytes()).unwrap();
+
+		let old_acl = FileAcl::read(&old_acl_path).unwrap();
+		let new_acl = FileAcl::read(&new_acl_path).unwrap();
+		assert_eq!(old_acl.revoked_documents(&new_acl), vec![H256::from(1)]);
+		assert!(new_acl.revoked_documents(&old_acl).is_empty());
+	}
+
+	#[test]
+	fn file_acl_storage_fails_to_read_invalid_file() {
+		let path = RandomTempPath::create_dir();
+		let acl_path = path.as_path().join("acl.json");
+		fs::File::create(&acl_path).unwrap().write_all(br#"{"not-an-address": ["*"]}"#).unwrap();
+		assert!(FileAclStorage::new(acl_path.to_str().unwrap()).is_err());
+	}
+
+	#[test]
+	fn http_acl_storage_caches_decisions() {
+		let requester = Random.generate().unwrap();
+		let allowed = Arc::new(AtomicBool::new(true));
+		let requests = Arc::new(AtomicUsize::new(0));
+		let mut service = start_policy_service(allowed.clone(), requests.clone());
+
+		let acl_storage = HttpAclStorage::new(format!("http://{}", service.socket), None);
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(2)), Ok(false));
+		assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+		// decisions are cached
+		allowed.store(false, Ordering::SeqCst);
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(1)), Ok(true));
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(2)), Ok(false));
+		assert_eq!(requests.load(Ordering::SeqCst), 2);
+		assert!(acl_storage.take_revoked_documents().is_empty());
+
+		service.close().unwrap();
+	}
+
+	#[test]
+	fn http_acl_storage_reports_revoked_documents() {
+		let requester = Random.generate().unwrap();
+		let allowed = Arc::new(AtomicBool::new(true));
+		let requests = Arc::new(AtomicUsize::new(0));
+		let mut service = start_policy_service(allowed.clone(), requests.clone());
+
+		let acl_storage = HttpAclStorage::new(format!("http://{}", service.socket), Some(0));
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(1)), Ok(true));
+		assert!(acl_storage.take_revoked_documents().is_empty());
+
+		allowed.store(false, Ordering::SeqCst);
+		assert_eq!(acl_storage.take_revoked_documents(), vec![H256::from(1)]);
+		assert_eq!(acl_storage.check(requester.public(), &H256::from(1)), Ok(false));
+		assert!(acl_storage.take_revoked_documents().is_empty());
+
+		service.close().unwrap();
+	}
+
+	#[test]
+	fn http_acl_storage_denies_access_when_service_is_unavailable() {
+		let requester = Random.generate().unwrap();
+		let acl_storage = HttpAclStorage::new("http://127.0.0.1:1".into(), None);
+		assert!(acl_storage.check(requester.public(), &H256::from(1)).is_err());
+	}
 }
@@ -0,0 +1,152 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use parking_lot::Mutex;
+use serde_json;
+use serialization::SerializableAuditRecord;
+use types::all::{Error, ServerKeyId, AuditRecord};
+
+/// Append-only log of operations, requested from this key server.
+pub trait AuditLog: Send + Sync {
+	/// Append new record to the log.
+	fn append(&self, record: AuditRecord) -> Result<(), Error>;
+	/// Read all records, related to given key.
+	fn query(&self, key_id: &ServerKeyId) -> Result<Vec<AuditRecord>, Error>;
+}
+
+/// File-based audit log. Every record is stored as a single-line JSON object.
+pub struct FileAuditLog {
+	/// Path to the log file.
+	path: PathBuf,
+	/// Log file, opened for appending.
+	file: Mutex<File>,
+}
+
+impl FileAuditLog {
+	/// Open (create if not exists) audit log file.
+	pub fn new(path: &str) -> Result<Self, Error> {
+		let path = PathBuf::from(path);
+		let file = OpenOptions::new().create(true).append(true).open(&path)
+			.map_err(|e| Error::Database(format!("error opening audit log: {}", e)))?;
+		Ok(FileAuditLog {
+			path: path,
+			file: Mutex::new(file),
+		})
+	}
+}
+
+impl AuditLog for FileAuditLog {
+	fn append(&self, record: AuditRecord) -> Result<(), Error> {
+		let mut line = serde_json::to_vec(&SerializableAuditRecord::from(record))?;
+		line.push(b'\n');
+
+		let mut file = self.file.lock();
+		file.write_all(&line)
+			.and_then(|_| file.flush())
+			.map_err(|e| Error::Database(format!("error writing audit log: {}", e)))
+	}
+
+	fn query(&self, key_id: &ServerKeyId) -> Result<Vec<AuditRecord>, Error> {
+		// hold the lock, so that partially written records are not read
+		let _file = self.file.lock();
+		let file = File::open(&self.path)
+			.map_err(|e| Error::Database(format!("error opening audit log: {}", e)))?;
+
+		let mut records = Vec::new();
+		for line in BufReader::new(file).lines() {
+			let line = line.map_err(|e| Error::Database(format!("error reading audit log: {}", e)))?;
+			if line.is_empty() {
+				continue;
+			}
+
+			let record: AuditRecord = serde_json::from_str::<SerializableAuditRecord>(&line)?.into();
+			if record.key_id == *key_id {
+				records.push(record);
+			}
+		}
+		Ok(records)
+	}
+}
+
+/// Current time (seconds since unix epoch).
+pub fn unix_timestamp() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+pub mod tests {
+	use parking_lot::Mutex;
+	use devtools::RandomTempPath;
+	use ethkey::{Random, Generator};
+	use bigint::hash::H256;
+	use types::all::{Error, ServerKeyId, AuditOperation, AuditRecord};
+	use super::{AuditLog, FileAuditLog};
+
+	/// In-memory audit log.
+	#[derive(Default)]
+	pub struct DummyAuditLog {
+		records: Mutex<Vec<AuditRecord>>,
+	}
+
+	impl AuditLog for DummyAuditLog {
+		fn append(&self, record: AuditRecord) -> Result<(), Error> {
+			self.records.lock().push(record);
+			Ok(())
+		}
+
+		fn query(&self, key_id: &ServerKeyId) -> Result<Vec<AuditRecord>, Error> {
+			Ok(self.records.lock().iter().filter(|r| r.key_id == *key_id).cloned().collect())
+		}
+	}
+
+	fn make_record(key_id: ServerKeyId, error: Option<String>) -> AuditRecord {
+		AuditRecord {
+			operation: AuditOperation::RestoreDocumentKey,
+			key_id: key_id,
+			requester: Some(Random.generate().unwrap().public().clone()),
+			nodes: vec![Random.generate().unwrap().public().clone()],
+			started: 1,
+			finished: 2,
+			error: error,
+		}
+	}
+
+	#[test]
+	fn file_audit_log_works() {
+		let path = RandomTempPath::create_dir();
+		let log_path = path.as_path().join("audit.log");
+		let record1 = make_record(H256::from(1), None);
+		let record2 = make_record(H256::from(2), Some("Access denied".into()));
+		let record3 = make_record(H256::from(1), Some("Access denied".into()));
+
+		{
+			let audit_log = FileAuditLog::new(log_path.to_str().unwrap()).unwrap();
+			audit_log.append(record1.clone()).unwrap();
+			audit_log.append(record2.clone()).unwrap();
+		}
+
+		// records are preserved when log is reopened
+		let audit_log = FileAuditLog::new(log_path.to_str().unwrap()).unwrap();
+		audit_log.append(record3.clone()).unwrap();
+		assert_eq!(audit_log.query(&H256::from(1)), Ok(vec![record1, record3]));
+		assert_eq!(audit_log.query(&H256::from(2)), Ok(vec![record2]));
+		assert_eq!(audit_log.query(&H256::from(3)), Ok(vec![]));
+	}
+}
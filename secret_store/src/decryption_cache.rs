@@ -0,0 +1,175 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use types::all::{Public, ServerKeyId, EncryptedDocumentKeyShadow};
+
+/// Max number of cached decryption results. When cache is full, expired results are removed && then the oldest result is evicted.
+const MAX_CACHED_RESULTS: usize = 4096;
+
+/// Cache of shadow decryption results, computed by this key server.
+/// Every result is encrypted with requester public, so it is only returned to the same requester.
+pub struct DecryptionCache {
+	/// Time during which cached result is valid.
+	ttl: Duration,
+	/// Cached results by (key id, requester).
+	results: Mutex<HashMap<(ServerKeyId, Public), CachedResult>>,
+}
+
+/// Single cached decryption result.
+struct CachedResult {
+	/// Time when result has been cached.
+	cached: Instant,
+	/// Shadow decryption result.
+	result: EncryptedDocumentKeyShadow,
+}
+
+impl DecryptionCache {
+	/// Create new cache with given TTL (in seconds).
+	pub fn new(ttl: u64) -> Self {
+		DecryptionCache {
+			ttl: Duration::from_secs(ttl),
+			results: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Get non-expired result of shadow decryption of given key for given requester.
+	pub fn get(&self, key_id: &ServerKeyId, requester: &Public) -> Option<EncryptedDocumentKeyShadow> {
+		let mut results = self.results.lock();
+		let cache_key = (key_id.clone(), requester.clone());
+		let is_expired = match results.get(&cache_key) {
+			Some(cached) => cached.cached.elapsed() > self.ttl,
+			None => return None,
+		};
+
+		if is_expired {
+			results.remove(&cache_key);
+			return None;
+		}
+
+		results.get(&cache_key).map(|cached| cached.result.clone())
+	}
+
+	/// Cache result of shadow decryption of given key for given requester.
+	pub fn insert(&self, key_id: ServerKeyId, requester: Public, result: EncryptedDocumentKeyShadow) {
+		let mut results = self.results.lock();
+		if results.len() >= MAX_CACHED_RESULTS {
+			let ttl = self.ttl;
+			results.retain(|_, cached| cached.cached.elapsed() <= ttl);
+		}
+		if results.len() >= MAX_CACHED_RESULTS {
+			let oldest = results.iter()
+				.min_by_key(|&(_, cached)| cached.cached)
+				.map(|(cache_key, _)| cache_key.clone());
+			if let Some(oldest) = oldest {
+				results.remove(&oldest);
+			}
+		}
+
+		results.insert((key_id, requester), CachedResult {
+			cached: Instant::now(),
+			result: result,
+		});
+	}
+
+	/// Remove cached result of shadow decryption of given key for given requester.
+	pub fn remove(&self, key_id: &ServerKeyId, requester: &Public) {
+		self.results.lock().remove(&(key_id.clone(), requester.clone()));
+	}
+
+	/// Remove cached results of shadow decryption of given key for all requesters.
+	pub fn remove_key(&self, key_id: &ServerKeyId) {
+		self.results.lock().retain(|&(ref cached_key_id, _), _| cached_key_id != key_id);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ethkey::{Random, Generator};
+	use types::all::{ServerKeyId, EncryptedDocumentKeyShadow};
+	use super::{DecryptionCache, MAX_CACHED_RESULTS};
+
+	fn make_result() -> EncryptedDocumentKeyShadow {
+		EncryptedDocumentKeyShadow {
+			decrypted_secret: Random.generate().unwrap().public().clone(),
+			common_point: Some(Random.generate().unwrap().public().clone()),
+			decrypt_shadows: Some(vec![vec![1, 2, 3]]),
+			encrypted_point: Some(Random.generate().unwrap().public().clone()),
+			decrypt_proofs: None,
+		}
+	}
+
+	#[test]
+	fn cached_result_is_returned_to_the_same_requester_only() {
+		let cache = DecryptionCache::new(60);
+		let key_id: ServerKeyId = 1.into();
+		let requester = Random.generate().unwrap().public().clone();
+		let other_requester = Random.generate().unwrap().public().clone();
+		let result = make_result();
+		cache.insert(key_id.clone(), requester.clone(), result.clone());
+
+		assert_eq!(cache.get(&key_id, &requester), Some(result));
+		assert_eq!(cache.get(&key_id, &other_requester), None);
+		assert_eq!(cache.get(&2.into(), &requester), None);
+	}
+
+	#[test]
+	fn expired_result_is_not_returned() {
+		let cache = DecryptionCache::new(0);
+		let key_id: ServerKeyId = 1.into();
+		let requester = Random.generate().unwrap().public().clone();
+		cache.insert(key_id.clone(), requester.clone(), make_result());
+		::std::thread::sleep(::std::time::Duration::from_millis(10));
+
+		assert_eq!(cache.get(&key_id, &requester), None);
+		assert!(cache.results.lock().is_empty());
+	}
+
+	#[test]
+	fn cached_results_are_removed() {
+		let cache = DecryptionCache::new(60);
+		let key_id: ServerKeyId = 1.into();
+		let requester1 = Random.generate().unwrap().public().clone();
+		let requester2 = Random.generate().unwrap().public().clone();
+		cache.insert(key_id.clone(), requester1.clone(), make_result());
+		cache.insert(key_id.clone(), requester2.clone(), make_result());
+		cache.insert(2.into(), requester1.clone(), make_result());
+
+		cache.remove(&key_id, &requester1);
+		assert_eq!(cache.get(&key_id, &requester1), None);
+		assert!(cache.get(&key_id, &requester2).is_some());
+
+		cache.remove_key(&key_id);
+		assert_eq!(cache.get(&key_id, &requester2), None);
+		assert!(cache.get(&2.into(), &requester1).is_some());
+	}
+
+	#[test]
+	fn oldest_result_is_evicted_when_cache_is_full() {
+		let cache = DecryptionCache::new(60);
+		let requester = Random.generate().unwrap().public().clone();
+		let result = make_result();
+		for i in 0..MAX_CACHED_RESULTS + 1 {
+			cache.insert((i as u64).into(), requester.clone(), result.clone());
+		}
+
+		assert_eq!(cache.results.lock().len(), MAX_CACHED_RESULTS);
+		assert_eq!(cache.get(&0.into(), &requester), None);
+		assert!(cache.get(&(MAX_CACHED_RESULTS as u64).into(), &requester).is_some());
+	}
+}
@@ -14,7 +14,10 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::fs;
+use std::io::{self, Read, Write, BufRead, BufReader};
 use std::sync::Arc;
+use std::collections::BTreeSet;
 use hyper::header;
 use hyper::uri::RequestUri;
 use hyper::method::Method as HttpMethod;
@@ -24,23 +27,87 @@ use hyper::server::{Server as HttpServer, Request as HttpRequest, Response as Ht
 use serde::Serialize;
 use serde_json;
 use url::percent_encoding::percent_decode;
+use bigint::hash::H256;
+use hash::keccak;
 
-use traits::{ServerKeyGenerator, DocumentKeyServer, MessageSigner, KeyServer};
-use serialization::{SerializableEncryptedDocumentKeyShadow, SerializableBytes, SerializablePublic};
-use types::all::{Error, Public, MessageHash, EncryptedMessageSignature, NodeAddress, RequestSignature, ServerKeyId,
-	EncryptedDocumentKey, EncryptedDocumentKeyShadow};
+use traits::KeyServer;
+use http_tls::TlsServer;
+use serialization::{SerializableEncryptedDocumentKeyShadow, SerializableEncryptedDocumentKeyWithEntropy, SerializableReEncryptedDocumentKey, SerializableBytes,
+	SerializablePublic, SerializableAuditRecord, SerializableClusterHealth, SerializableDocumentKeyShadowRequest,
+	SerializableDocumentKeyShadowResult, SerializableDrainReport, SerializableBootstrapReport, SerializablePeerLists, SerializableKeyList, SerializableRemovedKeyInfo,
+	SerializableAttestedServerKeyPublic, SerializableKeyExportRequest, SerializableExportedKeyShare, SerializableKeyExportReport,
+	SerializableKeyBackupReport};
+use types::all::{Error, Public, NodeId, MessageHash, NodeAddress, RequestSignature, ServerKeyId,
+	EncryptedDocumentKey, EncryptedDocumentKeyShadow, EncryptedDocumentKeyWithEntropy, DocumentKeyShadows, ReEncryptedDocumentKey,
+	AuditRecord, ClusterHealth, DrainReport, BootstrapReport, PeerLists, KeyList, RemovedKeyInfo, AttestedServerKeyPublic,
+	ExportedKeyShare, KeyExportReport, KeyBackupReport, ErrorCode, AdminRequestSignature};
+
+/// Max size of batch request body.
+const MAX_BATCH_REQUEST_SIZE: u64 = 256 * 1024;
+/// Max size of key label request body.
+const MAX_KEY_LABEL_REQUEST_SIZE: u64 = 4 * 1024;
+/// Max number of keys, returned by single keys list request.
+const KEYS_PAGE_SIZE: usize = 100;
+/// Name of the header, which holds signature of administrative request.
+const ADMIN_SIGNATURE_HEADER: &'static str = "X-Admin-Signature";
 
 /// Key server http-requests listener. Available requests:
 /// To generate server key:							POST		/shadow/{server_key_id}/{signature}/{threshold}
+/// To derive server key from existing one:		POST		/derive/{parent_server_key_id}/{signature}/{derivation_path}
+/// To get server key public, attested by key holders:	GET		/server/{server_key_id}/{signature}
 /// To store pregenerated encrypted document key: 	POST		/shadow/{server_key_id}/{signature}/{common_point}/{encrypted_key} 
 /// To generate server && document key:				POST		/{server_key_id}/{signature}/{threshold} 
+/// To generate server && document key, using requester entropy:	POST	/{server_key_id}/{signature}/{threshold}/{requester_entropy}
 /// To get document key:							GET			/{server_key_id}/{signature}
 /// To get document key shadow:						GET			/shadow/{server_key_id}/{signature} 
+/// To get multiple document keys shadows:			POST		/shadows (body: [{"key_id": server_key_id, "signature": signature}, ...])
+/// To re-encrypt document key with target key:	GET			/reencrypt/{server_key_id}/{signature}/{target_public}/{target_signature}
 /// To sign message with server key:				GET			/{server_key_id}/{signature}/{message_hash}
+/// To delete server key (and document key):		DELETE		/{server_key_id}/{signature}
+/// To get audit log of server key:					GET			/audit/{server_key_id}/{signature}
+/// To get key server metrics:						GET			/metrics
+/// To get key server health (if enabled):			GET			/health
+/// To start draining key server before shutdown:	POST		/drain (optional body: [target_node_id, ...])
+/// To get progress of key server drain:			GET			/drain/{drain_id}
+/// To cancel key server drain:					DELETE		/drain/{drain_id}
+/// To get peer allow/deny lists:					GET			/peers
+/// To allow connections with peer:				POST		/peers/allow/{node_id}
+/// To deny connections with peer:					POST		/peers/deny/{node_id}
+/// To remove peer from allow/deny lists:			DELETE		/peers/{node_id}
+/// To set (or remove) label of the key:			POST		/label/{server_key_id} (optional body: "label")
+/// To list stored keys:							GET			/keys[/{after_server_key_id}]
+/// To list removed keys, which are not yet purged:	GET			/removed
+/// To restore removed key:						POST		/removed/{server_key_id}
+/// To bootstrap new key server with key shares:	POST		/bootstrap/{node_id}[/{after_server_key_id}]
+/// To export keys to other cluster:				POST		/export/{threshold} (body: {"keys": [server_key_id, ...], "targets": [target_node_id, ...]})
+/// To import key share, exported by other cluster:	POST		/import (body: exported key share)
+/// To backup stored keys to the file:				POST		/backup (body: "backup_file_name")
+/// To restore stored keys from the backup file:	POST		/restore (body: "backup_file_name")
+///
+/// Administrative requests (drain, peers, label, keys, removed, bootstrap, export, import, backup && restore) must carry
+/// the `X-Admin-Signature: {timestamp}:{nonce}:{signature}` header, where `signature` is keccak("{method} {path} {timestamp} {nonce} {body_hash}"),
+/// signed with the key server key. `body_hash` is hex-encoded keccak of the request body (of the empty body if request has no body).
+/// Request is only accepted once && within ADMIN_REQUEST_WINDOW seconds from `timestamp`.
+///
+/// If TLS is configured, listener only accepts https connections. If client CA is also configured, every client must present
+/// the certificate, signed by this CA.
+/// If API keys are configured, every request (except for the health-check request) must carry one of API keys
+/// in the `Authorization: Bearer {api_key}` header. Cross-origin requests are only served for configured origins.
 
-pub struct KeyServerHttpListener<T: KeyServer + 'static> {
+pub struct KeyServerHttpListener {
 	http_server: Option<HttpListening>,
-	handler: Arc<KeyServerSharedHttpHandler<T>>,
+	_handler: Arc<KeyServerSharedHttpHandler>,
+}
+
+/// Access policy of key server http listener.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HttpAccessPolicy {
+	/// API keys, one of which must be presented by the requester. If empty, requests are not authenticated.
+	api_keys: Vec<String>,
+	/// Origins, which are allowed to make cross-origin requests ("*" - any origin). If None, all cross-origin requests are refused.
+	cors: Option<Vec<String>>,
+	/// Allowed values of the Host header (hostname or hostname:port). If None, any Host header is accepted.
+	hosts: Option<Vec<String>>,
 }
 
 /// Parsed http request
@@ -50,101 +117,234 @@ enum Request {
 	Invalid,
 	/// Generate server key.
 	GenerateServerKey(ServerKeyId, RequestSignature, usize),
+	/// Derive server key.
+	DeriveServerKey(ServerKeyId, RequestSignature, H256),
+	/// Request public portion of server key, attested by key holders.
+	GetServerKeyPublic(ServerKeyId, RequestSignature),
 	/// Store document key.
 	StoreDocumentKey(ServerKeyId, RequestSignature, Public, Public),
 	/// Generate encryption key.
 	GenerateDocumentKey(ServerKeyId, RequestSignature, usize),
+	/// Generate encryption key, using requester entropy.
+	GenerateDocumentKeyWithEntropy(ServerKeyId, RequestSignature, usize, Public),
 	/// Request encryption key of given document for given requestor.
 	GetDocumentKey(ServerKeyId, RequestSignature),
 	/// Request shadow of encryption key of given document for given requestor.
 	GetDocumentKeyShadow(ServerKeyId, RequestSignature),
+	/// Request shadows of encryption keys of multiple documents, listed in the request body.
+	GetDocumentKeyShadows,
+	/// Re-encrypt encryption key of given document with target public key.
+	ReEncryptDocumentKey(ServerKeyId, RequestSignature, Public, RequestSignature),
 	/// Sign message.
 	SignMessage(ServerKeyId, RequestSignature, MessageHash),
+	/// Sign message, using ECDSA.
+	SignMessageEcdsa(ServerKeyId, RequestSignature, MessageHash),
+	/// Delete server key.
+	DeleteServerKey(ServerKeyId, RequestSignature),
+	/// Request audit log of given key.
+	GetKeyAuditLog(ServerKeyId, RequestSignature),
+	/// Request key server metrics.
+	GetMetrics,
+	/// Request key server health.
+	GetHealth,
+	/// Start draining key server, moving its key shares to targets, listed in the request body.
+	Drain,
+	/// Request progress of key server drain.
+	GetDrainReport(u64),
+	/// Cancel key server drain.
+	CancelDrain(u64),
+	/// Request peer allow/deny lists.
+	GetPeerLists,
+	/// Allow connections with given peer.
+	AllowPeer(NodeId),
+	/// Deny connections with given peer.
+	DenyPeer(NodeId),
+	/// Remove given peer from allow/deny lists.
+	ForgetPeer(NodeId),
+	/// Set label of given key to the label from the request body.
+	SetKeyLabel(ServerKeyId),
+	/// List stored keys, starting after given key.
+	ListKeys(Option<ServerKeyId>),
+	/// List removed keys, which are not yet purged.
+	ListRemovedKeys,
+	/// Restore removed key.
+	RestoreRemovedKey(ServerKeyId),
+	/// Move key shares to the new key server, starting after given key.
+	BootstrapNode(NodeId, Option<ServerKeyId>),
+	/// Export keys, listed in the request body, to nodes of other cluster with given threshold.
+	ExportKeys(usize),
+	/// Import key share from the request body.
+	ImportKeyShare,
+	/// Backup stored keys to the file, which name is in the request body.
+	BackupKeys,
+	/// Restore stored keys from the backup file, which name is in the request body.
+	RestoreKeys,
 }
 
 /// Cloneable http handler
-struct KeyServerHttpHandler<T: KeyServer + 'static> {
-	handler: Arc<KeyServerSharedHttpHandler<T>>,
+struct KeyServerHttpHandler {
+	handler: Arc<KeyServerSharedHttpHandler>,
 }
 
 /// Shared http handler
-struct KeyServerSharedHttpHandler<T: KeyServer + 'static> {
-	key_server: T,
+struct KeyServerSharedHttpHandler {
+	key_server: Arc<KeyServer>,
+	/// Is health-check endpoint enabled.
+	health_check_enabled: bool,
+	/// Access policy.
+	access_policy: HttpAccessPolicy,
 }
 
-impl<T> KeyServerHttpListener<T> where T: KeyServer + 'static {
+impl KeyServerHttpListener {
 	/// Start KeyServer http listener
-	pub fn start(listener_address: Option<NodeAddress>, key_server: T) -> Result<Self, Error> {
+	pub fn start(listener_address: Option<NodeAddress>, health_check_enabled: bool, access_policy: HttpAccessPolicy, tls_server: Option<TlsServer>, key_server: Arc<KeyServer>) -> Result<Self, Error> {
 		let shared_handler = Arc::new(KeyServerSharedHttpHandler {
 			key_server: key_server,
+			health_check_enabled: health_check_enabled,
+			access_policy: access_policy,
 		});
 
 		let http_server = listener_address
 			.map(|listener_address| format!("{}:{}", listener_address.address, listener_address.port))
-			.map(|listener_address| HttpServer::http(&listener_address).expect("cannot start HttpServer"))
-			.map(|http_server| http_server.handle(KeyServerHttpHandler {
-				handler: shared_handler.clone(),
-			}).expect("cannot start HttpServer"));
+			.map(|listener_address| {
+				let handler = KeyServerHttpHandler {
+					handler: shared_handler.clone(),
+				};
+				match tls_server {
+					Some(tls_server) => HttpServer::https(&listener_address, tls_server).expect("cannot start HttpServer")
+						.handle(handler).expect("cannot start HttpServer"),
+					None => HttpServer::http(&listener_address).expect("cannot start HttpServer")
+						.handle(handler).expect("cannot start HttpServer"),
+				}
+			});
 
 		let listener = KeyServerHttpListener {
 			http_server: http_server,
-			handler: shared_handler,
+			_handler: shared_handler,
 		};
 		Ok(listener)
 	}
 }
 
-impl<T> KeyServer for KeyServerHttpListener<T> where T: KeyServer + 'static {}
+impl HttpAccessPolicy {
+	/// Create access policy. API keys are read from given file, one key per line. Empty lines are ignored.
+	pub fn new(api_keys_path: Option<&str>, cors: Option<Vec<String>>, hosts: Option<Vec<String>>) -> Result<Self, Error> {
+		let api_keys = match api_keys_path {
+			Some(api_keys_path) => {
+				let file = fs::File::open(api_keys_path).map_err(|e| Error::Internal(format!("{}", e)))?;
+				let api_keys = BufReader::new(file).lines()
+					.collect::<Result<Vec<_>, _>>()
+					.map_err(|e| Error::Internal(format!("{}", e)))?
+					.into_iter()
+					.map(|api_key| api_key.trim().to_owned())
+					.filter(|api_key| !api_key.is_empty())
+					.collect::<Vec<_>>();
+				if api_keys.is_empty() {
+					return Err(Error::Internal(format!("no API keys found in {}", api_keys_path)));
+				}
+				api_keys
+			},
+			None => Vec::new(),
+		};
 
-impl<T> ServerKeyGenerator for KeyServerHttpListener<T> where T: KeyServer + 'static {
-	fn generate_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<Public, Error> {
-		self.handler.key_server.generate_key(key_id, signature, threshold)
+		Ok(HttpAccessPolicy {
+			api_keys: api_keys,
+			cors: cors,
+			hosts: hosts,
+		})
 	}
-}
 
-impl<T> DocumentKeyServer for KeyServerHttpListener<T> where T: KeyServer + 'static {
-	fn store_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, common_point: Public, encrypted_document_key: Public) -> Result<(), Error> {
-		self.handler.key_server.store_document_key(key_id, signature, common_point, encrypted_document_key)
-	}
+	/// Check if request with given Host header is allowed.
+	fn is_host_allowed(&self, host: Option<&header::Host>) -> bool {
+		let hosts = match self.hosts.as_ref() {
+			Some(hosts) => hosts,
+			None => return true,
+		};
 
-	fn generate_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<EncryptedDocumentKey, Error> {
-		self.handler.key_server.generate_document_key(key_id, signature, threshold)
+		match host {
+			Some(host) => hosts.iter().any(|allowed| *allowed == host.hostname
+				|| host.port.map(|port| *allowed == format!("{}:{}", host.hostname, port)).unwrap_or(false)),
+			None => false,
+		}
 	}
 
-	fn restore_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<EncryptedDocumentKey, Error> {
-		self.handler.key_server.restore_document_key(key_id, signature)
+	/// Check if cross-origin request from given origin is allowed.
+	fn is_origin_allowed(&self, origin: &str) -> bool {
+		self.cors.as_ref()
+			.map(|cors| cors.iter().any(|allowed| allowed == "*" || allowed == origin))
+			.unwrap_or(false)
 	}
 
-	fn restore_document_key_shadow(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<EncryptedDocumentKeyShadow, Error> {
-		self.handler.key_server.restore_document_key_shadow(key_id, signature)
+	/// Check if request with given Authorization header is authenticated.
+	fn is_authenticated(&self, authorization: Option<&header::Authorization<header::Bearer>>) -> bool {
+		if self.api_keys.is_empty() {
+			return true;
+		}
+
+		// every key is compared in constant time, so that the valid key could not be guessed by measuring response time
+		let token = match authorization {
+			Some(authorization) => authorization.token.as_bytes(),
+			None => return false,
+		};
+		self.api_keys.iter().fold(false, |is_authenticated, api_key| constant_time_eq(api_key.as_bytes(), token) | is_authenticated)
 	}
 }
 
-impl <T> MessageSigner for KeyServerHttpListener<T> where T: KeyServer + 'static {
-	fn sign_message(&self, key_id: &ServerKeyId, signature: &RequestSignature, message: MessageHash) -> Result<EncryptedMessageSignature, Error> {
-		self.handler.key_server.sign_message(key_id, signature, message)
+/// Compare two byte slices in the time, which only depends on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	if a.len() != b.len() {
+		return false;
 	}
+
+	a.iter().zip(b.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
 }
 
-impl<T> Drop for KeyServerHttpListener<T> where T: KeyServer + 'static {
+impl Drop for KeyServerHttpListener {
 	fn drop(&mut self) {
 		// ignore error as we are dropping anyway
 		self.http_server.take().map(|mut s| { let _ = s.close(); });
 	}
 }
 
-impl<T> HttpHandler for KeyServerHttpHandler<T> where T: KeyServer + 'static {
+impl HttpHandler for KeyServerHttpHandler {
 	fn handle(&self, req: HttpRequest, mut res: HttpResponse) {
-		if req.headers.has::<header::Origin>() {
-			warn!(target: "secretstore", "Ignoring {}-request {} with Origin header", req.method, req.uri);
-			*res.status_mut() = HttpStatusCode::NotFound;
+		let access_policy = &self.handler.access_policy;
+		if !access_policy.is_host_allowed(req.headers.get::<header::Host>()) {
+			warn!(target: "secretstore", "Ignoring {}-request {} with not allowed Host header", req.method, req.uri);
+			*res.status_mut() = HttpStatusCode::Forbidden;
 			return;
 		}
 
+		if let Some(origin) = req.headers.get::<header::Origin>().map(|origin| format!("{}", origin)) {
+			if !access_policy.is_origin_allowed(&origin) {
+				warn!(target: "secretstore", "Ignoring {}-request {} with Origin header", req.method, req.uri);
+				*res.status_mut() = HttpStatusCode::NotFound;
+				return;
+			}
+
+			res.headers_mut().set(header::AccessControlAllowOrigin::Value(origin));
+			if req.method == HttpMethod::Options {
+				// answer to CORS preflight request
+				res.headers_mut().set(header::AccessControlAllowMethods(vec![HttpMethod::Get, HttpMethod::Post, HttpMethod::Delete]));
+				res.headers_mut().set_raw("Access-Control-Allow-Headers", vec![b"Authorization, Content-Type, X-Admin-Signature".to_vec()]);
+				return;
+			}
+		}
+
+		let is_authenticated = access_policy.is_authenticated(req.headers.get::<header::Authorization<header::Bearer>>());
 		let req_method = req.method.clone();
 		let req_uri = req.uri.clone();
+		let admin_signature_header = req.headers.get_raw(ADMIN_SIGNATURE_HEADER).map(|values| values.to_vec());
+		let admin_signature = |body: &[u8]| match &req_uri {
+			&RequestUri::AbsolutePath(ref path) => read_admin_signature(&req_method, path, admin_signature_header.as_ref().map(|values| &values[..]), body),
+			_ => Err(Error::BadSignature),
+		};
 		match &req_uri {
 			&RequestUri::AbsolutePath(ref path) => match parse_request(&req_method, &path) {
+				ref request if !is_authenticated && *request != Request::GetHealth => {
+					warn!(target: "secretstore", "Ignoring unauthenticated {}-request {}", req_method, req_uri);
+					*res.status_mut() = HttpStatusCode::Unauthorized;
+				},
 				Request::GenerateServerKey(document, signature, threshold) => {
 					return_server_public_key(req, res, self.handler.key_server.generate_key(&document, &signature, threshold)
 						.map_err(|err| {
@@ -152,6 +352,13 @@ impl<T> HttpHandler for KeyServerHttpHandler<T> where T: KeyServer + 'static {
 							err
 						}));
 				},
+				Request::DeriveServerKey(document, signature, derivation_path) => {
+					return_server_public_key(req, res, self.handler.key_server.derive_key(&document, &signature, &derivation_path)
+						.map_err(|err| {
+							warn!(target: "secretstore", "DeriveServerKey request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
 				Request::StoreDocumentKey(document, signature, common_point, encrypted_document_key) => {
 					return_empty(req, res, self.handler.key_server.store_document_key(&document, &signature, common_point, encrypted_document_key)
 						.map_err(|err| {
@@ -166,6 +373,13 @@ impl<T> HttpHandler for KeyServerHttpHandler<T> where T: KeyServer + 'static {
 							err
 						}));
 				},
+				Request::GenerateDocumentKeyWithEntropy(document, signature, threshold, requester_entropy) => {
+					return_document_key_with_entropy(req, res, self.handler.key_server.generate_document_key_with_entropy(&document, &signature, threshold, requester_entropy)
+						.map_err(|err| {
+							warn!(target: "secretstore", "GenerateDocumentKeyWithEntropy request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
 				Request::GetDocumentKey(document, signature) => {
 					return_document_key(req, res, self.handler.key_server.restore_document_key(&document, &signature)
 						.map_err(|err| {
@@ -180,6 +394,23 @@ impl<T> HttpHandler for KeyServerHttpHandler<T> where T: KeyServer + 'static {
 							err
 						}));
 				},
+				Request::GetDocumentKeyShadows => {
+					let mut req = req;
+					let document_key_shadows = read_document_key_shadow_requests(&mut req)
+						.and_then(|requests| self.handler.key_server.restore_document_key_shadows(requests))
+						.map_err(|err| {
+							warn!(target: "secretstore", "GetDocumentKeyShadows request {} has failed with: {}", req_uri, err);
+							err
+						});
+					return_document_key_shadows(req, res, document_key_shadows);
+				},
+				Request::ReEncryptDocumentKey(document, signature, target_public, target_signature) => {
+					return_reencrypted_document_key(req, res, self.handler.key_server.reencrypt_document_key(&document, &signature, &target_public, &target_signature)
+						.map_err(|err| {
+							warn!(target: "secretstore", "ReEncryptDocumentKey request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
 				Request::SignMessage(document, signature, message_hash) => {
 					return_message_signature(req, res, self.handler.key_server.sign_message(&document, &signature, message_hash)
 						.map_err(|err| {
@@ -187,6 +418,194 @@ impl<T> HttpHandler for KeyServerHttpHandler<T> where T: KeyServer + 'static {
 							err
 						}));
 				},
+				Request::SignMessageEcdsa(document, signature, message_hash) => {
+					return_message_signature(req, res, self.handler.key_server.sign_message_ecdsa(&document, &signature, message_hash)
+						.map_err(|err| {
+							warn!(target: "secretstore", "SignMessageEcdsa request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::DeleteServerKey(document, signature) => {
+					return_failed_nodes(req, res, self.handler.key_server.delete_key(&document, &signature)
+						.map_err(|err| {
+							warn!(target: "secretstore", "DeleteServerKey request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::GetServerKeyPublic(document, signature) => {
+					return_attested_server_public_key(req, res, self.handler.key_server.restore_key_public(&document, &signature)
+						.map_err(|err| {
+							warn!(target: "secretstore", "GetServerKeyPublic request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::GetKeyAuditLog(document, signature) => {
+					return_audit_log(req, res, self.handler.key_server.key_audit_log(&document, &signature)
+						.map_err(|err| {
+							warn!(target: "secretstore", "GetKeyAuditLog request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::GetMetrics => {
+					return_metrics(req, res, self.handler.key_server.metrics()
+						.map_err(|err| {
+							warn!(target: "secretstore", "GetMetrics request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::GetHealth if !self.handler.health_check_enabled => {
+					warn!(target: "secretstore", "Ignoring {}-request {}: health-check is disabled", req_method, req_uri);
+					*res.status_mut() = HttpStatusCode::NotFound;
+				},
+				Request::GetHealth => {
+					return_health(req, res, self.handler.key_server.health()
+						.map_err(|err| {
+							warn!(target: "secretstore", "GetHealth request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::Drain => {
+					let mut req = req;
+					let drain_report = read_admin_request_body(&mut req)
+						.and_then(|body| admin_signature(&body)
+							.and_then(|signature| read_drain_targets(&body[..])
+								.and_then(|targets| self.handler.key_server.drain(&signature, targets))))
+						.map_err(|err| {
+							warn!(target: "secretstore", "Drain request {} has failed with: {}", req_uri, err);
+							err
+						});
+					return_drain_report(req, res, drain_report);
+				},
+				Request::GetDrainReport(id) => {
+					return_drain_report(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.drain_report(&signature, id))
+						.map_err(|err| {
+							warn!(target: "secretstore", "GetDrainReport request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::CancelDrain(id) => {
+					return_drain_report(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.cancel_drain(&signature, id))
+						.map_err(|err| {
+							warn!(target: "secretstore", "CancelDrain request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::GetPeerLists => {
+					return_peer_lists(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.peer_lists(&signature))
+						.map_err(|err| {
+							warn!(target: "secretstore", "GetPeerLists request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::AllowPeer(node) => {
+					return_empty(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.allow_peer(&signature, node))
+						.map_err(|err| {
+							warn!(target: "secretstore", "AllowPeer request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::DenyPeer(node) => {
+					return_empty(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.deny_peer(&signature, node))
+						.map_err(|err| {
+							warn!(target: "secretstore", "DenyPeer request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::ForgetPeer(node) => {
+					return_empty(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.forget_peer(&signature, node))
+						.map_err(|err| {
+							warn!(target: "secretstore", "ForgetPeer request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::SetKeyLabel(document) => {
+					let mut req = req;
+					let result = read_admin_request_body(&mut req)
+						.and_then(|body| admin_signature(&body)
+							.and_then(|signature| read_key_label(&body[..])
+								.and_then(|label| self.handler.key_server.set_key_label(&signature, &document, label))))
+						.map_err(|err| {
+							warn!(target: "secretstore", "SetKeyLabel request {} has failed with: {}", req_uri, err);
+							err
+						});
+					return_empty(req, res, result);
+				},
+				Request::ListKeys(after) => {
+					return_key_list(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.list_keys(&signature, after, KEYS_PAGE_SIZE))
+						.map_err(|err| {
+							warn!(target: "secretstore", "ListKeys request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::ListRemovedKeys => {
+					return_removed_keys(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.list_removed_keys(&signature))
+						.map_err(|err| {
+							warn!(target: "secretstore", "ListRemovedKeys request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::RestoreRemovedKey(document) => {
+					return_empty(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.restore_removed_key(&signature, &document))
+						.map_err(|err| {
+							warn!(target: "secretstore", "RestoreRemovedKey request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::BootstrapNode(node, after) => {
+					return_bootstrap_report(req, res, admin_signature(&[]).and_then(|signature| self.handler.key_server.bootstrap_node(&signature, node, after))
+						.map_err(|err| {
+							warn!(target: "secretstore", "BootstrapNode request {} has failed with: {}", req_uri, err);
+							err
+						}));
+				},
+				Request::ExportKeys(threshold) => {
+					let mut req = req;
+					let export_report = read_admin_request_body(&mut req)
+						.and_then(|body| admin_signature(&body)
+							.and_then(|signature| read_key_export_request(&body[..])
+								.and_then(|(keys, targets)| self.handler.key_server.export_keys(&signature, keys, targets, threshold))))
+						.map_err(|err| {
+							warn!(target: "secretstore", "ExportKeys request {} has failed with: {}", req_uri, err);
+							err
+						});
+					return_key_export_report(req, res, export_report);
+				},
+				Request::ImportKeyShare => {
+					let mut req = req;
+					let import_result = read_admin_request_body(&mut req)
+						.and_then(|body| admin_signature(&body)
+							.and_then(|signature| read_exported_key_share(&body[..])
+								.and_then(|key_share| self.handler.key_server.import_key_share(&signature, key_share))))
+						.map_err(|err| {
+							warn!(target: "secretstore", "ImportKeyShare request {} has failed with: {}", req_uri, err);
+							err
+						});
+					return_empty(req, res, import_result);
+				},
+				Request::BackupKeys => {
+					let mut req = req;
+					let backup_report = read_admin_request_body(&mut req)
+						.and_then(|body| admin_signature(&body)
+							.and_then(|signature| read_backup_file_name(&body[..])
+								.and_then(|file_name| self.handler.key_server.backup_keys(&signature, file_name))))
+						.map_err(|err| {
+							warn!(target: "secretstore", "BackupKeys request {} has failed with: {}", req_uri, err);
+							err
+						});
+					return_key_backup_report(req, res, backup_report);
+				},
+				Request::RestoreKeys => {
+					let mut req = req;
+					let restore_report = read_admin_request_body(&mut req)
+						.and_then(|body| admin_signature(&body)
+							.and_then(|signature| read_backup_file_name(&body[..])
+								.and_then(|file_name| self.handler.key_server.restore_keys(&signature, file_name))))
+						.map_err(|err| {
+							warn!(target: "secretstore", "RestoreKeys request {} has failed with: {}", req_uri, err);
+							err
+						});
+					return_key_backup_report(req, res, restore_report);
+				},
 				Request::Invalid => {
 					warn!(target: "secretstore", "Ignoring invalid {}-request {}", req_method, req_uri);
 					*res.status_mut() = HttpStatusCode::BadRequest;
@@ -208,6 +627,10 @@ fn return_server_public_key(req: HttpRequest, res: HttpResponse, server_public:
 	return_bytes(req, res, server_public.map(|k| Some(SerializablePublic(k))))
 }
 
+fn return_attested_server_public_key(req: HttpRequest, res: HttpResponse, server_public: Result<AttestedServerKeyPublic, Error>) {
+	return_bytes(req, res, server_public.map(|k| Some(SerializableAttestedServerKeyPublic::from(k))))
+}
+
 fn return_message_signature(req: HttpRequest, res: HttpResponse, signature: Result<EncryptedDocumentKey, Error>) {
 	return_bytes(req, res, signature.map(|s| Some(SerializableBytes(s))))
 }
@@ -216,14 +639,217 @@ fn return_document_key(req: HttpRequest, res: HttpResponse, document_key: Result
 	return_bytes(req, res, document_key.map(|k| Some(SerializableBytes(k))))
 }
 
+fn return_document_key_with_entropy(req: HttpRequest, res: HttpResponse, document_key: Result<EncryptedDocumentKeyWithEntropy, Error>) {
+	return_bytes(req, res, document_key.map(|k| Some(SerializableEncryptedDocumentKeyWithEntropy {
+		encrypted_key: k.encrypted_key.into(),
+		server_entropy: k.server_entropy.into(),
+	})))
+}
+
 fn return_document_key_shadow(req: HttpRequest, res: HttpResponse, document_key_shadow: Result<EncryptedDocumentKeyShadow, Error>) {
-	return_bytes(req, res, document_key_shadow.map(|k| Some(SerializableEncryptedDocumentKeyShadow {
+	return_bytes(req, res, document_key_shadow.map(|k| Some(serializable_document_key_shadow(k))))
+}
+
+fn return_document_key_shadows(req: HttpRequest, mut res: HttpResponse, document_key_shadows: Result<DocumentKeyShadows, Error>) {
+	let document_key_shadows = match document_key_shadows {
+		Ok(document_key_shadows) => document_key_shadows,
+		Err(err) => return return_error(res, err),
+	};
+
+	// results are written as soon as they are available => response is streamed
+	res.headers_mut().set(header::ContentType::json());
+	let result = res.start()
+		.and_then(|mut res| write_document_key_shadows(&mut res, document_key_shadows).and_then(|_| res.end()));
+	if let Err(err) = result {
+		// nothing to do, but to log an error
+		warn!(target: "secretstore", "response to request {} has failed with: {}", req.uri, err);
+	}
+}
+
+fn serializable_document_key_shadow(k: EncryptedDocumentKeyShadow) -> SerializableEncryptedDocumentKeyShadow {
+	SerializableEncryptedDocumentKeyShadow {
 		decrypted_secret: k.decrypted_secret.into(),
 		common_point: k.common_point.expect("always filled when requesting document_key_shadow; qed").into(),
 		decrypt_shadows: k.decrypt_shadows.expect("always filled when requesting document_key_shadow; qed").into_iter().map(Into::into).collect(),
+		encrypted_point: k.encrypted_point.expect("always filled when requesting document_key_shadow; qed").into(),
+		decrypt_proofs: k.decrypt_proofs.expect("always filled when requesting document_key_shadow; qed").into_iter().map(Into::into).collect(),
+	}
+}
+
+/// Write document keys shadows as JSON array, flushing every item as soon as it is available.
+fn write_document_key_shadows<W: Write>(writer: &mut W, document_key_shadows: DocumentKeyShadows) -> io::Result<()> {
+	writer.write_all(b"[")?;
+	for (index, (key_id, document_key_shadow)) in document_key_shadows.enumerate() {
+		if index != 0 {
+			writer.write_all(b",")?;
+		}
+
+		let (shadow, error) = match document_key_shadow {
+			Ok(document_key_shadow) => (Some(serializable_document_key_shadow(document_key_shadow)), None),
+			Err(err) => (None, Some(format!("{}", err))),
+		};
+		serde_json::to_writer(&mut *writer, &SerializableDocumentKeyShadowResult {
+			key_id: key_id.into(),
+			shadow: shadow,
+			error: error,
+		}).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+		writer.flush()?;
+	}
+	writer.write_all(b"]")
+}
+
+/// Read list of document keys shadows requests from the request body.
+fn read_document_key_shadow_requests<R: Read>(reader: R) -> Result<Vec<(ServerKeyId, RequestSignature)>, Error> {
+	let requests: Vec<SerializableDocumentKeyShadowRequest> = serde_json::from_reader(reader.take(MAX_BATCH_REQUEST_SIZE))
+		.map_err(|err| Error::Serde(format!("{}", err)))?;
+	Ok(requests.into_iter().map(|r| (r.key_id.into(), r.signature.into())).collect())
+}
+
+/// Read signature of administrative request from the `X-Admin-Signature: {timestamp}:{nonce}:{signature}` header.
+/// `body` is the request body, which is also signed by the key server operator.
+fn read_admin_signature(method: &HttpMethod, endpoint: &str, header: Option<&[Vec<u8>]>, body: &[u8]) -> Result<AdminRequestSignature, Error> {
+	let header = match header {
+		Some(values) if values.len() == 1 => String::from_utf8(values[0].clone()).map_err(|_| Error::BadSignature)?,
+		_ => return Err(Error::BadSignature),
+	};
+
+	let parts: Vec<&str> = header.trim().split(':').collect();
+	match (parts.len(), parts.get(0).map(|v| v.parse()), parts.get(1).map(|v| v.parse()), parts.get(2).map(|v| v.parse())) {
+		(3, Some(Ok(timestamp)), Some(Ok(nonce)), Some(Ok(signature))) => Ok(AdminRequestSignature {
+			method: method.to_string(),
+			endpoint: endpoint.into(),
+			timestamp: timestamp,
+			nonce: nonce,
+			body_hash: keccak(body),
+			signature: signature,
+		}),
+		_ => Err(Error::BadSignature),
+	}
+}
+
+/// Read body of administrative request. Body is read before it is parsed, because it is signed by the key server operator.
+fn read_admin_request_body<R: Read>(reader: R) -> Result<Vec<u8>, Error> {
+	let mut body = Vec::new();
+	reader.take(MAX_BATCH_REQUEST_SIZE).read_to_end(&mut body)
+		.map_err(|err| Error::Internal(format!("{}", err)))?;
+	Ok(body)
+}
+
+/// Read drain targets from the request body. Empty body means that key shares must not be moved.
+fn read_drain_targets<R: Read>(reader: R) -> Result<BTreeSet<NodeId>, Error> {
+	let mut body = Vec::new();
+	reader.take(MAX_BATCH_REQUEST_SIZE).read_to_end(&mut body)
+		.map_err(|err| Error::Internal(format!("{}", err)))?;
+	if body.iter().all(|b| (*b as char).is_whitespace()) {
+		return Ok(BTreeSet::new());
+	}
+
+	let targets: Vec<SerializablePublic> = serde_json::from_slice(&body)
+		.map_err(|err| Error::Serde(format!("{}", err)))?;
+	Ok(targets.into_iter().map(Into::into).collect())
+}
+
+/// Read keys to export && target nodes from the request body.
+fn read_key_export_request<R: Read>(reader: R) -> Result<(BTreeSet<ServerKeyId>, BTreeSet<NodeId>), Error> {
+	let request: SerializableKeyExportRequest = serde_json::from_reader(reader.take(MAX_BATCH_REQUEST_SIZE))
+		.map_err(|err| Error::Serde(format!("{}", err)))?;
+	Ok((request.keys.into_iter().map(Into::into).collect(), request.targets.into_iter().map(Into::into).collect()))
+}
+
+/// Read exported key share from the request body.
+fn read_exported_key_share<R: Read>(reader: R) -> Result<ExportedKeyShare, Error> {
+	let key_share: SerializableExportedKeyShare = serde_json::from_reader(reader.take(MAX_BATCH_REQUEST_SIZE))
+		.map_err(|err| Error::Serde(format!("{}", err)))?;
+	Ok(key_share.into())
+}
+
+/// Read name of the backup file (within backup directory of the key server) from the request body.
+fn read_backup_file_name<R: Read>(reader: R) -> Result<String, Error> {
+	serde_json::from_reader(reader.take(MAX_KEY_LABEL_REQUEST_SIZE))
+		.map_err(|err| Error::Serde(format!("{}", err)))
+}
+
+/// Read key label from the request body. Empty body means that label must be removed.
+fn read_key_label<R: Read>(reader: R) -> Result<Option<String>, Error> {
+	let mut body = Vec::new();
+	reader.take(MAX_KEY_LABEL_REQUEST_SIZE).read_to_end(&mut body)
+		.map_err(|err| Error::Internal(format!("{}", err)))?;
+	if body.iter().all(|b| (*b as char).is_whitespace()) {
+		return Ok(None);
+	}
+
+	serde_json::from_slice(&body)
+		.map(Some)
+		.map_err(|err| Error::Serde(format!("{}", err)))
+}
+
+fn return_reencrypted_document_key(req: HttpRequest, res: HttpResponse, document_key: Result<ReEncryptedDocumentKey, Error>) {
+	return_bytes(req, res, document_key.map(|k| Some(SerializableReEncryptedDocumentKey {
+		common_point: k.common_point.into(),
+		encrypted_point: k.encrypted_point.into(),
 	})))
 }
 
+fn return_failed_nodes(req: HttpRequest, res: HttpResponse, failed_nodes: Result<BTreeSet<NodeId>, Error>) {
+	return_bytes(req, res, failed_nodes.map(|nodes| Some(nodes.into_iter().map(SerializablePublic).collect::<Vec<_>>())))
+}
+
+fn return_audit_log(req: HttpRequest, res: HttpResponse, audit_log: Result<Vec<AuditRecord>, Error>) {
+	return_bytes(req, res, audit_log.map(|records| Some(records.into_iter().map(SerializableAuditRecord::from).collect::<Vec<_>>())))
+}
+
+fn return_metrics(req: HttpRequest, mut res: HttpResponse, metrics: Result<String, Error>) {
+	match metrics {
+		Ok(metrics) => {
+			res.headers_mut().set(header::ContentType::plaintext());
+			if let Err(err) = res.send(metrics.as_bytes()) {
+				// nothing to do, but to log an error
+				warn!(target: "secretstore", "response to request {} has failed with: {}", req.uri, err);
+			}
+		},
+		Err(err) => return_error(res, err),
+	}
+}
+
+fn return_health(req: HttpRequest, mut res: HttpResponse, health: Result<ClusterHealth, Error>) {
+	// unhealthy key server is reported with 503, so that it could be taken out of rotation
+	if let Ok(ref health) = health {
+		if !is_healthy(health) {
+			*res.status_mut() = HttpStatusCode::ServiceUnavailable;
+		}
+	}
+
+	return_bytes(req, res, health.map(|h| Some(SerializableClusterHealth::from(h))))
+}
+
+fn return_drain_report(req: HttpRequest, res: HttpResponse, drain_report: Result<DrainReport, Error>) {
+	return_bytes(req, res, drain_report.map(|r| Some(SerializableDrainReport::from(r))))
+}
+
+fn return_bootstrap_report(req: HttpRequest, res: HttpResponse, bootstrap_report: Result<BootstrapReport, Error>) {
+	return_bytes(req, res, bootstrap_report.map(|r| Some(SerializableBootstrapReport::from(r))))
+}
+
+fn return_key_export_report(req: HttpRequest, res: HttpResponse, export_report: Result<KeyExportReport, Error>) {
+	return_bytes(req, res, export_report.map(|r| Some(SerializableKeyExportReport::from(r))))
+}
+
+fn return_key_backup_report(req: HttpRequest, res: HttpResponse, backup_report: Result<KeyBackupReport, Error>) {
+	return_bytes(req, res, backup_report.map(|r| Some(SerializableKeyBackupReport::from(r))))
+}
+
+fn return_peer_lists(req: HttpRequest, res: HttpResponse, peer_lists: Result<PeerLists, Error>) {
+	return_bytes(req, res, peer_lists.map(|l| Some(SerializablePeerLists::from(l))))
+}
+
+fn return_key_list(req: HttpRequest, res: HttpResponse, key_list: Result<KeyList, Error>) {
+	return_bytes(req, res, key_list.map(|l| Some(SerializableKeyList::from(l))))
+}
+
+fn return_removed_keys(req: HttpRequest, res: HttpResponse, removed_keys: Result<Vec<RemovedKeyInfo>, Error>) {
+	return_bytes(req, res, removed_keys.map(|keys| Some(keys.into_iter().map(SerializableRemovedKeyInfo::from).collect::<Vec<_>>())))
+}
+
 fn return_bytes<T: Serialize>(req: HttpRequest, mut res: HttpResponse, result: Result<Option<T>, Error>) {
 	match result {
 		Ok(Some(result)) => match serde_json::to_vec(&result) {
@@ -250,7 +876,15 @@ fn return_error(mut res: HttpResponse, err: Error) {
 		Error::DocumentNotFound => *res.status_mut() = HttpStatusCode::NotFound,
 		Error::Serde(_) => *res.status_mut() = HttpStatusCode::BadRequest,
 		Error::Database(_) => *res.status_mut() = HttpStatusCode::InternalServerError,
+		Error::RateLimited => *res.status_mut() = HttpStatusCode::TooManyRequests,
 		Error::Internal(_) => *res.status_mut() = HttpStatusCode::InternalServerError,
+		Error::Cluster(code, _) => *res.status_mut() = match code {
+			ErrorCode::AccessDenied => HttpStatusCode::Forbidden,
+			ErrorCode::InvalidRequest => HttpStatusCode::BadRequest,
+			ErrorCode::InvalidState | ErrorCode::StaleKey | ErrorCode::Cancelled => HttpStatusCode::Conflict,
+			ErrorCode::Unavailable | ErrorCode::ConsensusUnreachable => HttpStatusCode::ServiceUnavailable,
+			ErrorCode::Unknown | ErrorCode::InvalidMessage | ErrorCode::Storage | ErrorCode::Internal => HttpStatusCode::InternalServerError,
+		},
 	}
 
 	// return error text. ignore errors when returning error
@@ -272,6 +906,141 @@ fn parse_request(method: &HttpMethod, uri_path: &str) -> Request {
 		return Request::Invalid;
 	}
 
+	if &path[0] == "health" {
+		return match (path.len(), method) {
+			(1, &HttpMethod::Get) => Request::GetHealth,
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "metrics" {
+		return match (path.len(), method) {
+			(1, &HttpMethod::Get) => Request::GetMetrics,
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "drain" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse())) {
+			(1, &HttpMethod::Post, _) => Request::Drain,
+			(2, &HttpMethod::Get, Some(Ok(id))) => Request::GetDrainReport(id),
+			(2, &HttpMethod::Delete, Some(Ok(id))) => Request::CancelDrain(id),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "peers" {
+		return match (path.len(), method, path.get(1).map(|v| v.as_str()), path.get(1).map(|v| v.parse()), path.get(2).map(|v| v.parse())) {
+			(1, &HttpMethod::Get, _, _, _) => Request::GetPeerLists,
+			(3, &HttpMethod::Post, Some("allow"), _, Some(Ok(node))) => Request::AllowPeer(node),
+			(3, &HttpMethod::Post, Some("deny"), _, Some(Ok(node))) => Request::DenyPeer(node),
+			(2, &HttpMethod::Delete, _, Some(Ok(node)), _) => Request::ForgetPeer(node),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "keys" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse())) {
+			(1, &HttpMethod::Get, _) => Request::ListKeys(None),
+			(2, &HttpMethod::Get, Some(Ok(after))) => Request::ListKeys(Some(after)),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "removed" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse())) {
+			(1, &HttpMethod::Get, _) => Request::ListRemovedKeys,
+			(2, &HttpMethod::Post, Some(Ok(document))) => Request::RestoreRemovedKey(document),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "bootstrap" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse()), path.get(2).map(|v| v.parse())) {
+			(2, &HttpMethod::Post, Some(Ok(node)), _) => Request::BootstrapNode(node, None),
+			(3, &HttpMethod::Post, Some(Ok(node)), Some(Ok(after))) => Request::BootstrapNode(node, Some(after)),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "export" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse())) {
+			(2, &HttpMethod::Post, Some(Ok(threshold))) => Request::ExportKeys(threshold),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "import" {
+		return match (path.len(), method) {
+			(1, &HttpMethod::Post) => Request::ImportKeyShare,
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "backup" {
+		return match (path.len(), method) {
+			(1, &HttpMethod::Post) => Request::BackupKeys,
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "restore" {
+		return match (path.len(), method) {
+			(1, &HttpMethod::Post) => Request::RestoreKeys,
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "label" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse())) {
+			(2, &HttpMethod::Post, Some(Ok(document))) => Request::SetKeyLabel(document),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "shadows" {
+		return match (path.len(), method) {
+			(1, &HttpMethod::Post) => Request::GetDocumentKeyShadows,
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "audit" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse()), path.get(2).map(|v| v.parse())) {
+			(3, &HttpMethod::Get, Some(Ok(document)), Some(Ok(signature))) => Request::GetKeyAuditLog(document, signature),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "server" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse()), path.get(2).map(|v| v.parse())) {
+			(3, &HttpMethod::Get, Some(Ok(document)), Some(Ok(signature))) => Request::GetServerKeyPublic(document, signature),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "derive" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse()), path.get(2).map(|v| v.parse()), path.get(3).map(|v| v.parse())) {
+			(4, &HttpMethod::Post, Some(Ok(document)), Some(Ok(signature)), Some(Ok(derivation_path))) => Request::DeriveServerKey(document, signature, derivation_path),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "ecdsa" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse()), path.get(2).map(|v| v.parse()), path.get(3).map(|v| v.parse())) {
+			(4, &HttpMethod::Get, Some(Ok(document)), Some(Ok(signature)), Some(Ok(message_hash))) => Request::SignMessageEcdsa(document, signature, message_hash),
+			_ => Request::Invalid,
+		};
+	}
+
+	if &path[0] == "reencrypt" {
+		return match (path.len(), method, path.get(1).map(|v| v.parse()), path.get(2).map(|v| v.parse()), path.get(3).map(|v| v.parse()), path.get(4).map(|v| v.parse())) {
+			(5, &HttpMethod::Get, Some(Ok(document)), Some(Ok(signature)), Some(Ok(target_public)), Some(Ok(target_signature))) =>
+				Request::ReEncryptDocumentKey(document, signature, target_public, target_signature),
+			_ => Request::Invalid,
+		};
+	}
+
 	let (is_shadow_request, args_offset) = if &path[0] == "shadow" { (true, 1) } else { (false, 0) };
 	let args_count = path.len() - args_offset;
 	if args_count < 2 || path[args_offset].is_empty() || path[args_offset + 1].is_empty() {
@@ -298,28 +1067,49 @@ fn parse_request(method: &HttpMethod, uri_path: &str) -> Request {
 			Request::StoreDocumentKey(document, signature, common_point, encrypted_key),
 		(false, 3, &HttpMethod::Post, Some(Ok(threshold)), _, _, _) =>
 			Request::GenerateDocumentKey(document, signature, threshold),
+		(false, 4, &HttpMethod::Post, Some(Ok(threshold)), _, _, Some(Ok(requester_entropy))) =>
+			Request::GenerateDocumentKeyWithEntropy(document, signature, threshold, requester_entropy),
 		(false, 2, &HttpMethod::Get, _, _, _, _) =>
 			Request::GetDocumentKey(document, signature),
 		(true, 2, &HttpMethod::Get, _, _, _, _) =>
 			Request::GetDocumentKeyShadow(document, signature),
 		(false, 3, &HttpMethod::Get, _, Some(Ok(message_hash)), _, _) =>
 			Request::SignMessage(document, signature, message_hash),
+		(false, 2, &HttpMethod::Delete, _, _, _, _) =>
+			Request::DeleteServerKey(document, signature),
 		_ => Request::Invalid,
 	}
 }
 
+/// Key server is healthy if its key storage is available && it is connected to at least one other key server (if any).
+fn is_healthy(health: &ClusterHealth) -> bool {
+	health.is_key_storage_available && (health.peers.is_empty() || health.peers.iter().any(|p| p.is_connected))
+}
+
 #[cfg(test)]
 mod tests {
+	use std::fs;
+	use std::io::Write;
+	use std::sync::Arc;
+	use hyper::header;
 	use hyper::method::Method as HttpMethod;
+	use devtools::RandomTempPath;
+	use serde_json;
+	use ethkey::{Random, Generator};
+	use hash::{keccak, KECCAK_EMPTY};
 	use key_server::tests::DummyKeyServer;
-	use types::all::NodeAddress;
-	use super::{parse_request, Request, KeyServerHttpListener};
+	use serialization::{SerializablePublic, SerializableExportedKeyShare};
+	use types::all::{Error, NodeAddress, ClusterHealth, PeerHealth, ServerKeyId, RequestSignature, EncryptedDocumentKeyShadow,
+		ExportedKeyShare, ExportedShareContribution, AdminRequestSignature};
+	use super::{parse_request, is_healthy, read_document_key_shadow_requests, write_document_key_shadows,
+		read_admin_signature, read_drain_targets, read_key_label, read_key_export_request, read_exported_key_share, read_backup_file_name, Request,
+		KeyServerHttpListener, HttpAccessPolicy};
 
 	#[test]
 	fn http_listener_successfully_drops() {
-		let key_server = DummyKeyServer;
+		let key_server = Arc::new(DummyKeyServer);
 		let address = NodeAddress { address: "127.0.0.1".into(), port: 9000 };
-		let listener = KeyServerHttpListener::start(Some(address), key_server).unwrap();
+		let listener = KeyServerHttpListener::start(Some(address), false, Default::default(), None, key_server).unwrap();
 		drop(listener);
 	}
 
@@ -341,6 +1131,12 @@ mod tests {
 			Request::GenerateDocumentKey("0000000000000000000000000000000000000000000000000000000000000001".into(),
 				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
 				2));
+		// POST		/{server_key_id}/{signature}/{threshold}/{requester_entropy}		=> generate server && document key, using requester entropy
+		assert_eq!(parse_request(&HttpMethod::Post, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/2/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"),
+			Request::GenerateDocumentKeyWithEntropy("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+				2,
+				"b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse().unwrap()));
 		// GET		/{server_key_id}/{signature}										=> get document key
 		assert_eq!(parse_request(&HttpMethod::Get, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"),
 			Request::GetDocumentKey("0000000000000000000000000000000000000000000000000000000000000001".into(),
@@ -352,11 +1148,88 @@ mod tests {
 		assert_eq!(parse_request(&HttpMethod::Get, "/shadow/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"),
 			Request::GetDocumentKeyShadow("0000000000000000000000000000000000000000000000000000000000000001".into(),
 				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap()));
+		// POST		/shadows															=> get multiple document keys shadows
+		assert_eq!(parse_request(&HttpMethod::Post, "/shadows"), Request::GetDocumentKeyShadows);
 		// GET		/{server_key_id}/{signature}/{message_hash}							=> sign message with server key
 		assert_eq!(parse_request(&HttpMethod::Get, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c"),
 			Request::SignMessage("0000000000000000000000000000000000000000000000000000000000000001".into(),
 				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
 				"281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c".parse().unwrap()));
+		// GET		/ecdsa/{server_key_id}/{signature}/{message_hash}					=> sign message with server key, using ECDSA
+		assert_eq!(parse_request(&HttpMethod::Get, "/ecdsa/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c"),
+			Request::SignMessageEcdsa("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+				"281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c".parse().unwrap()));
+		// POST		/derive/{parent_server_key_id}/{signature}/{derivation_path}		=> derive server key
+		assert_eq!(parse_request(&HttpMethod::Post, "/derive/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c"),
+			Request::DeriveServerKey("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+				"281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c".parse().unwrap()));
+		// GET		/reencrypt/{server_key_id}/{signature}/{target_public}/{target_signature}	=> re-encrypt document key with target public key
+		assert_eq!(parse_request(&HttpMethod::Get, "/reencrypt/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8/d378d4c6c9f2e6fc2d9ebd52185e5d6e8f5ec1f3890ac2fe1d836080a4c2a5230670a09a9c2d3bae1bc35a31dd5ed3259d9d5dfd153e36e0ced64e0bea5f2d9f01"),
+			Request::ReEncryptDocumentKey("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+				"b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse().unwrap(),
+				"d378d4c6c9f2e6fc2d9ebd52185e5d6e8f5ec1f3890ac2fe1d836080a4c2a5230670a09a9c2d3bae1bc35a31dd5ed3259d9d5dfd153e36e0ced64e0bea5f2d9f01".parse().unwrap()));
+		// DELETE	/{server_key_id}/{signature}										=> delete server key
+		assert_eq!(parse_request(&HttpMethod::Delete, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"),
+			Request::DeleteServerKey("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap()));
+		// GET		/server/{server_key_id}/{signature}									=> get attested server key public
+		assert_eq!(parse_request(&HttpMethod::Get, "/server/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"),
+			Request::GetServerKeyPublic("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap()));
+		// GET		/audit/{server_key_id}/{signature}									=> get audit log of server key
+		assert_eq!(parse_request(&HttpMethod::Get, "/audit/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"),
+			Request::GetKeyAuditLog("0000000000000000000000000000000000000000000000000000000000000001".into(),
+				"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap()));
+		// GET		/metrics															=> get key server metrics
+		assert_eq!(parse_request(&HttpMethod::Get, "/metrics"), Request::GetMetrics);
+		// GET		/health																=> get key server health
+		assert_eq!(parse_request(&HttpMethod::Get, "/health"), Request::GetHealth);
+		// POST		/drain																=> start draining key server
+		assert_eq!(parse_request(&HttpMethod::Post, "/drain"), Request::Drain);
+		// GET		/drain/{drain_id}													=> get progress of key server drain
+		assert_eq!(parse_request(&HttpMethod::Get, "/drain/3"), Request::GetDrainReport(3));
+		// DELETE	/drain/{drain_id}													=> cancel key server drain
+		assert_eq!(parse_request(&HttpMethod::Delete, "/drain/3"), Request::CancelDrain(3));
+		// GET		/peers																=> get peer allow/deny lists
+		assert_eq!(parse_request(&HttpMethod::Get, "/peers"), Request::GetPeerLists);
+		// POST		/peers/allow/{node_id}												=> allow connections with peer
+		assert_eq!(parse_request(&HttpMethod::Post, "/peers/allow/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"),
+			Request::AllowPeer("b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse().unwrap()));
+		// POST		/peers/deny/{node_id}												=> deny connections with peer
+		assert_eq!(parse_request(&HttpMethod::Post, "/peers/deny/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"),
+			Request::DenyPeer("b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse().unwrap()));
+		// DELETE	/peers/{node_id}													=> remove peer from allow/deny lists
+		assert_eq!(parse_request(&HttpMethod::Delete, "/peers/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"),
+			Request::ForgetPeer("b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse().unwrap()));
+		// GET		/keys/{after_server_key_id}											=> list stored keys
+		assert_eq!(parse_request(&HttpMethod::Get, "/keys"), Request::ListKeys(None));
+		assert_eq!(parse_request(&HttpMethod::Get, "/keys/0000000000000000000000000000000000000000000000000000000000000001"),
+			Request::ListKeys(Some("0000000000000000000000000000000000000000000000000000000000000001".into())));
+		// GET		/removed															=> list removed keys
+		assert_eq!(parse_request(&HttpMethod::Get, "/removed"), Request::ListRemovedKeys);
+		// POST		/removed/{server_key_id}											=> restore removed key
+		assert_eq!(parse_request(&HttpMethod::Post, "/removed/0000000000000000000000000000000000000000000000000000000000000001"),
+			Request::RestoreRemovedKey("0000000000000000000000000000000000000000000000000000000000000001".into()));
+		// POST		/bootstrap/{node_id}/{after_server_key_id}							=> bootstrap new key server
+		assert_eq!(parse_request(&HttpMethod::Post, "/bootstrap/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"),
+			Request::BootstrapNode("b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse().unwrap(), None));
+		assert_eq!(parse_request(&HttpMethod::Post, "/bootstrap/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8/0000000000000000000000000000000000000000000000000000000000000001"),
+			Request::BootstrapNode("b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse().unwrap(),
+				Some("0000000000000000000000000000000000000000000000000000000000000001".into())));
+		// POST		/export/{threshold}													=> export keys to other cluster
+		assert_eq!(parse_request(&HttpMethod::Post, "/export/1"), Request::ExportKeys(1));
+		// POST		/import																=> import exported key share
+		assert_eq!(parse_request(&HttpMethod::Post, "/import"), Request::ImportKeyShare);
+		// POST		/backup																=> backup stored keys
+		assert_eq!(parse_request(&HttpMethod::Post, "/backup"), Request::BackupKeys);
+		// POST		/restore															=> restore stored keys from backup
+		assert_eq!(parse_request(&HttpMethod::Post, "/restore"), Request::RestoreKeys);
+		// POST		/label/{server_key_id}												=> set label of server key
+		assert_eq!(parse_request(&HttpMethod::Post, "/label/0000000000000000000000000000000000000000000000000000000000000001"),
+			Request::SetKeyLabel("0000000000000000000000000000000000000000000000000000000000000001".into()));
 	}
 
 	#[test]
@@ -368,6 +1241,223 @@ mod tests {
 		assert_eq!(parse_request(&HttpMethod::Get, "/0000000000000000000000000000000000000000000000000000000000000001"), Request::Invalid);
 		assert_eq!(parse_request(&HttpMethod::Get, "/0000000000000000000000000000000000000000000000000000000000000001/"), Request::Invalid);
 		assert_eq!(parse_request(&HttpMethod::Get, "/a/b"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/audit/0000000000000000000000000000000000000000000000000000000000000001"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/server/0000000000000000000000000000000000000000000000000000000000000001"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/server/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/metrics"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/ecdsa/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/health"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/drain"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/drain/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/drain/3"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/bootstrap/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/bootstrap"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/removed"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/export/1"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/export"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/import"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/backup"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/restore/1"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/removed/0000000000000000000000000000000000000000000000000000000000000001"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/peers"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/peers/block/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Delete, "/peers/allow/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/keys"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/keys/xxx"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/label/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Delete, "/shadow/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Delete, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/2"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/derive/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/metrics/1"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/shadows"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/shadows/1"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/reencrypt/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8/d378d4c6c9f2e6fc2d9ebd52185e5d6e8f5ec1f3890ac2fe1d836080a4c2a5230670a09a9c2d3bae1bc35a31dd5ed3259d9d5dfd153e36e0ced64e0bea5f2d9f01"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Get, "/reencrypt/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8"), Request::Invalid);
+		assert_eq!(parse_request(&HttpMethod::Post, "/audit/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"), Request::Invalid);
 		assert_eq!(parse_request(&HttpMethod::Get, "/0000000000000000000000000000000000000000000000000000000000000001/a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01/0000000000000000000000000000000000000000000000000000000000000002/0000000000000000000000000000000000000000000000000000000000000002"), Request::Invalid);
 	}
+
+	#[test]
+	fn document_key_shadow_requests_are_read() {
+		let body = r#"[{"key_id":"0x0000000000000000000000000000000000000000000000000000000000000001","signature":"0xa199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01"}]"#;
+		let expected: Vec<(ServerKeyId, RequestSignature)> = vec![(
+			"0000000000000000000000000000000000000000000000000000000000000001".into(),
+			"a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+		)];
+		assert_eq!(read_document_key_shadow_requests(body.as_bytes()).unwrap(), expected);
+		assert!(read_document_key_shadow_requests(&b"[{}]"[..]).is_err());
+	}
+
+	#[test]
+	fn admin_signature_is_read() {
+		let header = vec![b"1500000000:42:a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".to_vec()];
+		assert_eq!(read_admin_signature(&HttpMethod::Post, "/drain", Some(&header[..]), b"[]").unwrap(), AdminRequestSignature {
+			method: "POST".into(),
+			endpoint: "/drain".into(),
+			timestamp: 1500000000,
+			nonce: 42,
+			body_hash: keccak("[]"),
+			signature: "a199fb39e11eefb61c78a4074a53c0d4424600a3e74aad4fb9d93a26c30d067e1d4d29936de0c73f19827394a1dd049480a0d581aee7ae7546968da7d3d1c2fd01".parse().unwrap(),
+		});
+		assert_eq!(read_admin_signature(&HttpMethod::Post, "/drain", Some(&header[..]), b"").unwrap().body_hash, KECCAK_EMPTY);
+		assert_eq!(read_admin_signature(&HttpMethod::Post, "/drain", None, b""), Err(Error::BadSignature));
+		assert_eq!(read_admin_signature(&HttpMethod::Post, "/drain", Some(&[b"1500000000:42".to_vec()][..]), b""), Err(Error::BadSignature));
+		assert_eq!(read_admin_signature(&HttpMethod::Post, "/drain", Some(&[b"now:42:a199".to_vec()][..]), b""), Err(Error::BadSignature));
+		assert_eq!(read_admin_signature(&HttpMethod::Post, "/drain", Some(&[header[0].clone(), header[0].clone()][..]), b""), Err(Error::BadSignature));
+	}
+
+	#[test]
+	fn drain_targets_are_read() {
+		let target = Random.generate().unwrap().public().clone();
+		let body = serde_json::to_string(&vec![SerializablePublic(target.clone())]).unwrap();
+		assert_eq!(read_drain_targets(body.as_bytes()).unwrap(), vec![target].into_iter().collect());
+		assert!(read_drain_targets(&b""[..]).unwrap().is_empty());
+		assert!(read_drain_targets(&b"[1]"[..]).is_err());
+	}
+
+	#[test]
+	fn key_export_request_is_read() {
+		let target = Random.generate().unwrap().public().clone();
+		let body = format!(r#"{{"keys":["0x0000000000000000000000000000000000000000000000000000000000000001"],"targets":[{}]}}"#,
+			serde_json::to_string(&SerializablePublic(target.clone())).unwrap());
+		let (keys, targets) = read_key_export_request(body.as_bytes()).unwrap();
+		assert_eq!(keys, vec!["0000000000000000000000000000000000000000000000000000000000000001".into()].into_iter().collect());
+		assert_eq!(targets, vec![target].into_iter().collect());
+		assert!(read_key_export_request(&b"{}"[..]).is_err());
+	}
+
+	#[test]
+	fn exported_key_share_is_read() {
+		let node = Random.generate().unwrap().public().clone();
+		let key_share = ExportedKeyShare {
+			key_id: 1.into(),
+			node: node.clone(),
+			public: Random.generate().unwrap().public().clone(),
+			author: Random.generate().unwrap().public().clone(),
+			threshold: 0,
+			id_numbers: vec![(node.clone(), 2.into())].into_iter().collect(),
+			common_point: None,
+			encrypted_point: None,
+			contributions: vec![ExportedShareContribution {
+				node: Random.generate().unwrap().public().clone(),
+				commitments: vec![Random.generate().unwrap().public().clone()],
+				encrypted_sub_share: vec![1, 2, 3],
+			}],
+		};
+		let body = serde_json::to_string(&SerializableExportedKeyShare::from(key_share.clone())).unwrap();
+		assert_eq!(read_exported_key_share(body.as_bytes()).unwrap(), key_share);
+		assert!(read_exported_key_share(&b"[1]"[..]).is_err());
+	}
+
+	#[test]
+	fn key_label_is_read() {
+		assert_eq!(read_key_label(&b"\"backup key\""[..]).unwrap(), Some("backup key".into()));
+		assert_eq!(read_key_label(&b" "[..]).unwrap(), None);
+		assert!(read_key_label(&b"[1]"[..]).is_err());
+	}
+
+	#[test]
+	fn backup_file_name_is_read() {
+		assert_eq!(read_backup_file_name(&b"\"keys.backup\""[..]).unwrap(), "keys.backup".to_owned());
+		assert!(read_backup_file_name(&b" "[..]).is_err());
+	}
+
+	#[test]
+	fn access_policy_checks_api_keys() {
+		let bearer = |token: &str| header::Authorization(header::Bearer { token: token.into() });
+
+		let policy = HttpAccessPolicy::default();
+		assert!(policy.is_authenticated(None));
+		assert!(policy.is_authenticated(Some(&bearer("key"))));
+
+		let policy = HttpAccessPolicy { api_keys: vec!["key1".into(), "key2".into()], ..Default::default() };
+		assert!(!policy.is_authenticated(None));
+		assert!(policy.is_authenticated(Some(&bearer("key1"))));
+		assert!(policy.is_authenticated(Some(&bearer("key2"))));
+		assert!(!policy.is_authenticated(Some(&bearer("key"))));
+		assert!(!policy.is_authenticated(Some(&bearer("key3"))));
+	}
+
+	#[test]
+	fn access_policy_reads_api_keys() {
+		let path = RandomTempPath::new();
+		fs::File::create(path.as_path()).unwrap().write_all(b"key1\n\n  key2 \n").unwrap();
+		let policy = HttpAccessPolicy::new(Some(path.as_str()), None, None).unwrap();
+		assert_eq!(policy.api_keys, vec!["key1".to_owned(), "key2".to_owned()]);
+
+		fs::File::create(path.as_path()).unwrap().write_all(b"\n").unwrap();
+		assert!(HttpAccessPolicy::new(Some(path.as_str()), None, None).is_err());
+	}
+
+	#[test]
+	fn access_policy_checks_origin_and_host() {
+		let host = |hostname: &str, port: Option<u16>| header::Host { hostname: hostname.into(), port: port };
+
+		let policy = HttpAccessPolicy::default();
+		assert!(!policy.is_origin_allowed("http://localhost"));
+		assert!(policy.is_host_allowed(None));
+		assert!(policy.is_host_allowed(Some(&host("any", None))));
+
+		let policy = HttpAccessPolicy {
+			cors: Some(vec!["http://localhost".into()]),
+			hosts: Some(vec!["localhost".into(), "127.0.0.1:8082".into()]),
+			..Default::default()
+		};
+		assert!(policy.is_origin_allowed("http://localhost"));
+		assert!(!policy.is_origin_allowed("http://example.com"));
+		assert!(!policy.is_host_allowed(None));
+		assert!(policy.is_host_allowed(Some(&host("localhost", Some(8082)))));
+		assert!(policy.is_host_allowed(Some(&host("127.0.0.1", Some(8082)))));
+		assert!(!policy.is_host_allowed(Some(&host("127.0.0.1", Some(8083)))));
+		assert!(!policy.is_host_allowed(Some(&host("example.com", None))));
+
+		let policy = HttpAccessPolicy { cors: Some(vec!["*".into()]), ..Default::default() };
+		assert!(policy.is_origin_allowed("http://example.com"));
+	}
+
+	#[test]
+	fn document_key_shadows_are_written() {
+		let shadow = EncryptedDocumentKeyShadow {
+			decrypted_secret: Random.generate().unwrap().public().clone(),
+			common_point: Some(Random.generate().unwrap().public().clone()),
+			decrypt_shadows: Some(vec![vec![1, 2, 3]]),
+			encrypted_point: Some(Random.generate().unwrap().public().clone()),
+			decrypt_proofs: Some(vec![vec![4, 5, 6]]),
+		};
+		let shadows: Vec<(ServerKeyId, Result<EncryptedDocumentKeyShadow, Error>)> = vec![
+			(1.into(), Ok(shadow)),
+			(2.into(), Err(Error::DocumentNotFound)),
+		];
+
+		let mut output = Vec::new();
+		write_document_key_shadows(&mut output, Box::new(shadows.into_iter())).unwrap();
+		let output: serde_json::Value = serde_json::from_slice(&output).unwrap();
+		assert_eq!(output.as_array().unwrap().len(), 2);
+		assert!(output[0]["shadow"].is_object());
+		assert!(output[0]["error"].is_null());
+		assert!(output[1]["shadow"].is_null());
+		assert!(output[1]["error"].is_string());
+	}
+
+	#[test]
+	fn health_is_checked() {
+		let peer = PeerHealth {
+			node: Random.generate().unwrap().public().clone(),
+			is_connected: false,
+			last_session: None,
+			clock_skew: None,
+		};
+		let mut health = ClusterHealth {
+			peers: vec![],
+			is_key_storage_available: true,
+			is_externally_reachable: None,
+		};
+		assert!(is_healthy(&health));
+		health.peers.push(peer.clone());
+		assert!(!is_healthy(&health));
+		health.peers.push(PeerHealth { is_connected: true, ..peer });
+		assert!(is_healthy(&health));
+		health.is_key_storage_available = false;
+		assert!(!is_healthy(&health));
+	}
 }
@@ -0,0 +1,161 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, Shutdown};
+use std::sync::Arc;
+use std::time::Duration;
+use hyper;
+use hyper::net::{HttpStream, NetworkStream, SslServer};
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use openssl::ssl::{SslMethod, SslAcceptor, SslAcceptorBuilder, SslStream, SSL_VERIFY_PEER, SSL_VERIFY_FAIL_IF_NO_PEER_CERT};
+use parking_lot::Mutex;
+use types::all::Error;
+
+/// TLS configuration of key server http listener.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpTlsConfiguration {
+	/// Path to the PEM file with server certificate, optionally followed by intermediate certificates.
+	pub certificate: String,
+	/// Path to the PEM file with server private key.
+	pub private_key: String,
+	/// Path to the PEM file with CA certificates, used to verify client certificates.
+	/// If None, client certificates are not requested.
+	pub client_ca: Option<String>,
+}
+
+/// TLS server, accepting connections of key server http listener.
+#[derive(Clone)]
+pub struct TlsServer {
+	/// TLS acceptor.
+	acceptor: Arc<SslAcceptor>,
+}
+
+/// TLS stream of single http connection.
+#[derive(Clone)]
+pub struct TlsStream {
+	/// Stream is shared between hyper reader && writer.
+	stream: Arc<Mutex<SslStream<HttpStream>>>,
+}
+
+impl TlsServer {
+	/// Create TLS server from configuration.
+	pub fn new(config: &HttpTlsConfiguration) -> Result<Self, Error> {
+		let certificates = X509::stack_from_pem(&read_pem_file(&config.certificate)?)
+			.map_err(|e| Error::Internal(format!("invalid certificate in {}: {}", config.certificate, e)))?;
+		let private_key = PKey::private_key_from_pem(&read_pem_file(&config.private_key)?)
+			.map_err(|e| Error::Internal(format!("invalid private key in {}: {}", config.private_key, e)))?;
+		let (certificate, chain) = certificates.split_first()
+			.ok_or_else(|| Error::Internal(format!("no certificates found in {}", config.certificate)))?;
+
+		let mut acceptor = SslAcceptorBuilder::mozilla_intermediate(SslMethod::tls(), &private_key, certificate, chain)
+			.map_err(|e| Error::Internal(format!("{}", e)))?;
+		if let Some(ref client_ca) = config.client_ca {
+			let context = acceptor.builder_mut();
+			context.set_ca_file(client_ca)
+				.map_err(|e| Error::Internal(format!("invalid client CA certificates in {}: {}", client_ca, e)))?;
+			context.set_verify(SSL_VERIFY_PEER | SSL_VERIFY_FAIL_IF_NO_PEER_CERT);
+		}
+
+		Ok(TlsServer {
+			acceptor: Arc::new(acceptor.build()),
+		})
+	}
+}
+
+impl SslServer<HttpStream> for TlsServer {
+	type Stream = TlsStream;
+
+	fn wrap_server(&self, stream: HttpStream) -> hyper::Result<Self::Stream> {
+		self.acceptor.accept(stream)
+			.map(|stream| TlsStream {
+				stream: Arc::new(Mutex::new(stream)),
+			})
+			.map_err(|e| hyper::Error::Ssl(Box::new(io::Error::new(io::ErrorKind::Other, format!("{}", e)))))
+	}
+}
+
+impl Read for TlsStream {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.stream.lock().read(buf)
+	}
+}
+
+impl Write for TlsStream {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.stream.lock().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.stream.lock().flush()
+	}
+}
+
+impl NetworkStream for TlsStream {
+	fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+		self.stream.lock().get_mut().peer_addr()
+	}
+
+	fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+		self.stream.lock().get_ref().set_read_timeout(dur)
+	}
+
+	fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+		self.stream.lock().get_ref().set_write_timeout(dur)
+	}
+
+	fn close(&mut self, how: Shutdown) -> io::Result<()> {
+		self.stream.lock().get_mut().close(how)
+	}
+}
+
+/// Read PEM file contents.
+fn read_pem_file(path: &str) -> Result<Vec<u8>, Error> {
+	let mut contents = Vec::new();
+	fs::File::open(path)
+		.and_then(|mut file| file.read_to_end(&mut contents))
+		.map_err(|e| Error::Internal(format!("cannot read {}: {}", path, e)))?;
+	Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+	use std::io::Write;
+	use devtools::RandomTempPath;
+	use super::{HttpTlsConfiguration, TlsServer};
+
+	#[test]
+	fn tls_server_is_not_created_without_certificate() {
+		let config = HttpTlsConfiguration {
+			certificate: "/non/existing/certificate.pem".into(),
+			private_key: "/non/existing/key.pem".into(),
+			client_ca: None,
+		};
+		assert!(TlsServer::new(&config).is_err());
+
+		let path = RandomTempPath::new();
+		fs::File::create(path.as_path()).unwrap().write_all(b"not a certificate").unwrap();
+		let config = HttpTlsConfiguration {
+			certificate: path.as_str().into(),
+			private_key: path.as_str().into(),
+			client_ca: None,
+		};
+		assert!(TlsServer::new(&config).is_err());
+	}
+}
@@ -14,26 +14,67 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::fs;
+use std::io;
 use std::thread;
+use std::time;
+use std::path::{Path, PathBuf, Component};
 use std::sync::Arc;
+use std::collections::{BTreeSet, BTreeMap, VecDeque};
 use std::sync::mpsc;
 use futures::{self, Future};
 use parking_lot::Mutex;
 use tokio_core::reactor::Core;
 use ethcrypto;
 use ethkey;
+use bigint::hash::H256;
 use super::acl_storage::AclStorage;
-use super::key_storage::KeyStorage;
-use super::key_server_set::KeyServerSet;
-use key_server_cluster::{math, ClusterCore};
-use traits::{ServerKeyGenerator, DocumentKeyServer, MessageSigner, KeyServer, NodeKeyPair};
-use types::all::{Error, Public, RequestSignature, ServerKeyId, EncryptedDocumentKey, EncryptedDocumentKeyShadow,
-	ClusterConfiguration, MessageHash, EncryptedMessageSignature};
+use super::audit_log::{AuditLog, unix_timestamp};
+use super::decryption_cache::DecryptionCache;
+use super::key_storage::{KeyStorage, backup_key_storage, restore_key_storage};
+use super::key_server_set::{KeyServerSet, resolve_node_address};
+use super::peer_filter::PeerFilter;
+use super::nat;
+use key_server_cluster::{self, math, ClusterCore, DecryptionSession, AdminRequest, AdminResponse, SessionJournal, ADMIN_REQUEST_WINDOW,
+	Error as ClusterError};
+use traits::{ServerKeyGenerator, DocumentKeyServer, MessageSigner, AuditLogReader, MetricsReader, AdministrationServer, KeyServer,
+	NodeKeyPair};
+use types::all::{Error, Public, NodeId, RequestSignature, ServerKeyId, EncryptedDocumentKey, EncryptedDocumentKeyShadow,
+	EncryptedDocumentKeyWithEntropy, DocumentKeyShadows, ReEncryptedDocumentKey, ClusterConfiguration, MessageHash, EncryptedMessageSignature, AuditOperation, AuditRecord,
+	ClusterHealth, DrainReport, BootstrapReport, PeerLists, KeyInfo, KeyList, RemovedKeyInfo, AttestedServerKeyPublic, ExportedKeyShare,
+	KeyExportReport, KeyBackupReport, AdminRequestSignature};
 use key_server_cluster::{ClusterClient, ClusterConfiguration as NetClusterConfiguration};
 
+/// Max number of concurrent decryption sessions, started by single batch shadow decryption request.
+const MAX_PIPELINED_DECRYPTION_SESSIONS: usize = 8;
+/// When draining, active sessions are checked every DRAIN_CHECK_INTERVAL milliseconds.
+const DRAIN_CHECK_INTERVAL: u64 = 100;
+/// Max time (in seconds) to wait for in-flight sessions to complete when draining.
+const DRAIN_SESSIONS_TIMEOUT: u64 = 300;
+/// Last access time of the key is only updated if it has been accessed more than LAST_ACCESS_UPDATE_INTERVAL seconds ago.
+const LAST_ACCESS_UPDATE_INTERVAL: u64 = 60;
+
 /// Secret store key server implementation
 pub struct KeyServerImpl {
 	data: Arc<Mutex<KeyServerCore>>,
+	/// This key server key pair.
+	self_key_pair: Arc<NodeKeyPair>,
+	/// ACL storage.
+	acl_storage: Arc<AclStorage>,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Allow/deny lists of cluster connections.
+	peer_filter: Arc<PeerFilter>,
+	/// Audit log. None if audit is disabled.
+	audit_log: Option<Arc<AuditLog>>,
+	/// Cache of shadow decryption results. None if caching is disabled.
+	decryption_cache: Option<DecryptionCache>,
+	/// Progress of the latest drain. None if key server has not been drained yet.
+	drain: Arc<Mutex<Option<DrainReport>>>,
+	/// Signers && nonces of administrative requests, accepted within ADMIN_REQUEST_WINDOW, by request timestamp.
+	admin_nonces: Mutex<BTreeMap<u64, BTreeSet<(NodeId, u64)>>>,
+	/// Directory, where key storage backups are written to && restored from. None if backups are disabled.
+	backup_path: Option<PathBuf>,
 }
 
 /// Secret store key server data.
@@ -45,9 +86,21 @@ pub struct KeyServerCore {
 
 impl KeyServerImpl {
 	/// Create new key server instance
-	pub fn new(config: &ClusterConfiguration, key_server_set: Arc<KeyServerSet>, self_key_pair: Arc<NodeKeyPair>, acl_storage: Arc<AclStorage>, key_storage: Arc<KeyStorage>) -> Result<Self, Error> {
+	pub fn new(config: &ClusterConfiguration, key_server_set: Arc<KeyServerSet>, self_key_pair: Arc<NodeKeyPair>, acl_storage: Arc<AclStorage>, key_storage: Arc<KeyStorage>, peer_filter: Arc<PeerFilter>, audit_log: Option<Arc<AuditLog>>) -> Result<Self, Error> {
 		Ok(KeyServerImpl {
-			data: Arc::new(Mutex::new(KeyServerCore::new(config, key_server_set, self_key_pair, acl_storage, key_storage)?)),
+			data: Arc::new(Mutex::new(KeyServerCore::new(config, key_server_set, self_key_pair.clone(), acl_storage.clone(), key_storage.clone(), peer_filter.clone())?)),
+			self_key_pair: self_key_pair,
+			acl_storage: acl_storage,
+			key_storage: key_storage,
+			peer_filter: peer_filter,
+			audit_log: audit_log,
+			decryption_cache: match config.decryption_cache_ttl {
+				Some(ttl) if ttl != 0 => Some(DecryptionCache::new(ttl)),
+				_ => None,
+			},
+			drain: Arc::new(Mutex::new(None)),
+			admin_nonces: Mutex::new(BTreeMap::new()),
+			backup_path: config.backup_path.as_ref().map(PathBuf::from),
 		})
 	}
 
@@ -56,12 +109,23 @@ impl KeyServerImpl {
 	pub fn cluster(&self) -> Arc<ClusterClient> {
 		self.data.lock().cluster.clone()
 	}
-}
 
-impl KeyServer for KeyServerImpl {}
+	/// Execute operation && write its result to the audit log.
+	fn audited<T, F>(&self, operation: AuditOperation, key_id: &ServerKeyId, signature: &RequestSignature, execute: F) -> Result<T, Error>
+		where F: FnOnce() -> Result<T, Error> {
+		let audit_log = match self.audit_log.as_ref() {
+			Some(audit_log) => audit_log,
+			None => return execute(),
+		};
 
-impl ServerKeyGenerator for KeyServerImpl {
-	fn generate_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<Public, Error> {
+		let started = unix_timestamp();
+		let nodes = self.data.lock().cluster.cluster_state().connected.into_iter().collect();
+		let result = execute();
+		write_audit_record(&**audit_log, operation, key_id, signature, nodes, started, &result);
+		result
+	}
+
+	fn do_generate_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<Public, Error> {
 		// recover requestor' public key from signature
 		let public = ethkey::recover(signature, key_id)
 			.map_err(|_| Error::BadSignature)?;
@@ -70,29 +134,57 @@ impl ServerKeyGenerator for KeyServerImpl {
 		let generation_session = self.data.lock().cluster.new_generation_session(key_id.clone(), public, threshold)?;
 		generation_session.wait(None).map_err(Into::into)
 	}
-}
 
-impl DocumentKeyServer for KeyServerImpl {
-	fn store_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, common_point: Public, encrypted_document_key: Public) -> Result<(), Error> {
+	/// Get cached result of shadow decryption. Cached result is only returned if the requester still has access
+	/// to the key && the document key, stored by this key server, has not been changed since the result has been cached.
+	fn cached_document_key_shadow(&self, key_id: &ServerKeyId, requester: &Public) -> Option<EncryptedDocumentKeyShadow> {
+		let decryption_cache = match self.decryption_cache.as_ref() {
+			Some(decryption_cache) => decryption_cache,
+			None => return None,
+		};
+		let cached_shadow = match decryption_cache.get(key_id, requester) {
+			Some(cached_shadow) => cached_shadow,
+			None => return None,
+		};
+
+		let is_access_granted = self.acl_storage.check(requester, key_id).unwrap_or(false);
+		let is_key_unchanged = self.key_storage.get(key_id)
+			.map(|key_share| key_share.common_point == cached_shadow.common_point && key_share.encrypted_point == cached_shadow.encrypted_point)
+			.unwrap_or(false);
+		if !is_access_granted || !is_key_unchanged {
+			decryption_cache.remove(key_id, requester);
+			return None;
+		}
+
+		Some(cached_shadow)
+	}
+
+	/// Remember that the key has been accessed, if operation has succeeded.
+	fn accessed<T>(&self, key_id: &ServerKeyId, result: Result<T, Error>) -> Result<T, Error> {
+		if result.is_ok() {
+			update_last_accessed(&*self.key_storage, key_id);
+		}
+		result
+	}
+
+	fn do_store_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, common_point: Public, encrypted_document_key: Public) -> Result<(), Error> {
 		// store encrypted key
 		let encryption_session = self.data.lock().cluster.new_encryption_session(key_id.clone(), signature.clone(), common_point, encrypted_document_key)?;
 		encryption_session.wait(None).map_err(Into::into)
 	}
 
-	fn generate_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<EncryptedDocumentKey, Error> {
+	/// Generate server key && store given document key, encrypted with it. Returns document key, encrypted with requester public.
+	fn do_generate_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize, document_key: Public) -> Result<EncryptedDocumentKey, Error> {
 		// recover requestor' public key from signature
 		let public = ethkey::recover(signature, key_id)
 			.map_err(|_| Error::BadSignature)?;
 
 		// generate server key
-		let server_key = self.generate_key(key_id, signature, threshold)?;
+		let server_key = self.do_generate_key(key_id, signature, threshold)?;
 
-		// generate random document key
-		let document_key = math::generate_random_point()?;
+		// encrypt document key with server key && store it in the storage
 		let encrypted_document_key = math::encrypt_secret(&document_key, &server_key)?;
-
-		// store document key in the storage
-		self.store_document_key(key_id, signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point)?;
+		self.do_store_document_key(key_id, signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point)?;
 
 		// encrypt document key with requestor public key
 		let document_key = ethcrypto::ecies::encrypt(&public, &ethcrypto::DEFAULT_MAC, &document_key)
@@ -100,59 +192,714 @@ impl DocumentKeyServer for KeyServerImpl {
 		Ok(document_key)
 	}
 
+	/// Check that request is recently signed by this key server operator && it has not been accepted before.
+	fn check_administrator_signature(&self, signature: &AdminRequestSignature) -> Result<(), Error> {
+		match key_server_cluster::recover_admin_signer(signature, unix_timestamp()) {
+			Some(ref signer) if signer == self.self_key_pair.public() => self.accept_admin_nonce(signer, signature),
+			_ => Err(Error::AccessDenied),
+		}
+	}
+
+	/// Find key server, which operator has recently signed the administrative request. It is either this key server,
+	/// or one of connected key servers. Request is refused if it has been accepted before.
+	fn administrated_key_server(&self, cluster: &ClusterClient, signature: &AdminRequestSignature) -> Result<NodeId, Error> {
+		let signer = key_server_cluster::recover_admin_signer(signature, unix_timestamp())
+			.ok_or(Error::AccessDenied)?;
+		if signer != *self.self_key_pair.public() && !cluster.cluster_state().connected.contains(&signer) {
+			return Err(Error::AccessDenied);
+		}
+
+		self.accept_admin_nonce(&signer, signature)?;
+		Ok(signer)
+	}
+
+	/// Remember nonce of accepted administrative request, so that the same request could not be replayed. Nonces of
+	/// requests, signed before ADMIN_REQUEST_WINDOW, are forgotten, because these requests are refused anyway.
+	fn accept_admin_nonce(&self, signer: &NodeId, signature: &AdminRequestSignature) -> Result<(), Error> {
+		let mut admin_nonces = self.admin_nonces.lock();
+		let window_start = unix_timestamp().saturating_sub(ADMIN_REQUEST_WINDOW);
+		*admin_nonces = admin_nonces.split_off(&window_start);
+		match admin_nonces.entry(signature.timestamp).or_insert_with(BTreeSet::new).insert((signer.clone(), signature.nonce)) {
+			true => Ok(()),
+			false => Err(Error::AccessDenied),
+		}
+	}
+
+	/// Resolve name of the backup file to the path within backup directory. Only plain file names are accepted, so that
+	/// backups are never written to (or read from) the file outside of the backup directory.
+	fn backup_file_path(&self, file_name: &str) -> Result<PathBuf, Error> {
+		let backup_path = self.backup_path.as_ref()
+			.ok_or_else(|| Error::Internal("key storage backups are disabled".into()))?;
+		let mut components = Path::new(file_name).components();
+		match (components.next(), components.next()) {
+			(Some(Component::Normal(name)), None) if name == file_name => (),
+			_ => return Err(Error::AccessDenied),
+		}
+
+		let path = backup_path.join(file_name);
+		match fs::symlink_metadata(&path) {
+			Ok(ref metadata) if metadata.file_type().is_symlink() => Err(Error::AccessDenied),
+			_ => Ok(path),
+		}
+	}
+
+	/// Process administrative request on the master node. Request is forwarded if master node is other key server.
+	fn process_admin_request(&self, cluster: &ClusterClient, master: &NodeId, signature: &AdminRequestSignature, request: AdminRequest) -> Result<AdminResponse, ClusterError> {
+		match master == self.self_key_pair.public() {
+			true => key_server_cluster::process_admin_request(cluster, &*self.key_storage, &*self.self_key_pair, request, || ()),
+			false => cluster.forward_admin_request(master.clone(), signature.clone(), request)?.wait(None),
+		}
+	}
+}
+
+impl KeyServer for KeyServerImpl {}
+
+impl ServerKeyGenerator for KeyServerImpl {
+	fn generate_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<Public, Error> {
+		self.audited(AuditOperation::GenerateServerKey, key_id, signature,
+			|| self.do_generate_key(key_id, signature, threshold))
+	}
+
+	fn derive_key(&self, parent_key_id: &ServerKeyId, signature: &RequestSignature, derivation_path: &H256) -> Result<Public, Error> {
+		let key_id = math::compute_derived_key_id(parent_key_id, derivation_path);
+		self.audited(AuditOperation::DeriveServerKey, &key_id, signature, || {
+			let key_derivation_session = self.data.lock().cluster.new_key_derivation_session(parent_key_id.clone(), signature.clone(), derivation_path.clone())?;
+			key_derivation_session.wait(None).map_err(Into::into)
+		})
+	}
+
+	fn delete_key(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<BTreeSet<NodeId>, Error> {
+		if let Some(decryption_cache) = self.decryption_cache.as_ref() {
+			decryption_cache.remove_key(key_id);
+		}
+
+		self.audited(AuditOperation::DeleteServerKey, key_id, signature, || {
+			let key_deletion_session = self.data.lock().cluster.new_key_deletion_session(key_id.clone(), signature.clone())?;
+			key_deletion_session.wait(None).map_err(Into::into)
+		})
+	}
+
+	fn restore_key_public(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<AttestedServerKeyPublic, Error> {
+		self.audited(AuditOperation::RestoreServerKeyPublic, key_id, signature, || {
+			let server_key_retrieval_session = self.data.lock().cluster.new_server_key_retrieval_session(key_id.clone(), signature.clone())?;
+			server_key_retrieval_session.wait(None).map_err(Into::into)
+		})
+	}
+}
+
+impl DocumentKeyServer for KeyServerImpl {
+	fn store_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, common_point: Public, encrypted_document_key: Public) -> Result<(), Error> {
+		self.audited(AuditOperation::StoreDocumentKey, key_id, signature,
+			|| self.do_store_document_key(key_id, signature, common_point, encrypted_document_key))
+	}
+
+	fn generate_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<EncryptedDocumentKey, Error> {
+		self.audited(AuditOperation::GenerateDocumentKey, key_id, signature, || {
+			// generate random document key
+			let document_key = math::generate_random_point()?;
+			self.do_generate_document_key(key_id, signature, threshold, document_key)
+		})
+	}
+
+	fn generate_document_key_with_entropy(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize, requester_entropy: Public) -> Result<EncryptedDocumentKeyWithEntropy, Error> {
+		self.audited(AuditOperation::GenerateDocumentKey, key_id, signature, || {
+			// mix requester entropy into the document key
+			let (document_key, server_entropy) = math::generate_document_key_with_entropy(&requester_entropy)
+				.map_err(|err| Error::Internal(format!("Invalid requester entropy: {}", err)))?;
+			let encrypted_key = self.do_generate_document_key(key_id, signature, threshold, document_key)?;
+			Ok(EncryptedDocumentKeyWithEntropy {
+				encrypted_key: encrypted_key,
+				server_entropy: server_entropy,
+			})
+		})
+	}
+
 	fn restore_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<EncryptedDocumentKey, Error> {
-		// recover requestor' public key from signature
-		let public = ethkey::recover(signature, key_id)
-			.map_err(|_| Error::BadSignature)?;
+		let result = self.audited(AuditOperation::RestoreDocumentKey, key_id, signature, || {
+			// recover requestor' public key from signature
+			let public = ethkey::recover(signature, key_id)
+				.map_err(|_| Error::BadSignature)?;
 
-		// decrypt document key
-		let decryption_session = self.data.lock().cluster.new_decryption_session(key_id.clone(), signature.clone(), false)?;
-		let document_key = decryption_session.wait()?.decrypted_secret;
+			// decrypt document key
+			let decryption_session = self.data.lock().cluster.new_decryption_session(key_id.clone(), signature.clone(), false)?;
+			let document_key = decryption_session.wait()?.decrypted_secret;
 
-		// encrypt document key with requestor public key
-		let document_key = ethcrypto::ecies::encrypt(&public, &ethcrypto::DEFAULT_MAC, &document_key)
-			.map_err(|err| Error::Internal(format!("Error encrypting document key: {}", err)))?;
-		Ok(document_key)
+			// encrypt document key with requestor public key
+			let document_key = ethcrypto::ecies::encrypt(&public, &ethcrypto::DEFAULT_MAC, &document_key)
+				.map_err(|err| Error::Internal(format!("Error encrypting document key: {}", err)))?;
+			Ok(document_key)
+		});
+		self.accessed(key_id, result)
 	}
 
 	fn restore_document_key_shadow(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<EncryptedDocumentKeyShadow, Error> {
-		let decryption_session = self.data.lock().cluster.new_decryption_session(key_id.clone(), signature.clone(), true)?;
-		decryption_session.wait().map_err(Into::into)
+		let result = self.audited(AuditOperation::RestoreDocumentKeyShadow, key_id, signature, || {
+			// recover requestor' public key from signature
+			let requester = ethkey::recover(signature, key_id)
+				.map_err(|_| Error::BadSignature)?;
+			if let Some(cached_shadow) = self.cached_document_key_shadow(key_id, &requester) {
+				return Ok(cached_shadow);
+			}
+
+			let decryption_session = self.data.lock().cluster.new_decryption_session(key_id.clone(), signature.clone(), true)?;
+			let document_key_shadow = decryption_session.wait()?;
+			if let Some(decryption_cache) = self.decryption_cache.as_ref() {
+				decryption_cache.insert(key_id.clone(), requester, document_key_shadow.clone());
+			}
+			Ok(document_key_shadow)
+		});
+		self.accessed(key_id, result)
+	}
+
+	fn restore_document_key_shadows(&self, requests: Vec<(ServerKeyId, RequestSignature)>) -> Result<DocumentKeyShadows, Error> {
+		// all keys must be requested by the same requester
+		let requesters = requests.iter()
+			.map(|&(ref key_id, ref signature)| ethkey::recover(signature, key_id).map_err(|_| Error::BadSignature))
+			.collect::<Result<BTreeSet<_>, _>>()?;
+		if requesters.len() > 1 {
+			return Err(Error::BadSignature);
+		}
+
+		let mut driver = DocumentKeyShadowsDriver {
+			cluster: self.data.lock().cluster.clone(),
+			key_storage: self.key_storage.clone(),
+			audit_log: self.audit_log.clone(),
+			requests: requests.into_iter().collect(),
+			sessions: VecDeque::new(),
+		};
+		driver.start_sessions();
+		Ok(Box::new(driver))
+	}
+
+	fn reencrypt_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, target_public: &Public, target_signature: &RequestSignature) -> Result<ReEncryptedDocumentKey, Error> {
+		let result = self.audited(AuditOperation::ReEncryptDocumentKey, key_id, signature, || {
+			let reencryption_session = self.data.lock().cluster.new_reencryption_session(key_id.clone(), signature.clone(), target_public.clone(), target_signature.clone())?;
+			reencryption_session.wait().map_err(Into::into)
+		});
+		self.accessed(key_id, result)
 	}
 }
 
 impl MessageSigner for KeyServerImpl {
 	fn sign_message(&self, key_id: &ServerKeyId, signature: &RequestSignature, message: MessageHash) -> Result<EncryptedMessageSignature, Error> {
-		// recover requestor' public key from signature
+		let result = self.audited(AuditOperation::SignMessage, key_id, signature, || {
+			// recover requestor' public key from signature
+			let public = ethkey::recover(signature, key_id)
+				.map_err(|_| Error::BadSignature)?;
+
+			// sign message
+			let signing_session = self.data.lock().cluster.new_signing_session(key_id.clone(), signature.clone(), message)?;
+			let message_signature = signing_session.wait()?;
+
+			// compose two message signature components into single one
+			let mut combined_signature = [0; 64];
+			combined_signature[..32].clone_from_slice(&**message_signature.0);
+			combined_signature[32..].clone_from_slice(&**message_signature.1);
+
+			// encrypt combined signature with requestor public key
+			let message_signature = ethcrypto::ecies::encrypt(&public, &ethcrypto::DEFAULT_MAC, &combined_signature)
+				.map_err(|err| Error::Internal(format!("Error encrypting message signature: {}", err)))?;
+			Ok(message_signature)
+		});
+		self.accessed(key_id, result)
+	}
+
+	fn sign_message_ecdsa(&self, key_id: &ServerKeyId, signature: &RequestSignature, message: MessageHash) -> Result<EncryptedMessageSignature, Error> {
+		let result = self.audited(AuditOperation::SignMessageEcdsa, key_id, signature, || {
+			// recover requestor' public key from signature
+			let public = ethkey::recover(signature, key_id)
+				.map_err(|_| Error::BadSignature)?;
+
+			// sign message
+			let signing_session = self.data.lock().cluster.new_ecdsa_signing_session(key_id.clone(), signature.clone(), message)?;
+			let message_signature = signing_session.wait()?;
+
+			// encrypt signature with requestor public key
+			let message_signature = ethcrypto::ecies::encrypt(&public, &ethcrypto::DEFAULT_MAC, &*message_signature)
+				.map_err(|err| Error::Internal(format!("Error encrypting message signature: {}", err)))?;
+			Ok(message_signature)
+		});
+		self.accessed(key_id, result)
+	}
+}
+
+impl AuditLogReader for KeyServerImpl {
+	fn key_audit_log(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<Vec<AuditRecord>, Error> {
+		let audit_log = self.audit_log.as_ref().ok_or(Error::Internal("audit log is disabled".into()))?;
+
+		// only requesters, which have access to the key, can read its audit log
 		let public = ethkey::recover(signature, key_id)
 			.map_err(|_| Error::BadSignature)?;
+		if !self.acl_storage.check(&public, key_id)? {
+			return Err(Error::AccessDenied);
+		}
+
+		audit_log.query(key_id)
+	}
+}
+
+impl MetricsReader for KeyServerImpl {
+	fn metrics(&self) -> Result<String, Error> {
+		Ok(self.data.lock().cluster.metrics())
+	}
+
+	fn health(&self) -> Result<ClusterHealth, Error> {
+		Ok(self.data.lock().cluster.health())
+	}
+}
+
+impl AdministrationServer for KeyServerImpl {
+	fn drain(&self, signature: &AdminRequestSignature, targets: BTreeSet<NodeId>) -> Result<DrainReport, Error> {
+		// only key server operator is allowed to drain it
+		self.check_administrator_signature(signature)?;
+		let self_public = self.self_key_pair.public().clone();
+		let cluster = self.data.lock().cluster.clone();
+
+		// only single drain could be in progress
+		let report = {
+			let mut drain = self.drain.lock();
+			if drain.as_ref().map(|report| !report.is_completed).unwrap_or(false) {
+				return Err(Error::Internal("drain is already in progress".into()));
+			}
+
+			let report = DrainReport {
+				id: drain.as_ref().map(|report| report.id + 1).unwrap_or(0),
+				..Default::default()
+			};
+			*drain = Some(report.clone());
+
+			// refuse new sessions (under lock, so that the drain could not be cancelled before it is started)
+			cluster.start_draining();
+
+			report
+		};
+
+		// wait until in-flight sessions are completed && move key shares in background
+		let key_storage = self.key_storage.clone();
+		let drain = self.drain.clone();
+		let targets: Vec<_> = targets.into_iter().filter(|t| *t != self_public).collect();
+		thread::spawn(move || run_drain(&self_public, &*cluster, &*key_storage, &drain, targets));
 
-		// sign message
-		let signing_session = self.data.lock().cluster.new_signing_session(key_id.clone(), signature.clone(), message)?;
-		let message_signature = signing_session.wait()?;
+		Ok(report)
+	}
+
+	fn drain_report(&self, signature: &AdminRequestSignature, id: u64) -> Result<DrainReport, Error> {
+		self.check_administrator_signature(signature)?;
+		match *self.drain.lock() {
+			Some(ref report) if report.id == id => Ok(report.clone()),
+			_ => Err(Error::DocumentNotFound),
+		}
+	}
+
+	fn cancel_drain(&self, signature: &AdminRequestSignature, id: u64) -> Result<DrainReport, Error> {
+		self.check_administrator_signature(signature)?;
+		let cluster = self.data.lock().cluster.clone();
+		let mut drain = self.drain.lock();
+		match *drain {
+			Some(ref mut report) if report.id == id => {
+				// running drain stops before moving the next key share
+				report.is_cancelled = true;
+				report.is_safe_to_shutdown = false;
+				cluster.stop_draining();
+				Ok(report.clone())
+			},
+			_ => Err(Error::DocumentNotFound),
+		}
+	}
+
+	fn peer_lists(&self, signature: &AdminRequestSignature) -> Result<PeerLists, Error> {
+		self.check_administrator_signature(signature)?;
+		Ok(self.peer_filter.lists())
+	}
+
+	fn allow_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error> {
+		self.check_administrator_signature(signature)?;
+		self.peer_filter.allow(node)?;
+		// when allow list becomes non-empty, connections with all other nodes are closed
+		self.data.lock().cluster.apply_peer_filter();
+		Ok(())
+	}
+
+	fn deny_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error> {
+		self.check_administrator_signature(signature)?;
+		self.peer_filter.deny(node)?;
+		self.data.lock().cluster.apply_peer_filter();
+		Ok(())
+	}
+
+	fn forget_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error> {
+		self.check_administrator_signature(signature)?;
+		self.peer_filter.forget(&node)?;
+		// forgotten node is not allowed if allow list is still non-empty
+		self.data.lock().cluster.apply_peer_filter();
+		Ok(())
+	}
+
+	fn set_key_label(&self, signature: &AdminRequestSignature, key_id: &ServerKeyId, label: Option<String>) -> Result<(), Error> {
+		self.check_administrator_signature(signature)?;
+		let mut metadata = self.key_storage.get(key_id)?.metadata;
+		metadata.label = label;
+		self.key_storage.set_metadata(key_id, metadata)
+	}
+
+	fn list_keys(&self, signature: &AdminRequestSignature, after: Option<ServerKeyId>, limit: usize) -> Result<KeyList, Error> {
+		self.check_administrator_signature(signature)?;
+		if limit == 0 {
+			return Err(Error::Internal("keys list limit must be positive".into()));
+		}
+
+		let mut keys_iter = self.key_storage.iter(after.as_ref());
+		let keys = keys_iter.by_ref()
+			.take(limit)
+			.map(|key| key.map(|(key_id, key_share)| KeyInfo {
+				key_id: key_id,
+				threshold: key_share.threshold,
+				holders: key_share.id_numbers.keys().cloned().collect(),
+				created: key_share.metadata.created,
+				label: key_share.metadata.label,
+				requester: key_share.metadata.requester,
+				last_accessed: key_share.metadata.last_accessed,
+			}))
+			.collect::<Result<Vec<_>, _>>()?;
+		let next = match keys_iter.next() {
+			Some(_) => keys.last().map(|key| key.key_id.clone()),
+			None => None,
+		};
+
+		Ok(KeyList {
+			keys: keys,
+			next: next,
+		})
+	}
+
+	fn list_removed_keys(&self, signature: &AdminRequestSignature) -> Result<Vec<RemovedKeyInfo>, Error> {
+		self.check_administrator_signature(signature)?;
+		Ok(self.key_storage.removed_documents()?.into_iter()
+			.map(|(key_id, removed)| RemovedKeyInfo {
+				is_deleted: self.key_storage.is_tombstoned(&key_id),
+				key_id: key_id,
+				removed: removed,
+			})
+			.collect())
+	}
+
+	fn restore_removed_key(&self, signature: &AdminRequestSignature, key_id: &ServerKeyId) -> Result<(), Error> {
+		self.check_administrator_signature(signature)?;
+		self.key_storage.restore_removed(key_id)
+	}
+
+	fn bootstrap_node(&self, signature: &AdminRequestSignature, new_node: NodeId, after: Option<ServerKeyId>) -> Result<BootstrapReport, Error> {
+		// request, signed by operator of other key server, is forwarded to that key server
+		let cluster = self.data.lock().cluster.clone();
+		let master = self.administrated_key_server(&*cluster, signature)?;
+
+		// move key shares batch by batch. When batch fails, report is returned with the cursor, so that
+		// the operator could resume bootstrap from the failed batch
+		let mut report = BootstrapReport::default();
+		let mut cursor = after;
+		loop {
+			let request = AdminRequest::ShareBootstrap(new_node.clone(), cursor.clone());
+			let response = self.process_admin_request(&*cluster, &master, signature, request)
+				.and_then(|response| match response {
+					AdminResponse::ShareBootstrap(batch, moved_keys) => Ok((batch, moved_keys)),
+					_ => Err(ClusterError::InvalidMessage),
+				});
+			match response {
+				Ok((batch, moved_keys)) => {
+					let last_key_id = match batch.iter().next_back() {
+						Some(last_key_id) => last_key_id.clone(),
+						None => break,
+					};
+
+					report.skipped_keys.extend(batch.difference(&moved_keys).cloned());
+					report.moved_keys.extend(moved_keys);
+					cursor = Some(last_key_id);
+				},
+				Err(err) => {
+					warn!(target: "secretstore", "{}: failed to bootstrap key shares of {} on {}: {}", self.self_key_pair.public(), new_node, master, err);
+					report.error = Some(format!("{}", err));
+					report.next = cursor;
+					return Ok(report);
+				},
+			}
+		}
+
+		Ok(report)
+	}
+
+	fn export_keys(&self, signature: &AdminRequestSignature, keys: BTreeSet<ServerKeyId>, targets: BTreeSet<NodeId>, threshold: usize) -> Result<KeyExportReport, Error> {
+		// request, signed by operator of other key server, is forwarded to that key server
+		let cluster = self.data.lock().cluster.clone();
+		let master = self.administrated_key_server(&*cluster, signature)?;
+
+		// keys are exported one by one, so that single failed key does not stop the whole export
+		let mut report = KeyExportReport::default();
+		for key_id in keys {
+			let request = AdminRequest::KeyExport(key_id.clone(), targets.clone(), threshold);
+			let response = self.process_admin_request(&*cluster, &master, signature, request)
+				.and_then(|response| match response {
+					AdminResponse::KeyExport(key_shares) => Ok(key_shares),
+					_ => Err(ClusterError::InvalidMessage),
+				});
+			match response {
+				Ok(key_shares) => report.key_shares.extend(key_shares),
+				Err(err) => {
+					warn!(target: "secretstore", "{}: failed to export key {}: {}", self.self_key_pair.public(), key_id, err);
+					report.failed_keys.push(key_id);
+				},
+			}
+		}
+
+		Ok(report)
+	}
+
+	fn import_key_share(&self, signature: &AdminRequestSignature, key_share: ExportedKeyShare) -> Result<(), Error> {
+		self.check_administrator_signature(signature)?;
+		key_server_cluster::import_key_share(&*self.self_key_pair, &*self.key_storage, key_share)
+			.map_err(Into::into)
+	}
+
+	fn backup_keys(&self, signature: &AdminRequestSignature, file_name: String) -> Result<KeyBackupReport, Error> {
+		self.check_administrator_signature(signature)?;
+
+		// backup is written to the temporary file first, so that the previous backup is never replaced with partially written one
+		let path = self.backup_file_path(&file_name)?;
+		let temp_path = self.backup_file_path(&format!("{}.tmp", file_name))?;
+		let result = fs::File::create(&temp_path)
+			.map_err(|e| Error::Internal(format!("{}", e)))
+			.and_then(|file| {
+				let mut writer = io::BufWriter::new(file);
+				let report = backup_key_storage(&*self.key_storage, &*self.self_key_pair, &mut writer)?;
+				writer.get_ref().sync_all()
+					.and_then(|_| fs::rename(&temp_path, &path))
+					.map_err(|e| Error::Internal(format!("{}", e)))?;
+				Ok(report)
+			});
+		if result.is_err() {
+			let _ = fs::remove_file(&temp_path);
+		}
+		result
+	}
+
+	fn restore_keys(&self, signature: &AdminRequestSignature, file_name: String) -> Result<KeyBackupReport, Error> {
+		self.check_administrator_signature(signature)?;
+
+		let path = self.backup_file_path(&file_name)?;
+		let file = fs::File::open(&path).map_err(|e| Error::Internal(format!("{}", e)))?;
+		restore_key_storage(&*self.key_storage, &*self.self_key_pair, &mut io::BufReader::new(file))
+	}
+}
+
+/// Batch shadow decryption driver. Keeps up to MAX_PIPELINED_DECRYPTION_SESSIONS decryption sessions running.
+struct DocumentKeyShadowsDriver {
+	/// Cluster client.
+	cluster: Arc<ClusterClient>,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Audit log. None if audit is disabled.
+	audit_log: Option<Arc<AuditLog>>,
+	/// Requests, for which decryption sessions are not yet started.
+	requests: VecDeque<(ServerKeyId, RequestSignature)>,
+	/// Started decryption sessions, in the order of requests.
+	sessions: VecDeque<PipelinedDecryptionSession>,
+}
+
+/// Decryption session, started by batch shadow decryption driver.
+struct PipelinedDecryptionSession {
+	/// Server key id.
+	key_id: ServerKeyId,
+	/// Requester signature.
+	signature: RequestSignature,
+	/// Session start time.
+	started: u64,
+	/// Nodes, connected when session has been started.
+	nodes: Vec<NodeId>,
+	/// Started session or error, returned when starting session.
+	session: Result<Arc<DecryptionSession>, Error>,
+}
+
+impl DocumentKeyShadowsDriver {
+	/// Start decryption sessions for next requests.
+	fn start_sessions(&mut self) {
+		while self.sessions.len() < MAX_PIPELINED_DECRYPTION_SESSIONS {
+			let (key_id, signature) = match self.requests.pop_front() {
+				Some(request) => request,
+				None => break,
+			};
+
+			let nodes = match self.audit_log {
+				Some(_) => self.cluster.cluster_state().connected.into_iter().collect(),
+				None => Vec::new(),
+			};
+			self.sessions.push_back(PipelinedDecryptionSession {
+				started: unix_timestamp(),
+				nodes: nodes,
+				session: self.cluster.new_decryption_session(key_id.clone(), signature.clone(), true).map_err(Into::into),
+				key_id: key_id,
+				signature: signature,
+			});
+		}
+	}
+}
+
+impl Iterator for DocumentKeyShadowsDriver {
+	type Item = (ServerKeyId, Result<EncryptedDocumentKeyShadow, Error>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let session = match self.sessions.pop_front() {
+			Some(session) => session,
+			None => return None,
+		};
+
+		// keep pipeline full while waiting for the oldest session
+		self.start_sessions();
+
+		let result = session.session.and_then(|s| s.wait().map_err(Into::into));
+		if result.is_ok() {
+			update_last_accessed(&*self.key_storage, &session.key_id);
+		}
+		if let Some(audit_log) = self.audit_log.as_ref() {
+			write_audit_record(&**audit_log, AuditOperation::RestoreDocumentKeyShadow, &session.key_id, &session.signature,
+				session.nodes, session.started, &result);
+		}
+		Some((session.key_id, result))
+	}
+}
+
+/// Drain key server: wait until in-flight sessions are completed && move key shares to targets in round-robin order.
+/// Drain report is updated after every moved key share. Drain is stopped as soon as it is cancelled.
+fn run_drain(self_public: &NodeId, cluster: &ClusterClient, key_storage: &KeyStorage, drain: &Mutex<Option<DrainReport>>, targets: Vec<NodeId>) {
+	let wait_started = time::Instant::now();
+	while cluster.active_sessions_count() != 0 && wait_started.elapsed() < time::Duration::from_secs(DRAIN_SESSIONS_TIMEOUT)
+		&& !is_drain_cancelled(drain) {
+		thread::sleep(time::Duration::from_millis(DRAIN_CHECK_INTERVAL));
+	}
+
+	if !targets.is_empty() {
+		match key_storage.documents() {
+			Ok(documents) => {
+				let mut next_target = 0;
+				for key_id in documents {
+					if is_drain_cancelled(drain) {
+						break;
+					}
+
+					match move_key_share(cluster, key_storage, &key_id, &targets, &mut next_target) {
+						Ok(()) => update_drain_report(drain, |report| report.moved_keys.push(key_id)),
+						Err(err) => {
+							warn!(target: "secretstore", "{}: failed to move key share {} when draining: {}", self_public, key_id, err);
+							update_drain_report(drain, |report| report.failed_keys.push(key_id));
+						},
+					}
+				}
+			},
+			Err(err) => {
+				warn!(target: "secretstore", "{}: failed to read key shares when draining: {}", self_public, err);
+				update_drain_report(drain, |report| report.error = Some(format!("{}", err)));
+			},
+		}
+	}
+
+	let has_active_sessions = cluster.active_sessions_count() != 0;
+	update_drain_report(drain, |report| {
+		report.is_completed = true;
+		report.is_safe_to_shutdown = !report.is_cancelled && !has_active_sessions && report.failed_keys.is_empty() && report.error.is_none();
+	});
+}
+
+/// Check if the latest drain has been cancelled.
+fn is_drain_cancelled(drain: &Mutex<Option<DrainReport>>) -> bool {
+	drain.lock().as_ref().map(|report| report.is_cancelled).unwrap_or(false)
+}
+
+/// Update progress of the latest drain.
+fn update_drain_report<F: FnOnce(&mut DrainReport)>(drain: &Mutex<Option<DrainReport>>, update: F) {
+	if let Some(ref mut report) = *drain.lock() {
+		update(report);
+	}
+}
+
+/// Move key share of this key server to the next target (in round-robin order), which is not yet holding the key.
+fn move_key_share(cluster: &ClusterClient, key_storage: &KeyStorage, key_id: &ServerKeyId, targets: &[NodeId], next_target: &mut usize) -> Result<(), ClusterError> {
+	let key_share = key_storage.get(key_id).map_err(|e| ClusterError::KeyStorage(e.into()))?;
+	let target = (0..targets.len())
+		.map(|i| (*next_target + i) % targets.len())
+		.find(|i| !key_share.id_numbers.contains_key(&targets[*i]))
+		.ok_or(ClusterError::InvalidNodesConfiguration)?;
+	*next_target = (target + 1) % targets.len();
+
+	let share_move_session = cluster.new_share_move_session(key_id.clone(), targets[target].clone())?;
+	share_move_session.wait(None)
+}
+
+/// Update last access time of the key, stored by this key server.
+fn update_last_accessed(key_storage: &KeyStorage, key_id: &ServerKeyId) {
+	// key share could be missing on this node => nothing to update
+	let mut metadata = match key_storage.get(key_id) {
+		Ok(key_share) => key_share.metadata,
+		Err(_) => return,
+	};
 
-		// compose two message signature components into single one
-		let mut combined_signature = [0; 64];
-		combined_signature[..32].clone_from_slice(&**message_signature.0);
-		combined_signature[32..].clone_from_slice(&**message_signature.1);
+	let now = unix_timestamp();
+	if metadata.last_accessed.map(|last_accessed| last_accessed + LAST_ACCESS_UPDATE_INTERVAL > now).unwrap_or(false) {
+		return;
+	}
+
+	metadata.last_accessed = Some(now);
+	if let Err(err) = key_storage.set_metadata(key_id, metadata) {
+		warn!(target: "secretstore", "failed to update last access time of {}: {}", key_id, err);
+	}
+}
 
-		// encrypt combined signature with requestor public key
-		let message_signature = ethcrypto::ecies::encrypt(&public, &ethcrypto::DEFAULT_MAC, &combined_signature)
-			.map_err(|err| Error::Internal(format!("Error encrypting message signature: {}", err)))?;
-		Ok(message_signature)
+/// Write result of operation to the audit log.
+fn write_audit_record<T>(audit_log: &AuditLog, operation: AuditOperation, key_id: &ServerKeyId, signature: &RequestSignature, nodes: Vec<NodeId>, started: u64, result: &Result<T, Error>) {
+	let record = AuditRecord {
+		operation: operation,
+		key_id: key_id.clone(),
+		requester: ethkey::recover(signature, key_id).ok(),
+		nodes: nodes,
+		started: started,
+		finished: unix_timestamp(),
+		error: result.as_ref().err().map(|err| format!("{}", err)),
+	};
+	if let Err(err) = audit_log.append(record) {
+		warn!(target: "secretstore", "failed to write {:?} of {} to audit log: {}", operation, key_id, err);
 	}
 }
 
 impl KeyServerCore {
-	pub fn new(config: &ClusterConfiguration, key_server_set: Arc<KeyServerSet>, self_key_pair: Arc<NodeKeyPair>, acl_storage: Arc<AclStorage>, key_storage: Arc<KeyStorage>) -> Result<Self, Error> {
+	pub fn new(config: &ClusterConfiguration, key_server_set: Arc<KeyServerSet>, self_key_pair: Arc<NodeKeyPair>, acl_storage: Arc<AclStorage>, key_storage: Arc<KeyStorage>, peer_filter: Arc<PeerFilter>) -> Result<Self, Error> {
+		let external_address = match config.external_address {
+			Some(ref external_address) => Some(resolve_node_address(&external_address.address, external_address.port)?),
+			None if config.upnp_enabled => {
+				let listener_address = resolve_node_address(&config.listener_address.address, config.listener_address.port)?;
+				nat::map_external_address(&listener_address)
+			},
+			None => None,
+		};
+
 		let config = NetClusterConfiguration {
 			threads: config.threads,
 			self_key_pair: self_key_pair,
 			listen_address: (config.listener_address.address.clone(), config.listener_address.port),
+			external_address: external_address,
+			admin_messages_share: config.admin_messages_share,
+			removed_keys_retention: config.removed_keys_retention,
+			max_session_traffic: config.max_session_traffic,
+			session_journal: match config.session_journal {
+				Some(ref path) => Some(Arc::new(SessionJournal::new(path)?)),
+				None => None,
+			},
 			key_server_set: key_server_set,
 			allow_connecting_to_higher_nodes: config.allow_connecting_to_higher_nodes,
+			rate_limits: config.rate_limits.clone(),
+			peer_rate_limits: config.peer_rate_limits.clone(),
+			deterministic_nodes_selection: config.deterministic_nodes_selection,
+			read_only_nodes: config.read_only_nodes.iter().cloned().collect(),
+			timeouts: config.timeouts.clone(),
 			acl_storage: acl_storage,
 			key_storage: key_storage,
+			peer_filter: peer_filter,
 		};
 
 		let (stop, stopped) = futures::oneshot();
@@ -190,21 +937,31 @@ impl Drop for KeyServerCore {
 
 #[cfg(test)]
 pub mod tests {
+	use std::fs;
+	use std::thread;
 	use std::time;
 	use std::sync::Arc;
 	use std::net::SocketAddr;
-	use std::collections::BTreeMap;
+	use std::collections::{BTreeSet, BTreeMap};
 	use ethcrypto;
 	use ethkey::{self, Secret, Random, Generator};
+	use devtools::RandomTempPath;
 	use acl_storage::DummyAclStorage;
 	use key_storage::tests::DummyKeyStorage;
 	use node_key_pair::PlainNodeKeyPair;
 	use key_server_set::tests::MapKeyServerSet;
-	use key_server_cluster::math;
+	use peer_filter::MemoryPeerFilter;
+	use key_server_cluster::{math, admin_request_hash, ADMIN_REQUEST_WINDOW};
 	use bigint::hash::H256;
-	use types::all::{Error, Public, ClusterConfiguration, NodeAddress, RequestSignature, ServerKeyId,
-		EncryptedDocumentKey, EncryptedDocumentKeyShadow, MessageHash, EncryptedMessageSignature};
-	use traits::{ServerKeyGenerator, DocumentKeyServer, MessageSigner, KeyServer};
+	use hash::{keccak, KECCAK_EMPTY};
+	use audit_log::unix_timestamp;
+	use types::all::{Error, Public, NodeId, ClusterConfiguration, NodeAddress, RequestSignature, ServerKeyId,
+		EncryptedDocumentKey, EncryptedDocumentKeyShadow, EncryptedDocumentKeyWithEntropy, DocumentKeyShadows, ReEncryptedDocumentKey, MessageHash, EncryptedMessageSignature, AuditRecord, ClusterHealth,
+		DrainReport, BootstrapReport, PeerLists, KeyList, RemovedKeyInfo, AttestedServerKeyPublic, ExportedKeyShare, KeyExportReport,
+		KeyBackupReport, AdminRequestSignature};
+	use traits::{ServerKeyGenerator, DocumentKeyServer, MessageSigner, AuditLogReader, MetricsReader, AdministrationServer, KeyServer,
+		NodeKeyPair};
+	use decryption_cache::DecryptionCache;
 	use super::KeyServerImpl;
 
 	pub struct DummyKeyServer;
@@ -215,6 +972,18 @@ pub mod tests {
 		fn generate_key(&self, _key_id: &ServerKeyId, _signature: &RequestSignature, _threshold: usize) -> Result<Public, Error> {
 			unimplemented!()
 		}
+
+		fn derive_key(&self, _parent_key_id: &ServerKeyId, _signature: &RequestSignature, _derivation_path: &H256) -> Result<Public, Error> {
+			unimplemented!()
+		}
+
+		fn delete_key(&self, _key_id: &ServerKeyId, _signature: &RequestSignature) -> Result<BTreeSet<NodeId>, Error> {
+			unimplemented!()
+		}
+
+		fn restore_key_public(&self, _key_id: &ServerKeyId, _signature: &RequestSignature) -> Result<AttestedServerKeyPublic, Error> {
+			unimplemented!()
+		}
 	}
 
 	impl DocumentKeyServer for DummyKeyServer {
@@ -226,6 +995,10 @@ pub mod tests {
 			unimplemented!()
 		}
 
+		fn generate_document_key_with_entropy(&self, _key_id: &ServerKeyId, _signature: &RequestSignature, _threshold: usize, _requester_entropy: Public) -> Result<EncryptedDocumentKeyWithEntropy, Error> {
+			unimplemented!()
+		}
+
 		fn restore_document_key(&self, _key_id: &ServerKeyId, _signature: &RequestSignature) -> Result<EncryptedDocumentKey, Error> {
 			unimplemented!()
 		}
@@ -233,19 +1006,113 @@ pub mod tests {
 		fn restore_document_key_shadow(&self, _key_id: &ServerKeyId, _signature: &RequestSignature) -> Result<EncryptedDocumentKeyShadow, Error> {
 			unimplemented!()
 		}
+
+		fn restore_document_key_shadows(&self, _requests: Vec<(ServerKeyId, RequestSignature)>) -> Result<DocumentKeyShadows, Error> {
+			unimplemented!()
+		}
+
+		fn reencrypt_document_key(&self, _key_id: &ServerKeyId, _signature: &RequestSignature, _target_public: &Public, _target_signature: &RequestSignature) -> Result<ReEncryptedDocumentKey, Error> {
+			unimplemented!()
+		}
 	}
 
 	impl MessageSigner for DummyKeyServer {
 		fn sign_message(&self, _key_id: &ServerKeyId, _signature: &RequestSignature, _message: MessageHash) -> Result<EncryptedMessageSignature, Error> {
 			unimplemented!()
 		}
+
+		fn sign_message_ecdsa(&self, _key_id: &ServerKeyId, _signature: &RequestSignature, _message: MessageHash) -> Result<EncryptedMessageSignature, Error> {
+			unimplemented!()
+		}
 	}
 
-	fn make_key_servers(start_port: u16, num_nodes: usize) -> Vec<KeyServerImpl> {
-		let key_pairs: Vec<_> = (0..num_nodes).map(|_| Random.generate().unwrap()).collect();
-		let configs: Vec<_> = (0..num_nodes).map(|i| ClusterConfiguration {
-				threads: 1,
-				listener_address: NodeAddress {
+	impl AuditLogReader for DummyKeyServer {
+		fn key_audit_log(&self, _key_id: &ServerKeyId, _signature: &RequestSignature) -> Result<Vec<AuditRecord>, Error> {
+			unimplemented!()
+		}
+	}
+
+	impl MetricsReader for DummyKeyServer {
+		fn metrics(&self) -> Result<String, Error> {
+			unimplemented!()
+		}
+
+		fn health(&self) -> Result<ClusterHealth, Error> {
+			unimplemented!()
+		}
+	}
+
+	impl AdministrationServer for DummyKeyServer {
+		fn drain(&self, _signature: &AdminRequestSignature, _targets: BTreeSet<NodeId>) -> Result<DrainReport, Error> {
+			unimplemented!()
+		}
+
+		fn drain_report(&self, _signature: &AdminRequestSignature, _id: u64) -> Result<DrainReport, Error> {
+			unimplemented!()
+		}
+
+		fn cancel_drain(&self, _signature: &AdminRequestSignature, _id: u64) -> Result<DrainReport, Error> {
+			unimplemented!()
+		}
+
+		fn peer_lists(&self, _signature: &AdminRequestSignature) -> Result<PeerLists, Error> {
+			unimplemented!()
+		}
+
+		fn allow_peer(&self, _signature: &AdminRequestSignature, _node: NodeId) -> Result<(), Error> {
+			unimplemented!()
+		}
+
+		fn deny_peer(&self, _signature: &AdminRequestSignature, _node: NodeId) -> Result<(), Error> {
+			unimplemented!()
+		}
+
+		fn forget_peer(&self, _signature: &AdminRequestSignature, _node: NodeId) -> Result<(), Error> {
+			unimplemented!()
+		}
+
+		fn set_key_label(&self, _signature: &AdminRequestSignature, _key_id: &ServerKeyId, _label: Option<String>) -> Result<(), Error> {
+			unimplemented!()
+		}
+
+		fn list_keys(&self, _signature: &AdminRequestSignature, _after: Option<ServerKeyId>, _limit: usize) -> Result<KeyList, Error> {
+			unimplemented!()
+		}
+
+		fn list_removed_keys(&self, _signature: &AdminRequestSignature) -> Result<Vec<RemovedKeyInfo>, Error> {
+			unimplemented!()
+		}
+
+		fn restore_removed_key(&self, _signature: &AdminRequestSignature, _key_id: &ServerKeyId) -> Result<(), Error> {
+			unimplemented!()
+		}
+
+		fn bootstrap_node(&self, _signature: &AdminRequestSignature, _new_node: NodeId, _after: Option<ServerKeyId>) -> Result<BootstrapReport, Error> {
+			unimplemented!()
+		}
+
+		fn export_keys(&self, _signature: &AdminRequestSignature, _keys: BTreeSet<ServerKeyId>, _targets: BTreeSet<NodeId>, _threshold: usize) -> Result<KeyExportReport, Error> {
+			unimplemented!()
+		}
+
+		fn import_key_share(&self, _signature: &AdminRequestSignature, _key_share: ExportedKeyShare) -> Result<(), Error> {
+			unimplemented!()
+		}
+
+		fn backup_keys(&self, _signature: &AdminRequestSignature, _file_name: String) -> Result<KeyBackupReport, Error> {
+			unimplemented!()
+		}
+
+		fn restore_keys(&self, _signature: &AdminRequestSignature, _file_name: String) -> Result<KeyBackupReport, Error> {
+			unimplemented!()
+		}
+	}
+
+	fn make_key_servers(start_port: u16, num_nodes: usize) -> Vec<KeyServerImpl> {
+		let key_pairs: Vec<_> = (0..num_nodes).map(|_| Random.generate().unwrap()).collect();
+		let configs: Vec<_> = (0..num_nodes).map(|i| ClusterConfiguration {
+				threads: 1,
+				listener_address: NodeAddress {
 					address: "127.0.0.1".into(),
 					port: start_port + (i as u16),
 				},
@@ -255,6 +1122,18 @@ pub mod tests {
 						port: start_port + (j as u16),
 					})).collect(),
 				allow_connecting_to_higher_nodes: false,
+				rate_limits: Default::default(),
+				peer_rate_limits: Default::default(),
+				deterministic_nodes_selection: true,
+				read_only_nodes: Vec::new(),
+				timeouts: Default::default(),
+				external_address: None,
+				upnp_enabled: false,
+				admin_messages_share: None,
+				removed_keys_retention: None,
+				decryption_cache_ttl: None,
+				max_session_traffic: None,
+				session_journal: None,
 			}).collect();
 		let key_servers_set: BTreeMap<Public, SocketAddr> = configs[0].nodes.iter()
 			.map(|(k, a)| (k.clone(), format!("{}:{}", a.address, a.port).parse().unwrap()))
@@ -263,7 +1142,9 @@ pub mod tests {
 			KeyServerImpl::new(&cfg, Arc::new(MapKeyServerSet::new(key_servers_set.clone())),
 				Arc::new(PlainNodeKeyPair::new(key_pairs[i].clone())),
 				Arc::new(DummyAclStorage::default()),
-				Arc::new(DummyKeyStorage::default())).unwrap()
+				Arc::new(DummyKeyStorage::default()),
+				Arc::new(MemoryPeerFilter::default()),
+				None).unwrap()
 		).collect();
 
 		// wait until connections are established. It is fast => do not bother with events here
@@ -317,6 +1198,35 @@ pub mod tests {
 		}
 	}
 
+	#[test]
+	fn document_key_generation_with_requester_entropy_works() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6220, 3);
+
+		// generate document key, using requester entropy
+		let threshold = 1;
+		let document = Random.generate().unwrap().secret().clone();
+		let secret = Random.generate().unwrap().secret().clone();
+		let signature = ethkey::sign(&secret, &document).unwrap();
+		let requester_entropy = Random.generate().unwrap();
+		let generated_key = key_servers[0].generate_document_key_with_entropy(&document, &signature, threshold,
+			requester_entropy.public().clone()).unwrap();
+		let document_key = ethcrypto::ecies::decrypt(&secret, &ethcrypto::DEFAULT_MAC, &generated_key.encrypted_key).unwrap();
+
+		// requester could check that its entropy has been used
+		assert!(math::verify_document_key_entropy(&Public::from_slice(&document_key), &generated_key.server_entropy,
+			requester_entropy.secret()).unwrap());
+		assert!(!math::verify_document_key_entropy(&Public::from_slice(&document_key), &generated_key.server_entropy,
+			Random.generate().unwrap().secret()).unwrap());
+
+		// document key is stored as usual
+		for key_server in key_servers.iter() {
+			let retrieved_key = key_server.restore_document_key(&document, &signature).unwrap();
+			let retrieved_key = ethcrypto::ecies::decrypt(&secret, &ethcrypto::DEFAULT_MAC, &retrieved_key).unwrap();
+			assert_eq!(retrieved_key, document_key);
+		}
+	}
+
 	#[test]
 	fn document_key_generation_and_retrievement_works_over_network_with_3_nodes() {
 		//::logger::init_log();
@@ -394,4 +1304,670 @@ pub mod tests {
 			assert_eq!(math::verify_signature(&server_public, &(signature_c, signature_s), &message_hash), Ok(true));
 		}
 	}
+
+	#[test]
+	fn server_key_generation_and_ecdsa_message_signing_works_over_network_with_3_nodes() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6110, 3);
+
+		let test_cases = [0, 1];
+		for threshold in &test_cases {
+			// generate server key
+			let server_key_id = Random.generate().unwrap().secret().clone();
+			let requestor_secret = Random.generate().unwrap().secret().clone();
+			let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+			let server_public = key_servers[0].generate_key(&server_key_id, &signature, *threshold).unwrap();
+
+			// sign message
+			let message_hash = H256::from(42);
+			let signature = key_servers[0].sign_message_ecdsa(&server_key_id, &signature, message_hash.clone()).unwrap();
+			let signature = ethcrypto::ecies::decrypt(&requestor_secret, &ethcrypto::DEFAULT_MAC, &signature).unwrap();
+			let signature = ethkey::Signature::from_rsv(&H256::from_slice(&signature[..32]), &H256::from_slice(&signature[32..64]), signature[64]);
+
+			// check signature
+			assert!(ethkey::verify_public(&server_public, &signature, &message_hash).unwrap());
+		}
+	}
+
+	#[test]
+	fn server_key_derivation_and_document_key_storing_works_over_network_with_3_nodes() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6120, 3);
+
+		let test_cases = [0, 1];
+		for threshold in &test_cases {
+			// generate parent server key
+			let server_key_id = Random.generate().unwrap().secret().clone();
+			let requestor_secret = Random.generate().unwrap().secret().clone();
+			let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+			let server_public = key_servers[0].generate_key(&server_key_id, &signature, *threshold).unwrap();
+
+			// derive server key
+			let derivation_path = H256::from(42);
+			let derived_key_id = math::compute_derived_key_id(&server_key_id, &derivation_path);
+			let signature = ethkey::sign(&requestor_secret, &derived_key_id).unwrap();
+			let derived_public = key_servers[0].derive_key(&server_key_id, &signature, &derivation_path).unwrap();
+			let delta = math::compute_key_derivation_delta(&derived_key_id).unwrap();
+			assert_eq!(derived_public, math::compute_derived_public(&server_public, &delta).unwrap());
+
+			// store document key, encrypted with derived server key
+			let document_key = Random.generate().unwrap().public().clone();
+			let encrypted_document_key = math::encrypt_secret(&document_key, &derived_public).unwrap();
+			key_servers[0].store_document_key(&derived_key_id, &signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point).unwrap();
+
+			// now let's try to retrieve key back
+			for key_server in key_servers.iter() {
+				let retrieved_key = key_server.restore_document_key(&derived_key_id, &signature).unwrap();
+				let retrieved_key = ethcrypto::ecies::decrypt(&requestor_secret, &ethcrypto::DEFAULT_MAC, &retrieved_key).unwrap();
+				let retrieved_key = Public::from_slice(&retrieved_key);
+				assert_eq!(retrieved_key, document_key);
+			}
+		}
+	}
+
+	#[test]
+	fn document_key_reencryption_works_over_network_with_3_nodes() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6130, 3);
+
+		let test_cases = [0, 1, 2];
+		for threshold in &test_cases {
+			// generate server key and store document key
+			let server_key_id = Random.generate().unwrap().secret().clone();
+			let requestor_secret = Random.generate().unwrap().secret().clone();
+			let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+			let server_public = key_servers[0].generate_key(&server_key_id, &signature, *threshold).unwrap();
+			let document_key = Random.generate().unwrap().public().clone();
+			let encrypted_document_key = math::encrypt_secret(&document_key, &server_public).unwrap();
+			key_servers[0].store_document_key(&server_key_id, &signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point).unwrap();
+
+			// re-encrypt document key with target public and decrypt it with target secret
+			let target = Random.generate().unwrap();
+			let target_hash = math::compute_reencryption_target_hash(&server_key_id, target.public());
+			let target_signature = ethkey::sign(&requestor_secret, &target_hash).unwrap();
+			for key_server in key_servers.iter() {
+				let reencrypted_key = key_server.reencrypt_document_key(&server_key_id, &signature, target.public(), &target_signature).unwrap();
+				let retrieved_key = math::decrypt_with_joint_secret(&reencrypted_key.encrypted_point, &reencrypted_key.common_point, target.secret()).unwrap();
+				assert_eq!(retrieved_key, document_key);
+			}
+
+			// target, not signed by requester, is rejected
+			let other_target = Random.generate().unwrap();
+			assert!(key_servers[0].reencrypt_document_key(&server_key_id, &signature, other_target.public(), &target_signature).is_err());
+		}
+	}
+
+	#[test]
+	fn document_key_shadows_are_retrieved_in_batch_over_network_with_3_nodes() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6150, 3);
+
+		// generate server keys && store document keys
+		let requestor_secret = Random.generate().unwrap().secret().clone();
+		let documents: Vec<_> = (0..12).map(|_| {
+			let server_key_id = Random.generate().unwrap().secret().clone();
+			let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+			let server_public = key_servers[0].generate_key(&server_key_id, &signature, 1).unwrap();
+			let document_key = Random.generate().unwrap().public().clone();
+			let encrypted_document_key = math::encrypt_secret(&document_key, &server_public).unwrap();
+			key_servers[0].store_document_key(&server_key_id, &signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point).unwrap();
+			(server_key_id, signature, document_key)
+		}).collect();
+
+		// unknown key is reported as failed in the middle of the batch
+		let unknown_key_id = Random.generate().unwrap().secret().clone();
+		let unknown_key_signature = ethkey::sign(&requestor_secret, &unknown_key_id).unwrap();
+		let mut requests: Vec<_> = documents.iter().map(|&(ref id, ref signature, _)| (id.clone(), signature.clone())).collect();
+		requests.insert(5, (unknown_key_id.clone(), unknown_key_signature));
+
+		// retrieve all shadows at once
+		let shadows: Vec<_> = key_servers[1].restore_document_key_shadows(requests).unwrap().collect();
+		assert_eq!(shadows.len(), documents.len() + 1);
+		assert_eq!(shadows[5].0, unknown_key_id);
+		assert!(shadows[5].1.is_err());
+		for (&(ref key_id, ref shadow), &(ref server_key_id, _, ref document_key)) in shadows.iter().filter(|s| s.0 != unknown_key_id).zip(documents.iter()) {
+			assert_eq!(key_id, server_key_id);
+			let shadow = shadow.clone().unwrap();
+			let decrypt_shadows: Vec<_> = shadow.decrypt_shadows.unwrap().into_iter()
+				.map(|c| Secret::from_slice(&ethcrypto::ecies::decrypt(&requestor_secret, &ethcrypto::DEFAULT_MAC, &c).unwrap()))
+				.collect();
+			let decrypted_key = math::decrypt_with_shadow_coefficients(shadow.decrypted_secret, shadow.common_point.unwrap(), decrypt_shadows).unwrap();
+			assert_eq!(&decrypted_key, document_key);
+		}
+
+		// keys could only be requested by single requester
+		let other_signature = ethkey::sign(Random.generate().unwrap().secret(), &documents[1].0).unwrap();
+		assert_eq!(key_servers[1].restore_document_key_shadows(vec![
+			(documents[0].0.clone(), documents[0].1.clone()),
+			(documents[1].0.clone(), other_signature),
+		]).err(), Some(Error::BadSignature));
+	}
+
+	#[test]
+	fn document_key_shadow_is_verified_with_decryption_proofs_over_network_with_3_nodes() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6160, 3);
+
+		let test_cases = [0, 1, 2];
+		for threshold in &test_cases {
+			// generate server key && store document key
+			let server_key_id = Random.generate().unwrap().secret().clone();
+			let requestor_secret = Random.generate().unwrap().secret().clone();
+			let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+			let server_public = key_servers[0].generate_key(&server_key_id, &signature, *threshold).unwrap();
+			let document_key = Random.generate().unwrap().public().clone();
+			let encrypted_document_key = math::encrypt_secret(&document_key, &server_public).unwrap();
+			key_servers[0].store_document_key(&server_key_id, &signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point).unwrap();
+
+			// restore document key shadow && decrypt it on client
+			let shadow = key_servers[1].restore_document_key_shadow(&server_key_id, &signature).unwrap();
+			let decrypt_shadows: Vec<_> = shadow.decrypt_shadows.unwrap().into_iter()
+				.map(|c| Secret::from_slice(&ethcrypto::ecies::decrypt(&requestor_secret, &ethcrypto::DEFAULT_MAC, &c).unwrap()))
+				.collect();
+			let common_point = shadow.common_point.unwrap();
+			let decrypted_key = math::decrypt_with_shadow_coefficients(shadow.decrypted_secret, common_point.clone(), decrypt_shadows).unwrap();
+			assert_eq!(decrypted_key, document_key);
+
+			// check that decrypted key is proven by nodes
+			let decrypt_proofs: Vec<_> = shadow.decrypt_proofs.unwrap().into_iter()
+				.map(|p| math::NodeDecryptionProof::from_bytes(&ethcrypto::ecies::decrypt(&requestor_secret, &ethcrypto::DEFAULT_MAC, &p).unwrap()).unwrap())
+				.collect();
+			assert_eq!(decrypt_proofs.len(), threshold + 1);
+			let encrypted_point = shadow.encrypted_point.unwrap();
+			assert!(math::verify_decryption_proofs(&server_public, &common_point, &encrypted_point, &decrypted_key, &decrypt_proofs).unwrap());
+			assert!(!math::verify_decryption_proofs(&server_public, &common_point, &encrypted_point, Random.generate().unwrap().public(), &decrypt_proofs).unwrap());
+		}
+	}
+
+	#[test]
+	fn document_key_is_rekeyed_over_network_with_3_nodes() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6170, 3);
+
+		let test_cases = [0, 1, 2];
+		for threshold in &test_cases {
+			// generate server key && store document key
+			let server_key_id = Random.generate().unwrap().secret().clone();
+			let requestor_secret = Random.generate().unwrap().secret().clone();
+			let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+			let server_public = key_servers[0].generate_key(&server_key_id, &signature, *threshold).unwrap();
+			let document_key = Random.generate().unwrap().public().clone();
+			let encrypted_document_key = math::encrypt_secret(&document_key, &server_public).unwrap();
+			key_servers[0].store_document_key(&server_key_id, &signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point).unwrap();
+
+			// re-key document
+			key_servers[1].cluster().new_rekeying_session(server_key_id.clone()).unwrap().wait(None).unwrap();
+
+			// check that the same new document key is restored on every node
+			let rekeyed_document_keys: Vec<_> = key_servers.iter().map(|key_server| {
+				let shadow = key_server.restore_document_key_shadow(&server_key_id, &signature).unwrap();
+				let decrypt_shadows: Vec<_> = shadow.decrypt_shadows.unwrap().into_iter()
+					.map(|c| Secret::from_slice(&ethcrypto::ecies::decrypt(&requestor_secret, &ethcrypto::DEFAULT_MAC, &c).unwrap()))
+					.collect();
+				math::decrypt_with_shadow_coefficients(shadow.decrypted_secret, shadow.common_point.unwrap(), decrypt_shadows).unwrap()
+			}).collect();
+			assert!(rekeyed_document_keys[0] != document_key);
+			assert!(rekeyed_document_keys.iter().all(|k| *k == rekeyed_document_keys[0]));
+		}
+	}
+
+	#[test]
+	fn server_key_deletion_works_over_network_with_3_nodes() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6140, 3);
+
+		let test_cases = [0, 1, 2];
+		for threshold in &test_cases {
+			// generate server key and store document key
+			let server_key_id = Random.generate().unwrap().secret().clone();
+			let requestor_secret = Random.generate().unwrap().secret().clone();
+			let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+			let server_public = key_servers[0].generate_key(&server_key_id, &signature, *threshold).unwrap();
+			let document_key = Random.generate().unwrap().public().clone();
+			let encrypted_document_key = math::encrypt_secret(&document_key, &server_public).unwrap();
+			key_servers[0].store_document_key(&server_key_id, &signature, encrypted_document_key.common_point, encrypted_document_key.encrypted_point).unwrap();
+
+			// only author is allowed to delete key
+			let other_signature = ethkey::sign(Random.generate().unwrap().secret(), &server_key_id).unwrap();
+			assert!(key_servers[0].delete_key(&server_key_id, &other_signature).is_err());
+			assert!(key_servers[1].restore_document_key(&server_key_id, &signature).is_ok());
+
+			// delete key on all key servers
+			assert_eq!(key_servers[1].delete_key(&server_key_id, &signature), Ok(BTreeSet::<NodeId>::new()));
+
+			// deleted key could not be restored or generated again
+			for key_server in key_servers.iter() {
+				assert!(key_server.restore_document_key(&server_key_id, &signature).is_err());
+			}
+			assert!(key_servers[0].generate_key(&server_key_id, &signature, *threshold).is_err());
+		}
+	}
+
+	#[test]
+	fn server_key_public_is_attested_by_key_servers() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6230, 3);
+
+		// generate server key
+		let server_key_id = Random.generate().unwrap().secret().clone();
+		let requestor_secret = Random.generate().unwrap().secret().clone();
+		let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+		let server_public = key_servers[0].generate_key(&server_key_id, &signature, 1).unwrap();
+
+		// every key server restores the same public, attested by all key servers
+		let attestation_hash = math::compute_server_key_attestation_hash(&server_key_id, &server_public);
+		for key_server in key_servers.iter() {
+			let attested_public = key_server.restore_key_public(&server_key_id, &signature).unwrap();
+			assert_eq!(attested_public.public, server_public);
+			assert_eq!(attested_public.attestations.len(), 3);
+			for key_server in key_servers.iter() {
+				let node = key_server.self_key_pair.public();
+				assert!(ethkey::verify_public(node, &attested_public.attestations[node], &attestation_hash).unwrap());
+			}
+		}
+
+		// key is not found => error
+		let other_key_id = Random.generate().unwrap().secret().clone();
+		let other_signature = ethkey::sign(&requestor_secret, &other_key_id).unwrap();
+		assert!(key_servers[0].restore_key_public(&other_key_id, &other_signature).is_err());
+	}
+
+	/// Sign administrative request, signed at given time.
+	fn admin_signature_at(key_pair: &NodeKeyPair, method: &str, endpoint: &str, timestamp: u64) -> AdminRequestSignature {
+		let nonce = Random.generate().unwrap().secret().low_u64();
+		AdminRequestSignature {
+			method: method.into(),
+			endpoint: endpoint.into(),
+			timestamp: timestamp,
+			nonce: nonce,
+			body_hash: KECCAK_EMPTY,
+			signature: key_pair.sign(&admin_request_hash(method, endpoint, timestamp, nonce, &KECCAK_EMPTY)).unwrap(),
+		}
+	}
+
+	/// Sign administrative request.
+	fn admin_signature(key_pair: &NodeKeyPair, method: &str, endpoint: &str) -> AdminRequestSignature {
+		admin_signature_at(key_pair, method, endpoint, unix_timestamp())
+	}
+
+	/// Sign administrative request with random key.
+	fn other_admin_signature(method: &str, endpoint: &str) -> AdminRequestSignature {
+		admin_signature(&PlainNodeKeyPair::new(Random.generate().unwrap()), method, endpoint)
+	}
+
+	fn wait_drain(key_server: &KeyServerImpl, id: u64) -> DrainReport {
+		let wait_started = time::Instant::now();
+		loop {
+			let signature = admin_signature(&*key_server.self_key_pair, "GET", &format!("/drain/{}", id));
+			let report = key_server.drain_report(&signature, id).unwrap();
+			if report.is_completed {
+				return report;
+			}
+
+			assert!(wait_started.elapsed() < time::Duration::from_secs(30));
+			thread::sleep(time::Duration::from_millis(50));
+		}
+	}
+
+	#[test]
+	fn key_server_is_drained() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6180, 3);
+
+		// generate server key
+		let server_key_id = Random.generate().unwrap().secret().clone();
+		let requestor_secret = Random.generate().unwrap().secret().clone();
+		let signature = ethkey::sign(&requestor_secret, &server_key_id).unwrap();
+		key_servers[0].generate_key(&server_key_id, &signature, 1).unwrap();
+
+		// only key server operator is allowed to drain it
+		let self_key_pair = key_servers[2].self_key_pair.clone();
+		assert_eq!(key_servers[2].drain(&other_admin_signature("POST", "/drain"), BTreeSet::new()), Err(Error::AccessDenied));
+
+		// every other key server is already holding the key => key share could not be moved
+		let targets = key_servers.iter().take(2).map(|ks| ks.self_key_pair.public().clone()).collect();
+		let report = key_servers[2].drain(&admin_signature(&*self_key_pair, "POST", "/drain"), targets).unwrap();
+		assert!(!report.is_completed);
+		let report = wait_drain(&key_servers[2], report.id);
+		assert!(report.moved_keys.is_empty());
+		assert_eq!(report.failed_keys, vec![server_key_id.clone()]);
+		assert!(!report.is_safe_to_shutdown);
+
+		// when no share moves are requested, key server reports that it is safe to shutdown
+		let report = key_servers[2].drain(&admin_signature(&*self_key_pair, "POST", "/drain"), BTreeSet::new()).unwrap();
+		let report = wait_drain(&key_servers[2], report.id);
+		assert!(report.failed_keys.is_empty());
+		assert!(report.is_safe_to_shutdown);
+
+		// only the latest drain could be requested
+		let endpoint = format!("/drain/{}", report.id - 1);
+		assert_eq!(key_servers[2].drain_report(&admin_signature(&*self_key_pair, "GET", &endpoint), report.id - 1), Err(Error::DocumentNotFound));
+		let endpoint = format!("/drain/{}", report.id);
+		assert_eq!(key_servers[2].drain_report(&other_admin_signature("GET", &endpoint), report.id), Err(Error::AccessDenied));
+
+		// drained key server no longer accepts new sessions, but key share is still available
+		let other_server_key_id = Random.generate().unwrap().secret().clone();
+		let other_signature = ethkey::sign(&requestor_secret, &other_server_key_id).unwrap();
+		assert!(key_servers[2].generate_key(&other_server_key_id, &other_signature, 1).is_err());
+		assert!(key_servers[2].key_storage.contains(&server_key_id));
+
+		// only the latest drain could be cancelled && only by key server operator
+		let endpoint = format!("/drain/{}", report.id - 1);
+		assert_eq!(key_servers[2].cancel_drain(&admin_signature(&*self_key_pair, "DELETE", &endpoint), report.id - 1), Err(Error::DocumentNotFound));
+		let endpoint = format!("/drain/{}", report.id);
+		assert_eq!(key_servers[2].cancel_drain(&other_admin_signature("DELETE", &endpoint), report.id), Err(Error::AccessDenied));
+
+		// cancelled drain is not safe to shutdown && key server accepts new sessions again
+		let report = key_servers[2].cancel_drain(&admin_signature(&*self_key_pair, "DELETE", &endpoint), report.id).unwrap();
+		assert!(report.is_cancelled);
+		assert!(!report.is_safe_to_shutdown);
+		key_servers[2].generate_key(&other_server_key_id, &other_signature, 1).unwrap();
+	}
+
+	#[test]
+	fn key_server_peer_lists_are_managed() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6190, 3);
+		let self_key_pair = key_servers[0].self_key_pair.clone();
+		let node1 = key_servers[1].self_key_pair.public().clone();
+		let node2 = key_servers[2].self_key_pair.public().clone();
+		let peers_signature = || admin_signature(&*self_key_pair, "GET", "/peers");
+
+		// only key server operator is allowed to manage peer lists
+		let endpoint = format!("/peers/deny/{:?}", node1);
+		assert_eq!(key_servers[0].deny_peer(&other_admin_signature("POST", &endpoint), node1.clone()), Err(Error::AccessDenied));
+		assert_eq!(key_servers[0].peer_lists(&other_admin_signature("GET", "/peers")), Err(Error::AccessDenied));
+
+		// denied node is disconnected immediately
+		key_servers[0].deny_peer(&admin_signature(&*self_key_pair, "POST", &endpoint), node1.clone()).unwrap();
+		assert_eq!(key_servers[0].peer_lists(&peers_signature()), Ok(PeerLists { allowed: vec![], denied: vec![node1.clone()] }));
+		let connected = key_servers[0].cluster().cluster_state().connected;
+		assert!(!connected.contains(&node1));
+		assert!(connected.contains(&node2));
+
+		// allowed node is removed from deny list
+		let endpoint = format!("/peers/allow/{:?}", node1);
+		key_servers[0].allow_peer(&admin_signature(&*self_key_pair, "POST", &endpoint), node1.clone()).unwrap();
+		assert_eq!(key_servers[0].peer_lists(&peers_signature()), Ok(PeerLists { allowed: vec![node1.clone()], denied: vec![] }));
+		assert!(!key_servers[0].cluster().cluster_state().connected.contains(&node2));
+
+		let endpoint = format!("/peers/{:?}", node1);
+		key_servers[0].forget_peer(&admin_signature(&*self_key_pair, "DELETE", &endpoint), node1.clone()).unwrap();
+		assert_eq!(key_servers[0].peer_lists(&peers_signature()), Ok(PeerLists::default()));
+	}
+
+	#[test]
+	fn key_server_key_metadata_is_maintained() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6200, 3);
+
+		// generated key is marked with its creation time && requester on every node
+		let document = Random.generate().unwrap().secret().clone();
+		let requester = Random.generate().unwrap();
+		let signature = ethkey::sign(requester.secret(), &document).unwrap();
+		key_servers[0].generate_document_key(&document, &signature, 1).unwrap();
+		for key_server in &key_servers {
+			let metadata = key_server.key_storage.get(&document).unwrap().metadata;
+			assert!(metadata.created.is_some());
+			assert_eq!(metadata.requester, Some(requester.public().clone()));
+			assert_eq!(metadata.last_accessed, None);
+		}
+
+		// last access time is updated on the node, which has served the request
+		key_servers[1].restore_document_key(&document, &signature).unwrap();
+		assert!(key_servers[1].key_storage.get(&document).unwrap().metadata.last_accessed.is_some());
+		assert_eq!(key_servers[2].key_storage.get(&document).unwrap().metadata.last_accessed, None);
+
+		// only key server operator is allowed to label keys
+		let self_key_pair = key_servers[2].self_key_pair.clone();
+		let label_signature = || admin_signature(&*self_key_pair, "POST", &format!("/label/{:?}", document));
+		assert_eq!(key_servers[2].set_key_label(&other_admin_signature("POST", &format!("/label/{:?}", document)), &document, Some("label".into())),
+			Err(Error::AccessDenied));
+
+		key_servers[2].set_key_label(&label_signature(), &document, Some("label".into())).unwrap();
+		assert_eq!(key_servers[2].key_storage.get(&document).unwrap().metadata.label, Some("label".into()));
+		key_servers[2].set_key_label(&label_signature(), &document, None).unwrap();
+		assert_eq!(key_servers[2].key_storage.get(&document).unwrap().metadata.label, None);
+		assert_eq!(key_servers[2].set_key_label(&label_signature(), &Default::default(), None), Err(Error::DocumentNotFound));
+	}
+
+	#[test]
+	fn admin_request_is_only_accepted_once_within_window() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6310, 1);
+		let self_key_pair = key_servers[0].self_key_pair.clone();
+
+		// signed request is only accepted once
+		let signature = admin_signature(&*self_key_pair, "GET", "/peers");
+		assert_eq!(key_servers[0].peer_lists(&signature), Ok(PeerLists::default()));
+		assert_eq!(key_servers[0].peer_lists(&signature), Err(Error::AccessDenied));
+
+		// request, which has been signed for other endpoint, is refused
+		let mut signature = admin_signature(&*self_key_pair, "GET", "/keys");
+		signature.endpoint = "/peers".into();
+		assert_eq!(key_servers[0].peer_lists(&signature), Err(Error::AccessDenied));
+
+		// request, which body has been changed after signing, is refused
+		let mut signature = admin_signature(&*self_key_pair, "POST", "/drain");
+		signature.body_hash = keccak("[]");
+		assert_eq!(key_servers[0].drain(&signature, BTreeSet::new()), Err(Error::AccessDenied));
+
+		// requests, signed outside of the window, are refused
+		let now = unix_timestamp();
+		let signature = admin_signature_at(&*self_key_pair, "GET", "/peers", now - ADMIN_REQUEST_WINDOW - 10);
+		assert_eq!(key_servers[0].peer_lists(&signature), Err(Error::AccessDenied));
+		let signature = admin_signature_at(&*self_key_pair, "GET", "/peers", now + ADMIN_REQUEST_WINDOW + 10);
+		assert_eq!(key_servers[0].peer_lists(&signature), Err(Error::AccessDenied));
+	}
+
+	#[test]
+	fn key_server_keys_are_listed() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6210, 1);
+		let self_public = key_servers[0].self_key_pair.public().clone();
+		let requester = Random.generate().unwrap();
+		let mut key_ids: Vec<ServerKeyId> = (0..3).map(|_| (**Random.generate().unwrap().secret()).clone()).collect();
+		for key_id in &key_ids {
+			let signature = ethkey::sign(requester.secret(), key_id).unwrap();
+			key_servers[0].generate_key(key_id, &signature, 0).unwrap();
+		}
+		key_ids.sort();
+
+		// only key server operator is allowed to list keys
+		assert_eq!(key_servers[0].list_keys(&other_admin_signature("GET", "/keys"), None, 2), Err(Error::AccessDenied));
+
+		// keys are listed page by page
+		let signature = admin_signature(&*key_servers[0].self_key_pair, "GET", "/keys");
+		let first_page = key_servers[0].list_keys(&signature, None, 2).unwrap();
+		assert_eq!(first_page.keys.iter().map(|k| k.key_id.clone()).collect::<Vec<_>>(), key_ids[..2].to_vec());
+		assert_eq!(first_page.next, Some(key_ids[1].clone()));
+		assert_eq!(first_page.keys[0].threshold, 0);
+		assert_eq!(first_page.keys[0].holders, vec![self_public.clone()]);
+		assert_eq!(first_page.keys[0].requester, Some(requester.public().clone()));
+
+		let signature = admin_signature(&*key_servers[0].self_key_pair, "GET", &format!("/keys/{:?}", key_ids[1]));
+		let second_page = key_servers[0].list_keys(&signature, first_page.next, 2).unwrap();
+		assert_eq!(second_page.keys.iter().map(|k| k.key_id.clone()).collect::<Vec<_>>(), key_ids[2..].to_vec());
+		assert_eq!(second_page.next, None);
+	}
+
+	#[test]
+	fn key_server_deleted_key_is_restored() {
+		//::logger::init_log();
+		let key_servers = make_key_servers(6240, 1);
+		let self_key_pair = key_servers[0].self_key_pair.clone();
+		let requester = Random.generate().unwrap();
+		let key_id: ServerKeyId = (**Random.generate().unwrap().secret()).clone();
+		let signature = ethkey::sign(requester.secret(), &key_id).unwrap();
+		key_servers[0].generate_key(&key_id, &signature, 0).unwrap();
+		assert_eq!(key_servers[0].delete_key(&key_id, &signature), Ok(BTreeSet::new()));
+		assert!(key_servers[0].generate_key(&key_id, &signature, 0).is_err());
+
+		// only key server operator is allowed to list && restore removed keys
+		let restore_endpoint = format!("/removed/{:?}", key_id);
+		assert_eq!(key_servers[0].list_removed_keys(&other_admin_signature("GET", "/removed")), Err(Error::AccessDenied));
+		assert_eq!(key_servers[0].restore_removed_key(&other_admin_signature("POST", &restore_endpoint), &key_id), Err(Error::AccessDenied));
+
+		// deleted key is kept until purged
+		let removed_keys = key_servers[0].list_removed_keys(&admin_signature(&*self_key_pair, "GET", "/removed")).unwrap();
+		assert_eq!(removed_keys.len(), 1);
+		assert_eq!(removed_keys[0].key_id, key_id);
+		assert!(removed_keys[0].is_deleted);
+
+		// restored key is not treated as deleted anymore
+		key_servers[0].restore_removed_key(&admin_signature(&*self_key_pair, "POST", &restore_endpoint), &key_id).unwrap();
+		assert_eq!(key_servers[0].list_removed_keys(&admin_signature(&*self_key_pair, "GET", "/removed")), Ok(vec![]));
+		assert!(key_servers[0].key_storage.contains(&key_id));
+		assert!(!key_servers[0].key_storage.is_tombstoned(&key_id));
+	}
+
+	#[test]
+	fn document_key_shadow_is_cached() {
+		//::logger::init_log();
+		let mut key_servers = make_key_servers(6270, 3);
+		key_servers[0].decryption_cache = Some(DecryptionCache::new(60));
+
+		// generate document key
+		let document = Random.generate().unwrap().secret().clone();
+		let secret = Random.generate().unwrap().secret().clone();
+		let signature = ethkey::sign(&secret, &document).unwrap();
+		key_servers[0].generate_document_key(&document, &signature, 1).unwrap();
+
+		// every shadow decryption session produces new decryption shadows => cached result is the same result
+		let shadow = key_servers[0].restore_document_key_shadow(&document, &signature).unwrap();
+		assert_eq!(key_servers[0].restore_document_key_shadow(&document, &signature).unwrap(), shadow);
+		assert!(key_servers[1].restore_document_key_shadow(&document, &signature).unwrap() != shadow);
+
+		// cached result is not returned once document key is changed
+		let requester = ethkey::recover(&signature, &document).unwrap();
+		let mut key_share = key_servers[0].key_storage.get(&document).unwrap();
+		key_share.common_point = Some(Random.generate().unwrap().public().clone());
+		key_servers[0].key_storage.update((*document).clone(), key_share).unwrap();
+		assert_eq!(key_servers[0].cached_document_key_shadow(&document, &requester), None);
+		assert_eq!(key_servers[0].decryption_cache.as_ref().unwrap().get(&document, &requester), None);
+	}
+
+	#[test]
+	fn document_key_is_exported_to_other_cluster() {
+		//::logger::init_log();
+		let source_key_servers = make_key_servers(6250, 3);
+		let target_key_servers = make_key_servers(6260, 2);
+
+		// generate document key in source cluster
+		let document = Random.generate().unwrap().secret().clone();
+		let secret = Random.generate().unwrap().secret().clone();
+		let signature = ethkey::sign(&secret, &document).unwrap();
+		let generated_key = source_key_servers[0].generate_document_key(&document, &signature, 1).unwrap();
+		let generated_key = ethcrypto::ecies::decrypt(&secret, &ethcrypto::DEFAULT_MAC, &generated_key).unwrap();
+
+		// export it to target cluster
+		let source_admin_signature = admin_signature(&*source_key_servers[0].self_key_pair, "POST", "/export/1");
+		let targets: BTreeSet<_> = target_key_servers.iter().map(|ks| ks.self_key_pair.public().clone()).collect();
+		let report = source_key_servers[0].export_keys(&source_admin_signature, vec![document.clone()].into_iter().collect(), targets, 1).unwrap();
+		assert!(report.failed_keys.is_empty());
+		assert_eq!(report.key_shares.len(), 2);
+
+		// import key shares to target nodes
+		for key_share in report.key_shares {
+			let target_key_server = target_key_servers.iter().find(|ks| *ks.self_key_pair.public() == key_share.node).unwrap();
+			let target_admin_signature = admin_signature(&*target_key_server.self_key_pair, "POST", "/import");
+			target_key_server.import_key_share(&target_admin_signature, key_share).unwrap();
+		}
+
+		// document key is now retrieved from target cluster
+		for key_server in target_key_servers.iter() {
+			let retrieved_key = key_server.restore_document_key(&document, &signature).unwrap();
+			let retrieved_key = ethcrypto::ecies::decrypt(&secret, &ethcrypto::DEFAULT_MAC, &retrieved_key).unwrap();
+			assert_eq!(retrieved_key, generated_key);
+		}
+	}
+
+	#[test]
+	fn key_export_request_is_forwarded_to_master_node() {
+		//::logger::init_log();
+		let source_key_servers = make_key_servers(6280, 3);
+		let target_key_servers = make_key_servers(6290, 2);
+
+		// generate document key in source cluster
+		let document = Random.generate().unwrap().secret().clone();
+		let secret = Random.generate().unwrap().secret().clone();
+		let signature = ethkey::sign(&secret, &document).unwrap();
+		source_key_servers[0].generate_document_key(&document, &signature, 1).unwrap();
+
+		// request, signed by operator of node 0, is sent to node 1 && forwarded to node 0
+		let source_admin_signature = admin_signature(&*source_key_servers[0].self_key_pair, "POST", "/export/1");
+		let targets: BTreeSet<_> = target_key_servers.iter().map(|ks| ks.self_key_pair.public().clone()).collect();
+		let report = source_key_servers[1].export_keys(&source_admin_signature, vec![document.clone()].into_iter().collect(), targets.clone(), 1).unwrap();
+		assert!(report.failed_keys.is_empty());
+		assert_eq!(report.key_shares.len(), 2);
+
+		// the same request could not be replayed
+		assert_eq!(source_key_servers[1].export_keys(&source_admin_signature, vec![document.clone()].into_iter().collect(), targets, 1).err(),
+			Some(Error::AccessDenied));
+
+		// request, signed by someone else, is rejected
+		assert_eq!(source_key_servers[1].export_keys(&other_admin_signature("POST", "/export/1"), vec![document].into_iter().collect(), BTreeSet::new(), 1).err(),
+			Some(Error::AccessDenied));
+	}
+
+	#[test]
+	fn key_server_keys_are_backed_up() {
+		//::logger::init_log();
+		let mut key_servers = make_key_servers(6300, 1);
+		let self_key_pair = key_servers[0].self_key_pair.clone();
+		let key_id: ServerKeyId = (**Random.generate().unwrap().secret()).clone();
+		let signature = ethkey::sign(Random.generate().unwrap().secret(), &key_id).unwrap();
+		key_servers[0].generate_key(&key_id, &signature, 0).unwrap();
+
+		// backups are disabled unless backup directory is configured
+		assert!(key_servers[0].backup_keys(&admin_signature(&*self_key_pair, "POST", "/backup"), "keys.backup".into()).is_err());
+		let backup_path = RandomTempPath::new();
+		fs::create_dir_all(backup_path.as_path()).unwrap();
+		key_servers[0].backup_path = Some(backup_path.as_path().to_owned());
+
+		// only key server operator is allowed to backup && restore keys
+		assert_eq!(key_servers[0].backup_keys(&other_admin_signature("POST", "/backup"), "keys.backup".into()), Err(Error::AccessDenied));
+		assert_eq!(key_servers[0].restore_keys(&other_admin_signature("POST", "/restore"), "keys.backup".into()), Err(Error::AccessDenied));
+
+		// backup is only written to the file within backup directory
+		let outside_path = RandomTempPath::new();
+		for file_name in vec!["", ".", "..", "../keys.backup", "dir/keys.backup", "keys.backup/", outside_path.as_str()] {
+			assert_eq!(key_servers[0].backup_keys(&admin_signature(&*self_key_pair, "POST", "/backup"), file_name.into()), Err(Error::AccessDenied));
+			assert_eq!(key_servers[0].restore_keys(&admin_signature(&*self_key_pair, "POST", "/restore"), file_name.into()), Err(Error::AccessDenied));
+		}
+		assert!(!outside_path.as_path().exists());
+
+		// backup is written to the file
+		let report = key_servers[0].backup_keys(&admin_signature(&*self_key_pair, "POST", "/backup"), "keys.backup".into()).unwrap();
+		assert_eq!(report.keys, 1);
+		assert!(backup_path.as_path().join("keys.backup").exists());
+		assert!(!backup_path.as_path().join("keys.backup.tmp").exists());
+
+		// backup is never restored to non-empty key storage
+		assert!(key_servers[0].restore_keys(&admin_signature(&*self_key_pair, "POST", "/restore"), "keys.backup".into()).is_err());
+		assert!(key_servers[0].key_storage.contains(&key_id));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn key_server_backups_are_never_written_through_symlinks() {
+		use std::os::unix::fs::symlink;
+
+		//::logger::init_log();
+		let mut key_servers = make_key_servers(6311, 1);
+		let self_key_pair = key_servers[0].self_key_pair.clone();
+		let backup_path = RandomTempPath::new();
+		fs::create_dir_all(backup_path.as_path()).unwrap();
+		key_servers[0].backup_path = Some(backup_path.as_path().to_owned());
+
+		let outside_path = RandomTempPath::new();
+		symlink(outside_path.as_path(), backup_path.as_path().join("keys.backup")).unwrap();
+		assert_eq!(key_servers[0].backup_keys(&admin_signature(&*self_key_pair, "POST", "/backup"), "keys.backup".into()), Err(Error::AccessDenied));
+		assert_eq!(key_servers[0].restore_keys(&admin_signature(&*self_key_pair, "POST", "/restore"), "keys.backup".into()), Err(Error::AccessDenied));
+		assert!(!outside_path.as_path().exists());
+
+		symlink(outside_path.as_path(), backup_path.as_path().join("other.backup.tmp")).unwrap();
+		assert_eq!(key_servers[0].backup_keys(&admin_signature(&*self_key_pair, "POST", "/backup"), "other.backup".into()), Err(Error::AccessDenied));
+		assert!(!outside_path.as_path().exists());
+	}
 }
@@ -0,0 +1,401 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Forwarding of administrative requests to the master node. Admin session could only be started on the node, which
+//! operator has signed the request. Request, accepted by any other node, is relayed to the master node && status
+//! updates are sent back to the relaying node.
+
+use std::time;
+use std::sync::Arc;
+use std::collections::{BTreeSet, BTreeMap};
+use parking_lot::{Condvar, Mutex};
+use ethkey;
+use rustc_hex::ToHex;
+use bigint::hash::H256;
+use hash::keccak;
+use key_server_cluster::{Error, NodeId, SessionId, NodeKeyPair, KeyStorage, ExportedKeyShare, AdminRequestSignature};
+use key_server_cluster::cluster::ClusterClient;
+use key_server_cluster::share_bootstrap_session;
+use key_server_cluster::message;
+
+/// Administrative request is only accepted within ADMIN_REQUEST_WINDOW seconds from the time, when it has been signed.
+pub const ADMIN_REQUEST_WINDOW: u64 = 300;
+
+/// Administrative request, which could be forwarded to the master node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminRequest {
+	/// Move shares of the next batch of master node keys (following given key) to the new node.
+	ShareBootstrap(NodeId, Option<SessionId>),
+	/// Export master node key to given nodes of other cluster with given threshold.
+	KeyExport(SessionId, BTreeSet<NodeId>, usize),
+}
+
+/// Result of administrative request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminResponse {
+	/// Keys of the batch (empty if there are no more keys) && keys, which shares have been moved to the new node.
+	ShareBootstrap(BTreeSet<SessionId>, BTreeSet<SessionId>),
+	/// Key shares for all target nodes.
+	KeyExport(Vec<ExportedKeyShare>),
+}
+
+/// State of the forwarded request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForwardedRequestState {
+	/// Request has been sent to the master node.
+	WaitingForMaster,
+	/// Admin session has been started on the master node.
+	Started,
+	/// Request has completed successfully.
+	Finished,
+	/// Request has failed.
+	Failed,
+}
+
+/// Request, forwarded by this node to the master node.
+pub struct ForwardedRequest {
+	/// Master node.
+	master: NodeId,
+	/// Request completion condvar.
+	completed: Condvar,
+	/// Mutable request data.
+	data: Mutex<ForwardedRequestData>,
+}
+
+/// Mutable data of forwarded request.
+struct ForwardedRequestData {
+	/// Current state.
+	state: ForwardedRequestState,
+	/// Request result.
+	result: Option<Result<AdminResponse, Error>>,
+}
+
+/// Requests, forwarded by this node && not yet completed.
+#[derive(Default)]
+pub struct ForwardedRequests {
+	/// Mutable requests data.
+	data: Mutex<ForwardedRequestsData>,
+}
+
+/// Mutable data of forwarded requests.
+#[derive(Default)]
+struct ForwardedRequestsData {
+	/// Id of the next forwarded request.
+	next_request_id: u64,
+	/// Active requests by id.
+	requests: BTreeMap<u64, Arc<ForwardedRequest>>,
+}
+
+impl ForwardedRequest {
+	/// Get master node, to which request has been forwarded.
+	pub fn master(&self) -> &NodeId {
+		&self.master
+	}
+
+	/// Get current state of the request.
+	pub fn state(&self) -> ForwardedRequestState {
+		self.data.lock().state
+	}
+
+	/// Wait until request is completed on the master node.
+	pub fn wait(&self, timeout: Option<time::Duration>) -> Result<AdminResponse, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+
+	/// Process status update from the master node. Returns true if request is completed.
+	fn on_status(&self, status: message::AdminRequestStatus) -> bool {
+		let result = match status {
+			message::AdminRequestStatus::Started => {
+				self.data.lock().state = ForwardedRequestState::Started;
+				return false;
+			},
+			message::AdminRequestStatus::ShareBootstrapCompleted(batch, moved_keys) => Ok(AdminResponse::ShareBootstrap(
+				batch.into_iter().map(Into::into).collect(),
+				moved_keys.into_iter().map(Into::into).collect())),
+			message::AdminRequestStatus::KeyExportCompleted(key_shares) => Ok(AdminResponse::KeyExport(
+				key_shares.into_iter().map(Into::into).collect())),
+			message::AdminRequestStatus::Failed(error, code) => Err(Error::remote(code, error)),
+		};
+
+		self.complete(result);
+		true
+	}
+
+	/// Complete request with given result.
+	fn complete(&self, result: Result<AdminResponse, Error>) {
+		let mut data = self.data.lock();
+		data.state = match result.is_ok() {
+			true => ForwardedRequestState::Finished,
+			false => ForwardedRequestState::Failed,
+		};
+		data.result = Some(result);
+		self.completed.notify_all();
+	}
+}
+
+impl ForwardedRequests {
+	/// Register new request, forwarded to given master node.
+	pub fn insert(&self, master: NodeId) -> (u64, Arc<ForwardedRequest>) {
+		let mut data = self.data.lock();
+		let request_id = data.next_request_id;
+		data.next_request_id += 1;
+
+		let request = Arc::new(ForwardedRequest {
+			master: master,
+			completed: Condvar::new(),
+			data: Mutex::new(ForwardedRequestData {
+				state: ForwardedRequestState::WaitingForMaster,
+				result: None,
+			}),
+		});
+		data.requests.insert(request_id, request.clone());
+		(request_id, request)
+	}
+
+	/// Process status update, received from the master node.
+	pub fn on_status(&self, sender: &NodeId, message: message::ForwardedAdminRequestStatus) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		let is_completed = match data.requests.get(&message.request_id) {
+			Some(request) if request.master == *sender => request.on_status(message.status),
+			Some(_) => return Err(Error::InvalidNodeForRequest),
+			None => return Err(Error::InvalidSessionId),
+		};
+		if is_completed {
+			data.requests.remove(&message.request_id);
+		}
+
+		Ok(())
+	}
+
+	/// Fail all requests, forwarded to the disconnected node.
+	pub fn on_node_disconnected(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+		let disconnected_requests: Vec<_> = data.requests.iter()
+			.filter(|&(_, request)| request.master == *node)
+			.map(|(request_id, _)| request_id.clone())
+			.collect();
+		for request_id in disconnected_requests {
+			if let Some(request) = data.requests.remove(&request_id) {
+				request.complete(Err(Error::NodeDisconnected));
+			}
+		}
+	}
+}
+
+/// Compute hash of administrative request, which is signed by key server operator. `body_hash` is keccak of the request body.
+pub fn admin_request_hash(method: &str, endpoint: &str, timestamp: u64, nonce: u64, body_hash: &H256) -> H256 {
+	keccak(format!("{} {} {} {} {}", method, endpoint, timestamp, nonce, body_hash.to_hex()).as_bytes())
+}
+
+/// Recover key server, which operator has signed administrative request. None if signature is invalid, or
+/// the request has been signed more than ADMIN_REQUEST_WINDOW seconds before (or after) `now`.
+pub fn recover_admin_signer(signature: &AdminRequestSignature, now: u64) -> Option<NodeId> {
+	if signature.timestamp.saturating_add(ADMIN_REQUEST_WINDOW) < now || signature.timestamp > now.saturating_add(ADMIN_REQUEST_WINDOW) {
+		return None;
+	}
+
+	let request_hash = admin_request_hash(&signature.method, &signature.endpoint, signature.timestamp, signature.nonce, &signature.body_hash);
+	ethkey::recover(&signature.signature, &request_hash).ok()
+}
+
+/// Process administrative request on this (master) node, which operator has signed the request. Admin session
+/// is signed with this node key, so that other session participants could check that master node has started it.
+/// `on_started` is called when admin session is started.
+pub fn process_request<F: FnOnce()>(cluster: &ClusterClient, key_storage: &KeyStorage, self_key_pair: &NodeKeyPair, request: AdminRequest, on_started: F) -> Result<AdminResponse, Error> {
+	let admin_signature = self_key_pair.sign(&keccak(&**self_key_pair.public()))?;
+	match request {
+		AdminRequest::ShareBootstrap(new_node, after) => {
+			let batch = share_bootstrap_session::select_batch(key_storage, after.as_ref())?;
+			if batch.is_empty() {
+				return Ok(AdminResponse::ShareBootstrap(batch, BTreeSet::new()));
+			}
+
+			let session = cluster.new_share_bootstrap_session(admin_signature, new_node, batch.clone())?;
+			on_started();
+			session.wait(None).map(|moved_keys| AdminResponse::ShareBootstrap(batch, moved_keys))
+		},
+		AdminRequest::KeyExport(key_id, targets, threshold) => {
+			let session = cluster.new_key_export_session(admin_signature, key_id, targets, threshold)?;
+			on_started();
+			session.wait(None).map(AdminResponse::KeyExport)
+		},
+	}
+}
+
+impl From<AdminRequest> for message::AdminRequest {
+	fn from(request: AdminRequest) -> Self {
+		match request {
+			AdminRequest::ShareBootstrap(new_node, after) => message::AdminRequest::ShareBootstrap(new_node.into(), after.map(Into::into)),
+			AdminRequest::KeyExport(key_id, targets, threshold) => message::AdminRequest::KeyExport(key_id.into(),
+				targets.into_iter().map(Into::into).collect(), threshold),
+		}
+	}
+}
+
+impl From<message::AdminRequest> for AdminRequest {
+	fn from(request: message::AdminRequest) -> Self {
+		match request {
+			message::AdminRequest::ShareBootstrap(new_node, after) => AdminRequest::ShareBootstrap(new_node.into(), after.map(Into::into)),
+			message::AdminRequest::KeyExport(key_id, targets, threshold) => AdminRequest::KeyExport(key_id.into(),
+				targets.into_iter().map(Into::into).collect(), threshold),
+		}
+	}
+}
+
+impl From<AdminRequestSignature> for message::AdminRequestSignature {
+	fn from(signature: AdminRequestSignature) -> Self {
+		message::AdminRequestSignature {
+			method: signature.method,
+			endpoint: signature.endpoint,
+			timestamp: signature.timestamp,
+			nonce: signature.nonce,
+			body_hash: signature.body_hash.into(),
+			signature: signature.signature.into(),
+		}
+	}
+}
+
+impl From<message::AdminRequestSignature> for AdminRequestSignature {
+	fn from(signature: message::AdminRequestSignature) -> Self {
+		AdminRequestSignature {
+			method: signature.method,
+			endpoint: signature.endpoint,
+			timestamp: signature.timestamp,
+			nonce: signature.nonce,
+			body_hash: signature.body_hash.into(),
+			signature: signature.signature.into(),
+		}
+	}
+}
+
+impl From<Result<AdminResponse, Error>> for message::AdminRequestStatus {
+	fn from(result: Result<AdminResponse, Error>) -> Self {
+		match result {
+			Ok(AdminResponse::ShareBootstrap(batch, moved_keys)) => message::AdminRequestStatus::ShareBootstrapCompleted(
+				batch.into_iter().map(Into::into).collect(),
+				moved_keys.into_iter().map(Into::into).collect()),
+			Ok(AdminResponse::KeyExport(key_shares)) => message::AdminRequestStatus::KeyExportCompleted(
+				key_shares.into_iter().map(Into::into).collect()),
+			Err(err) => message::AdminRequestStatus::Failed(err.clone().into(), Some(err.code().into())),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeSet;
+	use ethkey::{self, Random, Generator};
+	use hash::{keccak, KECCAK_EMPTY};
+	use key_server_cluster::{Error, ErrorCode, SessionId, AdminRequestSignature};
+	use key_server_cluster::message::{self, AdminRequestStatus};
+	use super::{ForwardedRequests, ForwardedRequestState, AdminResponse, ADMIN_REQUEST_WINDOW, admin_request_hash, recover_admin_signer};
+
+	fn status(request_id: u64, status: AdminRequestStatus) -> message::ForwardedAdminRequestStatus {
+		message::ForwardedAdminRequestStatus {
+			request_id: request_id,
+			status: status,
+		}
+	}
+
+	#[test]
+	fn forwarded_request_is_completed_by_master_status_updates() {
+		let requests = ForwardedRequests::default();
+		let master = Random.generate().unwrap().public().clone();
+		let (request_id, request) = requests.insert(master.clone());
+		assert_eq!(request.state(), ForwardedRequestState::WaitingForMaster);
+
+		requests.on_status(&master, status(request_id, AdminRequestStatus::Started)).unwrap();
+		assert_eq!(request.state(), ForwardedRequestState::Started);
+
+		let batch: BTreeSet<SessionId> = vec![1.into(), 2.into()].into_iter().collect();
+		let moved_keys: BTreeSet<SessionId> = vec![1.into()].into_iter().collect();
+		requests.on_status(&master, status(request_id, AdminRequestStatus::ShareBootstrapCompleted(
+			batch.iter().cloned().map(Into::into).collect(), moved_keys.iter().cloned().map(Into::into).collect()))).unwrap();
+		assert_eq!(request.state(), ForwardedRequestState::Finished);
+		assert_eq!(request.wait(None), Ok(AdminResponse::ShareBootstrap(batch, moved_keys)));
+
+		// completed request is forgotten
+		assert_eq!(requests.on_status(&master, status(request_id, AdminRequestStatus::Started)), Err(Error::InvalidSessionId));
+	}
+
+	#[test]
+	fn forwarded_request_status_is_only_accepted_from_master() {
+		let requests = ForwardedRequests::default();
+		let master = Random.generate().unwrap().public().clone();
+		let other_node = Random.generate().unwrap().public().clone();
+		let (request_id, request) = requests.insert(master.clone());
+		assert_eq!(requests.on_status(&other_node, status(request_id, AdminRequestStatus::Started)), Err(Error::InvalidNodeForRequest));
+		assert_eq!(request.state(), ForwardedRequestState::WaitingForMaster);
+
+		requests.on_status(&master, status(request_id, AdminRequestStatus::Failed("error".into(), Some(ErrorCode::Storage.into())))).unwrap();
+		assert_eq!(request.state(), ForwardedRequestState::Failed);
+		assert_eq!(request.wait(None), Err(Error::Remote(ErrorCode::Storage, "error".into())));
+	}
+
+	#[test]
+	fn forwarded_request_fails_when_master_disconnects() {
+		let requests = ForwardedRequests::default();
+		let master = Random.generate().unwrap().public().clone();
+		let other_master = Random.generate().unwrap().public().clone();
+		let (_, request) = requests.insert(master.clone());
+		let (_, other_request) = requests.insert(other_master);
+
+		requests.on_node_disconnected(&master);
+		assert_eq!(request.state(), ForwardedRequestState::Failed);
+		assert_eq!(request.wait(None), Err(Error::NodeDisconnected));
+		assert_eq!(other_request.state(), ForwardedRequestState::WaitingForMaster);
+	}
+
+	#[test]
+	fn admin_signer_is_only_recovered_within_window() {
+		let key_pair = Random.generate().unwrap();
+		let now = 1_000_000;
+		let signature = |timestamp| AdminRequestSignature {
+			method: "POST".into(),
+			endpoint: "/bootstrap".into(),
+			timestamp: timestamp,
+			nonce: 42,
+			body_hash: KECCAK_EMPTY,
+			signature: ethkey::sign(key_pair.secret(), &admin_request_hash("POST", "/bootstrap", timestamp, 42, &KECCAK_EMPTY)).unwrap(),
+		};
+
+		assert_eq!(recover_admin_signer(&signature(now), now), Some(key_pair.public().clone()));
+		assert_eq!(recover_admin_signer(&signature(now - ADMIN_REQUEST_WINDOW), now), Some(key_pair.public().clone()));
+		assert_eq!(recover_admin_signer(&signature(now + ADMIN_REQUEST_WINDOW), now), Some(key_pair.public().clone()));
+		assert_eq!(recover_admin_signer(&signature(now - ADMIN_REQUEST_WINDOW - 1), now), None);
+		assert_eq!(recover_admin_signer(&signature(now + ADMIN_REQUEST_WINDOW + 1), now), None);
+
+		// signature does not match the request
+		let mut other_request = signature(now);
+		other_request.nonce = 43;
+		assert!(recover_admin_signer(&other_request, now) != Some(key_pair.public().clone()));
+
+		// signature does not match the request body
+		let mut other_body = signature(now);
+		other_body.body_hash = keccak("{}");
+		assert!(recover_admin_signer(&other_body, now) != Some(key_pair.public().clone()));
+	}
+}
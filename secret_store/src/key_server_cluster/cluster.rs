@@ -16,44 +16,96 @@
 
 use std::io;
 use std::time;
+use std::thread;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::collections::{BTreeMap, BTreeSet};
 use std::collections::btree_map::Entry;
-use std::net::{SocketAddr, IpAddr};
+use std::net::SocketAddr;
 use futures::{finished, failed, Future, Stream, BoxFuture};
 use futures_cpupool::CpuPool;
 use parking_lot::{RwLock, Mutex};
 use tokio_io::IoFuture;
-use tokio_core::reactor::{Handle, Remote, Interval};
+use tokio_core::reactor::{Handle, Remote, Interval, Timeout};
 use tokio_core::net::{TcpListener, TcpStream};
 use ethkey::{Public, KeyPair, Signature, Random, Generator};
 use bigint::hash::H256;
-use key_server_cluster::{Error, NodeId, SessionId, AclStorage, KeyStorage, KeyServerSet, NodeKeyPair};
+use audit_log::unix_timestamp;
+use key_server_set::resolve_node_address;
+use key_server_cluster::{Error, NodeId, SessionId, AclStorage, KeyStorage, KeyServerSet, NodeKeyPair, SessionsRateLimits, ClusterTimeouts, ClusterGauges,
+	PeerRateLimits, ClusterMetrics, ClusterHealth, PeerFilter, SessionJournal, AdminRequestSignature, is_key_storage_available};
 use key_server_cluster::cluster_sessions::{ClusterSession, ClusterSessions, GenerationSessionWrapper, EncryptionSessionWrapper,
-	DecryptionSessionWrapper, SigningSessionWrapper};
-use key_server_cluster::message::{self, Message, ClusterMessage, GenerationMessage, EncryptionMessage, DecryptionMessage,
-	SigningMessage, ConsensusMessage};
+	DecryptionSessionWrapper, ReEncryptionSessionWrapper, SigningSessionWrapper, EcdsaSigningSessionWrapper, ShareRecoverySessionWrapper, ShareRefreshSessionWrapper,
+	KeyDerivationSessionWrapper, KeyDeletionSessionWrapper, ShareMoveSessionWrapper, ServerKeyRetrievalSessionWrapper, ShareBootstrapSessionWrapper,
+	KeyExportSessionWrapper};
+use key_server_cluster::message::{self, Message, ClusterMessage, GenerationMessage, EncryptionMessage, DecryptionMessage, ReEncryptionMessage,
+	SigningMessage, EcdsaSigningMessage, ShareRecoveryMessage, ShareRefreshMessage, KeyDerivationMessage, KeyDeletionMessage, ShareMoveMessage,
+	ServerKeyRetrievalMessage, ShareBootstrapMessage, KeyExportMessage, ConsensusMessage};
 use key_server_cluster::generation_session::{Session as GenerationSession, SessionState as GenerationSessionState};
 #[cfg(test)]
 use key_server_cluster::generation_session::SessionImpl as GenerationSessionImpl;
 use key_server_cluster::decryption_session::{Session as DecryptionSession, DecryptionSessionId};
+use key_server_cluster::re_encryption_session::Session as ReEncryptionSession;
 use key_server_cluster::encryption_session::{Session as EncryptionSession, SessionState as EncryptionSessionState};
 use key_server_cluster::signing_session::{Session as SigningSession, SigningSessionId};
-use key_server_cluster::io::{DeadlineStatus, ReadMessage, SharedTcpStream, read_encrypted_message, WriteMessage, write_encrypted_message};
+use key_server_cluster::ecdsa_signing_session::Session as EcdsaSigningSession;
+use key_server_cluster::share_recovery_session::{Session as ShareRecoverySession, SessionState as ShareRecoverySessionState};
+use key_server_cluster::share_refresh_session::{Session as ShareRefreshSession, SessionState as ShareRefreshSessionState};
+use key_server_cluster::key_derivation_session::{Session as KeyDerivationSession, SessionState as KeyDerivationSessionState};
+use key_server_cluster::key_deletion_session::{Session as KeyDeletionSession, SessionState as KeyDeletionSessionState};
+use key_server_cluster::share_move_session::{Session as ShareMoveSession, SessionState as ShareMoveSessionState};
+use key_server_cluster::server_key_retrieval_session::{Session as ServerKeyRetrievalSession, SessionState as ServerKeyRetrievalSessionState};
+use key_server_cluster::share_bootstrap_session::{self, Session as ShareBootstrapSession, SessionState as ShareBootstrapSessionState};
+use key_server_cluster::key_export_session::{Session as KeyExportSession, SessionState as KeyExportSessionState};
+#[cfg(any(test, feature = "fault-injection"))]
+use key_server_cluster::fault_injection::FaultInjector;
+use key_server_cluster::admin_forwarding::{self, AdminRequest, AdminResponse, ForwardedRequest, ForwardedRequests};
+use key_server_cluster::math;
+use key_server_cluster::message_retransmitter::{MessageRetransmitter, RETRANSMISSION_INTERVAL};
+use key_server_cluster::message_scheduler::MessageScheduler;
+use key_server_cluster::deferred_messages::DeferredMessages;
+use key_server_cluster::peer_latency::{PeerLatencies, order_by_expected_delay};
+use key_server_cluster::peer_rate_limiter::{PeerRateLimiter, MessageClass};
+use key_server_cluster::reconnect_backoff::ReconnectBackoff;
+use key_server_cluster::io::{DeadlineStatus, ReadMessage, SharedTcpStream, SignedMessage, ReceivedSequences, read_encrypted_message,
+	WriteMessage, write_encrypted_message, message_id, HANDSHAKE_MESSAGE_SEQUENCE, SHARES_INVENTORY_HEADER_VERSION, ACKNOWLEDGEMENTS_HEADER_VERSION};
 use key_server_cluster::net::{accept_connection as net_accept_connection, connect as net_connect, Connection as NetConnection};
 
 /// Maintain interval (seconds). Every MAINTAIN_INTERVAL seconds node:
 /// 1) checks if connected nodes are responding to KeepAlive messages
-/// 2) tries to connect to disconnected nodes
+/// 2) tries to connect to disconnected nodes (if allowed by reconnect backoff)
 /// 3) checks if enc/dec sessions are time-outed
+/// 4) sends key shares inventory to connected nodes (every SHARES_INVENTORY_INTERVAL seconds)
+/// 5) checks if it is reachable through its external address (every EXTERNAL_REACHABILITY_CHECK_INTERVAL seconds)
+/// 6) purges key shares, removed more than removed keys retention time ago (every REMOVED_KEYS_PURGE_INTERVAL seconds)
 const MAINTAIN_INTERVAL: u64 = 10;
 
+/// Every SHARES_INVENTORY_INTERVAL seconds node sends to every connected node ids of keys, for which this node
+/// is listed as key holder. Receiver starts share recovery sessions for keys, which are missing from its key storage.
+const SHARES_INVENTORY_INTERVAL: u64 = 600;
+/// Maximal number of share recovery sessions, started when single inventory is received.
+const MAX_INVENTORY_RECOVERY_SESSIONS: usize = 4;
+/// Every EXTERNAL_REACHABILITY_CHECK_INTERVAL seconds node checks if it is reachable through its external address.
+const EXTERNAL_REACHABILITY_CHECK_INTERVAL: u64 = 300;
+/// Every REMOVED_KEYS_PURGE_INTERVAL seconds node purges key shares, soft-removed by share move && key deletion sessions,
+/// if they have been removed before the retention time.
+const REMOVED_KEYS_PURGE_INTERVAL: u64 = 3600;
+/// Soft-removed key shares are kept for DEFAULT_REMOVED_KEYS_RETENTION seconds (7 days), unless configured otherwise.
+const DEFAULT_REMOVED_KEYS_RETENTION: u64 = 7 * 24 * 60 * 60;
+
 /// When no messages have been received from node within KEEP_ALIVE_SEND_INTERVAL seconds,
 /// we must send KeepAlive message to the node to check if it still responds to messages.
 const KEEP_ALIVE_SEND_INTERVAL: u64 = 30;
 /// When no messages have been received from node within KEEP_ALIVE_DISCONNECT_INTERVAL seconds,
 /// we must treat this node as non-responding && disconnect from it.
 const KEEP_ALIVE_DISCONNECT_INTERVAL: u64 = 60;
+/// Max number of session messages, which are waiting to be sent to single node. When this number is reached,
+/// session, which is sending new message to the node, fails with QueueOverflow error.
+const MAX_QUEUED_SESSION_MESSAGES: usize = 4096;
+/// When sessions processing is paused, session message is treated as retried every PAUSED_MESSAGE_RETRY_INTERVAL milliseconds.
+const PAUSED_MESSAGE_RETRY_INTERVAL: u64 = 100;
+/// Delayed admin sessions messages are processed every ADMIN_MESSAGES_DRAIN_INTERVAL milliseconds.
+const ADMIN_MESSAGES_DRAIN_INTERVAL: u64 = 50;
 
 /// Encryption sesion timeout interval. It works
 /// Empty future.
@@ -67,10 +119,58 @@ pub trait ClusterClient: Send + Sync {
 	fn new_generation_session(&self, session_id: SessionId, author: Public, threshold: usize) -> Result<Arc<GenerationSession>, Error>;
 	/// Start new encryption session.
 	fn new_encryption_session(&self, session_id: SessionId, requestor_signature: Signature, common_point: Public, encrypted_point: Public) -> Result<Arc<EncryptionSession>, Error>;
+	/// Start new re-keying session. Is used to replace stored document key with the new random one, encrypted with the same server key.
+	fn new_rekeying_session(&self, session_id: SessionId) -> Result<Arc<EncryptionSession>, Error>;
 	/// Start new decryption session.
 	fn new_decryption_session(&self, session_id: SessionId, requestor_signature: Signature, is_shadow_decryption: bool) -> Result<Arc<DecryptionSession>, Error>;
+	/// Start new re-encryption session. Is used to re-encrypt document key with target public key, without revealing the document key.
+	fn new_reencryption_session(&self, session_id: SessionId, requestor_signature: Signature, target_public: Public, target_signature: Signature) -> Result<Arc<ReEncryptionSession>, Error>;
 	/// Start new signing session.
 	fn new_signing_session(&self, session_id: SessionId, requestor_signature: Signature, message_hash: H256) -> Result<Arc<SigningSession>, Error>;
+	/// Start new ECDSA signing session.
+	fn new_ecdsa_signing_session(&self, session_id: SessionId, requestor_signature: Signature, message_hash: H256) -> Result<Arc<EcdsaSigningSession>, Error>;
+	/// Start new share recovery session. Is used to re-derive corrupted secret share of this node from shares of other nodes.
+	fn new_share_recovery_session(&self, session_id: SessionId) -> Result<Arc<ShareRecoverySession>, Error>;
+	/// Start new share refresh session. Is used to re-randomize secret shares of all key holders, leaving joint secret the same.
+	fn new_share_refresh_session(&self, session_id: SessionId) -> Result<Arc<ShareRefreshSession>, Error>;
+	/// Start new key derivation session. Is used to derive child key from previously generated parent key.
+	fn new_key_derivation_session(&self, parent_key_id: SessionId, requestor_signature: Signature, derivation_path: H256) -> Result<Arc<KeyDerivationSession>, Error>;
+	/// Start new key deletion session. Is used to delete key shares of previously generated key from all key holders.
+	fn new_key_deletion_session(&self, session_id: SessionId, requestor_signature: Signature) -> Result<Arc<KeyDeletionSession>, Error>;
+	/// Start new share move session. Is used to move key share of this node to the node, which is not yet holding the key.
+	fn new_share_move_session(&self, session_id: SessionId, new_node: NodeId) -> Result<Arc<ShareMoveSession>, Error>;
+	/// Start new server key retrieval session. Is used to restore public portion of server key, attested by key holders.
+	fn new_server_key_retrieval_session(&self, session_id: SessionId, requestor_signature: Signature) -> Result<Arc<ServerKeyRetrievalSession>, Error>;
+	/// Start new share bootstrap session. Is used to move key shares of this node for the given batch of keys to the new node,
+	/// which is not yet holding these keys. Session must be authorized by keccak(self_public), signed with this node key.
+	fn new_share_bootstrap_session(&self, admin_signature: Signature, new_node: NodeId, keys: BTreeSet<SessionId>) -> Result<Arc<ShareBootstrapSession>, Error>;
+	/// Start new key export session. Is used to re-share the key among nodes of other cluster, so that every target node
+	/// receives its own key share. Session must be authorized by keccak(self_public), signed with this node key.
+	fn new_key_export_session(&self, admin_signature: Signature, session_id: SessionId, targets: BTreeSet<NodeId>, threshold: usize) -> Result<Arc<KeyExportSession>, Error>;
+	/// Forward administrative request to the master node, which operator has signed the request. Admin session is started
+	/// on the master node, which reports status updates back to this node.
+	fn forward_admin_request(&self, master: NodeId, admin_signature: AdminRequestSignature, request: AdminRequest) -> Result<Arc<ForwardedRequest>, Error>;
+
+	/// Pause processing of all sessions messages. Session messages are deferred until resumed.
+	fn pause_sessions(&self);
+	/// Resume processing of sessions messages.
+	fn resume_sessions(&self);
+	/// Stop accepting new sessions (except for share moves && bootstraps). Already running sessions are not affected.
+	fn start_draining(&self);
+	/// Accept new sessions again.
+	fn stop_draining(&self);
+	/// Get number of active sessions (except for share moves && bootstraps).
+	fn active_sessions_count(&self) -> usize;
+	/// Close connections to nodes, which are denied by peer filter. Sessions, involving these nodes, are failed.
+	fn apply_peer_filter(&self);
+	/// Get cluster metrics in Prometheus text exposition format.
+	fn metrics(&self) -> String;
+	/// Get health of this node && its view of the cluster.
+	fn health(&self) -> ClusterHealth;
+
+	/// Get injector of faults into messages, received by this node.
+	#[cfg(any(test, feature = "fault-injection"))]
+	fn fault_injector(&self) -> &FaultInjector;
 
 	/// Ask node to make 'faulty' generation sessions.
 	#[cfg(test)]
@@ -89,6 +189,16 @@ pub trait Cluster: Send + Sync {
 	fn broadcast(&self, message: Message) -> Result<(), Error>;
 	/// Send message to given node.
 	fn send(&self, to: &NodeId, message: Message) -> Result<(), Error>;
+	/// Get nodes, which are participating in the session.
+	fn nodes(&self) -> BTreeSet<NodeId>;
+	/// Get number of bytes, sent && received by the session.
+	fn traffic(&self) -> (u64, u64);
+	/// When session message of given size (in bytes) has been received. Returns error if session has exceeded traffic limit.
+	fn on_message_received(&self, size: usize) -> Result<(), Error>;
+	/// Order nodes by preference: nodes, which are expected to respond faster, come first.
+	fn order_by_preference(&self, nodes: BTreeSet<NodeId>) -> Vec<NodeId> {
+		nodes.into_iter().collect()
+	}
 }
 
 /// Cluster initialization parameters.
@@ -108,6 +218,29 @@ pub struct ClusterConfiguration {
 	pub key_storage: Arc<KeyStorage>,
 	/// Reference to ACL storage
 	pub acl_storage: Arc<AclStorage>,
+	/// Allow/deny lists of cluster connections.
+	pub peer_filter: Arc<PeerFilter>,
+	/// Limits of decryption && signing sessions, started by this node.
+	pub rate_limits: SessionsRateLimits,
+	/// Limits of messages, received from every other node.
+	pub peer_rate_limits: PeerRateLimits,
+	/// Select nodes for sessions in their natural order, ignoring measured latencies && load of nodes.
+	pub deterministic_nodes_selection: bool,
+	/// Nodes, which are not allowed to start share administration sessions.
+	pub read_only_nodes: BTreeSet<NodeId>,
+	/// Timeouts of sessions && session messages.
+	pub timeouts: ClusterTimeouts,
+	/// Address, which is used by other nodes to connect to this node. None if unknown.
+	pub external_address: Option<SocketAddr>,
+	/// Max share (in percents) of admin sessions messages in processed messages, while there are user sessions messages.
+	/// None if admin sessions messages are never delayed.
+	pub admin_messages_share: Option<usize>,
+	/// Time (in seconds), during which soft-removed key shares are kept in the key storage. None means DEFAULT_REMOVED_KEYS_RETENTION.
+	pub removed_keys_retention: Option<u64>,
+	/// Max number of bytes, sent && received by single session on this node. None if not limited.
+	pub max_session_traffic: Option<u64>,
+	/// Journal of sessions messages && state transitions. None if journal is disabled.
+	pub session_journal: Option<Arc<SessionJournal>>,
 }
 
 /// Cluster state.
@@ -151,6 +284,31 @@ pub struct ClusterData {
 	connections: ClusterConnections,
 	/// Active sessions data.
 	sessions: ClusterSessions,
+	/// Time, when key shares inventory has been sent last time.
+	shares_inventory_time: Mutex<time::Instant>,
+	/// Time, when removed key shares have been purged last time. None if they have not been purged yet.
+	removed_keys_purge_time: Mutex<Option<time::Instant>>,
+	/// Time, when external reachability has been checked last time. None if it has not been checked yet.
+	external_reachability_check_time: Mutex<Option<time::Instant>>,
+	/// Result of the last external reachability check.
+	is_externally_reachable: Mutex<Option<bool>>,
+	/// Scheduler of admin sessions messages.
+	scheduler: MessageScheduler<(Arc<Connection>, Message)>,
+	/// Scheduler of reconnect attempts.
+	reconnect_backoff: ReconnectBackoff,
+	/// Limiter of messages, received from other nodes.
+	peer_rate_limiter: PeerRateLimiter,
+	/// Round-trip times of connected nodes.
+	peer_latencies: PeerLatencies,
+	/// Administrative requests, forwarded by this node to master nodes.
+	forwarded_requests: ForwardedRequests,
+	/// Retransmitter of unacknowledged session messages.
+	retransmitter: MessageRetransmitter<Message>,
+	/// Session messages, deferred while sessions processing is paused.
+	deferred_messages: DeferredMessages<(Arc<Connection>, Message)>,
+	/// Injector of faults into received messages.
+	#[cfg(any(test, feature = "fault-injection"))]
+	fault_injector: FaultInjector,
 }
 
 /// Connections that are forming the cluster.
@@ -159,6 +317,8 @@ pub struct ClusterConnections {
 	pub self_node_id: NodeId,
 	/// All known other key servers.
 	pub key_server_set: Arc<KeyServerSet>,
+	/// Allow/deny lists of cluster connections.
+	pub peer_filter: Arc<PeerFilter>,
 	/// Connections data.
 	pub data: RwLock<ClusterConnectionsData>,
 }
@@ -177,6 +337,10 @@ struct ClusterViewCore {
 	cluster: Arc<ClusterData>,
 	/// Subset of nodes, required for this session.
 	nodes: BTreeSet<NodeId>,
+	/// Number of bytes, sent by this session.
+	sent_bytes: u64,
+	/// Number of bytes, received by this session.
+	received_bytes: u64,
 }
 
 /// Connection to single node.
@@ -191,8 +355,20 @@ pub struct Connection {
 	stream: SharedTcpStream,
 	/// Connection key.
 	key: KeyPair,
+	/// Self node key, used to sign messages.
+	self_key_pair: Arc<NodeKeyPair>,
+	/// Negotiated protocol version.
+	version: u8,
 	/// Last message time.
 	last_message_time: Mutex<time::Instant>,
+	/// Number of session messages, which are waiting to be sent to the node.
+	queued_session_messages: Arc<AtomicUsize>,
+	/// Cluster metrics, updated with number of bytes sent to the node.
+	metrics: Arc<ClusterMetrics>,
+	/// Sequence number of the next message, sent to the node.
+	next_sequence: AtomicUsize,
+	/// Sequence numbers of messages, received from the node.
+	received_sequences: Mutex<ReceivedSequences>,
 }
 
 impl ClusterCore {
@@ -233,6 +409,7 @@ impl ClusterCore {
 
 		// schedule maintain procedures
 		ClusterCore::schedule_maintain(&self.handle, self.data.clone());
+		ClusterCore::schedule_retransmissions(&self.handle, self.data.clone());
 
 		Ok(())
 	}
@@ -308,6 +485,36 @@ impl ClusterCore {
 		d.spawn(interval);
 	}
 
+	/// Schedule retransmission of unacknowledged session messages.
+	fn schedule_retransmissions(handle: &Handle, data: Arc<ClusterData>) {
+		let d = data.clone();
+		let interval: BoxedEmptyFuture = Interval::new(time::Duration::from_millis(RETRANSMISSION_INTERVAL), handle)
+			.expect("failed to create interval")
+			.and_then(move |_| Ok(ClusterCore::retransmit_messages(data.clone())))
+			.for_each(|_| Ok(()))
+			.then(|_| finished(()))
+			.boxed();
+
+		d.spawn(interval);
+	}
+
+	/// Retransmit session messages, which have not been acknowledged by receivers within RETRANSMISSION_INTERVAL.
+	/// Messages to disconnected nodes are retransmitted when connection is established again.
+	fn retransmit_messages(data: Arc<ClusterData>) {
+		for (node, message) in data.retransmitter.messages_to_retransmit() {
+			let connection = match data.connection(&node) {
+				Some(connection) => connection,
+				None => continue,
+			};
+
+			trace!(target: "secretstore_net", "{}: retransmitting message {} to {}", data.self_key_pair.public(), message, node);
+			match connection.send_session_message(message) {
+				Ok((_, future)) => data.spawn(future),
+				Err(err) => warn!(target: "secretstore_net", "{}: failed to retransmit message to {}: {}", data.self_key_pair.public(), node, err),
+			}
+		}
+	}
+
 	/// Execute maintain procedures.
 	fn maintain(data: Arc<ClusterData>) {
 		trace!(target: "secretstore_net", "{}: executing maintain procedures", data.self_key_pair.public());
@@ -315,6 +522,9 @@ impl ClusterCore {
 		ClusterCore::keep_alive(data.clone());
 		ClusterCore::connect_disconnected_nodes(data.clone());
 		data.sessions.stop_stalled_sessions();
+		ClusterCore::send_shares_inventory(data.clone());
+		ClusterCore::check_external_reachability(data.clone());
+		ClusterCore::purge_removed_keys(data);
 	}
 
 	/// Called for every incomming mesage.
@@ -322,9 +532,40 @@ impl ClusterCore {
 		connection
 			.read_message()
 			.then(move |result|
-				match result {
-					Ok((_, Ok(message))) => {
-						ClusterCore::process_connection_message(data.clone(), connection.clone(), message);
+				match result.map(|(stream, message)| (stream, message.and_then(|message| {
+					let size = message.size();
+					data.sessions.metrics().on_bytes_received(connection.node_id(), size);
+					connection.verify_message(message).map(|message| (message, size))
+				}))) {
+					Ok((_, Err(Error::InvalidMessageSignature))) => {
+						// message could have been injected into connection => close it
+						warn!(target: "secretstore_net", "{}: dropping message with invalid signature from node {}", data.self_key_pair.public(), connection.node_id());
+						ClusterCore::on_connection_lost(data, &connection);
+						finished(Err(Error::InvalidMessageSignature)).boxed()
+					},
+					Ok((_, Ok((message, size)))) => {
+						if !data.config.peer_filter.is_allowed(connection.node_id()) {
+							// close connection
+							warn!(target: "secretstore_net", "{}: dropping message {} from denied node {}", data.self_key_pair.public(), message, connection.node_id());
+							ClusterCore::on_connection_lost(data, &connection);
+							return finished(Ok(())).boxed();
+						}
+
+						let message_class = MessageClass::of(&message);
+						if !data.peer_rate_limiter.on_message_received(connection.node_id(), message_class) {
+							// close connection && do not accept/make connections to this node until throttle time passes
+							warn!(target: "secretstore_net", "{}: node {} has exceeded rate limit of {} messages and is temporary disconnected",
+								data.self_key_pair.public(), connection.node_id(), message_class.name());
+							data.sessions.metrics().on_peer_throttled(connection.node_id(), message_class.name());
+							ClusterCore::on_connection_lost(data, &connection);
+							return finished(Ok(())).boxed();
+						}
+
+						match data.sessions.on_message_received(&message, size) {
+							Ok(()) => ClusterCore::process_received_message(data.clone(), connection.clone(), message),
+							Err(err) => warn!(target: "secretstore_net", "{}: dropping message {} from node {}, session has been cancelled: {}",
+								data.self_key_pair.public(), message, connection.node_id(), err),
+						}
 						// continue serving connection
 						data.spawn(ClusterCore::process_connection_messages(data.clone(), connection));
 						finished(Ok(())).boxed()
@@ -338,43 +579,255 @@ impl ClusterCore {
 					Err(err) => {
 						warn!(target: "secretstore_net", "{}: network error '{}' when reading message from node {}", data.self_key_pair.public(), err, connection.node_id());
 						// close connection
-						data.connections.remove(connection.node_id(), connection.is_inbound());
+						ClusterCore::on_connection_lost(data, &connection);
 						failed(err).boxed()
 					},
 				}
 			).boxed()
 	}
 
-	/// Send keepalive messages to every othe node.
+	/// Called when connection is closed. Sessions, involving the node, are notified immediately && reconnect is scheduled.
+	fn on_connection_lost(data: Arc<ClusterData>, connection: &Connection) {
+		if !data.connections.remove(connection.node_id(), connection.is_inbound()) {
+			// connection has been already replaced or removed
+			return;
+		}
+
+		data.sessions.on_connection_timeout(connection.node_id());
+		data.forwarded_requests.on_node_disconnected(connection.node_id());
+		data.reconnect_backoff.on_disconnected(connection.node_id());
+		data.peer_latencies.on_disconnected(connection.node_id());
+		ClusterCore::schedule_reconnect(data);
+	}
+
+	/// Try to connect to disconnected nodes when the nearest reconnect attempt is allowed.
+	fn schedule_reconnect(data: Arc<ClusterData>) {
+		let delay = match data.reconnect_backoff.next_attempt_delay() {
+			Some(delay) => delay,
+			None => return,
+		};
+
+		let d = data.clone();
+		d.handle.spawn(move |handle| Timeout::new(delay, handle)
+			.expect("failed to create timeout")
+			.then(move |_| {
+				ClusterCore::connect_disconnected_nodes(data);
+				finished(())
+			}));
+	}
+
+	/// Send keepalive messages to every othe node. KeepAlive messages are also used to measure round-trip time to nodes.
 	fn keep_alive(data: Arc<ClusterData>) {
 		for connection in data.connections.active_connections() {
 			let last_message_diff = time::Instant::now() - connection.last_message_time();
 			if last_message_diff > time::Duration::from_secs(KEEP_ALIVE_DISCONNECT_INTERVAL) {
-				data.connections.remove(connection.node_id(), connection.is_inbound());
-				data.sessions.on_connection_timeout(connection.node_id());
+				warn!(target: "secretstore_net", "{}: node {} is not responding to KeepAlive messages", data.self_key_pair.public(), connection.node_id());
+				ClusterCore::on_connection_lost(data.clone(), &connection);
 			}
-			else if last_message_diff > time::Duration::from_secs(KEEP_ALIVE_SEND_INTERVAL) {
+			else if last_message_diff > time::Duration::from_secs(KEEP_ALIVE_SEND_INTERVAL) || data.peer_latencies.is_probe_required(connection.node_id()) {
+				data.peer_latencies.on_probe_sent(connection.node_id());
 				data.spawn(connection.send_message(Message::Cluster(ClusterMessage::KeepAlive(message::KeepAlive {}))));
 			}
 		}
 	}
 
-	/// Try to connect to every disconnected node.
+	/// Try to connect to every disconnected node, unless reconnect attempt has been made recently.
 	fn connect_disconnected_nodes(data: Arc<ClusterData>) {
 		data.connections.update_nodes_set();
 		for (node_id, node_address) in data.connections.disconnected_nodes() {
-			if data.config.allow_connecting_to_higher_nodes || data.self_key_pair.public() < &node_id {
+			if (data.config.allow_connecting_to_higher_nodes || data.self_key_pair.public() < &node_id)
+				&& !data.peer_rate_limiter.is_throttled(&node_id)
+				&& data.reconnect_backoff.begin_attempt(&node_id) {
 				ClusterCore::connect(data.clone(), node_address);
 			}
 		}
 	}
 
+	/// Connect to this node through its external address, if it has not been checked within EXTERNAL_REACHABILITY_CHECK_INTERVAL seconds.
+	fn check_external_reachability(data: Arc<ClusterData>) {
+		let external_address = match data.config.external_address {
+			Some(external_address) => external_address,
+			None => return,
+		};
+
+		{
+			let mut check_time = data.external_reachability_check_time.lock();
+			if check_time.map(|t| time::Instant::now() - t < time::Duration::from_secs(EXTERNAL_REACHABILITY_CHECK_INTERVAL)).unwrap_or(false) {
+				return;
+			}
+			*check_time = Some(time::Instant::now());
+		}
+
+		data.handle.clone().spawn(move |handle| {
+			// handshake succeeds only if the node on the other side holds our key
+			let pool = data.pool.clone();
+			let self_node_id = data.self_key_pair.public().clone();
+			let check = net_connect(&external_address, handle, data.self_key_pair.clone(), vec![self_node_id].into_iter().collect())
+				.then(move |result| {
+					let is_reachable = match result {
+						Ok(DeadlineStatus::Meet(Ok(_))) => true,
+						_ => false,
+					};
+					if !is_reachable {
+						warn!(target: "secretstore_net", "{}: node is not reachable through its external address {}", data.self_key_pair.public(), external_address);
+					}
+					*data.is_externally_reachable.lock() = Some(is_reachable);
+					finished::<(), ()>(())
+				});
+			pool.spawn(check)
+		})
+	}
+
+	/// Purge soft-removed key shares, which have been kept longer than retention time, if it has not been done
+	/// within REMOVED_KEYS_PURGE_INTERVAL seconds.
+	fn purge_removed_keys(data: Arc<ClusterData>) {
+		{
+			let mut purge_time = data.removed_keys_purge_time.lock();
+			if purge_time.map(|t| time::Instant::now() - t < time::Duration::from_secs(REMOVED_KEYS_PURGE_INTERVAL)).unwrap_or(false) {
+				return;
+			}
+			*purge_time = Some(time::Instant::now());
+		}
+
+		let retention = data.config.removed_keys_retention.unwrap_or(DEFAULT_REMOVED_KEYS_RETENTION);
+		match data.config.key_storage.purge_removed(unix_timestamp().saturating_sub(retention)) {
+			Ok(ref purged) if purged.is_empty() => (),
+			Ok(purged) => trace!(target: "secretstore_net", "{}: purged {} removed key shares", data.self_key_pair.public(), purged.len()),
+			Err(err) => warn!(target: "secretstore_net", "{}: failed to purge removed key shares: {}", data.self_key_pair.public(), err),
+		}
+	}
+
+	/// Send key shares inventory to connected nodes, if it has not been sent within SHARES_INVENTORY_INTERVAL seconds.
+	fn send_shares_inventory(data: Arc<ClusterData>) {
+		{
+			let mut shares_inventory_time = data.shares_inventory_time.lock();
+			if time::Instant::now() - *shares_inventory_time < time::Duration::from_secs(SHARES_INVENTORY_INTERVAL) {
+				return;
+			}
+			*shares_inventory_time = time::Instant::now();
+		}
+
+		// older nodes do not understand inventory messages && read-only nodes are not allowed to recover shares
+		let connections: Vec<_> = data.connections.active_connections().into_iter()
+			.filter(|connection| connection.version >= SHARES_INVENTORY_HEADER_VERSION)
+			.filter(|connection| !data.config.read_only_nodes.contains(connection.node_id()))
+			.collect();
+		if connections.is_empty() {
+			return;
+		}
+
+		let documents = match data.config.key_storage.documents() {
+			Ok(documents) => documents,
+			Err(err) => {
+				warn!(target: "secretstore_net", "{}: failed to read key shares inventory: {}", data.self_key_pair.public(), err);
+				return;
+			},
+		};
+
+		let mut inventories: BTreeMap<NodeId, Vec<SessionId>> = BTreeMap::new();
+		for document in documents {
+			let key_share = match data.config.key_storage.get(&document) {
+				Ok(key_share) => key_share,
+				Err(_) => continue,
+			};
+			for connection in &connections {
+				if key_share.id_numbers.contains_key(connection.node_id()) {
+					inventories.entry(connection.node_id().clone()).or_insert_with(Vec::new).push(document.clone());
+				}
+			}
+		}
+
+		for connection in connections {
+			let keys = match inventories.remove(connection.node_id()) {
+				Some(keys) => keys,
+				None => continue,
+			};
+			data.spawn(connection.send_message(Message::Cluster(ClusterMessage::KeySharesInventory(message::KeySharesInventory {
+				keys: keys.into_iter().map(Into::into).collect(),
+			}))));
+		}
+	}
+
+	/// Process administrative request, forwarded by other node. Admin session is awaited in separate thread, because
+	/// it could take a long time to complete.
+	fn process_forwarded_admin_request(data: Arc<ClusterData>, connection: Arc<Connection>, message: &message::ForwardAdminRequest) {
+		let sender = connection.node_id().clone();
+		let request_id = message.request_id;
+		let admin_signature: AdminRequestSignature = message.admin_signature.clone().into();
+		let request: AdminRequest = message.request.clone().into();
+		trace!(target: "secretstore_net", "{}: received admin request {:?} forwarded by node {}", data.self_key_pair.public(), request, sender);
+
+		// only recent requests, signed by this node operator, are processed. Replays of the same request are
+		// refused by the forwarding node, which has accepted it
+		match admin_forwarding::recover_admin_signer(&admin_signature, unix_timestamp()) {
+			Some(ref signer) if signer == data.self_key_pair.public() => (),
+			_ => {
+				let result: Result<AdminResponse, Error> = Err(Error::AccessDenied);
+				ClusterCore::send_admin_request_status(&data, &sender, request_id, result.into());
+				return;
+			},
+		}
+
+		thread::spawn(move || {
+			let cluster = ClusterClientImpl::new(data.clone());
+			let result = admin_forwarding::process_request(&cluster, &*data.config.key_storage, &*data.self_key_pair, request,
+				|| ClusterCore::send_admin_request_status(&data, &sender, request_id, message::AdminRequestStatus::Started));
+			if let Err(ref err) = result {
+				warn!(target: "secretstore_net", "{}: admin request forwarded by node {} has failed with: {}", data.self_key_pair.public(), sender, err);
+			}
+			ClusterCore::send_admin_request_status(&data, &sender, request_id, result.into());
+		});
+	}
+
+	/// Send status of forwarded administrative request to the node, which has forwarded it.
+	fn send_admin_request_status(data: &Arc<ClusterData>, node: &NodeId, request_id: u64, status: message::AdminRequestStatus) {
+		match data.connection(node) {
+			Some(connection) => data.spawn(connection.send_message(Message::Cluster(ClusterMessage::ForwardedAdminRequestStatus(message::ForwardedAdminRequestStatus {
+				request_id: request_id,
+				status: status,
+			})))),
+			None => warn!(target: "secretstore_net", "{}: failed to send status of admin request {} to disconnected node {}", data.self_key_pair.public(), request_id, node),
+		}
+	}
+
+	/// Start share recovery sessions for keys from received inventory, which are missing from the key storage.
+	fn process_shares_inventory(data: Arc<ClusterData>, connection: Arc<Connection>, inventory: &message::KeySharesInventory) {
+		let missing_keys: Vec<SessionId> = inventory.keys.iter()
+			.map(|key| key.clone().into())
+			.filter(|key| !data.config.key_storage.contains(key) && !data.config.key_storage.is_tombstoned(key)
+				&& data.sessions.share_recovery_sessions.get(key, false).is_none())
+			.take(MAX_INVENTORY_RECOVERY_SESSIONS)
+			.collect();
+
+		for key in missing_keys {
+			info!(target: "secretstore_net", "{}: key share {} is missing (reported by node {}). Starting share recovery",
+				data.self_key_pair.public(), key, connection.node_id());
+
+			let mut connected_nodes = data.connections.connected_nodes();
+			connected_nodes.insert(data.self_key_pair.public().clone());
+
+			let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes.clone()));
+			let result = data.sessions.new_share_recovery_session(data.self_key_pair.public().clone(), key.clone(), None, cluster)
+				.and_then(|session| session.initialize(connected_nodes));
+			if let Err(err) = result {
+				warn!(target: "secretstore_net", "{}: failed to start recovery of key share {}: {}", data.self_key_pair.public(), key, err);
+				data.sessions.share_recovery_sessions.remove(&key);
+			}
+		}
+	}
+
 	/// Process connection future result.
 	fn process_connection_result(data: Arc<ClusterData>, outbound_addr: Option<SocketAddr>, result: Result<DeadlineStatus<Result<NetConnection, Error>>, io::Error>) -> IoFuture<Result<(), Error>> {
 		match result {
 			Ok(DeadlineStatus::Meet(Ok(connection))) => {
-				let connection = Connection::new(outbound_addr.is_none(), connection);
+				if data.peer_rate_limiter.is_throttled(&connection.node_id) {
+					warn!(target: "secretstore_net", "{}: refusing connection with throttled node {}", data.self_key_pair.public(), connection.node_id);
+					return finished(Ok(())).boxed();
+				}
+
+				let connection = Connection::new(data.self_key_pair.clone(), data.sessions.metrics().clone(), outbound_addr.is_none(), connection);
 				if data.connections.insert(connection.clone()) {
+					data.reconnect_backoff.on_connected(connection.node_id());
 					ClusterCore::process_connection_messages(data.clone(), connection)
 				} else {
 					finished(Ok(())).boxed()
@@ -384,43 +837,218 @@ impl ClusterCore {
 				warn!(target: "secretstore_net", "{}: protocol error '{}' when establishing {} connection{}",
 					data.self_key_pair.public(), err, if outbound_addr.is_some() { "outbound" } else { "inbound" },
 					outbound_addr.map(|a| format!(" with {}", a)).unwrap_or_default());
+				if outbound_addr.is_some() {
+					ClusterCore::schedule_reconnect(data);
+				}
 				finished(Ok(())).boxed()
 			},
 			Ok(DeadlineStatus::Timeout) => {
 				warn!(target: "secretstore_net", "{}: timeout when establishing {} connection{}",
 					data.self_key_pair.public(), if outbound_addr.is_some() { "outbound" } else { "inbound" },
 					outbound_addr.map(|a| format!(" with {}", a)).unwrap_or_default());
+				if outbound_addr.is_some() {
+					ClusterCore::schedule_reconnect(data);
+				}
 				finished(Ok(())).boxed()
 			},
 			Err(err) => {
 				warn!(target: "secretstore_net", "{}: network error '{}' when establishing {} connection{}",
 					data.self_key_pair.public(), err, if outbound_addr.is_some() { "outbound" } else { "inbound" },
 					outbound_addr.map(|a| format!(" with {}", a)).unwrap_or_default());
+				if outbound_addr.is_some() {
+					ClusterCore::schedule_reconnect(data);
+				}
 				finished(Ok(())).boxed()
 			},
 		}
 	}
 
-	/// Process single message from the connection.
+	/// Process message, which has just been received from the connection.
+	#[cfg(not(any(test, feature = "fault-injection")))]
+	fn process_received_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: Message) {
+		ClusterCore::process_connection_message(data, connection, message)
+	}
+
+	/// Process message, which has just been received from the connection, injecting faults of the configured policy.
+	#[cfg(any(test, feature = "fault-injection"))]
+	fn process_received_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: Message) {
+		for (delay, message) in data.fault_injector.on_message_received(connection.node_id(), message) {
+			match delay {
+				Some(delay) => ClusterCore::delay_connection_message(data.clone(), connection.clone(), message, delay),
+				None => ClusterCore::process_connection_message(data.clone(), connection.clone(), message),
+			}
+		}
+	}
+
+	/// Process message from the connection after given delay.
+	#[cfg(any(test, feature = "fault-injection"))]
+	fn delay_connection_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: Message, delay: time::Duration) {
+		let d = data.clone();
+		d.handle.spawn(move |handle| Timeout::new(delay, handle)
+			.expect("failed to create timeout")
+			.then(move |_| {
+				ClusterCore::process_connection_message(data, connection, message);
+				finished(())
+			}));
+	}
+
+	/// Process single message from the connection. While sessions processing is paused, session messages are deferred.
 	fn process_connection_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: Message) {
 		connection.set_last_message_time(time::Instant::now());
 		trace!(target: "secretstore_net", "{}: received message {} from {}", data.self_key_pair.public(), message, connection.node_id());
+		let session_id = match message.session_id() {
+			Some(session_id) => session_id.clone(),
+			None => return ClusterCore::route_connection_message(data, connection, message),
+		};
+
+		if connection.version() >= ACKNOWLEDGEMENTS_HEADER_VERSION {
+			if !ClusterCore::acknowledge_session_message(&data, &connection, &message) {
+				return;
+			}
+		}
+
+		let is_paused = data.sessions.check_not_paused().is_err();
+		match data.deferred_messages.defer(&session_id, (connection, message), is_paused) {
+			Ok(Some((connection, message))) => ClusterCore::route_connection_message(data, connection, message),
+			Ok(None) => trace!(target: "secretstore_net", "{}: deferring message of session {}", data.self_key_pair.public(), session_id),
+			Err((connection, message)) => ClusterCore::reject_deferred_message(&data, &connection, &message, Error::QueueOverflow),
+		}
+	}
+
+	/// Process deferred session messages in the order of receival, until there are no more messages or sessions processing is paused.
+	fn process_deferred_messages(data: Arc<ClusterData>) {
+		while data.sessions.check_not_paused().is_ok() {
+			match data.deferred_messages.next() {
+				Some(Ok((connection, message))) => ClusterCore::route_connection_message(data.clone(), connection, message),
+				Some(Err((connection, message))) => ClusterCore::reject_deferred_message(&data, &connection, &message, Error::SessionPaused),
+				None => break,
+			}
+		}
+	}
+
+	/// Respond with session error to the message, which has not been processed, because sessions processing is paused.
+	/// Message is not dropped silently, because the sender could be too old to retransmit it.
+	fn reject_deferred_message(data: &Arc<ClusterData>, connection: &Arc<Connection>, message: &Message, err: Error) {
+		warn!(target: "secretstore_net", "{}: rejecting deferred message {} from {}: {}", data.self_key_pair.public(), message, connection.node_id(), err);
+		if let Some(error_message) = session_error_message(message, &err) {
+			data.spawn(connection.send_message(error_message));
+		}
+	}
+
+	/// Route single message, which is not deferred, to its processor.
+	fn route_connection_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: Message) {
+		let is_session_message = match message {
+			Message::Cluster(_) => false,
+			_ => true,
+		};
+		if is_session_message {
+			if let Some(journal) = data.config.session_journal.as_ref() {
+				journal.on_message_received(connection.node_id(), &message);
+			}
+		}
+
+		// user sessions are never delayed && admin sessions are processed within the configured share of throughput
+		match message {
+			Message::ShareRecovery(_) | Message::ShareRefresh(_) | Message::ShareMove(_) | Message::ShareBootstrap(_) | Message::KeyExport(_) => {
+				match data.scheduler.schedule_admin_message((connection, message)) {
+					Some((connection, message)) => ClusterCore::dispatch_connection_message(data, connection, message),
+					None => ClusterCore::schedule_admin_messages_drain(data),
+				}
+			},
+			Message::Cluster(message) => ClusterCore::process_cluster_message(data, connection, message),
+			message => {
+				data.scheduler.on_user_message();
+				ClusterCore::dispatch_connection_message(data, connection, message);
+			},
+		}
+	}
+
+	/// Acknowledge session message, received from the connection. Returns false if message has been retransmitted
+	/// && its previous copy has already been processed.
+	fn acknowledge_session_message(data: &Arc<ClusterData>, connection: &Arc<Connection>, message: &Message) -> bool {
+		let id = match message_id(message) {
+			Ok(id) => id,
+			Err(err) => {
+				warn!(target: "secretstore_net", "{}: failed to acknowledge message {} from {}: {}", data.self_key_pair.public(), message, connection.node_id(), err);
+				return true;
+			},
+		};
+
+		data.spawn(connection.send_message(Message::Cluster(ClusterMessage::MessageAcknowledgement(message::MessageAcknowledgement {
+			id: id.clone().into(),
+		}))));
+
+		if !data.retransmitter.on_message_received(connection.node_id(), id) {
+			trace!(target: "secretstore_net", "{}: ignoring retransmitted message {} from {}", data.self_key_pair.public(), message, connection.node_id());
+			return false;
+		}
+
+		true
+	}
+
+	/// Process delayed admin sessions messages after ADMIN_MESSAGES_DRAIN_INTERVAL, if there are any.
+	fn schedule_admin_messages_drain(data: Arc<ClusterData>) {
+		if !data.scheduler.begin_drain() {
+			return;
+		}
+
+		let d = data.clone();
+		d.handle.spawn(move |handle| Timeout::new(time::Duration::from_millis(ADMIN_MESSAGES_DRAIN_INTERVAL), handle)
+			.expect("failed to create timeout")
+			.then(move |_| {
+				data.scheduler.end_drain();
+				while let Some((connection, message)) = data.scheduler.dequeue_admin_message() {
+					ClusterCore::dispatch_connection_message(data.clone(), connection, message);
+				}
+				ClusterCore::schedule_admin_messages_drain(data);
+				finished(())
+			}));
+	}
+
+	/// Pass message to the processor of its session kind.
+	fn dispatch_connection_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: Message) {
 		match message {
 			Message::Generation(message) => ClusterCore::process_generation_message(data, connection, message),
 			Message::Encryption(message) => ClusterCore::process_encryption_message(data, connection, message),
 			Message::Decryption(message) => ClusterCore::process_decryption_message(data, connection, message),
+			Message::ReEncryption(message) => ClusterCore::process_reencryption_message(data, connection, message),
 			Message::Signing(message) => ClusterCore::process_signing_message(data, connection, message),
+			Message::EcdsaSigning(message) => ClusterCore::process_ecdsa_signing_message(data, connection, message),
+			Message::ShareRecovery(message) => ClusterCore::process_share_recovery_message(data, connection, message),
+			Message::ShareRefresh(message) => ClusterCore::process_share_refresh_message(data, connection, message),
+			Message::KeyDerivation(message) => ClusterCore::process_key_derivation_message(data, connection, message),
+			Message::KeyDeletion(message) => ClusterCore::process_key_deletion_message(data, connection, message),
+			Message::ShareMove(message) => ClusterCore::process_share_move_message(data, connection, message),
+			Message::ServerKeyRetrieval(message) => ClusterCore::process_server_key_retrieval_message(data, connection, message),
+			Message::ShareBootstrap(message) => ClusterCore::process_share_bootstrap_message(data, connection, message),
+			Message::KeyExport(message) => ClusterCore::process_key_export_message(data, connection, message),
 			Message::Cluster(message) => ClusterCore::process_cluster_message(data, connection, message),
 		}
 	}
 
+	/// Check if session initialization has been retransmitted by the master node.
+	/// Session has already been created && initialization has been confirmed over the same connection
+	/// => repeated initialization is ignored instead of failing the session with replay protection error.
+	fn is_repeated_initialization(data: &ClusterData, sender: &NodeId, session_nonce: u64) -> bool {
+		if !data.sessions.is_recent_initialization(sender, session_nonce) {
+			return false;
+		}
+
+		trace!(target: "secretstore_net", "{}: ignoring repeated initialization of session with nonce {} from {}", data.self_key_pair.public(), session_nonce, sender);
+		true
+	}
+
 	/// Process single generation message from the connection.
 	fn process_generation_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: GenerationMessage) {
 		let session_id = message.session_id().clone();
 		let session_nonce = message.session_nonce();
 		let mut sender = connection.node_id().clone();
-		let session = match message {
+		let mut session = match message {
 			GenerationMessage::InitializeSession(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
 				let mut connected_nodes = data.connections.connected_nodes();
 				connected_nodes.insert(data.self_key_pair.public().clone());
 
@@ -434,13 +1062,14 @@ impl ClusterCore {
 							session: session_id.into(),
 							session_nonce: session_nonce,
 							error: format!("{:?}", err),
+							code: Some(err.code().into()),
 						}))));
 						return;
 					},
 				}
 			},
 			_ => {
-				data.sessions.generation_sessions.get(&session_id)
+				data.sessions.generation_sessions.get(&session_id, true)
 					.ok_or(Error::InvalidSessionId)
 			},
 		};
@@ -470,9 +1099,10 @@ impl ClusterCore {
 						None => break,
 					}
 				},
-				Err(Error::TooEarlyForRequest) => {
-					data.sessions.generation_sessions.enqueue_message(&session_id, sender, message, is_queued_message);
-					break;
+				Err(Error::TooEarlyForRequest) => match data.sessions.generation_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
 				},
 				Err(err) => {
 					warn!(target: "secretstore_net", "{}: generation session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
@@ -480,6 +1110,7 @@ impl ClusterCore {
 						session: session_id.clone().into(),
 						session_nonce: session_nonce,
 						error: format!("{:?}", err),
+						code: Some(err.code().into()),
 					});
 					if err != Error::InvalidSessionId {
 						data.sessions.generation_sessions.remove(&session_id);
@@ -495,8 +1126,12 @@ impl ClusterCore {
 		let session_id = message.session_id().clone();
 		let session_nonce = message.session_nonce();
 		let mut sender = connection.node_id().clone();
-		let session = match message {
-			EncryptionMessage::InitializeEncryptionSession(_) => {
+		let mut session = match message {
+			EncryptionMessage::InitializeEncryptionSession(_) | EncryptionMessage::RequestRekeyingPublicShare(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
 				let mut connected_nodes = data.connections.connected_nodes();
 				connected_nodes.insert(data.self_key_pair.public().clone());
 
@@ -510,13 +1145,14 @@ impl ClusterCore {
 							session: session_id.into(),
 							session_nonce: session_nonce,
 							error: format!("{:?}", err),
+							code: Some(err.code().into()),
 						}))));
 						return;
 					},
 				}
 			},
 			_ => {
-				data.sessions.encryption_sessions.get(&session_id)
+				data.sessions.encryption_sessions.get(&session_id, true)
 					.ok_or(Error::InvalidSessionId)
 			},
 		};
@@ -530,6 +1166,12 @@ impl ClusterCore {
 					session.on_confirm_initialization(sender.clone(), message),
 				EncryptionMessage::EncryptionSessionError(ref message) =>
 					session.on_session_error(sender.clone(), message),
+				EncryptionMessage::RequestRekeyingPublicShare(ref message) =>
+					session.on_request_rekeying_public_share(sender.clone(), message),
+				EncryptionMessage::RekeyingPublicShare(ref message) =>
+					session.on_rekeying_public_share(sender.clone(), message),
+				EncryptionMessage::CommitRekeying(ref message) =>
+					session.on_commit_rekeying(sender.clone(), message),
 			}) {
 				Ok(_) => {
 					// if session is completed => stop
@@ -553,9 +1195,10 @@ impl ClusterCore {
 						None => break,
 					}
 				},
-				Err(Error::TooEarlyForRequest) => {
-					data.sessions.encryption_sessions.enqueue_message(&session_id, sender, message, is_queued_message);
-					break;
+				Err(Error::TooEarlyForRequest) => match data.sessions.encryption_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
 				},
 				Err(err) => {
 					warn!(target: "secretstore_net", "{}: encryption session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
@@ -563,6 +1206,7 @@ impl ClusterCore {
 						session: session_id.clone().into(),
 						session_nonce: session_nonce,
 						error: format!("{:?}", err),
+						code: Some(err.code().into()),
 					});
 					if err != Error::InvalidSessionId {
 						data.sessions.encryption_sessions.remove(&session_id);
@@ -585,6 +1229,10 @@ impl ClusterCore {
 				ConsensusMessage::InitializeConsensusSession(_) => true,
 				_ => false,
 			} => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
 				let mut connected_nodes = data.connections.connected_nodes();
 				connected_nodes.insert(data.self_key_pair.public().clone());
 
@@ -599,13 +1247,14 @@ impl ClusterCore {
 							sub_session: sub_session_id.clone().into(),
 							session_nonce: session_nonce,
 							error: format!("{:?}", err),
+							code: Some(err.code().into()),
 						}))));
 						return;
 					},
 				}
 			},
 			_ => {
-				data.sessions.decryption_sessions.get(&decryption_session_id)
+				data.sessions.decryption_sessions.get(&decryption_session_id, true)
 					.ok_or(Error::InvalidSessionId)
 			},
 		};
@@ -637,6 +1286,7 @@ impl ClusterCore {
 						sub_session: sub_session_id.clone().into(),
 						session_nonce: session_nonce,
 						error: format!("{:?}", err),
+						code: Some(err.code().into()),
 					});
 					if err != Error::InvalidSessionId {
 						data.sessions.decryption_sessions.remove(&decryption_session_id);
@@ -647,6 +1297,86 @@ impl ClusterCore {
 		}
 	}
 
+	/// Process single re-encryption message from the connection.
+	fn process_reencryption_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: ReEncryptionMessage) {
+		let session_id = message.session_id().clone();
+		let sub_session_id = message.sub_session_id().clone();
+		let session_nonce = message.session_nonce();
+		let reencryption_session_id = DecryptionSessionId::new(session_id.clone(), sub_session_id.clone());
+		let mut sender = connection.node_id().clone();
+		let session = match message {
+			ReEncryptionMessage::ReEncryptionConsensusMessage(ref message) if match message.message {
+				ConsensusMessage::InitializeConsensusSession(_) => true,
+				_ => false,
+			} => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_reencryption_session(sender.clone(), session_id.clone(), sub_session_id.clone(), Some(session_nonce), cluster, None) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: re-encryption session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(message::ReEncryptionSessionError {
+							session: session_id.into(),
+							sub_session: sub_session_id.clone().into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.reencryption_sessions.get(&reencryption_session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					if session.is_finished() {
+						info!(target: "secretstore_net", "{}: re-encryption session completed", data.self_key_pair.public());
+						data.sessions.reencryption_sessions.remove(&reencryption_session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.reencryption_sessions.dequeue_message(&reencryption_session_id) {
+						Some((msg_sender, msg)) => {
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: re-encryption session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_reencryption_error(&session_id, &sub_session_id, &sender, message::ReEncryptionSessionError {
+						session: session_id.clone().into(),
+						sub_session: sub_session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.reencryption_sessions.remove(&reencryption_session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
 	/// Process singlesigning message from the connection.
 	fn process_signing_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: SigningMessage) {
 		let session_id = message.session_id().clone();
@@ -654,11 +1384,15 @@ impl ClusterCore {
 		let session_nonce = message.session_nonce();
 		let signing_session_id = SigningSessionId::new(session_id.clone(), sub_session_id.clone());
 		let mut sender = connection.node_id().clone();
-		let session = match message {
+		let mut session = match message {
 			SigningMessage::SigningConsensusMessage(ref message) if match message.message {
 				ConsensusMessage::InitializeConsensusSession(_) => true,
 				_ => false,
 			} => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
 				let mut connected_nodes = data.connections.connected_nodes();
 				connected_nodes.insert(data.self_key_pair.public().clone());
 
@@ -673,13 +1407,14 @@ impl ClusterCore {
 							sub_session: sub_session_id.clone().into(),
 							session_nonce: session_nonce,
 							error: format!("{:?}", err),
+							code: Some(err.code().into()),
 						}))));
 						return;
 					},
 				}
 			},
 			_ => {
-				data.sessions.signing_sessions.get(&signing_session_id)
+				data.sessions.signing_sessions.get(&signing_session_id, true)
 					.ok_or(Error::InvalidSessionId)
 			},
 		};
@@ -706,9 +1441,10 @@ impl ClusterCore {
 						None => break,
 					}
 				},
-				Err(Error::TooEarlyForRequest) => {
-					data.sessions.signing_sessions.enqueue_message(&signing_session_id, sender, message, is_queued_message);
-					break;
+				Err(Error::TooEarlyForRequest) => match data.sessions.signing_sessions.enqueue_message(&signing_session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
 				},
 				Err(err) => {
 					warn!(target: "secretstore_net", "{}: signing session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
@@ -717,6 +1453,7 @@ impl ClusterCore {
 						sub_session: sub_session_id.clone().into(),
 						session_nonce: session_nonce,
 						error: format!("{:?}", err),
+						code: Some(err.code().into()),
 					});
 					if err != Error::InvalidSessionId {
 						data.sessions.signing_sessions.remove(&signing_session_id);
@@ -727,32 +1464,810 @@ impl ClusterCore {
 		}
 	}
 
-	/// Process single cluster message from the connection.
-	fn process_cluster_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: ClusterMessage) {
-		match message {
-			ClusterMessage::KeepAlive(_) => data.spawn(connection.send_message(Message::Cluster(ClusterMessage::KeepAliveResponse(message::KeepAliveResponse {})))),
-			ClusterMessage::KeepAliveResponse(_) => (),
-			_ => warn!(target: "secretstore_net", "{}: received unexpected message {} from node {} at {}", data.self_key_pair.public(), message, connection.node_id(), connection.node_address()),
-		}
-	}
-}
-
-impl ClusterConnections {
-	pub fn new(config: &ClusterConfiguration) -> Result<Self, Error> {
-		let mut nodes = config.key_server_set.get();
-		nodes.remove(config.self_key_pair.public());
+	/// Process single ECDSA signing message from the connection.
+	fn process_ecdsa_signing_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: EcdsaSigningMessage) {
+		let session_id = message.session_id().clone();
+		let sub_session_id = message.sub_session_id().clone();
+		let session_nonce = message.session_nonce();
+		let signing_session_id = SigningSessionId::new(session_id.clone(), sub_session_id.clone());
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			EcdsaSigningMessage::EcdsaSigningConsensusMessage(ref message) if match message.message {
+				ConsensusMessage::InitializeConsensusSession(_) => true,
+				_ => false,
+			} => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
 
-		Ok(ClusterConnections {
-			self_node_id: config.self_key_pair.public().clone(),
-			key_server_set: config.key_server_set.clone(),
-			data: RwLock::new(ClusterConnectionsData {
-				nodes: nodes,
-				connections: BTreeMap::new(),
-			}),
-		})
-	}
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
 
-	pub fn cluster_state(&self) -> ClusterState {
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_ecdsa_signing_session(sender.clone(), session_id.clone(), sub_session_id.clone(), Some(session_nonce), cluster, None) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: ECDSA signing session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(message::EcdsaSigningSessionError {
+							session: session_id.into(),
+							sub_session: sub_session_id.clone().into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.ecdsa_signing_sessions.get(&signing_session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					if session.is_finished() {
+						info!(target: "secretstore_net", "{}: ECDSA signing session completed", data.self_key_pair.public());
+						data.sessions.ecdsa_signing_sessions.remove(&signing_session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.ecdsa_signing_sessions.dequeue_message(&signing_session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.ecdsa_signing_sessions.enqueue_message(&signing_session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: ECDSA signing session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_ecdsa_signing_error(&session_id, &sub_session_id, &sender, message::EcdsaSigningSessionError {
+						session: session_id.clone().into(),
+						sub_session: sub_session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.ecdsa_signing_sessions.remove(&signing_session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single share recovery message from the connection.
+	fn process_share_recovery_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: ShareRecoveryMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			ShareRecoveryMessage::InitializeShareRecoverySession(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_share_recovery_session(sender.clone(), session_id.clone(), Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: share recovery session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(message::ShareRecoverySessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.share_recovery_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == ShareRecoverySessionState::Finished {
+						info!(target: "secretstore_net", "{}: share recovery session completed", data.self_key_pair.public());
+					}
+					if session_state == ShareRecoverySessionState::Finished || session_state == ShareRecoverySessionState::Failed {
+						data.sessions.share_recovery_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.share_recovery_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.share_recovery_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: share recovery session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_share_recovery_error(&session_id, &sender, message::ShareRecoverySessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.share_recovery_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single share refresh message from the connection.
+	fn process_share_refresh_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: ShareRefreshMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			ShareRefreshMessage::InitializeShareRefreshSession(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_share_refresh_session(sender.clone(), session_id.clone(), Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: share refresh session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(message::ShareRefreshSessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.share_refresh_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == ShareRefreshSessionState::Finished {
+						info!(target: "secretstore_net", "{}: share refresh session completed", data.self_key_pair.public());
+					}
+					if session_state == ShareRefreshSessionState::Finished || session_state == ShareRefreshSessionState::Failed {
+						data.sessions.share_refresh_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.share_refresh_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.share_refresh_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: share refresh session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_share_refresh_error(&session_id, &sender, message::ShareRefreshSessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.share_refresh_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single key derivation message from the connection.
+	fn process_key_derivation_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: KeyDerivationMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			KeyDerivationMessage::InitializeKeyDerivationSession(ref init_message) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				let parent_key_id = init_message.parent_key_id.clone().into();
+				match data.sessions.new_key_derivation_session(sender.clone(), session_id.clone(), parent_key_id, Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: key derivation session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(message::KeyDerivationSessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.key_derivation_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == KeyDerivationSessionState::Finished {
+						info!(target: "secretstore_net", "{}: key derivation session completed", data.self_key_pair.public());
+					}
+					if session_state == KeyDerivationSessionState::Finished || session_state == KeyDerivationSessionState::Failed {
+						data.sessions.key_derivation_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.key_derivation_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.key_derivation_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: key derivation session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_key_derivation_error(&session_id, &sender, message::KeyDerivationSessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.key_derivation_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single key deletion message from the connection.
+	fn process_key_deletion_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: KeyDeletionMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			KeyDeletionMessage::InitializeKeyDeletionSession(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_key_deletion_session(sender.clone(), session_id.clone(), Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: key deletion session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(message::KeyDeletionSessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.key_deletion_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == KeyDeletionSessionState::Finished {
+						info!(target: "secretstore_net", "{}: key deletion session completed", data.self_key_pair.public());
+					}
+					if session_state == KeyDeletionSessionState::Finished || session_state == KeyDeletionSessionState::Failed {
+						data.sessions.key_deletion_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.key_deletion_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.key_deletion_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: key deletion session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_key_deletion_error(&session_id, &sender, message::KeyDeletionSessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.key_deletion_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single server key retrieval message from the connection.
+	fn process_server_key_retrieval_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: ServerKeyRetrievalMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_server_key_retrieval_session(sender.clone(), session_id.clone(), Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: server key retrieval session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(message::ServerKeyRetrievalSessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.server_key_retrieval_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == ServerKeyRetrievalSessionState::Finished {
+						info!(target: "secretstore_net", "{}: server key retrieval session completed", data.self_key_pair.public());
+					}
+					if session_state == ServerKeyRetrievalSessionState::Finished || session_state == ServerKeyRetrievalSessionState::Failed {
+						data.sessions.server_key_retrieval_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.server_key_retrieval_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.server_key_retrieval_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: server key retrieval session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_server_key_retrieval_error(&session_id, &sender, message::ServerKeyRetrievalSessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.server_key_retrieval_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single share move message from the connection.
+	fn process_share_move_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: ShareMoveMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			ShareMoveMessage::InitializeShareMoveSession(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_share_move_session(sender.clone(), session_id.clone(), Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: share move session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(message::ShareMoveSessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			ShareMoveMessage::ShareMoveRollback(ref message) => match data.sessions.share_move_sessions.get(&session_id, true) {
+				Some(session) => Ok(session),
+				None => {
+					// session could be already completed on this node => undo its changes without session
+					if let Err(err) = data.sessions.rollback_share_move(&sender, message) {
+						warn!(target: "secretstore_net", "{}: share move rollback error '{}' when requested by node {}", data.self_key_pair.public(), err, sender);
+					}
+					return;
+				},
+			},
+			_ => {
+				data.sessions.share_move_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == ShareMoveSessionState::Finished {
+						info!(target: "secretstore_net", "{}: share move session completed", data.self_key_pair.public());
+					}
+					if session_state == ShareMoveSessionState::Finished || session_state == ShareMoveSessionState::Failed {
+						data.sessions.share_move_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.share_move_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.share_move_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: share move session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_share_move_error(&session_id, &sender, message::ShareMoveSessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.share_move_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single share bootstrap message from the connection.
+	fn process_share_bootstrap_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: ShareBootstrapMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			// the new node learns about the session from key shares && key holders - from commit request
+			ShareBootstrapMessage::InitializeShareBootstrapSession(_) | ShareBootstrapMessage::CommitShareBootstrap(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_share_bootstrap_session(sender.clone(), session_id.clone(), Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: share bootstrap session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(message::ShareBootstrapSessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.share_bootstrap_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == ShareBootstrapSessionState::Finished {
+						info!(target: "secretstore_net", "{}: share bootstrap session completed", data.self_key_pair.public());
+					}
+					if session_state == ShareBootstrapSessionState::Finished || session_state == ShareBootstrapSessionState::Failed {
+						data.sessions.share_bootstrap_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.share_bootstrap_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.share_bootstrap_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: share bootstrap session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_share_bootstrap_error(&session_id, &sender, message::ShareBootstrapSessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.share_bootstrap_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single key export message from the connection.
+	fn process_key_export_message(data: Arc<ClusterData>, connection: Arc<Connection>, mut message: KeyExportMessage) {
+		let session_id = message.session_id().clone();
+		let session_nonce = message.session_nonce();
+		let mut sender = connection.node_id().clone();
+		let mut session = match message {
+			KeyExportMessage::InitializeKeyExportSession(_) => {
+				if ClusterCore::is_repeated_initialization(&data, &sender, session_nonce) {
+					return;
+				}
+
+				let mut connected_nodes = data.connections.connected_nodes();
+				connected_nodes.insert(data.self_key_pair.public().clone());
+
+				let cluster = Arc::new(ClusterView::new(data.clone(), connected_nodes));
+				match data.sessions.new_key_export_session(sender.clone(), session_id.clone(), Some(session_nonce), cluster) {
+					Ok(session) => Ok(session),
+					Err(err) => {
+						// this is new session => it is not yet in container
+						warn!(target: "secretstore_net", "{}: key export session initialization error '{}' when requested for new session from node {}", data.self_key_pair.public(), err, sender);
+						data.spawn(connection.send_message(Message::KeyExport(KeyExportMessage::KeyExportSessionError(message::KeyExportSessionError {
+							session: session_id.into(),
+							session_nonce: session_nonce,
+							error: format!("{:?}", err),
+							code: Some(err.code().into()),
+						}))));
+						return;
+					},
+				}
+			},
+			_ => {
+				data.sessions.key_export_sessions.get(&session_id, true)
+					.ok_or(Error::InvalidSessionId)
+			},
+		};
+
+		let mut is_queued_message = false;
+		loop {
+			match session.clone().and_then(|session| session.process_message(&sender, &message)) {
+				Ok(_) => {
+					// if session is completed => stop
+					let session = session.clone().expect("session.method() call finished with success; session exists; qed");
+					let session_state = session.state();
+					if session_state == KeyExportSessionState::Finished {
+						info!(target: "secretstore_net", "{}: key export session completed", data.self_key_pair.public());
+					}
+					if session_state == KeyExportSessionState::Finished || session_state == KeyExportSessionState::Failed {
+						data.sessions.key_export_sessions.remove(&session_id);
+						break;
+					}
+
+					// try to dequeue message
+					match data.sessions.key_export_sessions.dequeue_message(&session_id) {
+						Some((msg_sender, msg)) => {
+							is_queued_message = true;
+							sender = msg_sender;
+							message = msg;
+						},
+						None => break,
+					}
+				},
+				Err(Error::TooEarlyForRequest) => match data.sessions.key_export_sessions.enqueue_message(&session_id, sender.clone(), message.clone(), is_queued_message) {
+					Ok(()) => break,
+					// queue is full => session is failed with QueueOverflow error on the next iteration
+					Err(err) => session = Err(err),
+				},
+				Err(err) => {
+					warn!(target: "secretstore_net", "{}: key export session error '{}' when processing message {} from node {}", data.self_key_pair.public(), err, message, sender);
+					data.sessions.respond_with_key_export_error(&session_id, &sender, message::KeyExportSessionError {
+						session: session_id.clone().into(),
+						session_nonce: session_nonce,
+						error: format!("{:?}", err),
+						code: Some(err.code().into()),
+					});
+					if err != Error::InvalidSessionId {
+						data.sessions.key_export_sessions.remove(&session_id);
+					}
+					break;
+				},
+			}
+		}
+	}
+
+	/// Process single cluster message from the connection.
+	fn process_cluster_message(data: Arc<ClusterData>, connection: Arc<Connection>, message: ClusterMessage) {
+		match message {
+			ClusterMessage::KeepAlive(_) => data.spawn(connection.send_message(Message::Cluster(ClusterMessage::KeepAliveResponse(message::KeepAliveResponse {
+				timestamp: Some(unix_timestamp()),
+			})))),
+			ClusterMessage::KeepAliveResponse(ref response) => {
+				data.peer_latencies.on_probe_answered(connection.node_id());
+				if let Some(timestamp) = response.timestamp {
+					data.sessions.metrics().on_peer_time(connection.node_id(), timestamp, unix_timestamp());
+				}
+			},
+			ClusterMessage::KeySharesInventory(ref inventory) => ClusterCore::process_shares_inventory(data, connection, inventory),
+			ClusterMessage::ForwardAdminRequest(ref request) => ClusterCore::process_forwarded_admin_request(data, connection, request),
+			ClusterMessage::ForwardedAdminRequestStatus(ref status) => if let Err(err) = data.forwarded_requests.on_status(connection.node_id(), status.clone()) {
+				warn!(target: "secretstore_net", "{}: failed to process status of forwarded admin request {} from node {}: {}", data.self_key_pair.public(), status.request_id, connection.node_id(), err);
+			},
+			ClusterMessage::MessageAcknowledgement(ref acknowledgement) =>
+				data.retransmitter.on_message_acknowledged(connection.node_id(), &acknowledgement.id),
+			_ => warn!(target: "secretstore_net", "{}: received unexpected message {} from node {} at {}", data.self_key_pair.public(), message, connection.node_id(), connection.node_address()),
+		}
+	}
+}
+
+impl ClusterConnections {
+	pub fn new(config: &ClusterConfiguration) -> Result<Self, Error> {
+		let mut nodes = config.key_server_set.get();
+		nodes.remove(config.self_key_pair.public());
+
+		Ok(ClusterConnections {
+			self_node_id: config.self_key_pair.public().clone(),
+			key_server_set: config.key_server_set.clone(),
+			peer_filter: config.peer_filter.clone(),
+			data: RwLock::new(ClusterConnectionsData {
+				nodes: nodes,
+				connections: BTreeMap::new(),
+			}),
+		})
+	}
+
+	pub fn cluster_state(&self) -> ClusterState {
 		ClusterState {
 			connected: self.data.read().connections.keys().cloned().collect(),
 		}
@@ -770,6 +2285,10 @@ impl ClusterConnections {
 			debug_assert!(connection.is_inbound());
 			return false;
 		}
+		if !self.peer_filter.is_allowed(connection.node_id()) {
+			trace!(target: "secretstore_net", "{}: ignoring denied connection from {} at {}", self.self_node_id, connection.node_id(), connection.node_address());
+			return false;
+		}
 		if data.connections.contains_key(connection.node_id()) {
 			// we have already connected to the same node
 			// the agreement is that node with lower id must establish connection to node with higher id
@@ -785,16 +2304,20 @@ impl ClusterConnections {
 		true
 	}
 
-	pub fn remove(&self, node: &NodeId, is_inbound: bool) {
+	/// Remove connection to the node. Returns false if there's no such connection.
+	pub fn remove(&self, node: &NodeId, is_inbound: bool) -> bool {
 		let mut data = self.data.write();
 		if let Entry::Occupied(entry) = data.connections.entry(node.clone()) {
 			if entry.get().is_inbound() != is_inbound {
-				return;
+				return false;
 			}
 
 			trace!(target: "secretstore_net", "{}: removing connection to {} at {}", self.self_node_id, entry.get().node_id(), entry.get().node_address());
 			entry.remove_entry();
+			return true;
 		}
+
+		false
 	}
 
 	pub fn connected_nodes(&self) -> BTreeSet<NodeId> {
@@ -808,11 +2331,26 @@ impl ClusterConnections {
 	pub fn disconnected_nodes(&self) -> BTreeMap<NodeId, SocketAddr> {
 		let data = self.data.read();
 		data.nodes.iter()
-			.filter(|&(node_id, _)| !data.connections.contains_key(node_id))
+			.filter(|&(node_id, _)| !data.connections.contains_key(node_id) && self.peer_filter.is_allowed(node_id))
 			.map(|(node_id, node_address)| (node_id.clone(), node_address.clone()))
 			.collect()
 	}
 
+	/// Remove connections to nodes, which are no longer allowed by peer filter. Returns removed nodes.
+	pub fn remove_denied(&self) -> Vec<NodeId> {
+		let mut data = self.data.write();
+		let denied_nodes: Vec<_> = data.connections.keys()
+			.filter(|node_id| !self.peer_filter.is_allowed(node_id))
+			.cloned()
+			.collect();
+		for denied_node in &denied_nodes {
+			if let Some(connection) = data.connections.remove(denied_node) {
+				trace!(target: "secretstore_net", "{}: removing denied connection to {} at {}", self.self_node_id, connection.node_id(), connection.node_address());
+			}
+		}
+		denied_nodes
+	}
+
 	pub fn update_nodes_set(&self) {
 		let mut data = self.data.write();
 		let mut new_nodes = self.key_server_set.get();
@@ -866,7 +2404,21 @@ impl ClusterData {
 			self_key_pair: config.self_key_pair.clone(),
 			connections: connections,
 			sessions: sessions,
+			scheduler: MessageScheduler::new(config.admin_messages_share),
+			peer_rate_limiter: PeerRateLimiter::new(&config.peer_rate_limits),
+			peer_latencies: PeerLatencies::default(),
 			config: config,
+			shares_inventory_time: Mutex::new(time::Instant::now()),
+			removed_keys_purge_time: Mutex::new(None),
+			external_reachability_check_time: Mutex::new(None),
+			is_externally_reachable: Mutex::new(None),
+			reconnect_backoff: ReconnectBackoff::default(),
+			forwarded_requests: ForwardedRequests::default(),
+			retransmitter: MessageRetransmitter::default(),
+			deferred_messages: DeferredMessages::new(config.timeouts.message_retries
+				.map(|retries| time::Duration::from_millis(PAUSED_MESSAGE_RETRY_INTERVAL * retries as u64))),
+			#[cfg(any(test, feature = "fault-injection"))]
+			fault_injector: FaultInjector::default(),
 		})
 	}
 
@@ -890,14 +2442,20 @@ impl ClusterData {
 }
 
 impl Connection {
-	pub fn new(is_inbound: bool, connection: NetConnection) -> Arc<Connection> {
+	pub fn new(self_key_pair: Arc<NodeKeyPair>, metrics: Arc<ClusterMetrics>, is_inbound: bool, connection: NetConnection) -> Arc<Connection> {
 		Arc::new(Connection {
 			node_id: connection.node_id,
 			node_address: connection.address,
 			is_inbound: is_inbound,
 			stream: connection.stream,
 			key: connection.key,
+			self_key_pair: self_key_pair,
+			version: connection.version,
 			last_message_time: Mutex::new(time::Instant::now()),
+			queued_session_messages: Arc::new(AtomicUsize::new(0)),
+			metrics: metrics,
+			next_sequence: AtomicUsize::new(HANDSHAKE_MESSAGE_SEQUENCE as usize + 1),
+			received_sequences: Mutex::new(ReceivedSequences::starting_at(HANDSHAKE_MESSAGE_SEQUENCE + 1)),
 		})
 	}
 
@@ -917,17 +2475,51 @@ impl Connection {
 		*self.last_message_time.lock() = last_message_time;
 	}
 
+	pub fn queued_session_messages(&self) -> usize {
+		self.queued_session_messages.load(Ordering::SeqCst)
+	}
+
 	pub fn node_address(&self) -> &SocketAddr {
 		&self.node_address
 	}
 
-	pub fn send_message(&self, message: Message) -> WriteMessage<SharedTcpStream> {
-		write_encrypted_message(self.stream.clone(), &self.key, message)
+	pub fn send_message(&self, message: Message) -> WriteMessage<SharedTcpStream> {
+		let sequence = self.next_sequence.fetch_add(1, Ordering::SeqCst) as u64;
+		let future = write_encrypted_message(self.stream.clone(), &self.key, &*self.self_key_pair, self.version, sequence, message);
+		self.metrics.on_bytes_sent(&self.node_id, future.size());
+		future
+	}
+
+	/// Send session message to the node, unless there are too many session messages waiting to be sent.
+	/// Returns size of the serialized message && the future, which completes when message is sent.
+	pub fn send_session_message(&self, message: Message) -> Result<(usize, BoxFuture<(), io::Error>), Error> {
+		let queued_session_messages = self.queued_session_messages.clone();
+		if queued_session_messages.fetch_add(1, Ordering::SeqCst) >= MAX_QUEUED_SESSION_MESSAGES {
+			queued_session_messages.fetch_sub(1, Ordering::SeqCst);
+			return Err(Error::QueueOverflow);
+		}
+
+		let future = self.send_message(message);
+		Ok((future.size(), future
+			.then(move |result| {
+				queued_session_messages.fetch_sub(1, Ordering::SeqCst);
+				result.map(|_| ())
+			})
+			.boxed()))
+	}
+
+	pub fn version(&self) -> u8 {
+		self.version
 	}
 
 	pub fn read_message(&self) -> ReadMessage<SharedTcpStream> {
 		read_encrypted_message(self.stream.clone(), self.key.clone())
 	}
+
+	/// Get message, read from the connection, verifying that it is signed by the node && has not been replayed.
+	pub fn verify_message(&self, message: SignedMessage) -> Result<Message, Error> {
+		message.verify(&self.node_id, self.version, &mut *self.received_sequences.lock())
+	}
 }
 
 impl ClusterView {
@@ -936,6 +2528,8 @@ impl ClusterView {
 			core: Arc::new(Mutex::new(ClusterViewCore {
 				cluster: cluster,
 				nodes: nodes,
+				sent_bytes: 0,
+				received_bytes: 0,
 			})),
 		}
 	}
@@ -943,29 +2537,88 @@ impl ClusterView {
 	pub fn is_connected(&self, node: &NodeId) -> bool {
 		self.core.lock().nodes.contains(node)
 	}
+}
 
-	pub fn nodes(&self) -> BTreeSet<NodeId> {
-		self.core.lock().nodes.clone()
+impl ClusterViewCore {
+	/// Send message to given node, unless session has exceeded traffic limit.
+	fn send(&mut self, to: &NodeId, message: Message) -> Result<(), Error> {
+		self.check_traffic()?;
+
+		trace!(target: "secretstore_net", "{}: sent message {} to {}", self.cluster.self_key_pair.public(), message, to);
+		let connection = self.cluster.connection(to).ok_or(Error::NodeDisconnected)?;
+		let unacknowledged_message = match connection.version() >= ACKNOWLEDGEMENTS_HEADER_VERSION {
+			true => Some((message_id(&message)?, message.clone())),
+			false => None,
+		};
+		if let Some(journal) = self.cluster.config.session_journal.as_ref() {
+			journal.on_message_sent(to, &message);
+		}
+		let (size, future) = connection.send_session_message(message)?;
+		self.sent_bytes += size as u64;
+		self.cluster.spawn(future);
+
+		// message is retransmitted until receiver acknowledges it
+		if let Some((id, message)) = unacknowledged_message {
+			self.cluster.retransmitter.on_message_sent(to, id, message);
+		}
+		Ok(())
+	}
+
+	/// Check that session has not exceeded traffic limit.
+	fn check_traffic(&self) -> Result<(), Error> {
+		match self.cluster.config.max_session_traffic {
+			Some(max_session_traffic) if self.sent_bytes + self.received_bytes > max_session_traffic => Err(Error::SessionTrafficLimitExceeded),
+			_ => Ok(()),
+		}
 	}
 }
 
 impl Cluster for ClusterView {
 	fn broadcast(&self, message: Message) -> Result<(), Error> {
-		let core = self.core.lock();
-		for node in core.nodes.iter().filter(|n| *n != core.cluster.self_key_pair.public()) {
-			trace!(target: "secretstore_net", "{}: sent message {} to {}", core.cluster.self_key_pair.public(), message, node);
-			let connection = core.cluster.connection(node).ok_or(Error::NodeDisconnected)?;
-			core.cluster.spawn(connection.send_message(message.clone()))
+		let mut core = self.core.lock();
+		let nodes: Vec<_> = core.nodes.iter().filter(|n| *n != core.cluster.self_key_pair.public()).cloned().collect();
+		for node in nodes {
+			core.send(&node, message.clone())?;
 		}
 		Ok(())
 	}
 
 	fn send(&self, to: &NodeId, message: Message) -> Result<(), Error> {
+		self.core.lock().send(to, message)
+	}
+
+	fn nodes(&self) -> BTreeSet<NodeId> {
+		self.core.lock().nodes.clone()
+	}
+
+	fn traffic(&self) -> (u64, u64) {
 		let core = self.core.lock();
-		trace!(target: "secretstore_net", "{}: sent message {} to {}", core.cluster.self_key_pair.public(), message, to);
-		let connection = core.cluster.connection(to).ok_or(Error::NodeDisconnected)?;
-		core.cluster.spawn(connection.send_message(message));
-		Ok(())
+		(core.sent_bytes, core.received_bytes)
+	}
+
+	fn on_message_received(&self, size: usize) -> Result<(), Error> {
+		let mut core = self.core.lock();
+		core.received_bytes += size as u64;
+		core.check_traffic()
+	}
+
+	fn order_by_preference(&self, nodes: BTreeSet<NodeId>) -> Vec<NodeId> {
+		let core = self.core.lock();
+		if core.cluster.config.deterministic_nodes_selection {
+			return nodes.into_iter().collect();
+		}
+
+		// this node is always preferred; nodes we're not connected to are expected to respond last
+		let self_node_id = core.cluster.self_key_pair.public();
+		order_by_expected_delay(nodes.into_iter().map(|node| {
+			let expected_delay = if &node == self_node_id {
+				Some(time::Duration::from_secs(0))
+			} else {
+				core.cluster.connections.get(&node)
+					.and_then(|connection| core.cluster.peer_latencies.expected_delay(&node, connection.queued_session_messages()))
+			};
+			(node, expected_delay)
+		}).collect())
 	}
 }
 
@@ -1002,6 +2655,16 @@ impl ClusterClient for ClusterClientImpl {
 		Ok(EncryptionSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
 	}
 
+	fn new_rekeying_session(&self, session_id: SessionId) -> Result<Arc<EncryptionSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_encryption_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize_rekeying()?;
+		Ok(EncryptionSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
 	fn new_decryption_session(&self, session_id: SessionId, requestor_signature: Signature, is_shadow_decryption: bool) -> Result<Arc<DecryptionSession>, Error> {
 		let mut connected_nodes = self.data.connections.connected_nodes();
 		connected_nodes.insert(self.data.self_key_pair.public().clone());
@@ -1013,6 +2676,17 @@ impl ClusterClient for ClusterClientImpl {
 		Ok(DecryptionSessionWrapper::new(Arc::downgrade(&self.data), DecryptionSessionId::new(session_id, access_key), session))
 	}
 
+	fn new_reencryption_session(&self, session_id: SessionId, requestor_signature: Signature, target_public: Public, target_signature: Signature) -> Result<Arc<ReEncryptionSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let access_key = Random.generate()?.secret().clone();
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_reencryption_session(self.data.self_key_pair.public().clone(), session_id, access_key.clone(), None, cluster, Some(requestor_signature))?;
+		session.initialize(target_public, target_signature)?;
+		Ok(ReEncryptionSessionWrapper::new(Arc::downgrade(&self.data), DecryptionSessionId::new(session_id, access_key), session))
+	}
+
 	fn new_signing_session(&self, session_id: SessionId, requestor_signature: Signature, message_hash: H256) -> Result<Arc<SigningSession>, Error> {
 		let mut connected_nodes = self.data.connections.connected_nodes();
 		connected_nodes.insert(self.data.self_key_pair.public().clone());
@@ -1024,6 +2698,171 @@ impl ClusterClient for ClusterClientImpl {
 		Ok(SigningSessionWrapper::new(Arc::downgrade(&self.data), SigningSessionId::new(session_id, access_key), session))
 	}
 
+	fn new_ecdsa_signing_session(&self, session_id: SessionId, requestor_signature: Signature, message_hash: H256) -> Result<Arc<EcdsaSigningSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let access_key = Random.generate()?.secret().clone();
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_ecdsa_signing_session(self.data.self_key_pair.public().clone(), session_id, access_key.clone(), None, cluster, Some(requestor_signature))?;
+		session.initialize(message_hash)?;
+		Ok(EcdsaSigningSessionWrapper::new(Arc::downgrade(&self.data), SigningSessionId::new(session_id, access_key), session))
+	}
+
+	fn new_share_recovery_session(&self, session_id: SessionId) -> Result<Arc<ShareRecoverySession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_share_recovery_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize(connected_nodes)?;
+		Ok(ShareRecoverySessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn new_share_refresh_session(&self, session_id: SessionId) -> Result<Arc<ShareRefreshSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_share_refresh_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize()?;
+		Ok(ShareRefreshSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn new_key_derivation_session(&self, parent_key_id: SessionId, requestor_signature: Signature, derivation_path: H256) -> Result<Arc<KeyDerivationSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let session_id = math::compute_derived_key_id(&parent_key_id, &derivation_path);
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_key_derivation_session(self.data.self_key_pair.public().clone(), session_id, parent_key_id, None, cluster)?;
+		session.initialize(derivation_path, requestor_signature)?;
+		Ok(KeyDerivationSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn new_key_deletion_session(&self, session_id: SessionId, requestor_signature: Signature) -> Result<Arc<KeyDeletionSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_key_deletion_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize(requestor_signature, connected_nodes)?;
+		Ok(KeyDeletionSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn new_share_move_session(&self, session_id: SessionId, new_node: NodeId) -> Result<Arc<ShareMoveSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_share_move_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize(new_node, connected_nodes)?;
+		Ok(ShareMoveSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn new_server_key_retrieval_session(&self, session_id: SessionId, requestor_signature: Signature) -> Result<Arc<ServerKeyRetrievalSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_server_key_retrieval_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize(requestor_signature, connected_nodes)?;
+		Ok(ServerKeyRetrievalSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn new_share_bootstrap_session(&self, admin_signature: Signature, new_node: NodeId, keys: BTreeSet<SessionId>) -> Result<Arc<ShareBootstrapSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let session_id = share_bootstrap_session::batch_session_id(&keys);
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_share_bootstrap_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize(admin_signature, new_node, keys, connected_nodes)?;
+		Ok(ShareBootstrapSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn new_key_export_session(&self, admin_signature: Signature, session_id: SessionId, targets: BTreeSet<NodeId>, threshold: usize) -> Result<Arc<KeyExportSession>, Error> {
+		let mut connected_nodes = self.data.connections.connected_nodes();
+		connected_nodes.insert(self.data.self_key_pair.public().clone());
+
+		let cluster = Arc::new(ClusterView::new(self.data.clone(), connected_nodes.clone()));
+		let session = self.data.sessions.new_key_export_session(self.data.self_key_pair.public().clone(), session_id, None, cluster)?;
+		session.initialize(admin_signature, targets, threshold, connected_nodes)?;
+		Ok(KeyExportSessionWrapper::new(Arc::downgrade(&self.data), session_id, session))
+	}
+
+	fn forward_admin_request(&self, master: NodeId, admin_signature: AdminRequestSignature, request: AdminRequest) -> Result<Arc<ForwardedRequest>, Error> {
+		let connection = self.data.connection(&master).ok_or(Error::NodeDisconnected)?;
+		let (request_id, forwarded_request) = self.data.forwarded_requests.insert(master);
+		self.data.spawn(connection.send_message(Message::Cluster(ClusterMessage::ForwardAdminRequest(message::ForwardAdminRequest {
+			request_id: request_id,
+			admin_signature: admin_signature.into(),
+			request: request.into(),
+		}))));
+		Ok(forwarded_request)
+	}
+
+	fn pause_sessions(&self) {
+		self.data.sessions.pause();
+	}
+
+	fn resume_sessions(&self) {
+		self.data.sessions.resume();
+		ClusterCore::process_deferred_messages(self.data.clone());
+	}
+
+	fn start_draining(&self) {
+		self.data.sessions.start_draining();
+	}
+
+	fn stop_draining(&self) {
+		self.data.sessions.stop_draining();
+	}
+
+	fn active_sessions_count(&self) -> usize {
+		self.data.sessions.active_sessions_count()
+	}
+
+	fn apply_peer_filter(&self) {
+		for denied_node in self.data.connections.remove_denied() {
+			self.data.sessions.on_connection_timeout(&denied_node);
+			self.data.forwarded_requests.on_node_disconnected(&denied_node);
+		}
+	}
+
+	fn metrics(&self) -> String {
+		let mut gauges = ClusterGauges::default();
+		self.data.sessions.fill_gauges(&mut gauges);
+		{
+			let connections = self.data.connections.data.read();
+			gauges.peers = connections.nodes.keys()
+				.map(|node| (node.clone(), connections.connections.contains_key(node)))
+				.collect();
+		}
+		gauges.stored_keys = self.data.config.key_storage.documents().ok().map(|documents| documents.len());
+		self.data.sessions.metrics().render(&gauges)
+	}
+
+	fn health(&self) -> ClusterHealth {
+		let peers = {
+			let connections = self.data.connections.data.read();
+			connections.nodes.keys()
+				.map(|node| self.data.sessions.metrics().peer_health(node, connections.connections.contains_key(node)))
+				.collect()
+		};
+
+		ClusterHealth {
+			peers: peers,
+			is_key_storage_available: is_key_storage_available(&*self.data.config.key_storage),
+			is_externally_reachable: *self.data.is_externally_reachable.lock(),
+		}
+	}
+
+	#[cfg(any(test, feature = "fault-injection"))]
+	fn fault_injector(&self) -> &FaultInjector {
+		&self.data.fault_injector
+	}
+
 	#[cfg(test)]
 	fn connect(&self) {
 		ClusterCore::connect_disconnected_nodes(self.data.clone());
@@ -1036,27 +2875,145 @@ impl ClusterClient for ClusterClientImpl {
 
 	#[cfg(test)]
 	fn generation_session(&self, session_id: &SessionId) -> Option<Arc<GenerationSessionImpl>> {
-		self.data.sessions.generation_sessions.get(session_id)
+		self.data.sessions.generation_sessions.get(session_id, false)
 	}
 }
 
 fn make_socket_address(address: &str, port: u16) -> Result<SocketAddr, Error> {
-	let ip_address: IpAddr = address.parse().map_err(|_| Error::InvalidNodeAddress)?;
-	Ok(SocketAddr::new(ip_address, port))
+	resolve_node_address(address, port).map_err(|_| Error::InvalidNodeAddress)
+}
+
+/// Make session error message, which is sent in response to the session message. Cluster messages && session
+/// error messages are not responded.
+fn session_error_message(message: &Message, err: &Error) -> Option<Message> {
+	let error = format!("{:?}", err);
+	let code = Some(err.code().into());
+	Some(match *message {
+		Message::Cluster(_) => return None,
+		Message::Generation(GenerationMessage::SessionError(_)) => return None,
+		Message::Generation(ref msg) => Message::Generation(GenerationMessage::SessionError(message::SessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::Encryption(EncryptionMessage::EncryptionSessionError(_)) => return None,
+		Message::Encryption(ref msg) => Message::Encryption(EncryptionMessage::EncryptionSessionError(message::EncryptionSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::Decryption(DecryptionMessage::DecryptionSessionError(_)) => return None,
+		Message::Decryption(ref msg) => Message::Decryption(DecryptionMessage::DecryptionSessionError(message::DecryptionSessionError {
+			session: msg.session_id().clone().into(),
+			sub_session: msg.sub_session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(_)) => return None,
+		Message::ReEncryption(ref msg) => Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(message::ReEncryptionSessionError {
+			session: msg.session_id().clone().into(),
+			sub_session: msg.sub_session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::Signing(SigningMessage::SigningSessionError(_)) => return None,
+		Message::Signing(ref msg) => Message::Signing(SigningMessage::SigningSessionError(message::SigningSessionError {
+			session: msg.session_id().clone().into(),
+			sub_session: msg.sub_session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(_)) => return None,
+		Message::EcdsaSigning(ref msg) => Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(message::EcdsaSigningSessionError {
+			session: msg.session_id().clone().into(),
+			sub_session: msg.sub_session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(_)) => return None,
+		Message::ShareRecovery(ref msg) => Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(message::ShareRecoverySessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(_)) => return None,
+		Message::ShareRefresh(ref msg) => Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(message::ShareRefreshSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(_)) => return None,
+		Message::KeyDerivation(ref msg) => Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(message::KeyDerivationSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(_)) => return None,
+		Message::KeyDeletion(ref msg) => Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(message::KeyDeletionSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(_)) => return None,
+		Message::ServerKeyRetrieval(ref msg) => Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(message::ServerKeyRetrievalSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(_)) => return None,
+		Message::ShareMove(ref msg) => Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(message::ShareMoveSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(_)) => return None,
+		Message::ShareBootstrap(ref msg) => Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(message::ShareBootstrapSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+		Message::KeyExport(KeyExportMessage::KeyExportSessionError(_)) => return None,
+		Message::KeyExport(ref msg) => Message::KeyExport(KeyExportMessage::KeyExportSessionError(message::KeyExportSessionError {
+			session: msg.session_id().clone().into(),
+			session_nonce: msg.session_nonce(),
+			error: error,
+			code: code,
+		})),
+	})
 }
 
 #[cfg(test)]
 pub mod tests {
 	use std::sync::Arc;
 	use std::time;
-	use std::collections::VecDeque;
+	use std::collections::{VecDeque, BTreeSet};
 	use parking_lot::Mutex;
 	use tokio_core::reactor::Core;
+	use devtools::RandomTempPath;
 	use ethkey::{Random, Generator, Public};
-	use key_server_cluster::{NodeId, SessionId, Error, DummyAclStorage, DummyKeyStorage, MapKeyServerSet, PlainNodeKeyPair};
+	use key_server_cluster::{NodeId, SessionId, Error, DummyAclStorage, DummyKeyStorage, MapKeyServerSet, PlainNodeKeyPair,
+		ClusterMetrics, SessionOutcome, PeerFilter, MemoryPeerFilter};
 	use key_server_cluster::message::Message;
-	use key_server_cluster::cluster::{Cluster, ClusterCore, ClusterConfiguration};
+	use key_server_cluster::cluster::{Cluster, ClusterCore, ClusterConfiguration, ClusterView};
+	use key_server_cluster::cluster_sessions::{ClusterSession, ClusterSessions, ClusterSessionsContainer, SessionTimeouts,
+		MAX_SESSION_QUEUE_SIZE};
 	use key_server_cluster::generation_session::{Session as GenerationSession, SessionState as GenerationSessionState};
+	use key_server_cluster::fault_injection::{FaultRule, FaultAction};
+	use key_server_cluster::message_retransmitter::RETRANSMISSION_INTERVAL;
+	use key_server_cluster::session_journal::{SessionJournal, JournalEvent};
 
 	#[derive(Debug)]
 	pub struct DummyCluster {
@@ -1106,6 +3063,44 @@ pub mod tests {
 			self.data.lock().messages.push_back((to.clone(), message));
 			Ok(())
 		}
+
+		fn nodes(&self) -> BTreeSet<NodeId> {
+			self.data.lock().nodes.iter().cloned().collect()
+		}
+
+		fn traffic(&self) -> (u64, u64) {
+			(0, 0)
+		}
+
+		fn on_message_received(&self, _size: usize) -> Result<(), Error> {
+			Ok(())
+		}
+	}
+
+	#[derive(Default)]
+	struct DummySession {
+		is_finished: Mutex<bool>,
+	}
+
+	impl ClusterSession for DummySession {
+		fn is_finished(&self) -> bool {
+			*self.is_finished.lock()
+		}
+
+		fn is_failed(&self) -> bool {
+			false
+		}
+
+		fn on_session_timeout(&self) {
+			*self.is_finished.lock() = true;
+		}
+
+		fn on_node_timeout(&self, _node_id: &NodeId) {
+		}
+
+		fn cancel(&self) {
+			*self.is_finished.lock() = true;
+		}
 	}
 
 	pub fn loop_until<F>(core: &mut Core, timeout: time::Duration, predicate: F) where F: Fn() -> bool {
@@ -1129,6 +3124,10 @@ pub mod tests {
 	}
 
 	pub fn make_clusters(core: &Core, ports_begin: u16, num_nodes: usize) -> Vec<Arc<ClusterCore>> {
+		make_configured_clusters(core, ports_begin, num_nodes, |_| ())
+	}
+
+	pub fn make_configured_clusters<F>(core: &Core, ports_begin: u16, num_nodes: usize, configure: F) -> Vec<Arc<ClusterCore>> where F: Fn(&mut ClusterConfiguration) {
 		let key_pairs: Vec<_> = (0..num_nodes).map(|_| Random.generate().unwrap()).collect();
 		let cluster_params: Vec<_> = (0..num_nodes).map(|i| ClusterConfiguration {
 			threads: 1,
@@ -1138,11 +3137,25 @@ pub mod tests {
 				.map(|(j, kp)| (kp.public().clone(), format!("127.0.0.1:{}", ports_begin + j as u16).parse().unwrap()))
 				.collect())),
 			allow_connecting_to_higher_nodes: false,
+			rate_limits: Default::default(),
+			peer_rate_limits: Default::default(),
+			deterministic_nodes_selection: true,
+			read_only_nodes: BTreeSet::new(),
+			timeouts: Default::default(),
+			external_address: None,
+			admin_messages_share: None,
+			removed_keys_retention: None,
+			max_session_traffic: None,
+			session_journal: None,
 			key_storage: Arc::new(DummyKeyStorage::default()),
 			acl_storage: Arc::new(DummyAclStorage::default()),
+			peer_filter: Arc::new(MemoryPeerFilter::default()),
 		}).collect();
 		let clusters: Vec<_> = cluster_params.into_iter().enumerate()
-			.map(|(_, params)| ClusterCore::new(core.handle(), params).unwrap())
+			.map(|(_, mut params)| {
+				configure(&mut params);
+				ClusterCore::new(core.handle(), params).unwrap()
+			})
 			.collect();
 
 		clusters
@@ -1228,4 +3241,326 @@ pub mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn paused_sessions_messages_are_deferred_until_resumed() {
+		let mut core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6062, 3);
+		run_clusters(&clusters);
+		loop_until(&mut core, time::Duration::from_millis(300), || clusters.iter().all(all_connections_established));
+
+		// pause one of nodes && start generation session
+		clusters[1].client().pause_sessions();
+		let session = clusters[0].client().new_generation_session(SessionId::default(), Public::default(), 1).unwrap();
+
+		// session can not complete while one of nodes is paused
+		let start = time::Instant::now();
+		while time::Instant::now() - start < time::Duration::from_millis(300) {
+			core.turn(Some(time::Duration::from_millis(1)));
+		}
+		assert!(session.joint_public_and_secret().is_none());
+		assert!(clusters[1].client().generation_session(&SessionId::default()).is_none());
+		assert!(clusters[1].data.deferred_messages.len() != 0);
+
+		// resume && wait for session to complete
+		clusters[1].client().resume_sessions();
+		loop_until(&mut core, time::Duration::from_millis(1000), || session.state() == GenerationSessionState::Finished
+			|| session.state() == GenerationSessionState::Failed);
+		assert!(session.joint_public_and_secret().unwrap().is_ok());
+	}
+
+	#[test]
+	fn slowly_progressing_session_is_stopped_by_total_timeout_only() {
+		let master = Random.generate().unwrap().public().clone();
+		let cluster: Arc<Cluster> = Arc::new(DummyCluster::new(master.clone()));
+		let sessions: ClusterSessionsContainer<SessionId, DummySession, ()> = ClusterSessionsContainer::with_timeouts(SessionTimeouts {
+			idle_timeout: time::Duration::from_secs(10),
+			total_timeout: time::Duration::from_secs(100),
+		});
+
+		let start = time::Instant::now();
+		let slow_session_id = SessionId::from(1);
+		let stuck_session_id = SessionId::from(2);
+		sessions.insert(master.clone(), slow_session_id.clone(), cluster.clone(), || Ok(DummySession::default())).unwrap();
+		sessions.insert(master, stuck_session_id.clone(), cluster, || Ok(DummySession::default())).unwrap();
+
+		// slow session receives message every 5 seconds, stuck session receives nothing
+		for i in 1..13 {
+			let now = start + time::Duration::from_secs(i * 5);
+			sessions.sessions.write().get_mut(&slow_session_id).unwrap().last_message_time = now;
+			sessions.stop_stalled_sessions_at(now);
+		}
+		assert!(sessions.get(&slow_session_id, false).is_some());
+		assert!(sessions.get(&stuck_session_id, false).is_none());
+
+		// but even slowly progressing session is stopped by total timeout
+		let now = start + time::Duration::from_secs(101);
+		sessions.sessions.write().get_mut(&slow_session_id).unwrap().last_message_time = now;
+		sessions.stop_stalled_sessions_at(now);
+		assert!(sessions.get(&slow_session_id, false).is_none());
+	}
+
+	#[test]
+	fn cancelled_session_is_removed_from_container() {
+		let core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6026, 1);
+		let cluster_view = Arc::new(ClusterView::new(clusters[0].data.clone(), BTreeSet::new()));
+		let sessions: ClusterSessionsContainer<SessionId, DummySession, ()> = ClusterSessionsContainer::new();
+
+		let master = clusters[0].config().self_key_pair.public().clone();
+		let session = sessions.insert(master, SessionId::default(), cluster_view, || Ok(DummySession::default())).unwrap();
+		assert_eq!(sessions.cancel(&SessionId::default()), Ok(()));
+		assert!(session.is_finished());
+		assert!(sessions.get(&SessionId::default(), false).is_none());
+		assert_eq!(sessions.cancel(&SessionId::default()), Err(Error::InvalidSessionId));
+	}
+
+	#[test]
+	fn session_queue_is_bounded() {
+		let core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6031, 1);
+		let cluster_view = Arc::new(ClusterView::new(clusters[0].data.clone(), BTreeSet::new()));
+		let sessions: ClusterSessionsContainer<SessionId, DummySession, usize> = ClusterSessionsContainer::new();
+
+		let master = clusters[0].config().self_key_pair.public().clone();
+		sessions.insert(master.clone(), SessionId::default(), cluster_view, || Ok(DummySession::default())).unwrap();
+		for i in 0..MAX_SESSION_QUEUE_SIZE {
+			assert_eq!(sessions.enqueue_message(&SessionId::default(), master.clone(), i, false), Ok(()));
+		}
+		assert_eq!(sessions.enqueue_message(&SessionId::default(), master.clone(), MAX_SESSION_QUEUE_SIZE, false), Err(Error::QueueOverflow));
+
+		// dequeued message is always returned to the queue
+		assert_eq!(sessions.dequeue_message(&SessionId::default()), Some((master.clone(), 0)));
+		assert_eq!(sessions.enqueue_message(&SessionId::default(), master.clone(), 0, true), Ok(()));
+		assert_eq!(sessions.dequeue_message(&SessionId::default()), Some((master, 0)));
+	}
+
+	#[test]
+	fn removed_sessions_are_counted_in_metrics() {
+		let core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6028, 1);
+		let cluster_view = Arc::new(ClusterView::new(clusters[0].data.clone(), BTreeSet::new()));
+		let metrics = Arc::new(ClusterMetrics::default());
+		let sessions: ClusterSessionsContainer<SessionId, DummySession, ()> = ClusterSessionsContainer::new()
+			.with_metrics("dummy", metrics.clone());
+
+		let master = clusters[0].config().self_key_pair.public().clone();
+		let session = sessions.insert(master.clone(), SessionId::from(1), cluster_view.clone(), || Ok(DummySession::default())).unwrap();
+		*session.is_finished.lock() = true;
+		sessions.remove(&SessionId::from(1));
+		sessions.insert(master.clone(), SessionId::from(2), cluster_view.clone(), || Ok(DummySession::default())).unwrap();
+		sessions.remove(&SessionId::from(2));
+		sessions.insert(master, SessionId::from(3), cluster_view, || Ok(DummySession::default())).unwrap();
+		assert_eq!(sessions.cancel(&SessionId::from(3)), Ok(()));
+
+		assert_eq!(metrics.sessions("dummy", SessionOutcome::Completed), 1);
+		assert_eq!(metrics.sessions("dummy", SessionOutcome::Failed), 1);
+		assert_eq!(metrics.sessions("dummy", SessionOutcome::Cancelled), 1);
+		assert_eq!(metrics.sessions("dummy", SessionOutcome::TimedOut), 0);
+	}
+
+	#[test]
+	fn session_nonces_survive_restart() {
+		let core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6027, 1);
+		let config = clusters[0].config();
+		let self_node_id = config.self_key_pair.public().clone();
+		let master = Random.generate().unwrap().public().clone();
+		let cluster_view = Arc::new(ClusterView::new(clusters[0].data.clone(), vec![self_node_id.clone()].into_iter().collect()));
+
+		// nonce, received before restart, is rejected after restart
+		let sessions = ClusterSessions::new(config);
+		assert!(sessions.new_generation_session(master.clone(), SessionId::from(1), Some(5), cluster_view.clone()).is_ok());
+		let sessions = ClusterSessions::new(config);
+		assert_eq!(sessions.new_generation_session(master.clone(), SessionId::from(2), Some(5), cluster_view.clone()).err(), Some(Error::ReplayProtection));
+		assert!(sessions.new_generation_session(master.clone(), SessionId::from(2), Some(6), cluster_view.clone()).is_ok());
+		assert_eq!(config.key_storage.max_session_nonce(&master, "generation"), Ok(Some(6)));
+
+		// nonce, generated before restart, is not reused after restart
+		assert!(sessions.new_generation_session(self_node_id.clone(), SessionId::from(3), None, cluster_view.clone()).is_ok());
+		assert_eq!(config.key_storage.max_session_nonce(&self_node_id, "generation"), Ok(Some(1)));
+		let sessions = ClusterSessions::new(config);
+		assert!(sessions.new_generation_session(self_node_id.clone(), SessionId::from(4), None, cluster_view).is_ok());
+		assert_eq!(config.key_storage.max_session_nonce(&self_node_id, "generation"), Ok(Some(2)));
+	}
+
+	#[test]
+	fn recent_initializations_are_remembered() {
+		let core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6032, 1);
+		let self_node_id = clusters[0].config().self_key_pair.public().clone();
+		let master = Random.generate().unwrap().public().clone();
+		let cluster_view = Arc::new(ClusterView::new(clusters[0].data.clone(), vec![self_node_id.clone()].into_iter().collect()));
+
+		let sessions = ClusterSessions::new(clusters[0].config());
+		assert!(sessions.new_generation_session(master.clone(), SessionId::from(1), Some(5), cluster_view.clone()).is_ok());
+		assert!(sessions.is_recent_initialization(&master, 5));
+		assert!(!sessions.is_recent_initialization(&master, 6));
+
+		// sessions, started by this node, are not remembered
+		assert!(sessions.new_generation_session(self_node_id.clone(), SessionId::from(2), None, cluster_view).is_ok());
+		assert!(!sessions.is_recent_initialization(&self_node_id, 1));
+
+		sessions.forget_recent_initializations(time::Instant::now() + time::Duration::from_secs(61));
+		assert!(!sessions.is_recent_initialization(&master, 5));
+	}
+
+	#[test]
+	fn read_only_node_cannot_start_share_administration_sessions() {
+		let core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6029, 1);
+		let self_node_id = clusters[0].config().self_key_pair.public().clone();
+		let read_only_node = Random.generate().unwrap().public().clone();
+		let cluster_view = Arc::new(ClusterView::new(clusters[0].data.clone(), vec![self_node_id.clone()].into_iter().collect()));
+
+		let mut config = clusters[0].config().clone();
+		config.read_only_nodes.insert(read_only_node.clone());
+		let sessions = ClusterSessions::new(&config);
+		assert_eq!(sessions.new_share_refresh_session(read_only_node.clone(), SessionId::from(1), Some(1), cluster_view.clone()).err(),
+			Some(Error::InvalidNodeForRequest));
+		assert_eq!(sessions.new_share_recovery_session(read_only_node.clone(), SessionId::from(2), Some(2), cluster_view.clone()).err(),
+			Some(Error::InvalidNodeForRequest));
+
+		// other sessions of read-only node are not restricted
+		assert!(sessions.new_generation_session(read_only_node, SessionId::from(3), Some(3), cluster_view).is_ok());
+	}
+
+	#[test]
+	fn session_is_cancelled_when_traffic_limit_is_exceeded() {
+		let core = Core::new().unwrap();
+		let clusters = make_configured_clusters(&core, 6050, 1, |config| config.max_session_traffic = Some(100));
+		let cluster_view = Arc::new(ClusterView::new(clusters[0].data.clone(), BTreeSet::new()));
+		let metrics = Arc::new(ClusterMetrics::default());
+		let sessions: ClusterSessionsContainer<SessionId, DummySession, ()> = ClusterSessionsContainer::new()
+			.with_metrics("dummy", metrics.clone());
+
+		let master = clusters[0].config().self_key_pair.public().clone();
+		let session = sessions.insert(master, SessionId::default(), cluster_view.clone(), || Ok(DummySession::default())).unwrap();
+		assert_eq!(sessions.on_message_received(&SessionId::default(), 100), Ok(()));
+		assert_eq!(sessions.on_message_received(&SessionId::default(), 1), Err(Error::SessionTrafficLimitExceeded));
+		assert!(session.is_finished());
+		assert!(sessions.get(&SessionId::default(), false).is_none());
+		assert_eq!(cluster_view.traffic(), (0, 101));
+		assert_eq!(metrics.sessions("dummy", SessionOutcome::Cancelled), 1);
+
+		// messages of unknown sessions are not accounted
+		assert_eq!(sessions.on_message_received(&SessionId::default(), 1), Ok(()));
+	}
+
+	#[test]
+	fn injected_faults_are_applied_to_received_messages() {
+		let mut core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6053, 3);
+		run_clusters(&clusters);
+		loop_until(&mut core, time::Duration::from_millis(300), || clusters.iter().all(all_connections_established));
+
+		// when master drops all generation messages, received from other nodes, session is not completed
+		clusters[0].client().fault_injector().set_rules(vec![FaultRule {
+			peer: None,
+			message: "Generation.".into(),
+			action: FaultAction::Drop,
+		}]);
+		let session = clusters[0].client().new_generation_session(SessionId::from(1), Public::default(), 1).unwrap();
+		for _ in 0..100 {
+			core.turn(Some(time::Duration::from_millis(1)));
+		}
+		assert!(session.state() != GenerationSessionState::Finished && session.state() != GenerationSessionState::Failed);
+
+		// when messages are only delayed, session is completed
+		clusters[0].client().fault_injector().set_rules(vec![FaultRule {
+			peer: None,
+			message: "Generation.".into(),
+			action: FaultAction::Delay(time::Duration::from_millis(10)),
+		}]);
+		let session = clusters[0].client().new_generation_session(SessionId::from(2), Public::default(), 1).unwrap();
+		loop_until(&mut core, time::Duration::from_millis(300), || session.state() == GenerationSessionState::Finished
+			|| session.state() == GenerationSessionState::Failed);
+		assert!(session.joint_public_and_secret().unwrap().is_ok());
+	}
+
+	#[test]
+	fn dropped_session_messages_are_retransmitted() {
+		let mut core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6056, 3);
+		run_clusters(&clusters);
+		loop_until(&mut core, time::Duration::from_millis(300), || clusters.iter().all(all_connections_established));
+
+		// master drops generation messages, received from other nodes, for a while
+		clusters[0].client().fault_injector().set_rules(vec![FaultRule {
+			peer: None,
+			message: "Generation.".into(),
+			action: FaultAction::Drop,
+		}]);
+		let session = clusters[0].client().new_generation_session(SessionId::from(1), Public::default(), 1).unwrap();
+		for _ in 0..100 {
+			core.turn(Some(time::Duration::from_millis(1)));
+		}
+		assert!(session.state() != GenerationSessionState::Finished && session.state() != GenerationSessionState::Failed);
+
+		// when messages are not dropped anymore, unacknowledged messages are retransmitted && session is completed
+		clusters[0].client().fault_injector().set_rules(Vec::new());
+		loop_until(&mut core, time::Duration::from_millis(3 * RETRANSMISSION_INTERVAL), || session.state() == GenerationSessionState::Finished
+			|| session.state() == GenerationSessionState::Failed);
+		assert!(session.joint_public_and_secret().unwrap().is_ok());
+		loop_until(&mut core, time::Duration::from_millis(300), || clusters.iter().all(|c| c.data.retransmitter.unacknowledged_messages() == 0));
+	}
+
+	#[test]
+	fn session_messages_are_journaled() {
+		let mut core = Core::new().unwrap();
+		let path = RandomTempPath::create_dir();
+		let clusters = make_configured_clusters(&core, 6059, 3, |config| {
+			let journal_path = path.as_path().join(format!("{:?}", config.self_key_pair.public()));
+			config.session_journal = Some(Arc::new(SessionJournal::new(journal_path.to_str().unwrap()).unwrap()));
+		});
+		run_clusters(&clusters);
+		loop_until(&mut core, time::Duration::from_millis(300), || clusters.iter().all(all_connections_established));
+
+		let session = clusters[0].client().new_generation_session(SessionId::default(), Public::default(), 1).unwrap();
+		loop_until(&mut core, time::Duration::from_millis(300), || session.state() == GenerationSessionState::Finished
+			|| session.state() == GenerationSessionState::Failed);
+		assert!(session.joint_public_and_secret().unwrap().is_ok());
+
+		// master journal starts with session creation && ends with session removal
+		let master = clusters[0].config().self_key_pair.public().clone();
+		let node1 = clusters[1].config().self_key_pair.public().clone();
+		let journal = clusters[0].config().session_journal.clone().unwrap();
+		let events: Vec<_> = journal.records(&SessionId::default()).unwrap().into_iter().map(|record| record.event).collect();
+		assert_eq!(events.first(), Some(&JournalEvent::Started { kind: "generation".into(), master: master.clone().into() }));
+		assert_eq!(events.last(), Some(&JournalEvent::Removed { kind: "generation".into(), outcome: "Completed".into() }));
+		assert!(events.iter().any(|event| match *event {
+			JournalEvent::Sent { ref to, ref message, .. } => **to == node1 && message == "Generation.InitializeSession",
+			_ => false,
+		}));
+		assert!(events.iter().any(|event| match *event {
+			JournalEvent::Received { ref from, .. } => **from == node1,
+			_ => false,
+		}));
+	}
+
+	#[test]
+	fn denied_node_is_disconnected() {
+		let mut core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6040, 3);
+		run_clusters(&clusters);
+		loop_until(&mut core, time::Duration::from_millis(300), || clusters.iter().all(all_connections_established));
+
+		// deny connections with node1 on node0
+		let node1 = clusters[1].config().self_key_pair.public().clone();
+		let node2 = clusters[2].config().self_key_pair.public().clone();
+		clusters[0].config().peer_filter.deny(node1.clone()).unwrap();
+		clusters[0].client().apply_peer_filter();
+		assert!(clusters[0].connection(&node1).is_none());
+		assert!(clusters[0].connection(&node2).is_some());
+
+		// connection is not re-established
+		for cluster in &clusters {
+			cluster.client().connect();
+		}
+		for _ in 0..100 {
+			core.turn(Some(time::Duration::from_millis(1)));
+		}
+		assert!(clusters[0].connection(&node1).is_none());
+		assert!(clusters[0].connection(&node2).is_some());
+	}
 }
@@ -0,0 +1,367 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time;
+use std::fmt::Write;
+use std::collections::{BTreeMap, BTreeSet};
+use parking_lot::Mutex;
+use key_server_cluster::{NodeId, PeerHealth};
+
+/// Upper bounds (in seconds) of session duration histogram buckets.
+const SESSION_DURATION_BUCKETS: &'static [f64] = &[0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0];
+
+/// How cluster session has been completed on this node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SessionOutcome {
+	/// Session has completed successfully.
+	Completed,
+	/// Session has failed.
+	Failed,
+	/// Session has been stopped, because it was stalled.
+	TimedOut,
+	/// Session has been cancelled.
+	Cancelled,
+}
+
+/// Cluster metrics, collected over this node lifetime.
+#[derive(Default)]
+pub struct ClusterMetrics {
+	/// Number of sessions, removed from this node, by session kind && outcome.
+	sessions: Mutex<BTreeMap<(&'static str, SessionOutcome), u64>>,
+	/// Durations of sessions, removed from this node, by session kind.
+	durations: Mutex<BTreeMap<&'static str, Histogram>>,
+	/// Time (seconds since unix epoch), when the last session with given node has completed successfully.
+	last_sessions: Mutex<BTreeMap<NodeId, u64>>,
+	/// Difference between given node clock && this node clock (in seconds), measured on last keep alive response.
+	clock_skews: Mutex<BTreeMap<NodeId, i64>>,
+	/// Number of bytes, sent && received by sessions, removed from this node, by session kind.
+	session_traffic: Mutex<BTreeMap<&'static str, Traffic>>,
+	/// Number of bytes, sent to && received from given node.
+	peer_traffic: Mutex<BTreeMap<NodeId, Traffic>>,
+	/// Number of times given node has been throttled, by class of messages, which has exceeded the rate limit.
+	throttled_peers: Mutex<BTreeMap<(NodeId, &'static str), u64>>,
+}
+
+/// Number of bytes, sent && received.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Traffic {
+	/// Number of sent bytes.
+	sent: u64,
+	/// Number of received bytes.
+	received: u64,
+}
+
+/// Current values of cluster gauges, collected when metrics are requested.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ClusterGauges {
+	/// Number of active sessions by session kind.
+	pub active_sessions: BTreeMap<&'static str, usize>,
+	/// Number of queued session messages by session kind.
+	pub queued_messages: BTreeMap<&'static str, usize>,
+	/// All other key servers && connection state (true if connected).
+	pub peers: BTreeMap<NodeId, bool>,
+	/// Number of keys in the key storage. None if key storage is unavailable.
+	pub stored_keys: Option<usize>,
+}
+
+/// Cumulative histogram of observed values.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Histogram {
+	/// Number of observations, which are less than or equal to the corresponding SESSION_DURATION_BUCKETS value.
+	buckets: Vec<u64>,
+	/// Sum of all observed values.
+	sum: f64,
+	/// Number of observations.
+	count: u64,
+}
+
+impl SessionOutcome {
+	/// Outcome name, used as metric label value.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			SessionOutcome::Completed => "completed",
+			SessionOutcome::Failed => "failed",
+			SessionOutcome::TimedOut => "timeout",
+			SessionOutcome::Cancelled => "cancelled",
+		}
+	}
+}
+
+impl ClusterMetrics {
+	/// When session of given kind is removed from this node.
+	pub fn on_session_removed(&self, kind: &'static str, outcome: SessionOutcome, duration: time::Duration) {
+		*self.sessions.lock().entry((kind, outcome)).or_insert(0) += 1;
+		self.durations.lock().entry(kind).or_insert_with(Histogram::default).observe(duration_secs(duration));
+	}
+
+	/// When session of given kind, which has sent && received given number of bytes, is removed from this node.
+	pub fn on_session_traffic(&self, kind: &'static str, sent_bytes: u64, received_bytes: u64) {
+		let mut session_traffic = self.session_traffic.lock();
+		let traffic = session_traffic.entry(kind).or_insert_with(Traffic::default);
+		traffic.sent += sent_bytes;
+		traffic.received += received_bytes;
+	}
+
+	/// When message of given size (in bytes) has been sent to given node.
+	pub fn on_bytes_sent(&self, node: &NodeId, size: usize) {
+		self.peer_traffic.lock().entry(node.clone()).or_insert_with(Traffic::default).sent += size as u64;
+	}
+
+	/// When message of given size (in bytes) has been received from given node.
+	pub fn on_bytes_received(&self, node: &NodeId, size: usize) {
+		self.peer_traffic.lock().entry(node.clone()).or_insert_with(Traffic::default).received += size as u64;
+	}
+
+	/// When given node has exceeded rate limit of given class of messages.
+	pub fn on_peer_throttled(&self, node: &NodeId, class: &'static str) {
+		*self.throttled_peers.lock().entry((node.clone(), class)).or_insert(0) += 1;
+	}
+
+	/// When session with given nodes has completed successfully at given moment.
+	pub fn on_session_completed(&self, nodes: &BTreeSet<NodeId>, now: u64) {
+		let mut last_sessions = self.last_sessions.lock();
+		for node in nodes {
+			last_sessions.insert(node.clone(), now);
+		}
+	}
+
+	/// When node has reported its current time.
+	pub fn on_peer_time(&self, node: &NodeId, peer_time: u64, now: u64) {
+		self.clock_skews.lock().insert(node.clone(), peer_time as i64 - now as i64);
+	}
+
+	/// Get health of given node, as seen by this node.
+	pub fn peer_health(&self, node: &NodeId, is_connected: bool) -> PeerHealth {
+		PeerHealth {
+			node: node.clone(),
+			is_connected: is_connected,
+			last_session: self.last_sessions.lock().get(node).cloned(),
+			clock_skew: self.clock_skews.lock().get(node).cloned(),
+		}
+	}
+
+	/// Number of sessions of given kind, removed with given outcome.
+	pub fn sessions(&self, kind: &'static str, outcome: SessionOutcome) -> u64 {
+		self.sessions.lock().get(&(kind, outcome)).cloned().unwrap_or_default()
+	}
+
+	/// Render metrics && given gauges using Prometheus text exposition format.
+	pub fn render(&self, gauges: &ClusterGauges) -> String {
+		let mut result = String::new();
+
+		result.push_str("# HELP secretstore_sessions_total Number of sessions, completed on this node.\n");
+		result.push_str("# TYPE secretstore_sessions_total counter\n");
+		for (&(kind, outcome), count) in self.sessions.lock().iter() {
+			let _ = writeln!(result, "secretstore_sessions_total{{kind=\"{}\",outcome=\"{}\"}} {}", kind, outcome.name(), count);
+		}
+
+		result.push_str("# HELP secretstore_session_duration_seconds Duration of sessions, completed on this node.\n");
+		result.push_str("# TYPE secretstore_session_duration_seconds histogram\n");
+		for (kind, histogram) in self.durations.lock().iter() {
+			for (bound, count) in SESSION_DURATION_BUCKETS.iter().zip(histogram.buckets.iter()) {
+				let _ = writeln!(result, "secretstore_session_duration_seconds_bucket{{kind=\"{}\",le=\"{}\"}} {}", kind, bound, count);
+			}
+			let _ = writeln!(result, "secretstore_session_duration_seconds_bucket{{kind=\"{}\",le=\"+Inf\"}} {}", kind, histogram.count);
+			let _ = writeln!(result, "secretstore_session_duration_seconds_sum{{kind=\"{}\"}} {}", kind, histogram.sum);
+			let _ = writeln!(result, "secretstore_session_duration_seconds_count{{kind=\"{}\"}} {}", kind, histogram.count);
+		}
+
+		let session_traffic = self.session_traffic.lock();
+		result.push_str("# HELP secretstore_session_sent_bytes_total Number of bytes, sent by sessions, completed on this node.\n");
+		result.push_str("# TYPE secretstore_session_sent_bytes_total counter\n");
+		for (kind, traffic) in session_traffic.iter() {
+			let _ = writeln!(result, "secretstore_session_sent_bytes_total{{kind=\"{}\"}} {}", kind, traffic.sent);
+		}
+
+		result.push_str("# HELP secretstore_session_received_bytes_total Number of bytes, received by sessions, completed on this node.\n");
+		result.push_str("# TYPE secretstore_session_received_bytes_total counter\n");
+		for (kind, traffic) in session_traffic.iter() {
+			let _ = writeln!(result, "secretstore_session_received_bytes_total{{kind=\"{}\"}} {}", kind, traffic.received);
+		}
+
+		result.push_str("# HELP secretstore_active_sessions Number of sessions, active on this node.\n");
+		result.push_str("# TYPE secretstore_active_sessions gauge\n");
+		for (kind, count) in &gauges.active_sessions {
+			let _ = writeln!(result, "secretstore_active_sessions{{kind=\"{}\"}} {}", kind, count);
+		}
+
+		result.push_str("# HELP secretstore_queued_messages Number of session messages, waiting to be processed.\n");
+		result.push_str("# TYPE secretstore_queued_messages gauge\n");
+		for (kind, count) in &gauges.queued_messages {
+			let _ = writeln!(result, "secretstore_queued_messages{{kind=\"{}\"}} {}", kind, count);
+		}
+
+		result.push_str("# HELP secretstore_peer_connected Connection state of other key servers.\n");
+		result.push_str("# TYPE secretstore_peer_connected gauge\n");
+		for (peer, is_connected) in &gauges.peers {
+			let _ = writeln!(result, "secretstore_peer_connected{{peer=\"{:?}\"}} {}", peer, if *is_connected { 1 } else { 0 });
+		}
+
+		let peer_traffic = self.peer_traffic.lock();
+		result.push_str("# HELP secretstore_peer_sent_bytes_total Number of bytes, sent to other key servers.\n");
+		result.push_str("# TYPE secretstore_peer_sent_bytes_total counter\n");
+		for (peer, traffic) in peer_traffic.iter() {
+			let _ = writeln!(result, "secretstore_peer_sent_bytes_total{{peer=\"{:?}\"}} {}", peer, traffic.sent);
+		}
+
+		result.push_str("# HELP secretstore_peer_received_bytes_total Number of bytes, received from other key servers.\n");
+		result.push_str("# TYPE secretstore_peer_received_bytes_total counter\n");
+		for (peer, traffic) in peer_traffic.iter() {
+			let _ = writeln!(result, "secretstore_peer_received_bytes_total{{peer=\"{:?}\"}} {}", peer, traffic.received);
+		}
+
+		result.push_str("# HELP secretstore_peer_throttled_total Number of times other key server has been disconnected for exceeding messages rate limit.\n");
+		result.push_str("# TYPE secretstore_peer_throttled_total counter\n");
+		for &((ref peer, class), count) in self.throttled_peers.lock().iter() {
+			let _ = writeln!(result, "secretstore_peer_throttled_total{{peer=\"{:?}\",class=\"{}\"}} {}", peer, class, count);
+		}
+
+		if let Some(stored_keys) = gauges.stored_keys {
+			result.push_str("# HELP secretstore_stored_keys Number of keys in the key storage.\n");
+			result.push_str("# TYPE secretstore_stored_keys gauge\n");
+			let _ = writeln!(result, "secretstore_stored_keys {}", stored_keys);
+		}
+
+		result
+	}
+}
+
+impl Histogram {
+	/// Add new observation.
+	pub fn observe(&mut self, value: f64) {
+		if self.buckets.is_empty() {
+			self.buckets = vec![0; SESSION_DURATION_BUCKETS.len()];
+		}
+
+		for (bound, count) in SESSION_DURATION_BUCKETS.iter().zip(self.buckets.iter_mut()) {
+			if value <= *bound {
+				*count += 1;
+			}
+		}
+		self.sum += value;
+		self.count += 1;
+	}
+}
+
+/// Convert duration to (fractional) seconds.
+fn duration_secs(duration: time::Duration) -> f64 {
+	duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000f64
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time;
+	use std::collections::BTreeMap;
+	use ethkey::{Random, Generator};
+	use key_server_cluster::PeerHealth;
+	use super::{ClusterMetrics, ClusterGauges, SessionOutcome, Histogram, Traffic};
+
+	#[test]
+	fn histogram_buckets_are_cumulative() {
+		let mut histogram = Histogram::default();
+		histogram.observe(0.03125);
+		histogram.observe(2.0);
+		histogram.observe(1000.0);
+		assert_eq!(histogram.buckets, vec![0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2]);
+		assert_eq!(histogram.count, 3);
+		assert_eq!(histogram.sum, 1002.03125);
+	}
+
+	#[test]
+	fn metrics_are_rendered() {
+		let metrics = ClusterMetrics::default();
+		metrics.on_session_removed("decryption", SessionOutcome::Completed, time::Duration::from_millis(200));
+		metrics.on_session_removed("decryption", SessionOutcome::Completed, time::Duration::from_millis(300));
+		metrics.on_session_removed("decryption", SessionOutcome::TimedOut, time::Duration::from_secs(60));
+		assert_eq!(metrics.sessions("decryption", SessionOutcome::Completed), 2);
+		assert_eq!(metrics.sessions("signing", SessionOutcome::Completed), 0);
+
+		let peer = Random.generate().unwrap().public().clone();
+		let mut peers = BTreeMap::new();
+		peers.insert(peer.clone(), false);
+		let rendered = metrics.render(&ClusterGauges {
+			active_sessions: vec![("signing", 1)].into_iter().collect(),
+			queued_messages: vec![("signing", 3)].into_iter().collect(),
+			peers: peers,
+			stored_keys: Some(10),
+		});
+
+		assert!(rendered.contains("secretstore_sessions_total{kind=\"decryption\",outcome=\"completed\"} 2\n"));
+		assert!(rendered.contains("secretstore_sessions_total{kind=\"decryption\",outcome=\"timeout\"} 1\n"));
+		assert!(rendered.contains("secretstore_session_duration_seconds_bucket{kind=\"decryption\",le=\"0.5\"} 2\n"));
+		assert!(rendered.contains("secretstore_session_duration_seconds_bucket{kind=\"decryption\",le=\"+Inf\"} 3\n"));
+		assert!(rendered.contains("secretstore_session_duration_seconds_count{kind=\"decryption\"} 3\n"));
+		assert!(rendered.contains("secretstore_active_sessions{kind=\"signing\"} 1\n"));
+		assert!(rendered.contains("secretstore_queued_messages{kind=\"signing\"} 3\n"));
+		assert!(rendered.contains(&format!("secretstore_peer_connected{{peer=\"{:?}\"}} 0\n", peer)));
+		assert!(rendered.contains("secretstore_stored_keys 10\n"));
+	}
+
+	#[test]
+	fn traffic_is_accounted_and_rendered() {
+		let metrics = ClusterMetrics::default();
+		metrics.on_session_traffic("decryption", 100, 200);
+		metrics.on_session_traffic("decryption", 10, 20);
+		let peer = Random.generate().unwrap().public().clone();
+		metrics.on_bytes_sent(&peer, 300);
+		metrics.on_bytes_sent(&peer, 30);
+		metrics.on_bytes_received(&peer, 400);
+		assert_eq!(metrics.session_traffic.lock().get("decryption"), Some(&Traffic { sent: 110, received: 220 }));
+		assert_eq!(metrics.peer_traffic.lock().get(&peer), Some(&Traffic { sent: 330, received: 400 }));
+
+		let rendered = metrics.render(&ClusterGauges::default());
+		assert!(rendered.contains("secretstore_session_sent_bytes_total{kind=\"decryption\"} 110\n"));
+		assert!(rendered.contains("secretstore_session_received_bytes_total{kind=\"decryption\"} 220\n"));
+		assert!(rendered.contains(&format!("secretstore_peer_sent_bytes_total{{peer=\"{:?}\"}} 330\n", peer)));
+		assert!(rendered.contains(&format!("secretstore_peer_received_bytes_total{{peer=\"{:?}\"}} 400\n", peer)));
+	}
+
+	#[test]
+	fn throttled_peers_are_rendered() {
+		let metrics = ClusterMetrics::default();
+		let peer = Random.generate().unwrap().public().clone();
+		metrics.on_peer_throttled(&peer, "session");
+		metrics.on_peer_throttled(&peer, "session");
+		metrics.on_peer_throttled(&peer, "session_error");
+
+		let rendered = metrics.render(&ClusterGauges::default());
+		assert!(rendered.contains(&format!("secretstore_peer_throttled_total{{peer=\"{:?}\",class=\"session\"}} 2\n", peer)));
+		assert!(rendered.contains(&format!("secretstore_peer_throttled_total{{peer=\"{:?}\",class=\"session_error\"}} 1\n", peer)));
+	}
+
+	#[test]
+	fn peer_health_is_tracked() {
+		let metrics = ClusterMetrics::default();
+		let node1 = Random.generate().unwrap().public().clone();
+		let node2 = Random.generate().unwrap().public().clone();
+		metrics.on_session_completed(&vec![node1.clone(), node2.clone()].into_iter().collect(), 100);
+		metrics.on_session_completed(&vec![node1.clone()].into_iter().collect(), 200);
+		metrics.on_peer_time(&node2, 95, 100);
+
+		assert_eq!(metrics.peer_health(&node1, true), PeerHealth {
+			node: node1.clone(),
+			is_connected: true,
+			last_session: Some(200),
+			clock_skew: None,
+		});
+		assert_eq!(metrics.peer_health(&node2, false), PeerHealth {
+			node: node2.clone(),
+			is_connected: false,
+			last_session: Some(100),
+			clock_skew: Some(-5),
+		});
+	}
+}
@@ -18,34 +18,118 @@ use std::time;
 use std::sync::{Arc, Weak};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::collections::{VecDeque, BTreeSet, BTreeMap};
-use parking_lot::RwLock;
-use ethkey::{Public, Secret, Signature};
-use key_server_cluster::{Error, NodeId, SessionId, AclStorage, KeyStorage, DocumentKeyShare, EncryptedDocumentKeyShadow, SessionMeta};
+use parking_lot::{Mutex, RwLock};
+use ethkey::{Public, Secret, Signature, recover};
+use audit_log::unix_timestamp;
+use key_server_cluster::{Error, NodeId, SessionId, AclStorage, KeyStorage, DocumentKeyShare, EncryptedDocumentKeyShadow, ReEncryptedDocumentKey, SessionMeta,
+	SessionsRateLimits, ClusterTimeouts, ClusterMetrics, ClusterGauges, SessionOutcome, NodeKeyPair, AttestedServerKeyPublic, ExportedKeyShare};
 use key_server_cluster::cluster::{Cluster, ClusterData, ClusterView, ClusterConfiguration};
-use key_server_cluster::message::{self, Message, GenerationMessage, EncryptionMessage, DecryptionMessage, SigningMessage};
+use key_server_cluster::session_journal::{SessionJournal, JournalSessionId};
+use key_server_cluster::message::{self, Message, GenerationMessage, EncryptionMessage, DecryptionMessage, ReEncryptionMessage, SigningMessage,
+	EcdsaSigningMessage, ShareRecoveryMessage, ShareRefreshMessage, KeyDerivationMessage, KeyDeletionMessage,
+	ShareMoveMessage, ServerKeyRetrievalMessage, ShareBootstrapMessage, KeyExportMessage};
 use key_server_cluster::generation_session::{Session as GenerationSession, SessionImpl as GenerationSessionImpl,
 	SessionParams as GenerationSessionParams, SessionState as GenerationSessionState};
 use key_server_cluster::decryption_session::{Session as DecryptionSession, SessionImpl as DecryptionSessionImpl,
 	DecryptionSessionId, SessionParams as DecryptionSessionParams};
+use key_server_cluster::re_encryption_session::{Session as ReEncryptionSession, SessionImpl as ReEncryptionSessionImpl,
+	SessionParams as ReEncryptionSessionParams};
 use key_server_cluster::encryption_session::{Session as EncryptionSession, SessionImpl as EncryptionSessionImpl,
 	SessionParams as EncryptionSessionParams, SessionState as EncryptionSessionState};
 use key_server_cluster::signing_session::{Session as SigningSession, SessionImpl as SigningSessionImpl,
 	SigningSessionId, SessionParams as SigningSessionParams};
+use key_server_cluster::ecdsa_signing_session::{Session as EcdsaSigningSession, SessionImpl as EcdsaSigningSessionImpl,
+	SessionParams as EcdsaSigningSessionParams};
+use key_server_cluster::share_recovery_session::{Session as ShareRecoverySession, SessionImpl as ShareRecoverySessionImpl,
+	SessionParams as ShareRecoverySessionParams, SessionState as ShareRecoverySessionState};
+use key_server_cluster::share_refresh_session::{Session as ShareRefreshSession, SessionImpl as ShareRefreshSessionImpl,
+	SessionParams as ShareRefreshSessionParams, SessionState as ShareRefreshSessionState};
+use key_server_cluster::key_derivation_session::{Session as KeyDerivationSession, SessionImpl as KeyDerivationSessionImpl,
+	SessionParams as KeyDerivationSessionParams, SessionState as KeyDerivationSessionState};
+use key_server_cluster::key_deletion_session::{Session as KeyDeletionSession, SessionImpl as KeyDeletionSessionImpl,
+	SessionParams as KeyDeletionSessionParams, SessionState as KeyDeletionSessionState};
+use key_server_cluster::share_move_session::{self, Session as ShareMoveSession, SessionImpl as ShareMoveSessionImpl,
+	SessionParams as ShareMoveSessionParams, SessionState as ShareMoveSessionState};
+use key_server_cluster::server_key_retrieval_session::{Session as ServerKeyRetrievalSession, SessionImpl as ServerKeyRetrievalSessionImpl,
+	SessionParams as ServerKeyRetrievalSessionParams, SessionState as ServerKeyRetrievalSessionState};
+use key_server_cluster::share_bootstrap_session::{Session as ShareBootstrapSession, SessionImpl as ShareBootstrapSessionImpl,
+	SessionParams as ShareBootstrapSessionParams, SessionState as ShareBootstrapSessionState};
+use key_server_cluster::key_export_session::{Session as KeyExportSession, SessionImpl as KeyExportSessionImpl,
+	SessionParams as KeyExportSessionParams, SessionState as KeyExportSessionState};
 
 /// When there are no session-related messages for SESSION_TIMEOUT_INTERVAL seconds,
 /// we must treat this session as stalled && finish it with an error.
 /// This timeout is for cases when node is responding to KeepAlive messages, but intentionally ignores
 /// session messages.
 const SESSION_TIMEOUT_INTERVAL: u64 = 60;
+/// When session is not completed within SESSION_TOTAL_TIMEOUT_INTERVAL seconds, it is finished with an error,
+/// even if it is still (slowly) making progress.
+const SESSION_TOTAL_TIMEOUT_INTERVAL: u64 = 600;
+/// Max number of messages, which are queued for single session until session is ready to process them.
+pub const MAX_SESSION_QUEUE_SIZE: usize = 1024;
+/// Sessions initializations are remembered for RECENT_INITIALIZATIONS_INTERVAL seconds to detect retransmitted initializations.
+const RECENT_INITIALIZATIONS_INTERVAL: u64 = 60;
+/// Per-minute session rate limits are checked against sessions, started within this interval.
+const RATE_LIMIT_INTERVAL: u64 = 60;
 
 /// Generic cluster session.
 pub trait ClusterSession {
 	/// If session is finished (either with succcess or not).
 	fn is_finished(&self) -> bool;
+	/// If session is finished with an error.
+	fn is_failed(&self) -> bool;
 	/// When it takes too much time to complete session.
 	fn on_session_timeout(&self);
 	/// When it takes too much time to receive response from the node.
 	fn on_node_timeout(&self, node_id: &NodeId);
+	/// Cancel session on this node: report error to other nodes, restore key storage && fail session.
+	/// Does nothing if session is already finished.
+	fn cancel(&self);
+}
+
+/// Cluster session event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+	/// Session has been initialized on this node.
+	Initialized,
+	/// Session initialization has been confirmed by other nodes.
+	Confirmed,
+	/// Session has completed successfully on this node.
+	Finished,
+	/// Session has failed on this node.
+	Failed(Error),
+}
+
+/// Cluster session events listener.
+pub trait SessionEventListener: Send + Sync {
+	/// When session has passed to the next stage. Called while session is locked => must not call session methods.
+	fn on_session_event(&self, session_id: &SessionId, event: SessionEvent);
+}
+
+/// Listeners of single cluster session events.
+#[derive(Default)]
+pub struct SessionEventListeners {
+	/// Registered listeners.
+	listeners: Mutex<Vec<Arc<SessionEventListener>>>,
+}
+
+/// Kind of the cluster session. Session nonces are tracked separately for every kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SessionKind {
+	Generation,
+	Encryption,
+	Decryption,
+	ReEncryption,
+	Signing,
+	EcdsaSigning,
+	ShareRecovery,
+	ShareRefresh,
+	KeyDerivation,
+	KeyDeletion,
+	ShareMove,
+	ServerKeyRetrieval,
+	ShareBootstrap,
+	KeyExport,
 }
 
 /// Active sessions on this cluster.
@@ -56,18 +140,46 @@ pub struct ClusterSessions {
 	pub encryption_sessions: ClusterSessionsContainer<SessionId, EncryptionSessionImpl, EncryptionMessage>,
 	/// Decryption sessions.
 	pub decryption_sessions: ClusterSessionsContainer<DecryptionSessionId, DecryptionSessionImpl, DecryptionMessage>,
+	/// Re-encryption sessions.
+	pub reencryption_sessions: ClusterSessionsContainer<DecryptionSessionId, ReEncryptionSessionImpl, ReEncryptionMessage>,
 	/// Signing sessions.
 	pub signing_sessions: ClusterSessionsContainer<SigningSessionId, SigningSessionImpl, SigningMessage>,
+	/// ECDSA signing sessions.
+	pub ecdsa_signing_sessions: ClusterSessionsContainer<SigningSessionId, EcdsaSigningSessionImpl, EcdsaSigningMessage>,
+	/// Share recovery sessions.
+	pub share_recovery_sessions: ClusterSessionsContainer<SessionId, ShareRecoverySessionImpl, ShareRecoveryMessage>,
+	/// Share refresh sessions.
+	pub share_refresh_sessions: ClusterSessionsContainer<SessionId, ShareRefreshSessionImpl, ShareRefreshMessage>,
+	/// Key derivation sessions.
+	pub key_derivation_sessions: ClusterSessionsContainer<SessionId, KeyDerivationSessionImpl, KeyDerivationMessage>,
+	/// Key deletion sessions.
+	pub key_deletion_sessions: ClusterSessionsContainer<SessionId, KeyDeletionSessionImpl, KeyDeletionMessage>,
+	/// Share move sessions.
+	pub share_move_sessions: ClusterSessionsContainer<SessionId, ShareMoveSessionImpl, ShareMoveMessage>,
+	/// Server key retrieval sessions.
+	pub server_key_retrieval_sessions: ClusterSessionsContainer<SessionId, ServerKeyRetrievalSessionImpl, ServerKeyRetrievalMessage>,
+	/// Share bootstrap sessions.
+	pub share_bootstrap_sessions: ClusterSessionsContainer<SessionId, ShareBootstrapSessionImpl, ShareBootstrapMessage>,
+	/// Key export sessions.
+	pub key_export_sessions: ClusterSessionsContainer<SessionId, KeyExportSessionImpl, KeyExportMessage>,
 	/// Self node id.
 	self_node_id: NodeId,
+	/// Self node key pair.
+	self_key_pair: Arc<NodeKeyPair>,
 	/// All nodes ids.
 	nodes: BTreeSet<NodeId>,
+	/// Nodes, which are not allowed to start share administration sessions.
+	read_only_nodes: BTreeSet<NodeId>,
 	/// Reference to key storage
 	key_storage: Arc<KeyStorage>,
 	/// Reference to ACL storage
 	acl_storage: Arc<AclStorage>,
 	/// Make faulty generation sessions.
 	make_faulty_generation_sessions: AtomicBool,
+	/// Time when sessions processing has been paused.
+	paused_at: RwLock<Option<time::Instant>>,
+	/// Is this node draining (i.e. refusing all new sessions, except for share moves && bootstraps)?
+	is_draining: AtomicBool,
 	/// Always-increasing sessions counter. Is used as session nonce to prevent replay attacks:
 	/// 1) during handshake, KeyServers generate new random key to encrypt messages
 	/// => there's no way to use messages from previous connections for replay attacks
@@ -75,25 +187,61 @@ pub struct ClusterSessions {
 	/// 3) when slave KeyServer receives session initialization message, it checks that new nonce is larger than previous (from the same master)
 	/// => there's no way to use messages from previous sessions for replay attacks
 	/// 4) KeyServer checks that each session message contains the same nonce that initialization message
+	/// 5) maximal nonces (both generated and received) are saved to the key storage
+	/// => there's no way to use messages from sessions, started before restart, for replay attacks
 	/// Given that: (A) handshake is secure and (B) session itself is initially replay-protected
 	/// => this guarantees that sessions are replay-protected.
 	session_counter: AtomicUsize,
-	/// Maximal session nonce, received from given connection.
-	max_nonce: RwLock<BTreeMap<NodeId, u64>>,
+	/// Maximal session nonce, received from given node for sessions of given kind. Cache of persistent values.
+	max_nonce: RwLock<BTreeMap<(NodeId, SessionKind), u64>>,
+	/// Recently accepted sessions initializations: (master node, session nonce) => time of initialization.
+	/// Nonces are unique across all kinds of sessions, started by the same master.
+	recent_initializations: Mutex<BTreeMap<(NodeId, u64), time::Instant>>,
+	/// Limiter of requester-initiated sessions.
+	rate_limiter: SessionsRateLimiter,
+	/// Sessions metrics.
+	metrics: Arc<ClusterMetrics>,
+}
+
+/// Limiter of requester-initiated (decryption && signing) sessions, started by this node.
+pub struct SessionsRateLimiter {
+	/// Configured limits.
+	limits: SessionsRateLimits,
+	/// Sessions, started within last RATE_LIMIT_INTERVAL seconds.
+	recent_sessions: Mutex<VecDeque<(time::Instant, Public)>>,
 }
 
 /// Active sessions container.
 pub struct ClusterSessionsContainer<K, V, M> {
 	/// Active sessions.
 	pub sessions: RwLock<BTreeMap<K, QueuedSession<V, M>>>,
+	/// Sessions timeouts.
+	timeouts: SessionTimeouts,
+	/// Sessions kind name && metrics, updated when session is removed from the container.
+	metrics: Option<(&'static str, Arc<ClusterMetrics>)>,
+	/// Sessions kind name && journal, where sessions creation && removal are recorded.
+	journal: Option<(&'static str, Arc<SessionJournal>)>,
+}
+
+/// Session liveness timeouts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionTimeouts {
+	/// Session is stalled if no messages have been received within this interval.
+	pub idle_timeout: time::Duration,
+	/// Session is stalled if it is not completed within this interval.
+	pub total_timeout: time::Duration,
 }
 
 /// Session and its message queue.
 pub struct QueuedSession<V, M> {
 	/// Session master.
 	pub master: NodeId,
+	/// Requester, which has started the session on this node. None on slave nodes && for sessions without requester.
+	pub requester: Option<Public>,
 	/// Cluster view.
-	pub cluster_view: Arc<ClusterView>,
+	pub cluster_view: Arc<Cluster>,
+	/// Session creation time.
+	pub creation_time: time::Instant,
 	/// Last received message time.
 	pub last_message_time: time::Instant,
 	/// Generation session.
@@ -132,6 +280,16 @@ pub struct DecryptionSessionWrapper {
 	cluster: Weak<ClusterData>,
 }
 
+/// Re-encryption session implementation, which removes session from cluster on drop.
+pub struct ReEncryptionSessionWrapper {
+	/// Wrapped session.
+	session: Arc<ReEncryptionSession>,
+	/// Session Id.
+	session_id: DecryptionSessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
 /// Signing session implementation, which removes session from cluster on drop.
 pub struct SigningSessionWrapper {
 	/// Wrapped session.
@@ -142,35 +300,331 @@ pub struct SigningSessionWrapper {
 	cluster: Weak<ClusterData>,
 }
 
+/// ECDSA signing session implementation, which removes session from cluster on drop.
+pub struct EcdsaSigningSessionWrapper {
+	/// Wrapped session.
+	session: Arc<EcdsaSigningSession>,
+	/// Session Id.
+	session_id: SigningSessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Share recovery session implementation, which removes session from cluster on drop.
+pub struct ShareRecoverySessionWrapper {
+	/// Wrapped session.
+	session: Arc<ShareRecoverySession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Share refresh session implementation, which removes session from cluster on drop.
+pub struct ShareRefreshSessionWrapper {
+	/// Wrapped session.
+	session: Arc<ShareRefreshSession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Key derivation session implementation, which removes session from cluster on drop.
+pub struct KeyDerivationSessionWrapper {
+	/// Wrapped session.
+	session: Arc<KeyDerivationSession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Key deletion session implementation, which removes session from cluster on drop.
+pub struct KeyDeletionSessionWrapper {
+	/// Wrapped session.
+	session: Arc<KeyDeletionSession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Share move session implementation, which removes session from cluster on drop.
+pub struct ShareMoveSessionWrapper {
+	/// Wrapped session.
+	session: Arc<ShareMoveSession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Server key retrieval session implementation, which removes session from cluster on drop.
+pub struct ServerKeyRetrievalSessionWrapper {
+	/// Wrapped session.
+	session: Arc<ServerKeyRetrievalSession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Share bootstrap session implementation, which removes session from cluster on drop.
+pub struct ShareBootstrapSessionWrapper {
+	/// Wrapped session.
+	session: Arc<ShareBootstrapSession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+/// Key export session implementation, which removes session from cluster on drop.
+pub struct KeyExportSessionWrapper {
+	/// Wrapped session.
+	session: Arc<KeyExportSession>,
+	/// Session Id.
+	session_id: SessionId,
+	/// Cluster data reference.
+	cluster: Weak<ClusterData>,
+}
+
+impl SessionEventListeners {
+	/// Register new listener.
+	pub fn add(&self, listener: Arc<SessionEventListener>) {
+		self.listeners.lock().push(listener);
+	}
+
+	/// Notify all registered listeners.
+	pub fn notify(&self, session_id: &SessionId, event: SessionEvent) {
+		for listener in self.listeners.lock().iter() {
+			listener.on_session_event(session_id, event.clone());
+		}
+	}
+}
+
 impl ClusterSessions {
 	/// Create new cluster sessions container.
 	pub fn new(config: &ClusterConfiguration) -> Self {
+		// continue numbering sessions from the maximal nonce, used before restart
+		// (if it cannot be read, sessions are rejected by other nodes until counter reaches previous value)
+		let self_node_id = config.self_key_pair.public().clone();
+		let session_counter = SessionKind::all().iter()
+			.filter_map(|kind| config.key_storage.max_session_nonce(&self_node_id, kind.name()).unwrap_or_default())
+			.max()
+			.unwrap_or_default();
+
+		let metrics = Arc::new(ClusterMetrics::default());
+		let timeouts: &ClusterTimeouts = &config.timeouts;
 		ClusterSessions {
-			self_node_id: config.self_key_pair.public().clone(),
+			self_node_id: self_node_id,
+			self_key_pair: config.self_key_pair.clone(),
 			nodes: config.key_server_set.get().keys().cloned().collect(),
+			read_only_nodes: config.read_only_nodes.clone(),
 			acl_storage: config.acl_storage.clone(),
 			key_storage: config.key_storage.clone(),
-			generation_sessions: ClusterSessionsContainer::new(),
-			encryption_sessions: ClusterSessionsContainer::new(),
-			decryption_sessions: ClusterSessionsContainer::new(),
-			signing_sessions: ClusterSessionsContainer::new(),
+			generation_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.generation_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::Generation.name(), metrics.clone())
+				.with_journal(SessionKind::Generation.name(), config.session_journal.clone()),
+			encryption_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.encryption_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::Encryption.name(), metrics.clone())
+				.with_journal(SessionKind::Encryption.name(), config.session_journal.clone()),
+			decryption_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.decryption_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::Decryption.name(), metrics.clone())
+				.with_journal(SessionKind::Decryption.name(), config.session_journal.clone()),
+			reencryption_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.decryption_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::ReEncryption.name(), metrics.clone())
+				.with_journal(SessionKind::ReEncryption.name(), config.session_journal.clone()),
+			signing_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.signing_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::Signing.name(), metrics.clone())
+				.with_journal(SessionKind::Signing.name(), config.session_journal.clone()),
+			ecdsa_signing_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.signing_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::EcdsaSigning.name(), metrics.clone())
+				.with_journal(SessionKind::EcdsaSigning.name(), config.session_journal.clone()),
+			share_recovery_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.share_recovery_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::ShareRecovery.name(), metrics.clone())
+				.with_journal(SessionKind::ShareRecovery.name(), config.session_journal.clone()),
+			share_refresh_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.share_refresh_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::ShareRefresh.name(), metrics.clone())
+				.with_journal(SessionKind::ShareRefresh.name(), config.session_journal.clone()),
+			key_derivation_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.encryption_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::KeyDerivation.name(), metrics.clone())
+				.with_journal(SessionKind::KeyDerivation.name(), config.session_journal.clone()),
+			key_deletion_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.encryption_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::KeyDeletion.name(), metrics.clone())
+				.with_journal(SessionKind::KeyDeletion.name(), config.session_journal.clone()),
+			share_move_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.share_refresh_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::ShareMove.name(), metrics.clone())
+				.with_journal(SessionKind::ShareMove.name(), config.session_journal.clone()),
+			server_key_retrieval_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.encryption_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::ServerKeyRetrieval.name(), metrics.clone())
+				.with_journal(SessionKind::ServerKeyRetrieval.name(), config.session_journal.clone()),
+			share_bootstrap_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.share_refresh_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::ShareBootstrap.name(), metrics.clone())
+				.with_journal(SessionKind::ShareBootstrap.name(), config.session_journal.clone()),
+			key_export_sessions: ClusterSessionsContainer::with_timeouts(SessionTimeouts::new(timeouts.encryption_idle_timeout, timeouts.total_timeout))
+				.with_metrics(SessionKind::KeyExport.name(), metrics.clone())
+				.with_journal(SessionKind::KeyExport.name(), config.session_journal.clone()),
 			make_faulty_generation_sessions: AtomicBool::new(false),
-			session_counter: AtomicUsize::new(0),
+			paused_at: RwLock::new(None),
+			is_draining: AtomicBool::new(false),
+			session_counter: AtomicUsize::new(session_counter as usize),
 			max_nonce: RwLock::new(BTreeMap::new()),
+			recent_initializations: Mutex::new(BTreeMap::new()),
+			rate_limiter: SessionsRateLimiter::new(config.rate_limits.clone()),
+			metrics: metrics,
+		}
+	}
+
+	/// Get sessions metrics.
+	pub fn metrics(&self) -> &Arc<ClusterMetrics> {
+		&self.metrics
+	}
+
+	/// Account session message of given size (in bytes), received from other node.
+	/// If session has exceeded traffic limit, it is cancelled && error is returned.
+	pub fn on_message_received(&self, message: &Message, size: usize) -> Result<(), Error> {
+		match *message {
+			Message::Generation(ref message) => self.generation_sessions.on_message_received(message.session_id(), size),
+			Message::Encryption(ref message) => self.encryption_sessions.on_message_received(message.session_id(), size),
+			Message::Decryption(ref message) => self.decryption_sessions.on_message_received(
+				&DecryptionSessionId::new(message.session_id().clone(), message.sub_session_id().clone()), size),
+			Message::ReEncryption(ref message) => self.reencryption_sessions.on_message_received(
+				&DecryptionSessionId::new(message.session_id().clone(), message.sub_session_id().clone()), size),
+			Message::Signing(ref message) => self.signing_sessions.on_message_received(
+				&SigningSessionId::new(message.session_id().clone(), message.sub_session_id().clone()), size),
+			Message::EcdsaSigning(ref message) => self.ecdsa_signing_sessions.on_message_received(
+				&SigningSessionId::new(message.session_id().clone(), message.sub_session_id().clone()), size),
+			Message::ShareRecovery(ref message) => self.share_recovery_sessions.on_message_received(message.session_id(), size),
+			Message::ShareRefresh(ref message) => self.share_refresh_sessions.on_message_received(message.session_id(), size),
+			Message::KeyDerivation(ref message) => self.key_derivation_sessions.on_message_received(message.session_id(), size),
+			Message::KeyDeletion(ref message) => self.key_deletion_sessions.on_message_received(message.session_id(), size),
+			Message::ShareMove(ref message) => self.share_move_sessions.on_message_received(message.session_id(), size),
+			Message::ServerKeyRetrieval(ref message) => self.server_key_retrieval_sessions.on_message_received(message.session_id(), size),
+			Message::ShareBootstrap(ref message) => self.share_bootstrap_sessions.on_message_received(message.session_id(), size),
+			Message::KeyExport(ref message) => self.key_export_sessions.on_message_received(message.session_id(), size),
+			Message::Cluster(_) => Ok(()),
 		}
 	}
 
+	/// Fill sessions-related gauges.
+	pub fn fill_gauges(&self, gauges: &mut ClusterGauges) {
+		self.generation_sessions.fill_gauges(SessionKind::Generation.name(), gauges);
+		self.encryption_sessions.fill_gauges(SessionKind::Encryption.name(), gauges);
+		self.decryption_sessions.fill_gauges(SessionKind::Decryption.name(), gauges);
+		self.reencryption_sessions.fill_gauges(SessionKind::ReEncryption.name(), gauges);
+		self.signing_sessions.fill_gauges(SessionKind::Signing.name(), gauges);
+		self.ecdsa_signing_sessions.fill_gauges(SessionKind::EcdsaSigning.name(), gauges);
+		self.share_recovery_sessions.fill_gauges(SessionKind::ShareRecovery.name(), gauges);
+		self.share_refresh_sessions.fill_gauges(SessionKind::ShareRefresh.name(), gauges);
+		self.key_derivation_sessions.fill_gauges(SessionKind::KeyDerivation.name(), gauges);
+		self.key_deletion_sessions.fill_gauges(SessionKind::KeyDeletion.name(), gauges);
+		self.share_move_sessions.fill_gauges(SessionKind::ShareMove.name(), gauges);
+		self.server_key_retrieval_sessions.fill_gauges(SessionKind::ServerKeyRetrieval.name(), gauges);
+		self.share_bootstrap_sessions.fill_gauges(SessionKind::ShareBootstrap.name(), gauges);
+		self.key_export_sessions.fill_gauges(SessionKind::KeyExport.name(), gauges);
+	}
+
 	#[cfg(test)]
 	pub fn make_faulty_generation_sessions(&self) {
 		self.make_faulty_generation_sessions.store(true, Ordering::Relaxed);
 	}
 
+	/// Pause processing of all sessions messages (i.e. while key storage is being backed up).
+	/// Sessions are not time-outed while paused.
+	pub fn pause(&self) {
+		let mut paused_at = self.paused_at.write();
+		if paused_at.is_none() {
+			*paused_at = Some(time::Instant::now());
+		}
+	}
+
+	/// Resume processing of sessions messages.
+	pub fn resume(&self) {
+		let paused_at = match self.paused_at.write().take() {
+			Some(paused_at) => paused_at,
+			None => return,
+		};
+
+		// time spent in pause should not be counted towards session timeout
+		let paused_for = time::Instant::now() - paused_at;
+		self.generation_sessions.suspend_timeouts(paused_for);
+		self.encryption_sessions.suspend_timeouts(paused_for);
+		self.decryption_sessions.suspend_timeouts(paused_for);
+		self.reencryption_sessions.suspend_timeouts(paused_for);
+		self.signing_sessions.suspend_timeouts(paused_for);
+		self.ecdsa_signing_sessions.suspend_timeouts(paused_for);
+		self.share_recovery_sessions.suspend_timeouts(paused_for);
+		self.share_refresh_sessions.suspend_timeouts(paused_for);
+		self.key_derivation_sessions.suspend_timeouts(paused_for);
+		self.key_deletion_sessions.suspend_timeouts(paused_for);
+		self.share_move_sessions.suspend_timeouts(paused_for);
+		self.server_key_retrieval_sessions.suspend_timeouts(paused_for);
+		self.share_bootstrap_sessions.suspend_timeouts(paused_for);
+		self.key_export_sessions.suspend_timeouts(paused_for);
+	}
+
+	/// Check that sessions processing is not paused.
+	pub fn check_not_paused(&self) -> Result<(), Error> {
+		match self.paused_at.read().is_some() {
+			true => Err(Error::SessionPaused),
+			false => Ok(()),
+		}
+	}
+
+	/// Start draining this node: all new sessions, except for share moves && bootstraps, are refused from now on.
+	/// Already running sessions are not affected.
+	pub fn start_draining(&self) {
+		self.is_draining.store(true, Ordering::SeqCst);
+	}
+
+	/// Stop draining this node: new sessions are accepted again.
+	pub fn stop_draining(&self) {
+		self.is_draining.store(false, Ordering::SeqCst);
+	}
+
+	/// Is this node draining?
+	pub fn is_draining(&self) -> bool {
+		self.is_draining.load(Ordering::SeqCst)
+	}
+
+	/// Check that this node is not draining.
+	fn check_not_draining(&self) -> Result<(), Error> {
+		match self.is_draining() {
+			true => Err(Error::NodeDraining),
+			false => Ok(()),
+		}
+	}
+
+	/// Get number of active sessions of all kinds, except for share moves && bootstraps.
+	pub fn active_sessions_count(&self) -> usize {
+		self.generation_sessions.sessions.read().len()
+			+ self.encryption_sessions.sessions.read().len()
+			+ self.decryption_sessions.sessions.read().len()
+			+ self.reencryption_sessions.sessions.read().len()
+			+ self.signing_sessions.sessions.read().len()
+			+ self.ecdsa_signing_sessions.sessions.read().len()
+			+ self.share_recovery_sessions.sessions.read().len()
+			+ self.share_refresh_sessions.sessions.read().len()
+			+ self.key_derivation_sessions.sessions.read().len()
+			+ self.key_deletion_sessions.sessions.read().len()
+			+ self.server_key_retrieval_sessions.sessions.read().len()
+			+ self.key_export_sessions.sessions.read().len()
+	}
+
 	/// Create new generation session.
 	pub fn new_generation_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<GenerationSessionImpl>, Error> {
+		self.check_not_draining()?;
+
 		// check that there's no finished encryption session with the same id
 		if self.key_storage.contains(&session_id) {
 			return Err(Error::DuplicateSessionId);
 		}
+		// check that the key with the same id has not been deleted
+		if self.key_storage.is_tombstoned(&session_id) {
+			return Err(Error::KeyDeleted);
+		}
 
 		// communicating to all other nodes is crucial for encryption session
 		// => check that we have connections to all cluster nodes
@@ -179,7 +633,7 @@ impl ClusterSessions {
 		}
 
 		// check that there's no active encryption session with the same id
-		let nonce = self.check_session_nonce(&master, nonce)?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::Generation)?;
 		self.generation_sessions.insert(master, session_id, cluster.clone(), move ||
 			Ok(GenerationSessionImpl::new(GenerationSessionParams {
 				id: session_id.clone(),
@@ -210,8 +664,9 @@ impl ClusterSessions {
 
 	/// Create new encryption session.
 	pub fn new_encryption_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<EncryptionSessionImpl>, Error> {
+		self.check_not_draining()?;
 		let encrypted_data = self.read_key_share(&session_id, &cluster)?;
-		let nonce = self.check_session_nonce(&master, nonce)?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::Encryption)?;
 
 		self.encryption_sessions.insert(master, session_id, cluster.clone(), move || EncryptionSessionImpl::new(EncryptionSessionParams {
 			id: session_id.clone(),
@@ -237,11 +692,13 @@ impl ClusterSessions {
 
 	/// Create new decryption session.
 	pub fn new_decryption_session(&self, master: NodeId, session_id: SessionId, sub_session_id: Secret, nonce: Option<u64>, cluster: Arc<ClusterView>, requester_signature: Option<Signature>) -> Result<Arc<DecryptionSessionImpl>, Error> {
+		self.check_not_draining()?;
 		let session_id = DecryptionSessionId::new(session_id, sub_session_id);
+		let requester = self.check_rate_limits(&session_id.id, requester_signature.as_ref())?;
 		let encrypted_data = self.read_key_share(&session_id.id, &cluster)?;
-		let nonce = self.check_session_nonce(&master, nonce)?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::Decryption)?;
 
-		self.decryption_sessions.insert(master, session_id.clone(), cluster.clone(), move || DecryptionSessionImpl::new(DecryptionSessionParams {
+		self.decryption_sessions.insert_with_requester(master, session_id.clone(), requester, cluster.clone(), move || DecryptionSessionImpl::new(DecryptionSessionParams {
 			meta: SessionMeta {
 				id: session_id.id,
 				self_node_id: self.self_node_id.clone(),
@@ -250,6 +707,7 @@ impl ClusterSessions {
 			},
 			access_key: session_id.access_key,
 			key_share: encrypted_data,
+			key_storage: self.key_storage.clone(),
 			acl_storage: self.acl_storage.clone(),
 			cluster: cluster,
 			nonce: nonce,
@@ -274,13 +732,57 @@ impl ClusterSessions {
 			});
 	}
 
+	/// Create new re-encryption session.
+	pub fn new_reencryption_session(&self, master: NodeId, session_id: SessionId, sub_session_id: Secret, nonce: Option<u64>, cluster: Arc<ClusterView>, requester_signature: Option<Signature>) -> Result<Arc<ReEncryptionSessionImpl>, Error> {
+		self.check_not_draining()?;
+		let session_id = DecryptionSessionId::new(session_id, sub_session_id);
+		let requester = self.check_rate_limits(&session_id.id, requester_signature.as_ref())?;
+		let encrypted_data = self.read_key_share(&session_id.id, &cluster)?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::ReEncryption)?;
+
+		self.reencryption_sessions.insert_with_requester(master, session_id.clone(), requester, cluster.clone(), move || ReEncryptionSessionImpl::new(ReEncryptionSessionParams {
+			meta: SessionMeta {
+				id: session_id.id,
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: encrypted_data.threshold,
+			},
+			access_key: session_id.access_key,
+			key_share: encrypted_data,
+			key_storage: self.key_storage.clone(),
+			acl_storage: self.acl_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}, requester_signature))
+	}
+
+	/// Send re-encryption session error.
+	pub fn respond_with_reencryption_error(&self, session_id: &SessionId, sub_session_id: &Secret, to: &NodeId, error: message::ReEncryptionSessionError) {
+		let session_id = DecryptionSessionId::new(session_id.clone(), sub_session_id.clone());
+		self.reencryption_sessions.sessions.read().get(&session_id)
+			.map(|s| {
+				// error in re-encryption session is non-fatal, if occurs on slave node
+				// => either respond with error
+				// => or broadcast error
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.cluster_view.broadcast(Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(error)));
+				} else {
+					let _ = s.cluster_view.send(to, Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(error)));
+				}
+			});
+	}
+
 	/// Create new signing session.
 	pub fn new_signing_session(&self, master: NodeId, session_id: SessionId, sub_session_id: Secret, nonce: Option<u64>, cluster: Arc<ClusterView>, requester_signature: Option<Signature>) -> Result<Arc<SigningSessionImpl>, Error> {
+		self.check_not_draining()?;
 		let session_id = SigningSessionId::new(session_id, sub_session_id);
+		let requester = self.check_rate_limits(&session_id.id, requester_signature.as_ref())?;
 		let encrypted_data = self.read_key_share(&session_id.id, &cluster)?;
-		let nonce = self.check_session_nonce(&master, nonce)?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::Signing)?;
 
-		self.signing_sessions.insert(master, session_id.clone(), cluster.clone(), move || SigningSessionImpl::new(SigningSessionParams {
+		self.signing_sessions.insert_with_requester(master, session_id.clone(), requester, cluster.clone(), move || SigningSessionImpl::new(SigningSessionParams {
 			meta: SessionMeta {
 				id: session_id.id,
 				self_node_id: self.self_node_id.clone(),
@@ -289,6 +791,7 @@ impl ClusterSessions {
 			},
 			access_key: session_id.access_key,
 			key_share: encrypted_data,
+			key_storage: self.key_storage.clone(),
 			acl_storage: self.acl_storage.clone(),
 			cluster: cluster,
 			nonce: nonce,
@@ -313,102 +816,781 @@ impl ClusterSessions {
 			});
 	}
 
-	/// Stop sessions that are stalling.
-	pub fn stop_stalled_sessions(&self) {
-		self.generation_sessions.stop_stalled_sessions();
-		self.encryption_sessions.stop_stalled_sessions();
-		self.decryption_sessions.stop_stalled_sessions();
-		self.signing_sessions.stop_stalled_sessions();
+	/// Create new ECDSA signing session.
+	pub fn new_ecdsa_signing_session(&self, master: NodeId, session_id: SessionId, sub_session_id: Secret, nonce: Option<u64>, cluster: Arc<ClusterView>, requester_signature: Option<Signature>) -> Result<Arc<EcdsaSigningSessionImpl>, Error> {
+		self.check_not_draining()?;
+		let session_id = SigningSessionId::new(session_id, sub_session_id);
+		let requester = self.check_rate_limits(&session_id.id, requester_signature.as_ref())?;
+		let encrypted_data = self.read_key_share(&session_id.id, &cluster)?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::EcdsaSigning)?;
+
+		self.ecdsa_signing_sessions.insert_with_requester(master, session_id.clone(), requester, cluster.clone(), move || EcdsaSigningSessionImpl::new(EcdsaSigningSessionParams {
+			meta: SessionMeta {
+				id: session_id.id,
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: encrypted_data.threshold,
+			},
+			access_key: session_id.access_key,
+			key_share: encrypted_data,
+			key_storage: self.key_storage.clone(),
+			acl_storage: self.acl_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}, requester_signature))
 	}
 
-	/// When connection to node is lost.
-	pub fn on_connection_timeout(&self, node_id: &NodeId) {
-		self.generation_sessions.on_connection_timeout(node_id);
-		self.encryption_sessions.on_connection_timeout(node_id);
-		self.decryption_sessions.on_connection_timeout(node_id);
-		self.signing_sessions.on_connection_timeout(node_id);
-		self.max_nonce.write().remove(node_id);
+	/// Send ECDSA signing session error.
+	pub fn respond_with_ecdsa_signing_error(&self, session_id: &SessionId, sub_session_id: &Secret, to: &NodeId, error: message::EcdsaSigningSessionError) {
+		let session_id = SigningSessionId::new(session_id.clone(), sub_session_id.clone());
+		self.ecdsa_signing_sessions.sessions.read().get(&session_id)
+			.map(|s| {
+				// error in ECDSA signing session is non-fatal, if occurs on slave node
+				// => either respond with error
+				// => or broadcast error
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.cluster_view.broadcast(Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(error)));
+				} else {
+					let _ = s.cluster_view.send(to, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(error)));
+				}
+			});
 	}
 
-	/// Read key share && remove disconnected nodes.
-	fn read_key_share(&self, key_id: &SessionId, cluster: &Arc<ClusterView>) -> Result<DocumentKeyShare, Error> {
-		let mut encrypted_data = self.key_storage.get(key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+	/// Create new share recovery session.
+	pub fn new_share_recovery_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<ShareRecoverySessionImpl>, Error> {
+		self.check_not_draining()?;
+		self.check_administration_session_master(&master)?;
 
-		// some of nodes, which were encrypting secret may be down
-		// => do not use these in session
-		let disconnected_nodes: BTreeSet<_> = encrypted_data.id_numbers.keys().cloned().collect();
-		for disconnected_node in disconnected_nodes.difference(&cluster.nodes()) {
-			encrypted_data.id_numbers.remove(&disconnected_node);
+		// deleted key must not be recovered from shares of key holders, which have failed to delete it
+		if self.key_storage.is_tombstoned(&session_id) {
+			return Err(Error::KeyDeleted);
 		}
-		Ok(encrypted_data)
-	}
 
-	/// Check or generate new session nonce.
-	fn check_session_nonce(&self, master: &NodeId, nonce: Option<u64>) -> Result<u64, Error> {
-		// if we're master node of the session, then nonce should be generated
-		// if we're slave node of the session, then nonce should be passed from outside
-		debug_assert!((master == &self.self_node_id) == nonce.is_none());
+		// master node could have lost its key share => it is recovering the whole key share
+		// && then every connected node is asked to contribute, including nodes, which are not holding the key
+		let key_share = match self.key_storage.contains(&session_id) {
+			false => None,
+			true => Some(self.read_key_share(&session_id, &cluster)?),
+		};
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::ShareRecovery)?;
 
-		match nonce {
-			Some(nonce) => match nonce > *self.max_nonce.write().entry(master.clone()).or_insert(0) {
-				true => Ok(nonce),
-				false => Err(Error::ReplayProtection),
+		self.share_recovery_sessions.insert(master, session_id, cluster.clone(), move || ShareRecoverySessionImpl::new(ShareRecoverySessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				// threshold is unknown until key data is received from contributing nodes
+				threshold: key_share.as_ref().map(|ks| ks.threshold).unwrap_or(0),
 			},
-			None => Ok(self.session_counter.fetch_add(1, Ordering::Relaxed) as u64 + 1),
-		}
+			key_share: key_share,
+			key_storage: self.key_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}))
 	}
-}
 
-impl<K, V, M> ClusterSessionsContainer<K, V, M> where K: Clone + Ord, V: ClusterSession {
-	pub fn new() -> Self {
-		ClusterSessionsContainer {
-			sessions: RwLock::new(BTreeMap::new()),
-		}
-	}
+	/// Send share recovery session error.
+	pub fn respond_with_share_recovery_error(&self, session_id: &SessionId, to: &NodeId, error: message::ShareRecoverySessionError) {
+		self.share_recovery_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in share recovery session is fatal
+				// => either respond with error to master node
+				// => or broadcast error from master node
 
-	pub fn get(&self, session_id: &K) -> Option<Arc<V>> {
-		self.sessions.read().get(session_id).map(|s| s.session.clone())
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.cluster_view.broadcast(Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(error)));
+				} else {
+					let _ = s.cluster_view.send(to, Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(error)));
+				}
+			});
 	}
 
-	pub fn insert<F: FnOnce() -> Result<V, Error>>(&self, master: NodeId, session_id: K, cluster: Arc<ClusterView>, session: F) -> Result<Arc<V>, Error> {
-		let mut sessions = self.sessions.write();
-		if sessions.contains_key(&session_id) {
-			return Err(Error::DuplicateSessionId);
-		}
+	/// Create new share refresh session.
+	pub fn new_share_refresh_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<ShareRefreshSessionImpl>, Error> {
+		self.check_not_draining()?;
+		self.check_administration_session_master(&master)?;
 
-		let session = Arc::new(session()?);
-		let queued_session = QueuedSession {
-			master: master,
-			cluster_view: cluster,
-			last_message_time: time::Instant::now(),
-			session: session.clone(),
-			queue: VecDeque::new(),
-		};
-		sessions.insert(session_id, queued_session);
-		Ok(session)
-	}
+		let key_share = self.key_storage.get(&session_id).map_err(|e| Error::KeyStorage(e.into()))?;
 
-	pub fn remove(&self, session_id: &K) {
-		self.sessions.write().remove(session_id);
-	}
+		// every key holder must take part in refresh
+		// => check that we have connections to all key holders
+		if key_share.id_numbers.keys().any(|n| !cluster.is_connected(n)) {
+			return Err(Error::NodeDisconnected);
+		}
 
-	pub fn enqueue_message(&self, session_id: &K, sender: NodeId, message: M, is_queued_message: bool) {
-		self.sessions.write().get_mut(session_id)
-			.map(|session| if is_queued_message { session.queue.push_front((sender, message)) }
-				else { session.queue.push_back((sender, message)) });
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::ShareRefresh)?;
+		self.share_refresh_sessions.insert(master, session_id, cluster.clone(), move || ShareRefreshSessionImpl::new(ShareRefreshSessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: key_share.threshold,
+			},
+			key_share: key_share,
+			key_storage: self.key_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}))
 	}
 
-	pub fn dequeue_message(&self, session_id: &K) -> Option<(NodeId, M)> {
-		self.sessions.write().get_mut(session_id)
-			.and_then(|session| session.queue.pop_front())
+	/// Send share refresh session error.
+	pub fn respond_with_share_refresh_error(&self, session_id: &SessionId, to: &NodeId, error: message::ShareRefreshSessionError) {
+		self.share_refresh_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in share refresh session is fatal
+				// => either respond with error to master node
+				// => or broadcast error from master node
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.cluster_view.broadcast(Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(error)));
+				} else {
+					let _ = s.cluster_view.send(to, Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(error)));
+				}
+			});
+	}
+
+	/// Create new key derivation session.
+	pub fn new_key_derivation_session(&self, master: NodeId, session_id: SessionId, parent_key_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<KeyDerivationSessionImpl>, Error> {
+		self.check_not_draining()?;
+
+		// check that there's no key with the same id
+		if self.key_storage.contains(&session_id) {
+			return Err(Error::DuplicateSessionId);
+		}
+		// check that the key with the same id has not been deleted
+		if self.key_storage.is_tombstoned(&session_id) {
+			return Err(Error::KeyDeleted);
+		}
+
+		let parent_key_share = self.key_storage.get(&parent_key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+
+		// every key holder must derive its share
+		// => check that we have connections to all key holders
+		if parent_key_share.id_numbers.keys().any(|n| !cluster.is_connected(n)) {
+			return Err(Error::NodeDisconnected);
+		}
+
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::KeyDerivation)?;
+		self.key_derivation_sessions.insert(master, session_id, cluster.clone(), move || KeyDerivationSessionImpl::new(KeyDerivationSessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: parent_key_share.threshold,
+			},
+			parent_key_id: parent_key_id,
+			parent_key_share: parent_key_share,
+			key_storage: self.key_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}))
+	}
+
+	/// Send key derivation session error.
+	pub fn respond_with_key_derivation_error(&self, session_id: &SessionId, to: &NodeId, error: message::KeyDerivationSessionError) {
+		self.key_derivation_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in key derivation session is fatal
+				// => either respond with error to master node
+				// => or broadcast error from master node
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.cluster_view.broadcast(Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(error)));
+				} else {
+					let _ = s.cluster_view.send(to, Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(error)));
+				}
+			});
+	}
+
+	/// Create new key deletion session.
+	pub fn new_key_deletion_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<KeyDeletionSessionImpl>, Error> {
+		self.check_not_draining()?;
+		let key_share = self.key_storage.get(&session_id).map_err(|e| Error::KeyStorage(e.into()))?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::KeyDeletion)?;
+
+		self.key_deletion_sessions.insert(master, session_id, cluster.clone(), move || KeyDeletionSessionImpl::new(KeyDeletionSessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: key_share.threshold,
+			},
+			key_share: key_share,
+			key_storage: self.key_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}))
+	}
+
+	/// Send key deletion session error.
+	pub fn respond_with_key_deletion_error(&self, session_id: &SessionId, to: &NodeId, error: message::KeyDeletionSessionError) {
+		self.key_deletion_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in key deletion session is fatal
+				// => either respond with error to master node
+				// => or broadcast error from master node
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.cluster_view.broadcast(Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(error)));
+				} else {
+					let _ = s.cluster_view.send(to, Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(error)));
+				}
+			});
+	}
+
+	/// Create new server key retrieval session.
+	pub fn new_server_key_retrieval_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<ServerKeyRetrievalSessionImpl>, Error> {
+		self.check_not_draining()?;
+		let key_share = self.key_storage.get(&session_id).map_err(|e| Error::KeyStorage(e.into()))?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::ServerKeyRetrieval)?;
+
+		self.server_key_retrieval_sessions.insert(master, session_id, cluster.clone(), move || ServerKeyRetrievalSessionImpl::new(ServerKeyRetrievalSessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: key_share.threshold,
+			},
+			key_share: key_share,
+			acl_storage: self.acl_storage.clone(),
+			self_key_pair: self.self_key_pair.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}))
+	}
+
+	/// Send server key retrieval session error.
+	pub fn respond_with_server_key_retrieval_error(&self, session_id: &SessionId, to: &NodeId, error: message::ServerKeyRetrievalSessionError) {
+		self.server_key_retrieval_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in server key retrieval session is fatal
+				// => either respond with error to master node
+				// => or broadcast error from master node
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.cluster_view.broadcast(Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(error)));
+				} else {
+					let _ = s.cluster_view.send(to, Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(error)));
+				}
+			});
+	}
+
+	/// Create new share move session. Share moves are allowed while this node is draining.
+	pub fn new_share_move_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<ShareMoveSessionImpl>, Error> {
+		self.check_administration_session_master(&master)?;
+
+		// check that the key has not been deleted
+		if self.key_storage.is_tombstoned(&session_id) {
+			return Err(Error::KeyDeleted);
+		}
+
+		// the new owner of the share has no key share yet
+		let key_share = match self.key_storage.contains(&session_id) {
+			true => Some(self.key_storage.get(&session_id).map_err(|e| Error::KeyStorage(e.into()))?),
+			false => None,
+		};
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::ShareMove)?;
+
+		self.share_move_sessions.insert(master, session_id, cluster.clone(), move || ShareMoveSessionImpl::new(ShareMoveSessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: key_share.as_ref().map(|ks| ks.threshold).unwrap_or_default(),
+			},
+			key_share: key_share,
+			key_storage: self.key_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}))
+	}
+
+	/// Send share move session error.
+	pub fn respond_with_share_move_error(&self, session_id: &SessionId, to: &NodeId, error: message::ShareMoveSessionError) {
+		self.share_move_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in share move session is fatal
+				// => either respond with error to master node
+				// => or ask all participants to roll back the move from master node
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.session.on_session_error(self.self_node_id.clone(), &error);
+				} else {
+					let _ = s.cluster_view.send(to, Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(error)));
+				}
+			});
+	}
+
+	/// Undo changes of share move session, which is already completed (and forgotten) on this node.
+	pub fn rollback_share_move(&self, master: &NodeId, message: &message::ShareMoveRollback) -> Result<(), Error> {
+		// only the latest share move session of master node could be rolled back
+		let max_nonce = match self.max_nonce.read().get(&(master.clone(), SessionKind::ShareMove)).cloned() {
+			Some(max_nonce) => Some(max_nonce),
+			None => self.key_storage.max_session_nonce(master, SessionKind::ShareMove.name())
+				.map_err(|e| Error::KeyStorage(e.into()))?,
+		};
+		if max_nonce != Some(message.session_nonce) {
+			return Err(Error::ReplayProtection);
+		}
+
+		share_move_session::rollback(&*self.key_storage, &self.self_node_id, master, &message.session, message.id_numbers.iter()
+			.map(|(k, v)| (k.clone().into(), v.clone().into()))
+			.collect())
+	}
+
+	/// Create new share bootstrap session. Share bootstraps are allowed while this node is draining.
+	pub fn new_share_bootstrap_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<ShareBootstrapSessionImpl>, Error> {
+		self.check_administration_session_master(&master)?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::ShareBootstrap)?;
+
+		self.share_bootstrap_sessions.insert(master, session_id, cluster.clone(), move || ShareBootstrapSessionImpl::new(ShareBootstrapSessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: 0,
+			},
+			self_key_pair: self.self_key_pair.clone(),
+			key_storage: self.key_storage.clone(),
+			cluster: cluster,
+			nonce: nonce,
+		}))
+	}
+
+	/// Send share bootstrap session error.
+	pub fn respond_with_share_bootstrap_error(&self, session_id: &SessionId, to: &NodeId, error: message::ShareBootstrapSessionError) {
+		self.share_bootstrap_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in share bootstrap session is fatal
+				// => either respond with error to master node
+				// => or fail the session on master node (there are no changes to undo on other nodes)
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.session.on_session_error(self.self_node_id.clone(), &error);
+				} else {
+					let _ = s.cluster_view.send(to, Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(error)));
+				}
+			});
+	}
+
+	/// Create new key export session.
+	pub fn new_key_export_session(&self, master: NodeId, session_id: SessionId, nonce: Option<u64>, cluster: Arc<ClusterView>) -> Result<Arc<KeyExportSessionImpl>, Error> {
+		self.check_not_draining()?;
+		self.check_administration_session_master(&master)?;
+		let key_share = self.key_storage.get(&session_id).map_err(|e| Error::KeyStorage(e.into()))?;
+		let nonce = self.check_session_nonce(&master, nonce, SessionKind::KeyExport)?;
+
+		self.key_export_sessions.insert(master, session_id, cluster.clone(), move || KeyExportSessionImpl::new(KeyExportSessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: self.self_node_id.clone(),
+				master_node_id: master,
+				threshold: key_share.threshold,
+			},
+			self_key_pair: self.self_key_pair.clone(),
+			key_share: key_share,
+			cluster: cluster,
+			nonce: nonce,
+		}))
+	}
+
+	/// Send key export session error.
+	pub fn respond_with_key_export_error(&self, session_id: &SessionId, to: &NodeId, error: message::KeyExportSessionError) {
+		self.key_export_sessions.sessions.read().get(session_id)
+			.map(|s| {
+				// error in key export session is fatal
+				// => either respond with error to master node
+				// => or fail the session on master node (key shares are never changed by the session)
+
+				// do not bother processing send error, as we already processing error
+				if s.master == self.self_node_id {
+					let _ = s.session.on_session_error(self.self_node_id.clone(), &error);
+				} else {
+					let _ = s.cluster_view.send(to, Message::KeyExport(KeyExportMessage::KeyExportSessionError(error)));
+				}
+			});
+	}
+
+	/// Stop sessions that are stalling.
+	pub fn stop_stalled_sessions(&self) {
+		self.forget_recent_initializations(time::Instant::now());
+		if self.check_not_paused().is_err() {
+			return;
+		}
+
+		self.generation_sessions.stop_stalled_sessions();
+		self.encryption_sessions.stop_stalled_sessions();
+		self.decryption_sessions.stop_stalled_sessions();
+		self.reencryption_sessions.stop_stalled_sessions();
+		self.signing_sessions.stop_stalled_sessions();
+		self.ecdsa_signing_sessions.stop_stalled_sessions();
+		self.share_recovery_sessions.stop_stalled_sessions();
+		self.share_refresh_sessions.stop_stalled_sessions();
+		self.key_derivation_sessions.stop_stalled_sessions();
+		self.key_deletion_sessions.stop_stalled_sessions();
+		self.share_move_sessions.stop_stalled_sessions();
+		self.server_key_retrieval_sessions.stop_stalled_sessions();
+		self.share_bootstrap_sessions.stop_stalled_sessions();
+		self.key_export_sessions.stop_stalled_sessions();
+	}
+
+	/// When connection to node is lost.
+	pub fn on_connection_timeout(&self, node_id: &NodeId) {
+		self.generation_sessions.on_connection_timeout(node_id);
+		self.encryption_sessions.on_connection_timeout(node_id);
+		self.decryption_sessions.on_connection_timeout(node_id);
+		self.reencryption_sessions.on_connection_timeout(node_id);
+		self.signing_sessions.on_connection_timeout(node_id);
+		self.ecdsa_signing_sessions.on_connection_timeout(node_id);
+		self.share_recovery_sessions.on_connection_timeout(node_id);
+		self.share_refresh_sessions.on_connection_timeout(node_id);
+		self.key_derivation_sessions.on_connection_timeout(node_id);
+		self.key_deletion_sessions.on_connection_timeout(node_id);
+		self.share_move_sessions.on_connection_timeout(node_id);
+		self.server_key_retrieval_sessions.on_connection_timeout(node_id);
+		self.share_bootstrap_sessions.on_connection_timeout(node_id);
+		self.key_export_sessions.on_connection_timeout(node_id);
+	}
+
+	/// Check that requester, which is about to start new session on this node, has not exceeded rate limits.
+	/// Sessions without requester signature (i.e. started by other nodes) are not limited.
+	fn check_rate_limits(&self, key_id: &SessionId, requester_signature: Option<&Signature>) -> Result<Option<Public>, Error> {
+		let requester = match requester_signature {
+			Some(requester_signature) => recover(requester_signature, key_id)?,
+			None => return Ok(None),
+		};
+
+		let active_sessions = self.decryption_sessions.requester_sessions(None)
+			+ self.reencryption_sessions.requester_sessions(None)
+			+ self.signing_sessions.requester_sessions(None)
+			+ self.ecdsa_signing_sessions.requester_sessions(None);
+		let active_requester_sessions = self.decryption_sessions.requester_sessions(Some(&requester))
+			+ self.reencryption_sessions.requester_sessions(Some(&requester))
+			+ self.signing_sessions.requester_sessions(Some(&requester))
+			+ self.ecdsa_signing_sessions.requester_sessions(Some(&requester));
+		self.rate_limiter.start_session(&requester, active_sessions, active_requester_sessions, time::Instant::now())?;
+		Ok(Some(requester))
+	}
+
+	/// Read key share && remove disconnected nodes.
+	fn read_key_share(&self, key_id: &SessionId, cluster: &Arc<ClusterView>) -> Result<DocumentKeyShare, Error> {
+		let mut encrypted_data = self.key_storage.get(key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+
+		// some of nodes, which were encrypting secret may be down
+		// => do not use these in session
+		let disconnected_nodes: BTreeSet<_> = encrypted_data.id_numbers.keys().cloned().collect();
+		for disconnected_node in disconnected_nodes.difference(&cluster.nodes()) {
+			encrypted_data.id_numbers.remove(&disconnected_node);
+		}
+		Ok(encrypted_data)
+	}
+
+	/// Check that given node is allowed to start share administration session.
+	fn check_administration_session_master(&self, master: &NodeId) -> Result<(), Error> {
+		match self.read_only_nodes.contains(master) {
+			true => Err(Error::InvalidNodeForRequest),
+			false => Ok(()),
+		}
+	}
+
+	/// Check if session with given nonce has been recently initialized by given master.
+	pub fn is_recent_initialization(&self, master: &NodeId, nonce: u64) -> bool {
+		self.recent_initializations.lock().contains_key(&(master.clone(), nonce))
+	}
+
+	/// Forget initializations, accepted more than RECENT_INITIALIZATIONS_INTERVAL seconds before given moment.
+	pub fn forget_recent_initializations(&self, now: time::Instant) {
+		let interval = time::Duration::from_secs(RECENT_INITIALIZATIONS_INTERVAL);
+		let mut recent_initializations = self.recent_initializations.lock();
+		let outdated: Vec<_> = recent_initializations.iter()
+			.filter(|&(_, initialization_time)| now - *initialization_time > interval)
+			.map(|(initialization, _)| initialization.clone())
+			.collect();
+		for initialization in outdated {
+			recent_initializations.remove(&initialization);
+		}
+	}
+
+	/// Check or generate new session nonce.
+	fn check_session_nonce(&self, master: &NodeId, nonce: Option<u64>, kind: SessionKind) -> Result<u64, Error> {
+		// if we're master node of the session, then nonce should be generated
+		// if we're slave node of the session, then nonce should be passed from outside
+		debug_assert!((master == &self.self_node_id) == nonce.is_none());
+
+		let is_received_nonce = nonce.is_some();
+		let mut max_nonce = self.max_nonce.write();
+		let nonce = match nonce {
+			Some(nonce) => {
+				let max_nonce = match max_nonce.get(&(master.clone(), kind)).cloned() {
+					Some(max_nonce) => max_nonce,
+					None => self.key_storage.max_session_nonce(master, kind.name())
+						.map_err(|e| Error::KeyStorage(e.into()))?
+						.unwrap_or_default(),
+				};
+				if nonce <= max_nonce {
+					return Err(Error::ReplayProtection);
+				}
+
+				nonce
+			},
+			None => self.session_counter.fetch_add(1, Ordering::Relaxed) as u64 + 1,
+		};
+
+		// remember nonce, so that it is checked/not reused after restart
+		self.key_storage.set_max_session_nonce(master, kind.name(), nonce)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		max_nonce.insert((master.clone(), kind), nonce);
+		if is_received_nonce {
+			self.recent_initializations.lock().insert((master.clone(), nonce), time::Instant::now());
+		}
+		Ok(nonce)
+	}
+}
+
+impl SessionKind {
+	/// All session kinds.
+	pub fn all() -> &'static [SessionKind] {
+		&[SessionKind::Generation, SessionKind::Encryption, SessionKind::Decryption, SessionKind::ReEncryption,
+			SessionKind::Signing, SessionKind::EcdsaSigning, SessionKind::ShareRecovery, SessionKind::ShareRefresh,
+			SessionKind::KeyDerivation, SessionKind::KeyDeletion, SessionKind::ShareMove, SessionKind::ServerKeyRetrieval,
+			SessionKind::ShareBootstrap, SessionKind::KeyExport]
+	}
+
+	/// Session kind name, used as a part of key storage key.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			SessionKind::Generation => "generation",
+			SessionKind::Encryption => "encryption",
+			SessionKind::Decryption => "decryption",
+			SessionKind::ReEncryption => "reencryption",
+			SessionKind::Signing => "signing",
+			SessionKind::EcdsaSigning => "ecdsa_signing",
+			SessionKind::ShareRecovery => "share_recovery",
+			SessionKind::ShareRefresh => "share_refresh",
+			SessionKind::KeyDerivation => "key_derivation",
+			SessionKind::KeyDeletion => "key_deletion",
+			SessionKind::ShareMove => "share_move",
+			SessionKind::ServerKeyRetrieval => "server_key_retrieval",
+			SessionKind::ShareBootstrap => "share_bootstrap",
+			SessionKind::KeyExport => "key_export",
+		}
+	}
+}
+
+impl SessionsRateLimiter {
+	/// Create new limiter.
+	pub fn new(limits: SessionsRateLimits) -> Self {
+		SessionsRateLimiter {
+			limits: limits,
+			recent_sessions: Mutex::new(VecDeque::new()),
+		}
+	}
+
+	/// Check limits && remember that requester has started new session at given moment.
+	pub fn start_session(&self, requester: &Public, active_sessions: usize, active_requester_sessions: usize, now: time::Instant) -> Result<(), Error> {
+		let exceeds = |limit: Option<usize>, value: usize| limit.map(|limit| value >= limit).unwrap_or(false);
+		if exceeds(self.limits.concurrent_sessions, active_sessions)
+			|| exceeds(self.limits.requester_concurrent_sessions, active_requester_sessions) {
+			return Err(Error::RateLimited);
+		}
+
+		if self.limits.sessions_per_minute.is_none() && self.limits.requester_sessions_per_minute.is_none() {
+			return Ok(());
+		}
+
+		let mut recent_sessions = self.recent_sessions.lock();
+		let interval = time::Duration::from_secs(RATE_LIMIT_INTERVAL);
+		while recent_sessions.front().map(|&(started, _)| now - started >= interval).unwrap_or(false) {
+			recent_sessions.pop_front();
+		}
+
+		let recent_requester_sessions = recent_sessions.iter().filter(|&&(_, ref r)| r == requester).count();
+		if exceeds(self.limits.sessions_per_minute, recent_sessions.len())
+			|| exceeds(self.limits.requester_sessions_per_minute, recent_requester_sessions) {
+			return Err(Error::RateLimited);
+		}
+
+		recent_sessions.push_back((now, requester.clone()));
+		Ok(())
+	}
+}
+
+impl Default for SessionTimeouts {
+	fn default() -> Self {
+		SessionTimeouts::new(None, None)
+	}
+}
+
+impl SessionTimeouts {
+	/// Create timeouts from configured values (in seconds). Default values are used for None.
+	pub fn new(idle_timeout: Option<u64>, total_timeout: Option<u64>) -> Self {
+		SessionTimeouts {
+			idle_timeout: time::Duration::from_secs(idle_timeout.unwrap_or(SESSION_TIMEOUT_INTERVAL)),
+			total_timeout: time::Duration::from_secs(total_timeout.unwrap_or(SESSION_TOTAL_TIMEOUT_INTERVAL)),
+		}
+	}
+
+	/// Check if session with given creation && last message times is stalled at given moment.
+	pub fn is_stalled(&self, now: time::Instant, creation_time: time::Instant, last_message_time: time::Instant) -> bool {
+		now - last_message_time > self.idle_timeout || now - creation_time > self.total_timeout
+	}
+}
+
+impl<K, V, M> ClusterSessionsContainer<K, V, M> where K: Clone + Ord + JournalSessionId, V: ClusterSession {
+	pub fn new() -> Self {
+		ClusterSessionsContainer::with_timeouts(SessionTimeouts::default())
+	}
+
+	pub fn with_timeouts(timeouts: SessionTimeouts) -> Self {
+		ClusterSessionsContainer {
+			sessions: RwLock::new(BTreeMap::new()),
+			timeouts: timeouts,
+			metrics: None,
+			journal: None,
+		}
+	}
+
+	/// Report removed sessions to given metrics.
+	pub fn with_metrics(mut self, kind: &'static str, metrics: Arc<ClusterMetrics>) -> Self {
+		self.metrics = Some((kind, metrics));
+		self
+	}
+
+	/// Record created && removed sessions in given journal (if journal is enabled).
+	pub fn with_journal(mut self, kind: &'static str, journal: Option<Arc<SessionJournal>>) -> Self {
+		self.journal = journal.map(|journal| (kind, journal));
+		self
+	}
+
+	pub fn get(&self, session_id: &K, update_last_message_time: bool) -> Option<Arc<V>> {
+		if !update_last_message_time {
+			return self.sessions.read().get(session_id).map(|s| s.session.clone());
+		}
+
+		self.sessions.write().get_mut(session_id).map(|s| {
+			s.last_message_time = time::Instant::now();
+			s.session.clone()
+		})
+	}
+
+	pub fn insert<F: FnOnce() -> Result<V, Error>>(&self, master: NodeId, session_id: K, cluster: Arc<Cluster>, session: F) -> Result<Arc<V>, Error> {
+		self.insert_with_requester(master, session_id, None, cluster, session)
+	}
+
+	pub fn insert_with_requester<F: FnOnce() -> Result<V, Error>>(&self, master: NodeId, session_id: K, requester: Option<Public>, cluster: Arc<Cluster>, session: F) -> Result<Arc<V>, Error> {
+		let mut sessions = self.sessions.write();
+		if sessions.contains_key(&session_id) {
+			return Err(Error::DuplicateSessionId);
+		}
+
+		let session = Arc::new(session()?);
+		if let Some((kind, ref journal)) = self.journal {
+			journal.on_session_started(session_id.journal_session_id(), kind, &master);
+		}
+
+		let now = time::Instant::now();
+		let queued_session = QueuedSession {
+			master: master,
+			requester: requester,
+			cluster_view: cluster,
+			creation_time: now,
+			last_message_time: now,
+			session: session.clone(),
+			queue: VecDeque::new(),
+		};
+		sessions.insert(session_id, queued_session);
+		Ok(session)
+	}
+
+	/// Number of active sessions, started by given requester (or by any requester if None).
+	pub fn requester_sessions(&self, requester: Option<&Public>) -> usize {
+		self.sessions.read().values()
+			.filter(|s| match (s.requester.as_ref(), requester) {
+				(Some(_), None) => true,
+				(Some(session_requester), Some(requester)) => session_requester == requester,
+				(None, _) => false,
+			})
+			.count()
+	}
+
+	pub fn remove(&self, session_id: &K) {
+		let session = self.sessions.write().remove(session_id);
+		if let Some(session) = session {
+			let outcome = if session.session.is_finished() && !session.session.is_failed() { SessionOutcome::Completed } else { SessionOutcome::Failed };
+			self.on_session_removed(session_id, &session, outcome);
+		}
+	}
+
+	pub fn cancel(&self, session_id: &K) -> Result<(), Error> {
+		let session = self.sessions.write().remove(session_id).ok_or(Error::InvalidSessionId)?;
+		session.session.cancel();
+		self.on_session_removed(session_id, &session, SessionOutcome::Cancelled);
+		Ok(())
+	}
+
+	/// Account message of given size (in bytes), received by given session. Session is cancelled if it has exceeded traffic limit.
+	pub fn on_message_received(&self, session_id: &K, size: usize) -> Result<(), Error> {
+		let result = match self.sessions.read().get(session_id) {
+			Some(session) => session.cluster_view.on_message_received(size),
+			None => return Ok(()),
+		};
+
+		if result.is_err() {
+			// session could have been removed concurrently => cancellation error is ignored
+			let _ = self.cancel(session_id);
+		}
+		result
+	}
+
+	/// Add number of active sessions && queued messages to the gauges.
+	pub fn fill_gauges(&self, kind: &'static str, gauges: &mut ClusterGauges) {
+		let sessions = self.sessions.read();
+		gauges.active_sessions.insert(kind, sessions.len());
+		gauges.queued_messages.insert(kind, sessions.values().map(|s| s.queue.len()).sum());
+	}
+
+	pub fn enqueue_message(&self, session_id: &K, sender: NodeId, message: M, is_queued_message: bool) -> Result<(), Error> {
+		let mut sessions = self.sessions.write();
+		let session = match sessions.get_mut(session_id) {
+			Some(session) => session,
+			None => return Ok(()),
+		};
+
+		// message, which has been dequeued, is returned to the queue regardless of its size
+		if is_queued_message {
+			session.queue.push_front((sender, message));
+		} else if session.queue.len() >= MAX_SESSION_QUEUE_SIZE {
+			return Err(Error::QueueOverflow);
+		} else {
+			session.queue.push_back((sender, message));
+		}
+		Ok(())
+	}
+
+	pub fn dequeue_message(&self, session_id: &K) -> Option<(NodeId, M)> {
+		self.sessions.write().get_mut(session_id)
+			.and_then(|session| session.queue.pop_front())
 	}
 
 	pub fn stop_stalled_sessions(&self) {
+		self.stop_stalled_sessions_at(time::Instant::now())
+	}
+
+	pub fn stop_stalled_sessions_at(&self, now: time::Instant) {
 		let mut sessions = self.sessions.write();
 		for sid in sessions.keys().cloned().collect::<Vec<_>>() {
 			let remove_session = {
 				let session = sessions.get(&sid).expect("enumerating only existing sessions; qed");
-				if time::Instant::now() - session.last_message_time > time::Duration::from_secs(SESSION_TIMEOUT_INTERVAL) {
+				if self.timeouts.is_stalled(now, session.creation_time, session.last_message_time) {
 					session.session.on_session_timeout();
 					session.session.is_finished()
 				} else {
@@ -417,11 +1599,19 @@ impl<K, V, M> ClusterSessionsContainer<K, V, M> where K: Clone + Ord, V: Cluster
 			};
 
 			if remove_session {
-				sessions.remove(&sid);
+				let session = sessions.remove(&sid).expect("enumerating only existing sessions; qed");
+				self.on_session_removed(&sid, &session, SessionOutcome::TimedOut);
 			}
 		}
 	}
 
+	pub fn suspend_timeouts(&self, duration: time::Duration) {
+		for session in self.sessions.write().values_mut() {
+			session.creation_time += duration;
+			session.last_message_time += duration;
+		}
+	}
+
 	pub fn on_connection_timeout(&self, node_id: &NodeId) {
 		let mut sessions = self.sessions.write();
 		for sid in sessions.keys().cloned().collect::<Vec<_>>() {
@@ -431,7 +1621,22 @@ impl<K, V, M> ClusterSessionsContainer<K, V, M> where K: Clone + Ord, V: Cluster
 				session.session.is_finished()
 			};
 			if remove_session {
-				sessions.remove(&sid);
+				let session = sessions.remove(&sid).expect("enumerating only existing sessions; qed");
+				self.on_session_removed(&sid, &session, SessionOutcome::Failed);
+			}
+		}
+	}
+
+	fn on_session_removed(&self, session_id: &K, session: &QueuedSession<V, M>, outcome: SessionOutcome) {
+		if let Some((kind, ref journal)) = self.journal {
+			journal.on_session_removed(session_id.journal_session_id(), kind, outcome);
+		}
+		if let Some((kind, ref metrics)) = self.metrics {
+			metrics.on_session_removed(kind, outcome, time::Instant::now() - session.creation_time);
+			let (sent_bytes, received_bytes) = session.cluster_view.traffic();
+			metrics.on_session_traffic(kind, sent_bytes, received_bytes);
+			if outcome == SessionOutcome::Completed {
+				metrics.on_session_completed(&session.cluster_view.nodes(), unix_timestamp());
 			}
 		}
 	}
@@ -521,6 +1726,30 @@ impl Drop for DecryptionSessionWrapper {
 	}
 }
 
+impl ReEncryptionSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: DecryptionSessionId, session: Arc<ReEncryptionSession>) -> Arc<Self> {
+		Arc::new(ReEncryptionSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl ReEncryptionSession for ReEncryptionSessionWrapper {
+	fn wait(&self) -> Result<ReEncryptedDocumentKey, Error> {
+		self.session.wait()
+	}
+}
+
+impl Drop for ReEncryptionSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().reencryption_sessions.remove(&self.session_id);
+		}
+	}
+}
+
 impl SigningSessionWrapper {
 	pub fn new(cluster: Weak<ClusterData>, session_id: SigningSessionId, session: Arc<SigningSession>) -> Arc<Self> {
 		Arc::new(SigningSessionWrapper {
@@ -544,3 +1773,334 @@ impl Drop for SigningSessionWrapper {
 		}
 	}
 }
+
+impl EcdsaSigningSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SigningSessionId, session: Arc<EcdsaSigningSession>) -> Arc<Self> {
+		Arc::new(EcdsaSigningSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl EcdsaSigningSession for EcdsaSigningSessionWrapper {
+	fn wait(&self) -> Result<Signature, Error> {
+		self.session.wait()
+	}
+}
+
+impl Drop for EcdsaSigningSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().ecdsa_signing_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl ShareRecoverySessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<ShareRecoverySession>) -> Arc<Self> {
+		Arc::new(ShareRecoverySessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl ShareRecoverySession for ShareRecoverySessionWrapper {
+	fn state(&self) -> ShareRecoverySessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for ShareRecoverySessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().share_recovery_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl ShareRefreshSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<ShareRefreshSession>) -> Arc<Self> {
+		Arc::new(ShareRefreshSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl ShareRefreshSession for ShareRefreshSessionWrapper {
+	fn state(&self) -> ShareRefreshSessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for ShareRefreshSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().share_refresh_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl KeyDerivationSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<KeyDerivationSession>) -> Arc<Self> {
+		Arc::new(KeyDerivationSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl KeyDerivationSession for KeyDerivationSessionWrapper {
+	fn state(&self) -> KeyDerivationSessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<Public, Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for KeyDerivationSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().key_derivation_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl KeyDeletionSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<KeyDeletionSession>) -> Arc<Self> {
+		Arc::new(KeyDeletionSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl KeyDeletionSession for KeyDeletionSessionWrapper {
+	fn state(&self) -> KeyDeletionSessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<BTreeSet<NodeId>, Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for KeyDeletionSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().key_deletion_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl ShareMoveSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<ShareMoveSession>) -> Arc<Self> {
+		Arc::new(ShareMoveSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl ShareMoveSession for ShareMoveSessionWrapper {
+	fn state(&self) -> ShareMoveSessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for ShareMoveSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().share_move_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl ServerKeyRetrievalSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<ServerKeyRetrievalSession>) -> Arc<Self> {
+		Arc::new(ServerKeyRetrievalSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl ServerKeyRetrievalSession for ServerKeyRetrievalSessionWrapper {
+	fn state(&self) -> ServerKeyRetrievalSessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<AttestedServerKeyPublic, Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for ServerKeyRetrievalSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().server_key_retrieval_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl ShareBootstrapSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<ShareBootstrapSession>) -> Arc<Self> {
+		Arc::new(ShareBootstrapSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl ShareBootstrapSession for ShareBootstrapSessionWrapper {
+	fn state(&self) -> ShareBootstrapSessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<BTreeSet<SessionId>, Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for ShareBootstrapSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().share_bootstrap_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+impl KeyExportSessionWrapper {
+	pub fn new(cluster: Weak<ClusterData>, session_id: SessionId, session: Arc<KeyExportSession>) -> Arc<Self> {
+		Arc::new(KeyExportSessionWrapper {
+			session: session,
+			session_id: session_id,
+			cluster: cluster,
+		})
+	}
+}
+
+impl KeyExportSession for KeyExportSessionWrapper {
+	fn state(&self) -> KeyExportSessionState {
+		self.session.state()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<Vec<ExportedKeyShare>, Error> {
+		self.session.wait(timeout)
+	}
+}
+
+impl Drop for KeyExportSessionWrapper {
+	fn drop(&mut self) {
+		if let Some(cluster) = self.cluster.upgrade() {
+			cluster.sessions().key_export_sessions.remove(&self.session_id);
+		}
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use std::time;
+	use parking_lot::Mutex;
+	use tokio_core::reactor::Core;
+	use ethkey::{Random, Generator};
+	use key_server_cluster::{Error, SessionId, SessionsRateLimits};
+	use key_server_cluster::cluster::tests::make_clusters;
+	use super::{ClusterSessions, SessionEvent, SessionEventListener, SessionsRateLimiter, SessionTimeouts};
+
+	#[derive(Default)]
+	pub struct DummySessionEventListener {
+		events: Mutex<Vec<SessionEvent>>,
+	}
+
+	impl DummySessionEventListener {
+		pub fn events(&self) -> Vec<SessionEvent> {
+			self.events.lock().clone()
+		}
+	}
+
+	impl SessionEventListener for DummySessionEventListener {
+		fn on_session_event(&self, _session_id: &SessionId, event: SessionEvent) {
+			self.events.lock().push(event);
+		}
+	}
+
+	#[test]
+	fn rate_limiter_limits_concurrent_sessions() {
+		let requester = Random.generate().unwrap().public().clone();
+		let limiter = SessionsRateLimiter::new(SessionsRateLimits {
+			requester_concurrent_sessions: Some(2),
+			concurrent_sessions: Some(5),
+			..Default::default()
+		});
+
+		let now = time::Instant::now();
+		assert_eq!(limiter.start_session(&requester, 1, 1, now), Ok(()));
+		assert_eq!(limiter.start_session(&requester, 2, 2, now), Err(Error::RateLimited));
+		assert_eq!(limiter.start_session(&requester, 5, 0, now), Err(Error::RateLimited));
+	}
+
+	#[test]
+	fn rate_limiter_limits_sessions_per_minute() {
+		let requester1 = Random.generate().unwrap().public().clone();
+		let requester2 = Random.generate().unwrap().public().clone();
+		let limiter = SessionsRateLimiter::new(SessionsRateLimits {
+			requester_sessions_per_minute: Some(2),
+			sessions_per_minute: Some(3),
+			..Default::default()
+		});
+
+		let now = time::Instant::now();
+		assert_eq!(limiter.start_session(&requester1, 0, 0, now), Ok(()));
+		assert_eq!(limiter.start_session(&requester1, 0, 0, now), Ok(()));
+		assert_eq!(limiter.start_session(&requester1, 0, 0, now), Err(Error::RateLimited));
+		assert_eq!(limiter.start_session(&requester2, 0, 0, now), Ok(()));
+		assert_eq!(limiter.start_session(&requester2, 0, 0, now), Err(Error::RateLimited));
+
+		// sessions, started more than a minute ago, are not counted
+		let later = now + time::Duration::from_secs(61);
+		assert_eq!(limiter.start_session(&requester1, 0, 0, later), Ok(()));
+		assert_eq!(limiter.start_session(&requester2, 0, 0, later), Ok(()));
+	}
+
+	#[test]
+	fn session_timeouts_are_configured_per_session_kind() {
+		let core = Core::new().unwrap();
+		let clusters = make_clusters(&core, 6030, 1);
+		let mut config = clusters[0].config().clone();
+		config.timeouts.decryption_idle_timeout = Some(5);
+		config.timeouts.total_timeout = Some(50);
+
+		let sessions = ClusterSessions::new(&config);
+		assert_eq!(sessions.decryption_sessions.timeouts, SessionTimeouts {
+			idle_timeout: time::Duration::from_secs(5),
+			total_timeout: time::Duration::from_secs(50),
+		});
+		assert_eq!(sessions.signing_sessions.timeouts, SessionTimeouts::new(None, Some(50)));
+		assert_eq!(SessionTimeouts::new(None, None), SessionTimeouts::default());
+	}
+}
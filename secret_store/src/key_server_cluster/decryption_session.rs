@@ -18,9 +18,10 @@ use std::cmp::{Ord, PartialOrd, Ordering};
 use std::sync::Arc;
 use parking_lot::{Mutex, Condvar};
 use ethkey::{Secret, Signature};
-use key_server_cluster::{Error, AclStorage, DocumentKeyShare, NodeId, SessionId, EncryptedDocumentKeyShadow, SessionMeta};
+use bigint::hash::H256;
+use key_server_cluster::{Error, AclStorage, KeyStorage, DocumentKeyShare, NodeId, SessionId, EncryptedDocumentKeyShadow, SessionMeta};
 use key_server_cluster::cluster::Cluster;
-use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::cluster_sessions::{ClusterSession, SessionEvent, SessionEventListener, SessionEventListeners};
 use key_server_cluster::message::{Message, DecryptionMessage, DecryptionConsensusMessage, RequestPartialDecryption,
 	PartialDecryption, DecryptionSessionError, DecryptionSessionCompleted, ConsensusMessage, InitializeConsensusSession,
 	ConfirmConsensusInitialization};
@@ -40,6 +41,7 @@ pub trait Session: Send + Sync + 'static {
 /// Brief overview:
 /// 1) initialization: master node (which has received request for decrypting the secret) requests all other nodes to decrypt the secret
 /// 2) ACL check: all nodes which have received the request are querying ACL-contract to check if requestor has access to the document
+/// 2.1) version negotiation: nodes, which do not have the latest key version of the master node, are treated as rejecting consensus
 /// 3) partial decryption: every node which has succussfully checked access for the requestor do a partial decryption
 /// 4) decryption: master node receives all partial decryptions of the secret and restores the secret
 pub struct SessionImpl {
@@ -57,12 +59,16 @@ struct SessionCore {
 	pub access_key: Secret,
 	/// Key share.
 	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
 	/// Cluster which allows this node to send messages to other nodes in the cluster.
 	pub cluster: Arc<Cluster>,
 	/// Session-level nonce.
 	pub nonce: u64,
 	/// SessionImpl completion condvar.
 	pub completed: Condvar,
+	/// Session events listeners.
+	pub listeners: SessionEventListeners,
 }
 
 /// Decryption consensus session type.
@@ -74,6 +80,8 @@ struct SessionData {
 	pub consensus_session: DecryptionConsensusSession,
 	/// Is shadow decryption requested?
 	pub is_shadow_decryption: Option<bool>,
+	/// Key version, negotiated by master node.
+	pub key_version: Option<H256>,
 	/// Decryption result.
 	pub result: Option<Result<EncryptedDocumentKeyShadow, Error>>,
 }
@@ -95,6 +103,8 @@ pub struct SessionParams {
 	pub access_key: Secret,
 	/// Key share.
 	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
 	/// ACL storage.
 	pub acl_storage: Arc<AclStorage>,
 	/// Cluster.
@@ -111,6 +121,8 @@ struct DecryptionConsensusTransport {
 	access_key: Secret,
 	/// Session-level nonce.
 	nonce: u64,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
 	/// Cluster.
 	cluster: Arc<Cluster>,
 }
@@ -123,6 +135,8 @@ struct DecryptionJobTransport {
 	access_key: Secret,
 	/// Session-level nonce.
 	nonce: u64,
+	/// Key version, used for decryption.
+	key_version: H256,
 	/// Cluster.
 	cluster: Arc<Cluster>,
 }
@@ -149,6 +163,7 @@ impl SessionImpl {
 			id: params.meta.id.clone(),
 			access_key: params.access_key.clone(),
 			nonce: params.nonce,
+			key_storage: params.key_storage.clone(),
 			cluster: params.cluster.clone(),
 		};
 
@@ -157,9 +172,11 @@ impl SessionImpl {
 				meta: params.meta.clone(),
 				access_key: params.access_key,
 				key_share: params.key_share,
+				key_storage: params.key_storage,
 				cluster: params.cluster,
 				nonce: params.nonce,
 				completed: Condvar::new(),
+				listeners: SessionEventListeners::default(),
 			},
 			data: Mutex::new(SessionData {
 				consensus_session: match requester_signature {
@@ -175,6 +192,7 @@ impl SessionImpl {
 					})?,
 				},
 				is_shadow_decryption: None,
+				key_version: None,
 				result: None,
 			}),
 		})
@@ -204,17 +222,27 @@ impl SessionImpl {
 		self.data.lock().result.clone()
 	}
 
+	/// Register session events listener.
+	pub fn add_listener(&self, listener: Arc<SessionEventListener>) {
+		self.core.listeners.add(listener);
+	}
+
 	/// Initialize decryption session on master node.
 	pub fn initialize(&self, is_shadow_decryption: bool) -> Result<(), Error> {
 		let mut data = self.data.lock();
+		let key_version = self.core.latest_key_version()?;
 		data.is_shadow_decryption = Some(is_shadow_decryption);
+		data.key_version = Some(key_version.clone());
 		data.consensus_session.initialize(self.core.key_share.id_numbers.keys().cloned().collect())?;
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Initialized);
 
 		if data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished {
-			self.core.disseminate_jobs(&mut data.consensus_session, is_shadow_decryption)?;
+			self.core.listeners.notify(&self.core.meta.id, SessionEvent::Confirmed);
+			self.core.disseminate_jobs(&mut data.consensus_session, is_shadow_decryption, key_version)?;
 
 			debug_assert!(data.consensus_session.state() == ConsensusSessionState::Finished);
 			data.result = Some(Ok(data.consensus_session.result()?));
+			self.core.listeners.notify(&self.core.meta.id, SessionEvent::Finished);
 			self.core.completed.notify_all();
 		}
 
@@ -247,17 +275,27 @@ impl SessionImpl {
 		debug_assert!(self.core.access_key == *message.sub_session);
 
 		let mut data = self.data.lock();
+		let is_waiting_for_initialization = data.consensus_session.state() == ConsensusSessionState::WaitingForInitialization;
 		let is_establishing_consensus = data.consensus_session.state() == ConsensusSessionState::EstablishingConsensus;
-		data.consensus_session.on_consensus_message(&sender, &message.message)?;
+		let consensus_message = self.core.filter_consensus_message(data.key_version.as_ref(), &message.message)?;
+		data.consensus_session.on_consensus_message(&sender, &consensus_message)?;
+
+		if is_waiting_for_initialization && data.consensus_session.state() != ConsensusSessionState::WaitingForInitialization {
+			self.core.listeners.notify(&self.core.meta.id, SessionEvent::Initialized);
+		}
 
 		let is_consensus_established = data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished;
 		if self.core.meta.self_node_id != self.core.meta.master_node_id || !is_establishing_consensus || !is_consensus_established {
 			return Ok(());
 		}
 
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Confirmed);
+
 		let is_shadow_decryption = data.is_shadow_decryption
 			.expect("we are on master node; on master node is_shadow_decryption is filled in initialize(); on_consensus_message follows initialize (state check in consensus_session); qed");
-		self.core.disseminate_jobs(&mut data.consensus_session, is_shadow_decryption)
+		let key_version = data.key_version.clone()
+			.expect("we are on master node; on master node key_version is filled in initialize(); on_consensus_message follows initialize (state check in consensus_session); qed");
+		self.core.disseminate_jobs(&mut data.consensus_session, is_shadow_decryption, key_version)
 	}
 
 	/// When partial decryption is requested.
@@ -266,10 +304,17 @@ impl SessionImpl {
 		debug_assert!(self.core.access_key == *message.sub_session);
 		debug_assert!(sender != &self.core.meta.self_node_id);
 
+		if sender != &self.core.meta.master_node_id {
+			return Err(Error::InvalidMessage);
+		}
+
 		let mut data = self.data.lock();
 		let requester = data.consensus_session.requester()?.clone();
-		let decryption_job = DecryptionJob::new_on_slave(self.core.meta.self_node_id.clone(), self.core.access_key.clone(), requester, self.core.key_share.clone())?;
-		let decryption_transport = self.core.decryption_transport();
+		let key_version: H256 = message.key_version.clone().into();
+		let key_share = self.core.key_storage.get_version(&self.core.meta.id, &key_version)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		let decryption_job = DecryptionJob::new_on_slave(self.core.meta.self_node_id.clone(), self.core.access_key.clone(), requester, key_share)?;
+		let decryption_transport = self.core.decryption_transport(key_version);
 
 		data.consensus_session.on_job_request(&sender, PartialDecryptionRequest {
 			id: message.request_id.clone().into(),
@@ -289,6 +334,7 @@ impl SessionImpl {
 			request_id: message.request_id.clone().into(),
 			shadow_point: message.shadow_point.clone().into(),
 			decrypt_shadow: message.decrypt_shadow.clone(),
+			decrypt_proof: message.decrypt_proof.clone(),
 		})?;
 
 		if data.consensus_session.state() != ConsensusSessionState::Finished {
@@ -305,6 +351,7 @@ impl SessionImpl {
 		}
 
 		data.result = Some(Ok(data.consensus_session.result()?));
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Finished);
 		self.core.completed.notify_all();
 
 		Ok(())
@@ -316,16 +363,19 @@ impl SessionImpl {
 		debug_assert!(self.core.access_key == *message.sub_session);
 		debug_assert!(sender != &self.core.meta.self_node_id);
 
-		self.data.lock().consensus_session.on_session_completed(sender)
+		self.data.lock().consensus_session.on_session_completed(sender)?;
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Finished);
+
+		Ok(())
 	}
 
 	/// When error has occured on another node.
 	pub fn on_session_error(&self, sender: &NodeId, message: &DecryptionSessionError) -> Result<(), Error> {
-		self.process_node_error(Some(&sender), &message.error)
+		self.process_node_error(Some(&sender), &Error::remote(message.code, message.error.clone()))
 	}
 
 	/// Process error from the other node.
-	fn process_node_error(&self, node: Option<&NodeId>, error: &String) -> Result<(), Error> {
+	fn process_node_error(&self, node: Option<&NodeId>, error: &Error) -> Result<(), Error> {
 		let mut data = self.data.lock();
 		match {
 			match node {
@@ -336,13 +386,15 @@ impl SessionImpl {
 			Ok(false) => Ok(()),
 			Ok(true) => {
 				let is_shadow_decryption = data.is_shadow_decryption.expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when is_shadow_decryption.is_some(); qed");
-				let disseminate_result = self.core.disseminate_jobs(&mut data.consensus_session, is_shadow_decryption);
+				let key_version = data.key_version.clone().expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when key_version.is_some(); qed");
+				let disseminate_result = self.core.disseminate_jobs(&mut data.consensus_session, is_shadow_decryption, key_version);
 				match disseminate_result {
 					Ok(()) => Ok(()),
 					Err(err) => {
 						warn!("{}: decryption session failed with error: {:?} from {:?}", &self.core.meta.self_node_id, error, node);
 
 						data.result = Some(Err(err.clone()));
+						self.core.listeners.notify(&self.core.meta.id, SessionEvent::Failed(err.clone()));
 						self.core.completed.notify_all();
 						Err(err)
 					}
@@ -352,6 +404,7 @@ impl SessionImpl {
 				warn!("{}: decryption session failed with error: {:?} from {:?}", &self.core.meta.self_node_id, error, node);
 
 				data.result = Some(Err(err.clone()));
+				self.core.listeners.notify(&self.core.meta.id, SessionEvent::Failed(err.clone()));
 				self.core.completed.notify_all();
 				Err(err)
 			},
@@ -366,14 +419,48 @@ impl ClusterSession for SessionImpl {
 			|| data.consensus_session.state() == ConsensusSessionState::Finished
 	}
 
+	fn is_failed(&self) -> bool {
+		self.data.lock().consensus_session.state() == ConsensusSessionState::Failed
+	}
+
 	fn on_node_timeout(&self, node: &NodeId) {
 		// ignore error, only state matters
-		let _ = self.process_node_error(Some(node), &Error::NodeDisconnected.into());
+		let _ = self.process_node_error(Some(node), &Error::NodeDisconnected);
 	}
 
 	fn on_session_timeout(&self) {
 		// ignore error, only state matters
-		let _ = self.process_node_error(None, &Error::NodeDisconnected.into());
+		let _ = self.process_node_error(None, &Error::NodeDisconnected);
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		let state = data.consensus_session.state();
+		if state == ConsensusSessionState::Failed || state == ConsensusSessionState::Finished {
+			return;
+		}
+
+		warn!("{}: decryption session has been cancelled", &self.core.meta.self_node_id);
+
+		// key storage is never modified by decryption session => nothing to restore
+		// do not bother processing send error, as we already processing error
+		let error = Message::Decryption(DecryptionMessage::DecryptionSessionError(DecryptionSessionError {
+			session: self.core.meta.id.clone().into(),
+			sub_session: self.core.access_key.clone().into(),
+			session_nonce: self.core.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.core.meta.self_node_id == self.core.meta.master_node_id {
+			self.core.cluster.broadcast(error)
+		} else {
+			self.core.cluster.send(&self.core.meta.master_node_id, error)
+		};
+
+		data.consensus_session.on_session_cancelled();
+		data.result = Some(Err(Error::SessionCancelled));
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Failed(Error::SessionCancelled));
+		self.core.completed.notify_all();
 	}
 }
 
@@ -391,19 +478,43 @@ impl Session for SessionImpl {
 }
 
 impl SessionCore {
-	pub fn decryption_transport(&self) -> DecryptionJobTransport {
+	pub fn decryption_transport(&self, key_version: H256) -> DecryptionJobTransport {
 		DecryptionJobTransport {
 			id: self.meta.id.clone(),
 			access_key: self.access_key.clone(),
 			nonce: self.nonce,
+			key_version: key_version,
 			cluster: self.cluster.clone()
 		}
 	}
 
-	pub fn disseminate_jobs(&self, consensus_session: &mut DecryptionConsensusSession, is_shadow_decryption: bool) -> Result<(), Error> {
+	pub fn latest_key_version(&self) -> Result<H256, Error> {
+		self.key_storage.versions(&self.meta.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?
+			.into_iter()
+			.nth(0)
+			.ok_or(Error::KeyStorage("key share has no versions".into()))
+	}
+
+	pub fn filter_consensus_message(&self, key_version: Option<&H256>, message: &ConsensusMessage) -> Result<ConsensusMessage, Error> {
+		match *message {
+			// on master node: nodes, which do not have the negotiated key version, are treated as rejecting consensus
+			ConsensusMessage::ConfirmConsensusInitialization(ref message) if self.meta.self_node_id == self.meta.master_node_id => {
+				let key_version = key_version.ok_or(Error::InvalidStateForRequest)?;
+				Ok(ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+					is_confirmed: message.is_confirmed && message.key_versions.iter().any(|v| &**v == key_version),
+					key_versions: message.key_versions.clone(),
+				}))
+			},
+			_ => Ok(message.clone()),
+		}
+	}
+
+	pub fn disseminate_jobs(&self, consensus_session: &mut DecryptionConsensusSession, is_shadow_decryption: bool, key_version: H256) -> Result<(), Error> {
 		let requester = consensus_session.requester()?.clone();
 		let decryption_job = DecryptionJob::new_on_master(self.meta.self_node_id.clone(), self.access_key.clone(), requester, self.key_share.clone(), is_shadow_decryption)?;
-		consensus_session.disseminate_jobs(decryption_job, self.decryption_transport())
+		consensus_session.set_nodes_preference(self.cluster.order_by_preference(self.key_share.id_numbers.keys().cloned().collect()));
+		consensus_session.disseminate_jobs(decryption_job, self.decryption_transport(key_version))
 	}
 }
 
@@ -423,12 +534,15 @@ impl JobTransport for DecryptionConsensusTransport {
 	}
 
 	fn send_partial_response(&self, node: &NodeId, response: bool) -> Result<(), Error> {
+		let key_versions = self.key_storage.versions(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
 		self.cluster.send(node, Message::Decryption(DecryptionMessage::DecryptionConsensusMessage(DecryptionConsensusMessage {
 			session: self.id.clone().into(),
 			sub_session: self.access_key.clone().into(),
 			session_nonce: self.nonce,
 			message: ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 				is_confirmed: response,
+				key_versions: key_versions.into_iter().map(Into::into).collect(),
 			})
 		})))
 	}
@@ -446,6 +560,7 @@ impl JobTransport for DecryptionJobTransport {
 			request_id: request.id.into(),
 			is_shadow_decryption: request.is_shadow_decryption,
 			nodes: request.other_nodes_ids.into_iter().map(Into::into).collect(),
+			key_version: self.key_version.clone().into(),
 		})))
 	}
 
@@ -457,6 +572,7 @@ impl JobTransport for DecryptionJobTransport {
 			request_id: response.request_id.into(),
 			shadow_point: response.shadow_point.into(),
 			decrypt_shadow: response.decrypt_shadow,
+			decrypt_proof: response.decrypt_proof,
 		})))
 	}
 }
@@ -492,9 +608,10 @@ mod tests {
 	use std::collections::BTreeMap;
 	use acl_storage::DummyAclStorage;
 	use ethkey::{self, KeyPair, Random, Generator, Public, Secret};
-	use key_server_cluster::{NodeId, DocumentKeyShare, SessionId, Error, EncryptedDocumentKeyShadow, SessionMeta};
+	use key_server_cluster::{NodeId, DocumentKeyShare, SessionId, Error, EncryptedDocumentKeyShadow, SessionMeta, KeyStorage, DummyKeyStorage};
 	use key_server_cluster::cluster::tests::DummyCluster;
-	use key_server_cluster::cluster_sessions::ClusterSession;
+	use key_server_cluster::cluster_sessions::{ClusterSession, SessionEvent};
+	use key_server_cluster::cluster_sessions::tests::DummySessionEventListener;
 	use key_server_cluster::decryption_session::{SessionImpl, SessionParams};
 	use key_server_cluster::message::{self, Message, DecryptionMessage};
 	use key_server_cluster::math;
@@ -534,6 +651,13 @@ mod tests {
 			secret_share: secret_shares[i].clone(),
 			common_point: Some(common_point.clone()),
 			encrypted_point: Some(encrypted_point.clone()),
+			refresh_id: None,
+			metadata: Default::default(),
+		}).collect();
+		let key_storages: Vec<_> = (0..5).map(|i| {
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			key_storage.insert(session_id.clone(), encrypted_datas[i].clone()).unwrap();
+			key_storage
 		}).collect();
 		let acl_storages: Vec<_> = (0..5).map(|_| Arc::new(DummyAclStorage::default())).collect();
 		let clusters: Vec<_> = (0..5).map(|i| {
@@ -554,6 +678,7 @@ mod tests {
 			},
 			access_key: access_key.clone(),
 			key_share: encrypted_datas[i].clone(),
+			key_storage: key_storages[i].clone(),
 			acl_storage: acl_storages[i].clone(),
 			cluster: clusters[i].clone(),
 			nonce: 0,
@@ -602,7 +727,10 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Some(Random.generate().unwrap().public().clone()),
 				encrypted_point: Some(Random.generate().unwrap().public().clone()),
+				refresh_id: None,
+				metadata: Default::default(),
 			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
 			nonce: 0,
@@ -633,7 +761,10 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Some(Random.generate().unwrap().public().clone()),
 				encrypted_point: Some(Random.generate().unwrap().public().clone()),
+				refresh_id: None,
+				metadata: Default::default(),
 			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
 			nonce: 0,
@@ -664,7 +795,10 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Some(Random.generate().unwrap().public().clone()),
 				encrypted_point: Some(Random.generate().unwrap().public().clone()),
+				refresh_id: None,
+				metadata: Default::default(),
 			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
 			nonce: 0,
@@ -713,6 +847,7 @@ mod tests {
 			request_id: Random.generate().unwrap().secret().clone().into(),
 			is_shadow_decryption: false,
 			nodes: sessions.iter().map(|s| s.node().clone().into()).take(4).collect(),
+			key_version: sessions[1].core.key_share.version().into(),
 		}).unwrap_err(), Error::InvalidMessage);
 	}
 
@@ -734,6 +869,7 @@ mod tests {
 			request_id: Random.generate().unwrap().secret().clone().into(),
 			is_shadow_decryption: false,
 			nodes: sessions.iter().map(|s| s.node().clone().into()).take(2).collect(),
+			key_version: sessions[1].core.key_share.version().into(),
 		}).unwrap_err(), Error::InvalidMessage);
 	}
 
@@ -747,6 +883,7 @@ mod tests {
 			request_id: Random.generate().unwrap().secret().clone().into(),
 			shadow_point: Random.generate().unwrap().public().clone().into(),
 			decrypt_shadow: None,
+			decrypt_proof: None,
 		}).unwrap_err(), Error::InvalidStateForRequest);
 	}
 
@@ -888,6 +1025,64 @@ mod tests {
 			decrypted_secret: SECRET_PLAIN.into(),
 			common_point: None,
 			decrypt_shadows: None,
+			encrypted_point: None,
+			decrypt_proofs: None,
+		});
+	}
+
+	#[test]
+	fn cancelled_session_fails_on_all_nodes() {
+		let (_, clusters, _, sessions) = prepare_decryption_sessions();
+		sessions[0].initialize(false).unwrap();
+
+		// cancel session on master before consensus is established
+		sessions[0].cancel();
+		assert_eq!(sessions[0].state(), ConsensusSessionState::Failed);
+		assert_eq!(sessions[0].decrypted_secret(), Some(Err(Error::SessionCancelled)));
+
+		// slaves are failing after receiving an error from master (errors are ignored until all messages are processed)
+		while do_messages_exchange(&clusters, &sessions).is_err() {}
+		assert!(sessions.iter().all(|s| s.is_finished()));
+	}
+
+	#[test]
+	fn session_events_are_reported_to_listeners() {
+		let (_, clusters, _, sessions) = prepare_decryption_sessions();
+		let listeners: Vec<_> = sessions.iter().map(|s| {
+			let listener = Arc::new(DummySessionEventListener::default());
+			s.add_listener(listener.clone());
+			listener
+		}).collect();
+
+		sessions[0].initialize(false).unwrap();
+		do_messages_exchange(&clusters, &sessions).unwrap();
+
+		assert_eq!(listeners[0].events(), vec![SessionEvent::Initialized, SessionEvent::Confirmed, SessionEvent::Finished]);
+		for listener in listeners.iter().skip(1) {
+			assert_eq!(listener.events(), vec![SessionEvent::Initialized, SessionEvent::Finished]);
+		}
+	}
+
+	#[test]
+	fn node_without_negotiated_key_version_is_rejected() {
+		let (_, clusters, _, sessions) = prepare_decryption_sessions();
+
+		// last node only knows the other version of the key
+		let mut other_key_share = sessions[4].core.key_share.clone();
+		other_key_share.id_numbers.insert(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone());
+		sessions[4].core.key_storage.insert(SessionId::default(), other_key_share).unwrap();
+
+		sessions[0].initialize(false).unwrap();
+		do_messages_exchange(&clusters, &sessions).unwrap();
+
+		// decryption is completed without this node
+		assert!(sessions[0].data.lock().consensus_session.consensus_job().rejects().contains(sessions[4].node()));
+		assert_eq!(sessions[0].decrypted_secret().unwrap().unwrap(), EncryptedDocumentKeyShadow {
+			decrypted_secret: SECRET_PLAIN.into(),
+			common_point: None,
+			decrypt_shadows: None,
+			encrypted_point: None,
+			decrypt_proofs: None,
 		});
 	}
 
@@ -963,6 +1158,8 @@ mod tests {
 			decrypted_secret: SECRET_PLAIN.into(),
 			common_point: None,
 			decrypt_shadows: None,
+			encrypted_point: None,
+			decrypt_proofs: None,
 		});
 	}
 
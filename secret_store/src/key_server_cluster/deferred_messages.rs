@@ -0,0 +1,161 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time;
+use std::collections::{BTreeMap, VecDeque};
+use parking_lot::Mutex;
+use key_server_cluster::SessionId;
+
+/// Max number of messages, deferred for single session.
+const MAX_DEFERRED_SESSION_MESSAGES: usize = 256;
+/// Max number of sessions, which have deferred messages.
+const MAX_DEFERRED_SESSIONS: usize = 1024;
+
+/// Session messages, deferred while sessions processing is paused. Messages of every session are kept in
+/// a separate bounded FIFO queue && are processed in the order of receival when processing is resumed.
+pub struct DeferredMessages<M> {
+	/// Max time message could stay deferred. None means that message is deferred until it is processed.
+	max_delay: Option<time::Duration>,
+	/// Deferred messages of every session, along with time they have been deferred at.
+	queues: Mutex<BTreeMap<SessionId, VecDeque<(time::Instant, M)>>>,
+}
+
+impl<M> DeferredMessages<M> {
+	/// Create new deferred messages queue.
+	pub fn new(max_delay: Option<time::Duration>) -> Self {
+		DeferredMessages {
+			max_delay: max_delay,
+			queues: Mutex::new(BTreeMap::new()),
+		}
+	}
+
+	/// Defer message of given session if either processing is paused, or there are messages of the same session,
+	/// which are waiting to be processed. Returns message back if it must not be deferred. Returns error with
+	/// the message if it must be deferred, but there's no room for it.
+	pub fn defer(&self, session: &SessionId, message: M, is_paused: bool) -> Result<Option<M>, M> {
+		self.defer_at(session, message, is_paused, time::Instant::now())
+	}
+
+	/// Take the next deferred message. Message, which has been deferred for longer than allowed, is returned
+	/// as error. Queue of the session is only removed after all its messages have been taken && processed,
+	/// so that messages, received meanwhile, are not processed out of order.
+	pub fn next(&self) -> Option<Result<M, M>> {
+		self.next_at(time::Instant::now())
+	}
+
+	/// Get number of deferred messages.
+	pub fn len(&self) -> usize {
+		self.queues.lock().values().map(|queue| queue.len()).sum()
+	}
+
+	fn defer_at(&self, session: &SessionId, message: M, is_paused: bool, now: time::Instant) -> Result<Option<M>, M> {
+		let mut queues = self.queues.lock();
+		let has_room = match queues.get(session) {
+			Some(queue) => queue.len() < MAX_DEFERRED_SESSION_MESSAGES,
+			None if !is_paused => return Ok(Some(message)),
+			None => queues.len() < MAX_DEFERRED_SESSIONS,
+		};
+		if !has_room {
+			return Err(message);
+		}
+
+		queues.entry(session.clone()).or_insert_with(VecDeque::new).push_back((now, message));
+		Ok(None)
+	}
+
+	fn next_at(&self, now: time::Instant) -> Option<Result<M, M>> {
+		let mut queues = self.queues.lock();
+		loop {
+			let session = match queues.keys().next() {
+				Some(session) => session.clone(),
+				None => return None,
+			};
+
+			match queues.get_mut(&session).and_then(|queue| queue.pop_front()) {
+				Some((deferred_at, message)) => return Some(match self.max_delay {
+					Some(max_delay) if now.duration_since(deferred_at) > max_delay => Err(message),
+					_ => Ok(message),
+				}),
+				None => {
+					queues.remove(&session);
+				},
+			}
+		}
+	}
+}
+
+impl<M> Default for DeferredMessages<M> {
+	fn default() -> Self {
+		DeferredMessages::new(None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time;
+	use key_server_cluster::SessionId;
+	use super::{DeferredMessages, MAX_DEFERRED_SESSION_MESSAGES};
+
+	#[test]
+	fn messages_are_deferred_while_paused_and_taken_in_order() {
+		let messages = DeferredMessages::default();
+		let session1 = SessionId::from(1);
+		let session2 = SessionId::from(2);
+
+		// nothing is deferred when not paused
+		assert_eq!(messages.defer(&session1, 1, false), Ok(Some(1)));
+
+		assert_eq!(messages.defer(&session1, 2, true), Ok(None));
+		assert_eq!(messages.defer(&session2, 3, true), Ok(None));
+		assert_eq!(messages.defer(&session1, 4, true), Ok(None));
+		assert_eq!(messages.len(), 3);
+
+		// when resumed, messages of session with deferred messages are still deferred
+		assert_eq!(messages.next(), Some(Ok(2)));
+		assert_eq!(messages.next(), Some(Ok(4)));
+		assert_eq!(messages.defer(&session1, 5, false), Ok(None));
+		assert_eq!(messages.next(), Some(Ok(5)));
+		assert_eq!(messages.next(), Some(Ok(3)));
+		assert_eq!(messages.next(), None);
+		assert_eq!(messages.defer(&session1, 6, false), Ok(Some(6)));
+		assert_eq!(messages.defer(&session2, 7, false), Ok(Some(7)));
+	}
+
+	#[test]
+	fn session_queue_is_bounded() {
+		let messages = DeferredMessages::default();
+		let session = SessionId::from(1);
+		for i in 0..MAX_DEFERRED_SESSION_MESSAGES {
+			assert_eq!(messages.defer(&session, i, true), Ok(None));
+		}
+		assert_eq!(messages.defer(&session, MAX_DEFERRED_SESSION_MESSAGES, true), Err(MAX_DEFERRED_SESSION_MESSAGES));
+		assert_eq!(messages.defer(&session, MAX_DEFERRED_SESSION_MESSAGES, false), Err(MAX_DEFERRED_SESSION_MESSAGES));
+		assert_eq!(messages.defer(&SessionId::from(2), 0, true), Ok(None));
+		assert_eq!(messages.len(), MAX_DEFERRED_SESSION_MESSAGES + 1);
+	}
+
+	#[test]
+	fn message_deferred_for_too_long_is_returned_as_error() {
+		let messages = DeferredMessages::new(Some(time::Duration::from_millis(300)));
+		let session = SessionId::from(1);
+		let now = time::Instant::now();
+		assert_eq!(messages.defer_at(&session, 1, true, now), Ok(None));
+		assert_eq!(messages.defer_at(&session, 2, true, now + time::Duration::from_millis(200)), Ok(None));
+		assert_eq!(messages.next_at(now + time::Duration::from_millis(400)), Some(Err(1)));
+		assert_eq!(messages.next_at(now + time::Duration::from_millis(400)), Some(Ok(2)));
+		assert_eq!(messages.next_at(now + time::Duration::from_millis(400)), None);
+	}
+}
@@ -0,0 +1,1256 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::sync::Arc;
+use parking_lot::{Mutex, Condvar};
+use ethkey::{Public, Secret, Signature};
+use bigint::hash::H256;
+use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, AclStorage, KeyStorage, DocumentKeyShare};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::generation_session::{SessionImpl as GenerationSession, SessionParams as GenerationSessionParams,
+	Session as GenerationSessionApi};
+use key_server_cluster::math;
+use key_server_cluster::message::{Message, EcdsaSigningMessage, EcdsaSigningConsensusMessage, EcdsaSignatureNonceGenerationMessage,
+	EcdsaInversionNonceGenerationMessage, EcdsaSigningZeroShares, EcdsaSigningInversedNonceCoeffShare, EcdsaRequestPartialSignature,
+	EcdsaPartialSignature, EcdsaSigningSessionError, EcdsaSigningSessionCompleted, GenerationMessage, InitializeSession,
+	ConsensusMessage, InitializeConsensusSession, ConfirmConsensusInitialization};
+use key_server_cluster::jobs::job_session::JobTransport;
+use key_server_cluster::jobs::ecdsa_signing_job::{EcdsaPartialSigningRequest, EcdsaPartialSigningResponse, EcdsaSigningJob};
+use key_server_cluster::jobs::consensus_session::{ConsensusSessionParams, ConsensusSessionState, ConsensusSession};
+
+/// ECDSA signing session API.
+pub trait Session: Send + Sync + 'static {
+	/// Wait until session is completed. Returns signed message.
+	fn wait(&self) -> Result<Signature, Error>;
+}
+
+/// Distributed ECDSA signing session.
+/// Based on "A robust threshold elliptic curve digital signature providing a new verifiable secret sharing scheme" paper.
+/// WARNING: can only be used if 2*t < N is true for key generation scheme
+/// Brief overview:
+/// 1) initialization: master node (which has received request for signing the message) requests all other nodes to sign the message
+/// 2) ACL check: all nodes which have received the request are querying ACL-contract to check if requestor has access to the private key
+/// 2.1) version negotiation: nodes, which do not have the latest key version of the master node, are treated as rejecting consensus
+/// 3) nonces generation: 2*t+1 nodes of signing group are generating shares of signature nonce (k) && inversion nonce (a), using DKG
+/// 4) blinding: every node of signing group sends shares of two random 2*t-degree polynoms with zero constant term to every other node
+/// 5) inversion: every node sends its (blinded) share of k*a to master node, which restores k*a && computes inv(k*a)
+/// 6) partial signing: every node computes its (blinded) share of s = a*inv(k*a) * (m + r*x) = inv(k) * (m + r*x)
+/// 7) signing: master node receives all partial signatures, restores s && computes the signature
+pub struct SessionImpl {
+	/// Session core.
+	core: SessionCore,
+	/// Session data.
+	data: Mutex<SessionData>,
+}
+
+/// Immutable session data.
+struct SessionCore {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Signing session access key.
+	pub access_key: Secret,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	pub cluster: Arc<Cluster>,
+	/// Session-level nonce.
+	pub nonce: u64,
+	/// SessionImpl completion condvar.
+	pub completed: Condvar,
+}
+
+/// ECDSA signing consensus session type.
+type EcdsaSigningConsensusSession = ConsensusSession<EcdsaSigningConsensusTransport, EcdsaSigningJob, EcdsaSigningJobTransport>;
+
+/// Mutable session data.
+struct SessionData {
+	/// Session state.
+	pub state: SessionState,
+	/// Message hash.
+	pub message_hash: Option<H256>,
+	/// Key version, negotiated by master node.
+	pub key_version: Option<H256>,
+	/// Consensus-based signing session.
+	pub consensus_session: EcdsaSigningConsensusSession,
+	/// Nodes of signing group && their id numbers.
+	pub signing_group: Option<BTreeMap<NodeId, Secret>>,
+	/// Signature nonce generation session.
+	pub sig_nonce_generation_session: Option<GenerationSession>,
+	/// Inversion nonce generation session.
+	pub inv_nonce_generation_session: Option<GenerationSession>,
+	/// Zero shares (inversion zero share, signature zero share), received from signing group nodes.
+	pub zero_shares: BTreeMap<NodeId, (Secret, Secret)>,
+	/// Inversed nonce coefficient shares, received by master node.
+	pub inversed_nonce_coeff_shares: BTreeMap<NodeId, Secret>,
+	/// Signing result.
+	pub result: Option<Result<Signature, Error>>,
+}
+
+/// ECDSA signing session state.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Clone, Copy))]
+pub enum SessionState {
+	/// State when consensus is establishing.
+	ConsensusEstablishing,
+	/// State when signature && inversion nonces are generating.
+	NoncesGenerating,
+	/// State when inversed nonce coefficient is computing.
+	InversedNonceCoeffComputing,
+	/// State when signature is computing.
+	SignatureComputing,
+}
+
+/// Session creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Session access key.
+	pub access_key: Secret,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// ACL storage.
+	pub acl_storage: Arc<AclStorage>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// ECDSA signing consensus transport.
+struct EcdsaSigningConsensusTransport {
+	/// Session id.
+	id: SessionId,
+	/// Session access key.
+	access_key: Secret,
+	/// Session-level nonce.
+	nonce: u64,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster.
+	cluster: Arc<Cluster>,
+}
+
+/// ECDSA signing nonce generation transport.
+struct NonceGenerationTransport<F: Fn(SessionId, Secret, u64, GenerationMessage) -> EcdsaSigningMessage + Send + Sync> {
+	/// Session id.
+	id: SessionId,
+	/// Session access key.
+	access_key: Secret,
+	/// Session-level nonce.
+	nonce: u64,
+	/// Cluster.
+	cluster: Arc<Cluster>,
+	/// Other nodes ids.
+	other_nodes_ids: BTreeSet<NodeId>,
+	/// Message mapping function.
+	map: F,
+}
+
+/// ECDSA signing job transport
+struct EcdsaSigningJobTransport {
+	/// Session id.
+	id: SessionId,
+	/// Session access key.
+	access_key: Secret,
+	/// Session-level nonce.
+	nonce: u64,
+	/// Key version, used for signing.
+	key_version: H256,
+	/// Cluster.
+	cluster: Arc<Cluster>,
+}
+
+impl SessionImpl {
+	/// Create new ECDSA signing session.
+	pub fn new(params: SessionParams, requester_signature: Option<Signature>) -> Result<Self, Error> {
+		debug_assert_eq!(params.meta.threshold, params.key_share.threshold);
+		debug_assert_eq!(params.meta.self_node_id == params.meta.master_node_id, requester_signature.is_some());
+
+		use key_server_cluster::generation_session::{check_cluster_nodes, check_threshold};
+
+		// check nodes and threshold
+		let nodes = params.key_share.id_numbers.keys().cloned().collect();
+		check_cluster_nodes(&params.meta.self_node_id, &nodes)?;
+		check_threshold(params.key_share.threshold, &nodes)?;
+
+		// signature is computed by 2*t+1 nodes
+		if nodes.len() < params.key_share.threshold * 2 + 1 {
+			return Err(Error::ConsensusUnreachable);
+		}
+
+		let consensus_transport = EcdsaSigningConsensusTransport {
+			id: params.meta.id.clone(),
+			access_key: params.access_key.clone(),
+			nonce: params.nonce,
+			key_storage: params.key_storage.clone(),
+			cluster: params.cluster.clone(),
+		};
+		let consensus_meta = SessionMeta {
+			threshold: params.key_share.threshold * 2,
+			..params.meta.clone()
+		};
+
+		Ok(SessionImpl {
+			core: SessionCore {
+				meta: params.meta,
+				access_key: params.access_key,
+				key_share: params.key_share,
+				key_storage: params.key_storage,
+				cluster: params.cluster,
+				nonce: params.nonce,
+				completed: Condvar::new(),
+			},
+			data: Mutex::new(SessionData {
+				state: SessionState::ConsensusEstablishing,
+				message_hash: None,
+				key_version: None,
+				consensus_session: match requester_signature {
+					Some(requester_signature) => ConsensusSession::new_on_master(ConsensusSessionParams {
+						meta: consensus_meta,
+						acl_storage: params.acl_storage.clone(),
+						consensus_transport: consensus_transport,
+					}, requester_signature)?,
+					None => ConsensusSession::new_on_slave(ConsensusSessionParams {
+						meta: consensus_meta,
+						acl_storage: params.acl_storage.clone(),
+						consensus_transport: consensus_transport,
+					})?,
+				},
+				signing_group: None,
+				sig_nonce_generation_session: None,
+				inv_nonce_generation_session: None,
+				zero_shares: BTreeMap::new(),
+				inversed_nonce_coeff_shares: BTreeMap::new(),
+				result: None,
+			}),
+		})
+	}
+
+	/// Get session state.
+	#[cfg(test)]
+	pub fn state(&self) -> SessionState {
+		self.data.lock().state
+	}
+
+	/// Initialize ECDSA signing session on master node.
+	pub fn initialize(&self, message_hash: H256) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		let key_version = self.core.latest_key_version()?;
+		data.message_hash = Some(message_hash);
+		data.key_version = Some(key_version);
+		data.consensus_session.initialize(self.core.key_share.id_numbers.keys().cloned().collect())?;
+
+		if data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished {
+			self.start_nonces_generation(&mut data)?;
+		}
+
+		Ok(())
+	}
+
+	/// Process ECDSA signing message.
+	pub fn process_message(&self, sender: &NodeId, message: &EcdsaSigningMessage) -> Result<(), Error> {
+		if self.core.nonce != message.session_nonce() {
+			return Err(Error::ReplayProtection);
+		}
+
+		match message {
+			&EcdsaSigningMessage::EcdsaSigningConsensusMessage(ref message) =>
+				self.on_consensus_message(sender, message),
+			&EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(ref message) =>
+				self.on_signature_nonce_generation_message(sender, message),
+			&EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(ref message) =>
+				self.on_inversion_nonce_generation_message(sender, message),
+			&EcdsaSigningMessage::EcdsaSigningZeroShares(ref message) =>
+				self.on_zero_shares(sender, message),
+			&EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(ref message) =>
+				self.on_inversed_nonce_coeff_share(sender, message),
+			&EcdsaSigningMessage::EcdsaRequestPartialSignature(ref message) =>
+				self.on_partial_signature_requested(sender, message),
+			&EcdsaSigningMessage::EcdsaPartialSignature(ref message) =>
+				self.on_partial_signature(sender, message),
+			&EcdsaSigningMessage::EcdsaSigningSessionError(ref message) =>
+				self.on_session_error(sender, message),
+			&EcdsaSigningMessage::EcdsaSigningSessionCompleted(ref message) =>
+				self.on_session_completed(sender, message),
+		}
+	}
+
+	/// When consensus-related message is received.
+	pub fn on_consensus_message(&self, sender: &NodeId, message: &EcdsaSigningConsensusMessage) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+		let is_establishing_consensus = data.consensus_session.state() == ConsensusSessionState::EstablishingConsensus;
+		let consensus_message = self.core.filter_consensus_message(data.key_version.as_ref(), &message.message)?;
+		data.consensus_session.on_consensus_message(&sender, &consensus_message)?;
+
+		let is_consensus_established = data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished;
+		if self.core.meta.self_node_id != self.core.meta.master_node_id || !is_establishing_consensus || !is_consensus_established {
+			return Ok(());
+		}
+
+		self.start_nonces_generation(&mut data)
+	}
+
+	/// When signature nonce generation message is received.
+	pub fn on_signature_nonce_generation_message(&self, sender: &NodeId, message: &EcdsaSignatureNonceGenerationMessage) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+
+		if let &GenerationMessage::InitializeSession(ref init_message) = &message.message {
+			if data.sig_nonce_generation_session.is_some() {
+				return Err(Error::InvalidStateForRequest);
+			}
+
+			let signing_group = self.on_signing_group_received(&mut data, sender, init_message)?;
+			data.sig_nonce_generation_session = Some(self.core.signature_nonce_generation_session(&signing_group));
+		}
+
+		data.sig_nonce_generation_session.as_ref()
+			.ok_or(Error::InvalidStateForRequest)?
+			.process_message(sender, &message.message)?;
+		self.try_compute_inversed_nonce_coeff_share(&mut data)
+	}
+
+	/// When inversion nonce generation message is received.
+	pub fn on_inversion_nonce_generation_message(&self, sender: &NodeId, message: &EcdsaInversionNonceGenerationMessage) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+
+		if let &GenerationMessage::InitializeSession(ref init_message) = &message.message {
+			if data.inv_nonce_generation_session.is_some() {
+				return Err(Error::InvalidStateForRequest);
+			}
+
+			let signing_group = self.on_signing_group_received(&mut data, sender, init_message)?;
+			data.inv_nonce_generation_session = Some(self.core.inversion_nonce_generation_session(&signing_group));
+		}
+
+		data.inv_nonce_generation_session.as_ref()
+			.ok_or(Error::InvalidStateForRequest)?
+			.process_message(sender, &message.message)?;
+		self.try_compute_inversed_nonce_coeff_share(&mut data)
+	}
+
+	/// When zero shares are received.
+	pub fn on_zero_shares(&self, sender: &NodeId, message: &EcdsaSigningZeroShares) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+
+		let is_signing_group_node = match data.signing_group {
+			Some(ref signing_group) => signing_group.contains_key(sender),
+			None => return Err(Error::TooEarlyForRequest),
+		};
+		if !is_signing_group_node || self.core.key_share.threshold == 0 || data.zero_shares.contains_key(sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.zero_shares.insert(sender.clone(), (message.inversion_zero_share.clone().into(), message.signature_zero_share.clone().into()));
+		self.try_compute_inversed_nonce_coeff_share(&mut data)
+	}
+
+	/// When share of inversed nonce coefficient is received.
+	pub fn on_inversed_nonce_coeff_share(&self, sender: &NodeId, message: &EcdsaSigningInversedNonceCoeffShare) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+
+		if self.core.meta.self_node_id != self.core.meta.master_node_id {
+			return Err(Error::InvalidMessage);
+		}
+		match data.state {
+			SessionState::NoncesGenerating => return Err(Error::TooEarlyForRequest),
+			SessionState::InversedNonceCoeffComputing => (),
+			_ => return Err(Error::InvalidStateForRequest),
+		}
+
+		let is_signing_group_node = data.signing_group.as_ref()
+			.expect("signing group is selected before nonces are generated; we are in InversedNonceCoeffComputing state; qed")
+			.contains_key(sender);
+		if !is_signing_group_node || data.inversed_nonce_coeff_shares.contains_key(sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.inversed_nonce_coeff_shares.insert(sender.clone(), message.inversed_nonce_coeff_share.clone().into());
+		self.try_disseminate_jobs(&mut data)
+	}
+
+	/// When partial signature is requested.
+	pub fn on_partial_signature_requested(&self, sender: &NodeId, message: &EcdsaRequestPartialSignature) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+
+		if sender != &self.core.meta.master_node_id {
+			return Err(Error::InvalidMessage);
+		}
+		if data.state != SessionState::InversedNonceCoeffComputing {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		let key_version: H256 = message.key_version.clone().into();
+		let key_share = self.core.key_storage.get_version(&self.core.meta.id, &key_version)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		let other_nodes_ids: BTreeSet<NodeId> = message.nodes.iter().cloned().map(Into::into).collect();
+		{
+			// signature must be computed by the signing group && nonces must be generated using key share id numbers
+			let signing_group = data.signing_group.as_ref()
+				.expect("signing group is received before nonces are generated; we are in InversedNonceCoeffComputing state; qed");
+			if other_nodes_ids.len() + 1 != signing_group.len()
+				|| other_nodes_ids.iter().any(|n| !signing_group.contains_key(n))
+				|| signing_group.iter().any(|(n, id_number)| key_share.id_numbers.get(n) != Some(id_number)) {
+				return Err(Error::InvalidMessage);
+			}
+		}
+
+		let (nonce_public, inversion_nonce_share, signature_zero_share) = generated_nonces(&data)?;
+		let signing_job = EcdsaSigningJob::new_on_slave(self.core.meta.self_node_id.clone(), key_share,
+			nonce_public, inversion_nonce_share, signature_zero_share)?;
+		let signing_transport = self.core.signing_transport(key_version);
+		data.state = SessionState::SignatureComputing;
+
+		data.consensus_session.on_job_request(sender, EcdsaPartialSigningRequest {
+			id: message.request_id.clone().into(),
+			inversed_nonce_coeff: message.inversed_nonce_coeff.clone().into(),
+			message_hash: message.message_hash.clone().into(),
+			other_nodes_ids: other_nodes_ids,
+		}, signing_job, signing_transport)
+	}
+
+	/// When partial signature is received.
+	pub fn on_partial_signature(&self, sender: &NodeId, message: &EcdsaPartialSignature) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+		data.consensus_session.on_job_response(sender, EcdsaPartialSigningResponse {
+			request_id: message.request_id.clone().into(),
+			partial_signature_s: message.partial_signature_s.clone().into(),
+		})?;
+
+		self.try_complete(&mut data)
+	}
+
+	/// When session is completed.
+	pub fn on_session_completed(&self, sender: &NodeId, message: &EcdsaSigningSessionCompleted) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+		data.consensus_session.on_session_completed(sender)?;
+		data.clear_secrets();
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: &NodeId, message: &EcdsaSigningSessionError) -> Result<(), Error> {
+		self.process_node_error(Some(&sender), &Error::remote(message.code, message.error.clone()))
+	}
+
+	/// Select signing group && start nonces generation on master node.
+	fn start_nonces_generation(&self, data: &mut SessionData) -> Result<(), Error> {
+		let signing_group: BTreeMap<_, _> = data.consensus_session.select_consensus_group()?
+			.iter()
+			.map(|n| (n.clone(), self.core.key_share.id_numbers[n].clone()))
+			.collect();
+
+		let sig_nonce_generation_session = self.core.signature_nonce_generation_session(&signing_group);
+		sig_nonce_generation_session.initialize_with_id_numbers(Public::default(), self.core.key_share.threshold, signing_group.clone())?;
+		let inv_nonce_generation_session = self.core.inversion_nonce_generation_session(&signing_group);
+		inv_nonce_generation_session.initialize_with_id_numbers(Public::default(), self.core.key_share.threshold, signing_group.clone())?;
+		data.sig_nonce_generation_session = Some(sig_nonce_generation_session);
+		data.inv_nonce_generation_session = Some(inv_nonce_generation_session);
+		data.state = SessionState::NoncesGenerating;
+
+		self.core.disseminate_zero_shares(data, signing_group)?;
+		self.try_compute_inversed_nonce_coeff_share(data)
+	}
+
+	/// When signing group is received from master node (as a part of nonce generation session initialization).
+	fn on_signing_group_received(&self, data: &mut SessionData, sender: &NodeId, message: &InitializeSession) -> Result<BTreeMap<NodeId, Secret>, Error> {
+		if &self.core.meta.master_node_id != sender || message.threshold != self.core.key_share.threshold {
+			return Err(Error::InvalidMessage);
+		}
+
+		// both nonces are generated by the same signing group
+		let signing_group: BTreeMap<NodeId, Secret> = message.nodes.iter()
+			.map(|(n, id_number)| (n.clone().into(), id_number.clone().into()))
+			.collect();
+		if let Some(ref known_signing_group) = data.signing_group {
+			if known_signing_group != &signing_group {
+				return Err(Error::InvalidMessage);
+			}
+
+			return Ok(signing_group);
+		}
+
+		data.state = SessionState::NoncesGenerating;
+		self.core.disseminate_zero_shares(data, signing_group.clone())?;
+		Ok(signing_group)
+	}
+
+	/// Compute && send share of inversed nonce coefficient, if both nonces are generated && all zero shares are received.
+	fn try_compute_inversed_nonce_coeff_share(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::NoncesGenerating {
+			return Ok(());
+		}
+
+		let nonce_share = match generated_secret_share(&data.sig_nonce_generation_session)? {
+			Some(nonce_share) => nonce_share,
+			None => return Ok(()),
+		};
+		let inversion_nonce_share = match generated_secret_share(&data.inv_nonce_generation_session)? {
+			Some(inversion_nonce_share) => inversion_nonce_share,
+			None => return Ok(()),
+		};
+		let signing_group_len = data.signing_group.as_ref()
+			.expect("signing group is known before nonces are generating; we are in NoncesGenerating state; qed")
+			.len();
+		if self.core.key_share.threshold != 0 && data.zero_shares.len() != signing_group_len {
+			return Ok(());
+		}
+
+		let inversion_zero_share = compute_zero_shares_sum(data.zero_shares.values().map(|s| &s.0))?;
+		let inversed_nonce_coeff_share = math::compute_ecdsa_inversed_nonce_coeff_share(&nonce_share, &inversion_nonce_share, inversion_zero_share.as_ref())?;
+		data.state = SessionState::InversedNonceCoeffComputing;
+
+		if self.core.meta.self_node_id != self.core.meta.master_node_id {
+			return self.core.cluster.send(&self.core.meta.master_node_id, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(EcdsaSigningInversedNonceCoeffShare {
+				session: self.core.meta.id.clone().into(),
+				sub_session: self.core.access_key.clone().into(),
+				session_nonce: self.core.nonce,
+				inversed_nonce_coeff_share: inversed_nonce_coeff_share.into(),
+			})));
+		}
+
+		data.inversed_nonce_coeff_shares.insert(self.core.meta.self_node_id.clone(), inversed_nonce_coeff_share);
+		self.try_disseminate_jobs(data)
+	}
+
+	/// Compute inversed nonce coefficient && disseminate signing jobs, if all inversed nonce coefficient shares are received.
+	fn try_disseminate_jobs(&self, data: &mut SessionData) -> Result<(), Error> {
+		let inversed_nonce_coeff = {
+			let signing_group = data.signing_group.as_ref()
+				.expect("signing group is selected before nonces are generated; we are in InversedNonceCoeffComputing state; qed");
+			if data.inversed_nonce_coeff_shares.len() != signing_group.len() {
+				return Ok(());
+			}
+
+			let (coeff_shares, id_numbers): (Vec<_>, Vec<_>) = data.inversed_nonce_coeff_shares.iter()
+				.map(|(n, coeff_share)| (coeff_share, &signing_group[n]))
+				.unzip();
+			math::compute_ecdsa_inversed_nonce_coeff(&coeff_shares, &id_numbers)?
+		};
+
+		let message_hash = data.message_hash.clone()
+			.expect("we are on master node; on master node message_hash is filled in initialize(); try_disseminate_jobs follows initialize; qed");
+		let key_version = data.key_version.clone()
+			.expect("we are on master node; on master node key_version is filled in initialize(); try_disseminate_jobs follows initialize; qed");
+		let (nonce_public, inversion_nonce_share, signature_zero_share) = generated_nonces(data)?;
+		let signing_job = EcdsaSigningJob::new_on_master(self.core.meta.self_node_id.clone(), self.core.key_share.clone(),
+			nonce_public, inversion_nonce_share, signature_zero_share, inversed_nonce_coeff, message_hash)?;
+		data.state = SessionState::SignatureComputing;
+		data.consensus_session.disseminate_jobs(signing_job, self.core.signing_transport(key_version))?;
+
+		self.try_complete(data)
+	}
+
+	/// Complete session on master node, if signature is computed.
+	fn try_complete(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.consensus_session.state() != ConsensusSessionState::Finished {
+			return Ok(());
+		}
+
+		// send completion signal to all nodes, except for rejected nodes
+		for node in data.consensus_session.consensus_non_rejected_nodes() {
+			self.core.cluster.send(&node, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionCompleted(EcdsaSigningSessionCompleted {
+				session: self.core.meta.id.clone().into(),
+				sub_session: self.core.access_key.clone().into(),
+				session_nonce: self.core.nonce,
+			})))?;
+		}
+
+		data.result = Some(Ok(data.consensus_session.result()?));
+		data.clear_secrets();
+		self.core.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Process error from the other node.
+	fn process_node_error(&self, node: Option<&NodeId>, error: &Error) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		let consensus_state = data.consensus_session.state();
+		if consensus_state == ConsensusSessionState::Finished || consensus_state == ConsensusSessionState::Failed || data.result.is_some() {
+			return Ok(());
+		}
+
+		// once signing group is selected, every node of the group is required to compute the signature
+		let is_signing_group_failed = match (node, data.signing_group.as_ref()) {
+			(Some(node), Some(signing_group)) => signing_group.contains_key(node),
+			(None, Some(_)) => true,
+			(_, None) => false,
+		};
+		let result = match node {
+			Some(node) => data.consensus_session.on_node_error(node),
+			None => data.consensus_session.on_session_timeout(),
+		};
+
+		match result {
+			Ok(_) if !is_signing_group_failed => Ok(()),
+			result => {
+				let err = result.err().unwrap_or(Error::ConsensusUnreachable);
+				warn!("{}: ECDSA signing session failed with error: {:?} from {:?}", &self.core.meta.self_node_id, error, node);
+
+				data.result = Some(Err(err.clone()));
+				data.clear_secrets();
+				self.core.completed.notify_all();
+				Err(err)
+			},
+		}
+	}
+}
+
+impl SessionData {
+	/// Forget zero shares && inversed nonce coefficient shares, which are not required once the session is completed.
+	fn clear_secrets(&mut self) {
+		self.zero_shares.clear();
+		self.inversed_nonce_coeff_shares.clear();
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.consensus_session.state() == ConsensusSessionState::Failed
+			|| data.consensus_session.state() == ConsensusSessionState::Finished
+			|| data.result.is_some()
+	}
+
+	fn is_failed(&self) -> bool {
+		let data = self.data.lock();
+		data.consensus_session.state() == ConsensusSessionState::Failed
+			|| data.result.as_ref().map(|r| r.is_err()).unwrap_or(false)
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		// ignore error, only state matters
+		let _ = self.process_node_error(Some(node), &Error::NodeDisconnected);
+	}
+
+	fn on_session_timeout(&self) {
+		// ignore error, only state matters
+		let _ = self.process_node_error(None, &Error::NodeDisconnected);
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		let state = data.consensus_session.state();
+		if state == ConsensusSessionState::Failed || state == ConsensusSessionState::Finished || data.result.is_some() {
+			return;
+		}
+
+		warn!("{}: ECDSA signing session has been cancelled", &self.core.meta.self_node_id);
+
+		// key storage is never modified by signing session => nothing to restore
+		// do not bother processing send error, as we already processing error
+		let error = Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(EcdsaSigningSessionError {
+			session: self.core.meta.id.clone().into(),
+			sub_session: self.core.access_key.clone().into(),
+			session_nonce: self.core.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.core.meta.self_node_id == self.core.meta.master_node_id {
+			self.core.cluster.broadcast(error)
+		} else {
+			self.core.cluster.send(&self.core.meta.master_node_id, error)
+		};
+
+		data.consensus_session.on_session_cancelled();
+		data.result = Some(Err(Error::SessionCancelled));
+		data.clear_secrets();
+		self.core.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn wait(&self) -> Result<Signature, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			self.core.completed.wait(&mut data);
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+impl<F> NonceGenerationTransport<F> where F: Fn(SessionId, Secret, u64, GenerationMessage) -> EcdsaSigningMessage + Send + Sync {
+	fn map_message(&self, message: Message) -> Result<Message, Error> {
+		match message {
+			Message::Generation(message) => Ok(Message::EcdsaSigning((self.map)(self.id.clone(), self.access_key.clone(), self.nonce, message))),
+			_ => Err(Error::InvalidMessage),
+		}
+	}
+}
+
+impl<F> Cluster for NonceGenerationTransport<F> where F: Fn(SessionId, Secret, u64, GenerationMessage) -> EcdsaSigningMessage + Send + Sync {
+	fn broadcast(&self, message: Message) -> Result<(), Error> {
+		let message = self.map_message(message)?;
+		for to in &self.other_nodes_ids {
+			self.cluster.send(to, message.clone())?;
+		}
+		Ok(())
+	}
+
+	fn send(&self, to: &NodeId, message: Message) -> Result<(), Error> {
+		debug_assert!(self.other_nodes_ids.contains(to));
+		self.cluster.send(to, self.map_message(message)?)
+	}
+
+	fn nodes(&self) -> BTreeSet<NodeId> {
+		self.cluster.nodes()
+	}
+
+	fn traffic(&self) -> (u64, u64) {
+		self.cluster.traffic()
+	}
+
+	fn on_message_received(&self, size: usize) -> Result<(), Error> {
+		self.cluster.on_message_received(size)
+	}
+}
+
+impl SessionCore {
+	pub fn signing_transport(&self, key_version: H256) -> EcdsaSigningJobTransport {
+		EcdsaSigningJobTransport {
+			id: self.meta.id.clone(),
+			access_key: self.access_key.clone(),
+			nonce: self.nonce,
+			key_version: key_version,
+			cluster: self.cluster.clone()
+		}
+	}
+
+	pub fn latest_key_version(&self) -> Result<H256, Error> {
+		self.key_storage.versions(&self.meta.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?
+			.into_iter()
+			.nth(0)
+			.ok_or(Error::KeyStorage("key share has no versions".into()))
+	}
+
+	pub fn filter_consensus_message(&self, key_version: Option<&H256>, message: &ConsensusMessage) -> Result<ConsensusMessage, Error> {
+		match *message {
+			// on master node: nodes, which do not have the negotiated key version, are treated as rejecting consensus
+			ConsensusMessage::ConfirmConsensusInitialization(ref message) if self.meta.self_node_id == self.meta.master_node_id => {
+				let key_version = key_version.ok_or(Error::InvalidStateForRequest)?;
+				Ok(ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+					is_confirmed: message.is_confirmed && message.key_versions.iter().any(|v| &**v == key_version),
+					key_versions: message.key_versions.clone(),
+				}))
+			},
+			_ => Ok(message.clone()),
+		}
+	}
+
+	pub fn signature_nonce_generation_session(&self, signing_group: &BTreeMap<NodeId, Secret>) -> GenerationSession {
+		self.nonce_generation_session(signing_group, |id, access_key, nonce, message|
+			EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(EcdsaSignatureNonceGenerationMessage {
+				session: id.into(),
+				sub_session: access_key.into(),
+				session_nonce: nonce,
+				message: message,
+			}))
+	}
+
+	pub fn inversion_nonce_generation_session(&self, signing_group: &BTreeMap<NodeId, Secret>) -> GenerationSession {
+		self.nonce_generation_session(signing_group, |id, access_key, nonce, message|
+			EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(EcdsaInversionNonceGenerationMessage {
+				session: id.into(),
+				sub_session: access_key.into(),
+				session_nonce: nonce,
+				message: message,
+			}))
+	}
+
+	fn nonce_generation_session<F>(&self, signing_group: &BTreeMap<NodeId, Secret>, map: F) -> GenerationSession
+		where F: Fn(SessionId, Secret, u64, GenerationMessage) -> EcdsaSigningMessage + Send + Sync + 'static {
+		GenerationSession::new(GenerationSessionParams {
+			id: self.meta.id.clone(),
+			self_node_id: self.meta.self_node_id.clone(),
+			key_storage: None,
+			cluster: Arc::new(NonceGenerationTransport {
+				id: self.meta.id.clone(),
+				access_key: self.access_key.clone(),
+				nonce: self.nonce,
+				cluster: self.cluster.clone(),
+				other_nodes_ids: signing_group.keys().filter(|n| **n != self.meta.self_node_id).cloned().collect(),
+				map: map,
+			}),
+			nonce: None,
+		})
+	}
+
+	/// Generate shares of two random 2*t-degree polynoms with zero constant term && send these to every other node of signing group.
+	fn disseminate_zero_shares(&self, data: &mut SessionData, signing_group: BTreeMap<NodeId, Secret>) -> Result<(), Error> {
+		// when t == 0, there's nothing to blind
+		if self.key_share.threshold != 0 {
+			let inversion_zero_polynom = math::generate_random_polynom(self.key_share.threshold * 2 - 1)?;
+			let signature_zero_polynom = math::generate_random_polynom(self.key_share.threshold * 2 - 1)?;
+			for (node, id_number) in &signing_group {
+				let inversion_zero_share = math::compute_share_refresh_delta(&inversion_zero_polynom, id_number)?;
+				let signature_zero_share = math::compute_share_refresh_delta(&signature_zero_polynom, id_number)?;
+				if node == &self.meta.self_node_id {
+					data.zero_shares.insert(node.clone(), (inversion_zero_share, signature_zero_share));
+					continue;
+				}
+
+				self.cluster.send(node, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningZeroShares(EcdsaSigningZeroShares {
+					session: self.meta.id.clone().into(),
+					sub_session: self.access_key.clone().into(),
+					session_nonce: self.nonce,
+					inversion_zero_share: inversion_zero_share.into(),
+					signature_zero_share: signature_zero_share.into(),
+				})))?;
+			}
+		}
+
+		data.signing_group = Some(signing_group);
+		Ok(())
+	}
+}
+
+impl JobTransport for EcdsaSigningConsensusTransport {
+	type PartialJobRequest=Signature;
+	type PartialJobResponse=bool;
+
+	fn send_partial_request(&self, node: &NodeId, request: Signature) -> Result<(), Error> {
+		self.cluster.send(node, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningConsensusMessage(EcdsaSigningConsensusMessage {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			message: ConsensusMessage::InitializeConsensusSession(InitializeConsensusSession {
+				requestor_signature: request.into(),
+			})
+		})))
+	}
+
+	fn send_partial_response(&self, node: &NodeId, response: bool) -> Result<(), Error> {
+		let key_versions = self.key_storage.versions(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		self.cluster.send(node, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningConsensusMessage(EcdsaSigningConsensusMessage {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			message: ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+				is_confirmed: response,
+				key_versions: key_versions.into_iter().map(Into::into).collect(),
+			})
+		})))
+	}
+}
+
+impl JobTransport for EcdsaSigningJobTransport {
+	type PartialJobRequest=EcdsaPartialSigningRequest;
+	type PartialJobResponse=EcdsaPartialSigningResponse;
+
+	fn send_partial_request(&self, node: &NodeId, request: EcdsaPartialSigningRequest) -> Result<(), Error> {
+		self.cluster.send(node, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaRequestPartialSignature(EcdsaRequestPartialSignature {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			request_id: request.id.into(),
+			inversed_nonce_coeff: request.inversed_nonce_coeff.into(),
+			message_hash: request.message_hash.into(),
+			nodes: request.other_nodes_ids.into_iter().map(Into::into).collect(),
+			key_version: self.key_version.clone().into(),
+		})))
+	}
+
+	fn send_partial_response(&self, node: &NodeId, response: EcdsaPartialSigningResponse) -> Result<(), Error> {
+		self.cluster.send(node, Message::EcdsaSigning(EcdsaSigningMessage::EcdsaPartialSignature(EcdsaPartialSignature {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			request_id: response.request_id.into(),
+			partial_signature_s: response.partial_signature_s.into(),
+		})))
+	}
+}
+
+/// Get secret share, generated by nonce generation session (if it is already generated).
+fn generated_secret_share(generation_session: &Option<GenerationSession>) -> Result<Option<Secret>, Error> {
+	match generation_session.as_ref().and_then(|s| s.key_share()) {
+		Some(key_share) => key_share.map(|key_share| Some(key_share.secret_share)),
+		None => Ok(None),
+	}
+}
+
+/// Get signature nonce public, inversion nonce share && signature zero share of this node.
+fn generated_nonces(data: &SessionData) -> Result<(Public, Secret, Option<Secret>), Error> {
+	let nonce_public = data.sig_nonce_generation_session.as_ref()
+		.and_then(|s| s.joint_public_and_secret())
+		.expect("nonces are generated before signature is computed; qed")?
+		.0;
+	let inversion_nonce_share = generated_secret_share(&data.inv_nonce_generation_session)?
+		.expect("nonces are generated before signature is computed; qed");
+	let signature_zero_share = compute_zero_shares_sum(data.zero_shares.values().map(|s| &s.1))?;
+	Ok((nonce_public, inversion_nonce_share, signature_zero_share))
+}
+
+/// Compute sum of zero shares (None if there are no zero shares).
+fn compute_zero_shares_sum<'a, I>(mut zero_shares: I) -> Result<Option<Secret>, Error> where I: Iterator<Item=&'a Secret> {
+	match zero_shares.next() {
+		Some(zero_share) => math::compute_secret_sum(::std::iter::once(zero_share).chain(zero_shares)).map(Some),
+		None => Ok(None),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::str::FromStr;
+	use std::collections::{BTreeMap, VecDeque};
+	use bigint::hash::H256;
+	use ethkey::{self, Random, Generator, Public, Secret, KeyPair};
+	use acl_storage::DummyAclStorage;
+	use key_server_cluster::{NodeId, DocumentKeyShare, SessionId, SessionMeta, Error, KeyStorage, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::cluster_sessions::ClusterSession;
+	use key_server_cluster::generation_session::{Session as GenerationSession};
+	use key_server_cluster::generation_session::tests::MessageLoop as KeyGenerationMessageLoop;
+	use key_server_cluster::message::{Message, EcdsaSigningMessage, EcdsaSigningZeroShares, EcdsaSigningInversedNonceCoeffShare,
+		EcdsaRequestPartialSignature};
+	use key_server_cluster::ecdsa_signing_session::{Session, SessionImpl, SessionState, SessionParams};
+
+	struct Node {
+		pub node_id: NodeId,
+		pub cluster: Arc<DummyCluster>,
+		pub session: SessionImpl,
+	}
+
+	struct MessageLoop {
+		pub session_id: SessionId,
+		pub requester: KeyPair,
+		pub nodes: BTreeMap<NodeId, Node>,
+		pub queue: VecDeque<(NodeId, NodeId, Message)>,
+		pub acl_storages: Vec<Arc<DummyAclStorage>>,
+	}
+
+	impl MessageLoop {
+		pub fn new(gl: &KeyGenerationMessageLoop) -> Self {
+			let mut nodes = BTreeMap::new();
+			let session_id = gl.session_id.clone();
+			let requester = Random.generate().unwrap();
+			let signature = Some(ethkey::sign(requester.secret(), &SessionId::default()).unwrap());
+			let master_node_id = gl.nodes.keys().nth(0).unwrap().clone();
+			let mut acl_storages = Vec::new();
+			for (i, (gl_node_id, gl_node)) in gl.nodes.iter().enumerate() {
+				let acl_storage = Arc::new(DummyAclStorage::default());
+				acl_storages.push(acl_storage.clone());
+				let cluster = Arc::new(DummyCluster::new(gl_node_id.clone()));
+				let session = SessionImpl::new(SessionParams {
+					meta: SessionMeta {
+						id: session_id.clone(),
+						self_node_id: gl_node_id.clone(),
+						master_node_id: master_node_id.clone(),
+						threshold: gl_node.key_storage.get(&session_id).unwrap().threshold,
+					},
+					access_key: "834cb736f02d9c968dfaf0c37658a1d86ff140554fc8b59c9fdad5a8cf810eec".parse().unwrap(),
+					key_share: gl_node.key_storage.get(&session_id).unwrap(),
+					key_storage: gl_node.key_storage.clone(),
+					acl_storage: acl_storage,
+					cluster: cluster.clone(),
+					nonce: 0,
+				}, if i == 0 { signature.clone() } else { None }).unwrap();
+				nodes.insert(gl_node_id.clone(), Node { node_id: gl_node_id.clone(), cluster: cluster, session: session });
+			}
+
+			let nodes_ids: Vec<_> = nodes.keys().cloned().collect();
+			for node in nodes.values() {
+				for node_id in &nodes_ids {
+					node.cluster.add_node(node_id.clone());
+				}
+			}
+
+			MessageLoop {
+				session_id: session_id,
+				requester: requester,
+				nodes: nodes,
+				queue: VecDeque::new(),
+				acl_storages: acl_storages,
+			}
+		}
+
+		pub fn master(&self) -> &SessionImpl {
+			&self.nodes.values().nth(0).unwrap().session
+		}
+
+		pub fn take_message(&mut self) -> Option<(NodeId, NodeId, Message)> {
+			self.nodes.values()
+				.filter_map(|n| n.cluster.take_message().map(|m| (n.node_id.clone(), m.0, m.1)))
+				.nth(0)
+				.or_else(|| self.queue.pop_front())
+		}
+
+		pub fn process_message(&mut self, mut msg: (NodeId, NodeId, Message)) -> Result<(), Error> {
+			let mut is_queued_message = false;
+			loop {
+				match {
+					match msg.2 {
+						Message::EcdsaSigning(ref message) => self.nodes[&msg.1].session.process_message(&msg.0, message),
+						_ => panic!("unexpected"),
+					}
+				} {
+					Ok(_) => {
+						if let Some(message) = self.queue.pop_front() {
+							msg = message;
+							is_queued_message = true;
+							continue;
+						}
+						return Ok(());
+					},
+					Err(Error::TooEarlyForRequest) => {
+						if is_queued_message {
+							self.queue.push_front(msg);
+						} else {
+							self.queue.push_back(msg);
+						}
+						return Ok(());
+					},
+					Err(err) => return Err(err),
+				}
+			}
+		}
+
+		pub fn run_until<F: Fn(&MessageLoop) -> bool>(&mut self, predicate: F) -> Result<(), Error> {
+			while let Some((from, to, message)) = self.take_message() {
+				if predicate(self) {
+					return Ok(());
+				}
+
+				self.process_message((from, to, message))?;
+			}
+
+			unreachable!("either wrong predicate, or failing test")
+		}
+	}
+
+	fn prepare_signing_sessions(threshold: usize, num_nodes: usize) -> (KeyGenerationMessageLoop, MessageLoop) {
+		// run key generation sessions
+		let mut gl = KeyGenerationMessageLoop::new(num_nodes);
+		gl.master().initialize(Public::default(), threshold, gl.nodes.keys().cloned().collect()).unwrap();
+		while let Some((from, to, message)) = gl.take_message() {
+			gl.process_message((from, to, message)).unwrap();
+		}
+
+		// run signing session
+		let sl = MessageLoop::new(&gl);
+		(gl, sl)
+	}
+
+	#[test]
+	fn complete_gen_ecdsa_sign_session() {
+		let test_cases = [(0, 1), (0, 3), (1, 3), (1, 4), (2, 5), (2, 7)];
+		for &(threshold, num_nodes) in &test_cases {
+			let (gl, mut sl) = prepare_signing_sessions(threshold, num_nodes);
+
+			// run signing session
+			let message_hash = H256::from(777);
+			sl.master().initialize(message_hash).unwrap();
+			while let Some((from, to, message)) = sl.take_message() {
+				sl.process_message((from, to, message)).unwrap();
+			}
+
+			// verify signature
+			let public = gl.master().joint_public_and_secret().unwrap().unwrap().0;
+			let signature = sl.master().wait().unwrap();
+			assert!(ethkey::verify_public(&public, &signature, &message_hash).unwrap());
+			assert_eq!(ethkey::recover(&signature, &message_hash).unwrap(), public);
+		}
+	}
+
+	#[test]
+	fn fails_to_construct_if_not_enough_nodes() {
+		let mut nodes = BTreeMap::new();
+		let self_node_id = Random.generate().unwrap().public().clone();
+		nodes.insert(self_node_id.clone(), Random.generate().unwrap().secret().clone());
+		nodes.insert(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone());
+		match SessionImpl::new(SessionParams {
+			meta: SessionMeta {
+				id: SessionId::default(),
+				self_node_id: self_node_id.clone(),
+				master_node_id: self_node_id.clone(),
+				threshold: 1,
+			},
+			access_key: Random.generate().unwrap().secret().clone(),
+			key_share: DocumentKeyShare {
+				author: Public::default(),
+				threshold: 1,
+				id_numbers: nodes,
+				secret_share: Random.generate().unwrap().secret().clone(),
+				common_point: Some(Random.generate().unwrap().public().clone()),
+				encrypted_point: Some(Random.generate().unwrap().public().clone()),
+				refresh_id: None,
+				metadata: Default::default(),
+			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
+			acl_storage: Arc::new(DummyAclStorage::default()),
+			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
+			nonce: 0,
+		}, Some(ethkey::sign(Random.generate().unwrap().secret(), &SessionId::default()).unwrap())) {
+			Err(Error::ConsensusUnreachable) => (),
+			_ => panic!("unexpected"),
+		}
+	}
+
+	#[test]
+	fn fails_to_initialize_when_already_initialized() {
+		let (_, sl) = prepare_signing_sessions(1, 3);
+		assert_eq!(sl.master().initialize(777.into()), Ok(()));
+		assert_eq!(sl.master().initialize(777.into()), Err(Error::InvalidStateForRequest));
+	}
+
+	#[test]
+	fn zero_shares_are_delayed_until_signing_group_is_known() {
+		let (_, sl) = prepare_signing_sessions(1, 3);
+		let slave1 = &sl.nodes.values().nth(1).unwrap().session;
+		assert_eq!(slave1.process_message(sl.nodes.keys().nth(2).unwrap(), &EcdsaSigningMessage::EcdsaSigningZeroShares(EcdsaSigningZeroShares {
+			session: SessionId::default().into(),
+			sub_session: sl.master().core.access_key.clone().into(),
+			session_nonce: 0,
+			inversion_zero_share: Random.generate().unwrap().secret().clone().into(),
+			signature_zero_share: Random.generate().unwrap().secret().clone().into(),
+		})), Err(Error::TooEarlyForRequest));
+	}
+
+	#[test]
+	fn fails_when_inversed_nonce_coeff_share_is_received_by_slave_node() {
+		let (_, sl) = prepare_signing_sessions(1, 3);
+		let slave1 = &sl.nodes.values().nth(1).unwrap().session;
+		assert_eq!(slave1.on_inversed_nonce_coeff_share(sl.nodes.keys().nth(2).unwrap(), &EcdsaSigningInversedNonceCoeffShare {
+			session: SessionId::default().into(),
+			sub_session: sl.master().core.access_key.clone().into(),
+			session_nonce: 0,
+			inversed_nonce_coeff_share: Random.generate().unwrap().secret().clone().into(),
+		}), Err(Error::InvalidMessage));
+	}
+
+	#[test]
+	fn fails_when_signature_requested_by_slave_node() {
+		let (_, sl) = prepare_signing_sessions(1, 3);
+		let slave1 = &sl.nodes.values().nth(1).unwrap().session;
+		assert_eq!(slave1.on_partial_signature_requested(sl.nodes.keys().nth(2).unwrap(), &EcdsaRequestPartialSignature {
+			session: SessionId::default().into(),
+			sub_session: sl.master().core.access_key.clone().into(),
+			session_nonce: 0,
+			request_id: Secret::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap().into(),
+			inversed_nonce_coeff: Secret::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap().into(),
+			message_hash: H256::default().into(),
+			nodes: Default::default(),
+			key_version: H256::default().into(),
+		}), Err(Error::InvalidMessage));
+	}
+
+	#[test]
+	fn failed_ecdsa_signing_session() {
+		let (_, mut sl) = prepare_signing_sessions(1, 4);
+		sl.master().initialize(777.into()).unwrap();
+
+		// we need at least 3-of-4 nodes to agree to reach consensus
+		// let's say 2 of 4 nodes disagee
+		sl.acl_storages[1].prohibit(sl.requester.public().clone(), SessionId::default());
+		sl.acl_storages[2].prohibit(sl.requester.public().clone(), SessionId::default());
+
+		// then consensus is unreachable
+		assert_eq!(sl.run_until(|_| false), Err(Error::ConsensusUnreachable));
+	}
+
+	#[test]
+	fn complete_ecdsa_signing_session_with_single_node_failing() {
+		let (_, mut sl) = prepare_signing_sessions(1, 4);
+		sl.master().initialize(777.into()).unwrap();
+
+		// we need at least 3-of-4 nodes to agree to reach consensus
+		// let's say 1 of 4 nodes disagee
+		sl.acl_storages[1].prohibit(sl.requester.public().clone(), SessionId::default());
+
+		// then consensus reachable, but single node will disagree
+		while let Some((from, to, message)) = sl.take_message() {
+			sl.process_message((from, to, message)).unwrap();
+		}
+
+		let data = sl.master().data.lock();
+		match data.result {
+			Some(Ok(_)) => (),
+			_ => unreachable!(),
+		}
+	}
+
+	#[test]
+	fn ecdsa_signing_session_fails_when_signing_group_node_is_disconnected() {
+		let (_, mut sl) = prepare_signing_sessions(1, 4);
+		sl.master().initialize(777.into()).unwrap();
+		sl.run_until(|sl| sl.master().state() == SessionState::NoncesGenerating).unwrap();
+
+		// signing group node is disconnected => signature can not be computed
+		let signing_group_node = sl.master().data.lock().signing_group.as_ref().unwrap().keys()
+			.find(|n| **n != sl.master().core.meta.self_node_id)
+			.cloned()
+			.unwrap();
+		sl.master().on_node_timeout(&signing_group_node);
+		assert!(sl.master().is_failed());
+		assert_eq!(sl.master().wait(), Err(Error::ConsensusUnreachable));
+	}
+
+	#[test]
+	fn ecdsa_signing_message_fails_when_nonce_is_wrong() {
+		let (_, sl) = prepare_signing_sessions(1, 3);
+		assert_eq!(sl.master().process_message(sl.nodes.keys().nth(1).unwrap(), &EcdsaSigningMessage::EcdsaSigningZeroShares(EcdsaSigningZeroShares {
+			session: SessionId::default().into(),
+			sub_session: sl.master().core.access_key.clone().into(),
+			session_nonce: 10,
+			inversion_zero_share: Random.generate().unwrap().secret().clone().into(),
+			signature_zero_share: Random.generate().unwrap().secret().clone().into(),
+		})), Err(Error::ReplayProtection));
+	}
+}
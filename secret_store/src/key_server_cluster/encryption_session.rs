@@ -23,8 +23,9 @@ use ethkey::{self, Public, Signature};
 use key_server_cluster::{Error, NodeId, SessionId, KeyStorage, DocumentKeyShare};
 use key_server_cluster::cluster::Cluster;
 use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::math;
 use key_server_cluster::message::{Message, EncryptionMessage, InitializeEncryptionSession,
-	ConfirmEncryptionInitialization, EncryptionSessionError};
+	ConfirmEncryptionInitialization, EncryptionSessionError, RequestRekeyingPublicShare, RekeyingPublicShare, CommitRekeying};
 
 /// Encryption session API.
 pub trait Session: Send + Sync + 'static {
@@ -42,6 +43,11 @@ pub trait Session: Send + Sync + 'static {
 /// 2) master node sends common_point + encrypted_point to all other nodes
 /// 3) common_point + encrypted_point are saved on all nodes
 /// 4) in case of error, previous values are restored
+/// Session could also be used to re-key previously stored document key (i.e. when access to the document is revoked):
+/// 1) master node asks all other nodes to send public portions of their key shares
+/// 2) master node restores server key public from these portions, generates new random document key && encrypts it
+/// 3) new common_point + encrypted_point are saved on all nodes, replacing the previous values
+/// The previous document key is lost => documents must be re-encrypted with the new document key by their owners.
 pub struct SessionImpl {
 	/// Unique session id.
 	id: SessionId,
@@ -84,6 +90,11 @@ struct SessionData {
 	state: SessionState,
 	/// Nodes-specific data.
 	nodes: BTreeMap<NodeId, NodeData>,
+	/// === Values, filled during re-keying ===
+	/// Master node, which has requested re-keying (on slave nodes).
+	rekeying_master: Option<NodeId>,
+	/// Public portions of key shares, received from key holders (on master node).
+	public_shares: BTreeMap<NodeId, Public>,
 	/// Encryption session result.
 	result: Option<Result<(), Error>>,
 }
@@ -105,6 +116,12 @@ pub enum SessionState {
 	/// Master node waits for every other node to confirm initialization.
 	WaitingForInitializationConfirm,
 
+	// === Re-keying states ===
+	/// Master node waits for every other node to send public portion of its key share.
+	WaitingForPublicShares,
+	/// Slave node waits for master node to send new document key.
+	WaitingForRekeying,
+
 	// === Final states of the session ===
 	/// Encryption data is saved.
 	Finished,
@@ -128,6 +145,8 @@ impl SessionImpl {
 			data: Mutex::new(SessionData {
 				state: SessionState::WaitingForInitialization,
 				nodes: BTreeMap::new(),
+				rekeying_master: None,
+				public_shares: BTreeMap::new(),
 				result: None,
 			}),
 		})
@@ -153,6 +172,14 @@ impl SessionImpl {
 			return Err(Error::AccessDenied);
 		}
 
+		// check that common_point and encrypted_point are still not set yet
+		check_encrypted_data_is_not_stored(&self.encrypted_data)?;
+
+		// check that key share has not been modified since session has been created
+		let actual_encrypted_data = self.key_storage.get(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		check_encrypted_data_is_actual(&self.encrypted_data, &actual_encrypted_data)?;
+
 		// update state
 		data.state = SessionState::WaitingForInitializationConfirm;
 		for node_id in self.encrypted_data.id_numbers.keys() {
@@ -208,6 +235,9 @@ impl SessionImpl {
 			return Err(Error::AccessDenied);
 		}
 
+		// check that common_point and encrypted_point are still not set yet
+		check_encrypted_data_is_not_stored(&self.encrypted_data)?;
+
 		// save encryption data
 		let mut encrypted_data = self.encrypted_data.clone();
 		encrypted_data.common_point = Some(message.common_point.clone().into());
@@ -225,6 +255,129 @@ impl SessionImpl {
 		})))
 	}
 
+	/// Start re-keying of previously stored document key. This must be called on master node.
+	pub fn initialize_rekeying(&self) -> Result<(), Error> {
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		// check that document key has been stored && key share has not been modified since session has been created
+		check_encrypted_data_is_stored(&self.encrypted_data)?;
+		let actual_encrypted_data = self.key_storage.get(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		check_encrypted_data_is_actual(&self.encrypted_data, &actual_encrypted_data)?;
+
+		// update state
+		data.state = SessionState::WaitingForPublicShares;
+		for node_id in self.encrypted_data.id_numbers.keys() {
+			data.nodes.insert(node_id.clone(), NodeData {
+				initialization_confirmed: node_id == self.node(),
+			});
+		}
+		let public_share = math::compute_public_share(&self.encrypted_data.secret_share)?;
+		data.public_shares.insert(self.node().clone(), public_share);
+
+		// ask other nodes for public portions of their key shares
+		if self.encrypted_data.id_numbers.len() > 1 {
+			self.cluster.broadcast(Message::Encryption(EncryptionMessage::RequestRekeyingPublicShare(RequestRekeyingPublicShare {
+				session: self.id.clone().into(),
+				session_nonce: self.nonce,
+			})))
+		} else {
+			self.try_commit_rekeying(&mut data)
+		}
+	}
+
+	/// When public portion of key share is requested by re-keying master node.
+	pub fn on_request_rekeying_public_share(&self, sender: NodeId, message: &RequestRekeyingPublicShare) -> Result<(), Error> {
+		debug_assert!(self.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		// only stored document key could be re-keyed
+		check_encrypted_data_is_stored(&self.encrypted_data)?;
+
+		// update state
+		data.state = SessionState::WaitingForRekeying;
+		data.rekeying_master = Some(sender.clone());
+
+		// send public portion of key share back to master node
+		self.cluster.send(&sender, Message::Encryption(EncryptionMessage::RekeyingPublicShare(RekeyingPublicShare {
+			session: self.id.clone().into(),
+			session_nonce: self.nonce,
+			public_share: math::compute_public_share(&self.encrypted_data.secret_share)?.into(),
+		})))
+	}
+
+	/// When public portion of key share is received from other node.
+	pub fn on_rekeying_public_share(&self, sender: NodeId, message: &RekeyingPublicShare) -> Result<(), Error> {
+		debug_assert!(self.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForPublicShares {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !self.encrypted_data.id_numbers.contains_key(&sender) || data.public_shares.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.public_shares.insert(sender, message.public_share.clone().into());
+		self.try_commit_rekeying(&mut data)
+	}
+
+	/// When new document key is received from re-keying master node.
+	pub fn on_commit_rekeying(&self, sender: NodeId, message: &CommitRekeying) -> Result<(), Error> {
+		debug_assert!(self.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForRekeying {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if data.rekeying_master.as_ref() != Some(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		// check that key share has not been modified since session has been created && save new document key
+		let actual_encrypted_data = self.key_storage.get(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		check_encrypted_data_is_actual(&self.encrypted_data, &actual_encrypted_data)?;
+		let mut encrypted_data = actual_encrypted_data;
+		encrypted_data.common_point = Some(message.common_point.clone().into());
+		encrypted_data.encrypted_point = Some(message.encrypted_point.clone().into());
+		self.key_storage.update(self.id.clone(), encrypted_data)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+
+		// update state
+		data.state = SessionState::Finished;
+
+		// send confirmation back to master node
+		self.cluster.send(&sender, Message::Encryption(EncryptionMessage::ConfirmEncryptionInitialization(ConfirmEncryptionInitialization {
+			session: self.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
 	/// When session initialization confirmation message is reeived.
 	pub fn on_confirm_initialization(&self, sender: NodeId, message: &ConfirmEncryptionInitialization) -> Result<(), Error> {
 		debug_assert!(self.id == *message.session);
@@ -260,12 +413,69 @@ impl SessionImpl {
 		warn!("{}: encryption session failed with error: {} from {}", self.node(), message.error, sender);
 
 		data.state = SessionState::Failed;
-		data.result = Some(Err(Error::Io(message.error.clone())));
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
 		self.completed.notify_all();
 
 		Ok(())
 	}
 
+	/// Generate new document key && ask every node to save it, if public portions of key shares from all nodes are received.
+	fn try_commit_rekeying(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.public_shares.len() != self.encrypted_data.id_numbers.len() {
+			return Ok(());
+		}
+
+		// restore server key public && encrypt new random document key with it
+		let server_key_public = self.compute_server_key_public(&data.public_shares)?;
+		let document_key = math::generate_random_point()?;
+		let encrypted_document_key = math::encrypt_secret(&document_key, &server_key_public)?;
+
+		// check that key share has not been modified since session has been created && save new document key
+		let actual_encrypted_data = self.key_storage.get(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		check_encrypted_data_is_actual(&self.encrypted_data, &actual_encrypted_data)?;
+		let mut encrypted_data = actual_encrypted_data;
+		encrypted_data.common_point = Some(encrypted_document_key.common_point.clone());
+		encrypted_data.encrypted_point = Some(encrypted_document_key.encrypted_point.clone());
+		self.key_storage.update(self.id.clone(), encrypted_data)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+
+		if self.encrypted_data.id_numbers.len() == 1 {
+			data.state = SessionState::Finished;
+			data.result = Some(Ok(()));
+			self.completed.notify_all();
+			return Ok(());
+		}
+
+		data.state = SessionState::WaitingForInitializationConfirm;
+		for node in self.encrypted_data.id_numbers.keys().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::Encryption(EncryptionMessage::CommitRekeying(CommitRekeying {
+				session: self.id.clone().into(),
+				session_nonce: self.nonce,
+				common_point: encrypted_document_key.common_point.clone().into(),
+				encrypted_point: encrypted_document_key.encrypted_point.clone().into(),
+			})))?;
+		}
+
+		Ok(())
+	}
+
+	/// Restore server key public from public portions of key shares of all key holders.
+	fn compute_server_key_public(&self, public_shares: &BTreeMap<NodeId, Public>) -> Result<Public, Error> {
+		let id_numbers: Vec<_> = public_shares.keys().map(|n| &self.encrypted_data.id_numbers[n]).collect();
+		let public_shares: Vec<_> = public_shares.values().collect();
+		let server_key_public = math::compute_joint_public_from_shares(&public_shares, &id_numbers)?;
+
+		// any threshold + 1 shares must lead to the same public => otherwise some node has sent wrong share
+		let threshold = self.encrypted_data.threshold;
+		if public_shares.len() > threshold + 1
+			&& math::compute_joint_public_from_shares(&public_shares[..threshold + 1], &id_numbers[..threshold + 1])? != server_key_public {
+			return Err(Error::InvalidMessage);
+		}
+
+		Ok(server_key_public)
+	}
+
 	/// Check session nonce.
 	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
 		match self.nonce == message_session_nonce {
@@ -282,6 +492,10 @@ impl ClusterSession for SessionImpl {
 			|| data.state == SessionState::Finished
 	}
 
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
 	fn on_node_timeout(&self, node: &NodeId) {
 		let mut data = self.data.lock();
 
@@ -301,6 +515,41 @@ impl ClusterSession for SessionImpl {
 		data.result = Some(Err(Error::NodeDisconnected));
 		self.completed.notify_all();
 	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: encryption session has been cancelled", self.node());
+
+		// master node saves encryption data before initialization is confirmed by other nodes
+		// => restore previous values (session encrypted data could miss id_numbers of disconnected nodes => do not store it as is)
+		if data.state == SessionState::WaitingForInitializationConfirm {
+			let restore_result = self.key_storage.get(&self.id)
+				.and_then(|mut encrypted_data| {
+					encrypted_data.common_point = self.encrypted_data.common_point.clone();
+					encrypted_data.encrypted_point = self.encrypted_data.encrypted_point.clone();
+					self.key_storage.update(self.id.clone(), encrypted_data)
+				});
+			if let Err(err) = restore_result {
+				warn!("{}: failed to restore key share of cancelled encryption session: {}", self.node(), err);
+			}
+		}
+
+		// do not bother processing send error, as we already processing error
+		let _ = self.cluster.broadcast(Message::Encryption(EncryptionMessage::EncryptionSessionError(EncryptionSessionError {
+			session: self.id.clone().into(),
+			session_nonce: self.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		})));
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
 }
 
 impl Session for SessionImpl {
@@ -332,12 +581,206 @@ impl Debug for SessionImpl {
 fn check_encrypted_data(self_node_id: &Public, encrypted_data: &DocumentKeyShare) -> Result<(), Error> {
 	use key_server_cluster::generation_session::{check_cluster_nodes, check_threshold};
 
-	// check that common_point and encrypted_point are still not set yet
+	let nodes = encrypted_data.id_numbers.keys().cloned().collect();
+	check_cluster_nodes(self_node_id, &nodes)?;
+	check_threshold(encrypted_data.threshold, &nodes)
+}
+
+/// Check that common_point and encrypted_point are not set yet.
+fn check_encrypted_data_is_not_stored(encrypted_data: &DocumentKeyShare) -> Result<(), Error> {
 	if encrypted_data.common_point.is_some() || encrypted_data.encrypted_point.is_some() {
 		return Err(Error::CompletedSessionId);
 	}
 
-	let nodes = encrypted_data.id_numbers.keys().cloned().collect();
-	check_cluster_nodes(self_node_id, &nodes)?;
-	check_threshold(encrypted_data.threshold, &nodes)
+	Ok(())
+}
+
+/// Check that common_point and encrypted_point are already set.
+fn check_encrypted_data_is_stored(encrypted_data: &DocumentKeyShare) -> Result<(), Error> {
+	if encrypted_data.common_point.is_none() || encrypted_data.encrypted_point.is_none() {
+		return Err(Error::NotStartedSessionId);
+	}
+
+	Ok(())
+}
+
+/// Check that encrypted data, which session has been created with, is the same as currently stored one.
+/// Captured encrypted data could miss id_numbers of nodes, which have been disconnected when session has been created.
+fn check_encrypted_data_is_actual(encrypted_data: &DocumentKeyShare, actual_encrypted_data: &DocumentKeyShare) -> Result<(), Error> {
+	if encrypted_data.author != actual_encrypted_data.author
+		|| encrypted_data.threshold != actual_encrypted_data.threshold
+		|| encrypted_data.secret_share != actual_encrypted_data.secret_share
+		|| encrypted_data.common_point != actual_encrypted_data.common_point
+		|| encrypted_data.encrypted_point != actual_encrypted_data.encrypted_point
+		|| encrypted_data.id_numbers.iter().any(|(node, id_number)| actual_encrypted_data.id_numbers.get(node) != Some(id_number)) {
+		return Err(Error::StaleKeyShare);
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use ethkey::{self, Random, Generator};
+	use key_server_cluster::{Error, SessionId, KeyStorage, DocumentKeyShare, KeyMetadata, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::cluster_sessions::ClusterSession;
+	use key_server_cluster::math;
+	use super::{SessionImpl, SessionParams, SessionState, Session};
+
+	fn prepare_session(nodes_num: usize) -> (SessionImpl, Arc<DummyKeyStorage>, ethkey::KeyPair) {
+		let id = SessionId::from(42);
+		let self_node_id = Random.generate().unwrap().public().clone();
+		let requestor = Random.generate().unwrap();
+		let key_storage = Arc::new(DummyKeyStorage::default());
+		let encrypted_data = DocumentKeyShare {
+			author: requestor.public().clone(),
+			threshold: 0,
+			id_numbers: ::std::iter::once(self_node_id.clone())
+				.chain((1..nodes_num).map(|_| Random.generate().unwrap().public().clone()))
+				.map(|n| (n, math::generate_random_scalar().unwrap()))
+				.collect(),
+			secret_share: math::generate_random_scalar().unwrap(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+		key_storage.insert(id.clone(), encrypted_data.clone()).unwrap();
+
+		let session = SessionImpl::new(SessionParams {
+			id: id,
+			self_node_id: self_node_id.clone(),
+			encrypted_data: encrypted_data,
+			key_storage: key_storage.clone(),
+			cluster: Arc::new(DummyCluster::new(self_node_id)),
+			nonce: 0,
+		}).unwrap();
+		(session, key_storage, requestor)
+	}
+
+	#[test]
+	fn encryption_session_completes_on_single_node() {
+		let (session, key_storage, requestor) = prepare_session(1);
+		let signature = ethkey::sign(requestor.secret(), &SessionId::from(42)).unwrap();
+		let common_point = math::generate_random_point().unwrap();
+		let encrypted_point = math::generate_random_point().unwrap();
+		session.initialize(signature, common_point.clone(), encrypted_point.clone()).unwrap();
+		assert_eq!(session.state(), SessionState::Finished);
+		assert_eq!(key_storage.get(&SessionId::from(42)).unwrap().common_point, Some(common_point));
+	}
+
+	#[test]
+	fn encryption_session_fails_to_initialize_if_key_share_is_stale() {
+		let (session, key_storage, requestor) = prepare_session(1);
+
+		// key share is modified after session has been created
+		let mut encrypted_data = key_storage.get(&SessionId::from(42)).unwrap();
+		encrypted_data.common_point = Some(math::generate_random_point().unwrap());
+		encrypted_data.encrypted_point = Some(math::generate_random_point().unwrap());
+		key_storage.update(SessionId::from(42), encrypted_data.clone()).unwrap();
+
+		let signature = ethkey::sign(requestor.secret(), &SessionId::from(42)).unwrap();
+		assert_eq!(session.initialize(signature, math::generate_random_point().unwrap(), math::generate_random_point().unwrap()),
+			Err(Error::StaleKeyShare));
+		assert_eq!(session.state(), SessionState::WaitingForInitialization);
+		assert_eq!(key_storage.get(&SessionId::from(42)).unwrap(), encrypted_data);
+	}
+
+	#[test]
+	fn encryption_session_ignores_key_metadata_changes() {
+		let (session, key_storage, requestor) = prepare_session(1);
+
+		// key metadata is modified after session has been created
+		key_storage.set_metadata(&SessionId::from(42), KeyMetadata {
+			label: Some("label".into()),
+			last_accessed: Some(100),
+			..Default::default()
+		}).unwrap();
+
+		let signature = ethkey::sign(requestor.secret(), &SessionId::from(42)).unwrap();
+		session.initialize(signature, math::generate_random_point().unwrap(), math::generate_random_point().unwrap()).unwrap();
+		assert_eq!(session.state(), SessionState::Finished);
+	}
+
+	#[test]
+	fn encryption_session_restores_key_share_when_cancelled() {
+		let (session, key_storage, requestor) = prepare_session(3);
+		let encrypted_data = key_storage.get(&SessionId::from(42)).unwrap();
+
+		let signature = ethkey::sign(requestor.secret(), &SessionId::from(42)).unwrap();
+		session.initialize(signature, math::generate_random_point().unwrap(), math::generate_random_point().unwrap()).unwrap();
+		assert_eq!(session.state(), SessionState::WaitingForInitializationConfirm);
+		assert!(key_storage.get(&SessionId::from(42)).unwrap().common_point.is_some());
+
+		session.cancel();
+		assert_eq!(session.state(), SessionState::Failed);
+		assert_eq!(session.wait(None), Err(Error::SessionCancelled));
+		assert_eq!(key_storage.get(&SessionId::from(42)).unwrap(), encrypted_data);
+	}
+
+	#[test]
+	fn rekeying_session_completes_on_single_node() {
+		let (_, key_storage, _) = prepare_session(1);
+
+		// document key is stored
+		let mut encrypted_data = key_storage.get(&SessionId::from(42)).unwrap();
+		let server_key_public = math::compute_public_share(&encrypted_data.secret_share).unwrap();
+		let document_key = math::generate_random_point().unwrap();
+		let encrypted_document_key = math::encrypt_secret(&document_key, &server_key_public).unwrap();
+		encrypted_data.common_point = Some(encrypted_document_key.common_point.clone());
+		encrypted_data.encrypted_point = Some(encrypted_document_key.encrypted_point.clone());
+		key_storage.update(SessionId::from(42), encrypted_data.clone()).unwrap();
+
+		let self_node_id = encrypted_data.id_numbers.keys().next().unwrap().clone();
+		let session = SessionImpl::new(SessionParams {
+			id: SessionId::from(42),
+			self_node_id: self_node_id.clone(),
+			encrypted_data: encrypted_data.clone(),
+			key_storage: key_storage.clone(),
+			cluster: Arc::new(DummyCluster::new(self_node_id)),
+			nonce: 0,
+		}).unwrap();
+		session.initialize_rekeying().unwrap();
+		assert_eq!(session.state(), SessionState::Finished);
+
+		// new document key is encrypted with the same server key
+		let rekeyed_data = key_storage.get(&SessionId::from(42)).unwrap();
+		assert!(rekeyed_data.common_point != encrypted_data.common_point);
+		assert!(rekeyed_data.encrypted_point != encrypted_data.encrypted_point);
+		let rekeyed_document_key = math::decrypt_with_joint_secret(rekeyed_data.encrypted_point.as_ref().unwrap(),
+			rekeyed_data.common_point.as_ref().unwrap(), &encrypted_data.secret_share).unwrap();
+		assert!(rekeyed_document_key != document_key);
+	}
+
+	#[test]
+	fn rekeying_session_fails_to_initialize_if_document_key_is_not_stored() {
+		let (session, _, _) = prepare_session(1);
+		assert_eq!(session.initialize_rekeying(), Err(Error::NotStartedSessionId));
+		assert_eq!(session.state(), SessionState::WaitingForInitialization);
+	}
+
+	#[test]
+	fn encryption_session_fails_to_initialize_if_document_key_is_already_stored() {
+		let (session, key_storage, requestor) = prepare_session(1);
+		let mut encrypted_data = key_storage.get(&SessionId::from(42)).unwrap();
+		encrypted_data.common_point = Some(math::generate_random_point().unwrap());
+		encrypted_data.encrypted_point = Some(math::generate_random_point().unwrap());
+		key_storage.update(SessionId::from(42), encrypted_data.clone()).unwrap();
+		drop(session);
+
+		let self_node_id = encrypted_data.id_numbers.keys().next().unwrap().clone();
+		let session = SessionImpl::new(SessionParams {
+			id: SessionId::from(42),
+			self_node_id: self_node_id.clone(),
+			encrypted_data: encrypted_data,
+			key_storage: key_storage.clone(),
+			cluster: Arc::new(DummyCluster::new(self_node_id)),
+			nonce: 0,
+		}).unwrap();
+		let signature = ethkey::sign(requestor.secret(), &SessionId::from(42)).unwrap();
+		assert_eq!(session.initialize(signature, math::generate_random_point().unwrap(), math::generate_random_point().unwrap()),
+			Err(Error::CompletedSessionId));
+	}
 }
@@ -0,0 +1,176 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Injection of faults into cluster messages, received by this node. Is only available with `fault-injection` feature
+//! && is intended to reproduce races && failures, which are hard to get on real network.
+
+use std::time;
+use std::collections::BTreeMap;
+use parking_lot::{Mutex, RwLock};
+use key_server_cluster::NodeId;
+use key_server_cluster::message::Message;
+
+/// Fault, injected into matching message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FaultAction {
+	/// Message is dropped.
+	Drop,
+	/// Message is processed after given delay.
+	Delay(time::Duration),
+	/// Message is processed twice.
+	Duplicate,
+	/// Message is held back && processed right after the next message from the same node.
+	Reorder,
+}
+
+/// Rule of fault injection policy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultRule {
+	/// Sender of matching messages. None if messages from all nodes are matching.
+	pub peer: Option<NodeId>,
+	/// Prefix of matching messages names (like "ShareMove." or "ShareMove.ShareMoveData").
+	pub message: String,
+	/// Fault to inject.
+	pub action: FaultAction,
+}
+
+/// Faults injector. First matching rule of the policy is applied to every received message.
+#[derive(Default)]
+pub struct FaultInjector {
+	/// Current policy.
+	rules: RwLock<Vec<FaultRule>>,
+	/// Messages, held back by reorder rules, by sender.
+	held_messages: Mutex<BTreeMap<NodeId, Message>>,
+}
+
+impl FaultRule {
+	/// Check if rule matches message from given node.
+	pub fn matches(&self, sender: &NodeId, message: &Message) -> bool {
+		self.peer.as_ref().map(|peer| peer == sender).unwrap_or(true)
+			&& message.to_string().starts_with(&self.message)
+	}
+}
+
+impl FaultInjector {
+	/// Replace current policy. Held messages are released with the next message from the same node.
+	pub fn set_rules(&self, rules: Vec<FaultRule>) {
+		*self.rules.write() = rules;
+	}
+
+	/// Get current policy.
+	pub fn rules(&self) -> Vec<FaultRule> {
+		self.rules.read().clone()
+	}
+
+	/// Apply policy to the message, received from given node. Returns messages to process (in given order),
+	/// each with optional processing delay.
+	pub fn on_message_received(&self, sender: &NodeId, message: Message) -> Vec<(Option<time::Duration>, Message)> {
+		let action = self.rules.read().iter()
+			.find(|rule| rule.matches(sender, &message))
+			.map(|rule| rule.action.clone());
+		if let Some(ref action) = action {
+			warn!(target: "secretstore_net", "injecting fault {:?} into message {} from {}", action, message, sender);
+		}
+
+		let mut held_messages = self.held_messages.lock();
+		let mut messages = match action {
+			None => vec![(None, message)],
+			Some(FaultAction::Drop) => Vec::new(),
+			Some(FaultAction::Delay(delay)) => vec![(Some(delay), message)],
+			Some(FaultAction::Duplicate) => vec![(None, message.clone()), (None, message)],
+			Some(FaultAction::Reorder) if held_messages.contains_key(sender) => vec![(None, message)],
+			Some(FaultAction::Reorder) => {
+				held_messages.insert(sender.clone(), message);
+				return Vec::new();
+			},
+		};
+
+		// held message is released right after the next message from the same node
+		messages.extend(held_messages.remove(sender).map(|message| (None, message)));
+		messages
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time;
+	use ethkey::{Random, Generator};
+	use key_server_cluster::message::{self, Message, ClusterMessage};
+	use super::{FaultInjector, FaultRule, FaultAction};
+
+	fn keep_alive(timestamp: u64) -> Message {
+		Message::Cluster(ClusterMessage::KeepAliveResponse(message::KeepAliveResponse {
+			timestamp: Some(timestamp),
+		}))
+	}
+
+	fn timestamps(messages: Vec<(Option<time::Duration>, Message)>) -> Vec<(Option<time::Duration>, u64)> {
+		messages.into_iter().map(|(delay, message)| match message {
+			Message::Cluster(ClusterMessage::KeepAliveResponse(message)) => (delay, message.timestamp.unwrap()),
+			_ => unreachable!("only keep alive responses are used in tests"),
+		}).collect()
+	}
+
+	#[test]
+	fn messages_are_processed_when_no_rules_match() {
+		let injector = FaultInjector::default();
+		let sender = Random.generate().unwrap().public().clone();
+		injector.set_rules(vec![FaultRule {
+			peer: None,
+			message: "ShareMove.".into(),
+			action: FaultAction::Drop,
+		}]);
+		assert_eq!(timestamps(injector.on_message_received(&sender, keep_alive(1))), vec![(None, 1)]);
+	}
+
+	#[test]
+	fn faults_are_injected_into_messages_from_given_peer() {
+		let injector = FaultInjector::default();
+		let sender = Random.generate().unwrap().public().clone();
+		let other_sender = Random.generate().unwrap().public().clone();
+		let delay = time::Duration::from_millis(100);
+		for action in vec![FaultAction::Drop, FaultAction::Delay(delay), FaultAction::Duplicate] {
+			injector.set_rules(vec![FaultRule {
+				peer: Some(sender.clone()),
+				message: "Cluster.KeepAliveResponse".into(),
+				action: action,
+			}]);
+			assert_eq!(timestamps(injector.on_message_received(&other_sender, keep_alive(1))), vec![(None, 1)]);
+		}
+
+		injector.set_rules(vec![FaultRule { peer: Some(sender.clone()), message: "Cluster.".into(), action: FaultAction::Drop }]);
+		assert_eq!(timestamps(injector.on_message_received(&sender, keep_alive(1))), vec![]);
+		injector.set_rules(vec![FaultRule { peer: Some(sender.clone()), message: "Cluster.".into(), action: FaultAction::Delay(delay) }]);
+		assert_eq!(timestamps(injector.on_message_received(&sender, keep_alive(1))), vec![(Some(delay), 1)]);
+		injector.set_rules(vec![FaultRule { peer: Some(sender.clone()), message: "Cluster.".into(), action: FaultAction::Duplicate }]);
+		assert_eq!(timestamps(injector.on_message_received(&sender, keep_alive(1))), vec![(None, 1), (None, 1)]);
+	}
+
+	#[test]
+	fn reordered_message_is_processed_after_next_message() {
+		let injector = FaultInjector::default();
+		let sender = Random.generate().unwrap().public().clone();
+		let other_sender = Random.generate().unwrap().public().clone();
+		injector.set_rules(vec![FaultRule { peer: None, message: "Cluster.".into(), action: FaultAction::Reorder }]);
+		assert_eq!(timestamps(injector.on_message_received(&sender, keep_alive(1))), vec![]);
+		assert_eq!(timestamps(injector.on_message_received(&other_sender, keep_alive(2))), vec![]);
+		assert_eq!(timestamps(injector.on_message_received(&sender, keep_alive(3))), vec![(None, 3), (None, 1)]);
+
+		injector.set_rules(Vec::new());
+		assert_eq!(timestamps(injector.on_message_received(&other_sender, keep_alive(4))), vec![(None, 4), (None, 2)]);
+		assert_eq!(timestamps(injector.on_message_received(&other_sender, keep_alive(5))), vec![(None, 5)]);
+	}
+}
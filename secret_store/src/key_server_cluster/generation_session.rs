@@ -20,10 +20,10 @@ use std::time;
 use std::sync::Arc;
 use parking_lot::{Condvar, Mutex};
 use ethkey::{Public, Secret};
-use key_server_cluster::{Error, NodeId, SessionId, KeyStorage, DocumentKeyShare};
+use key_server_cluster::{Error, NodeId, SessionId, KeyStorage, DocumentKeyShare, KeyMetadata};
 use key_server_cluster::math;
 use key_server_cluster::cluster::Cluster;
-use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::cluster_sessions::{ClusterSession, SessionEvent, SessionEventListener, SessionEventListeners};
 use key_server_cluster::message::{Message, GenerationMessage, InitializeSession, ConfirmInitialization, CompleteInitialization,
 	KeysDissemination, PublicKeyShare, SessionError, SessionCompleted};
 
@@ -58,6 +58,8 @@ pub struct SessionImpl {
 	nonce: u64,
 	/// SessionImpl completion condvar.
 	completed: Condvar,
+	/// Session events listeners.
+	listeners: SessionEventListeners,
 	/// Mutable session data.
 	data: Mutex<SessionData>,
 }
@@ -195,6 +197,7 @@ impl SessionImpl {
 			// => nonce is checked somewhere else && we can pass any value
 			nonce: params.nonce.unwrap_or_default(),
 			completed: Condvar::new(),
+			listeners: SessionEventListeners::default(),
 			data: Mutex::new(SessionData {
 				state: SessionState::WaitingForInitialization,
 				simulate_faulty_behaviour: false,
@@ -227,8 +230,28 @@ impl SessionImpl {
 		self.data.lock().simulate_faulty_behaviour = true;
 	}
 
+	/// Register session events listener.
+	pub fn add_listener(&self, listener: Arc<SessionEventListener>) {
+		self.listeners.add(listener);
+	}
+
+	/// Get key share (if it is generated).
+	pub fn key_share(&self) -> Option<Result<DocumentKeyShare, Error>> {
+		self.data.lock().key_share.clone()
+	}
+
 	/// Start new session initialization. This must be called on master node.
 	pub fn initialize(&self, author: Public, threshold: usize, nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		// generate nodes identification parameters
+		let id_numbers = nodes.into_iter()
+			.map(|node_id| math::generate_random_scalar().map(|node_id_number| (node_id, node_id_number)))
+			.collect::<Result<BTreeMap<_, _>, _>>()?;
+		self.initialize_with_id_numbers(author, threshold, id_numbers)
+	}
+
+	/// Start new session initialization, using given nodes identification parameters. This must be called on master node.
+	pub fn initialize_with_id_numbers(&self, author: Public, threshold: usize, id_numbers: BTreeMap<NodeId, Secret>) -> Result<(), Error> {
+		let nodes = id_numbers.keys().cloned().collect();
 		check_cluster_nodes(self.node(), &nodes)?;
 		check_threshold(threshold, &nodes)?;
 
@@ -243,11 +266,10 @@ impl SessionImpl {
 		data.master = Some(self.node().clone());
 		data.author = Some(author.clone());
 		data.threshold = Some(threshold);
-		for node_id in &nodes {
-			// generate node identification parameter
-			let node_id_number = math::generate_random_scalar()?;
-			data.nodes.insert(node_id.clone(), NodeData::with_id_number(node_id_number));
+		for (node_id, node_id_number) in id_numbers {
+			data.nodes.insert(node_id, NodeData::with_id_number(node_id_number));
 		}
+		self.listeners.notify(&self.id, SessionEvent::Initialized);
 
 		let mut visit_policy = EveryOtherNodeVisitor::new(self.node(), data.nodes.keys().cloned());
 		let derived_point = math::generate_random_point()?;
@@ -333,6 +355,7 @@ impl SessionImpl {
 		data.state = SessionState::WaitingForInitializationComplete;
 		data.nodes = message.nodes.iter().map(|(id, number)| (id.clone().into(), NodeData::with_id_number(number.clone().into()))).collect();
 		data.threshold = Some(message.threshold);
+		self.listeners.notify(&self.id, SessionEvent::Initialized);
 
 		Ok(())
 	}
@@ -392,6 +415,7 @@ impl SessionImpl {
 
 		// remember passed data
 		data.derived_point = Some(message.derived_point.clone().into());
+		self.listeners.notify(&self.id, SessionEvent::Confirmed);
 
 		// now it is time for keys dissemination (KD) phase
 		drop(data);
@@ -509,6 +533,8 @@ impl SessionImpl {
 				secret_share: data.secret_share.as_ref().expect("secret_share is filled in KG phase; we are at the end of KG phase; qed").clone(),
 				common_point: None,
 				encrypted_point: None,
+				refresh_id: None,
+				metadata: KeyMetadata::generated(data.author.as_ref().expect("author is filled in initialization phase; KG phase follows initialization phase; qed").clone()),
 			};
 			
 			if let Some(ref key_storage) = self.key_storage {
@@ -518,6 +544,8 @@ impl SessionImpl {
 
 			// then respond with confirmation
 			data.state = SessionState::Finished;
+			data.clear_secrets();
+			self.listeners.notify(&self.id, SessionEvent::Finished);
 			return self.cluster.send(&sender, Message::Generation(GenerationMessage::SessionCompleted(SessionCompleted {
 				session: self.id.clone().into(),
 				session_nonce: self.nonce,
@@ -541,6 +569,8 @@ impl SessionImpl {
 
 		// we have received enough confirmations => complete session
 		data.state = SessionState::Finished;
+		data.clear_secrets();
+		self.listeners.notify(&self.id, SessionEvent::Finished);
 		self.completed.notify_all();
 
 		Ok(())
@@ -553,8 +583,11 @@ impl SessionImpl {
 		warn!("{}: generation session failed with error: {} from {}", self.node(), message.error, sender);
 
 		data.state = SessionState::Failed;
-		data.key_share = Some(Err(Error::Io(message.error.clone())));
-		data.joint_public_and_secret = Some(Err(Error::Io(message.error.clone())));
+		data.clear_secrets();
+		let error = Error::remote(message.code, message.error.clone());
+		data.key_share = Some(Err(error.clone()));
+		data.joint_public_and_secret = Some(Err(error.clone()));
+		self.listeners.notify(&self.id, SessionEvent::Failed(error));
 		self.completed.notify_all();
 
 		Ok(())
@@ -574,7 +607,10 @@ impl SessionImpl {
 			session: self.id.clone().into(),
 			session_nonce: self.nonce,
 			derived_point: derived_point.into(),
-		})))
+		})))?;
+		self.listeners.notify(&self.id, SessionEvent::Confirmed);
+
+		Ok(())
 	}
 
 	/// Keys dissemination (KD) phase
@@ -689,6 +725,8 @@ impl SessionImpl {
 			secret_share: data.secret_share.as_ref().expect("secret_share is filled in KG phase; we are at the end of KG phase; qed").clone(),
 			common_point: None,
 			encrypted_point: None,
+			refresh_id: None,
+			metadata: KeyMetadata::generated(data.author.as_ref().expect("author is filled in initialization phase; KG phase follows initialization phase; qed").clone()),
 		};
 
 		// if we are at the slave node - wait for session completion
@@ -725,6 +763,19 @@ impl SessionImpl {
 	}
 }
 
+impl SessionData {
+	/// Forget intermediate secret values, which are not required once the session is completed.
+	fn clear_secrets(&mut self) {
+		self.secret_coeff = None;
+		for node_data in self.nodes.values_mut() {
+			node_data.secret1_sent = None;
+			node_data.secret2_sent = None;
+			node_data.secret1 = None;
+			node_data.secret2 = None;
+		}
+	}
+}
+
 impl ClusterSession for SessionImpl {
 	fn is_finished(&self) -> bool {
 		let data = self.data.lock();
@@ -732,6 +783,10 @@ impl ClusterSession for SessionImpl {
 			|| data.state == SessionState::Finished
 	}
 
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
 	fn on_node_timeout(&self, node: &NodeId) {
 		let mut data = self.data.lock();
 
@@ -740,8 +795,10 @@ impl ClusterSession for SessionImpl {
 		warn!("{}: generation session failed because {} connection has timeouted", self.node(), node);
 
 		data.state = SessionState::Failed;
+		data.clear_secrets();
 		data.key_share = Some(Err(Error::NodeDisconnected));
 		data.joint_public_and_secret = Some(Err(Error::NodeDisconnected));
+		self.listeners.notify(&self.id, SessionEvent::Failed(Error::NodeDisconnected));
 		self.completed.notify_all();
 	}
 
@@ -751,8 +808,44 @@ impl ClusterSession for SessionImpl {
 		warn!("{}: generation session failed with timeout", self.node());
 
 		data.state = SessionState::Failed;
+		data.clear_secrets();
 		data.key_share = Some(Err(Error::NodeDisconnected));
 		data.joint_public_and_secret = Some(Err(Error::NodeDisconnected));
+		self.listeners.notify(&self.id, SessionEvent::Failed(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: generation session has been cancelled", self.node());
+
+		// master node saves key share before generation is confirmed by other nodes
+		// => remove it to restore key storage state
+		if data.state == SessionState::WaitingForGenerationConfirmation && data.master.as_ref() == Some(self.node()) {
+			if let Some(ref key_storage) = self.key_storage {
+				if let Err(err) = key_storage.remove(&self.id) {
+					warn!("{}: failed to remove key share of cancelled generation session: {}", self.node(), err);
+				}
+			}
+		}
+
+		// do not bother processing send error, as we already processing error
+		let _ = self.cluster.broadcast(Message::Generation(GenerationMessage::SessionError(SessionError {
+			session: self.id.clone().into(),
+			session_nonce: self.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		})));
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.key_share = Some(Err(Error::SessionCancelled));
+		data.joint_public_and_secret = Some(Err(Error::SessionCancelled));
+		self.listeners.notify(&self.id, SessionEvent::Failed(Error::SessionCancelled));
 		self.completed.notify_all();
 	}
 }
@@ -856,10 +949,11 @@ pub mod tests {
 	use std::collections::{BTreeSet, BTreeMap, VecDeque};
 	use tokio_core::reactor::Core;
 	use ethkey::{Random, Generator, Public};
-	use key_server_cluster::{NodeId, SessionId, Error, DummyKeyStorage};
+	use key_server_cluster::{NodeId, SessionId, Error, KeyStorage, DummyKeyStorage};
 	use key_server_cluster::message::{self, Message, GenerationMessage};
 	use key_server_cluster::cluster::tests::{DummyCluster, make_clusters, run_clusters, loop_until, all_connections_established};
-	use key_server_cluster::cluster_sessions::ClusterSession;
+	use key_server_cluster::cluster_sessions::{ClusterSession, SessionEvent};
+	use key_server_cluster::cluster_sessions::tests::DummySessionEventListener;
 	use key_server_cluster::generation_session::{Session, SessionImpl, SessionState, SessionParams};
 	use key_server_cluster::math;
 	use key_server_cluster::math::tests::do_encryption_and_decryption;
@@ -1229,6 +1323,69 @@ pub mod tests {
 		assert!(l.master().joint_public_and_secret().unwrap().unwrap_err() == Error::NodeDisconnected);
 	}
 
+	#[test]
+	fn master_removes_key_share_when_cancelled_before_generation_is_confirmed() {
+		let mut l = MessageLoop::new(3);
+		l.master().initialize(Public::default(), 1, l.nodes.keys().cloned().collect()).unwrap();
+		while l.master().state() != SessionState::WaitingForGenerationConfirmation {
+			l.take_and_process_message().unwrap();
+		}
+
+		let master_key_storage = l.nodes.values().nth(0).unwrap().key_storage.clone();
+		assert!(master_key_storage.contains(&l.session_id));
+
+		l.master().cancel();
+		assert_eq!(l.master().state(), SessionState::Failed);
+		assert_eq!(l.master().joint_public_and_secret(), Some(Err(Error::SessionCancelled)));
+		assert!(!master_key_storage.contains(&l.session_id));
+	}
+
+	#[test]
+	fn session_events_are_reported_to_listeners() {
+		let mut l = MessageLoop::new(3);
+		let listeners: Vec<_> = l.nodes.values().map(|n| {
+			let listener = Arc::new(DummySessionEventListener::default());
+			n.session.add_listener(listener.clone());
+			listener
+		}).collect();
+
+		l.master().initialize(Public::default(), 1, l.nodes.keys().cloned().collect()).unwrap();
+		while let Some((from, to, message)) = l.take_message() {
+			l.process_message((from, to, message)).unwrap();
+		}
+
+		for listener in &listeners {
+			assert_eq!(listener.events(), vec![SessionEvent::Initialized, SessionEvent::Confirmed, SessionEvent::Finished]);
+		}
+	}
+
+	#[test]
+	fn session_failure_is_reported_to_listeners() {
+		let (_, _, _, l) = make_simple_cluster(0, 2).unwrap();
+		let listener = Arc::new(DummySessionEventListener::default());
+		l.master().add_listener(listener.clone());
+		l.master().on_session_timeout();
+		assert_eq!(listener.events(), vec![SessionEvent::Failed(Error::NodeDisconnected)]);
+	}
+
+	#[test]
+	fn intermediate_secrets_are_cleared_when_session_is_completed() {
+		let mut l = MessageLoop::new(3);
+		l.master().initialize(Public::default(), 1, l.nodes.keys().cloned().collect()).unwrap();
+		while let Some((from, to, message)) = l.take_message() {
+			l.process_message((from, to, message)).unwrap();
+		}
+
+		for node in l.nodes.values() {
+			let data = node.session.data.lock();
+			assert_eq!(data.state, SessionState::Finished);
+			assert!(data.secret_coeff.is_none());
+			assert!(data.nodes.values().all(|n| n.secret1_sent.is_none() && n.secret2_sent.is_none()
+				&& n.secret1.is_none() && n.secret2.is_none()));
+			assert!(data.secret_share.is_some());
+		}
+	}
+
 	#[test]
 	fn complete_enc_dec_session() {
 		let test_cases = [(0, 5), (2, 5), (3, 5)];
@@ -20,8 +20,9 @@
 ///! 1) both nodes are generating random `KeyPair` (`session_key_pair`), which will be used for channel encryption
 ///! 2) both nodes are generating random H256 (`confirmation_plain`)
 ///! 3) both nodes are signing `confirmation_plain` using `session_key_pair` to receive `confirmation_signed_session`
-///! 4) nodes exchange with `NodePublicKey` messages, containing: `self_key_pair.public`, `confirmation_plain`, `confirmation_signed_session`
+///! 4) nodes exchange with `NodePublicKey` messages, containing: `self_key_pair.public`, `confirmation_plain`, `confirmation_signed_session`, `protocol_version`
 ///! 5) both nodes are checking that they're configured to communicate to server with received `message.self_key_pair.public`. Connection is closed otherwise
+///! 5.1) both nodes are selecting the lowest of own && peer' `protocol_version`. Connection is closed if this version is not supported
 ///! 6) both nodes are recovering peer' `session_key_pair.public` from `message.confirmation_plain` and `message.confirmation_signed_session`
 ///! 7) both nodes are computing shared session key pair using self' `session_key_pair.secret` && peer' `session_key_pair.public`. All following messages are encrypted using this key_pair.
 ///! 8) both nodes are signing `message.confirmation_plain` with their own `self_key_pair.private` to receive `confirmation_signed`
@@ -31,6 +32,7 @@
 ///! Result of handshake is:
 ///! 1) belief, that we are connected to the KS from our KS-set
 ///! 2) session key pair, which is used to enrypt all connection messages
+///! 3) protocol version, which is used to serialize all connection messages
 
 use std::io;
 use std::sync::Arc;
@@ -43,7 +45,11 @@ use bigint::hash::H256;
 use key_server_cluster::{NodeId, Error, NodeKeyPair};
 use key_server_cluster::message::{Message, ClusterMessage, NodePublicKey, NodePrivateKeySignature};
 use key_server_cluster::io::{write_message, write_encrypted_message, WriteMessage, ReadMessage,
-	read_message, read_encrypted_message, fix_shared_key};
+	read_message, read_encrypted_message, fix_shared_key, negotiate_version, ReceivedSequences, CURRENT_HEADER_VERSION};
+
+/// Sequence number of the signed message, sent during handshake. Messages, sent after handshake, are numbered from
+/// HANDSHAKE_MESSAGE_SEQUENCE + 1.
+pub const HANDSHAKE_MESSAGE_SEQUENCE: u64 = 0;
 
 /// Start handshake procedure with another node from the cluster.
 pub fn handshake<A>(a: A, self_key_pair: Arc<NodeKeyPair>, trusted_nodes: BTreeSet<NodeId>) -> Handshake<A> where A: AsyncWrite + AsyncRead {
@@ -75,6 +81,7 @@ pub fn handshake_with_init_data<A>(a: A, init_data: Result<(H256, KeyPair), Erro
 		peer_session_public: None,
 		peer_confirmation_plain: None,
 		shared_key: None,
+		version: None,
 	}
 }
 
@@ -101,6 +108,7 @@ pub fn accept_handshake<A>(a: A, self_key_pair: Arc<NodeKeyPair>) -> Handshake<A
 		peer_session_public: None,
 		peer_confirmation_plain: None,
 		shared_key: None,
+		version: None,
 	}
 }
 
@@ -111,6 +119,8 @@ pub struct HandshakeResult {
 	pub node_id: NodeId,
 	/// Shared key.
 	pub shared_key: KeyPair,
+	/// Negotiated protocol version.
+	pub version: u8,
 }
 
 /// Future handshake procedure.
@@ -126,6 +136,7 @@ pub struct Handshake<A> {
 	peer_session_public: Option<Public>,
 	peer_confirmation_plain: Option<H256>,
 	shared_key: Option<KeyPair>,
+	version: Option<u8>,
 }
 
 /// Active handshake state.
@@ -153,6 +164,7 @@ impl<A> Handshake<A> where A: AsyncRead + AsyncWrite {
 			node_id: self_node_id.into(),
 			confirmation_plain: confirmation_plain.into(),
 			confirmation_signed_session: confirmation_signed_session.into(),
+			protocol_version: Some(CURRENT_HEADER_VERSION),
 		})))
 	}
 
@@ -208,13 +220,16 @@ impl<A> Future for Handshake<A> where A: AsyncRead + AsyncWrite {
 
 					(HandshakeState::SendPrivateKeySignature(write_encrypted_message(stream,
 						self.shared_key.as_ref().expect("filled couple of lines above; qed"),
+						&*self.self_key_pair,
+						self.version.expect("version is filled in ReceivePublicKey; SendPrivateKeySignature follows ReceivePublicKey; qed"),
+						HANDSHAKE_MESSAGE_SEQUENCE,
 					message)), Async::NotReady)
 				}
 			},
 			HandshakeState::ReceivePublicKey(ref mut future) => {
 				let (stream, message) = try_ready!(future.poll());
 
-				let message = match message {
+				let message = match message.and_then(|message| message.unsigned()) {
 					Ok(message) => match message {
 						Message::Cluster(ClusterMessage::NodePublicKey(message)) => message,
 						_ => return Ok((stream, Err(Error::InvalidMessage)).into()),
@@ -226,6 +241,10 @@ impl<A> Future for Handshake<A> where A: AsyncRead + AsyncWrite {
 					return Ok((stream, Err(Error::InvalidNodeId)).into());
 				}
 
+				self.version = match negotiate_version(message.protocol_version) {
+					Ok(version) => Some(version),
+					Err(err) => return Ok((stream, Err(err)).into()),
+				};
 				self.peer_node_id = Some(message.node_id.into());
 				self.peer_session_public = Some(match recover(&message.confirmation_signed_session, &message.confirmation_plain) {
 					Ok(peer_session_public) => peer_session_public,
@@ -254,6 +273,9 @@ impl<A> Future for Handshake<A> where A: AsyncRead + AsyncWrite {
 
 					(HandshakeState::SendPrivateKeySignature(write_encrypted_message(stream,
 						self.shared_key.as_ref().expect("filled couple of lines above; qed"),
+						&*self.self_key_pair,
+						self.version.expect("version is filled in ReceivePublicKey; SendPrivateKeySignature follows ReceivePublicKey; qed"),
+						HANDSHAKE_MESSAGE_SEQUENCE,
 					message)), Async::NotReady)
 				} else {
 					let self_session_key_pair = self.self_session_key_pair.as_ref()
@@ -282,7 +304,10 @@ impl<A> Future for Handshake<A> where A: AsyncRead + AsyncWrite {
 			HandshakeState::ReceivePrivateKeySignature(ref mut future) => {
 				let (stream, message) = try_ready!(future.poll());
 
-				let message = match message {
+				let peer_public = self.peer_node_id.as_ref().expect("peer_node_id is filled in ReceivePublicKey; ReceivePrivateKeySignature follows ReceivePublicKey; qed");
+				let version = self.version.expect("version is filled in ReceivePublicKey; ReceivePrivateKeySignature follows ReceivePublicKey; qed");
+				let mut received_sequences = ReceivedSequences::starting_at(HANDSHAKE_MESSAGE_SEQUENCE);
+				let message = match message.and_then(|message| message.verify(peer_public, version, &mut received_sequences)) {
 					Ok(message) => match message {
 						Message::Cluster(ClusterMessage::NodePrivateKeySignature(message)) => message,
 						_ => return Ok((stream, Err(Error::InvalidMessage)).into()),
@@ -290,7 +315,6 @@ impl<A> Future for Handshake<A> where A: AsyncRead + AsyncWrite {
 					Err(err) => return Ok((stream, Err(err.into())).into()),
 				};
 
-				let peer_public = self.peer_node_id.as_ref().expect("peer_node_id is filled in ReceivePublicKey; ReceivePrivateKeySignature follows ReceivePublicKey; qed");
 				if !verify_public(peer_public, &*message.confirmation_signed, &self.self_confirmation_plain).unwrap_or(false) {
 					return Ok((stream, Err(Error::InvalidMessage)).into());
 				}
@@ -298,6 +322,7 @@ impl<A> Future for Handshake<A> where A: AsyncRead + AsyncWrite {
 				(HandshakeState::Finished, Async::Ready((stream, Ok(HandshakeResult {
 					node_id: self.peer_node_id.expect("peer_node_id is filled in ReceivePublicKey; ReceivePrivateKeySignature follows ReceivePublicKey; qed"),
 					shared_key: self.shared_key.clone().expect("shared_key is filled in Send/ReceivePublicKey; ReceivePrivateKeySignature follows Send/ReceivePublicKey; qed"),
+					version: version,
 				}))))
 			},
 			HandshakeState::Finished => panic!("poll Handshake after it's done"),
@@ -319,12 +344,13 @@ mod tests {
 	use futures::Future;
 	use ethkey::{Random, Generator, sign};
 	use bigint::hash::H256;
-	use key_server_cluster::PlainNodeKeyPair;
+	use key_server_cluster::{Error, PlainNodeKeyPair};
 	use key_server_cluster::io::message::tests::TestIo;
+	use key_server_cluster::io::message::{CURRENT_HEADER_VERSION, LEGACY_HEADER_VERSION};
 	use key_server_cluster::message::{Message, ClusterMessage, NodePublicKey, NodePrivateKeySignature};
 	use super::{handshake_with_init_data, accept_handshake, HandshakeResult};
 
-	fn prepare_test_io() -> (H256, TestIo) {
+	fn prepare_test_io(peer_version: Option<u8>) -> (H256, TestIo) {
 		let mut io = TestIo::new();
 
 		let self_confirmation_plain = *Random.generate().unwrap().secret().clone();
@@ -338,6 +364,7 @@ mod tests {
 			node_id: peer_public.into(),
 			confirmation_plain: peer_confirmation_plain.into(),
 			confirmation_signed_session: peer_confirmation_signed.into(),
+			protocol_version: peer_version,
 		})));
 		io.add_encrypted_input_message(Message::Cluster(ClusterMessage::NodePrivateKeySignature(NodePrivateKeySignature {
 			confirmation_signed: self_confirmation_signed.into(),
@@ -348,7 +375,7 @@ mod tests {
 
 	#[test]
 	fn active_handshake_works() {
-		let (self_confirmation_plain, io) = prepare_test_io();
+		let (self_confirmation_plain, io) = prepare_test_io(Some(CURRENT_HEADER_VERSION));
 		let trusted_nodes: BTreeSet<_> = vec![io.peer_key_pair().public().clone()].into_iter().collect();
 		let self_session_key_pair = io.self_session_key_pair().clone();
 		let self_key_pair = Arc::new(PlainNodeKeyPair::new(io.self_key_pair().clone()));
@@ -359,12 +386,13 @@ mod tests {
 		assert_eq!(handshake_result.1, Ok(HandshakeResult {
 			node_id: handshake_result.0.peer_key_pair().public().clone(),
 			shared_key: shared_key,
+			version: CURRENT_HEADER_VERSION,
 		}));
 	}
 
 	#[test]
 	fn passive_handshake_works() {
-		let (self_confirmation_plain, io) = prepare_test_io();
+		let (self_confirmation_plain, io) = prepare_test_io(Some(CURRENT_HEADER_VERSION));
 		let self_key_pair = Arc::new(PlainNodeKeyPair::new(io.self_key_pair().clone()));
 		let self_session_key_pair = io.self_session_key_pair().clone();
 		let shared_key = io.shared_key_pair().clone();
@@ -377,6 +405,42 @@ mod tests {
 		assert_eq!(handshake_result.1, Ok(HandshakeResult {
 			node_id: handshake_result.0.peer_key_pair().public().clone(),
 			shared_key: shared_key,
+			version: CURRENT_HEADER_VERSION,
 		}));
 	}
+
+	#[test]
+	fn handshake_with_legacy_node_uses_legacy_version() {
+		let (self_confirmation_plain, io) = prepare_test_io(None);
+		let trusted_nodes: BTreeSet<_> = vec![io.peer_key_pair().public().clone()].into_iter().collect();
+		let self_session_key_pair = io.self_session_key_pair().clone();
+		let self_key_pair = Arc::new(PlainNodeKeyPair::new(io.self_key_pair().clone()));
+
+		let handshake = handshake_with_init_data(io, Ok((self_confirmation_plain, self_session_key_pair)), self_key_pair, trusted_nodes);
+		assert_eq!(handshake.wait().unwrap().1.unwrap().version, LEGACY_HEADER_VERSION);
+	}
+
+	#[test]
+	fn handshake_with_newer_node_uses_own_version() {
+		let (self_confirmation_plain, io) = prepare_test_io(Some(CURRENT_HEADER_VERSION + 1));
+		let self_key_pair = Arc::new(PlainNodeKeyPair::new(io.self_key_pair().clone()));
+		let self_session_key_pair = io.self_session_key_pair().clone();
+
+		let mut handshake = accept_handshake(io, self_key_pair);
+		handshake.set_self_confirmation_plain(self_confirmation_plain);
+		handshake.set_self_session_key_pair(self_session_key_pair);
+
+		assert_eq!(handshake.wait().unwrap().1.unwrap().version, CURRENT_HEADER_VERSION);
+	}
+
+	#[test]
+	fn handshake_with_node_of_unsupported_version_fails() {
+		let (self_confirmation_plain, io) = prepare_test_io(Some(0));
+		let trusted_nodes: BTreeSet<_> = vec![io.peer_key_pair().public().clone()].into_iter().collect();
+		let self_session_key_pair = io.self_session_key_pair().clone();
+		let self_key_pair = Arc::new(PlainNodeKeyPair::new(io.self_key_pair().clone()));
+
+		let handshake = handshake_with_init_data(io, Ok((self_confirmation_plain, self_session_key_pair)), self_key_pair, trusted_nodes);
+		assert_eq!(handshake.wait().unwrap().1, Err(Error::InvalidMessageVersion));
+	}
 }
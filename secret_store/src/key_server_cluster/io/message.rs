@@ -19,19 +19,51 @@ use std::u16;
 use std::ops::Deref;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use serde_json;
+use util::snappy;
 use ethcrypto::ecies::{encrypt_single_message, decrypt_single_message};
-use ethkey::{Secret, KeyPair};
+use ethkey::{Secret, KeyPair, Signature, verify_public};
 use ethkey::math::curve_order;
 use bigint::prelude::U256;
-use bigint::hash::H256;
-use key_server_cluster::Error;
+use bigint::hash::{H256, H520};
+use hash::keccak;
+use key_server_cluster::{Error, NodeId, NodeKeyPair};
 use key_server_cluster::message::{Message, ClusterMessage, GenerationMessage, EncryptionMessage,
-	DecryptionMessage, SigningMessage};
+	DecryptionMessage, ReEncryptionMessage, SigningMessage, EcdsaSigningMessage, ShareRefreshMessage, ShareRecoveryMessage, KeyDerivationMessage,
+	KeyDeletionMessage, ShareMoveMessage, ServerKeyRetrievalMessage, ShareBootstrapMessage, KeyExportMessage};
 
 /// Size of serialized header.
 pub const MESSAGE_HEADER_SIZE: usize = 4;
 /// Current header version.
-pub const CURRENT_HEADER_VERSION: u8 = 1;
+pub const CURRENT_HEADER_VERSION: u8 = 6;
+/// The oldest header version, which is still supported.
+pub const MIN_HEADER_VERSION: u8 = 1;
+/// Header version of nodes, which are not announcing version in handshake.
+pub const LEGACY_HEADER_VERSION: u8 = 1;
+/// The first header version, where payload is prefixed with compression flag.
+pub const COMPRESSION_HEADER_VERSION: u8 = 2;
+/// The first header version, where nodes are exchanging key shares inventory.
+pub const SHARES_INVENTORY_HEADER_VERSION: u8 = 3;
+/// The first header version, where payload of every encrypted message is prefixed with the envelope,
+/// signed by the sender node key.
+pub const SIGNED_ENVELOPE_HEADER_VERSION: u8 = 4;
+/// The first header version, where session messages are acknowledged by the receiver.
+pub const ACKNOWLEDGEMENTS_HEADER_VERSION: u8 = 5;
+/// The first header version, where the envelope contains sequence number of the message within the connection.
+pub const SEQUENCED_ENVELOPE_HEADER_VERSION: u8 = 6;
+/// Payloads larger than this are compressed (if negotiated version supports compression).
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Size of the message envelope (signature of the message digest).
+const MESSAGE_ENVELOPE_SIZE: usize = 65;
+/// Size of the sequenced message envelope (signature of the message digest && message sequence number).
+const SEQUENCED_MESSAGE_ENVELOPE_SIZE: usize = 73;
+/// Number of the most recent sequence numbers, which are remembered to detect replayed messages.
+const SEQUENCE_WINDOW_SIZE: u64 = 64;
+
+/// Payload compression flag: payload is not compressed.
+const PAYLOAD_PLAIN: u8 = 0;
+/// Payload compression flag: payload is compressed using snappy.
+const PAYLOAD_SNAPPY: u8 = 1;
 
 /// Message header.
 #[derive(Debug, PartialEq)]
@@ -62,13 +94,134 @@ impl Into<Vec<u8>> for SerializedMessage {
 	}
 }
 
-/// Serialize message.
-pub fn serialize_message(message: Message) -> Result<SerializedMessage, Error> {
+/// Message, read from the connection. Envelope is only present if message has been encrypted using
+/// the protocol version, which supports envelopes.
+#[derive(Debug)]
+pub struct SignedMessage {
+	/// The message itself.
+	message: Message,
+	/// Message envelope.
+	envelope: Option<MessageEnvelope>,
+	/// Size of the message (including header), read from the connection. Zero if message has not been read from encrypted connection.
+	size: usize,
+}
+
+/// Opened message envelope.
+#[derive(Debug)]
+struct MessageEnvelope {
+	/// Signature of the message digest, made by the sender node key.
+	signature: Signature,
+	/// Digest of the message, including session nonce && sequence number.
+	digest: H256,
+	/// Sequence number of the message within the connection. None if envelope version does not support sequence numbers.
+	sequence: Option<u64>,
+}
+
+/// Sequence numbers of messages, received over single connection. Messages are written to the connection
+/// from multiple threads, so they could arrive slightly out of order => the most recent SEQUENCE_WINDOW_SIZE
+/// sequence numbers are remembered. Message with sequence number, which has already been received or is
+/// too old to tell, is treated as replayed.
+#[derive(Debug)]
+pub struct ReceivedSequences {
+	/// Sequence number, following the highest received sequence number.
+	next: u64,
+	/// Bit i is set if message with sequence number (next - 1 - i) has been received.
+	window: u64,
+}
+
+impl SignedMessage {
+	/// Get size of the message (including header), read from the connection.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// Get message, which is sent before protocol version is negotiated && thus is never signed.
+	pub fn unsigned(self) -> Result<Message, Error> {
+		match self.envelope {
+			None => Ok(self.message),
+			Some(_) => Err(Error::InvalidMessage),
+		}
+	}
+
+	/// Get message, verifying that it is signed by the sender && has not been received before. Every message
+	/// must be signed if connection version supports envelopes.
+	pub fn verify(self, sender: &NodeId, connection_version: u8, received_sequences: &mut ReceivedSequences) -> Result<Message, Error> {
+		let envelope = match self.envelope {
+			Some(envelope) => envelope,
+			None if connection_version < SIGNED_ENVELOPE_HEADER_VERSION => return Ok(self.message),
+			None => return Err(Error::InvalidMessageSignature),
+		};
+
+		match verify_public(sender, &envelope.signature, &envelope.digest) {
+			Ok(true) => (),
+			Ok(false) | Err(_) => return Err(Error::InvalidMessageSignature),
+		}
+
+		match envelope.sequence {
+			Some(sequence) if received_sequences.on_received(sequence) => Ok(self.message),
+			Some(_) => Err(Error::InvalidMessageSignature),
+			None if connection_version < SEQUENCED_ENVELOPE_HEADER_VERSION => Ok(self.message),
+			None => Err(Error::InvalidMessageSignature),
+		}
+	}
+}
+
+impl ReceivedSequences {
+	/// Create sequence numbers of connection, where all messages with sequence number, less than given, have been received.
+	pub fn starting_at(next: u64) -> Self {
+		ReceivedSequences {
+			next: next,
+			window: !0,
+		}
+	}
+
+	/// When message with given sequence number is received. Returns false if message has been replayed.
+	pub fn on_received(&mut self, sequence: u64) -> bool {
+		if sequence >= self.next {
+			let shift = sequence - self.next;
+			self.window = if shift >= SEQUENCE_WINDOW_SIZE - 1 { 0 } else { self.window << (shift + 1) };
+			self.window |= 1;
+			self.next = sequence.saturating_add(1);
+			return true;
+		}
+
+		let age = self.next - 1 - sequence;
+		if age >= SEQUENCE_WINDOW_SIZE || self.window & (1 << age) != 0 {
+			return false;
+		}
+
+		self.window |= 1 << age;
+		true
+	}
+}
+
+impl Default for ReceivedSequences {
+	fn default() -> Self {
+		ReceivedSequences::starting_at(0)
+	}
+}
+
+impl From<Message> for SignedMessage {
+	fn from(message: Message) -> Self {
+		SignedMessage {
+			message: message,
+			envelope: None,
+			size: 0,
+		}
+	}
+}
+
+/// Serialize message using given (negotiated) protocol version.
+pub fn serialize_message(message: Message, version: u8) -> Result<SerializedMessage, Error> {
 	let (message_kind, payload) = match message {
 		Message::Cluster(ClusterMessage::NodePublicKey(payload))							=> (1, serde_json::to_vec(&payload)),
 		Message::Cluster(ClusterMessage::NodePrivateKeySignature(payload))					=> (2, serde_json::to_vec(&payload)),
 		Message::Cluster(ClusterMessage::KeepAlive(payload))								=> (3, serde_json::to_vec(&payload)),
 		Message::Cluster(ClusterMessage::KeepAliveResponse(payload))						=> (4, serde_json::to_vec(&payload)),
+		Message::Cluster(ClusterMessage::KeySharesInventory(payload))						=> (5, serde_json::to_vec(&payload)),
+		Message::Cluster(ClusterMessage::ForwardAdminRequest(payload))						=> (6, serde_json::to_vec(&payload)),
+		Message::Cluster(ClusterMessage::ForwardedAdminRequestStatus(payload))				=> (7, serde_json::to_vec(&payload)),
+		Message::Cluster(ClusterMessage::MessageAcknowledgement(payload))					=> (8, serde_json::to_vec(&payload)),
 
 		Message::Generation(GenerationMessage::InitializeSession(payload))					=> (50, serde_json::to_vec(&payload)),
 		Message::Generation(GenerationMessage::ConfirmInitialization(payload))				=> (51, serde_json::to_vec(&payload)),
@@ -81,6 +234,9 @@ pub fn serialize_message(message: Message) -> Result<SerializedMessage, Error> {
 		Message::Encryption(EncryptionMessage::InitializeEncryptionSession(payload))		=> (100, serde_json::to_vec(&payload)),
 		Message::Encryption(EncryptionMessage::ConfirmEncryptionInitialization(payload))	=> (101, serde_json::to_vec(&payload)),
 		Message::Encryption(EncryptionMessage::EncryptionSessionError(payload))				=> (102, serde_json::to_vec(&payload)),
+		Message::Encryption(EncryptionMessage::RequestRekeyingPublicShare(payload))			=> (103, serde_json::to_vec(&payload)),
+		Message::Encryption(EncryptionMessage::RekeyingPublicShare(payload))				=> (104, serde_json::to_vec(&payload)),
+		Message::Encryption(EncryptionMessage::CommitRekeying(payload))						=> (105, serde_json::to_vec(&payload)),
 
 		Message::Decryption(DecryptionMessage::DecryptionConsensusMessage(payload))			=> (150, serde_json::to_vec(&payload)),
 		Message::Decryption(DecryptionMessage::RequestPartialDecryption(payload))			=> (151, serde_json::to_vec(&payload)),
@@ -88,29 +244,111 @@ pub fn serialize_message(message: Message) -> Result<SerializedMessage, Error> {
 		Message::Decryption(DecryptionMessage::DecryptionSessionError(payload))				=> (153, serde_json::to_vec(&payload)),
 		Message::Decryption(DecryptionMessage::DecryptionSessionCompleted(payload))			=> (154, serde_json::to_vec(&payload)),
 
+		Message::ReEncryption(ReEncryptionMessage::ReEncryptionConsensusMessage(payload))	=> (155, serde_json::to_vec(&payload)),
+		Message::ReEncryption(ReEncryptionMessage::RequestPartialReEncryption(payload))		=> (156, serde_json::to_vec(&payload)),
+		Message::ReEncryption(ReEncryptionMessage::PartialReEncryption(payload))			=> (157, serde_json::to_vec(&payload)),
+		Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(payload))		=> (158, serde_json::to_vec(&payload)),
+		Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionCompleted(payload))	=> (159, serde_json::to_vec(&payload)),
+
+		Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(payload))	=> (160, serde_json::to_vec(&payload)),
+		Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalPublicShare(payload))		=> (161, serde_json::to_vec(&payload)),
+		Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::RequestServerKeyAttestation(payload))		=> (162, serde_json::to_vec(&payload)),
+		Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyAttestation(payload))				=> (163, serde_json::to_vec(&payload)),
+		Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(payload))		=> (164, serde_json::to_vec(&payload)),
+
+		Message::ShareBootstrap(ShareBootstrapMessage::InitializeShareBootstrapSession(payload))	=> (165, serde_json::to_vec(&payload)),
+		Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapKeySharesSaved(payload))		=> (166, serde_json::to_vec(&payload)),
+		Message::ShareBootstrap(ShareBootstrapMessage::CommitShareBootstrap(payload))				=> (167, serde_json::to_vec(&payload)),
+		Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapCommitted(payload))			=> (168, serde_json::to_vec(&payload)),
+		Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(payload))		=> (169, serde_json::to_vec(&payload)),
+
+		Message::KeyExport(KeyExportMessage::InitializeKeyExportSession(payload))				=> (170, serde_json::to_vec(&payload)),
+		Message::KeyExport(KeyExportMessage::KeyExportContribution(payload))					=> (171, serde_json::to_vec(&payload)),
+		Message::KeyExport(KeyExportMessage::KeyExportSessionError(payload))					=> (172, serde_json::to_vec(&payload)),
+
 		Message::Signing(SigningMessage::SigningConsensusMessage(payload))					=> (200, serde_json::to_vec(&payload)),
 		Message::Signing(SigningMessage::SigningGenerationMessage(payload))					=> (201, serde_json::to_vec(&payload)),
 		Message::Signing(SigningMessage::RequestPartialSignature(payload))					=> (202, serde_json::to_vec(&payload)),
 		Message::Signing(SigningMessage::PartialSignature(payload))							=> (203, serde_json::to_vec(&payload)),
 		Message::Signing(SigningMessage::SigningSessionError(payload))						=> (204, serde_json::to_vec(&payload)),
 		Message::Signing(SigningMessage::SigningSessionCompleted(payload))					=> (205, serde_json::to_vec(&payload)),
+
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningConsensusMessage(payload))			=> (206, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(payload))	=> (207, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(payload))	=> (208, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningZeroShares(payload))					=> (209, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(payload))	=> (210, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaRequestPartialSignature(payload))			=> (211, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaPartialSignature(payload))					=> (212, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(payload))				=> (213, serde_json::to_vec(&payload)),
+		Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionCompleted(payload))			=> (214, serde_json::to_vec(&payload)),
+
+		Message::ShareRefresh(ShareRefreshMessage::InitializeShareRefreshSession(payload))			=> (225, serde_json::to_vec(&payload)),
+		Message::ShareRefresh(ShareRefreshMessage::ConfirmShareRefreshInitialization(payload))		=> (226, serde_json::to_vec(&payload)),
+		Message::ShareRefresh(ShareRefreshMessage::RequestShareRefreshDeltas(payload))				=> (227, serde_json::to_vec(&payload)),
+		Message::ShareRefresh(ShareRefreshMessage::ShareRefreshDelta(payload))						=> (228, serde_json::to_vec(&payload)),
+		Message::ShareRefresh(ShareRefreshMessage::ConfirmShareRefresh(payload))					=> (229, serde_json::to_vec(&payload)),
+		Message::ShareRefresh(ShareRefreshMessage::CommitShareRefresh(payload))						=> (230, serde_json::to_vec(&payload)),
+		Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(payload))				=> (231, serde_json::to_vec(&payload)),
+		Message::ShareRefresh(ShareRefreshMessage::ShareRefreshCommitted(payload))					=> (249, serde_json::to_vec(&payload)),
+
+		Message::KeyDerivation(KeyDerivationMessage::InitializeKeyDerivationSession(payload))		=> (232, serde_json::to_vec(&payload)),
+		Message::KeyDerivation(KeyDerivationMessage::ConfirmKeyDerivationInitialization(payload))	=> (233, serde_json::to_vec(&payload)),
+		Message::KeyDerivation(KeyDerivationMessage::CommitKeyDerivation(payload))					=> (234, serde_json::to_vec(&payload)),
+		Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(payload))			=> (235, serde_json::to_vec(&payload)),
+
+		Message::KeyDeletion(KeyDeletionMessage::InitializeKeyDeletionSession(payload))			=> (236, serde_json::to_vec(&payload)),
+		Message::KeyDeletion(KeyDeletionMessage::ConfirmKeyDeletionInitialization(payload))		=> (237, serde_json::to_vec(&payload)),
+		Message::KeyDeletion(KeyDeletionMessage::CommitKeyDeletion(payload))						=> (238, serde_json::to_vec(&payload)),
+		Message::KeyDeletion(KeyDeletionMessage::KeyDeletionCompleted(payload))					=> (239, serde_json::to_vec(&payload)),
+		Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(payload))				=> (240, serde_json::to_vec(&payload)),
+
+		Message::ShareMove(ShareMoveMessage::InitializeShareMoveSession(payload))					=> (241, serde_json::to_vec(&payload)),
+		Message::ShareMove(ShareMoveMessage::ConfirmShareMoveInitialization(payload))				=> (242, serde_json::to_vec(&payload)),
+		Message::ShareMove(ShareMoveMessage::ShareMoveData(payload))								=> (243, serde_json::to_vec(&payload)),
+		Message::ShareMove(ShareMoveMessage::CommitShareMove(payload))								=> (244, serde_json::to_vec(&payload)),
+		Message::ShareMove(ShareMoveMessage::ShareMoveCompleted(payload))							=> (245, serde_json::to_vec(&payload)),
+		Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(payload))						=> (246, serde_json::to_vec(&payload)),
+		Message::ShareMove(ShareMoveMessage::ShareMoveRollback(payload))							=> (247, serde_json::to_vec(&payload)),
+		Message::ShareMove(ShareMoveMessage::ShareMoveDataChunk(payload))							=> (248, serde_json::to_vec(&payload)),
+
+		Message::ShareRecovery(ShareRecoveryMessage::InitializeShareRecoverySession(payload))		=> (250, serde_json::to_vec(&payload)),
+		Message::ShareRecovery(ShareRecoveryMessage::ConfirmShareRecoveryInitialization(payload))	=> (251, serde_json::to_vec(&payload)),
+		Message::ShareRecovery(ShareRecoveryMessage::RequestShareRecoveryContribution(payload))		=> (252, serde_json::to_vec(&payload)),
+		Message::ShareRecovery(ShareRecoveryMessage::ShareRecoveryBlindingShare(payload))			=> (253, serde_json::to_vec(&payload)),
+		Message::ShareRecovery(ShareRecoveryMessage::ShareRecoveryContribution(payload))			=> (254, serde_json::to_vec(&payload)),
+		Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(payload))			=> (255, serde_json::to_vec(&payload)),
 	};
 
 	let payload = payload.map_err(|err| Error::Serde(err.to_string()))?;
+	let payload = if version >= COMPRESSION_HEADER_VERSION { compress_payload(payload) } else { payload };
 	build_serialized_message(MessageHeader {
 		kind: message_kind,
-		version: CURRENT_HEADER_VERSION,
+		version: version,
 		size: 0,
 	}, payload)
 }
 
-/// Deserialize message.
+/// Compute id of the message, which is used to acknowledge it. Id is computed using fixed protocol version, so that
+/// the same message, retransmitted over connection of other version, has the same id.
+pub fn message_id(message: &Message) -> Result<H256, Error> {
+	serialize_message(message.clone(), ACKNOWLEDGEMENTS_HEADER_VERSION)
+		.map(|message| keccak(&*message))
+}
+
+/// Deserialize message. Payload of every supported version is accepted: fields, added in later versions,
+/// must be optional, and unknown fields are ignored.
 pub fn deserialize_message(header: &MessageHeader, payload: Vec<u8>) -> Result<Message, Error> {
+	let payload = if header.version >= COMPRESSION_HEADER_VERSION { decompress_payload(payload)? } else { payload };
 	Ok(match header.kind {
 		1	=> Message::Cluster(ClusterMessage::NodePublicKey(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		2	=> Message::Cluster(ClusterMessage::NodePrivateKeySignature(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		3	=> Message::Cluster(ClusterMessage::KeepAlive(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		4	=> Message::Cluster(ClusterMessage::KeepAliveResponse(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		5	=> Message::Cluster(ClusterMessage::KeySharesInventory(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		6	=> Message::Cluster(ClusterMessage::ForwardAdminRequest(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		7	=> Message::Cluster(ClusterMessage::ForwardedAdminRequestStatus(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		8	=> Message::Cluster(ClusterMessage::MessageAcknowledgement(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 
 		50	=> Message::Generation(GenerationMessage::InitializeSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		51	=> Message::Generation(GenerationMessage::ConfirmInitialization(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
@@ -123,6 +361,9 @@ pub fn deserialize_message(header: &MessageHeader, payload: Vec<u8>) -> Result<M
 		100	=> Message::Encryption(EncryptionMessage::InitializeEncryptionSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		101	=> Message::Encryption(EncryptionMessage::ConfirmEncryptionInitialization(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		102	=> Message::Encryption(EncryptionMessage::EncryptionSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		103	=> Message::Encryption(EncryptionMessage::RequestRekeyingPublicShare(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		104	=> Message::Encryption(EncryptionMessage::RekeyingPublicShare(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		105	=> Message::Encryption(EncryptionMessage::CommitRekeying(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 
 		150	=> Message::Decryption(DecryptionMessage::DecryptionConsensusMessage(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		151	=> Message::Decryption(DecryptionMessage::RequestPartialDecryption(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
@@ -130,6 +371,28 @@ pub fn deserialize_message(header: &MessageHeader, payload: Vec<u8>) -> Result<M
 		153	=> Message::Decryption(DecryptionMessage::DecryptionSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		154	=> Message::Decryption(DecryptionMessage::DecryptionSessionCompleted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 
+		155	=> Message::ReEncryption(ReEncryptionMessage::ReEncryptionConsensusMessage(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		156	=> Message::ReEncryption(ReEncryptionMessage::RequestPartialReEncryption(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		157	=> Message::ReEncryption(ReEncryptionMessage::PartialReEncryption(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		158	=> Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		159	=> Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionCompleted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		160	=> Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		161	=> Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalPublicShare(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		162	=> Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::RequestServerKeyAttestation(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		163	=> Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyAttestation(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		164	=> Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		165	=> Message::ShareBootstrap(ShareBootstrapMessage::InitializeShareBootstrapSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		166	=> Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapKeySharesSaved(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		167	=> Message::ShareBootstrap(ShareBootstrapMessage::CommitShareBootstrap(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		168	=> Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapCommitted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		169	=> Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		170	=> Message::KeyExport(KeyExportMessage::InitializeKeyExportSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		171	=> Message::KeyExport(KeyExportMessage::KeyExportContribution(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		172	=> Message::KeyExport(KeyExportMessage::KeyExportSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
 		200	=> Message::Signing(SigningMessage::SigningConsensusMessage(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		201	=> Message::Signing(SigningMessage::SigningGenerationMessage(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		202	=> Message::Signing(SigningMessage::RequestPartialSignature(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
@@ -137,10 +400,164 @@ pub fn deserialize_message(header: &MessageHeader, payload: Vec<u8>) -> Result<M
 		204	=> Message::Signing(SigningMessage::SigningSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 		205	=> Message::Signing(SigningMessage::SigningSessionCompleted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
 
+		206	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningConsensusMessage(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		207	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		208	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		209	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningZeroShares(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		210	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		211	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaRequestPartialSignature(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		212	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaPartialSignature(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		213	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		214	=> Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionCompleted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		225	=> Message::ShareRefresh(ShareRefreshMessage::InitializeShareRefreshSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		226	=> Message::ShareRefresh(ShareRefreshMessage::ConfirmShareRefreshInitialization(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		227	=> Message::ShareRefresh(ShareRefreshMessage::RequestShareRefreshDeltas(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		228	=> Message::ShareRefresh(ShareRefreshMessage::ShareRefreshDelta(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		229	=> Message::ShareRefresh(ShareRefreshMessage::ConfirmShareRefresh(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		230	=> Message::ShareRefresh(ShareRefreshMessage::CommitShareRefresh(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		231	=> Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		249	=> Message::ShareRefresh(ShareRefreshMessage::ShareRefreshCommitted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		232	=> Message::KeyDerivation(KeyDerivationMessage::InitializeKeyDerivationSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		233	=> Message::KeyDerivation(KeyDerivationMessage::ConfirmKeyDerivationInitialization(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		234	=> Message::KeyDerivation(KeyDerivationMessage::CommitKeyDerivation(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		235	=> Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		236	=> Message::KeyDeletion(KeyDeletionMessage::InitializeKeyDeletionSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		237	=> Message::KeyDeletion(KeyDeletionMessage::ConfirmKeyDeletionInitialization(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		238	=> Message::KeyDeletion(KeyDeletionMessage::CommitKeyDeletion(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		239	=> Message::KeyDeletion(KeyDeletionMessage::KeyDeletionCompleted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		240	=> Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		241	=> Message::ShareMove(ShareMoveMessage::InitializeShareMoveSession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		242	=> Message::ShareMove(ShareMoveMessage::ConfirmShareMoveInitialization(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		243	=> Message::ShareMove(ShareMoveMessage::ShareMoveData(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		244	=> Message::ShareMove(ShareMoveMessage::CommitShareMove(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		245	=> Message::ShareMove(ShareMoveMessage::ShareMoveCompleted(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		246	=> Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		247	=> Message::ShareMove(ShareMoveMessage::ShareMoveRollback(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		248	=> Message::ShareMove(ShareMoveMessage::ShareMoveDataChunk(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
+		250	=> Message::ShareRecovery(ShareRecoveryMessage::InitializeShareRecoverySession(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		251	=> Message::ShareRecovery(ShareRecoveryMessage::ConfirmShareRecoveryInitialization(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		252	=> Message::ShareRecovery(ShareRecoveryMessage::RequestShareRecoveryContribution(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		253	=> Message::ShareRecovery(ShareRecoveryMessage::ShareRecoveryBlindingShare(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		254	=> Message::ShareRecovery(ShareRecoveryMessage::ShareRecoveryContribution(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+		255	=> Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(serde_json::from_slice(&payload).map_err(|err| Error::Serde(err.to_string()))?)),
+
 		_ => return Err(Error::Serde(format!("unknown message type {}", header.kind))),
 	})
 }
 
+/// Deserialize message, read from encrypted connection, opening its envelope if message version supports envelopes.
+pub fn deserialize_signed_message(header: &MessageHeader, mut payload: Vec<u8>) -> Result<SignedMessage, Error> {
+	let size = MESSAGE_HEADER_SIZE + header.size as usize;
+	if header.version < SIGNED_ENVELOPE_HEADER_VERSION {
+		return Ok(SignedMessage {
+			message: deserialize_message(header, payload)?,
+			envelope: None,
+			size: size,
+		});
+	}
+
+	let envelope_size = match header.version >= SEQUENCED_ENVELOPE_HEADER_VERSION {
+		true => SEQUENCED_MESSAGE_ENVELOPE_SIZE,
+		false => MESSAGE_ENVELOPE_SIZE,
+	};
+	if payload.len() < envelope_size {
+		return Err(Error::InvalidMessage);
+	}
+
+	let message_payload = payload.split_off(envelope_size);
+	let signature = Signature::from(H520::from_slice(&payload[..MESSAGE_ENVELOPE_SIZE]));
+	let sequence = match header.version >= SEQUENCED_ENVELOPE_HEADER_VERSION {
+		true => Some(Cursor::new(&payload[MESSAGE_ENVELOPE_SIZE..]).read_u64::<LittleEndian>()?),
+		false => None,
+	};
+	let message = deserialize_message(header, message_payload.clone())?;
+	let digest = message_digest(header, sequence, message.session_nonce(), &message_payload)?;
+	Ok(SignedMessage {
+		message: message,
+		envelope: Some(MessageEnvelope {
+			signature: signature,
+			digest: digest,
+			sequence: sequence,
+		}),
+		size: size,
+	})
+}
+
+/// Prefix serialized message payload with the envelope, signed by the sender node key. Sequence number
+/// is only included if message version supports sequenced envelopes.
+pub fn sign_message(signer: &NodeKeyPair, sequence: u64, session_nonce: Option<u64>, message: SerializedMessage) -> Result<SerializedMessage, Error> {
+	let mut header: Vec<_> = message.into();
+	let payload = header.split_off(MESSAGE_HEADER_SIZE);
+	let header = deserialize_header(&header)?;
+	let sequence = match header.version >= SEQUENCED_ENVELOPE_HEADER_VERSION {
+		true => Some(sequence),
+		false => None,
+	};
+	let signature = signer.sign(&message_digest(&header, sequence, session_nonce, &payload)?)?;
+
+	let mut enveloped_payload = Vec::with_capacity(SEQUENCED_MESSAGE_ENVELOPE_SIZE + payload.len());
+	enveloped_payload.extend_from_slice(&*signature);
+	if let Some(sequence) = sequence {
+		enveloped_payload.write_u64::<LittleEndian>(sequence)?;
+	}
+	enveloped_payload.extend(payload);
+	build_serialized_message(header, enveloped_payload)
+}
+
+/// Compute digest of the serialized message, bound to the session nonce (so that message can't be replayed
+/// in other session) && to the sequence number of the message within the connection (so that message can't
+/// be replayed within the same session). Payload size is not included, as it is changed by the envelope.
+fn message_digest(header: &MessageHeader, sequence: Option<u64>, session_nonce: Option<u64>, payload: &[u8]) -> Result<H256, Error> {
+	let mut data = Vec::with_capacity(19 + payload.len());
+	data.write_u8(header.version)?;
+	data.write_u8(header.kind)?;
+	if let Some(sequence) = sequence {
+		data.write_u64::<LittleEndian>(sequence)?;
+	}
+	match session_nonce {
+		Some(session_nonce) => {
+			data.write_u8(1)?;
+			data.write_u64::<LittleEndian>(session_nonce)?;
+		},
+		None => data.write_u8(0)?,
+	}
+	data.extend_from_slice(payload);
+	Ok(keccak(&data))
+}
+
+/// Prefix payload with compression flag, compressing it if it is large enough.
+fn compress_payload(payload: Vec<u8>) -> Vec<u8> {
+	let (flag, payload) = if payload.len() > COMPRESSION_THRESHOLD {
+		(PAYLOAD_SNAPPY, snappy::compress(&payload))
+	} else {
+		(PAYLOAD_PLAIN, payload)
+	};
+
+	let mut flagged_payload = Vec::with_capacity(payload.len() + 1);
+	flagged_payload.push(flag);
+	flagged_payload.extend(payload);
+	flagged_payload
+}
+
+/// Strip compression flag from payload, decompressing it if required.
+fn decompress_payload(mut payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+	if payload.is_empty() {
+		return Err(Error::InvalidMessage);
+	}
+
+	let payload_data = payload.split_off(1);
+	match payload[0] {
+		PAYLOAD_PLAIN => Ok(payload_data),
+		PAYLOAD_SNAPPY => snappy::decompress(&payload_data).map_err(|_| Error::InvalidMessage),
+		_ => Err(Error::InvalidMessage),
+	}
+}
+
 /// Encrypt serialized message.
 pub fn encrypt_message(key: &KeyPair, message: SerializedMessage) -> Result<SerializedMessage, Error> {
 	let mut header: Vec<_> = message.into();
@@ -156,6 +573,16 @@ pub fn decrypt_message(key: &KeyPair, payload: Vec<u8>) -> Result<Vec<u8>, Error
 	Ok(decrypt_single_message(key.secret(), &payload)?)
 }
 
+/// Select protocol version to use for the connection with peer, which has announced given version.
+pub fn negotiate_version(peer_version: Option<u8>) -> Result<u8, Error> {
+	let version = ::std::cmp::min(CURRENT_HEADER_VERSION, peer_version.unwrap_or(LEGACY_HEADER_VERSION));
+	if version < MIN_HEADER_VERSION {
+		return Err(Error::InvalidMessageVersion);
+	}
+
+	Ok(version)
+}
+
 /// Fix shared encryption key.
 pub fn fix_shared_key(shared_secret: &Secret) -> Result<KeyPair, Error> {
 	// secret key created in agree function is invalid, as it is not calculated mod EC.field.n
@@ -180,7 +607,7 @@ fn serialize_header(header: &MessageHeader) -> Result<Vec<u8>, Error> {
 pub fn deserialize_header(data: &[u8]) -> Result<MessageHeader, Error> {
 	let mut reader = Cursor::new(data);
 	let version = reader.read_u8()?;
-	if version != CURRENT_HEADER_VERSION {
+	if version < MIN_HEADER_VERSION || version > CURRENT_HEADER_VERSION {
 		return Err(Error::InvalidMessageVersion);
 	}
 
@@ -211,10 +638,15 @@ pub mod tests {
 	use tokio_io::{AsyncRead, AsyncWrite};
 	use ethkey::{Random, Generator, KeyPair};
 	use ethcrypto::ecdh::agree;
-	use key_server_cluster::Error;
-	use key_server_cluster::message::Message;
-	use super::{MESSAGE_HEADER_SIZE, CURRENT_HEADER_VERSION, MessageHeader, fix_shared_key, encrypt_message,
-		serialize_message, serialize_header, deserialize_header};
+	use bigint::hash::H256;
+	use serde_json;
+	use key_server_cluster::{Error, ErrorCode, PlainNodeKeyPair};
+	use key_server_cluster::message::{Message, ClusterMessage, GenerationMessage, SessionError, KeySharesInventory};
+	use key_server_cluster::io::HANDSHAKE_MESSAGE_SEQUENCE;
+	use super::{MESSAGE_HEADER_SIZE, CURRENT_HEADER_VERSION, MIN_HEADER_VERSION, LEGACY_HEADER_VERSION, COMPRESSION_THRESHOLD,
+		SHARES_INVENTORY_HEADER_VERSION, ACKNOWLEDGEMENTS_HEADER_VERSION, MESSAGE_ENVELOPE_SIZE, SEQUENCE_WINDOW_SIZE, MessageHeader, SignedMessage,
+		ReceivedSequences, fix_shared_key, encrypt_message, serialize_message, deserialize_message,
+		deserialize_signed_message, sign_message, serialize_header, deserialize_header, negotiate_version, message_id};
 
 	pub struct TestIo {
 		self_key_pair: KeyPair,
@@ -263,7 +695,7 @@ pub mod tests {
 		}
 
 		pub fn add_input_message(&mut self, message: Message) {
-			let serialized_message = serialize_message(message).unwrap();
+			let serialized_message = serialize_message(message, CURRENT_HEADER_VERSION).unwrap();
 			let serialized_message: Vec<_> = serialized_message.into();
 			let input_buffer = self.input_buffer.get_mut();
 			for b in serialized_message {
@@ -272,7 +704,11 @@ pub mod tests {
 		}
 
 		pub fn add_encrypted_input_message(&mut self, message: Message) {
-			let serialized_message = encrypt_message(&self.shared_key_pair, serialize_message(message).unwrap()).unwrap();
+			let signer = PlainNodeKeyPair::new(self.peer_key_pair.clone());
+			let session_nonce = message.session_nonce();
+			let serialized_message = sign_message(&signer, HANDSHAKE_MESSAGE_SEQUENCE, session_nonce,
+				serialize_message(message, CURRENT_HEADER_VERSION).unwrap()).unwrap();
+			let serialized_message = encrypt_message(&self.shared_key_pair, serialized_message).unwrap();
 			let serialized_message: Vec<_> = serialized_message.into();
 			let input_buffer = self.input_buffer.get_mut();
 			for b in serialized_message {
@@ -330,4 +766,220 @@ pub mod tests {
 
 		assert_eq!(deserialize_header(&serialize_header(&header).unwrap()).unwrap_err(), Error::InvalidMessageVersion);
 	}
+
+	#[test]
+	fn version_negotiation_selects_lowest_common_version() {
+		assert_eq!(negotiate_version(None), Ok(LEGACY_HEADER_VERSION));
+		assert_eq!(negotiate_version(Some(CURRENT_HEADER_VERSION)), Ok(CURRENT_HEADER_VERSION));
+		assert_eq!(negotiate_version(Some(CURRENT_HEADER_VERSION + 1)), Ok(CURRENT_HEADER_VERSION));
+		assert_eq!(negotiate_version(Some(MIN_HEADER_VERSION - 1)), Err(Error::InvalidMessageVersion));
+	}
+
+	fn session_error_message(error_len: usize) -> Message {
+		Message::Generation(GenerationMessage::SessionError(SessionError {
+			session: H256::default().into(),
+			session_nonce: 0,
+			error: ::std::iter::repeat('e').take(error_len).collect(),
+			code: Some(ErrorCode::Storage.into()),
+		}))
+	}
+
+	fn serialize_and_deserialize(message: Message, version: u8) -> (usize, Message) {
+		let serialized_message: Vec<_> = serialize_message(message, version).unwrap().into();
+		let header = deserialize_header(&serialized_message[..MESSAGE_HEADER_SIZE]).unwrap();
+		let payload_size = header.size as usize;
+		(payload_size, deserialize_message(&header, serialized_message[MESSAGE_HEADER_SIZE..].to_vec()).unwrap())
+	}
+
+	#[test]
+	fn large_message_is_compressed() {
+		let (payload_size, message) = serialize_and_deserialize(session_error_message(COMPRESSION_THRESHOLD * 4), CURRENT_HEADER_VERSION);
+		assert!(payload_size < COMPRESSION_THRESHOLD);
+		match message {
+			Message::Generation(GenerationMessage::SessionError(message)) => assert_eq!(message.error.len(), COMPRESSION_THRESHOLD * 4),
+			_ => panic!("unexpected message"),
+		}
+	}
+
+	#[test]
+	fn small_message_is_not_compressed() {
+		let (payload_size, message) = serialize_and_deserialize(session_error_message(COMPRESSION_THRESHOLD / 4), CURRENT_HEADER_VERSION);
+		assert!(payload_size > COMPRESSION_THRESHOLD / 4);
+		match message {
+			Message::Generation(GenerationMessage::SessionError(message)) => assert_eq!(message.error.len(), COMPRESSION_THRESHOLD / 4),
+			_ => panic!("unexpected message"),
+		}
+	}
+
+	#[test]
+	fn session_error_code_is_serialized() {
+		let (_, message) = serialize_and_deserialize(session_error_message(1), CURRENT_HEADER_VERSION);
+		match message {
+			Message::Generation(GenerationMessage::SessionError(message)) => assert_eq!(message.code, Some(ErrorCode::Storage.into())),
+			_ => panic!("unexpected message"),
+		}
+	}
+
+	#[test]
+	fn session_error_without_code_is_deserialized() {
+		let message: SessionError = serde_json::from_str(r#"{
+			"session": "0x0000000000000000000000000000000000000000000000000000000000000000",
+			"session_nonce": 0,
+			"error": "error"
+		}"#).unwrap();
+		assert_eq!(message.code, None);
+		assert_eq!(Error::remote(message.code, message.error), Error::Remote(ErrorCode::Unknown, "error".into()));
+	}
+
+	#[test]
+	fn message_id_does_not_depend_on_connection_version() {
+		let id = message_id(&session_error_message(COMPRESSION_THRESHOLD * 4)).unwrap();
+		for version in MIN_HEADER_VERSION..CURRENT_HEADER_VERSION + 1 {
+			let (_, message) = serialize_and_deserialize(session_error_message(COMPRESSION_THRESHOLD * 4), version);
+			assert_eq!(message_id(&message).unwrap(), id);
+		}
+		assert!(message_id(&session_error_message(1)).unwrap() != id);
+	}
+
+	#[test]
+	fn message_is_not_compressed_when_using_legacy_version() {
+		let (payload_size, message) = serialize_and_deserialize(session_error_message(COMPRESSION_THRESHOLD * 4), LEGACY_HEADER_VERSION);
+		assert!(payload_size > COMPRESSION_THRESHOLD * 4);
+		match message {
+			Message::Generation(GenerationMessage::SessionError(message)) => assert_eq!(message.error.len(), COMPRESSION_THRESHOLD * 4),
+			_ => panic!("unexpected message"),
+		}
+	}
+
+	fn sign_and_deserialize(signer: &KeyPair, sequence: u64, session_nonce: Option<u64>, message: Message) -> SignedMessage {
+		sign_and_deserialize_with_version(signer, sequence, session_nonce, message, CURRENT_HEADER_VERSION)
+	}
+
+	fn sign_and_deserialize_with_version(signer: &KeyPair, sequence: u64, session_nonce: Option<u64>, message: Message, version: u8) -> SignedMessage {
+		let signer = PlainNodeKeyPair::new(signer.clone());
+		let serialized_message = sign_message(&signer, sequence, session_nonce, serialize_message(message, version).unwrap()).unwrap();
+		let serialized_message: Vec<_> = serialized_message.into();
+		let header = deserialize_header(&serialized_message[..MESSAGE_HEADER_SIZE]).unwrap();
+		deserialize_signed_message(&header, serialized_message[MESSAGE_HEADER_SIZE..].to_vec()).unwrap()
+	}
+
+	#[test]
+	fn signed_message_is_verified() {
+		let sender = Random.generate().unwrap();
+		let message = session_error_message(10);
+		let session_nonce = message.session_nonce();
+		match sign_and_deserialize(&sender, 1, session_nonce, message).verify(sender.public(), CURRENT_HEADER_VERSION, &mut ReceivedSequences::default()) {
+			Ok(Message::Generation(GenerationMessage::SessionError(message))) => assert_eq!(message.error.len(), 10),
+			_ => panic!("unexpected message"),
+		}
+	}
+
+	#[test]
+	fn message_signed_by_other_node_is_rejected() {
+		let sender = Random.generate().unwrap();
+		let other = Random.generate().unwrap();
+		let message = session_error_message(10);
+		let session_nonce = message.session_nonce();
+		assert_eq!(sign_and_deserialize(&other, 1, session_nonce, message).verify(sender.public(), CURRENT_HEADER_VERSION, &mut ReceivedSequences::default()).unwrap_err(),
+			Error::InvalidMessageSignature);
+	}
+
+	#[test]
+	fn message_signed_for_other_session_is_rejected() {
+		let sender = Random.generate().unwrap();
+		let message = session_error_message(10);
+		let session_nonce = message.session_nonce().map(|nonce| nonce + 1);
+		assert_eq!(sign_and_deserialize(&sender, 1, session_nonce, message).verify(sender.public(), CURRENT_HEADER_VERSION, &mut ReceivedSequences::default()).unwrap_err(),
+			Error::InvalidMessageSignature);
+	}
+
+	#[test]
+	fn unsigned_message_is_only_accepted_from_connection_without_envelopes() {
+		let serialized_message: Vec<_> = serialize_message(session_error_message(10), SHARES_INVENTORY_HEADER_VERSION).unwrap().into();
+		let header = deserialize_header(&serialized_message[..MESSAGE_HEADER_SIZE]).unwrap();
+		let payload = serialized_message[MESSAGE_HEADER_SIZE..].to_vec();
+		let sender = Random.generate().unwrap();
+		assert!(deserialize_signed_message(&header, payload.clone()).unwrap()
+			.verify(sender.public(), SHARES_INVENTORY_HEADER_VERSION, &mut ReceivedSequences::default()).is_ok());
+		assert_eq!(deserialize_signed_message(&header, payload).unwrap()
+			.verify(sender.public(), CURRENT_HEADER_VERSION, &mut ReceivedSequences::default()).unwrap_err(), Error::InvalidMessageSignature);
+	}
+
+	#[test]
+	fn message_with_invalid_signature_is_rejected_with_invalid_signature_error() {
+		let sender = Random.generate().unwrap();
+		let message = session_error_message(10);
+		let session_nonce = message.session_nonce();
+		let mut message = sign_and_deserialize(&sender, 1, session_nonce, message);
+		message.envelope.as_mut().unwrap().signature = Default::default();
+		assert_eq!(message.verify(sender.public(), CURRENT_HEADER_VERSION, &mut ReceivedSequences::default()).unwrap_err(),
+			Error::InvalidMessageSignature);
+	}
+
+	#[test]
+	fn replayed_message_is_rejected() {
+		let sender = Random.generate().unwrap();
+		let message = session_error_message(10);
+		let session_nonce = message.session_nonce();
+		let mut received_sequences = ReceivedSequences::starting_at(1);
+		assert!(sign_and_deserialize(&sender, 1, session_nonce, message.clone()).verify(sender.public(), CURRENT_HEADER_VERSION, &mut received_sequences).is_ok());
+		assert_eq!(sign_and_deserialize(&sender, 1, session_nonce, message.clone()).verify(sender.public(), CURRENT_HEADER_VERSION, &mut received_sequences).unwrap_err(),
+			Error::InvalidMessageSignature);
+		assert_eq!(sign_and_deserialize(&sender, 0, session_nonce, message).verify(sender.public(), CURRENT_HEADER_VERSION, &mut received_sequences).unwrap_err(),
+			Error::InvalidMessageSignature);
+	}
+
+	#[test]
+	fn message_with_forged_sequence_is_rejected() {
+		let sender = Random.generate().unwrap();
+		let message = session_error_message(10);
+		let session_nonce = message.session_nonce();
+		let signer = PlainNodeKeyPair::new(sender.clone());
+		let mut serialized_message: Vec<_> = sign_message(&signer, 1, session_nonce, serialize_message(message, CURRENT_HEADER_VERSION).unwrap()).unwrap().into();
+		serialized_message[MESSAGE_HEADER_SIZE + MESSAGE_ENVELOPE_SIZE] = 2;
+		let header = deserialize_header(&serialized_message[..MESSAGE_HEADER_SIZE]).unwrap();
+		assert_eq!(deserialize_signed_message(&header, serialized_message[MESSAGE_HEADER_SIZE..].to_vec()).unwrap()
+			.verify(sender.public(), CURRENT_HEADER_VERSION, &mut ReceivedSequences::default()).unwrap_err(), Error::InvalidMessageSignature);
+	}
+
+	#[test]
+	fn unsequenced_message_is_only_accepted_from_connection_without_sequenced_envelopes() {
+		let sender = Random.generate().unwrap();
+		let message = session_error_message(10);
+		let session_nonce = message.session_nonce();
+		let mut received_sequences = ReceivedSequences::default();
+		assert!(sign_and_deserialize_with_version(&sender, 1, session_nonce, message.clone(), ACKNOWLEDGEMENTS_HEADER_VERSION)
+			.verify(sender.public(), ACKNOWLEDGEMENTS_HEADER_VERSION, &mut received_sequences).is_ok());
+		assert!(sign_and_deserialize_with_version(&sender, 1, session_nonce, message.clone(), ACKNOWLEDGEMENTS_HEADER_VERSION)
+			.verify(sender.public(), ACKNOWLEDGEMENTS_HEADER_VERSION, &mut received_sequences).is_ok());
+		assert_eq!(sign_and_deserialize_with_version(&sender, 1, session_nonce, message, ACKNOWLEDGEMENTS_HEADER_VERSION)
+			.verify(sender.public(), CURRENT_HEADER_VERSION, &mut received_sequences).unwrap_err(), Error::InvalidMessageSignature);
+	}
+
+	#[test]
+	fn out_of_order_sequences_are_accepted_within_window() {
+		let mut received_sequences = ReceivedSequences::starting_at(1);
+		assert!(!received_sequences.on_received(0));
+		assert!(received_sequences.on_received(3));
+		assert!(received_sequences.on_received(1));
+		assert!(received_sequences.on_received(2));
+		assert!(!received_sequences.on_received(2));
+		assert!(received_sequences.on_received(3 + SEQUENCE_WINDOW_SIZE));
+		assert!(!received_sequences.on_received(3));
+		assert!(received_sequences.on_received(4));
+		assert!(!received_sequences.on_received(4));
+	}
+
+	#[test]
+	fn key_shares_inventory_is_serialized() {
+		let keys = vec![H256::from(1), H256::from(2)];
+		let message = Message::Cluster(ClusterMessage::KeySharesInventory(KeySharesInventory {
+			keys: keys.iter().cloned().map(Into::into).collect(),
+		}));
+		match serialize_and_deserialize(message, CURRENT_HEADER_VERSION).1 {
+			Message::Cluster(ClusterMessage::KeySharesInventory(message)) =>
+				assert_eq!(message.keys.into_iter().map(Into::into).collect::<Vec<H256>>(), keys),
+			_ => panic!("unexpected message"),
+		}
+	}
 }
@@ -24,9 +24,10 @@ mod shared_tcp_stream;
 mod write_message;
 
 pub use self::deadline::{deadline, Deadline, DeadlineStatus};
-pub use self::handshake::{handshake, accept_handshake, Handshake, HandshakeResult};
-pub use self::message::{MessageHeader, SerializedMessage, serialize_message, deserialize_message,
-	encrypt_message, fix_shared_key};
+pub use self::handshake::{handshake, accept_handshake, Handshake, HandshakeResult, HANDSHAKE_MESSAGE_SEQUENCE};
+pub use self::message::{MessageHeader, SerializedMessage, SignedMessage, ReceivedSequences, CURRENT_HEADER_VERSION, MIN_HEADER_VERSION, SHARES_INVENTORY_HEADER_VERSION,
+	SIGNED_ENVELOPE_HEADER_VERSION, ACKNOWLEDGEMENTS_HEADER_VERSION, serialize_message, deserialize_message, message_id, sign_message, encrypt_message,
+	negotiate_version, fix_shared_key};
 pub use self::read_header::{read_header, ReadHeader};
 pub use self::read_payload::{read_payload, read_encrypted_payload, ReadPayload};
 pub use self::read_message::{read_message, read_encrypted_message, ReadMessage};
@@ -19,8 +19,7 @@ use futures::{Poll, Future, Async};
 use tokio_io::AsyncRead;
 use ethkey::KeyPair;
 use key_server_cluster::Error;
-use key_server_cluster::message::Message;
-use key_server_cluster::io::{read_header, ReadHeader, read_payload, read_encrypted_payload, ReadPayload};
+use key_server_cluster::io::{SignedMessage, read_header, ReadHeader, read_payload, read_encrypted_payload, ReadPayload};
 
 /// Create future for read single message from the stream.
 pub fn read_message<A>(a: A) -> ReadMessage<A> where A: AsyncRead {
@@ -51,7 +50,7 @@ pub struct ReadMessage<A> {
 }
 
 impl<A> Future for ReadMessage<A> where A: AsyncRead {
-	type Item = (A, Result<Message, Error>);
+	type Item = (A, Result<SignedMessage, Error>);
 	type Error = io::Error;
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
@@ -20,8 +20,7 @@ use tokio_io::AsyncRead;
 use tokio_io::io::{read_exact, ReadExact};
 use ethkey::KeyPair;
 use key_server_cluster::Error;
-use key_server_cluster::message::Message;
-use key_server_cluster::io::message::{MessageHeader, deserialize_message, decrypt_message};
+use key_server_cluster::io::message::{MessageHeader, SignedMessage, deserialize_message, deserialize_signed_message, decrypt_message};
 
 /// Create future for read single message payload from the stream.
 pub fn read_payload<A>(a: A, header: MessageHeader) -> ReadPayload<A> where A: AsyncRead {
@@ -49,16 +48,16 @@ pub struct ReadPayload<A> {
 }
 
 impl<A> Future for ReadPayload<A> where A: AsyncRead {
-	type Item = (A, Result<Message, Error>);
+	type Item = (A, Result<SignedMessage, Error>);
 	type Error = io::Error;
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
 		let (read, data) = try_ready!(self.reader.poll());
 		let payload = if let Some(key) = self.key.take() {
 			decrypt_message(&key, data)
-				.and_then(|data| deserialize_message(&self.header, data))
+				.and_then(|data| deserialize_signed_message(&self.header, data))
 		} else {
-			deserialize_message(&self.header, data)
+			deserialize_message(&self.header, data).map(Into::into)
 		};
 		Ok((read, payload).into())
 	}
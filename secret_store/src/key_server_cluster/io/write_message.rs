@@ -19,44 +19,69 @@ use futures::{Future, Poll};
 use tokio_io::AsyncWrite;
 use tokio_io::io::{WriteAll, write_all};
 use ethkey::KeyPair;
+use key_server_cluster::NodeKeyPair;
 use key_server_cluster::message::Message;
-use key_server_cluster::io::{serialize_message, encrypt_message};
+use key_server_cluster::io::{MIN_HEADER_VERSION, SIGNED_ENVELOPE_HEADER_VERSION, serialize_message, sign_message, encrypt_message};
 
-/// Write plain message to the channel.
+/// Write plain message to the channel. Plain messages are only sent before version is negotiated
+/// => the oldest supported version is used.
 pub fn write_message<A>(a: A, message: Message) -> WriteMessage<A> where A: AsyncWrite {
-	let (error, future) = match serialize_message(message)
+	let (error, size, future) = match serialize_message(message, MIN_HEADER_VERSION)
 		.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string())) {
-		Ok(message) => (None, write_all(a, message.into())),
-		Err(error) => (Some(error), write_all(a, Vec::new())),
+		Ok(message) => write_serialized(a, message.into()),
+		Err(error) => (Some(error), 0, write_all(a, Vec::new())),
 	};
 	WriteMessage {
 		error: error,
+		size: size,
 		future: future,
 	}
 }
 
-/// Write encrypted message to the channel.
-pub fn write_encrypted_message<A>(a: A, key: &KeyPair, message: Message) -> WriteMessage<A> where A: AsyncWrite {
-	let (error, future) = match serialize_message(message)
+/// Write encrypted message of given protocol version to the channel. If version supports envelopes,
+/// message is signed by the sender node key. `sequence` is the sequence number of the message within the connection.
+pub fn write_encrypted_message<A>(a: A, key: &KeyPair, signer: &NodeKeyPair, version: u8, sequence: u64, message: Message) -> WriteMessage<A> where A: AsyncWrite {
+	let session_nonce = message.session_nonce();
+	let (error, size, future) = match serialize_message(message, version)
+		.and_then(|message| if version >= SIGNED_ENVELOPE_HEADER_VERSION {
+			sign_message(signer, sequence, session_nonce, message)
+		} else {
+			Ok(message)
+		})
 		.and_then(|message| encrypt_message(key, message))
 		.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string())) {
-		Ok(message) => (None, write_all(a, message.into())),
-		Err(error) => (Some(error), write_all(a, Vec::new())),
+		Ok(message) => write_serialized(a, message.into()),
+		Err(error) => (Some(error), 0, write_all(a, Vec::new())),
 	};
 
 
 	WriteMessage {
 		error: error,
+		size: size,
 		future: future,
 	}
 }
 
+/// Start writing serialized message to the channel.
+fn write_serialized<A>(a: A, message: Vec<u8>) -> (Option<io::Error>, usize, WriteAll<A, Vec<u8>>) where A: AsyncWrite {
+	let size = message.len();
+	(None, size, write_all(a, message))
+}
+
 /// Future message write.
 pub struct WriteMessage<A> {
 	error: Option<io::Error>,
+	size: usize,
 	future: WriteAll<A, Vec<u8>>,
 }
 
+impl<A> WriteMessage<A> {
+	/// Get size of the serialized message (including header), which is written to the channel.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+}
+
 impl<A> Future for WriteMessage<A> where A: AsyncWrite {
 	type Item = (A, Vec<u8>);
 	type Error = io::Error;
@@ -58,6 +58,8 @@ pub struct ConsensusSession<ConsensusTransport: JobTransport<PartialJobRequest=S
 	consensus_job: JobSession<KeyAccessJob, ConsensusTransport>,
 	/// Consensus group.
 	consensus_group: BTreeSet<NodeId>,
+	/// Nodes, which are preferred to be selected into consensus group, ordered by preference.
+	nodes_preference: Vec<NodeId>,
 	/// Computation job.
 	computation_job: Option<JobSession<ComputationExecutor, ComputationTransport>>,
 }
@@ -97,6 +99,7 @@ impl<ConsensusTransport, ComputationExecutor, ComputationTransport> ConsensusSes
 			requester: requester,
 			consensus_job: consensus_job,
 			consensus_group: BTreeSet::new(),
+			nodes_preference: Vec::new(),
 			computation_job: None,
 		})
 	}
@@ -169,6 +172,12 @@ impl<ConsensusTransport, ComputationExecutor, ComputationTransport> ConsensusSes
 		self.process_result(consensus_result)
 	}
 
+	/// Set nodes, which are preferred to be selected into consensus group, ordered by preference.
+	/// Other nodes are selected in their natural order, after all preferred nodes.
+	pub fn set_nodes_preference(&mut self, nodes_preference: Vec<NodeId>) {
+		self.nodes_preference = nodes_preference;
+	}
+
 	/// Select nodes for processing partial requests.
 	pub fn select_consensus_group(&mut self) -> Result<&BTreeSet<NodeId>, Error> {
 		debug_assert!(self.meta.self_node_id == self.meta.master_node_id);
@@ -179,7 +188,9 @@ impl<ConsensusTransport, ComputationExecutor, ComputationTransport> ConsensusSes
 		if self.consensus_group.is_empty() {
 			let consensus_group = self.consensus_job.result()?;
 			let is_self_in_consensus = consensus_group.contains(&self.meta.self_node_id);
-			self.consensus_group = consensus_group.into_iter().take(self.meta.threshold + 1).collect();
+			let preferred_nodes: Vec<_> = self.nodes_preference.iter().filter(|n| consensus_group.contains(n)).cloned().collect();
+			let other_nodes: Vec<_> = consensus_group.into_iter().filter(|n| !preferred_nodes.contains(n)).collect();
+			self.consensus_group = preferred_nodes.into_iter().chain(other_nodes).take(self.meta.threshold + 1).collect();
 
 			if is_self_in_consensus {
 				self.consensus_group.remove(&self.meta.master_node_id);
@@ -240,6 +251,11 @@ impl<ConsensusTransport, ComputationExecutor, ComputationTransport> ConsensusSes
 		Ok(())
 	}
 
+	/// When session is cancelled on this node.
+	pub fn on_session_cancelled(&mut self) {
+		self.state = ConsensusSessionState::Failed;
+	}
+
 	/// When error is received from node.
 	pub fn on_node_error(&mut self, node: &NodeId) -> Result<bool, Error> {
 		let is_self_master = self.meta.master_node_id == self.meta.self_node_id;
@@ -385,6 +401,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::EstablishingConsensus);
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 	}
@@ -396,6 +413,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 	}
@@ -411,6 +429,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::EstablishingConsensus);
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 	}
@@ -433,6 +452,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::EstablishingConsensus);
 		assert_eq!(session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: false,
+			key_versions: Vec::new(),
 		})).unwrap_err(), Error::ConsensusUnreachable);
 		assert_eq!(session.state(), ConsensusSessionState::Failed);
 	}
@@ -450,6 +470,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
@@ -468,6 +489,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::EstablishingConsensus);
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
@@ -573,6 +595,7 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2), NodeId::from(3)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.on_node_error(&NodeId::from(2)), Ok(false));
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
@@ -584,6 +607,7 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2), NodeId::from(3)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.on_node_error(&NodeId::from(3)), Ok(false));
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
@@ -595,6 +619,7 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.on_node_error(&NodeId::from(2)), Err(Error::ConsensusUnreachable));
 		assert_eq!(session.state(), ConsensusSessionState::Failed);
@@ -606,9 +631,11 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2), NodeId::from(3), NodeId::from(4)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		session.on_consensus_message(&NodeId::from(3), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
 		assert_eq!(session.on_node_error(&NodeId::from(3)), Ok(false));
@@ -622,12 +649,14 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2), NodeId::from(3), NodeId::from(4)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::WaitingForPartialResults);
 
 		session.on_consensus_message(&NodeId::from(3), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.on_node_error(&NodeId::from(2)), Ok(true));
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
@@ -644,6 +673,7 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
 		assert_eq!(session.on_node_error(&NodeId::from(2)), Err(Error::ConsensusUnreachable));
@@ -662,6 +692,7 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2), NodeId::from(3), NodeId::from(4)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
@@ -669,6 +700,7 @@ mod tests {
 
 		session.on_consensus_message(&NodeId::from(3), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.on_session_timeout(), Ok(true));
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
@@ -686,6 +718,7 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::WaitingForPartialResults);
@@ -700,9 +733,11 @@ mod tests {
 		session.initialize(vec![NodeId::from(1), NodeId::from(2), NodeId::from(3)].into_iter().collect()).unwrap();
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		session.on_consensus_message(&NodeId::from(3), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 
 		let consensus_group1 = session.select_consensus_group().unwrap().clone();
@@ -710,6 +745,23 @@ mod tests {
 		assert_eq!(consensus_group1, consensus_group2);
 	}
 
+	#[test]
+	fn preferred_nodes_are_selected_into_consensus_group() {
+		let mut session = make_master_consensus_session(1, None, None);
+		session.initialize(vec![NodeId::from(1), NodeId::from(2), NodeId::from(3)].into_iter().collect()).unwrap();
+		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+			is_confirmed: true,
+			key_versions: Vec::new(),
+		})).unwrap();
+		session.on_consensus_message(&NodeId::from(3), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+			is_confirmed: true,
+			key_versions: Vec::new(),
+		})).unwrap();
+
+		session.set_nodes_preference(vec![NodeId::from(3), NodeId::from(4)]);
+		assert_eq!(session.select_consensus_group().unwrap(), &vec![NodeId::from(1), NodeId::from(3)].into_iter().collect());
+	}
+
 	#[test]
 	fn consensus_session_complete_2_of_4() {
 		let mut session = make_master_consensus_session(1, None, None);
@@ -717,6 +769,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::EstablishingConsensus);
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 		session.disseminate_jobs(SquaredSumJobExecutor, DummyJobTransport::default()).unwrap();
@@ -733,6 +786,7 @@ mod tests {
 		assert_eq!(session.state(), ConsensusSessionState::EstablishingConsensus);
 		session.on_consensus_message(&NodeId::from(2), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 
@@ -741,6 +795,7 @@ mod tests {
 
 		session.on_consensus_message(&NodeId::from(3), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 
 		assert_eq!(session.on_node_error(&NodeId::from(2)).unwrap(), true);
@@ -754,6 +809,7 @@ mod tests {
 
 		session.on_consensus_message(&NodeId::from(4), &ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 			is_confirmed: true,
+			key_versions: Vec::new(),
 		})).unwrap();
 		assert_eq!(session.state(), ConsensusSessionState::ConsensusEstablished);
 
@@ -56,6 +56,8 @@ pub struct PartialDecryptionResponse {
 	pub shadow_point: Public,
 	/// Decryption shadow coefficient, if requested.
 	pub decrypt_shadow: Option<Vec<u8>>,
+	/// Proof of node decryption share, if shadow decryption is requested.
+	pub decrypt_proof: Option<Vec<u8>>,
 }
 
 impl DecryptionJob {
@@ -119,6 +121,11 @@ impl JobExecutor for DecryptionJob {
 		let decrypt_shadow = if partial_request.is_shadow_decryption { Some(math::generate_random_scalar()?) } else { None };
 		let common_point = self.key_share.common_point.as_ref().expect("DecryptionJob is only created when common_point is known; qed");
 		let (shadow_point, decrypt_shadow) = math::compute_node_shadow_point(&self.access_key, &common_point, &node_shadow, decrypt_shadow)?;
+		// proof reveals node_shadow * common_point => it is only visible to the requester
+		let decrypt_proof = if partial_request.is_shadow_decryption {
+			let decrypt_proof = math::compute_node_decryption_proof(&common_point, &node_shadow)?;
+			Some(encrypt(&self.requester, &DEFAULT_MAC, &decrypt_proof.to_bytes())?)
+		} else { None };
 		Ok(JobPartialRequestAction::Respond(PartialDecryptionResponse {
 			request_id: partial_request.id,
 			shadow_point: shadow_point,
@@ -126,6 +133,7 @@ impl JobExecutor for DecryptionJob {
 				None => None,
 				Some(decrypt_shadow) => Some(encrypt(&self.requester, &DEFAULT_MAC, &**decrypt_shadow)?),
 			},
+			decrypt_proof: decrypt_proof,
 		}))
 	}
 
@@ -133,7 +141,8 @@ impl JobExecutor for DecryptionJob {
 		if Some(&partial_response.request_id) != self.request_id.as_ref() {
 			return Ok(JobPartialResponseAction::Ignore);
 		}
-		if self.is_shadow_decryption != Some(partial_response.decrypt_shadow.is_some()) {
+		if self.is_shadow_decryption != Some(partial_response.decrypt_shadow.is_some())
+			|| self.is_shadow_decryption != Some(partial_response.decrypt_proof.is_some()) {
 			return Ok(JobPartialResponseAction::Reject);
 		}
 		Ok(JobPartialResponseAction::Accept)
@@ -151,12 +160,21 @@ impl JobExecutor for DecryptionJob {
 			common_point: if is_shadow_decryption {
 				Some(math::make_common_shadow_point(self.key_share.threshold, common_point.clone())?)
 			} else { None },
+			encrypted_point: if is_shadow_decryption {
+				Some(encrypted_point.clone())
+			} else { None },
 			decrypt_shadows: if is_shadow_decryption {
 				Some(partial_responses.values().map(|r| r.decrypt_shadow.as_ref()
 					.expect("is_shadow_decryption == true; decrypt_shadow.is_some() is checked in check_partial_response; qed")
 					.clone())
 					.collect())
 			} else { None },
+			decrypt_proofs: if is_shadow_decryption {
+				Some(partial_responses.values().map(|r| r.decrypt_proof.as_ref()
+					.expect("is_shadow_decryption == true; decrypt_proof.is_some() is checked in check_partial_response; qed")
+					.clone())
+					.collect())
+			} else { None },
 		})
 	}
 }
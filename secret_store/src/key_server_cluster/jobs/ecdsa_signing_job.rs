@@ -0,0 +1,151 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use ethkey::{Public, Secret, Signature};
+use bigint::hash::H256;
+use key_server_cluster::{Error, NodeId, DocumentKeyShare};
+use key_server_cluster::math;
+use key_server_cluster::jobs::job_session::{JobPartialRequestAction, JobPartialResponseAction, JobExecutor};
+
+/// ECDSA signing job.
+pub struct EcdsaSigningJob {
+	/// This node id.
+	self_node_id: NodeId,
+	/// Key share.
+	key_share: DocumentKeyShare,
+	/// Signature nonce public.
+	nonce_public: Public,
+	/// Share of inversion nonce.
+	inversion_nonce_share: Secret,
+	/// Share of random polynom with zero constant term, used to blind partial signature.
+	signature_zero_share: Option<Secret>,
+	/// Request id.
+	request_id: Option<Secret>,
+	/// Inversed nonce coefficient.
+	inversed_nonce_coeff: Option<Secret>,
+	/// Message hash.
+	message_hash: Option<H256>,
+}
+
+/// ECDSA signing job partial request.
+pub struct EcdsaPartialSigningRequest {
+	/// Request id.
+	pub id: Secret,
+	/// Inversed nonce coefficient.
+	pub inversed_nonce_coeff: Secret,
+	/// Message hash.
+	pub message_hash: H256,
+	/// Id of other nodes, participating in signing.
+	pub other_nodes_ids: BTreeSet<NodeId>,
+}
+
+/// ECDSA signing job partial response.
+pub struct EcdsaPartialSigningResponse {
+	/// Request id.
+	pub request_id: Secret,
+	/// Partial signature' s share.
+	pub partial_signature_s: Secret,
+}
+
+impl EcdsaSigningJob {
+	pub fn new_on_slave(self_node_id: NodeId, key_share: DocumentKeyShare, nonce_public: Public, inversion_nonce_share: Secret, signature_zero_share: Option<Secret>) -> Result<Self, Error> {
+		Ok(EcdsaSigningJob {
+			self_node_id: self_node_id,
+			key_share: key_share,
+			nonce_public: nonce_public,
+			inversion_nonce_share: inversion_nonce_share,
+			signature_zero_share: signature_zero_share,
+			request_id: None,
+			inversed_nonce_coeff: None,
+			message_hash: None,
+		})
+	}
+
+	pub fn new_on_master(self_node_id: NodeId, key_share: DocumentKeyShare, nonce_public: Public, inversion_nonce_share: Secret, signature_zero_share: Option<Secret>, inversed_nonce_coeff: Secret, message_hash: H256) -> Result<Self, Error> {
+		Ok(EcdsaSigningJob {
+			self_node_id: self_node_id,
+			key_share: key_share,
+			nonce_public: nonce_public,
+			inversion_nonce_share: inversion_nonce_share,
+			signature_zero_share: signature_zero_share,
+			request_id: Some(math::generate_random_scalar()?),
+			inversed_nonce_coeff: Some(inversed_nonce_coeff),
+			message_hash: Some(message_hash),
+		})
+	}
+}
+
+impl JobExecutor for EcdsaSigningJob {
+	type PartialJobRequest = EcdsaPartialSigningRequest;
+	type PartialJobResponse = EcdsaPartialSigningResponse;
+	type JobResponse = Signature;
+
+	fn prepare_partial_request(&self, node: &NodeId, nodes: &BTreeSet<NodeId>) -> Result<EcdsaPartialSigningRequest, Error> {
+		debug_assert!(nodes.len() > self.key_share.threshold * 2);
+
+		let request_id = self.request_id.as_ref()
+			.expect("prepare_partial_request is only called on master nodes; request_id is filed in constructor on master nodes; qed");
+		let inversed_nonce_coeff = self.inversed_nonce_coeff.as_ref()
+			.expect("prepare_partial_request is only called on master nodes; inversed_nonce_coeff is filed in constructor on master nodes; qed");
+		let message_hash = self.message_hash.as_ref()
+			.expect("prepare_partial_request is only called on master nodes; message_hash is filed in constructor on master nodes; qed");
+		let mut other_nodes_ids = nodes.clone();
+		other_nodes_ids.remove(node);
+
+		Ok(EcdsaPartialSigningRequest {
+			id: request_id.clone(),
+			inversed_nonce_coeff: inversed_nonce_coeff.clone(),
+			message_hash: message_hash.clone(),
+			other_nodes_ids: other_nodes_ids,
+		})
+	}
+
+	fn process_partial_request(&self, partial_request: EcdsaPartialSigningRequest) -> Result<JobPartialRequestAction<EcdsaPartialSigningResponse>, Error> {
+		if partial_request.other_nodes_ids.len() < self.key_share.threshold * 2
+			|| partial_request.other_nodes_ids.contains(&self.self_node_id)
+			|| partial_request.other_nodes_ids.iter().any(|n| !self.key_share.id_numbers.contains_key(n)) {
+			return Err(Error::InvalidMessage);
+		}
+
+		Ok(JobPartialRequestAction::Respond(EcdsaPartialSigningResponse {
+			request_id: partial_request.id,
+			partial_signature_s: math::compute_ecdsa_partial_signature(
+				&self.inversion_nonce_share,
+				&partial_request.inversed_nonce_coeff,
+				&self.nonce_public,
+				&self.key_share.secret_share,
+				&partial_request.message_hash,
+				self.signature_zero_share.as_ref()
+			)?,
+		}))
+	}
+
+	fn check_partial_response(&self, partial_response: &EcdsaPartialSigningResponse) -> Result<JobPartialResponseAction, Error> {
+		if Some(&partial_response.request_id) != self.request_id.as_ref() {
+			return Ok(JobPartialResponseAction::Ignore);
+		}
+
+		Ok(JobPartialResponseAction::Accept)
+	}
+
+	fn compute_response(&self, partial_responses: &BTreeMap<NodeId, EcdsaPartialSigningResponse>) -> Result<Signature, Error> {
+		let (partial_signatures, id_numbers): (Vec<_>, Vec<_>) = partial_responses.iter()
+			.map(|(node, response)| (&response.partial_signature_s, &self.key_share.id_numbers[node]))
+			.unzip();
+		math::compute_ecdsa_signature(&self.nonce_public, &partial_signatures, &id_numbers)
+	}
+}
@@ -16,6 +16,8 @@
 
 pub mod consensus_session;
 pub mod decryption_job;
+pub mod ecdsa_signing_job;
 pub mod job_session;
 pub mod key_access_job;
+pub mod re_encryption_job;
 pub mod signing_job;
@@ -0,0 +1,138 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use ethkey::{Public, Secret, Signature};
+use key_server_cluster::{Error, NodeId, DocumentKeyShare, ReEncryptedDocumentKey};
+use key_server_cluster::math;
+use key_server_cluster::jobs::job_session::{JobPartialRequestAction, JobPartialResponseAction, JobExecutor};
+
+/// Re-encryption job.
+pub struct ReEncryptionJob {
+	/// This node id.
+	self_node_id: NodeId,
+	/// Key share.
+	key_share: DocumentKeyShare,
+	/// Request id.
+	request_id: Option<Secret>,
+	/// Public key, which document key must be re-encrypted with && its signature, made by requester.
+	target: Option<(Public, Signature)>,
+}
+
+/// Re-encryption job partial request.
+pub struct PartialReEncryptionRequest {
+	/// Request id.
+	pub id: Secret,
+	/// Public key, which document key must be re-encrypted with.
+	pub target_public: Public,
+	/// Target public key signature, made by requester.
+	pub target_signature: Signature,
+	/// Id of other nodes, participating in re-encryption.
+	pub other_nodes_ids: BTreeSet<NodeId>,
+}
+
+/// Re-encryption job partial response.
+pub struct PartialReEncryptionResponse {
+	/// Request id.
+	pub request_id: Secret,
+	/// Node contribution to common point of re-encrypted document key.
+	pub common_shadow: Public,
+	/// Node contribution to encrypted point of re-encrypted document key.
+	pub reencryption_shadow: Public,
+}
+
+impl ReEncryptionJob {
+	pub fn new_on_slave(self_node_id: NodeId, key_share: DocumentKeyShare) -> Result<Self, Error> {
+		debug_assert!(key_share.common_point.is_some() && key_share.encrypted_point.is_some());
+		Ok(ReEncryptionJob {
+			self_node_id: self_node_id,
+			key_share: key_share,
+			request_id: None,
+			target: None,
+		})
+	}
+
+	pub fn new_on_master(self_node_id: NodeId, key_share: DocumentKeyShare, target_public: Public, target_signature: Signature) -> Result<Self, Error> {
+		debug_assert!(key_share.common_point.is_some() && key_share.encrypted_point.is_some());
+		Ok(ReEncryptionJob {
+			self_node_id: self_node_id,
+			key_share: key_share,
+			request_id: Some(math::generate_random_scalar()?),
+			target: Some((target_public, target_signature)),
+		})
+	}
+}
+
+impl JobExecutor for ReEncryptionJob {
+	type PartialJobRequest = PartialReEncryptionRequest;
+	type PartialJobResponse = PartialReEncryptionResponse;
+	type JobResponse = ReEncryptedDocumentKey;
+
+	fn prepare_partial_request(&self, node: &NodeId, nodes: &BTreeSet<NodeId>) -> Result<PartialReEncryptionRequest, Error> {
+		debug_assert!(nodes.len() == self.key_share.threshold + 1);
+
+		let request_id = self.request_id.as_ref()
+			.expect("prepare_partial_request is only called on master nodes; request_id is filed in constructor on master nodes; qed");
+		let &(ref target_public, ref target_signature) = self.target.as_ref()
+			.expect("prepare_partial_request is only called on master nodes; target is filed in constructor on master nodes; qed");
+		let mut other_nodes_ids = nodes.clone();
+		other_nodes_ids.remove(node);
+
+		Ok(PartialReEncryptionRequest {
+			id: request_id.clone(),
+			target_public: target_public.clone(),
+			target_signature: target_signature.clone(),
+			other_nodes_ids: other_nodes_ids,
+		})
+	}
+
+	fn process_partial_request(&self, partial_request: PartialReEncryptionRequest) -> Result<JobPartialRequestAction<PartialReEncryptionResponse>, Error> {
+		if partial_request.other_nodes_ids.len() != self.key_share.threshold
+			|| partial_request.other_nodes_ids.contains(&self.self_node_id)
+			|| partial_request.other_nodes_ids.iter().any(|n| !self.key_share.id_numbers.contains_key(n)) {
+			return Err(Error::InvalidMessage);
+		}
+
+		let self_id_number = &self.key_share.id_numbers[&self.self_node_id];
+		let other_id_numbers = partial_request.other_nodes_ids.iter().map(|n| &self.key_share.id_numbers[n]);
+		let node_shadow = math::compute_node_shadow(&self.key_share.secret_share, &self_id_number, other_id_numbers)?;
+		let common_point = self.key_share.common_point.as_ref().expect("ReEncryptionJob is only created when common_point is known; qed");
+		let (common_shadow, reencryption_shadow) = math::compute_node_reencryption_shadow(common_point, &node_shadow, &partial_request.target_public)?;
+		Ok(JobPartialRequestAction::Respond(PartialReEncryptionResponse {
+			request_id: partial_request.id,
+			common_shadow: common_shadow,
+			reencryption_shadow: reencryption_shadow,
+		}))
+	}
+
+	fn check_partial_response(&self, partial_response: &PartialReEncryptionResponse) -> Result<JobPartialResponseAction, Error> {
+		if Some(&partial_response.request_id) != self.request_id.as_ref() {
+			return Ok(JobPartialResponseAction::Ignore);
+		}
+		Ok(JobPartialResponseAction::Accept)
+	}
+
+	fn compute_response(&self, partial_responses: &BTreeMap<NodeId, PartialReEncryptionResponse>) -> Result<ReEncryptedDocumentKey, Error> {
+		let encrypted_point = self.key_share.encrypted_point.as_ref().expect("ReEncryptionJob is only created when encrypted_point is known; qed");
+		let joint_common_shadow = math::compute_public_sum(partial_responses.values().map(|r| &r.common_shadow))?;
+		let joint_reencryption_shadow = math::compute_public_sum(partial_responses.values().map(|r| &r.reencryption_shadow))?;
+		let reencrypted_secret = math::reencrypt_with_joint_shadow(self.key_share.threshold, encrypted_point, &joint_common_shadow, &joint_reencryption_shadow)?;
+		Ok(ReEncryptedDocumentKey {
+			common_point: reencrypted_secret.common_point,
+			encrypted_point: reencrypted_secret.encrypted_point,
+		})
+	}
+}
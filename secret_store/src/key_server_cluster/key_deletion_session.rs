@@ -0,0 +1,737 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeSet;
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use ethkey::{self, Signature};
+use key_server_cluster::{Error, NodeId, SessionMeta, KeyStorage, DocumentKeyShare};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::message::{Message, KeyDeletionMessage, InitializeKeyDeletionSession, ConfirmKeyDeletionInitialization,
+	CommitKeyDeletion, KeyDeletionCompleted, KeyDeletionSessionError};
+
+/// Key deletion session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get key deletion session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed. Returns key holders, which have failed to delete their key shares.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<BTreeSet<NodeId>, Error>;
+}
+
+/// Key deletion session.
+/// Deletes key shares of previously generated key from all key holders.
+/// Brief overview:
+/// 1) initialization: master node checks that requester is the author of the key && asks every connected key holder
+///   to confirm deletion
+/// 2) every key holder checks that requester is the author of the key && confirms deletion
+/// 3) when all connected key holders have confirmed deletion (and there are at least threshold + 1 of them), master
+///   node asks every key holder to delete its key share
+/// 4) every key holder deletes its key share, leaving tombstone in the key storage (so that the key with the same id
+///   could not be generated again) && reports deletion result to the master node. Deleted key share is kept in the
+///   key storage until purged, so that the key could be restored by key servers operators
+/// Key holders, which were not connected at the moment of initialization, which have disconnected after being asked to
+/// delete key share or have failed to delete key share, are reported back to the requester.
+pub struct SessionImpl {
+	/// Session metadata. Session id is the id of deleted key.
+	meta: SessionMeta,
+	/// Key share.
+	key_share: DocumentKeyShare,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of key deletion session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// === Values, filled on master node ===
+	/// Key holders, which have confirmed deletion (including this node).
+	confirmed_nodes: BTreeSet<NodeId>,
+	/// Key holders, which have deleted their key shares.
+	deleted_nodes: BTreeSet<NodeId>,
+	/// Key holders, which have failed to delete their key shares.
+	failed_nodes: BTreeSet<NodeId>,
+	/// === Values, filled on all nodes ===
+	/// Key deletion session result.
+	result: Option<Result<BTreeSet<NodeId>, Error>>,
+}
+
+/// Key deletion session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	// === Initialization states ===
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for every connected key holder to confirm deletion.
+	WaitingForConfirmations,
+	/// Slave node waits for commit request from master node.
+	WaitingForCommit,
+	/// Master node waits for every key holder to report deletion result.
+	WaitingForDeletionReports,
+
+	// === Final states of the session ===
+	/// Key shares are deleted.
+	Finished,
+	/// Failed to reach consensus on deletion. Key storage is left untouched.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new key deletion session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		if !params.key_share.id_numbers.contains_key(&params.meta.self_node_id)
+			|| !params.key_share.id_numbers.contains_key(&params.meta.master_node_id) {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+
+		Ok(SessionImpl {
+			meta: params.meta,
+			key_share: params.key_share,
+			key_storage: params.key_storage,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				confirmed_nodes: BTreeSet::new(),
+				deleted_nodes: BTreeSet::new(),
+				failed_nodes: BTreeSet::new(),
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	pub fn initialize(&self, requestor_signature: Signature, connected_nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		self.check_deletion_request(&requestor_signature)?;
+
+		// key holders, which are not connected, are not able to delete their shares
+		// => at least threshold + 1 key holders must confirm deletion
+		let (connected_holders, disconnected_holders): (BTreeSet<_>, BTreeSet<_>) = self.key_share.id_numbers.keys()
+			.cloned()
+			.partition(|n| n == self.node() || connected_nodes.contains(n));
+		if connected_holders.len() < self.key_share.threshold + 1 {
+			return Err(Error::ConsensusUnreachable);
+		}
+
+		// update state
+		data.confirmed_nodes.insert(self.node().clone());
+		data.failed_nodes = disconnected_holders;
+		data.state = SessionState::WaitingForConfirmations;
+
+		// start initialization
+		for node in connected_holders.iter().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::KeyDeletion(KeyDeletionMessage::InitializeKeyDeletionSession(InitializeKeyDeletionSession {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				requestor_signature: requestor_signature.clone().into(),
+			})))?;
+		}
+
+		self.try_commit(&mut *data)
+	}
+
+	/// Process key deletion message.
+	pub fn process_message(&self, sender: &NodeId, message: &KeyDeletionMessage) -> Result<(), Error> {
+		match message {
+			&KeyDeletionMessage::InitializeKeyDeletionSession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&KeyDeletionMessage::ConfirmKeyDeletionInitialization(ref message) =>
+				self.on_confirm_initialization(sender.clone(), message),
+			&KeyDeletionMessage::CommitKeyDeletion(ref message) =>
+				self.on_commit(sender.clone(), message),
+			&KeyDeletionMessage::KeyDeletionCompleted(ref message) =>
+				self.on_deletion_completed(sender.clone(), message),
+			&KeyDeletionMessage::KeyDeletionSessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+		}
+	}
+
+	/// When session initialization message is received.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeKeyDeletionSession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		self.check_deletion_request(&message.requestor_signature.clone().into())?;
+
+		// update state
+		data.state = SessionState::WaitingForCommit;
+
+		// confirm deletion
+		self.cluster.send(&sender, Message::KeyDeletion(KeyDeletionMessage::ConfirmKeyDeletionInitialization(ConfirmKeyDeletionInitialization {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When session initialization confirmation message is received.
+	pub fn on_confirm_initialization(&self, sender: NodeId, message: &ConfirmKeyDeletionInitialization) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForConfirmations {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !self.key_share.id_numbers.contains_key(&sender) || data.failed_nodes.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.confirmed_nodes.contains(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.confirmed_nodes.insert(sender);
+
+		self.try_commit(&mut *data)
+	}
+
+	/// When commit request is received.
+	pub fn on_commit(&self, sender: NodeId, message: &CommitKeyDeletion) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForCommit {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// delete key share && report result back to master node
+		let is_deleted = self.delete_key_share();
+		let mut failed_nodes = BTreeSet::new();
+		if !is_deleted {
+			failed_nodes.insert(self.node().clone());
+		}
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(failed_nodes));
+		self.completed.notify_all();
+
+		self.cluster.send(&sender, Message::KeyDeletion(KeyDeletionMessage::KeyDeletionCompleted(KeyDeletionCompleted {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			is_deleted: is_deleted,
+		})))
+	}
+
+	/// When deletion result is received.
+	pub fn on_deletion_completed(&self, sender: NodeId, message: &KeyDeletionCompleted) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForDeletionReports {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.confirmed_nodes.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.deleted_nodes.contains(&sender) || data.failed_nodes.contains(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		match message.is_deleted {
+			true => data.deleted_nodes.insert(sender),
+			false => {
+				warn!("{}: key deletion session: {} has failed to delete key share", self.node(), sender);
+				data.failed_nodes.insert(sender)
+			},
+		};
+
+		self.try_complete(&mut *data);
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &KeyDeletionSessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: key deletion session failed with error: {} from {}", self.node(), message.error, sender);
+
+		// when deletion is already committed, error on key holder only means that it has failed to delete its share
+		if data.state == SessionState::WaitingForDeletionReports {
+			if data.confirmed_nodes.contains(&sender) && !data.deleted_nodes.contains(&sender) {
+				data.failed_nodes.insert(sender);
+				self.try_complete(&mut *data);
+			}
+			return Ok(());
+		}
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Check that requester is the author of the key.
+	fn check_deletion_request(&self, requestor_signature: &Signature) -> Result<(), Error> {
+		let requestor_public = ethkey::recover(requestor_signature, &self.meta.id)?;
+		if self.key_share.author != requestor_public {
+			return Err(Error::AccessDenied);
+		}
+
+		Ok(())
+	}
+
+	/// Ask every confirmed key holder to delete its key share, if all connected key holders have confirmed deletion.
+	fn try_commit(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::WaitingForConfirmations
+			|| data.confirmed_nodes.len() + data.failed_nodes.len() != self.key_share.id_numbers.len() {
+			return Ok(());
+		}
+
+		data.state = SessionState::WaitingForDeletionReports;
+		for node in data.confirmed_nodes.iter().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::KeyDeletion(KeyDeletionMessage::CommitKeyDeletion(CommitKeyDeletion {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+			})))?;
+		}
+
+		match self.delete_key_share() {
+			true => data.deleted_nodes.insert(self.node().clone()),
+			false => data.failed_nodes.insert(self.node().clone()),
+		};
+
+		self.try_complete(data);
+		Ok(())
+	}
+
+	/// Complete session, if all key holders have reported deletion result.
+	fn try_complete(&self, data: &mut SessionData) {
+		if data.state != SessionState::WaitingForDeletionReports
+			|| data.deleted_nodes.len() + data.failed_nodes.len() != self.key_share.id_numbers.len() {
+			return;
+		}
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(data.failed_nodes.clone()));
+		self.completed.notify_all();
+	}
+
+	/// Delete key share of this node, leaving tombstone in the key storage. Returns true if key share has been deleted.
+	fn delete_key_share(&self) -> bool {
+		match self.key_storage.tombstone(&self.meta.id) {
+			Ok(()) => true,
+			Err(err) => {
+				warn!("{}: key deletion session has failed to delete key share: {}", self.node(), err);
+				false
+			},
+		}
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		if !self.key_share.id_numbers.contains_key(node) {
+			return;
+		}
+
+		match data.state {
+			// deletion is committed => key holder has failed to report deletion result
+			SessionState::WaitingForDeletionReports => {
+				if data.confirmed_nodes.contains(node) && !data.deleted_nodes.contains(node) && !data.failed_nodes.contains(node) {
+					warn!("{}: key deletion session: {} connection has timeouted before deletion has been reported", self.node(), node);
+					data.failed_nodes.insert(node.clone());
+					self.try_complete(&mut *data);
+				}
+			},
+			// consensus is not yet reached => nothing is deleted && all connected key holders must confirm deletion
+			SessionState::WaitingForInitialization | SessionState::WaitingForConfirmations | SessionState::WaitingForCommit => {
+				if (self.meta.self_node_id != self.meta.master_node_id && node != &self.meta.master_node_id)
+					|| data.failed_nodes.contains(node) {
+					return;
+				}
+
+				warn!("{}: key deletion session failed because {} connection has timeouted", self.node(), node);
+
+				data.state = SessionState::Failed;
+				data.result = Some(Err(Error::NodeDisconnected));
+				self.completed.notify_all();
+			},
+			SessionState::Finished | SessionState::Failed => (),
+		}
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		match data.state {
+			// deletion is committed => all key holders, which have not reported deletion result, have failed
+			SessionState::WaitingForDeletionReports => {
+				warn!("{}: key deletion session has timeouted before all key holders have reported deletion", self.node());
+
+				let unreported_nodes: Vec<_> = data.confirmed_nodes.iter()
+					.filter(|n| !data.deleted_nodes.contains(n) && !data.failed_nodes.contains(n))
+					.cloned()
+					.collect();
+				data.failed_nodes.extend(unreported_nodes);
+				self.try_complete(&mut *data);
+			},
+			SessionState::Finished | SessionState::Failed => (),
+			_ => {
+				warn!("{}: key deletion session failed with timeout", self.node());
+
+				data.state = SessionState::Failed;
+				data.result = Some(Err(Error::NodeDisconnected));
+				self.completed.notify_all();
+			},
+		}
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: key deletion session has been cancelled", self.node());
+
+		// deleted key shares could not be restored => only notify other nodes
+		// do not bother processing send error, as we already processing error
+		let error = Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(KeyDeletionSessionError {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.meta.self_node_id == self.meta.master_node_id {
+			self.cluster.broadcast(error)
+		} else {
+			self.cluster.send(&self.meta.master_node_id, error)
+		};
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<BTreeSet<NodeId>, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::{BTreeSet, BTreeMap};
+	use ethkey::{self, Random, Generator, KeyPair, Secret};
+	use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::cluster_sessions::ClusterSession;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, KeyDeletionMessage};
+	use super::{SessionImpl, SessionParams, SessionState, Session};
+
+	struct Node {
+		cluster: Arc<DummyCluster>,
+		key_storage: Arc<DummyKeyStorage>,
+		session: SessionImpl,
+	}
+
+	fn prepare_nodes(threshold: usize, num_nodes: usize) -> (KeyPair, Vec<Node>) {
+		let author = Random.generate().unwrap();
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let id_numbers: BTreeMap<NodeId, Secret> = (0..num_nodes)
+			.map(|_| (Random.generate().unwrap().public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		let master_node_id = id_numbers.keys().nth(0).cloned().unwrap();
+		let nodes = id_numbers.iter().map(|(node_id, id_number)| {
+			let key_share = DocumentKeyShare {
+				author: author.public().clone(),
+				threshold: threshold,
+				id_numbers: id_numbers.clone(),
+				secret_share: math::compute_polynom(&polynom, id_number).unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			};
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			key_storage.insert(SessionId::default(), key_share.clone()).unwrap();
+
+			let cluster = Arc::new(DummyCluster::new(node_id.clone()));
+			for node in id_numbers.keys() {
+				cluster.add_node(node.clone());
+			}
+
+			let session = SessionImpl::new(SessionParams {
+				meta: SessionMeta {
+					id: SessionId::default(),
+					master_node_id: master_node_id.clone(),
+					self_node_id: node_id.clone(),
+					threshold: threshold,
+				},
+				key_share: key_share,
+				key_storage: key_storage.clone(),
+				cluster: cluster.clone(),
+				nonce: 0,
+			}).unwrap();
+
+			Node {
+				cluster: cluster,
+				key_storage: key_storage,
+				session: session,
+			}
+		}).collect();
+		(author, nodes)
+	}
+
+	fn all_nodes(nodes: &[Node]) -> BTreeSet<NodeId> {
+		nodes.iter().map(|n| n.session.node().clone()).collect()
+	}
+
+	fn do_messages_exchange_until<F>(nodes: &[Node], mut cond: F) -> Result<(), Error> where F: FnMut(&NodeId, &NodeId, &Message) -> bool {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			if cond(&from, &to, &message) {
+				break;
+			}
+
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::KeyDeletion(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn key_is_deleted_on_all_key_holders() {
+		let (author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+		assert_eq!(nodes[0].session.wait(None), Ok(BTreeSet::new()));
+
+		// key shares are replaced with tombstones
+		assert!(nodes.iter().all(|n| !n.key_storage.contains(&SessionId::default())));
+		assert!(nodes.iter().all(|n| n.key_storage.is_tombstoned(&SessionId::default())));
+	}
+
+	#[test]
+	fn key_is_deleted_on_single_node() {
+		let (author, nodes) = prepare_nodes(0, 1);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		assert_eq!(nodes[0].session.state(), SessionState::Finished);
+		assert_eq!(nodes[0].session.wait(None), Ok(BTreeSet::new()));
+		assert!(nodes[0].key_storage.is_tombstoned(&SessionId::default()));
+	}
+
+	#[test]
+	fn key_shares_are_not_deleted_until_commit() {
+		let (author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::KeyDeletion(KeyDeletionMessage::CommitKeyDeletion(_)) => true,
+			_ => false,
+		}).unwrap();
+
+		// master has deleted its share, but slaves are waiting for commit
+		assert!(!nodes[0].key_storage.contains(&SessionId::default()));
+		assert!(nodes.iter().skip(1).all(|n| n.key_storage.contains(&SessionId::default())));
+		assert!(nodes.iter().skip(1).all(|n| n.session.state() == SessionState::WaitingForCommit));
+	}
+
+	#[test]
+	fn key_deletion_fails_if_requester_is_not_author() {
+		let (_, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(Random.generate().unwrap().secret(), &SessionId::default()).unwrap();
+
+		assert_eq!(nodes[0].session.initialize(signature, all_nodes(&nodes)), Err(Error::AccessDenied));
+		assert!(nodes.iter().all(|n| n.key_storage.contains(&SessionId::default())));
+	}
+
+	#[test]
+	fn key_deletion_fails_if_consensus_is_unreachable() {
+		let (author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+		let connected_nodes = vec![nodes[0].session.node().clone()].into_iter().collect();
+
+		assert_eq!(nodes[0].session.initialize(signature, connected_nodes), Err(Error::ConsensusUnreachable));
+		assert!(nodes[0].key_storage.contains(&SessionId::default()));
+	}
+
+	#[test]
+	fn disconnected_key_holders_are_reported() {
+		let (author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+		let disconnected_node = nodes[2].session.node().clone();
+		let connected_nodes = all_nodes(&nodes).into_iter().filter(|n| n != &disconnected_node).collect();
+
+		nodes[0].session.initialize(signature, connected_nodes).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert_eq!(nodes[0].session.wait(None), Ok(vec![disconnected_node].into_iter().collect()));
+		assert!(nodes.iter().take(2).all(|n| n.key_storage.is_tombstoned(&SessionId::default())));
+		assert!(nodes[2].key_storage.contains(&SessionId::default()));
+	}
+
+	#[test]
+	fn key_holder_which_has_disconnected_after_commit_is_reported() {
+		let (author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, to, message| match *message {
+			Message::KeyDeletion(KeyDeletionMessage::CommitKeyDeletion(_)) => to == nodes[2].session.node(),
+			_ => false,
+		}).unwrap();
+
+		nodes[0].session.on_node_timeout(nodes[2].session.node());
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert_eq!(nodes[0].session.state(), SessionState::Finished);
+		assert_eq!(nodes[0].session.wait(None), Ok(vec![nodes[2].session.node().clone()].into_iter().collect()));
+	}
+
+	#[test]
+	fn key_deletion_fails_if_key_holder_disconnects_before_confirmation() {
+		let (author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		nodes[0].session.on_node_timeout(nodes[1].session.node());
+		assert_eq!(nodes[0].session.state(), SessionState::Failed);
+		assert_eq!(nodes[0].session.wait(None), Err(Error::NodeDisconnected));
+		assert!(nodes[0].key_storage.contains(&SessionId::default()));
+	}
+
+	#[test]
+	fn key_deletion_message_fails_when_nonce_is_wrong() {
+		let (author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		let (_, message) = nodes[0].cluster.take_message().unwrap();
+		let mut message = match message {
+			Message::KeyDeletion(KeyDeletionMessage::InitializeKeyDeletionSession(message)) => message,
+			_ => unreachable!(),
+		};
+		message.session_nonce = 10;
+		assert_eq!(nodes[1].session.process_message(nodes[0].session.node(),
+			&KeyDeletionMessage::InitializeKeyDeletionSession(message)), Err(Error::ReplayProtection));
+	}
+}
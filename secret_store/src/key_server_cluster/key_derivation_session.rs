@@ -0,0 +1,676 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use ethkey::{self, Public, Secret, Signature};
+use bigint::hash::H256;
+use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, KeyMetadata};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::math;
+use key_server_cluster::message::{Message, KeyDerivationMessage, InitializeKeyDerivationSession, ConfirmKeyDerivationInitialization,
+	CommitKeyDerivation, KeyDerivationSessionError};
+
+/// Key derivation session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get key derivation session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed. Returns public portion of derived key.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<Public, Error>;
+}
+
+/// Key derivation session.
+/// Derives child key from previously generated parent key without running new distributed key generation,
+/// so that single generation session could back unbounded number of keys.
+/// Brief overview:
+/// 1) derived key id is computed as keccak(parent_key_id | derivation_path)
+/// 2) initialization: master node initializes the session on all other holders of the parent key
+/// 3) every key holder computes its share of derived key as parent_secret_share + delta, where delta is derived key id
+///   (taken as scalar) => derived secret is parent_secret + delta && derived public is parent_public + delta * G
+/// 4) every key holder sends public portion of its derived share to master node
+/// 5) master node restores derived public from these shares && asks every key holder to save derived share
+/// Derived key has the same author, threshold && key holders as the parent key. Since delta is public, derived keys
+/// are not independent from the parent key: whoever learns one of these secrets, learns all of them.
+pub struct SessionImpl {
+	/// Session metadata. Session id is the id of derived key.
+	meta: SessionMeta,
+	/// Parent key id.
+	parent_key_id: SessionId,
+	/// Parent key share.
+	parent_key_share: DocumentKeyShare,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Parent key id.
+	pub parent_key_id: SessionId,
+	/// Parent key share.
+	pub parent_key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of key derivation session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// === Values, filled on master node ===
+	/// Public portions of derived shares, received from key holders (including this node).
+	public_shares: BTreeMap<NodeId, Public>,
+	/// === Values, filled on all nodes ===
+	/// Derived secret share.
+	derived_share: Option<Secret>,
+	/// Key derivation session result.
+	result: Option<Result<Public, Error>>,
+}
+
+/// Key derivation session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	// === Initialization states ===
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for every other node to send public portion of derived share.
+	WaitingForPublicShares,
+	/// Slave node waits for commit request from master node.
+	WaitingForCommit,
+
+	// === Final states of the session ===
+	/// Derived share is saved.
+	Finished,
+	/// Failed to derive key. Key storage is left untouched.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new key derivation session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		if !params.parent_key_share.id_numbers.contains_key(&params.meta.self_node_id)
+			|| !params.parent_key_share.id_numbers.contains_key(&params.meta.master_node_id) {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+
+		Ok(SessionImpl {
+			meta: params.meta,
+			parent_key_id: params.parent_key_id,
+			parent_key_share: params.parent_key_share,
+			key_storage: params.key_storage,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				public_shares: BTreeMap::new(),
+				derived_share: None,
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	pub fn initialize(&self, derivation_path: H256, requestor_signature: Signature) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		self.check_derivation_request(&derivation_path, &requestor_signature)?;
+
+		// update state
+		let public_share = self.derive_share(&mut *data)?;
+		data.public_shares.insert(self.node().clone(), public_share);
+		data.state = SessionState::WaitingForPublicShares;
+
+		// start initialization
+		for node in self.parent_key_share.id_numbers.keys().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::KeyDerivation(KeyDerivationMessage::InitializeKeyDerivationSession(InitializeKeyDerivationSession {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				parent_key_id: self.parent_key_id.clone().into(),
+				derivation_path: derivation_path.clone().into(),
+				requestor_signature: requestor_signature.clone().into(),
+			})))?;
+		}
+
+		self.try_commit(&mut *data)
+	}
+
+	/// Process key derivation message.
+	pub fn process_message(&self, sender: &NodeId, message: &KeyDerivationMessage) -> Result<(), Error> {
+		match message {
+			&KeyDerivationMessage::InitializeKeyDerivationSession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&KeyDerivationMessage::ConfirmKeyDerivationInitialization(ref message) =>
+				self.on_confirm_initialization(sender.clone(), message),
+			&KeyDerivationMessage::CommitKeyDerivation(ref message) =>
+				self.on_commit(sender.clone(), message),
+			&KeyDerivationMessage::KeyDerivationSessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+		}
+	}
+
+	/// When session initialization message is received.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeKeyDerivationSession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if self.parent_key_id != *message.parent_key_id {
+			return Err(Error::InvalidMessage);
+		}
+
+		self.check_derivation_request(&*message.derivation_path, &message.requestor_signature.clone().into())?;
+
+		// update state
+		let public_share = self.derive_share(&mut *data)?;
+		data.state = SessionState::WaitingForCommit;
+
+		// send public portion of derived share back to master node
+		self.cluster.send(&sender, Message::KeyDerivation(KeyDerivationMessage::ConfirmKeyDerivationInitialization(ConfirmKeyDerivationInitialization {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			public_share: public_share.into(),
+		})))
+	}
+
+	/// When session initialization confirmation message is received.
+	pub fn on_confirm_initialization(&self, sender: NodeId, message: &ConfirmKeyDerivationInitialization) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForPublicShares {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !self.parent_key_share.id_numbers.contains_key(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.public_shares.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.public_shares.insert(sender, message.public_share.clone().into());
+
+		self.try_commit(&mut *data)
+	}
+
+	/// When commit request is received.
+	pub fn on_commit(&self, sender: NodeId, message: &CommitKeyDerivation) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForCommit {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		self.save_derived_share(&mut *data, message.derived_public.clone().into())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &KeyDerivationSessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: key derivation session failed with error: {} from {}", self.node(), message.error, sender);
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Check that derived key id matches derivation path && that requester is the author of the parent key.
+	fn check_derivation_request(&self, derivation_path: &H256, requestor_signature: &Signature) -> Result<(), Error> {
+		if math::compute_derived_key_id(&self.parent_key_id, derivation_path) != self.meta.id {
+			return Err(Error::InvalidMessage);
+		}
+
+		let requestor_public = ethkey::recover(requestor_signature, &self.meta.id)?;
+		if self.parent_key_share.author != requestor_public {
+			return Err(Error::AccessDenied);
+		}
+
+		Ok(())
+	}
+
+	/// Compute derived share of this node. Returns public portion of derived share.
+	fn derive_share(&self, data: &mut SessionData) -> Result<Public, Error> {
+		let delta = math::compute_key_derivation_delta(&self.meta.id)?;
+		let derived_share = math::compute_derived_secret_share(&self.parent_key_share.secret_share, &delta)?;
+		let public_share = math::compute_public_share(&derived_share)?;
+		data.derived_share = Some(derived_share);
+		Ok(public_share)
+	}
+
+	/// Ask every node to save derived share, if public shares from all nodes are received.
+	fn try_commit(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::WaitingForPublicShares || data.public_shares.len() != self.parent_key_share.id_numbers.len() {
+			return Ok(());
+		}
+
+		let derived_public = self.compute_derived_public(&data.public_shares)?;
+		self.save_derived_share(data, derived_public.clone())?;
+
+		for node in self.parent_key_share.id_numbers.keys().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::KeyDerivation(KeyDerivationMessage::CommitKeyDerivation(CommitKeyDerivation {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				derived_public: derived_public.clone().into(),
+			})))?;
+		}
+
+		Ok(())
+	}
+
+	/// Restore derived public from public portions of derived shares of all key holders.
+	fn compute_derived_public(&self, public_shares: &BTreeMap<NodeId, Public>) -> Result<Public, Error> {
+		let id_numbers: Vec<_> = public_shares.keys().map(|n| &self.parent_key_share.id_numbers[n]).collect();
+		let public_shares: Vec<_> = public_shares.values().collect();
+		let derived_public = math::compute_joint_public_from_shares(&public_shares, &id_numbers)?;
+
+		// any threshold + 1 shares must lead to the same public => otherwise some node has derived wrong share
+		let threshold = self.parent_key_share.threshold;
+		if public_shares.len() > threshold + 1
+			&& math::compute_joint_public_from_shares(&public_shares[..threshold + 1], &id_numbers[..threshold + 1])? != derived_public {
+			return Err(Error::InvalidMessage);
+		}
+
+		Ok(derived_public)
+	}
+
+	/// Save derived share to the key storage.
+	fn save_derived_share(&self, data: &mut SessionData, derived_public: Public) -> Result<(), Error> {
+		// check that parent key share has not been modified since session has been created
+		let parent_key_share = self.key_storage.get(&self.parent_key_id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		if parent_key_share.secret_share != self.parent_key_share.secret_share || parent_key_share.id_numbers != self.parent_key_share.id_numbers {
+			return Err(Error::StaleKeyShare);
+		}
+		if self.key_storage.contains(&self.meta.id) {
+			return Err(Error::DuplicateSessionId);
+		}
+
+		self.key_storage.insert(self.meta.id.clone(), DocumentKeyShare {
+			author: self.parent_key_share.author.clone(),
+			threshold: self.parent_key_share.threshold,
+			id_numbers: self.parent_key_share.id_numbers.clone(),
+			secret_share: data.derived_share.clone()
+				.expect("save_derived_share is called after derived share is computed; qed"),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: KeyMetadata::generated(self.parent_key_share.author.clone()),
+		}).map_err(|e| Error::KeyStorage(e.into()))?;
+
+		// update state
+		data.state = SessionState::Finished;
+		data.clear_secrets();
+		data.result = Some(Ok(derived_public));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+impl SessionData {
+	/// Forget derived share, which is not required once the session is completed.
+	fn clear_secrets(&mut self) {
+		self.derived_share = None;
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		// every key holder is required to complete the session
+		if !self.parent_key_share.id_numbers.contains_key(node) {
+			return;
+		}
+
+		warn!("{}: key derivation session failed because {} connection has timeouted", self.node(), node);
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		warn!("{}: key derivation session failed with timeout", self.node());
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: key derivation session has been cancelled", self.node());
+
+		// key storage is only updated when session is finished => nothing to restore
+		// do not bother processing send error, as we already processing error
+		let error = Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(KeyDerivationSessionError {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.meta.self_node_id == self.meta.master_node_id {
+			self.cluster.broadcast(error)
+		} else {
+			self.cluster.send(&self.meta.master_node_id, error)
+		};
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<Public, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::BTreeMap;
+	use ethkey::{self, Random, Generator, KeyPair, Secret};
+	use bigint::hash::H256;
+	use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, KeyDerivationMessage};
+	use super::{SessionImpl, SessionParams, SessionState, Session};
+
+	struct Node {
+		cluster: Arc<DummyCluster>,
+		key_storage: Arc<DummyKeyStorage>,
+		session: SessionImpl,
+	}
+
+	fn derivation_path() -> H256 {
+		H256::from(7)
+	}
+
+	fn derived_key_id() -> SessionId {
+		math::compute_derived_key_id(&SessionId::default(), &derivation_path())
+	}
+
+	fn prepare_nodes(threshold: usize, num_nodes: usize) -> (Vec<Secret>, KeyPair, Vec<Node>) {
+		let author = Random.generate().unwrap();
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let id_numbers: BTreeMap<NodeId, Secret> = (0..num_nodes)
+			.map(|_| (Random.generate().unwrap().public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		let master_node_id = id_numbers.keys().nth(0).cloned().unwrap();
+		let nodes = id_numbers.iter().map(|(node_id, id_number)| {
+			let key_share = DocumentKeyShare {
+				author: author.public().clone(),
+				threshold: threshold,
+				id_numbers: id_numbers.clone(),
+				secret_share: math::compute_polynom(&polynom, id_number).unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			};
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			key_storage.insert(SessionId::default(), key_share.clone()).unwrap();
+
+			let cluster = Arc::new(DummyCluster::new(node_id.clone()));
+			for node in id_numbers.keys() {
+				cluster.add_node(node.clone());
+			}
+
+			let session = SessionImpl::new(SessionParams {
+				meta: SessionMeta {
+					id: derived_key_id(),
+					master_node_id: master_node_id.clone(),
+					self_node_id: node_id.clone(),
+					threshold: threshold,
+				},
+				parent_key_id: SessionId::default(),
+				parent_key_share: key_share,
+				key_storage: key_storage.clone(),
+				cluster: cluster.clone(),
+				nonce: 0,
+			}).unwrap();
+
+			Node {
+				cluster: cluster,
+				key_storage: key_storage,
+				session: session,
+			}
+		}).collect();
+		(polynom, author, nodes)
+	}
+
+	fn do_messages_exchange_until<F>(nodes: &[Node], mut cond: F) -> Result<(), Error> where F: FnMut(&NodeId, &NodeId, &Message) -> bool {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			if cond(&from, &to, &message) {
+				break;
+			}
+
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::KeyDerivation(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn key_is_derived_on_all_key_holders() {
+		let (polynom, author, nodes) = prepare_nodes(2, 4);
+		let signature = ethkey::sign(author.secret(), &derived_key_id()).unwrap();
+
+		nodes[0].session.initialize(derivation_path(), signature).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+
+		// derived public is parent public, shifted by delta
+		let delta = math::compute_key_derivation_delta(&derived_key_id()).unwrap();
+		let parent_public = math::compute_public_share(&polynom[0]).unwrap();
+		let derived_public = math::compute_derived_public(&parent_public, &delta).unwrap();
+		assert!(nodes.iter().all(|n| n.session.wait(None) == Ok(derived_public.clone())));
+
+		// derived key shares are stored alongside parent key shares
+		for node in &nodes {
+			let parent_key_share = node.key_storage.get(&SessionId::default()).unwrap();
+			let derived_key_share = node.key_storage.get(&derived_key_id()).unwrap();
+			assert_eq!(derived_key_share.author, parent_key_share.author);
+			assert_eq!(derived_key_share.threshold, parent_key_share.threshold);
+			assert_eq!(derived_key_share.id_numbers, parent_key_share.id_numbers);
+			assert_eq!(derived_key_share.secret_share, math::compute_derived_secret_share(&parent_key_share.secret_share, &delta).unwrap());
+			assert!(derived_key_share.common_point.is_none());
+		}
+	}
+
+	#[test]
+	fn key_is_derived_on_single_node() {
+		let (polynom, author, nodes) = prepare_nodes(0, 1);
+		let signature = ethkey::sign(author.secret(), &derived_key_id()).unwrap();
+
+		nodes[0].session.initialize(derivation_path(), signature).unwrap();
+		assert_eq!(nodes[0].session.state(), SessionState::Finished);
+
+		let delta = math::compute_key_derivation_delta(&derived_key_id()).unwrap();
+		let parent_public = math::compute_public_share(&polynom[0]).unwrap();
+		assert_eq!(nodes[0].session.wait(None), Ok(math::compute_derived_public(&parent_public, &delta).unwrap()));
+	}
+
+	#[test]
+	fn derived_shares_are_not_saved_until_commit() {
+		let (_, author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &derived_key_id()).unwrap();
+
+		nodes[0].session.initialize(derivation_path(), signature).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::KeyDerivation(KeyDerivationMessage::CommitKeyDerivation(_)) => true,
+			_ => false,
+		}).unwrap();
+
+		// master has saved its share, but slaves are waiting for commit
+		assert!(nodes[0].key_storage.contains(&derived_key_id()));
+		assert!(nodes.iter().skip(1).all(|n| !n.key_storage.contains(&derived_key_id())));
+		assert!(nodes.iter().skip(1).all(|n| n.session.state() == SessionState::WaitingForCommit));
+	}
+
+	#[test]
+	fn key_derivation_fails_if_requester_is_not_author_of_parent_key() {
+		let (_, _, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(Random.generate().unwrap().secret(), &derived_key_id()).unwrap();
+
+		assert_eq!(nodes[0].session.initialize(derivation_path(), signature), Err(Error::AccessDenied));
+		assert!(!nodes[0].key_storage.contains(&derived_key_id()));
+	}
+
+	#[test]
+	fn key_derivation_fails_if_derivation_path_does_not_match_session_id() {
+		let (_, author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &derived_key_id()).unwrap();
+
+		assert_eq!(nodes[0].session.initialize(H256::from(8), signature), Err(Error::InvalidMessage));
+	}
+
+	#[test]
+	fn key_derivation_fails_if_derived_key_already_exists() {
+		let (_, author, nodes) = prepare_nodes(0, 1);
+		let signature = ethkey::sign(author.secret(), &derived_key_id()).unwrap();
+		let parent_key_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		nodes[0].key_storage.insert(derived_key_id(), parent_key_share).unwrap();
+
+		assert_eq!(nodes[0].session.initialize(derivation_path(), signature), Err(Error::DuplicateSessionId));
+	}
+
+	#[test]
+	fn derived_key_could_be_used_for_encryption() {
+		let (polynom, author, nodes) = prepare_nodes(1, 3);
+		let signature = ethkey::sign(author.secret(), &derived_key_id()).unwrap();
+
+		nodes[0].session.initialize(derivation_path(), signature).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		let derived_public = nodes[0].session.wait(None).unwrap();
+
+		// document key, encrypted with derived public, is decrypted with derived secret
+		let mut derived_secret = polynom[0].clone();
+		derived_secret.add(&math::compute_key_derivation_delta(&derived_key_id()).unwrap()).unwrap();
+		let document_key = math::generate_random_point().unwrap();
+		let encrypted_document_key = math::encrypt_secret(&document_key, &derived_public).unwrap();
+		assert_eq!(math::decrypt_with_joint_secret(&encrypted_document_key.encrypted_point, &encrypted_document_key.common_point, &derived_secret).unwrap(),
+			document_key);
+	}
+}
@@ -0,0 +1,762 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::iter::once;
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use ethkey::{self, Public, Secret, Signature};
+use ethcrypto::ecies::{encrypt_single_message, decrypt_single_message};
+use hash::keccak;
+use key_server_cluster::{Error, NodeId, SessionMeta, KeyStorage, DocumentKeyShare, NodeKeyPair,
+	ExportedKeyShare, ExportedShareContribution};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::math;
+use key_server_cluster::message::{Message, KeyExportMessage, InitializeKeyExportSession, KeyExportContribution,
+	KeyExportSessionError};
+
+/// Key export session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get key export session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed. Returns key shares for all target nodes.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<Vec<ExportedKeyShare>, Error>;
+}
+
+/// Key export session.
+/// Is used to re-share the key among nodes of other (target) cluster, so that nobody (including master node && key server
+/// operator, delivering key shares to target nodes) could reconstruct the joint secret or secret shares of other nodes.
+/// Brief overview:
+/// 1) initialization: master node (which must be holding the key) selects threshold + 1 contributing key holders
+///   && generates random id numbers of target nodes
+/// 2) every contributor i computes its contribution to the joint secret: w_i = share_i * Lagrange coefficient of i,
+///   generates random polynom f_i of target threshold degree with f_i(0) = w_i && computes commitments (f_i coefficients * G)
+///   && values f_i(x_j) for every target node j. Every value is encrypted with the key, agreed by contributor && target node j.
+///   Commitments && encrypted values are sent to master node
+/// 3) master node groups contributions by target nodes && returns resulting key shares, which must be delivered to target nodes
+/// 4) target node j checks every decrypted value against commitments && saves sum(f_i(x_j)) as its secret share (see `import_key_share`).
+///   Sum of commitments to constant terms is the public of the exported key
+/// Key shares of this cluster are never changed by the session.
+pub struct SessionImpl {
+	/// Session metadata.
+	meta: SessionMeta,
+	/// This node key pair.
+	self_key_pair: Arc<NodeKeyPair>,
+	/// Key share.
+	key_share: DocumentKeyShare,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// This node key pair.
+	pub self_key_pair: Arc<NodeKeyPair>,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of key export session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// === Values, filled on master node ===
+	/// Key holders, contributing to the key shares of target nodes.
+	contributors: BTreeSet<NodeId>,
+	/// Key threshold in the target cluster.
+	threshold: usize,
+	/// Id numbers of target nodes.
+	id_numbers: BTreeMap<NodeId, Secret>,
+	/// Received contributions.
+	contributions: BTreeMap<NodeId, Contribution>,
+	/// === Values, filled on all nodes ===
+	/// Key export session result.
+	result: Option<Result<Vec<ExportedKeyShare>, Error>>,
+}
+
+/// Contribution of single key holder to the key shares of target nodes.
+#[derive(Debug)]
+struct Contribution {
+	/// Commitments to coefficients of key holder polynom.
+	commitments: Vec<Public>,
+	/// Values of key holder polynom at id numbers of target nodes, encrypted for target nodes.
+	encrypted_sub_shares: BTreeMap<NodeId, Vec<u8>>,
+}
+
+/// Key export session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for contributions of other key holders.
+	WaitingForContributions,
+	/// Key is exported.
+	Finished,
+	/// Failed to export key.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new key export session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		if !params.key_share.id_numbers.contains_key(&params.meta.self_node_id)
+			|| !params.key_share.id_numbers.contains_key(&params.meta.master_node_id) {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+
+		Ok(SessionImpl {
+			meta: params.meta,
+			self_key_pair: params.self_key_pair,
+			key_share: params.key_share,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				contributors: BTreeSet::new(),
+				threshold: 0,
+				id_numbers: BTreeMap::new(),
+				contributions: BTreeMap::new(),
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	pub fn initialize(&self, admin_signature: Signature, targets: BTreeSet<NodeId>, threshold: usize, connected_nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if threshold >= targets.len() {
+			return Err(Error::InvalidThreshold);
+		}
+		self.check_admin_signature(&admin_signature)?;
+
+		// select contributors: this node && first connected key holders
+		let contributors: BTreeSet<_> = once(self.node())
+			.chain(self.key_share.id_numbers.keys().filter(|n| *n != self.node() && connected_nodes.contains(n)))
+			.take(self.key_share.threshold + 1)
+			.cloned()
+			.collect();
+		if contributors.len() != self.key_share.threshold + 1 {
+			return Err(Error::ConsensusUnreachable);
+		}
+
+		// every target node gets random id number
+		let id_numbers = targets.into_iter()
+			.map(|n| math::generate_random_scalar().map(|id_number| (n, id_number)))
+			.collect::<Result<BTreeMap<_, _>, _>>()?;
+
+		// update state
+		let contribution = compute_contribution(&*self.self_key_pair, &self.key_share, self.node(), &contributors, threshold, &id_numbers)?;
+		data.contributions.insert(self.node().clone(), contribution);
+		data.contributors = contributors.clone();
+		data.threshold = threshold;
+		data.id_numbers = id_numbers.clone();
+		data.state = SessionState::WaitingForContributions;
+
+		// ask other contributors to compute their contributions
+		for node in contributors.iter().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::KeyExport(KeyExportMessage::InitializeKeyExportSession(InitializeKeyExportSession {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				admin_signature: admin_signature.clone().into(),
+				contributors: contributors.iter().cloned().map(Into::into).collect(),
+				threshold: threshold,
+				id_numbers: id_numbers.iter().map(|(k, v)| (k.clone().into(), v.clone().into())).collect(),
+			})))?;
+		}
+
+		self.try_complete(&mut *data);
+		Ok(())
+	}
+
+	/// Process key export message.
+	pub fn process_message(&self, sender: &NodeId, message: &KeyExportMessage) -> Result<(), Error> {
+		match message {
+			&KeyExportMessage::InitializeKeyExportSession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&KeyExportMessage::KeyExportContribution(ref message) =>
+				self.on_contribution(sender.clone(), message),
+			&KeyExportMessage::KeyExportSessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+		}
+	}
+
+	/// When contribution is requested by master node.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeKeyExportSession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		self.check_admin_signature(&message.admin_signature.clone().into())?;
+
+		// check that exactly threshold + 1 key holders are contributing
+		let contributors: BTreeSet<NodeId> = message.contributors.iter().cloned().map(Into::into).collect();
+		if !contributors.contains(self.node()) || !contributors.contains(&sender)
+			|| contributors.len() != self.key_share.threshold + 1
+			|| contributors.iter().any(|n| !self.key_share.id_numbers.contains_key(n)) {
+			return Err(Error::InvalidMessage);
+		}
+		let id_numbers: BTreeMap<NodeId, Secret> = message.id_numbers.iter()
+			.map(|(k, v)| (k.clone().into(), v.clone().into()))
+			.collect();
+		if message.threshold >= id_numbers.len() {
+			return Err(Error::InvalidThreshold);
+		}
+
+		// compute contribution && send it to master node
+		let contribution = compute_contribution(&*self.self_key_pair, &self.key_share, self.node(), &contributors, message.threshold, &id_numbers)?;
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(Vec::new()));
+		self.completed.notify_all();
+
+		self.cluster.send(&sender, Message::KeyExport(KeyExportMessage::KeyExportContribution(KeyExportContribution {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			commitments: contribution.commitments.into_iter().map(Into::into).collect(),
+			encrypted_sub_shares: contribution.encrypted_sub_shares.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+		})))
+	}
+
+	/// When contribution is received by master node.
+	pub fn on_contribution(&self, sender: NodeId, message: &KeyExportContribution) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForContributions {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.contributors.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.contributions.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		// check that contribution is computed for every target node, using polynom of target threshold degree
+		let contribution = Contribution {
+			commitments: message.commitments.iter().cloned().map(Into::into).collect(),
+			encrypted_sub_shares: message.encrypted_sub_shares.iter().map(|(k, v)| (k.clone().into(), v.clone().into())).collect(),
+		};
+		if contribution.commitments.len() != data.threshold + 1
+			|| !contribution.encrypted_sub_shares.keys().eq(data.id_numbers.keys()) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.contributions.insert(sender, contribution);
+
+		self.try_complete(&mut *data);
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &KeyExportSessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: key export session failed with error: {} from {}", self.node(), message.error, sender);
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return Ok(());
+		}
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Group contributions by target nodes, if all contributions are received.
+	fn try_complete(&self, data: &mut SessionData) {
+		if data.state != SessionState::WaitingForContributions || data.contributions.len() != data.contributors.len() {
+			return;
+		}
+
+		let result = self.make_key_shares(data);
+		data.state = match result.is_ok() {
+			true => SessionState::Finished,
+			false => SessionState::Failed,
+		};
+		data.result = Some(result);
+		self.completed.notify_all();
+	}
+
+	/// Make key shares for all target nodes.
+	fn make_key_shares(&self, data: &SessionData) -> Result<Vec<ExportedKeyShare>, Error> {
+		let public = math::compute_public_sum(data.contributions.values().map(|c| &c.commitments[0]))?;
+		Ok(data.id_numbers.keys()
+			.map(|node| ExportedKeyShare {
+				key_id: self.meta.id.clone(),
+				node: node.clone(),
+				public: public.clone(),
+				author: self.key_share.author.clone(),
+				threshold: data.threshold,
+				id_numbers: data.id_numbers.iter().map(|(k, v)| (k.clone(), (**v).clone())).collect(),
+				common_point: self.key_share.common_point.clone(),
+				encrypted_point: self.key_share.encrypted_point.clone(),
+				contributions: data.contributions.iter()
+					.map(|(contributor, contribution)| ExportedShareContribution {
+						node: contributor.clone(),
+						commitments: contribution.commitments.clone(),
+						encrypted_sub_share: contribution.encrypted_sub_shares[node].clone(),
+					})
+					.collect(),
+			})
+			.collect())
+	}
+
+	/// Check that the session is authorized by operator of master node.
+	fn check_admin_signature(&self, signature: &Signature) -> Result<(), Error> {
+		let master = &self.meta.master_node_id;
+		match ethkey::verify_public(master, signature, &keccak(&**master))? {
+			true => Ok(()),
+			false => Err(Error::AccessDenied),
+		}
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+/// Compute contribution of this key holder to the key shares of target nodes.
+fn compute_contribution(self_key_pair: &NodeKeyPair, key_share: &DocumentKeyShare, self_node: &NodeId, contributors: &BTreeSet<NodeId>, threshold: usize, id_numbers: &BTreeMap<NodeId, Secret>) -> Result<Contribution, Error> {
+	let mut polynom = math::generate_random_polynom(threshold)?;
+	polynom[0] = math::compute_joint_secret_contribution(&key_share.secret_share, &key_share.id_numbers[self_node],
+		contributors.iter().filter(|n| *n != self_node).map(|n| &key_share.id_numbers[n]))?;
+
+	let mut encrypted_sub_shares = BTreeMap::new();
+	for (node, id_number) in id_numbers {
+		let sub_share = math::compute_polynom(&polynom, id_number)?;
+		let encryption_key = self_key_pair.compute_shared_key(node)?;
+		encrypted_sub_shares.insert(node.clone(), encrypt_single_message(encryption_key.public(), &**sub_share)?);
+	}
+
+	Ok(Contribution {
+		commitments: math::compute_polynom_commitments(&polynom)?,
+		encrypted_sub_shares: encrypted_sub_shares,
+	})
+}
+
+/// Import key share, exported from other cluster for this node.
+pub fn import_key_share(self_key_pair: &NodeKeyPair, key_storage: &KeyStorage, key_share: ExportedKeyShare) -> Result<(), Error> {
+	// check that the key share is exported for this node
+	let self_node = self_key_pair.public();
+	if &key_share.node != self_node {
+		return Err(Error::InvalidNodeForRequest);
+	}
+	let id_numbers = key_share.id_numbers.iter()
+		.map(|(k, v)| Secret::from_unsafe_slice(&**v).map(|id_number| (k.clone(), id_number)))
+		.collect::<Result<BTreeMap<_, _>, _>>()?;
+	let self_id_number = id_numbers.get(self_node).cloned().ok_or(Error::InvalidMessage)?;
+	if key_share.threshold >= id_numbers.len() || key_share.contributions.is_empty() {
+		return Err(Error::InvalidMessage);
+	}
+	if key_storage.is_tombstoned(&key_share.key_id) {
+		return Err(Error::KeyDeleted);
+	}
+
+	// check every contribution against its commitments
+	let mut contributors = BTreeSet::new();
+	let mut sub_shares = Vec::with_capacity(key_share.contributions.len());
+	for contribution in &key_share.contributions {
+		if contribution.commitments.len() != key_share.threshold + 1 || !contributors.insert(contribution.node.clone()) {
+			return Err(Error::InvalidMessage);
+		}
+
+		let decryption_key = self_key_pair.compute_shared_key(&contribution.node)?;
+		let sub_share = Secret::from_unsafe_slice(&decrypt_single_message(decryption_key.secret(), &contribution.encrypted_sub_share)?)?;
+		if !math::verify_polynom_value(&contribution.commitments, &self_id_number, &sub_share)? {
+			return Err(Error::InvalidMessage);
+		}
+
+		sub_shares.push(sub_share);
+	}
+	if math::compute_public_sum(key_share.contributions.iter().map(|c| &c.commitments[0]))? != key_share.public {
+		return Err(Error::InvalidMessage);
+	}
+
+	let document_key_share = DocumentKeyShare {
+		author: key_share.author,
+		threshold: key_share.threshold,
+		id_numbers: id_numbers,
+		secret_share: math::compute_secret_sum(sub_shares.iter())?,
+		common_point: key_share.common_point,
+		encrypted_point: key_share.encrypted_point,
+		refresh_id: None,
+		metadata: Default::default(),
+	};
+
+	// key share could be already imported by the previous attempt
+	if key_storage.contains(&key_share.key_id) {
+		let stored_key_share = key_storage.get(&key_share.key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+		return match stored_key_share.id_numbers == document_key_share.id_numbers && stored_key_share.secret_share == document_key_share.secret_share {
+			true => Ok(()),
+			false => Err(Error::InvalidNodesConfiguration),
+		};
+	}
+
+	key_storage.insert(key_share.key_id, document_key_share).map_err(|e| Error::KeyStorage(e.into()))
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return;
+		}
+
+		// slave nodes only care about master node && master node cares about all contributors
+		let is_master = self.meta.self_node_id == self.meta.master_node_id;
+		if (!is_master && node != &self.meta.master_node_id) || (is_master && !data.contributors.contains(node)) {
+			return;
+		}
+
+		warn!("{}: key export session failed because {} connection has timeouted", self.node(), node);
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return;
+		}
+
+		warn!("{}: key export session failed with timeout", self.node());
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: key export session has been cancelled", self.node());
+
+		if self.meta.self_node_id != self.meta.master_node_id {
+			// do not bother processing send error, as we already processing error
+			let _ = self.cluster.send(&self.meta.master_node_id, Message::KeyExport(KeyExportMessage::KeyExportSessionError(KeyExportSessionError {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				error: Error::SessionCancelled.into(),
+				code: Some(Error::SessionCancelled.code().into()),
+			})));
+		}
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<Vec<ExportedKeyShare>, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::{BTreeSet, BTreeMap};
+	use ethkey::{self, Random, Generator, KeyPair, Secret, Signature};
+	use hash::keccak;
+	use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, DummyKeyStorage, PlainNodeKeyPair,
+		ExportedKeyShare};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, KeyExportMessage};
+	use super::{SessionImpl, SessionParams, SessionState, Session, import_key_share};
+
+	struct Node {
+		key_pair: KeyPair,
+		cluster: Arc<DummyCluster>,
+		session: SessionImpl,
+	}
+
+	/// Prepare key holders of the key with threshold 1 (the first one is master) && returns the joint secret.
+	fn prepare_nodes(num_nodes: usize) -> (Vec<Node>, Secret) {
+		let key_pairs: Vec<_> = (0..num_nodes).map(|_| Random.generate().unwrap()).collect();
+		let master_node_id = key_pairs[0].public().clone();
+		let author = Random.generate().unwrap();
+		let polynom = math::generate_random_polynom(1).unwrap();
+		let id_numbers: BTreeMap<NodeId, Secret> = key_pairs.iter()
+			.map(|kp| (kp.public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		let nodes = key_pairs.iter().map(|key_pair| {
+			let cluster = Arc::new(DummyCluster::new(key_pair.public().clone()));
+			for node in &key_pairs {
+				cluster.add_node(node.public().clone());
+			}
+
+			Node {
+				key_pair: key_pair.clone(),
+				cluster: cluster.clone(),
+				session: SessionImpl::new(SessionParams {
+					meta: SessionMeta {
+						id: SessionId::default(),
+						master_node_id: master_node_id.clone(),
+						self_node_id: key_pair.public().clone(),
+						threshold: 1,
+					},
+					self_key_pair: Arc::new(PlainNodeKeyPair::new(key_pair.clone())),
+					key_share: DocumentKeyShare {
+						author: author.public().clone(),
+						threshold: 1,
+						id_numbers: id_numbers.clone(),
+						secret_share: math::compute_polynom(&polynom, &id_numbers[key_pair.public()]).unwrap(),
+						common_point: None,
+						encrypted_point: None,
+						refresh_id: None,
+						metadata: Default::default(),
+					},
+					cluster: cluster,
+					nonce: 0,
+				}).unwrap(),
+			}
+		}).collect();
+
+		(nodes, polynom[0].clone())
+	}
+
+	fn admin_signature(nodes: &[Node]) -> Signature {
+		ethkey::sign(nodes[0].key_pair.secret(), &keccak(&**nodes[0].key_pair.public())).unwrap()
+	}
+
+	fn all_nodes(nodes: &[Node]) -> BTreeSet<NodeId> {
+		nodes.iter().map(|n| n.session.node().clone()).collect()
+	}
+
+	fn do_messages_exchange(nodes: &[Node]) -> Result<(), Error> {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::KeyExport(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Export the key to target nodes.
+	fn export_key(nodes: &[Node], targets: &[KeyPair], threshold: usize) -> Vec<ExportedKeyShare> {
+		let targets = targets.iter().map(|kp| kp.public().clone()).collect();
+		nodes[0].session.initialize(admin_signature(nodes), targets, threshold, all_nodes(nodes)).unwrap();
+		do_messages_exchange(nodes).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+		nodes[0].session.wait(None).unwrap()
+	}
+
+	fn import(target: &KeyPair, key_storage: &DummyKeyStorage, key_share: ExportedKeyShare) -> Result<(), Error> {
+		import_key_share(&PlainNodeKeyPair::new(target.clone()), key_storage, key_share)
+	}
+
+	#[test]
+	fn key_is_reshared_among_target_nodes() {
+		let (nodes, joint_secret) = prepare_nodes(3);
+		let targets: Vec<_> = (0..4).map(|_| Random.generate().unwrap()).collect();
+		let key_shares = export_key(&nodes, &targets, 2);
+		assert_eq!(key_shares.len(), 4);
+		assert!(key_shares.iter().all(|ks| ks.contributions.len() == 2));
+		assert_eq!(key_shares[0].public, math::compute_public_share(&joint_secret).unwrap());
+
+		// every target node imports its key share
+		let key_storages: Vec<_> = targets.iter().map(|_| DummyKeyStorage::default()).collect();
+		for key_share in key_shares {
+			let target = targets.iter().position(|kp| kp.public() == &key_share.node).unwrap();
+			import(&targets[target], &key_storages[target], key_share).unwrap();
+		}
+
+		// any threshold + 1 target nodes could reconstruct the same joint secret
+		let imported_shares: Vec<_> = key_storages.iter().map(|ks| ks.get(&SessionId::default()).unwrap()).collect();
+		for skip in 0..4 {
+			let id_numbers: Vec<_> = (0..4).filter(|i| *i != skip).map(|i| &imported_shares[i].id_numbers[targets[i].public()]).collect();
+			let secret_shares: Vec<_> = (0..4).filter(|i| *i != skip).map(|i| &imported_shares[i].secret_share).collect();
+			assert_eq!(math::compute_joint_secret_from_shares(&secret_shares, &id_numbers).unwrap(), joint_secret);
+		}
+		assert!(imported_shares.iter().all(|ks| ks.threshold == 2 && ks.id_numbers.len() == 4));
+	}
+
+	#[test]
+	fn key_export_fails_when_not_enough_key_holders_are_connected() {
+		let (nodes, _) = prepare_nodes(3);
+		let targets = vec![Random.generate().unwrap().public().clone()].into_iter().collect();
+		let connected_nodes = vec![nodes[0].session.node().clone()].into_iter().collect();
+		assert_eq!(nodes[0].session.initialize(admin_signature(&nodes), targets, 0, connected_nodes), Err(Error::ConsensusUnreachable));
+	}
+
+	#[test]
+	fn key_export_fails_when_threshold_is_too_large() {
+		let (nodes, _) = prepare_nodes(3);
+		let targets = vec![Random.generate().unwrap().public().clone()].into_iter().collect();
+		assert_eq!(nodes[0].session.initialize(admin_signature(&nodes), targets, 1, all_nodes(&nodes)), Err(Error::InvalidThreshold));
+	}
+
+	#[test]
+	fn key_holder_refuses_to_contribute_when_admin_signature_is_wrong() {
+		let (nodes, _) = prepare_nodes(3);
+		let targets = vec![Random.generate().unwrap().public().clone()].into_iter().collect();
+		nodes[0].session.initialize(admin_signature(&nodes), targets, 0, all_nodes(&nodes)).unwrap();
+		let (to, message) = nodes[0].cluster.take_message().unwrap();
+		let mut message = match message {
+			Message::KeyExport(KeyExportMessage::InitializeKeyExportSession(message)) => message,
+			_ => unreachable!(),
+		};
+		message.admin_signature = ethkey::sign(Random.generate().unwrap().secret(), &keccak(&**nodes[0].key_pair.public())).unwrap().into();
+		let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+		assert_eq!(node.session.process_message(nodes[0].session.node(),
+			&KeyExportMessage::InitializeKeyExportSession(message)), Err(Error::AccessDenied));
+	}
+
+	#[test]
+	fn key_export_message_fails_when_nonce_is_wrong() {
+		let (nodes, _) = prepare_nodes(3);
+		let targets = vec![Random.generate().unwrap().public().clone()].into_iter().collect();
+		nodes[0].session.initialize(admin_signature(&nodes), targets, 0, all_nodes(&nodes)).unwrap();
+		let (to, message) = nodes[0].cluster.take_message().unwrap();
+		let mut message = match message {
+			Message::KeyExport(KeyExportMessage::InitializeKeyExportSession(message)) => message,
+			_ => unreachable!(),
+		};
+		message.session_nonce = 10;
+		let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+		assert_eq!(node.session.process_message(nodes[0].session.node(),
+			&KeyExportMessage::InitializeKeyExportSession(message)), Err(Error::ReplayProtection));
+	}
+
+	#[test]
+	fn key_share_is_imported_only_by_its_target_node() {
+		let (nodes, _) = prepare_nodes(3);
+		let targets: Vec<_> = (0..2).map(|_| Random.generate().unwrap()).collect();
+		let key_shares = export_key(&nodes, &targets, 1);
+		let key_share = key_shares.into_iter().find(|ks| &ks.node == targets[0].public()).unwrap();
+
+		let key_storage = DummyKeyStorage::default();
+		assert_eq!(import(&targets[1], &key_storage, key_share.clone()), Err(Error::InvalidNodeForRequest));
+		assert!(!key_storage.contains(&SessionId::default()));
+
+		// repeated import of the same key share succeeds
+		import(&targets[0], &key_storage, key_share.clone()).unwrap();
+		import(&targets[0], &key_storage, key_share).unwrap();
+	}
+
+	#[test]
+	fn key_share_with_altered_contribution_is_not_imported() {
+		let (nodes, _) = prepare_nodes(3);
+		let targets: Vec<_> = (0..2).map(|_| Random.generate().unwrap()).collect();
+		let key_shares = export_key(&nodes, &targets, 1);
+		let key_share = key_shares.into_iter().find(|ks| &ks.node == targets[0].public()).unwrap();
+		let key_storage = DummyKeyStorage::default();
+
+		// contribution, which does not match its commitments
+		let mut altered_key_share = key_share.clone();
+		altered_key_share.contributions[0].commitments.swap(0, 1);
+		assert_eq!(import(&targets[0], &key_storage, altered_key_share), Err(Error::InvalidMessage));
+
+		// public of other key
+		let mut altered_key_share = key_share.clone();
+		altered_key_share.public = Random.generate().unwrap().public().clone();
+		assert_eq!(import(&targets[0], &key_storage, altered_key_share), Err(Error::InvalidMessage));
+
+		// missing contribution
+		let mut altered_key_share = key_share;
+		altered_key_share.contributions.pop();
+		assert_eq!(import(&targets[0], &key_storage, altered_key_share), Err(Error::InvalidMessage));
+		assert!(!key_storage.contains(&SessionId::default()));
+	}
+}
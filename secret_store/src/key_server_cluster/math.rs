@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use ethkey::{Public, Secret, Random, Generator, math};
+use ethkey::{Public, Secret, Signature, Random, Generator, math};
 use bigint::prelude::U256;
 use bigint::hash::H256;
 use hash::keccak;
@@ -29,6 +29,51 @@ pub struct EncryptedSecret {
 	pub encrypted_point: Public,
 }
 
+/// Proof that the node has computed its decryption share using its own key share.
+/// Contains node shadow point (node_shadow * common_point), public of node shadow (node_shadow * G)
+/// and Chaum-Pedersen proof that both points are computed using the same node_shadow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeDecryptionProof {
+	/// Public of node shadow: node_shadow * G.
+	pub public_shadow: Public,
+	/// Node shadow point: node_shadow * common_point.
+	pub shadow_point: Public,
+	/// Proof challenge.
+	pub challenge: Secret,
+	/// Proof response.
+	pub response: Secret,
+}
+
+impl NodeDecryptionProof {
+	/// Serialize proof as public_shadow || shadow_point || challenge || response.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(192);
+		bytes.extend_from_slice(&*self.public_shadow);
+		bytes.extend_from_slice(&*self.shadow_point);
+		bytes.extend_from_slice(&**self.challenge);
+		bytes.extend_from_slice(&**self.response);
+		bytes
+	}
+
+	/// Deserialize proof, serialized with `to_bytes`.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+		if bytes.len() != 192 {
+			return Err(Error::InvalidMessage);
+		}
+
+		let challenge = Secret::from_slice(&bytes[128..160]);
+		challenge.check_validity()?;
+		let response = Secret::from_slice(&bytes[160..192]);
+		response.check_validity()?;
+		Ok(NodeDecryptionProof {
+			public_shadow: Public::from_slice(&bytes[0..64]),
+			shadow_point: Public::from_slice(&bytes[64..128]),
+			challenge: challenge,
+			response: response,
+		})
+	}
+}
+
 /// Generate random scalar
 pub fn generate_random_scalar() -> Result<Secret, Error> {
 	Ok(Random.generate()?.secret().clone())
@@ -113,6 +158,25 @@ pub fn compute_polynom(polynom: &[Secret], node_number: &Secret) -> Result<Secre
 	Ok(result)
 }
 
+/// Compute value of polynom with zero constant term, using `node_number` as argument.
+/// `polynom` holds coefficients of the first and higher powers of argument.
+pub fn compute_share_refresh_delta(polynom: &[Secret], node_number: &Secret) -> Result<Secret, Error> {
+	debug_assert!(!polynom.is_empty());
+
+	let mut result = polynom[0].clone();
+	result.mul(node_number)?;
+	for i in 1..polynom.len() {
+		// calculate coeff * pow(point, i + 1)
+		let mut appendum = node_number.clone();
+		appendum.pow(i + 1)?;
+		appendum.mul(&polynom[i])?;
+
+		result.add(&appendum)?;
+	}
+
+	Ok(result)
+}
+
 /// Generate public keys for other participants.
 pub fn public_values_generation(threshold: usize, derived_point: &Public, polynom1: &[Secret], polynom2: &[Secret]) -> Result<Vec<Public>, Error> {
 	debug_assert_eq!(polynom1.len(), threshold + 1);
@@ -189,6 +253,74 @@ pub fn compute_joint_secret<'a, I>(secret_coeffs: I) -> Result<Secret, Error> wh
 	compute_secret_sum(secret_coeffs)
 }
 
+/// Compute joint secret key from shares of given nodes. Number of shares must be equal to the shared polynom degree + 1.
+pub fn compute_joint_secret_from_shares<'a>(secret_shares: &[&'a Secret], id_numbers: &[&'a Secret]) -> Result<Secret, Error> {
+	debug_assert!(!secret_shares.is_empty() && secret_shares.len() == id_numbers.len());
+
+	let mut shadows = Vec::with_capacity(secret_shares.len());
+	for i in 0..secret_shares.len() {
+		let other_id_numbers = id_numbers.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, n)| *n);
+		shadows.push(compute_node_shadow(secret_shares[i], id_numbers[i], other_id_numbers)?);
+	}
+
+	// node shadows are multiplied by (-1) ^ (number of other nodes) when compared to Lagrange coefficients
+	let mut joint_secret = compute_secret_sum(shadows.iter())?;
+	if secret_shares.len() % 2 == 0 {
+		joint_secret.neg()?;
+	}
+	Ok(joint_secret)
+}
+
+/// Compute joint public key from public shares (secret_share * G) of given nodes. Number of shares must be greater than the shared polynom degree.
+pub fn compute_joint_public_from_shares<'a>(public_shares: &[&'a Public], id_numbers: &[&'a Secret]) -> Result<Public, Error> {
+	debug_assert!(!public_shares.is_empty() && public_shares.len() == id_numbers.len());
+
+	let one = Secret::from_slice(&*H256::from(1));
+	let mut weighted_public_shares = Vec::with_capacity(public_shares.len());
+	for i in 0..public_shares.len() {
+		let other_id_numbers = id_numbers.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, n)| *n);
+		let mut lagrange_coeff = compute_shadow_mul(&one, id_numbers[i], other_id_numbers)?;
+		// shadow coefficients are multiplied by (-1) ^ (number of other nodes) when compared to Lagrange coefficients
+		if public_shares.len() % 2 == 0 {
+			lagrange_coeff.neg()?;
+		}
+
+		let mut weighted_public_share = public_shares[i].clone();
+		math::public_mul_secret(&mut weighted_public_share, &lagrange_coeff)?;
+		weighted_public_shares.push(weighted_public_share);
+	}
+
+	compute_public_sum(weighted_public_shares.iter())
+}
+
+/// Compute id of the key, derived from parent key using given derivation path: keccak(parent_key_id | derivation_path).
+pub fn compute_derived_key_id(parent_key_id: &H256, derivation_path: &H256) -> H256 {
+	let mut buffer = [0; 64];
+	buffer[0..32].copy_from_slice(&parent_key_id[0..32]);
+	buffer[32..64].copy_from_slice(&derivation_path[0..32]);
+	keccak(&buffer[..])
+}
+
+/// Compute delta, which is added to every share of parent key to get share of derived key.
+/// Since every shared polynom value is shifted by the same delta, joint secret is also shifted by delta.
+pub fn compute_key_derivation_delta(derived_key_id: &H256) -> Result<Secret, Error> {
+	to_scalar(derived_key_id.clone())
+}
+
+/// Compute secret share of derived key: parent_secret_share + delta.
+pub fn compute_derived_secret_share(parent_secret_share: &Secret, delta: &Secret) -> Result<Secret, Error> {
+	let mut derived_secret_share = parent_secret_share.clone();
+	derived_secret_share.add(delta)?;
+	Ok(derived_secret_share)
+}
+
+/// Compute public portion of derived key: parent_public + delta * G.
+pub fn compute_derived_public(parent_public: &Public, delta: &Secret) -> Result<Public, Error> {
+	let mut derived_public = compute_public_share(delta)?;
+	math::public_add(&mut derived_public, parent_public)?;
+	Ok(derived_public)
+}
+
 /// Encrypt secret with joint public key.
 pub fn encrypt_secret(secret: &Public, joint_public: &Public) -> Result<EncryptedSecret, Error> {
 	// this is performed by KS-cluster client (or KS master)
@@ -209,11 +341,79 @@ pub fn encrypt_secret(secret: &Public, joint_public: &Public) -> Result<Encrypte
 	})
 }
 
+/// Generate document key, mixing in requester entropy point `r * T`. Result is `(s * (r * T), s * T)`,
+/// where `s` is random server scalar. Requester could check that document key is equal to `r * (s * T)`.
+pub fn generate_document_key_with_entropy(requester_entropy: &Public) -> Result<(Public, Public), Error> {
+	let server_entropy = generate_random_scalar()?;
+
+	let mut document_key = requester_entropy.clone();
+	math::public_mul_secret(&mut document_key, &server_entropy)?;
+	let server_entropy_point = compute_public_share(&server_entropy)?;
+	Ok((document_key, server_entropy_point))
+}
+
+/// Check that document key has been generated using requester entropy scalar `r`.
+pub fn verify_document_key_entropy(document_key: &Public, server_entropy_point: &Public, requester_entropy: &Secret) -> Result<bool, Error> {
+	let mut expected_document_key = server_entropy_point.clone();
+	math::public_mul_secret(&mut expected_document_key, requester_entropy)?;
+	Ok(&expected_document_key == document_key)
+}
+
 /// Compute shadow for the node.
 pub fn compute_node_shadow<'a, I>(node_secret_share: &Secret, node_number: &Secret, other_nodes_numbers: I) -> Result<Secret, Error> where I: Iterator<Item=&'a Secret> {
 	compute_shadow_mul(node_secret_share, node_number, other_nodes_numbers)
 }
 
+/// Compute node contribution to the secret share of target node: node_secret_share * multiplication((t - s[j]) / (s[i] - s[j])) for every i != j.
+/// Sum of contributions of threshold + 1 nodes is the value of shared polynom at t.
+pub fn compute_share_recovery_contribution<'a, I>(node_secret_share: &Secret, node_number: &Secret, target_node_number: &Secret, other_nodes_numbers: I) -> Result<Secret, Error> where I: Iterator<Item=&'a Secret> {
+	let mut contribution = node_secret_share.clone();
+	for other_node_number in other_nodes_numbers {
+		let mut numerator = target_node_number.clone();
+		numerator.sub(other_node_number)?;
+		let mut denominator = node_number.clone();
+		denominator.sub(other_node_number)?;
+		denominator.inv()?;
+		numerator.mul(&denominator)?;
+		contribution.mul(&numerator)?;
+	}
+	Ok(contribution)
+}
+
+/// Compute node contribution to the joint secret: node_secret_share * Lagrange coefficient of the node at zero.
+/// Sum of contributions of threshold + 1 nodes is the joint secret.
+pub fn compute_joint_secret_contribution<'a, I>(node_secret_share: &Secret, node_number: &Secret, other_nodes_numbers: I) -> Result<Secret, Error> where I: Iterator<Item=&'a Secret> {
+	let other_nodes_numbers: Vec<_> = other_nodes_numbers.collect();
+	let mut contribution = compute_node_shadow(node_secret_share, node_number, other_nodes_numbers.iter().cloned())?;
+	// node shadow is multiplied by (-1) ^ (number of other nodes) when compared to Lagrange coefficient
+	if other_nodes_numbers.len() % 2 == 1 {
+		contribution.neg()?;
+	}
+	Ok(contribution)
+}
+
+/// Compute commitments to polynom coefficients: coeff * G.
+pub fn compute_polynom_commitments(polynom: &[Secret]) -> Result<Vec<Public>, Error> {
+	polynom.iter().map(compute_public_share).collect()
+}
+
+/// Check that value of polynom at `node_number` is consistent with commitments to polynom coefficients.
+pub fn verify_polynom_value(commitments: &[Public], node_number: &Secret, value: &Secret) -> Result<bool, Error> {
+	debug_assert!(!commitments.is_empty());
+
+	let mut expected_public = commitments[0].clone();
+	for i in 1..commitments.len() {
+		let mut node_number_pow = node_number.clone();
+		node_number_pow.pow(i)?;
+
+		let mut appendum = commitments[i].clone();
+		math::public_mul_secret(&mut appendum, &node_number_pow)?;
+		math::public_add(&mut expected_public, &appendum)?;
+	}
+
+	Ok(expected_public == compute_public_share(value)?)
+}
+
 /// Compute shadow point for the node.
 pub fn compute_node_shadow_point(access_key: &Secret, common_point: &Public, node_shadow: &Secret, decrypt_shadow: Option<Secret>) -> Result<(Public, Option<Secret>), Error> {
 	let mut shadow_key = node_shadow.clone();
@@ -235,6 +435,99 @@ pub fn compute_node_shadow_point(access_key: &Secret, common_point: &Public, nod
 	Ok((node_shadow_point, decrypt_shadow))
 }
 
+/// Compute proof of node decryption share: (node_shadow * G, node_shadow * common_point) + proof of equality of discrete logarithms.
+pub fn compute_node_decryption_proof(common_point: &Public, node_shadow: &Secret) -> Result<NodeDecryptionProof, Error> {
+	let nonce = generate_random_scalar()?;
+
+	let mut public_shadow = math::generation_point();
+	math::public_mul_secret(&mut public_shadow, node_shadow)?;
+	let mut shadow_point = common_point.clone();
+	math::public_mul_secret(&mut shadow_point, node_shadow)?;
+	let mut nonce_public = math::generation_point();
+	math::public_mul_secret(&mut nonce_public, &nonce)?;
+	let mut nonce_common_point = common_point.clone();
+	math::public_mul_secret(&mut nonce_common_point, &nonce)?;
+
+	// response = nonce + challenge * node_shadow
+	let challenge = compute_decryption_proof_challenge(common_point, &public_shadow, &shadow_point, &nonce_public, &nonce_common_point)?;
+	let mut response = challenge.clone();
+	response.mul(node_shadow)?;
+	response.add(&nonce)?;
+
+	Ok(NodeDecryptionProof {
+		public_shadow: public_shadow,
+		shadow_point: shadow_point,
+		challenge: challenge,
+		response: response,
+	})
+}
+
+/// Verify proof of node decryption share.
+pub fn verify_node_decryption_proof(common_point: &Public, proof: &NodeDecryptionProof) -> Result<bool, Error> {
+	// nonce_public = response * G - challenge * public_shadow
+	let mut nonce_public = math::generation_point();
+	math::public_mul_secret(&mut nonce_public, &proof.response)?;
+	let mut public_shadow_mul = proof.public_shadow.clone();
+	math::public_mul_secret(&mut public_shadow_mul, &proof.challenge)?;
+	math::public_sub(&mut nonce_public, &public_shadow_mul)?;
+
+	// nonce_common_point = response * common_point - challenge * shadow_point
+	let mut nonce_common_point = common_point.clone();
+	math::public_mul_secret(&mut nonce_common_point, &proof.response)?;
+	let mut shadow_point_mul = proof.shadow_point.clone();
+	math::public_mul_secret(&mut shadow_point_mul, &proof.challenge)?;
+	math::public_sub(&mut nonce_common_point, &shadow_point_mul)?;
+
+	let challenge = compute_decryption_proof_challenge(common_point, &proof.public_shadow, &proof.shadow_point, &nonce_public, &nonce_common_point)?;
+	Ok(challenge == proof.challenge)
+}
+
+/// Verify that decrypted secret has been computed using proven decryption shares of threshold + 1 nodes.
+/// `common_point` is the common point, returned by shadow decryption. `decrypted_point` is the finally decrypted secret.
+pub fn verify_decryption_proofs(server_key_public: &Public, common_point: &Public, encrypted_point: &Public, decrypted_point: &Public, proofs: &[NodeDecryptionProof]) -> Result<bool, Error> {
+	if proofs.is_empty() {
+		return Ok(false);
+	}
+
+	// shadow decryption returns negated common point when threshold is odd
+	let threshold = proofs.len() - 1;
+	let common_point = make_common_shadow_point(threshold, common_point.clone())?;
+	for proof in proofs {
+		if !verify_node_decryption_proof(&common_point, proof)? {
+			return Ok(false);
+		}
+	}
+
+	// sum of node shadows is (-1)^threshold * server key secret
+	let mut joint_public = compute_public_sum(proofs.iter().map(|p| &p.public_shadow))?;
+	if threshold % 2 != 0 {
+		math::public_negate(&mut joint_public)?;
+	}
+	if joint_public != *server_key_public {
+		return Ok(false);
+	}
+
+	let joint_shadow_point = compute_joint_shadow_point(proofs.iter().map(|p| &p.shadow_point))?;
+	let mut expected_decrypted_point = encrypted_point.clone();
+	if threshold % 2 != 0 {
+		math::public_add(&mut expected_decrypted_point, &joint_shadow_point)?;
+	} else {
+		math::public_sub(&mut expected_decrypted_point, &joint_shadow_point)?;
+	}
+	Ok(expected_decrypted_point == *decrypted_point)
+}
+
+/// Compute challenge of node decryption proof.
+fn compute_decryption_proof_challenge(common_point: &Public, public_shadow: &Public, shadow_point: &Public, nonce_public: &Public, nonce_common_point: &Public) -> Result<Secret, Error> {
+	let mut buffer = [0; 320];
+	buffer[0..64].copy_from_slice(&common_point[0..64]);
+	buffer[64..128].copy_from_slice(&public_shadow[0..64]);
+	buffer[128..192].copy_from_slice(&shadow_point[0..64]);
+	buffer[192..256].copy_from_slice(&nonce_public[0..64]);
+	buffer[256..320].copy_from_slice(&nonce_common_point[0..64]);
+	to_scalar(keccak(&buffer[..]))
+}
+
 /// Compute joint shadow point.
 pub fn compute_joint_shadow_point<'a, I>(nodes_shadow_points: I) -> Result<Public, Error> where I: Iterator<Item=&'a Public> {
 	compute_public_sum(nodes_shadow_points)
@@ -269,6 +562,59 @@ pub fn decrypt_with_joint_shadow(threshold: usize, access_key: &Secret, encrypte
 	Ok(decrypted_point)
 }
 
+/// Compute hash of re-encryption target, which must be signed by requester.
+pub fn compute_reencryption_target_hash(key_id: &H256, target_public: &Public) -> H256 {
+	let mut buffer = [0; 96];
+	buffer[0..32].copy_from_slice(&key_id[0..32]);
+	buffer[32..96].copy_from_slice(&target_public[0..64]);
+	keccak(&buffer[..])
+}
+
+/// Compute hash of server key public, which is signed by key holders to attest it: keccak(key_id | public).
+pub fn compute_server_key_attestation_hash(key_id: &H256, public: &Public) -> H256 {
+	let mut buffer = [0; 96];
+	buffer[0..32].copy_from_slice(&key_id[0..32]);
+	buffer[32..96].copy_from_slice(&public[0..64]);
+	keccak(&buffer[..])
+}
+
+/// Compute node contribution to re-encryption of the secret with target public key. Returns (k * T, node_shadow * common_point + k * target_public),
+/// where k is random && is never revealed, so that the secret can not be recovered from the joint shadow.
+pub fn compute_node_reencryption_shadow(common_point: &Public, node_shadow: &Secret, target_public: &Public) -> Result<(Public, Public), Error> {
+	let blinding_key = generate_random_scalar()?;
+
+	// k * T
+	let mut common_shadow = math::generation_point();
+	math::public_mul_secret(&mut common_shadow, &blinding_key)?;
+
+	// node_shadow * common_point + k * target_public
+	let mut reencryption_shadow = common_point.clone();
+	math::public_mul_secret(&mut reencryption_shadow, node_shadow)?;
+	let mut blinding_point = target_public.clone();
+	math::public_mul_secret(&mut blinding_point, &blinding_key)?;
+	math::public_add(&mut reencryption_shadow, &blinding_point)?;
+
+	Ok((common_shadow, reencryption_shadow))
+}
+
+/// Re-encrypt data with target public key, using joint re-encryption shadows of t + 1 nodes.
+pub fn reencrypt_with_joint_shadow(threshold: usize, encrypted_point: &Public, joint_common_shadow: &Public, joint_reencryption_shadow: &Public) -> Result<EncryptedSecret, Error> {
+	let mut common_point = joint_common_shadow.clone();
+	let mut encrypted_point = encrypted_point.clone();
+	if threshold % 2 != 0 {
+		math::public_add(&mut encrypted_point, joint_reencryption_shadow)?;
+	} else {
+		// M - k * y => (-k * T, M + (-k) * y)
+		math::public_sub(&mut encrypted_point, joint_reencryption_shadow)?;
+		math::public_negate(&mut common_point)?;
+	}
+
+	Ok(EncryptedSecret {
+		common_point: common_point,
+		encrypted_point: encrypted_point,
+	})
+}
+
 /// Prepare common point for shadow decryption.
 pub fn make_common_shadow_point(threshold: usize, mut common_point: Public) -> Result<Public, Error> {
 	if threshold % 2 != 1 {
@@ -311,6 +657,11 @@ pub fn combine_message_hash_with_public(message_hash: &H256, public: &Public) ->
 	let hash = keccak(&buffer[..]);
 
 	// map hash to EC finite field value
+	to_scalar(hash)
+}
+
+/// Map hash to EC finite field value.
+fn to_scalar(hash: H256) -> Result<Secret, Error> {
 	let hash: U256 = hash.into();
 	let hash: H256 = (hash % math::curve_order()).into();
 	let hash = Secret::from_slice(&*hash);
@@ -387,6 +738,57 @@ pub fn verify_signature(public: &Public, signature: &(Secret, Secret), message_h
 	Ok(combined_hash == signature.0)
 }
 
+/// Compute share of ECDSA inversed nonce coefficient: nonce_share * inversion_nonce_share + zero_share,
+/// where zero_share is a share of random 2 * threshold-degree polynom with zero constant term.
+pub fn compute_ecdsa_inversed_nonce_coeff_share(nonce_share: &Secret, inversion_nonce_share: &Secret, zero_share: Option<&Secret>) -> Result<Secret, Error> {
+	let mut coeff_share = nonce_share.clone();
+	coeff_share.mul(inversion_nonce_share)?;
+	if let Some(zero_share) = zero_share {
+		coeff_share.add(zero_share)?;
+	}
+	Ok(coeff_share)
+}
+
+/// Compute inversed ECDSA nonce coefficient from its shares, computed by 2 * threshold + 1 nodes.
+pub fn compute_ecdsa_inversed_nonce_coeff<'a>(coeff_shares: &[&'a Secret], id_numbers: &[&'a Secret]) -> Result<Secret, Error> {
+	let mut coeff = compute_joint_secret_from_shares(coeff_shares, id_numbers)?;
+	coeff.inv()?;
+	Ok(coeff)
+}
+
+/// Compute ECDSA partial signature: inversed_nonce_share * (message_hash + r * node_secret_share) + zero_share,
+/// where inversed_nonce_share = inversion_nonce_share * inversed_nonce_coeff && r = nonce_public.x.
+pub fn compute_ecdsa_partial_signature(inversion_nonce_share: &Secret, inversed_nonce_coeff: &Secret, nonce_public: &Public, node_secret_share: &Secret, message_hash: &H256, zero_share: Option<&Secret>) -> Result<Secret, Error> {
+	let mut inversed_nonce_share = inversion_nonce_share.clone();
+	inversed_nonce_share.mul(inversed_nonce_coeff)?;
+
+	let mut partial_signature = to_scalar(H256::from_slice(&nonce_public[0..32]))?;
+	partial_signature.mul(node_secret_share)?;
+	partial_signature.add(&to_scalar(message_hash.clone())?)?;
+	partial_signature.mul(&inversed_nonce_share)?;
+	if let Some(zero_share) = zero_share {
+		partial_signature.add(zero_share)?;
+	}
+	Ok(partial_signature)
+}
+
+/// Compute ECDSA signature from partial signatures, computed by 2 * threshold + 1 nodes.
+pub fn compute_ecdsa_signature<'a>(nonce_public: &Public, partial_signatures: &[&'a Secret], id_numbers: &[&'a Secret]) -> Result<Signature, Error> {
+	let r = to_scalar(H256::from_slice(&nonce_public[0..32]))?;
+	let mut s = compute_joint_secret_from_shares(partial_signatures, id_numbers)?;
+	let mut v = nonce_public[63] & 1;
+
+	// only signatures with low s are considered valid
+	let signature = Signature::from_rsv(&*r, &*s, v);
+	if signature.is_low_s() {
+		return Ok(signature);
+	}
+
+	s.neg()?;
+	v ^= 1;
+	Ok(Signature::from_rsv(&*r, &*s, v))
+}
+
 #[cfg(test)]
 pub mod tests {
 	use std::iter::once;
@@ -501,6 +903,82 @@ pub mod tests {
 		}
 	}
 
+	#[test]
+	fn full_reencryption_math_session() {
+		let test_cases = [(0, 1), (0, 2), (1, 2), (1, 3), (2, 3), (1, 4), (2, 4), (3, 4), (1, 5), (2, 5), (3, 5), (4, 5)];
+		for &(t, n) in &test_cases {
+			let artifacts = run_key_generation(t, n, None);
+
+			// encrypt document key with joint public key
+			let document_secret_plain = generate_random_point().unwrap();
+			let encrypted_secret = encrypt_secret(&document_secret_plain, &artifacts.joint_public).unwrap();
+
+			// use t + 1 nodes to re-encrypt document key with target public key
+			let target = Random.generate().unwrap();
+			let nodes_shadows: Vec<_> = (0..t + 1).map(|i|
+				compute_node_shadow(&artifacts.secret_shares[i], &artifacts.id_numbers[i], artifacts.id_numbers.iter()
+					.enumerate()
+					.filter(|&(j, _)| j != i)
+					.take(t)
+					.map(|(_, id_number)| id_number)).unwrap()).collect();
+			let reencryption_shadows: Vec<_> = nodes_shadows.iter()
+				.map(|s| compute_node_reencryption_shadow(&encrypted_secret.common_point, s, target.public()).unwrap())
+				.collect();
+			let joint_common_shadow = compute_public_sum(reencryption_shadows.iter().map(|s| &s.0)).unwrap();
+			let joint_reencryption_shadow = compute_public_sum(reencryption_shadows.iter().map(|s| &s.1)).unwrap();
+			let reencrypted_secret = reencrypt_with_joint_shadow(t, &encrypted_secret.encrypted_point, &joint_common_shadow, &joint_reencryption_shadow).unwrap();
+
+			// re-encrypted secret can be decrypted with target secret
+			assert!(reencrypted_secret.encrypted_point != document_secret_plain);
+			assert_eq!(decrypt_with_joint_secret(&reencrypted_secret.encrypted_point, &reencrypted_secret.common_point, target.secret()).unwrap(),
+				document_secret_plain);
+		}
+	}
+
+	#[test]
+	fn decryption_proofs_math_session() {
+		let test_cases = [(0, 1), (0, 2), (1, 2), (1, 3), (2, 3), (1, 4), (2, 4), (3, 4), (1, 5), (2, 5), (3, 5), (4, 5)];
+		for &(t, n) in &test_cases {
+			let artifacts = run_key_generation(t, n, None);
+
+			// encrypt document key with joint public key
+			let document_secret_plain = generate_random_point().unwrap();
+			let encrypted_secret = encrypt_secret(&document_secret_plain, &artifacts.joint_public).unwrap();
+
+			// use t + 1 nodes to prove their decryption shares
+			let nodes_shadows: Vec<_> = (0..t + 1).map(|i|
+				compute_node_shadow(&artifacts.secret_shares[i], &artifacts.id_numbers[i], artifacts.id_numbers.iter()
+					.enumerate()
+					.filter(|&(j, _)| j != i)
+					.take(t)
+					.map(|(_, id_number)| id_number)).unwrap()).collect();
+			let proofs: Vec<_> = nodes_shadows.iter()
+				.map(|s| compute_node_decryption_proof(&encrypted_secret.common_point, s).unwrap())
+				.map(|p| NodeDecryptionProof::from_bytes(&p.to_bytes()).unwrap())
+				.collect();
+			assert!(proofs.iter().all(|p| verify_node_decryption_proof(&encrypted_secret.common_point, p).unwrap()));
+
+			// client is able to check decrypted secret
+			let common_shadow_point = make_common_shadow_point(t, encrypted_secret.common_point.clone()).unwrap();
+			assert!(verify_decryption_proofs(&artifacts.joint_public, &common_shadow_point, &encrypted_secret.encrypted_point,
+				&document_secret_plain, &proofs).unwrap());
+
+			// ...and to detect wrong decrypted secret
+			assert!(!verify_decryption_proofs(&artifacts.joint_public, &common_shadow_point, &encrypted_secret.encrypted_point,
+				&generate_random_point().unwrap(), &proofs).unwrap());
+
+			// ...and forged proofs
+			let mut forged_proofs = proofs.clone();
+			let forged_shadow = generate_random_scalar().unwrap();
+			forged_proofs[0] = compute_node_decryption_proof(&encrypted_secret.common_point, &forged_shadow).unwrap();
+			assert!(!verify_decryption_proofs(&artifacts.joint_public, &common_shadow_point, &encrypted_secret.encrypted_point,
+				&document_secret_plain, &forged_proofs).unwrap());
+			forged_proofs[0] = proofs[0].clone();
+			forged_proofs[0].shadow_point = generate_random_point().unwrap();
+			assert!(!verify_node_decryption_proof(&encrypted_secret.common_point, &forged_proofs[0]).unwrap());
+		}
+	}
+
 	#[test]
 	fn local_signature_works() {
 		let key_pair = Random.generate().unwrap();
@@ -583,4 +1061,195 @@ pub mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn share_recovery_math_session() {
+		let test_cases = [(0, 2), (1, 3), (2, 4), (1, 5), (3, 5), (4, 10)];
+		for &(t, n) in &test_cases {
+			let artifacts = run_key_generation(t, n, None);
+
+			// recover share of the last node from shares of first t + 1 nodes
+			let target = n - 1;
+			let contributions: Vec<_> = (0..t + 1)
+				.map(|i| compute_share_recovery_contribution(
+					&artifacts.secret_shares[i],
+					&artifacts.id_numbers[i],
+					&artifacts.id_numbers[target],
+					artifacts.id_numbers.iter()
+						.take(t + 1)
+						.enumerate()
+						.filter(|&(j, _)| i != j)
+						.map(|(_, n)| n)
+				).unwrap())
+				.collect();
+			let recovered_share = compute_secret_sum(contributions.iter()).unwrap();
+			assert_eq!(recovered_share, artifacts.secret_shares[target]);
+		}
+	}
+
+	#[test]
+	fn key_export_math_session() {
+		let test_cases = [(0, 1, 0, 1), (0, 2, 1, 3), (1, 3, 2, 4), (2, 4, 1, 2), (3, 5, 4, 10)];
+		for &(t, n, new_t, new_n) in &test_cases {
+			let artifacts = run_key_generation(t, n, None);
+			let new_id_numbers: Vec<_> = (0..new_n).map(|_| generate_random_scalar().unwrap()).collect();
+
+			// every one of first t + 1 nodes shares its contribution to the joint secret using random polynom of degree new_t
+			let polynoms: Vec<_> = (0..t + 1)
+				.map(|i| {
+					let mut polynom = generate_random_polynom(new_t).unwrap();
+					polynom[0] = compute_joint_secret_contribution(
+						&artifacts.secret_shares[i],
+						&artifacts.id_numbers[i],
+						artifacts.id_numbers.iter()
+							.take(t + 1)
+							.enumerate()
+							.filter(|&(j, _)| i != j)
+							.map(|(_, n)| n)
+					).unwrap();
+					polynom
+				})
+				.collect();
+			let commitments: Vec<_> = polynoms.iter().map(|p| compute_polynom_commitments(p).unwrap()).collect();
+			let new_shares: Vec<_> = new_id_numbers.iter()
+				.map(|id_number| {
+					let sub_shares: Vec<_> = polynoms.iter().map(|p| compute_polynom(p, id_number).unwrap()).collect();
+					assert!(sub_shares.iter().zip(commitments.iter()).all(|(s, c)| verify_polynom_value(c, id_number, s).unwrap()));
+					compute_secret_sum(sub_shares.iter()).unwrap()
+				})
+				.collect();
+
+			// commitments to constant terms are summed to the joint public
+			assert_eq!(compute_public_sum(commitments.iter().map(|c| &c[0])).unwrap(), artifacts.joint_public);
+
+			// new shares are the points of polynom with the same constant term
+			let joint_secret = compute_joint_secret(artifacts.polynoms1.iter().map(|p| &p[0])).unwrap();
+			let new_joint_secret = compute_joint_secret_from_shares(&new_shares.iter().take(new_t + 1).collect::<Vec<_>>(),
+				&new_id_numbers.iter().take(new_t + 1).collect::<Vec<_>>()).unwrap();
+			assert_eq!(new_joint_secret, joint_secret);
+
+			// altered sub-share is detected
+			let mut altered_sub_share = compute_polynom(&polynoms[0], &new_id_numbers[0]).unwrap();
+			altered_sub_share.add(&generate_random_scalar().unwrap()).unwrap();
+			assert_eq!(verify_polynom_value(&commitments[0], &new_id_numbers[0], &altered_sub_share), Ok(false));
+		}
+	}
+
+	#[test]
+	fn share_refresh_math_session() {
+		let test_cases = [(1, 2), (1, 3), (2, 4), (3, 5), (4, 10)];
+		for &(t, n) in &test_cases {
+			let artifacts = run_key_generation(t, n, None);
+
+			// every node adds values of random polynoms with zero constant term to its share
+			let refresh_polynoms: Vec<_> = (0..n).map(|_| generate_random_polynom(t - 1).unwrap()).collect();
+			let refreshed_shares: Vec<_> = (0..n)
+				.map(|i| {
+					let deltas: Vec<_> = refresh_polynoms.iter().map(|p| compute_share_refresh_delta(p, &artifacts.id_numbers[i]).unwrap()).collect();
+					compute_secret_sum(deltas.iter().chain(once(&artifacts.secret_shares[i]))).unwrap()
+				})
+				.collect();
+			assert!(refreshed_shares.iter().zip(artifacts.secret_shares.iter()).all(|(r, s)| r != s));
+
+			// refreshed shares are the points of polynom with the same constant term
+			let joint_secret = compute_joint_secret(artifacts.polynoms1.iter().map(|p| &p[0])).unwrap();
+			let shadows: Vec<_> = (0..t + 1)
+				.map(|i| compute_node_shadow(&refreshed_shares[i], &artifacts.id_numbers[i], artifacts.id_numbers.iter()
+					.take(t + 1)
+					.enumerate()
+					.filter(|&(j, _)| i != j)
+					.map(|(_, n)| n)).unwrap())
+				.collect();
+			let mut expected_secret = joint_secret.clone();
+			if t % 2 != 0 {
+				expected_secret.neg().unwrap();
+			}
+			assert_eq!(compute_secret_sum(shadows.iter()).unwrap(), expected_secret);
+		}
+	}
+
+	#[test]
+	fn key_derivation_math_session() {
+		let test_cases = [(0, 1), (0, 2), (1, 2), (1, 3), (2, 5), (3, 7)];
+		for &(t, n) in &test_cases {
+			let artifacts = run_key_generation(t, n, None);
+			let joint_secret = compute_joint_secret(artifacts.polynoms1.iter().map(|p| &p[0])).unwrap();
+
+			// every node derives its share locally
+			let derived_key_id = compute_derived_key_id(&H256::from(42), &H256::from(1));
+			let delta = compute_key_derivation_delta(&derived_key_id).unwrap();
+			let derived_secret_shares: Vec<_> = artifacts.secret_shares.iter()
+				.map(|s| compute_derived_secret_share(s, &delta).unwrap())
+				.collect();
+			let derived_public = compute_derived_public(&artifacts.joint_public, &delta).unwrap();
+
+			// derived shares are shares of derived secret
+			let mut derived_joint_secret = joint_secret.clone();
+			derived_joint_secret.add(&delta).unwrap();
+			let derived_key_pair = KeyPair::from_secret(derived_joint_secret.clone()).unwrap();
+			assert_eq!(derived_key_pair.public(), &derived_public);
+			assert_eq!(compute_joint_secret_from_shares(&derived_secret_shares.iter().take(t + 1).collect::<Vec<_>>(),
+				&artifacts.id_numbers.iter().take(t + 1).collect::<Vec<_>>()).unwrap(), derived_joint_secret);
+
+			// derived public could be restored from public shares of all nodes
+			let derived_public_shares: Vec<_> = derived_secret_shares.iter().map(|s| compute_public_share(s).unwrap()).collect();
+			assert_eq!(compute_joint_public_from_shares(&derived_public_shares.iter().collect::<Vec<_>>(),
+				&artifacts.id_numbers.iter().collect::<Vec<_>>()).unwrap(), derived_public);
+
+			// data, encrypted with derived public, is decrypted using derived shares
+			let document_secret_plain = generate_random_point().unwrap();
+			let (document_secret_decrypted, document_secret_decrypted_test) =
+				do_encryption_and_decryption(t, &derived_public, &artifacts.id_numbers, &derived_secret_shares, Some(&derived_joint_secret), document_secret_plain.clone());
+			assert_eq!(document_secret_plain, document_secret_decrypted_test);
+			assert_eq!(document_secret_plain, document_secret_decrypted);
+		}
+	}
+
+	#[test]
+	fn ecdsa_signature_math_session() {
+		let test_cases = [(0, 1), (0, 2), (1, 3), (1, 4), (2, 5), (3, 8)];
+		for &(t, n) in &test_cases {
+			// key, signature nonce && inversion nonce are generated by the same nodes
+			let artifacts = run_key_generation(t, n, None);
+			let nonce_artifacts = run_key_generation(t, n, Some(artifacts.id_numbers.clone()));
+			let inversion_nonce_artifacts = run_key_generation(t, n, Some(artifacts.id_numbers.clone()));
+
+			// signature is computed by 2 * t + 1 nodes
+			let m = 2 * t + 1;
+			let id_numbers: Vec<_> = artifacts.id_numbers.iter().take(m).collect();
+
+			// every node generates shares of two random 2 * t-degree polynoms with zero constant term
+			let (inversion_zero_shares, signature_zero_shares): (Vec<_>, Vec<_>) = if t == 0 {
+				(vec![None; m], vec![None; m])
+			} else {
+				let zero_share = |polynoms: &Vec<Vec<Secret>>, i: usize| Some(compute_secret_sum(polynoms.iter()
+					.map(|p| compute_share_refresh_delta(p, id_numbers[i]).unwrap())
+					.collect::<Vec<_>>()
+					.iter()).unwrap());
+				let inversion_zero_polynoms: Vec<_> = (0..m).map(|_| generate_random_polynom(2 * t - 1).unwrap()).collect();
+				let signature_zero_polynoms: Vec<_> = (0..m).map(|_| generate_random_polynom(2 * t - 1).unwrap()).collect();
+				((0..m).map(|i| zero_share(&inversion_zero_polynoms, i)).collect(), (0..m).map(|i| zero_share(&signature_zero_polynoms, i)).collect())
+			};
+
+			// master restores inversed nonce coefficient from its shares
+			let inversed_nonce_coeff_shares: Vec<_> = (0..m)
+				.map(|i| compute_ecdsa_inversed_nonce_coeff_share(&nonce_artifacts.secret_shares[i],
+					&inversion_nonce_artifacts.secret_shares[i], inversion_zero_shares[i].as_ref()).unwrap())
+				.collect();
+			let inversed_nonce_coeff = compute_ecdsa_inversed_nonce_coeff(&inversed_nonce_coeff_shares.iter().collect::<Vec<_>>(), &id_numbers).unwrap();
+
+			// every node computes partial signature && master combines these
+			let message_hash: H256 = "0000000000000000000000000000000000000000000000000000000000000042".parse().unwrap();
+			let partial_signatures: Vec<_> = (0..m)
+				.map(|i| compute_ecdsa_partial_signature(&inversion_nonce_artifacts.secret_shares[i], &inversed_nonce_coeff,
+					&nonce_artifacts.joint_public, &artifacts.secret_shares[i], &message_hash, signature_zero_shares[i].as_ref()).unwrap())
+				.collect();
+			let signature = compute_ecdsa_signature(&nonce_artifacts.joint_public, &partial_signatures.iter().collect::<Vec<_>>(), &id_numbers).unwrap();
+
+			// === verify signature ===
+			assert!(signature.is_low_s());
+			assert!(::ethkey::verify_public(&artifacts.joint_public, &signature, &message_hash).unwrap());
+			assert_eq!(::ethkey::recover(&signature, &message_hash).unwrap(), artifacts.joint_public);
+		}
+	}
 }
@@ -18,7 +18,8 @@ use std::fmt;
 use std::collections::{BTreeSet, BTreeMap};
 use ethkey::Secret;
 use key_server_cluster::SessionId;
-use super::{SerializableH256, SerializablePublic, SerializableSecret, SerializableSignature, SerializableMessageHash};
+use super::{SerializableH256, SerializablePublic, SerializableSecret, SerializableSignature, SerializableMessageHash, SerializableBytes,
+	SerializableErrorCode, SerializableExportedKeyShare};
 
 pub type MessageSessionId = SerializableH256;
 pub type MessageNodeId = SerializablePublic;
@@ -34,8 +35,28 @@ pub enum Message {
 	Encryption(EncryptionMessage),
 	/// Decryption message.
 	Decryption(DecryptionMessage),
+	/// Re-encryption message.
+	ReEncryption(ReEncryptionMessage),
 	/// Signing message.
 	Signing(SigningMessage),
+	/// ECDSA signing message.
+	EcdsaSigning(EcdsaSigningMessage),
+	/// Share recovery message.
+	ShareRecovery(ShareRecoveryMessage),
+	/// Share refresh message.
+	ShareRefresh(ShareRefreshMessage),
+	/// Key derivation message.
+	KeyDerivation(KeyDerivationMessage),
+	/// Key deletion message.
+	KeyDeletion(KeyDeletionMessage),
+	/// Share move message.
+	ShareMove(ShareMoveMessage),
+	/// Server key retrieval message.
+	ServerKeyRetrieval(ServerKeyRetrievalMessage),
+	/// Share bootstrap message.
+	ShareBootstrap(ShareBootstrapMessage),
+	/// Key export message.
+	KeyExport(KeyExportMessage),
 }
 
 /// All possible cluster-level messages.
@@ -49,6 +70,14 @@ pub enum ClusterMessage {
 	KeepAlive(KeepAlive),
 	/// Keep alive message response.
 	KeepAliveResponse(KeepAliveResponse),
+	/// Inventory of key shares, which receiver must hold.
+	KeySharesInventory(KeySharesInventory),
+	/// Administrative request, forwarded to the master node.
+	ForwardAdminRequest(ForwardAdminRequest),
+	/// Status of administrative request, forwarded to the master node.
+	ForwardedAdminRequestStatus(ForwardedAdminRequestStatus),
+	/// Acknowledgement of received session message.
+	MessageAcknowledgement(MessageAcknowledgement),
 }
 
 /// All possible messages that can be sent during key generation session.
@@ -79,6 +108,12 @@ pub enum EncryptionMessage {
 	ConfirmEncryptionInitialization(ConfirmEncryptionInitialization),
 	/// When encryption session error has occured.
 	EncryptionSessionError(EncryptionSessionError),
+	/// Request public portion of key share to re-key document key.
+	RequestRekeyingPublicShare(RequestRekeyingPublicShare),
+	/// Public portion of key share.
+	RekeyingPublicShare(RekeyingPublicShare),
+	/// Save re-keyed document key.
+	CommitRekeying(CommitRekeying),
 }
 
 /// All possible messages that can be sent during consensus establishing.
@@ -105,6 +140,21 @@ pub enum DecryptionMessage {
 	DecryptionSessionCompleted(DecryptionSessionCompleted),
 }
 
+/// All possible messages that can be sent during re-encryption session.
+#[derive(Clone, Debug)]
+pub enum ReEncryptionMessage {
+	/// Consensus establishing message.
+	ReEncryptionConsensusMessage(ReEncryptionConsensusMessage),
+	/// Request partial re-encryption from node.
+	RequestPartialReEncryption(RequestPartialReEncryption),
+	/// Partial re-encryption is completed.
+	PartialReEncryption(PartialReEncryption),
+	/// When re-encryption session error has occured.
+	ReEncryptionSessionError(ReEncryptionSessionError),
+	/// When re-encryption session is completed.
+	ReEncryptionSessionCompleted(ReEncryptionSessionCompleted),
+}
+
 /// All possible messages that can be sent during signing session.
 #[derive(Clone, Debug)]
 pub enum SigningMessage {
@@ -122,6 +172,157 @@ pub enum SigningMessage {
 	SigningSessionCompleted(SigningSessionCompleted),
 }
 
+/// All possible messages that can be sent during ECDSA signing session.
+#[derive(Clone, Debug)]
+pub enum EcdsaSigningMessage {
+	/// Consensus establishing message.
+	EcdsaSigningConsensusMessage(EcdsaSigningConsensusMessage),
+	/// Signature nonce generation message.
+	EcdsaSignatureNonceGenerationMessage(EcdsaSignatureNonceGenerationMessage),
+	/// Inversion nonce generation message.
+	EcdsaInversionNonceGenerationMessage(EcdsaInversionNonceGenerationMessage),
+	/// Shares of zero polynoms are sent to every other node of signing group.
+	EcdsaSigningZeroShares(EcdsaSigningZeroShares),
+	/// Share of inversed nonce coefficient is sent to master node.
+	EcdsaSigningInversedNonceCoeffShare(EcdsaSigningInversedNonceCoeffShare),
+	/// Request partial signature from node.
+	EcdsaRequestPartialSignature(EcdsaRequestPartialSignature),
+	/// Partial signature is generated.
+	EcdsaPartialSignature(EcdsaPartialSignature),
+	/// ECDSA signing error occured.
+	EcdsaSigningSessionError(EcdsaSigningSessionError),
+	/// ECDSA signing session completed.
+	EcdsaSigningSessionCompleted(EcdsaSigningSessionCompleted),
+}
+
+/// All possible messages that can be sent during share recovery session.
+#[derive(Clone, Debug)]
+pub enum ShareRecoveryMessage {
+	/// Initialize share recovery session.
+	InitializeShareRecoverySession(InitializeShareRecoverySession),
+	/// Confirm share recovery session initialization.
+	ConfirmShareRecoveryInitialization(ConfirmShareRecoveryInitialization),
+	/// Request share contribution from node.
+	RequestShareRecoveryContribution(RequestShareRecoveryContribution),
+	/// Blinding value is sent to other contributing node.
+	ShareRecoveryBlindingShare(ShareRecoveryBlindingShare),
+	/// Blinded share contribution is sent to the recovering node.
+	ShareRecoveryContribution(ShareRecoveryContribution),
+	/// When share recovery session error has occured.
+	ShareRecoverySessionError(ShareRecoverySessionError),
+}
+
+/// All possible messages that can be sent during share refresh session.
+#[derive(Clone, Debug)]
+pub enum ShareRefreshMessage {
+	/// Initialize share refresh session.
+	InitializeShareRefreshSession(InitializeShareRefreshSession),
+	/// Confirm share refresh session initialization.
+	ConfirmShareRefreshInitialization(ConfirmShareRefreshInitialization),
+	/// Request share deltas from node.
+	RequestShareRefreshDeltas(RequestShareRefreshDeltas),
+	/// Share delta is sent to every other node.
+	ShareRefreshDelta(ShareRefreshDelta),
+	/// Node has computed refreshed share && is ready to save it.
+	ConfirmShareRefresh(ConfirmShareRefresh),
+	/// Refreshed shares must be saved.
+	CommitShareRefresh(CommitShareRefresh),
+	/// Node has saved its refreshed share.
+	ShareRefreshCommitted(ShareRefreshCommitted),
+	/// When share refresh session error has occured.
+	ShareRefreshSessionError(ShareRefreshSessionError),
+}
+
+/// All possible messages that can be sent during key derivation session.
+#[derive(Clone, Debug)]
+pub enum KeyDerivationMessage {
+	/// Initialize key derivation session.
+	InitializeKeyDerivationSession(InitializeKeyDerivationSession),
+	/// Confirm key derivation session initialization.
+	ConfirmKeyDerivationInitialization(ConfirmKeyDerivationInitialization),
+	/// Derived key shares must be saved.
+	CommitKeyDerivation(CommitKeyDerivation),
+	/// When key derivation session error has occured.
+	KeyDerivationSessionError(KeyDerivationSessionError),
+}
+
+/// All possible messages that can be sent during key deletion session.
+#[derive(Clone, Debug)]
+pub enum KeyDeletionMessage {
+	/// Initialize key deletion session.
+	InitializeKeyDeletionSession(InitializeKeyDeletionSession),
+	/// Confirm key deletion session initialization.
+	ConfirmKeyDeletionInitialization(ConfirmKeyDeletionInitialization),
+	/// Key shares must be deleted.
+	CommitKeyDeletion(CommitKeyDeletion),
+	/// Key share deletion result.
+	KeyDeletionCompleted(KeyDeletionCompleted),
+	/// When key deletion session error has occured.
+	KeyDeletionSessionError(KeyDeletionSessionError),
+}
+
+/// All possible messages that can be sent during server key retrieval session.
+#[derive(Clone, Debug)]
+pub enum ServerKeyRetrievalMessage {
+	/// Initialize server key retrieval session.
+	InitializeServerKeyRetrievalSession(InitializeServerKeyRetrievalSession),
+	/// Public portion of key share.
+	ServerKeyRetrievalPublicShare(ServerKeyRetrievalPublicShare),
+	/// Key holder must attest the joint public.
+	RequestServerKeyAttestation(RequestServerKeyAttestation),
+	/// Joint public attestation.
+	ServerKeyAttestation(ServerKeyAttestation),
+	/// When server key retrieval session error has occured.
+	ServerKeyRetrievalSessionError(ServerKeyRetrievalSessionError),
+}
+
+/// All possible messages that can be sent during share move session.
+#[derive(Clone, Debug)]
+pub enum ShareMoveMessage {
+	/// Initialize share move session.
+	InitializeShareMoveSession(InitializeShareMoveSession),
+	/// Confirm share move session initialization.
+	ConfirmShareMoveInitialization(ConfirmShareMoveInitialization),
+	/// Moved key share.
+	ShareMoveData(ShareMoveData),
+	/// Old owner of the moved share must be replaced with the new owner.
+	CommitShareMove(CommitShareMove),
+	/// Share move has been completed on the node.
+	ShareMoveCompleted(ShareMoveCompleted),
+	/// When share move session error has occured.
+	ShareMoveSessionError(ShareMoveSessionError),
+	/// Share move session has failed => changes must be undone.
+	ShareMoveRollback(ShareMoveRollback),
+	/// Part of the moved key share, which is too large to be sent in a single message.
+	ShareMoveDataChunk(ShareMoveDataChunk),
+}
+
+/// All possible messages that can be sent during share bootstrap session.
+#[derive(Clone, Debug)]
+pub enum ShareBootstrapMessage {
+	/// Initialize share bootstrap session && send key shares to the new node.
+	InitializeShareBootstrapSession(InitializeShareBootstrapSession),
+	/// The new node has saved all key shares.
+	ShareBootstrapKeySharesSaved(ShareBootstrapKeySharesSaved),
+	/// Master node must be replaced with the new node in key shares.
+	CommitShareBootstrap(CommitShareBootstrap),
+	/// Key holder has replaced master node with the new node.
+	ShareBootstrapCommitted(ShareBootstrapCommitted),
+	/// When share bootstrap session error has occured.
+	ShareBootstrapSessionError(ShareBootstrapSessionError),
+}
+
+/// All possible messages that can be sent during key export session.
+#[derive(Clone, Debug)]
+pub enum KeyExportMessage {
+	/// Initialize key export session && ask key holder to compute its contribution.
+	InitializeKeyExportSession(InitializeKeyExportSession),
+	/// Key holder contribution to the key shares of the target nodes.
+	KeyExportContribution(KeyExportContribution),
+	/// When key export session error has occured.
+	KeyExportSessionError(KeyExportSessionError),
+}
+
 /// Introduce node public key.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodePublicKey {
@@ -131,6 +332,8 @@ pub struct NodePublicKey {
 	pub confirmation_plain: SerializableH256,
 	/// The same random `confirmation_plain`, signed with one-time session key.
 	pub confirmation_signed_session: SerializableSignature,
+	/// The newest protocol version, supported by node. Is missing in messages from nodes, not supporting negotiation.
+	pub protocol_version: Option<u8>,
 }
 
 /// Confirm that node owns the private key of previously passed public key (aka node id).
@@ -148,6 +351,81 @@ pub struct KeepAlive {
 /// Confirm that the node is still alive.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeepAliveResponse {
+	/// Node time (seconds since unix epoch), when response has been sent. None if sent by older node.
+	pub timestamp: Option<u64>,
+}
+
+/// Keys, for which receiver is listed as key holder in the sender key storage.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeySharesInventory {
+	/// Keys ids.
+	pub keys: Vec<MessageSessionId>,
+}
+
+/// Administrative request, signed by operator of the receiver && forwarded by the sender.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForwardAdminRequest {
+	/// Request id, unique on the sender node.
+	pub request_id: u64,
+	/// Request signature of the receiver node operator.
+	pub admin_signature: AdminRequestSignature,
+	/// Forwarded request.
+	pub request: AdminRequest,
+}
+
+/// Status of administrative request, forwarded to the sender.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForwardedAdminRequestStatus {
+	/// Request id.
+	pub request_id: u64,
+	/// Current status.
+	pub status: AdminRequestStatus,
+}
+
+/// Acknowledgement of session message, received from the receiver. Unacknowledged messages are retransmitted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MessageAcknowledgement {
+	/// Id of the acknowledged message.
+	pub id: SerializableH256,
+}
+
+/// Signature of administrative request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminRequestSignature {
+	/// HTTP method of the request.
+	pub method: String,
+	/// Path of the request.
+	pub endpoint: String,
+	/// Time (seconds since unix epoch), when the request has been signed.
+	pub timestamp: u64,
+	/// Request nonce.
+	pub nonce: u64,
+	/// Hash of the request body.
+	pub body_hash: SerializableH256,
+	/// Request hash, signed with node key.
+	pub signature: SerializableSignature,
+}
+
+/// Administrative request, which could be forwarded to the master node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AdminRequest {
+	/// Move shares of the next batch of master node keys (following given key) to the new node.
+	ShareBootstrap(MessageNodeId, Option<MessageSessionId>),
+	/// Export master node key to given nodes of other cluster with given threshold.
+	KeyExport(MessageSessionId, BTreeSet<MessageNodeId>, usize),
+}
+
+/// Status of administrative request on the master node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AdminRequestStatus {
+	/// Admin session has been started.
+	Started,
+	/// Share bootstrap has completed: keys of the batch && keys, which shares have been moved.
+	ShareBootstrapCompleted(BTreeSet<MessageSessionId>, BTreeSet<MessageSessionId>),
+	/// Key export has completed: key shares for all target nodes.
+	KeyExportCompleted(Vec<SerializableExportedKeyShare>),
+	/// Request has failed with given error.
+	Failed(String, Option<SerializableErrorCode>),
 }
 
 /// Initialize new DKG session.
@@ -228,6 +506,8 @@ pub struct SessionError {
 	pub session_nonce: u64,
 	/// Public key share.
 	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
 }
 
 /// When session is completed.
@@ -272,6 +552,41 @@ pub struct EncryptionSessionError {
 	pub session_nonce: u64,
 	/// Error message.
 	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Node is requested to send public portion of its key share, so that document key could be re-keyed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RequestRekeyingPublicShare {
+	/// Encryption session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Node is responding with public portion of its key share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RekeyingPublicShare {
+	/// Encryption session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Public portion of key share.
+	pub public_share: SerializablePublic,
+}
+
+/// Node is requested to replace stored document key with the new one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommitRekeying {
+	/// Encryption session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// New common point.
+	pub common_point: SerializablePublic,
+	/// New encrypted point.
+	pub encrypted_point: SerializablePublic,
 }
 
 /// Node is asked to be part of consensus group.
@@ -286,6 +601,8 @@ pub struct InitializeConsensusSession {
 pub struct ConfirmConsensusInitialization {
 	/// Is node confirmed consensus participation.
 	pub is_confirmed: bool,
+	/// Versions of the key share, known to the node (starting from the latest one).
+	pub key_versions: Vec<SerializableH256>,
 }
 
 /// Consensus-related signing message.
@@ -329,6 +646,8 @@ pub struct RequestPartialSignature {
 	pub message_hash: SerializableMessageHash,
 	/// Selected nodes.
 	pub nodes: BTreeSet<MessageNodeId>,
+	/// Version of the key share, which must be used for signing.
+	pub key_version: SerializableH256,
 }
 
 /// Partial signature.
@@ -357,6 +676,8 @@ pub struct SigningSessionError {
 	pub session_nonce: u64,
 	/// Error description.
 	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
 }
 
 /// Signing session completed.
@@ -370,6 +691,135 @@ pub struct SigningSessionCompleted {
 	pub session_nonce: u64,
 }
 
+/// Consensus-related ECDSA signing message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaSigningConsensusMessage {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Consensus message.
+	pub message: ConsensusMessage,
+}
+
+/// ECDSA signature nonce generation message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaSignatureNonceGenerationMessage {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Generation message.
+	pub message: GenerationMessage,
+}
+
+/// ECDSA inversion nonce generation message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaInversionNonceGenerationMessage {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Generation message.
+	pub message: GenerationMessage,
+}
+
+/// Shares of random polynoms with zero constant term, computed for the receiver.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaSigningZeroShares {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Share, used to blind inversed nonce coefficient share.
+	pub inversion_zero_share: SerializableSecret,
+	/// Share, used to blind partial signature.
+	pub signature_zero_share: SerializableSecret,
+}
+
+/// Share of inversed nonce coefficient.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaSigningInversedNonceCoeffShare {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Inversed nonce coefficient share.
+	pub inversed_nonce_coeff_share: SerializableSecret,
+}
+
+/// Request partial ECDSA signature.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaRequestPartialSignature {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Request id.
+	pub request_id: SerializableSecret,
+	/// Inversed nonce coefficient.
+	pub inversed_nonce_coeff: SerializableSecret,
+	/// Message hash.
+	pub message_hash: SerializableMessageHash,
+	/// Selected nodes.
+	pub nodes: BTreeSet<MessageNodeId>,
+	/// Version of the key share, which must be used for signing.
+	pub key_version: SerializableH256,
+}
+
+/// Partial ECDSA signature.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaPartialSignature {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Request id.
+	pub request_id: SerializableSecret,
+	/// S part of signature.
+	pub partial_signature_s: SerializableSecret,
+}
+
+/// When ECDSA signing session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaSigningSessionError {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error description.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// ECDSA signing session completed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaSigningSessionCompleted {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Signing session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
 /// Consensus-related decryption message.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DecryptionConsensusMessage {
@@ -399,6 +849,8 @@ pub struct RequestPartialDecryption {
 	pub is_shadow_decryption: bool,
 	/// Nodes that are agreed to do a decryption.
 	pub nodes: BTreeSet<MessageNodeId>,
+	/// Version of the key share, which must be used for decryption.
+	pub key_version: SerializableH256,
 }
 
 /// Node has partially decrypted the secret.
@@ -416,6 +868,8 @@ pub struct PartialDecryption {
 	pub shadow_point: SerializablePublic,
 	/// Decrypt shadow coefficient (if requested), encrypted with requestor public.
 	pub decrypt_shadow: Option<Vec<u8>>,
+	/// Proof of node decryption share (if shadow decryption is requested), encrypted with requestor public.
+	pub decrypt_proof: Option<Vec<u8>>,
 }
 
 /// When decryption session error has occured.
@@ -429,6 +883,8 @@ pub struct DecryptionSessionError {
 	pub session_nonce: u64,
 	/// Public key share.
 	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
 }
 
 /// When decryption session is completed.
@@ -442,55 +898,736 @@ pub struct DecryptionSessionCompleted {
 	pub session_nonce: u64,
 }
 
-impl GenerationMessage {
-	pub fn session_id(&self) -> &SessionId {
-		match *self {
-			GenerationMessage::InitializeSession(ref msg) => &msg.session,
-			GenerationMessage::ConfirmInitialization(ref msg) => &msg.session,
-			GenerationMessage::CompleteInitialization(ref msg) => &msg.session,
-			GenerationMessage::KeysDissemination(ref msg) => &msg.session,
-			GenerationMessage::PublicKeyShare(ref msg) => &msg.session,
-			GenerationMessage::SessionError(ref msg) => &msg.session,
-			GenerationMessage::SessionCompleted(ref msg) => &msg.session,
-		}
-	}
+/// Consensus-related re-encryption message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReEncryptionConsensusMessage {
+	/// Generation session Id.
+	pub session: MessageSessionId,
+	/// Re-encryption session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Consensus message.
+	pub message: ConsensusMessage,
+}
 
-	pub fn session_nonce(&self) -> u64 {
-		match *self {
-			GenerationMessage::InitializeSession(ref msg) => msg.session_nonce,
-			GenerationMessage::ConfirmInitialization(ref msg) => msg.session_nonce,
-			GenerationMessage::CompleteInitialization(ref msg) => msg.session_nonce,
-			GenerationMessage::KeysDissemination(ref msg) => msg.session_nonce,
-			GenerationMessage::PublicKeyShare(ref msg) => msg.session_nonce,
-			GenerationMessage::SessionError(ref msg) => msg.session_nonce,
-			GenerationMessage::SessionCompleted(ref msg) => msg.session_nonce,
-		}
-	}
+/// Node is requested to do a partial re-encryption.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RequestPartialReEncryption {
+	/// Encryption session Id.
+	pub session: MessageSessionId,
+	/// Re-encryption session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Request id.
+	pub request_id: SerializableSecret,
+	/// Public key, which document key must be re-encrypted with.
+	pub target_public: SerializablePublic,
+	/// Target public key signature, made by requester.
+	pub target_signature: SerializableSignature,
+	/// Nodes that are agreed to do a re-encryption.
+	pub nodes: BTreeSet<MessageNodeId>,
+	/// Version of the key share, which must be used for re-encryption.
+	pub key_version: SerializableH256,
 }
 
-impl EncryptionMessage {
-	pub fn session_id(&self) -> &SessionId {
-		match *self {
-			EncryptionMessage::InitializeEncryptionSession(ref msg) => &msg.session,
-			EncryptionMessage::ConfirmEncryptionInitialization(ref msg) => &msg.session,
-			EncryptionMessage::EncryptionSessionError(ref msg) => &msg.session,
-		}
-	}
+/// Node has partially re-encrypted the secret.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartialReEncryption {
+	/// Encryption session Id.
+	pub session: MessageSessionId,
+	/// Re-encryption session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Request id.
+	pub request_id: SerializableSecret,
+	/// Node contribution to common point of re-encrypted secret.
+	pub common_shadow: SerializablePublic,
+	/// Node contribution to encrypted point of re-encrypted secret.
+	pub reencryption_shadow: SerializablePublic,
+}
 
-	pub fn session_nonce(&self) -> u64 {
-		match *self {
-			EncryptionMessage::InitializeEncryptionSession(ref msg) => msg.session_nonce,
-			EncryptionMessage::ConfirmEncryptionInitialization(ref msg) => msg.session_nonce,
-			EncryptionMessage::EncryptionSessionError(ref msg) => msg.session_nonce,
-		}
-	}
+/// When re-encryption session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReEncryptionSessionError {
+	/// Encryption session Id.
+	pub session: MessageSessionId,
+	/// Re-encryption session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
 }
 
-impl DecryptionMessage {
-	pub fn session_id(&self) -> &SessionId {
-		match *self {
-			DecryptionMessage::DecryptionConsensusMessage(ref msg) => &msg.session,
-			DecryptionMessage::RequestPartialDecryption(ref msg) => &msg.session,
+/// When re-encryption session is completed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReEncryptionSessionCompleted {
+	/// Encryption session Id.
+	pub session: MessageSessionId,
+	/// Re-encryption session Id.
+	pub sub_session: SerializableSecret,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Node is requested to contribute to recovery of the share of master node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeShareRecoverySession {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Nodes, which are contributing to the share recovery.
+	pub nodes: BTreeSet<MessageNodeId>,
+	/// Is key data requested (when recovering node has lost its key share).
+	pub is_key_data_requested: bool,
+}
+
+/// Node is responding to share recovery initialization request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConfirmShareRecoveryInitialization {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Key data (if requested).
+	pub key_data: Option<ShareRecoveryKeyData>,
+}
+
+/// Key data (all but the secret share), which is the same on all key holders.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRecoveryKeyData {
+	/// Key author.
+	pub author: SerializablePublic,
+	/// Decryption threshold.
+	pub threshold: usize,
+	/// Nodes ids numbers.
+	pub id_numbers: BTreeMap<MessageNodeId, SerializableSecret>,
+	/// Common (shared) encryption point.
+	pub common_point: Option<SerializablePublic>,
+	/// Encrypted point.
+	pub encrypted_point: Option<SerializablePublic>,
+	/// Id of the last share refresh. None if key shares have never been refreshed or if sent by older node.
+	pub refresh_id: Option<SerializableH256>,
+}
+
+/// Node is requested to compute its share contribution.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RequestShareRecoveryContribution {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Random blinding value, which is added to the contribution of receiver && subtracted from the contribution of sender.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRecoveryBlindingShare {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Blinding value.
+	pub blinding: SerializableSecret,
+}
+
+/// Blinded share contribution.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRecoveryContribution {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Blinded contribution.
+	pub contribution: SerializableSecret,
+}
+
+/// When share recovery session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRecoverySessionError {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Node is requested to take part in share refresh.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeShareRefreshSession {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// All key share holders.
+	pub nodes: BTreeSet<MessageNodeId>,
+}
+
+/// Node is responding to share refresh initialization request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConfirmShareRefreshInitialization {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Node is requested to generate && send share deltas to every other node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RequestShareRefreshDeltas {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Value of random polynom with zero constant term at receiver id number.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRefreshDelta {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Share delta.
+	pub delta: SerializableSecret,
+}
+
+/// Node has computed refreshed share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConfirmShareRefresh {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Every node must save its refreshed share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommitShareRefresh {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Node has saved its refreshed share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRefreshCommitted {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// When share refresh session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareRefreshSessionError {
+	/// Key (generation session) Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Node is requested to derive share of child key from its share of parent key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeKeyDerivationSession {
+	/// Derived key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Parent key Id.
+	pub parent_key_id: MessageSessionId,
+	/// Derivation path.
+	pub derivation_path: SerializableH256,
+	/// Requestor signature.
+	pub requestor_signature: SerializableSignature,
+}
+
+/// Node has derived its share of child key && is ready to save it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConfirmKeyDerivationInitialization {
+	/// Derived key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Public portion of derived key share.
+	pub public_share: SerializablePublic,
+}
+
+/// Every node must save its share of derived key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommitKeyDerivation {
+	/// Derived key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Public portion of derived key.
+	pub derived_public: SerializablePublic,
+}
+
+/// When key derivation session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyDerivationSessionError {
+	/// Derived key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Node is requested to confirm deletion of its key share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeKeyDeletionSession {
+	/// Deleted key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Requestor signature.
+	pub requestor_signature: SerializableSignature,
+}
+
+/// Node has confirmed that requester is allowed to delete key && is ready to delete its key share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConfirmKeyDeletionInitialization {
+	/// Deleted key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Every node must delete its key share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommitKeyDeletion {
+	/// Deleted key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Node reports result of key share deletion.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyDeletionCompleted {
+	/// Deleted key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Is key share deleted?
+	pub is_deleted: bool,
+}
+
+/// When key deletion session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyDeletionSessionError {
+	/// Deleted key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Node is requested to confirm move of master node key share to the new owner.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeShareMoveSession {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// The new owner of the moved share.
+	pub new_node: MessageNodeId,
+}
+
+/// Node has confirmed that it is ready to participate in share move.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConfirmShareMoveInitialization {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Key share, moved to the new owner.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareMoveData {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Key author.
+	pub author: SerializablePublic,
+	/// Key threshold.
+	pub threshold: usize,
+	/// Id numbers of all key holders (with the new owner instead of the old one).
+	pub id_numbers: BTreeMap<MessageNodeId, SerializableSecret>,
+	/// Moved secret share.
+	pub secret_share: SerializableSecret,
+	/// Common (shared) encryption point.
+	pub common_point: Option<SerializablePublic>,
+	/// Encrypted point.
+	pub encrypted_point: Option<SerializablePublic>,
+	/// Id of the last share refresh. None if key shares have never been refreshed or if sent by older node.
+	pub refresh_id: Option<SerializableH256>,
+}
+
+/// Part of the serialized ShareMoveData message, which is too large to be sent in a single message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareMoveDataChunk {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Index of this chunk.
+	pub index: usize,
+	/// Total number of chunks.
+	pub count: usize,
+	/// Hash of the whole serialized ShareMoveData message.
+	pub payload_hash: SerializableH256,
+	/// Chunk data.
+	pub data: SerializableBytes,
+}
+
+/// Every key holder must replace the old owner of the moved share with the new owner.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommitShareMove {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Node reports that share move has been completed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareMoveCompleted {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// When share move session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareMoveSessionError {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Share move session has failed && every node must return to the key share, it has been holding before the session.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareMoveRollback {
+	/// Moved key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Id numbers of all key holders before the session.
+	pub id_numbers: BTreeMap<MessageNodeId, SerializableSecret>,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Key share of master node, sent to the new node during share bootstrap.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BootstrapKeyShare {
+	/// Key Id.
+	pub key_id: MessageSessionId,
+	/// Key author.
+	pub author: SerializablePublic,
+	/// Key threshold.
+	pub threshold: usize,
+	/// Id numbers of all key holders (with the new node instead of master node).
+	pub id_numbers: BTreeMap<MessageNodeId, SerializableSecret>,
+	/// Secret share, encrypted with the key, agreed by master node && the new node.
+	pub encrypted_secret_share: SerializableBytes,
+	/// Common (shared) encryption point.
+	pub common_point: Option<SerializablePublic>,
+	/// Encrypted point.
+	pub encrypted_point: Option<SerializablePublic>,
+	/// Id of the last share refresh. None if key shares have never been refreshed or if sent by older node.
+	pub refresh_id: Option<SerializableH256>,
+}
+
+/// The new node is requested to save batch of master node key shares.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeShareBootstrapSession {
+	/// Session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Signature of master node operator.
+	pub admin_signature: SerializableSignature,
+	/// Key shares.
+	pub key_shares: Vec<BootstrapKeyShare>,
+}
+
+/// The new node reports that all key shares are saved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareBootstrapKeySharesSaved {
+	/// Session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// Key holder must replace master node with the new node in the given key shares.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommitShareBootstrap {
+	/// Session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Signature of master node operator.
+	pub admin_signature: SerializableSignature,
+	/// The new node.
+	pub new_node: MessageNodeId,
+	/// Ids of keys, held by the key holder.
+	pub keys: BTreeSet<MessageSessionId>,
+}
+
+/// Key holder reports that master node is replaced with the new node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareBootstrapCommitted {
+	/// Session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+}
+
+/// When share bootstrap session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShareBootstrapSessionError {
+	/// Session Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Key holder is requested to compute its contribution to the key shares of the target nodes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeKeyExportSession {
+	/// Key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Signature of master node operator.
+	pub admin_signature: SerializableSignature,
+	/// Key holders, which are contributing to the key shares of the target nodes.
+	pub contributors: BTreeSet<MessageNodeId>,
+	/// Key threshold in the target cluster.
+	pub threshold: usize,
+	/// Id numbers of all target nodes.
+	pub id_numbers: BTreeMap<MessageNodeId, SerializableSecret>,
+}
+
+/// Key holder contribution to the key shares of the target nodes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyExportContribution {
+	/// Key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Commitments to coefficients of key holder polynom.
+	pub commitments: Vec<SerializablePublic>,
+	/// Values of key holder polynom at id numbers of target nodes, encrypted with keys, agreed by key holder && target nodes.
+	pub encrypted_sub_shares: BTreeMap<MessageNodeId, SerializableBytes>,
+}
+
+/// When key export session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyExportSessionError {
+	/// Key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+/// Node is requested to send public portion of its key share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitializeServerKeyRetrievalSession {
+	/// Server key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Requestor signature.
+	pub requestor_signature: SerializableSignature,
+}
+
+/// Public portion of node key share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerKeyRetrievalPublicShare {
+	/// Server key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Public portion of key share.
+	pub public_share: SerializablePublic,
+}
+
+/// Node is requested to attest the joint public, restored from given public shares.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RequestServerKeyAttestation {
+	/// Server key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Public portions of key shares of all session participants.
+	pub public_shares: BTreeMap<MessageNodeId, SerializablePublic>,
+}
+
+/// Node has attested the joint public.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerKeyAttestation {
+	/// Server key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// keccak(key_id | joint_public), signed with node key.
+	pub signature: SerializableSignature,
+}
+
+/// When server key retrieval session error has occured.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerKeyRetrievalSessionError {
+	/// Server key Id.
+	pub session: MessageSessionId,
+	/// Session-level nonce.
+	pub session_nonce: u64,
+	/// Error message.
+	pub error: String,
+	/// Error code. None if sent by older node.
+	pub code: Option<SerializableErrorCode>,
+}
+
+impl Message {
+	/// Get id of the session, which the message is attached to. Cluster messages are not attached to any session.
+	pub fn session_id(&self) -> Option<&SessionId> {
+		match *self {
+			Message::Cluster(_) => None,
+			Message::Generation(ref message) => Some(message.session_id()),
+			Message::Encryption(ref message) => Some(message.session_id()),
+			Message::Decryption(ref message) => Some(message.session_id()),
+			Message::ReEncryption(ref message) => Some(message.session_id()),
+			Message::Signing(ref message) => Some(message.session_id()),
+			Message::EcdsaSigning(ref message) => Some(message.session_id()),
+			Message::ShareRecovery(ref message) => Some(message.session_id()),
+			Message::ShareRefresh(ref message) => Some(message.session_id()),
+			Message::KeyDerivation(ref message) => Some(message.session_id()),
+			Message::KeyDeletion(ref message) => Some(message.session_id()),
+			Message::ShareMove(ref message) => Some(message.session_id()),
+			Message::ServerKeyRetrieval(ref message) => Some(message.session_id()),
+			Message::ShareBootstrap(ref message) => Some(message.session_id()),
+			Message::KeyExport(ref message) => Some(message.session_id()),
+		}
+	}
+
+	/// Get session-level nonce of the message. Cluster messages are not attached to any session.
+	pub fn session_nonce(&self) -> Option<u64> {
+		match *self {
+			Message::Cluster(_) => None,
+			Message::Generation(ref message) => Some(message.session_nonce()),
+			Message::Encryption(ref message) => Some(message.session_nonce()),
+			Message::Decryption(ref message) => Some(message.session_nonce()),
+			Message::ReEncryption(ref message) => Some(message.session_nonce()),
+			Message::Signing(ref message) => Some(message.session_nonce()),
+			Message::EcdsaSigning(ref message) => Some(message.session_nonce()),
+			Message::ShareRecovery(ref message) => Some(message.session_nonce()),
+			Message::ShareRefresh(ref message) => Some(message.session_nonce()),
+			Message::KeyDerivation(ref message) => Some(message.session_nonce()),
+			Message::KeyDeletion(ref message) => Some(message.session_nonce()),
+			Message::ShareMove(ref message) => Some(message.session_nonce()),
+			Message::ServerKeyRetrieval(ref message) => Some(message.session_nonce()),
+			Message::ShareBootstrap(ref message) => Some(message.session_nonce()),
+			Message::KeyExport(ref message) => Some(message.session_nonce()),
+		}
+	}
+}
+
+impl GenerationMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			GenerationMessage::InitializeSession(ref msg) => &msg.session,
+			GenerationMessage::ConfirmInitialization(ref msg) => &msg.session,
+			GenerationMessage::CompleteInitialization(ref msg) => &msg.session,
+			GenerationMessage::KeysDissemination(ref msg) => &msg.session,
+			GenerationMessage::PublicKeyShare(ref msg) => &msg.session,
+			GenerationMessage::SessionError(ref msg) => &msg.session,
+			GenerationMessage::SessionCompleted(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			GenerationMessage::InitializeSession(ref msg) => msg.session_nonce,
+			GenerationMessage::ConfirmInitialization(ref msg) => msg.session_nonce,
+			GenerationMessage::CompleteInitialization(ref msg) => msg.session_nonce,
+			GenerationMessage::KeysDissemination(ref msg) => msg.session_nonce,
+			GenerationMessage::PublicKeyShare(ref msg) => msg.session_nonce,
+			GenerationMessage::SessionError(ref msg) => msg.session_nonce,
+			GenerationMessage::SessionCompleted(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl EncryptionMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			EncryptionMessage::InitializeEncryptionSession(ref msg) => &msg.session,
+			EncryptionMessage::ConfirmEncryptionInitialization(ref msg) => &msg.session,
+			EncryptionMessage::EncryptionSessionError(ref msg) => &msg.session,
+			EncryptionMessage::RequestRekeyingPublicShare(ref msg) => &msg.session,
+			EncryptionMessage::RekeyingPublicShare(ref msg) => &msg.session,
+			EncryptionMessage::CommitRekeying(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			EncryptionMessage::InitializeEncryptionSession(ref msg) => msg.session_nonce,
+			EncryptionMessage::ConfirmEncryptionInitialization(ref msg) => msg.session_nonce,
+			EncryptionMessage::EncryptionSessionError(ref msg) => msg.session_nonce,
+			EncryptionMessage::RequestRekeyingPublicShare(ref msg) => msg.session_nonce,
+			EncryptionMessage::RekeyingPublicShare(ref msg) => msg.session_nonce,
+			EncryptionMessage::CommitRekeying(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl DecryptionMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			DecryptionMessage::DecryptionConsensusMessage(ref msg) => &msg.session,
+			DecryptionMessage::RequestPartialDecryption(ref msg) => &msg.session,
 			DecryptionMessage::PartialDecryption(ref msg) => &msg.session,
 			DecryptionMessage::DecryptionSessionError(ref msg) => &msg.session,
 			DecryptionMessage::DecryptionSessionCompleted(ref msg) => &msg.session,
@@ -518,6 +1655,38 @@ impl DecryptionMessage {
 	}
 }
 
+impl ReEncryptionMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			ReEncryptionMessage::ReEncryptionConsensusMessage(ref msg) => &msg.session,
+			ReEncryptionMessage::RequestPartialReEncryption(ref msg) => &msg.session,
+			ReEncryptionMessage::PartialReEncryption(ref msg) => &msg.session,
+			ReEncryptionMessage::ReEncryptionSessionError(ref msg) => &msg.session,
+			ReEncryptionMessage::ReEncryptionSessionCompleted(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn sub_session_id(&self) -> &Secret {
+		match *self {
+			ReEncryptionMessage::ReEncryptionConsensusMessage(ref msg) => &msg.sub_session,
+			ReEncryptionMessage::RequestPartialReEncryption(ref msg) => &msg.sub_session,
+			ReEncryptionMessage::PartialReEncryption(ref msg) => &msg.sub_session,
+			ReEncryptionMessage::ReEncryptionSessionError(ref msg) => &msg.sub_session,
+			ReEncryptionMessage::ReEncryptionSessionCompleted(ref msg) => &msg.sub_session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			ReEncryptionMessage::ReEncryptionConsensusMessage(ref msg) => msg.session_nonce,
+			ReEncryptionMessage::RequestPartialReEncryption(ref msg) => msg.session_nonce,
+			ReEncryptionMessage::PartialReEncryption(ref msg) => msg.session_nonce,
+			ReEncryptionMessage::ReEncryptionSessionError(ref msg) => msg.session_nonce,
+			ReEncryptionMessage::ReEncryptionSessionCompleted(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
 impl SigningMessage {
 	pub fn session_id(&self) -> &SessionId {
 		match *self {
@@ -553,6 +1722,234 @@ impl SigningMessage {
 	}
 }
 
+impl EcdsaSigningMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			EcdsaSigningMessage::EcdsaSigningConsensusMessage(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaSigningZeroShares(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaRequestPartialSignature(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaPartialSignature(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaSigningSessionError(ref msg) => &msg.session,
+			EcdsaSigningMessage::EcdsaSigningSessionCompleted(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn sub_session_id(&self) -> &Secret {
+		match *self {
+			EcdsaSigningMessage::EcdsaSigningConsensusMessage(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaSigningZeroShares(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaRequestPartialSignature(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaPartialSignature(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaSigningSessionError(ref msg) => &msg.sub_session,
+			EcdsaSigningMessage::EcdsaSigningSessionCompleted(ref msg) => &msg.sub_session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			EcdsaSigningMessage::EcdsaSigningConsensusMessage(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaSigningZeroShares(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaRequestPartialSignature(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaPartialSignature(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaSigningSessionError(ref msg) => msg.session_nonce,
+			EcdsaSigningMessage::EcdsaSigningSessionCompleted(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl ShareRecoveryMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			ShareRecoveryMessage::InitializeShareRecoverySession(ref msg) => &msg.session,
+			ShareRecoveryMessage::ConfirmShareRecoveryInitialization(ref msg) => &msg.session,
+			ShareRecoveryMessage::RequestShareRecoveryContribution(ref msg) => &msg.session,
+			ShareRecoveryMessage::ShareRecoveryBlindingShare(ref msg) => &msg.session,
+			ShareRecoveryMessage::ShareRecoveryContribution(ref msg) => &msg.session,
+			ShareRecoveryMessage::ShareRecoverySessionError(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			ShareRecoveryMessage::InitializeShareRecoverySession(ref msg) => msg.session_nonce,
+			ShareRecoveryMessage::ConfirmShareRecoveryInitialization(ref msg) => msg.session_nonce,
+			ShareRecoveryMessage::RequestShareRecoveryContribution(ref msg) => msg.session_nonce,
+			ShareRecoveryMessage::ShareRecoveryBlindingShare(ref msg) => msg.session_nonce,
+			ShareRecoveryMessage::ShareRecoveryContribution(ref msg) => msg.session_nonce,
+			ShareRecoveryMessage::ShareRecoverySessionError(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl ShareRefreshMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			ShareRefreshMessage::InitializeShareRefreshSession(ref msg) => &msg.session,
+			ShareRefreshMessage::ConfirmShareRefreshInitialization(ref msg) => &msg.session,
+			ShareRefreshMessage::RequestShareRefreshDeltas(ref msg) => &msg.session,
+			ShareRefreshMessage::ShareRefreshDelta(ref msg) => &msg.session,
+			ShareRefreshMessage::ConfirmShareRefresh(ref msg) => &msg.session,
+			ShareRefreshMessage::CommitShareRefresh(ref msg) => &msg.session,
+			ShareRefreshMessage::ShareRefreshCommitted(ref msg) => &msg.session,
+			ShareRefreshMessage::ShareRefreshSessionError(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			ShareRefreshMessage::InitializeShareRefreshSession(ref msg) => msg.session_nonce,
+			ShareRefreshMessage::ConfirmShareRefreshInitialization(ref msg) => msg.session_nonce,
+			ShareRefreshMessage::RequestShareRefreshDeltas(ref msg) => msg.session_nonce,
+			ShareRefreshMessage::ShareRefreshDelta(ref msg) => msg.session_nonce,
+			ShareRefreshMessage::ConfirmShareRefresh(ref msg) => msg.session_nonce,
+			ShareRefreshMessage::CommitShareRefresh(ref msg) => msg.session_nonce,
+			ShareRefreshMessage::ShareRefreshCommitted(ref msg) => msg.session_nonce,
+			ShareRefreshMessage::ShareRefreshSessionError(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl KeyDerivationMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			KeyDerivationMessage::InitializeKeyDerivationSession(ref msg) => &msg.session,
+			KeyDerivationMessage::ConfirmKeyDerivationInitialization(ref msg) => &msg.session,
+			KeyDerivationMessage::CommitKeyDerivation(ref msg) => &msg.session,
+			KeyDerivationMessage::KeyDerivationSessionError(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			KeyDerivationMessage::InitializeKeyDerivationSession(ref msg) => msg.session_nonce,
+			KeyDerivationMessage::ConfirmKeyDerivationInitialization(ref msg) => msg.session_nonce,
+			KeyDerivationMessage::CommitKeyDerivation(ref msg) => msg.session_nonce,
+			KeyDerivationMessage::KeyDerivationSessionError(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl KeyDeletionMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			KeyDeletionMessage::InitializeKeyDeletionSession(ref msg) => &msg.session,
+			KeyDeletionMessage::ConfirmKeyDeletionInitialization(ref msg) => &msg.session,
+			KeyDeletionMessage::CommitKeyDeletion(ref msg) => &msg.session,
+			KeyDeletionMessage::KeyDeletionCompleted(ref msg) => &msg.session,
+			KeyDeletionMessage::KeyDeletionSessionError(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			KeyDeletionMessage::InitializeKeyDeletionSession(ref msg) => msg.session_nonce,
+			KeyDeletionMessage::ConfirmKeyDeletionInitialization(ref msg) => msg.session_nonce,
+			KeyDeletionMessage::CommitKeyDeletion(ref msg) => msg.session_nonce,
+			KeyDeletionMessage::KeyDeletionCompleted(ref msg) => msg.session_nonce,
+			KeyDeletionMessage::KeyDeletionSessionError(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl ServerKeyRetrievalMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(ref msg) => &msg.session,
+			ServerKeyRetrievalMessage::ServerKeyRetrievalPublicShare(ref msg) => &msg.session,
+			ServerKeyRetrievalMessage::RequestServerKeyAttestation(ref msg) => &msg.session,
+			ServerKeyRetrievalMessage::ServerKeyAttestation(ref msg) => &msg.session,
+			ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(ref msg) => msg.session_nonce,
+			ServerKeyRetrievalMessage::ServerKeyRetrievalPublicShare(ref msg) => msg.session_nonce,
+			ServerKeyRetrievalMessage::RequestServerKeyAttestation(ref msg) => msg.session_nonce,
+			ServerKeyRetrievalMessage::ServerKeyAttestation(ref msg) => msg.session_nonce,
+			ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl ShareMoveMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			ShareMoveMessage::InitializeShareMoveSession(ref msg) => &msg.session,
+			ShareMoveMessage::ConfirmShareMoveInitialization(ref msg) => &msg.session,
+			ShareMoveMessage::ShareMoveData(ref msg) => &msg.session,
+			ShareMoveMessage::CommitShareMove(ref msg) => &msg.session,
+			ShareMoveMessage::ShareMoveCompleted(ref msg) => &msg.session,
+			ShareMoveMessage::ShareMoveSessionError(ref msg) => &msg.session,
+			ShareMoveMessage::ShareMoveRollback(ref msg) => &msg.session,
+			ShareMoveMessage::ShareMoveDataChunk(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			ShareMoveMessage::InitializeShareMoveSession(ref msg) => msg.session_nonce,
+			ShareMoveMessage::ConfirmShareMoveInitialization(ref msg) => msg.session_nonce,
+			ShareMoveMessage::ShareMoveData(ref msg) => msg.session_nonce,
+			ShareMoveMessage::CommitShareMove(ref msg) => msg.session_nonce,
+			ShareMoveMessage::ShareMoveCompleted(ref msg) => msg.session_nonce,
+			ShareMoveMessage::ShareMoveSessionError(ref msg) => msg.session_nonce,
+			ShareMoveMessage::ShareMoveRollback(ref msg) => msg.session_nonce,
+			ShareMoveMessage::ShareMoveDataChunk(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl ShareBootstrapMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			ShareBootstrapMessage::InitializeShareBootstrapSession(ref msg) => &msg.session,
+			ShareBootstrapMessage::ShareBootstrapKeySharesSaved(ref msg) => &msg.session,
+			ShareBootstrapMessage::CommitShareBootstrap(ref msg) => &msg.session,
+			ShareBootstrapMessage::ShareBootstrapCommitted(ref msg) => &msg.session,
+			ShareBootstrapMessage::ShareBootstrapSessionError(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			ShareBootstrapMessage::InitializeShareBootstrapSession(ref msg) => msg.session_nonce,
+			ShareBootstrapMessage::ShareBootstrapKeySharesSaved(ref msg) => msg.session_nonce,
+			ShareBootstrapMessage::CommitShareBootstrap(ref msg) => msg.session_nonce,
+			ShareBootstrapMessage::ShareBootstrapCommitted(ref msg) => msg.session_nonce,
+			ShareBootstrapMessage::ShareBootstrapSessionError(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
+impl KeyExportMessage {
+	pub fn session_id(&self) -> &SessionId {
+		match *self {
+			KeyExportMessage::InitializeKeyExportSession(ref msg) => &msg.session,
+			KeyExportMessage::KeyExportContribution(ref msg) => &msg.session,
+			KeyExportMessage::KeyExportSessionError(ref msg) => &msg.session,
+		}
+	}
+
+	pub fn session_nonce(&self) -> u64 {
+		match *self {
+			KeyExportMessage::InitializeKeyExportSession(ref msg) => msg.session_nonce,
+			KeyExportMessage::KeyExportContribution(ref msg) => msg.session_nonce,
+			KeyExportMessage::KeyExportSessionError(ref msg) => msg.session_nonce,
+		}
+	}
+}
+
 impl fmt::Display for Message {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match *self {
@@ -560,7 +1957,17 @@ impl fmt::Display for Message {
 			Message::Generation(ref message) => write!(f, "Generation.{}", message),
 			Message::Encryption(ref message) => write!(f, "Encryption.{}", message),
 			Message::Decryption(ref message) => write!(f, "Decryption.{}", message),
+			Message::ReEncryption(ref message) => write!(f, "ReEncryption.{}", message),
 			Message::Signing(ref message) => write!(f, "Signing.{}", message),
+			Message::EcdsaSigning(ref message) => write!(f, "EcdsaSigning.{}", message),
+			Message::ShareRecovery(ref message) => write!(f, "ShareRecovery.{}", message),
+			Message::ShareRefresh(ref message) => write!(f, "ShareRefresh.{}", message),
+			Message::KeyDerivation(ref message) => write!(f, "KeyDerivation.{}", message),
+			Message::KeyDeletion(ref message) => write!(f, "KeyDeletion.{}", message),
+			Message::ShareMove(ref message) => write!(f, "ShareMove.{}", message),
+			Message::ServerKeyRetrieval(ref message) => write!(f, "ServerKeyRetrieval.{}", message),
+			Message::ShareBootstrap(ref message) => write!(f, "ShareBootstrap.{}", message),
+			Message::KeyExport(ref message) => write!(f, "KeyExport.{}", message),
 		}
 	}
 }
@@ -572,6 +1979,10 @@ impl fmt::Display for ClusterMessage {
 			ClusterMessage::NodePrivateKeySignature(_) => write!(f, "NodePrivateKeySignature"),
 			ClusterMessage::KeepAlive(_) => write!(f, "KeepAlive"),
 			ClusterMessage::KeepAliveResponse(_) => write!(f, "KeepAliveResponse"),
+			ClusterMessage::KeySharesInventory(_) => write!(f, "KeySharesInventory"),
+			ClusterMessage::ForwardAdminRequest(_) => write!(f, "ForwardAdminRequest"),
+			ClusterMessage::ForwardedAdminRequestStatus(_) => write!(f, "ForwardedAdminRequestStatus"),
+			ClusterMessage::MessageAcknowledgement(_) => write!(f, "MessageAcknowledgement"),
 		}
 	}
 }
@@ -596,6 +2007,9 @@ impl fmt::Display for EncryptionMessage {
 			EncryptionMessage::InitializeEncryptionSession(_) => write!(f, "InitializeEncryptionSession"),
 			EncryptionMessage::ConfirmEncryptionInitialization(_) => write!(f, "ConfirmEncryptionInitialization"),
 			EncryptionMessage::EncryptionSessionError(ref msg) => write!(f, "EncryptionSessionError({})", msg.error),
+			EncryptionMessage::RequestRekeyingPublicShare(_) => write!(f, "RequestRekeyingPublicShare"),
+			EncryptionMessage::RekeyingPublicShare(_) => write!(f, "RekeyingPublicShare"),
+			EncryptionMessage::CommitRekeying(_) => write!(f, "CommitRekeying"),
 		}
 	}
 }
@@ -621,6 +2035,18 @@ impl fmt::Display for DecryptionMessage {
 	}
 }
 
+impl fmt::Display for ReEncryptionMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReEncryptionMessage::ReEncryptionConsensusMessage(ref m) => write!(f, "ReEncryptionConsensusMessage.{}", m.message),
+			ReEncryptionMessage::RequestPartialReEncryption(_) => write!(f, "RequestPartialReEncryption"),
+			ReEncryptionMessage::PartialReEncryption(_) => write!(f, "PartialReEncryption"),
+			ReEncryptionMessage::ReEncryptionSessionError(_) => write!(f, "ReEncryptionSessionError"),
+			ReEncryptionMessage::ReEncryptionSessionCompleted(_) => write!(f, "ReEncryptionSessionCompleted"),
+		}
+	}
+}
+
 impl fmt::Display for SigningMessage {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match *self {
@@ -633,3 +2059,119 @@ impl fmt::Display for SigningMessage {
 		}
 	}
 }
+
+impl fmt::Display for EcdsaSigningMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			EcdsaSigningMessage::EcdsaSigningConsensusMessage(ref m) => write!(f, "EcdsaSigningConsensusMessage.{}", m.message),
+			EcdsaSigningMessage::EcdsaSignatureNonceGenerationMessage(ref m) => write!(f, "EcdsaSignatureNonceGenerationMessage.{}", m.message),
+			EcdsaSigningMessage::EcdsaInversionNonceGenerationMessage(ref m) => write!(f, "EcdsaInversionNonceGenerationMessage.{}", m.message),
+			EcdsaSigningMessage::EcdsaSigningZeroShares(_) => write!(f, "EcdsaSigningZeroShares"),
+			EcdsaSigningMessage::EcdsaSigningInversedNonceCoeffShare(_) => write!(f, "EcdsaSigningInversedNonceCoeffShare"),
+			EcdsaSigningMessage::EcdsaRequestPartialSignature(_) => write!(f, "EcdsaRequestPartialSignature"),
+			EcdsaSigningMessage::EcdsaPartialSignature(_) => write!(f, "EcdsaPartialSignature"),
+			EcdsaSigningMessage::EcdsaSigningSessionError(_) => write!(f, "EcdsaSigningSessionError"),
+			EcdsaSigningMessage::EcdsaSigningSessionCompleted(_) => write!(f, "EcdsaSigningSessionCompleted"),
+		}
+	}
+}
+
+impl fmt::Display for ShareRecoveryMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ShareRecoveryMessage::InitializeShareRecoverySession(_) => write!(f, "InitializeShareRecoverySession"),
+			ShareRecoveryMessage::ConfirmShareRecoveryInitialization(_) => write!(f, "ConfirmShareRecoveryInitialization"),
+			ShareRecoveryMessage::RequestShareRecoveryContribution(_) => write!(f, "RequestShareRecoveryContribution"),
+			ShareRecoveryMessage::ShareRecoveryBlindingShare(_) => write!(f, "ShareRecoveryBlindingShare"),
+			ShareRecoveryMessage::ShareRecoveryContribution(_) => write!(f, "ShareRecoveryContribution"),
+			ShareRecoveryMessage::ShareRecoverySessionError(ref msg) => write!(f, "ShareRecoverySessionError({})", msg.error),
+		}
+	}
+}
+
+impl fmt::Display for ShareRefreshMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ShareRefreshMessage::InitializeShareRefreshSession(_) => write!(f, "InitializeShareRefreshSession"),
+			ShareRefreshMessage::ConfirmShareRefreshInitialization(_) => write!(f, "ConfirmShareRefreshInitialization"),
+			ShareRefreshMessage::RequestShareRefreshDeltas(_) => write!(f, "RequestShareRefreshDeltas"),
+			ShareRefreshMessage::ShareRefreshDelta(_) => write!(f, "ShareRefreshDelta"),
+			ShareRefreshMessage::ConfirmShareRefresh(_) => write!(f, "ConfirmShareRefresh"),
+			ShareRefreshMessage::CommitShareRefresh(_) => write!(f, "CommitShareRefresh"),
+			ShareRefreshMessage::ShareRefreshCommitted(_) => write!(f, "ShareRefreshCommitted"),
+			ShareRefreshMessage::ShareRefreshSessionError(ref msg) => write!(f, "ShareRefreshSessionError({})", msg.error),
+		}
+	}
+}
+
+impl fmt::Display for KeyDerivationMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			KeyDerivationMessage::InitializeKeyDerivationSession(_) => write!(f, "InitializeKeyDerivationSession"),
+			KeyDerivationMessage::ConfirmKeyDerivationInitialization(_) => write!(f, "ConfirmKeyDerivationInitialization"),
+			KeyDerivationMessage::CommitKeyDerivation(_) => write!(f, "CommitKeyDerivation"),
+			KeyDerivationMessage::KeyDerivationSessionError(ref msg) => write!(f, "KeyDerivationSessionError({})", msg.error),
+		}
+	}
+}
+
+impl fmt::Display for KeyDeletionMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			KeyDeletionMessage::InitializeKeyDeletionSession(_) => write!(f, "InitializeKeyDeletionSession"),
+			KeyDeletionMessage::ConfirmKeyDeletionInitialization(_) => write!(f, "ConfirmKeyDeletionInitialization"),
+			KeyDeletionMessage::CommitKeyDeletion(_) => write!(f, "CommitKeyDeletion"),
+			KeyDeletionMessage::KeyDeletionCompleted(ref msg) => write!(f, "KeyDeletionCompleted({})", msg.is_deleted),
+			KeyDeletionMessage::KeyDeletionSessionError(ref msg) => write!(f, "KeyDeletionSessionError({})", msg.error),
+		}
+	}
+}
+
+impl fmt::Display for ServerKeyRetrievalMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(_) => write!(f, "InitializeServerKeyRetrievalSession"),
+			ServerKeyRetrievalMessage::ServerKeyRetrievalPublicShare(_) => write!(f, "ServerKeyRetrievalPublicShare"),
+			ServerKeyRetrievalMessage::RequestServerKeyAttestation(_) => write!(f, "RequestServerKeyAttestation"),
+			ServerKeyRetrievalMessage::ServerKeyAttestation(_) => write!(f, "ServerKeyAttestation"),
+			ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(ref msg) => write!(f, "ServerKeyRetrievalSessionError({})", msg.error),
+		}
+	}
+}
+
+impl fmt::Display for ShareMoveMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ShareMoveMessage::InitializeShareMoveSession(ref msg) => write!(f, "InitializeShareMoveSession({})", msg.new_node),
+			ShareMoveMessage::ConfirmShareMoveInitialization(_) => write!(f, "ConfirmShareMoveInitialization"),
+			ShareMoveMessage::ShareMoveData(_) => write!(f, "ShareMoveData"),
+			ShareMoveMessage::CommitShareMove(_) => write!(f, "CommitShareMove"),
+			ShareMoveMessage::ShareMoveCompleted(_) => write!(f, "ShareMoveCompleted"),
+			ShareMoveMessage::ShareMoveSessionError(ref msg) => write!(f, "ShareMoveSessionError({})", msg.error),
+			ShareMoveMessage::ShareMoveRollback(ref msg) => write!(f, "ShareMoveRollback({})", msg.error),
+			ShareMoveMessage::ShareMoveDataChunk(ref msg) => write!(f, "ShareMoveDataChunk({}/{})", msg.index, msg.count),
+		}
+	}
+}
+
+impl fmt::Display for ShareBootstrapMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ShareBootstrapMessage::InitializeShareBootstrapSession(ref msg) => write!(f, "InitializeShareBootstrapSession({})", msg.key_shares.len()),
+			ShareBootstrapMessage::ShareBootstrapKeySharesSaved(_) => write!(f, "ShareBootstrapKeySharesSaved"),
+			ShareBootstrapMessage::CommitShareBootstrap(ref msg) => write!(f, "CommitShareBootstrap({})", msg.new_node),
+			ShareBootstrapMessage::ShareBootstrapCommitted(_) => write!(f, "ShareBootstrapCommitted"),
+			ShareBootstrapMessage::ShareBootstrapSessionError(ref msg) => write!(f, "ShareBootstrapSessionError({})", msg.error),
+		}
+	}
+}
+
+impl fmt::Display for KeyExportMessage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			KeyExportMessage::InitializeKeyExportSession(ref msg) => write!(f, "InitializeKeyExportSession({})", msg.id_numbers.len()),
+			KeyExportMessage::KeyExportContribution(_) => write!(f, "KeyExportContribution"),
+			KeyExportMessage::KeyExportSessionError(ref msg) => write!(f, "KeyExportSessionError({})", msg.error),
+		}
+	}
+}
@@ -0,0 +1,220 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use parking_lot::Mutex;
+use bigint::hash::H256;
+use key_server_cluster::NodeId;
+
+/// Unacknowledged session message is retransmitted every RETRANSMISSION_INTERVAL milliseconds.
+pub const RETRANSMISSION_INTERVAL: u64 = 2_000;
+/// Session message is forgotten after MAX_RETRANSMISSIONS unacknowledged retransmissions. Session is then
+/// failed by its own timeout.
+const MAX_RETRANSMISSIONS: usize = 10;
+/// Max number of unacknowledged messages, remembered for single node. When limit is reached, the oldest message is forgotten.
+const MAX_UNACKNOWLEDGED_MESSAGES: usize = 4096;
+/// Max number of ids of processed messages, remembered for single node to detect retransmitted messages.
+const MAX_RECEIVED_MESSAGES: usize = 4096;
+
+/// Retransmitter of session messages, which have not been acknowledged by the receiver.
+/// Every message is identified by the hash of its contents. Receiver acknowledges every message (including
+/// retransmitted ones), but only processes the first copy.
+pub struct MessageRetransmitter<M> {
+	/// Mutable retransmitter data.
+	data: Mutex<RetransmitterData<M>>,
+}
+
+/// Mutable retransmitter data.
+struct RetransmitterData<M> {
+	/// Messages, sent to every node, which are waiting for acknowledgement.
+	sent: BTreeMap<NodeId, VecDeque<SentMessage<M>>>,
+	/// Ids of messages, recently received from every node.
+	received: BTreeMap<NodeId, ReceivedMessages>,
+}
+
+/// Unacknowledged message.
+struct SentMessage<M> {
+	/// Message id.
+	id: H256,
+	/// The message itself.
+	message: M,
+	/// Number of retransmissions made.
+	retransmissions: usize,
+	/// Time of the next retransmission.
+	next_retransmission: time::Instant,
+}
+
+/// Ids of messages, recently received from single node.
+#[derive(Default)]
+struct ReceivedMessages {
+	/// Ids set.
+	ids: HashSet<H256>,
+	/// Ids in the order of receival.
+	order: VecDeque<H256>,
+}
+
+impl<M: Clone> MessageRetransmitter<M> {
+	/// Remember message, which has been sent to the node && must be acknowledged.
+	pub fn on_message_sent(&self, node: &NodeId, id: H256, message: M) {
+		self.on_message_sent_at(node, id, message, time::Instant::now())
+	}
+
+	/// Forget message, which has been acknowledged by the node.
+	pub fn on_message_acknowledged(&self, node: &NodeId, id: &H256) {
+		let mut data = self.data.lock();
+		let is_empty = match data.sent.get_mut(node) {
+			Some(messages) => {
+				messages.retain(|message| message.id != *id);
+				messages.is_empty()
+			},
+			None => return,
+		};
+
+		if is_empty {
+			data.sent.remove(node);
+		}
+	}
+
+	/// Remember message, which has been received from the node. Returns false if message has already been received.
+	pub fn on_message_received(&self, node: &NodeId, id: H256) -> bool {
+		let mut data = self.data.lock();
+		let received = data.received.entry(node.clone()).or_insert_with(Default::default);
+		if !received.ids.insert(id.clone()) {
+			return false;
+		}
+
+		received.order.push_back(id);
+		if received.order.len() > MAX_RECEIVED_MESSAGES {
+			if let Some(oldest) = received.order.pop_front() {
+				received.ids.remove(&oldest);
+			}
+		}
+		true
+	}
+
+	/// Get messages, which must be retransmitted now.
+	pub fn messages_to_retransmit(&self) -> Vec<(NodeId, M)> {
+		self.messages_to_retransmit_at(time::Instant::now())
+	}
+
+	/// Get number of messages, which are waiting for acknowledgement.
+	pub fn unacknowledged_messages(&self) -> usize {
+		self.data.lock().sent.values().map(|messages| messages.len()).sum()
+	}
+
+	fn on_message_sent_at(&self, node: &NodeId, id: H256, message: M, now: time::Instant) {
+		let mut data = self.data.lock();
+		let messages = data.sent.entry(node.clone()).or_insert_with(VecDeque::new);
+		if messages.len() >= MAX_UNACKNOWLEDGED_MESSAGES {
+			messages.pop_front();
+		}
+
+		messages.push_back(SentMessage {
+			id: id,
+			message: message,
+			retransmissions: 0,
+			next_retransmission: now + time::Duration::from_millis(RETRANSMISSION_INTERVAL),
+		});
+	}
+
+	fn messages_to_retransmit_at(&self, now: time::Instant) -> Vec<(NodeId, M)> {
+		let mut data = self.data.lock();
+		let mut to_retransmit = Vec::new();
+		for (node, messages) in data.sent.iter_mut() {
+			messages.retain(|message| message.retransmissions < MAX_RETRANSMISSIONS || message.next_retransmission > now);
+			for message in messages.iter_mut().filter(|message| message.next_retransmission <= now) {
+				message.retransmissions += 1;
+				message.next_retransmission = now + time::Duration::from_millis(RETRANSMISSION_INTERVAL);
+				to_retransmit.push((node.clone(), message.message.clone()));
+			}
+		}
+
+		data.sent.retain(|_, messages| !messages.is_empty());
+		to_retransmit
+	}
+}
+
+impl<M> Default for MessageRetransmitter<M> {
+	fn default() -> Self {
+		MessageRetransmitter {
+			data: Mutex::new(RetransmitterData {
+				sent: BTreeMap::new(),
+				received: BTreeMap::new(),
+			}),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time;
+	use ethkey::{Random, Generator};
+	use super::{MessageRetransmitter, RETRANSMISSION_INTERVAL, MAX_RETRANSMISSIONS, MAX_RECEIVED_MESSAGES};
+
+	#[test]
+	fn unacknowledged_message_is_retransmitted() {
+		let node = Random.generate().unwrap().public().clone();
+		let retransmitter = MessageRetransmitter::default();
+		let now = time::Instant::now();
+		retransmitter.on_message_sent_at(&node, 1.into(), 1, now);
+		retransmitter.on_message_sent_at(&node, 2.into(), 2, now);
+
+		// messages are not retransmitted before RETRANSMISSION_INTERVAL
+		assert_eq!(retransmitter.messages_to_retransmit_at(now), vec![]);
+
+		// acknowledged message is not retransmitted
+		retransmitter.on_message_acknowledged(&node, &1.into());
+		let now = now + time::Duration::from_millis(RETRANSMISSION_INTERVAL);
+		assert_eq!(retransmitter.messages_to_retransmit_at(now), vec![(node.clone(), 2)]);
+		assert_eq!(retransmitter.messages_to_retransmit_at(now), vec![]);
+
+		retransmitter.on_message_acknowledged(&node, &2.into());
+		assert_eq!(retransmitter.unacknowledged_messages(), 0);
+	}
+
+	#[test]
+	fn message_is_forgotten_after_max_retransmissions() {
+		let node = Random.generate().unwrap().public().clone();
+		let retransmitter = MessageRetransmitter::default();
+		let mut now = time::Instant::now();
+		retransmitter.on_message_sent_at(&node, 1.into(), 1, now);
+		for _ in 0..MAX_RETRANSMISSIONS {
+			now = now + time::Duration::from_millis(RETRANSMISSION_INTERVAL);
+			assert_eq!(retransmitter.messages_to_retransmit_at(now), vec![(node.clone(), 1)]);
+		}
+
+		now = now + time::Duration::from_millis(RETRANSMISSION_INTERVAL);
+		assert_eq!(retransmitter.messages_to_retransmit_at(now), vec![]);
+		assert_eq!(retransmitter.unacknowledged_messages(), 0);
+	}
+
+	#[test]
+	fn retransmitted_message_is_detected() {
+		let node1 = Random.generate().unwrap().public().clone();
+		let node2 = Random.generate().unwrap().public().clone();
+		let retransmitter: MessageRetransmitter<u64> = MessageRetransmitter::default();
+		assert!(retransmitter.on_message_received(&node1, 1.into()));
+		assert!(!retransmitter.on_message_received(&node1, 1.into()));
+		assert!(retransmitter.on_message_received(&node2, 1.into()));
+
+		// only the most recent ids are remembered
+		for i in 0..MAX_RECEIVED_MESSAGES {
+			assert!(retransmitter.on_message_received(&node1, (i as u64 + 2).into()));
+		}
+		assert!(retransmitter.on_message_received(&node1, 1.into()));
+	}
+}
@@ -0,0 +1,188 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time;
+use std::collections::VecDeque;
+use parking_lot::Mutex;
+
+/// Shares of processed messages are computed within THROUGHPUT_WINDOW milliseconds.
+const THROUGHPUT_WINDOW: u64 = 1000;
+
+/// Scheduler of admin sessions messages.
+/// User sessions messages are always processed immediately. Admin sessions messages are processed immediately
+/// only while their share in the messages, processed within the current window, is below configured limit.
+/// Otherwise they are queued && processed (in the order of receival) when the limit allows.
+/// When there are no user sessions messages, admin sessions messages are never delayed.
+pub struct MessageScheduler<M> {
+	/// Max share (in percents) of admin sessions messages. None if admin messages are never delayed.
+	admin_messages_share: Option<usize>,
+	/// Mutable scheduler data.
+	data: Mutex<SchedulerData<M>>,
+}
+
+/// Mutable scheduler data.
+struct SchedulerData<M> {
+	/// Time when current window has started.
+	window_start: time::Instant,
+	/// Number of user sessions messages, processed within current window.
+	user_messages: usize,
+	/// Number of admin sessions messages, processed within current window.
+	admin_messages: usize,
+	/// Delayed admin sessions messages.
+	queue: VecDeque<M>,
+	/// True if queue draining is scheduled.
+	is_drain_scheduled: bool,
+}
+
+impl<M> MessageScheduler<M> {
+	/// Create new scheduler.
+	pub fn new(admin_messages_share: Option<usize>) -> Self {
+		MessageScheduler {
+			admin_messages_share: admin_messages_share,
+			data: Mutex::new(SchedulerData {
+				window_start: time::Instant::now(),
+				user_messages: 0,
+				admin_messages: 0,
+				queue: VecDeque::new(),
+				is_drain_scheduled: false,
+			}),
+		}
+	}
+
+	/// Remember that user session message is processed.
+	pub fn on_user_message(&self) {
+		let mut data = self.data.lock();
+		data.update_window();
+		data.user_messages += 1;
+	}
+
+	/// Schedule admin session message. Returns message back if it must be processed immediately.
+	pub fn schedule_admin_message(&self, message: M) -> Option<M> {
+		let mut data = self.data.lock();
+		data.update_window();
+		if !data.queue.is_empty() || !data.is_admin_message_allowed(self.admin_messages_share) {
+			data.queue.push_back(message);
+			return None;
+		}
+
+		data.admin_messages += 1;
+		Some(message)
+	}
+
+	/// Take next delayed admin session message, if limit allows.
+	pub fn dequeue_admin_message(&self) -> Option<M> {
+		let mut data = self.data.lock();
+		data.update_window();
+		if data.queue.is_empty() || !data.is_admin_message_allowed(self.admin_messages_share) {
+			return None;
+		}
+
+		data.admin_messages += 1;
+		data.queue.pop_front()
+	}
+
+	/// Returns true if there are delayed messages && caller must schedule queue draining.
+	pub fn begin_drain(&self) -> bool {
+		let mut data = self.data.lock();
+		if data.queue.is_empty() || data.is_drain_scheduled {
+			return false;
+		}
+
+		data.is_drain_scheduled = true;
+		true
+	}
+
+	/// Called when scheduled queue draining has started.
+	pub fn end_drain(&self) {
+		self.data.lock().is_drain_scheduled = false;
+	}
+
+	/// Get number of delayed messages.
+	pub fn queue_len(&self) -> usize {
+		self.data.lock().queue.len()
+	}
+}
+
+impl<M> SchedulerData<M> {
+	/// Start new window, if current has ended.
+	fn update_window(&mut self) {
+		let now = time::Instant::now();
+		if now - self.window_start >= time::Duration::from_millis(THROUGHPUT_WINDOW) {
+			self.window_start = now;
+			self.user_messages = 0;
+			self.admin_messages = 0;
+		}
+	}
+
+	/// Check if one more admin message could be processed within current window.
+	fn is_admin_message_allowed(&self, admin_messages_share: Option<usize>) -> bool {
+		match admin_messages_share {
+			None => true,
+			Some(_) if self.user_messages == 0 => true,
+			Some(admin_messages_share) => (self.admin_messages + 1) * 100 <= admin_messages_share * (self.admin_messages + 1 + self.user_messages),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::MessageScheduler;
+
+	#[test]
+	fn admin_messages_are_not_delayed_when_there_are_no_user_messages() {
+		let scheduler = MessageScheduler::new(Some(10));
+		for i in 0..100 {
+			assert_eq!(scheduler.schedule_admin_message(i), Some(i));
+		}
+		assert!(!scheduler.begin_drain());
+	}
+
+	#[test]
+	fn admin_messages_are_not_delayed_when_share_is_not_limited() {
+		let scheduler = MessageScheduler::new(None);
+		scheduler.on_user_message();
+		for i in 0..100 {
+			assert_eq!(scheduler.schedule_admin_message(i), Some(i));
+		}
+	}
+
+	#[test]
+	fn admin_messages_are_delayed_when_share_is_reached() {
+		let scheduler = MessageScheduler::new(Some(50));
+		scheduler.on_user_message();
+		assert_eq!(scheduler.schedule_admin_message(1), Some(1));
+		assert_eq!(scheduler.schedule_admin_message(2), None);
+		assert_eq!(scheduler.schedule_admin_message(3), None);
+		assert_eq!(scheduler.queue_len(), 2);
+
+		// drain is only scheduled once
+		assert!(scheduler.begin_drain());
+		assert!(!scheduler.begin_drain());
+		scheduler.end_drain();
+
+		// limit is not yet increased => nothing is dequeued
+		assert_eq!(scheduler.dequeue_admin_message(), None);
+
+		// delayed messages are processed in order, when limit allows
+		scheduler.on_user_message();
+		assert_eq!(scheduler.dequeue_admin_message(), Some(2));
+		assert_eq!(scheduler.dequeue_admin_message(), None);
+		scheduler.on_user_message();
+		assert_eq!(scheduler.schedule_admin_message(4), None);
+		assert_eq!(scheduler.dequeue_admin_message(), Some(3));
+		assert_eq!(scheduler.queue_len(), 1);
+	}
+}
@@ -21,15 +21,32 @@ use ethcrypto;
 use super::types::all::ServerKeyId;
 
 pub use super::traits::NodeKeyPair;
-pub use super::types::all::{NodeId, EncryptedDocumentKeyShadow};
+pub use super::types::all::{NodeId, EncryptedDocumentKeyShadow, ReEncryptedDocumentKey, AttestedServerKeyPublic, SessionsRateLimits, ClusterTimeouts,
+	PeerRateLimits, ClusterHealth, PeerHealth, ExportedKeyShare, ExportedShareContribution, ErrorCode, AdminRequestSignature};
 pub use super::acl_storage::AclStorage;
-pub use super::key_storage::{KeyStorage, DocumentKeyShare};
+pub use super::peer_filter::PeerFilter;
+pub use super::key_storage::{KeyStorage, DocumentKeyShare, KeyMetadata, is_key_storage_available};
 pub use super::key_server_set::KeyServerSet;
-pub use super::serialization::{SerializableSignature, SerializableH256, SerializableSecret, SerializablePublic, SerializableMessageHash};
+pub use super::serialization::{SerializableSignature, SerializableH256, SerializableSecret, SerializablePublic, SerializableMessageHash,
+	SerializableBytes, SerializableErrorCode, SerializableExportedKeyShare};
 pub use self::cluster::{ClusterCore, ClusterConfiguration, ClusterClient};
+pub use self::cluster_metrics::{ClusterMetrics, ClusterGauges, SessionOutcome};
 pub use self::generation_session::Session as GenerationSession;
 pub use self::encryption_session::Session as EncryptionSession;
 pub use self::decryption_session::Session as DecryptionSession;
+pub use self::re_encryption_session::Session as ReEncryptionSession;
+pub use self::share_recovery_session::Session as ShareRecoverySession;
+pub use self::share_refresh_session::Session as ShareRefreshSession;
+pub use self::key_derivation_session::Session as KeyDerivationSession;
+pub use self::key_deletion_session::Session as KeyDeletionSession;
+pub use self::share_move_session::Session as ShareMoveSession;
+pub use self::share_bootstrap_session::Session as ShareBootstrapSession;
+pub use self::key_export_session::{Session as KeyExportSession, import_key_share};
+pub use self::server_key_retrieval_session::Session as ServerKeyRetrievalSession;
+pub use self::share_audit::{AuditedKeyShare, ClusterShareReport, ShareInconsistency, check_cluster_shares};
+pub use self::session_journal::SessionJournal;
+pub use self::admin_forwarding::{AdminRequest, AdminResponse, ForwardedRequest, ForwardedRequestState, ADMIN_REQUEST_WINDOW,
+	process_request as process_admin_request, admin_request_hash, recover_admin_signer};
 
 #[cfg(test)]
 pub use super::node_key_pair::PlainNodeKeyPair;
@@ -38,6 +55,8 @@ pub use super::key_storage::tests::DummyKeyStorage;
 #[cfg(test)]
 pub use super::acl_storage::DummyAclStorage;
 #[cfg(test)]
+pub use super::peer_filter::MemoryPeerFilter;
+#[cfg(test)]
 pub use super::key_server_set::tests::MapKeyServerSet;
 
 pub type SessionId = ServerKeyId;
@@ -92,6 +111,8 @@ pub enum Error {
 	InvalidMessage,
 	/// Message version is not supported.
 	InvalidMessageVersion,
+	/// Message is not signed by the node it is received from.
+	InvalidMessageSignature,
 	/// Message is invalid because of replay-attack protection.
 	ReplayProtection,
 	/// Connection to node, required for this session is not established.
@@ -108,6 +129,54 @@ pub enum Error {
 	ConsensusUnreachable,
 	/// Acl storage error.
 	AccessDenied,
+	/// Sessions processing is paused (i.e. while key storage is being backed up).
+	/// Reschedule this request for processing after resume.
+	SessionPaused,
+	/// Key share has been modified since session has been created.
+	/// Session must be recreated to act on actual key share.
+	StaleKeyShare,
+	/// Session has been cancelled on one of nodes.
+	SessionCancelled,
+	/// Requester has started too many sessions.
+	RateLimited,
+	/// Messages queue is full.
+	QueueOverflow,
+	/// Key has been deleted && could not be generated or recovered again.
+	KeyDeleted,
+	/// Node is draining && does not accept new sessions.
+	NodeDraining,
+	/// Session has sent && received more bytes than it is allowed to.
+	SessionTrafficLimitExceeded,
+	/// Session has failed on other node with given error code && error text.
+	Remote(ErrorCode, String),
+}
+
+impl Error {
+	/// Create error, received from other node. Older nodes are not sending error code.
+	pub fn remote(code: Option<SerializableErrorCode>, error: String) -> Self {
+		Error::Remote(code.map(|code| code.0).unwrap_or(ErrorCode::Unknown), error)
+	}
+
+	/// Get category of this error.
+	pub fn code(&self) -> ErrorCode {
+		match *self {
+			Error::InvalidNodeAddress | Error::InvalidNodeId | Error::InvalidNodesCount | Error::InvalidNodesConfiguration
+				| Error::InvalidThreshold | Error::InvalidNodeForRequest => ErrorCode::InvalidRequest,
+			Error::DuplicateSessionId | Error::CompletedSessionId | Error::InvalidSessionId
+				| Error::InvalidStateForRequest => ErrorCode::InvalidState,
+			Error::TooEarlyForRequest | Error::NotStartedSessionId | Error::NodeDisconnected | Error::SessionPaused
+				| Error::RateLimited | Error::QueueOverflow | Error::NodeDraining => ErrorCode::Unavailable,
+			Error::InvalidMessage | Error::InvalidMessageVersion | Error::InvalidMessageSignature
+				| Error::ReplayProtection => ErrorCode::InvalidMessage,
+			Error::KeyStorage(_) => ErrorCode::Storage,
+			Error::StaleKeyShare | Error::KeyDeleted => ErrorCode::StaleKey,
+			Error::ConsensusUnreachable => ErrorCode::ConsensusUnreachable,
+			Error::AccessDenied => ErrorCode::AccessDenied,
+			Error::SessionCancelled | Error::SessionTrafficLimitExceeded => ErrorCode::Cancelled,
+			Error::EthKey(_) | Error::Io(_) | Error::Serde(_) => ErrorCode::Internal,
+			Error::Remote(code, _) => code,
+		}
+	}
 }
 
 impl From<ethkey::Error> for Error {
@@ -145,6 +214,7 @@ impl fmt::Display for Error {
 			Error::InvalidNodeForRequest => write!(f, "invalid node for this request"),
 			Error::InvalidMessage => write!(f, "invalid message is received"),
 			Error::InvalidMessageVersion => write!(f, "unsupported message is received"),
+			Error::InvalidMessageSignature => write!(f, "message with invalid signature is received"),
 			Error::ReplayProtection => write!(f, "replay message is received"),
 			Error::NodeDisconnected => write!(f, "node required for this operation is currently disconnected"),
 			Error::EthKey(ref e) => write!(f, "cryptographic error {}", e),
@@ -153,6 +223,15 @@ impl fmt::Display for Error {
 			Error::KeyStorage(ref e) => write!(f, "key storage error {}", e),
 			Error::ConsensusUnreachable => write!(f, "Consensus unreachable"),
 			Error::AccessDenied => write!(f, "Access denied"),
+			Error::SessionPaused => write!(f, "sessions processing is paused"),
+			Error::StaleKeyShare => write!(f, "key share has been modified since session has been created"),
+			Error::SessionCancelled => write!(f, "session has been cancelled"),
+			Error::RateLimited => write!(f, "too many sessions have been started"),
+			Error::QueueOverflow => write!(f, "messages queue is full"),
+			Error::KeyDeleted => write!(f, "key has been deleted"),
+			Error::NodeDraining => write!(f, "node is draining"),
+			Error::SessionTrafficLimitExceeded => write!(f, "session traffic limit has been exceeded"),
+			Error::Remote(_, ref e) => write!(f, "{}", e),
 		}
 	}
 }
@@ -163,14 +242,38 @@ impl Into<String> for Error {
 	}
 }
 
+mod admin_forwarding;
 mod cluster;
+mod cluster_metrics;
 mod cluster_sessions;
 mod decryption_session;
+mod deferred_messages;
+mod ecdsa_signing_session;
 mod encryption_session;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod fault_injection;
 mod generation_session;
 mod io;
 mod jobs;
+mod key_deletion_session;
+mod key_derivation_session;
+mod key_export_session;
 pub mod math;
 mod message;
+mod message_retransmitter;
+mod message_scheduler;
+mod peer_latency;
+mod peer_rate_limiter;
+mod reconnect_backoff;
+mod re_encryption_session;
+mod server_key_retrieval_session;
+mod session_journal;
+mod share_audit;
+mod share_bootstrap_session;
+mod share_recovery_session;
+mod share_move_session;
+mod share_refresh_session;
 mod signing_session;
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod simulator;
 mod net;
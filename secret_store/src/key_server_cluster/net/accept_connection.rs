@@ -56,6 +56,7 @@ impl Future for AcceptConnection {
 			address: self.address,
 			node_id: result.node_id,
 			key: result.shared_key,
+			version: result.version,
 		};
 		Ok(Ok(connection).into())
 	}
@@ -74,6 +74,7 @@ impl Future for Connect {
 					address: self.address,
 					node_id: result.node_id,
 					key: result.shared_key,
+					version: result.version,
 				};
 				(ConnectState::Connected, Async::Ready(Ok(connection)))
 			},
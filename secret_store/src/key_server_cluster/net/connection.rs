@@ -29,4 +29,6 @@ pub struct Connection {
 	pub node_id: NodeId,
 	/// Encryption key.
 	pub key: KeyPair,
+	/// Negotiated protocol version.
+	pub version: u8,
 }
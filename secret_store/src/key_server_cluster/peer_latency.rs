@@ -0,0 +1,202 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time;
+use std::collections::BTreeMap;
+use parking_lot::Mutex;
+use key_server_cluster::NodeId;
+
+/// Round-trip time of every connected node is measured at least every LATENCY_PROBE_INTERVAL seconds.
+/// Probe, which has not been answered within this interval, is considered lost.
+pub const LATENCY_PROBE_INTERVAL: u64 = 30;
+/// Every session message, which is waiting to be sent to the node, adds QUEUED_MESSAGE_DELAY microseconds
+/// to the expected response delay of this node.
+const QUEUED_MESSAGE_DELAY: u64 = 1_000;
+/// Weight (1 / SMOOTHING_FACTOR) of the new round-trip time sample in the smoothed round-trip time.
+const SMOOTHING_FACTOR: u64 = 8;
+
+/// Smoothed round-trip times of connected nodes, measured by KeepAlive messages.
+#[derive(Default)]
+pub struct PeerLatencies {
+	/// Latency of every connected node.
+	nodes: Mutex<BTreeMap<NodeId, NodeLatency>>,
+}
+
+/// Latency of single node.
+#[derive(Default)]
+struct NodeLatency {
+	/// Time, when the last unanswered probe has been sent.
+	probe_sent: Option<time::Instant>,
+	/// Time, when the last probe has been answered.
+	probe_answered: Option<time::Instant>,
+	/// Smoothed round-trip time (microseconds). None if no probes have been answered yet.
+	round_trip_time: Option<u64>,
+}
+
+impl PeerLatencies {
+	/// Check if round-trip time to the node must be measured now.
+	pub fn is_probe_required(&self, node: &NodeId) -> bool {
+		self.is_probe_required_at(node, time::Instant::now())
+	}
+
+	/// Remember that probe (KeepAlive message) has been sent to the node.
+	pub fn on_probe_sent(&self, node: &NodeId) {
+		self.on_probe_sent_at(node, time::Instant::now())
+	}
+
+	/// Remember that probe response (KeepAliveResponse message) has been received from the node.
+	pub fn on_probe_answered(&self, node: &NodeId) {
+		self.on_probe_answered_at(node, time::Instant::now())
+	}
+
+	/// Forget everything about disconnected node.
+	pub fn on_disconnected(&self, node: &NodeId) {
+		self.nodes.lock().remove(node);
+	}
+
+	/// Get smoothed round-trip time to the node. None if it has not been measured yet.
+	pub fn round_trip_time(&self, node: &NodeId) -> Option<time::Duration> {
+		self.nodes.lock().get(node)
+			.and_then(|latency| latency.round_trip_time)
+			.map(duration_from_micros)
+	}
+
+	/// Get expected delay of response from the node, which has given number of queued messages.
+	/// None if round-trip time to the node has not been measured yet.
+	pub fn expected_delay(&self, node: &NodeId, queued_messages: usize) -> Option<time::Duration> {
+		self.nodes.lock().get(node)
+			.and_then(|latency| latency.round_trip_time)
+			.map(|round_trip_time| duration_from_micros(round_trip_time + queued_messages as u64 * QUEUED_MESSAGE_DELAY))
+	}
+
+	fn is_probe_required_at(&self, node: &NodeId, now: time::Instant) -> bool {
+		let probe_interval = time::Duration::from_secs(LATENCY_PROBE_INTERVAL);
+		match self.nodes.lock().get(node) {
+			None => true,
+			Some(latency) => match latency.probe_sent {
+				Some(probe_sent) => now >= probe_sent + probe_interval,
+				None => latency.probe_answered.map(|answered| now >= answered + probe_interval).unwrap_or(true),
+			},
+		}
+	}
+
+	fn on_probe_sent_at(&self, node: &NodeId, now: time::Instant) {
+		let mut nodes = self.nodes.lock();
+		let latency = nodes.entry(node.clone()).or_insert_with(NodeLatency::default);
+		// only the oldest unanswered probe is measured, unless it is lost
+		let is_probe_lost = latency.probe_sent.map(|sent| now >= sent + time::Duration::from_secs(LATENCY_PROBE_INTERVAL)).unwrap_or(true);
+		if is_probe_lost {
+			latency.probe_sent = Some(now);
+		}
+	}
+
+	fn on_probe_answered_at(&self, node: &NodeId, now: time::Instant) {
+		let mut nodes = self.nodes.lock();
+		let latency = match nodes.get_mut(node) {
+			Some(latency) => latency,
+			None => return,
+		};
+		let probe_sent = match latency.probe_sent.take() {
+			Some(probe_sent) => probe_sent,
+			None => return,
+		};
+
+		let sample = duration_to_micros(if now > probe_sent { now - probe_sent } else { time::Duration::from_secs(0) });
+		latency.probe_answered = Some(now);
+		latency.round_trip_time = Some(match latency.round_trip_time {
+			Some(round_trip_time) => (round_trip_time * (SMOOTHING_FACTOR - 1) + sample) / SMOOTHING_FACTOR,
+			None => sample,
+		});
+	}
+}
+
+/// Order nodes by expected response delay. Nodes with unknown delay are placed after all other nodes, in their natural order.
+pub fn order_by_expected_delay(nodes: Vec<(NodeId, Option<time::Duration>)>) -> Vec<NodeId> {
+	let mut nodes: Vec<_> = nodes.into_iter().enumerate().collect();
+	nodes.sort_by_key(|&(index, (_, delay))| (delay.is_none(), delay, index));
+	nodes.into_iter().map(|(_, (node, _))| node).collect()
+}
+
+fn duration_to_micros(duration: time::Duration) -> u64 {
+	duration.as_secs() * 1_000_000 + duration.subsec_nanos() as u64 / 1_000
+}
+
+fn duration_from_micros(micros: u64) -> time::Duration {
+	time::Duration::new(micros / 1_000_000, (micros % 1_000_000) as u32 * 1_000)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time;
+	use ethkey::{Random, Generator};
+	use super::{PeerLatencies, LATENCY_PROBE_INTERVAL, order_by_expected_delay};
+
+	#[test]
+	fn round_trip_time_is_measured_and_smoothed() {
+		let node = Random.generate().unwrap().public().clone();
+		let latencies = PeerLatencies::default();
+		let now = time::Instant::now();
+
+		// unknown node must be probed
+		assert!(latencies.is_probe_required_at(&node, now));
+		latencies.on_probe_sent_at(&node, now);
+		assert!(!latencies.is_probe_required_at(&node, now));
+		latencies.on_probe_answered_at(&node, now + time::Duration::from_millis(80));
+		assert_eq!(latencies.round_trip_time(&node), Some(time::Duration::from_millis(80)));
+
+		// next probe is sent after LATENCY_PROBE_INTERVAL
+		let now = now + time::Duration::from_secs(LATENCY_PROBE_INTERVAL);
+		assert!(!latencies.is_probe_required_at(&node, now));
+		let now = now + time::Duration::from_millis(80);
+		assert!(latencies.is_probe_required_at(&node, now));
+		latencies.on_probe_sent_at(&node, now);
+		latencies.on_probe_answered_at(&node, now + time::Duration::from_millis(160));
+		assert_eq!(latencies.round_trip_time(&node), Some(time::Duration::from_millis(90)));
+		assert_eq!(latencies.expected_delay(&node, 10), Some(time::Duration::from_millis(100)));
+
+		// disconnected node is forgotten
+		latencies.on_disconnected(&node);
+		assert_eq!(latencies.round_trip_time(&node), None);
+	}
+
+	#[test]
+	fn lost_probe_is_resent() {
+		let node = Random.generate().unwrap().public().clone();
+		let latencies = PeerLatencies::default();
+		let now = time::Instant::now();
+
+		latencies.on_probe_sent_at(&node, now);
+		let now = now + time::Duration::from_secs(LATENCY_PROBE_INTERVAL);
+		assert!(latencies.is_probe_required_at(&node, now));
+		latencies.on_probe_sent_at(&node, now);
+		latencies.on_probe_answered_at(&node, now + time::Duration::from_millis(10));
+		assert_eq!(latencies.round_trip_time(&node), Some(time::Duration::from_millis(10)));
+	}
+
+	#[test]
+	fn nodes_are_ordered_by_expected_delay() {
+		let node1 = Random.generate().unwrap().public().clone();
+		let node2 = Random.generate().unwrap().public().clone();
+		let node3 = Random.generate().unwrap().public().clone();
+		let node4 = Random.generate().unwrap().public().clone();
+		assert_eq!(order_by_expected_delay(vec![
+			(node1.clone(), None),
+			(node2.clone(), Some(time::Duration::from_millis(50))),
+			(node3.clone(), None),
+			(node4.clone(), Some(time::Duration::from_millis(10))),
+		]), vec![node4, node2, node1, node3]);
+	}
+}
@@ -0,0 +1,277 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time;
+use std::collections::BTreeMap;
+use parking_lot::Mutex;
+use key_server_cluster::{NodeId, PeerRateLimits};
+use key_server_cluster::message::{Message, ClusterMessage, GenerationMessage, EncryptionMessage, DecryptionMessage, ReEncryptionMessage,
+	SigningMessage, EcdsaSigningMessage, ShareRecoveryMessage, ShareRefreshMessage, KeyDerivationMessage, KeyDeletionMessage, ShareMoveMessage,
+	ServerKeyRetrievalMessage, ShareBootstrapMessage, KeyExportMessage, ConsensusMessage};
+
+/// Default max number of session initialization messages, received from single node within a second.
+const DEFAULT_SESSION_INITIALIZATIONS_PER_SECOND: u64 = 50;
+/// Default max number of session error messages, received from single node within a second.
+const DEFAULT_SESSION_ERRORS_PER_SECOND: u64 = 20;
+/// Default max number of other session messages, received from single node within a second.
+const DEFAULT_SESSION_MESSAGES_PER_SECOND: u64 = 2000;
+/// Default max number of cluster messages, received from single node within a second.
+const DEFAULT_CLUSTER_MESSAGES_PER_SECOND: u64 = 50;
+/// Default time (in seconds), during which throttled node is disconnected.
+const DEFAULT_THROTTLE_TIME: u64 = 60;
+/// Node could send messages of every class at the rate, which is BURST_SECONDS times the limit, for a short time.
+const BURST_SECONDS: u64 = 5;
+
+/// Class of message, received from other node. Every class is rate-limited separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageClass {
+	/// Message, which starts new session on this node.
+	SessionInitialization,
+	/// Session error message.
+	SessionError,
+	/// Any other session message.
+	Session,
+	/// Cluster-level message.
+	Cluster,
+}
+
+/// Per-node token bucket limits of received messages. When node exceeds the limit of any message class,
+/// it is throttled: connection is closed && it is not allowed to connect again for the throttle time.
+pub struct PeerRateLimiter {
+	/// Limit (messages per second) of every message class.
+	limits: BTreeMap<MessageClass, u64>,
+	/// Time, during which throttled node is disconnected.
+	throttle_time: time::Duration,
+	/// State of every node, we have received messages from.
+	nodes: Mutex<BTreeMap<NodeId, NodeLimits>>,
+}
+
+/// Rate limiting state of single node.
+#[derive(Default)]
+struct NodeLimits {
+	/// Token bucket of every message class.
+	buckets: BTreeMap<MessageClass, TokenBucket>,
+	/// Node is throttled until this time.
+	throttled_until: Option<time::Instant>,
+}
+
+/// Token bucket of single message class.
+struct TokenBucket {
+	/// Number of messages, which could be received right now.
+	tokens: f64,
+	/// Time, when tokens have been updated last time.
+	updated: time::Instant,
+}
+
+impl MessageClass {
+	/// Get class of given message.
+	pub fn of(message: &Message) -> Self {
+		match *message {
+			Message::Cluster(ClusterMessage::MessageAcknowledgement(_)) => MessageClass::Session,
+			Message::Cluster(_) => MessageClass::Cluster,
+			Message::Decryption(DecryptionMessage::DecryptionConsensusMessage(ref message))
+				if is_consensus_initialization(&message.message) => MessageClass::SessionInitialization,
+			Message::ReEncryption(ReEncryptionMessage::ReEncryptionConsensusMessage(ref message))
+				if is_consensus_initialization(&message.message) => MessageClass::SessionInitialization,
+			Message::Signing(SigningMessage::SigningConsensusMessage(ref message))
+				if is_consensus_initialization(&message.message) => MessageClass::SessionInitialization,
+			Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningConsensusMessage(ref message))
+				if is_consensus_initialization(&message.message) => MessageClass::SessionInitialization,
+			Message::Generation(GenerationMessage::InitializeSession(_)) |
+			Message::Encryption(EncryptionMessage::InitializeEncryptionSession(_)) |
+			Message::ShareRecovery(ShareRecoveryMessage::InitializeShareRecoverySession(_)) |
+			Message::ShareRefresh(ShareRefreshMessage::InitializeShareRefreshSession(_)) |
+			Message::KeyDerivation(KeyDerivationMessage::InitializeKeyDerivationSession(_)) |
+			Message::KeyDeletion(KeyDeletionMessage::InitializeKeyDeletionSession(_)) |
+			Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(_)) |
+			Message::ShareMove(ShareMoveMessage::InitializeShareMoveSession(_)) |
+			Message::ShareBootstrap(ShareBootstrapMessage::InitializeShareBootstrapSession(_)) |
+			Message::KeyExport(KeyExportMessage::InitializeKeyExportSession(_)) => MessageClass::SessionInitialization,
+			Message::Generation(GenerationMessage::SessionError(_)) |
+			Message::Encryption(EncryptionMessage::EncryptionSessionError(_)) |
+			Message::Decryption(DecryptionMessage::DecryptionSessionError(_)) |
+			Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(_)) |
+			Message::Signing(SigningMessage::SigningSessionError(_)) |
+			Message::EcdsaSigning(EcdsaSigningMessage::EcdsaSigningSessionError(_)) |
+			Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(_)) |
+			Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(_)) |
+			Message::KeyDerivation(KeyDerivationMessage::KeyDerivationSessionError(_)) |
+			Message::KeyDeletion(KeyDeletionMessage::KeyDeletionSessionError(_)) |
+			Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(_)) |
+			Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(_)) |
+			Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(_)) |
+			Message::KeyExport(KeyExportMessage::KeyExportSessionError(_)) => MessageClass::SessionError,
+			_ => MessageClass::Session,
+		}
+	}
+
+	/// Class name, used as metric label value.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			MessageClass::SessionInitialization => "session_initialization",
+			MessageClass::SessionError => "session_error",
+			MessageClass::Session => "session",
+			MessageClass::Cluster => "cluster",
+		}
+	}
+}
+
+impl PeerRateLimiter {
+	/// Create new rate limiter.
+	pub fn new(limits: &PeerRateLimits) -> Self {
+		PeerRateLimiter {
+			limits: vec![
+				(MessageClass::SessionInitialization, limits.session_initializations.unwrap_or(DEFAULT_SESSION_INITIALIZATIONS_PER_SECOND)),
+				(MessageClass::SessionError, limits.session_errors.unwrap_or(DEFAULT_SESSION_ERRORS_PER_SECOND)),
+				(MessageClass::Session, limits.session_messages.unwrap_or(DEFAULT_SESSION_MESSAGES_PER_SECOND)),
+				(MessageClass::Cluster, limits.cluster_messages.unwrap_or(DEFAULT_CLUSTER_MESSAGES_PER_SECOND)),
+			].into_iter().collect(),
+			throttle_time: time::Duration::from_secs(limits.throttle_time.unwrap_or(DEFAULT_THROTTLE_TIME)),
+			nodes: Mutex::new(BTreeMap::new()),
+		}
+	}
+
+	/// Remember that message of given class has been received from the node. Returns false if node has exceeded
+	/// the limit && it is now throttled.
+	pub fn on_message_received(&self, node: &NodeId, class: MessageClass) -> bool {
+		self.on_message_received_at(node, class, time::Instant::now())
+	}
+
+	/// Check if node is throttled now.
+	pub fn is_throttled(&self, node: &NodeId) -> bool {
+		self.is_throttled_at(node, time::Instant::now())
+	}
+
+	fn on_message_received_at(&self, node: &NodeId, class: MessageClass, now: time::Instant) -> bool {
+		let limit = self.limits.get(&class).cloned().unwrap_or_default() as f64;
+		let capacity = limit * BURST_SECONDS as f64;
+
+		let mut nodes = self.nodes.lock();
+		let node_limits = nodes.entry(node.clone()).or_insert_with(NodeLimits::default);
+		if node_limits.throttled_until.map(|until| now < until).unwrap_or(false) {
+			return false;
+		}
+
+		let bucket = node_limits.buckets.entry(class).or_insert_with(|| TokenBucket {
+			tokens: capacity,
+			updated: now,
+		});
+		let elapsed = if now > bucket.updated { now - bucket.updated } else { time::Duration::from_secs(0) };
+		let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000f64;
+		bucket.tokens = (bucket.tokens + elapsed * limit).min(capacity);
+		bucket.updated = now;
+		if bucket.tokens >= 1f64 {
+			bucket.tokens -= 1f64;
+			return true;
+		}
+
+		// node starts with full buckets when throttle time is over
+		node_limits.buckets.clear();
+		node_limits.throttled_until = Some(now + self.throttle_time);
+		false
+	}
+
+	fn is_throttled_at(&self, node: &NodeId, now: time::Instant) -> bool {
+		let mut nodes = self.nodes.lock();
+		let is_throttled = match nodes.get(node).and_then(|node_limits| node_limits.throttled_until) {
+			Some(until) => now < until,
+			None => return false,
+		};
+		if !is_throttled {
+			nodes.remove(node);
+		}
+		is_throttled
+	}
+}
+
+/// Check if consensus message initializes new consensus session.
+fn is_consensus_initialization(message: &ConsensusMessage) -> bool {
+	match *message {
+		ConsensusMessage::InitializeConsensusSession(_) => true,
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time;
+	use ethkey::{Random, Generator};
+	use key_server_cluster::PeerRateLimits;
+	use key_server_cluster::message::{self, Message, ClusterMessage, GenerationMessage};
+	use super::{PeerRateLimiter, MessageClass, BURST_SECONDS};
+
+	fn limits() -> PeerRateLimits {
+		PeerRateLimits {
+			session_initializations: Some(1),
+			session_errors: Some(1),
+			session_messages: Some(10),
+			cluster_messages: Some(1),
+			throttle_time: Some(60),
+		}
+	}
+
+	#[test]
+	fn messages_are_classified() {
+		assert_eq!(MessageClass::of(&Message::Cluster(ClusterMessage::KeepAlive(message::KeepAlive {}))), MessageClass::Cluster);
+		assert_eq!(MessageClass::of(&Message::Generation(GenerationMessage::SessionError(message::SessionError {
+			session: Default::default(),
+			session_nonce: 0,
+			error: "error".into(),
+			code: None,
+		}))), MessageClass::SessionError);
+	}
+
+	#[test]
+	fn node_is_throttled_when_limit_is_exceeded() {
+		let node = Random.generate().unwrap().public().clone();
+		let limiter = PeerRateLimiter::new(&limits());
+		let now = time::Instant::now();
+
+		// burst is allowed
+		for _ in 0..BURST_SECONDS {
+			assert!(limiter.on_message_received_at(&node, MessageClass::SessionInitialization, now));
+		}
+		// other classes are limited separately
+		assert!(limiter.on_message_received_at(&node, MessageClass::Session, now));
+		assert!(!limiter.is_throttled_at(&node, now));
+
+		// bucket is empty => node is throttled
+		assert!(!limiter.on_message_received_at(&node, MessageClass::SessionInitialization, now));
+		assert!(limiter.is_throttled_at(&node, now));
+		assert!(!limiter.on_message_received_at(&node, MessageClass::Session, now));
+
+		// when throttle time is over, node starts with full buckets
+		let now = now + time::Duration::from_secs(60);
+		assert!(!limiter.is_throttled_at(&node, now));
+		for _ in 0..BURST_SECONDS {
+			assert!(limiter.on_message_received_at(&node, MessageClass::SessionInitialization, now));
+		}
+	}
+
+	#[test]
+	fn tokens_are_refilled() {
+		let node = Random.generate().unwrap().public().clone();
+		let limiter = PeerRateLimiter::new(&limits());
+		let mut now = time::Instant::now();
+
+		// node, which is sending messages at the limit rate, is never throttled
+		for _ in 0..100 {
+			assert!(limiter.on_message_received_at(&node, MessageClass::Cluster, now));
+			now = now + time::Duration::from_secs(1);
+		}
+		assert!(!limiter.is_throttled_at(&node, now));
+	}
+}
@@ -0,0 +1,803 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use parking_lot::{Mutex, Condvar};
+use ethkey::{Public, Secret, Signature, recover};
+use bigint::hash::H256;
+use key_server_cluster::{Error, AclStorage, KeyStorage, DocumentKeyShare, NodeId, SessionId, ReEncryptedDocumentKey, SessionMeta};
+use key_server_cluster::math;
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::{ClusterSession, SessionEvent, SessionEventListener, SessionEventListeners};
+use key_server_cluster::message::{Message, ReEncryptionMessage, ReEncryptionConsensusMessage, RequestPartialReEncryption,
+	PartialReEncryption, ReEncryptionSessionError, ReEncryptionSessionCompleted, ConsensusMessage, InitializeConsensusSession,
+	ConfirmConsensusInitialization};
+use key_server_cluster::jobs::job_session::JobTransport;
+use key_server_cluster::jobs::re_encryption_job::{PartialReEncryptionRequest, PartialReEncryptionResponse, ReEncryptionJob};
+use key_server_cluster::jobs::consensus_session::{ConsensusSessionParams, ConsensusSessionState, ConsensusSession};
+
+/// Re-encryption session API.
+pub trait Session: Send + Sync + 'static {
+	/// Wait until session is completed. Returns document key, re-encrypted with target public key.
+	fn wait(&self) -> Result<ReEncryptedDocumentKey, Error>;
+}
+
+/// Distributed proxy re-encryption session.
+/// Transforms document key, encrypted with server key, into document key, encrypted with target public key.
+/// Neither nodes, nor requester are learning the document key itself during the session.
+/// Brief overview:
+/// 1) initialization: master node (which has received request for re-encrypting the secret) requests all other nodes to re-encrypt the secret
+/// 2) ACL check: all nodes which have received the request are querying ACL-contract to check if requestor has access to the document
+/// 2.1) version negotiation: nodes, which do not have the latest key version of the master node, are treated as rejecting consensus
+/// 3) partial re-encryption: every node which has succussfully checked access for the requestor computes its shadow point,
+///    blinded with random multiple of target public key
+/// 4) re-encryption: master node receives all partial re-encryptions of the secret and computes re-encrypted secret
+pub struct SessionImpl {
+	/// Session core.
+	core: SessionCore,
+	/// Session data.
+	data: Mutex<SessionData>,
+}
+
+/// Immutable session data.
+struct SessionCore {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Re-encryption session access key.
+	pub access_key: Secret,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	pub cluster: Arc<Cluster>,
+	/// Session-level nonce.
+	pub nonce: u64,
+	/// SessionImpl completion condvar.
+	pub completed: Condvar,
+	/// Session events listeners.
+	pub listeners: SessionEventListeners,
+}
+
+/// Re-encryption consensus session type.
+type ReEncryptionConsensusSession = ConsensusSession<ReEncryptionConsensusTransport, ReEncryptionJob, ReEncryptionJobTransport>;
+
+/// Mutable session data.
+struct SessionData {
+	/// Consensus-based re-encryption session.
+	pub consensus_session: ReEncryptionConsensusSession,
+	/// Public key, which document key must be re-encrypted with && its signature, made by requester.
+	pub target: Option<(Public, Signature)>,
+	/// Key version, negotiated by master node.
+	pub key_version: Option<H256>,
+	/// Re-encryption result.
+	pub result: Option<Result<ReEncryptedDocumentKey, Error>>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Session access key.
+	pub access_key: Secret,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// ACL storage.
+	pub acl_storage: Arc<AclStorage>,
+	/// Cluster.
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Re-encryption consensus transport.
+struct ReEncryptionConsensusTransport {
+	/// Session id.
+	id: SessionId,
+	/// Session access key.
+	access_key: Secret,
+	/// Session-level nonce.
+	nonce: u64,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster.
+	cluster: Arc<Cluster>,
+}
+
+/// Re-encryption job transport
+struct ReEncryptionJobTransport {
+	/// Session id.
+	id: SessionId,
+	//// Session access key.
+	access_key: Secret,
+	/// Session-level nonce.
+	nonce: u64,
+	/// Key version, used for re-encryption.
+	key_version: H256,
+	/// Cluster.
+	cluster: Arc<Cluster>,
+}
+
+impl SessionImpl {
+	/// Create new re-encryption session.
+	pub fn new(params: SessionParams, requester_signature: Option<Signature>) -> Result<Self, Error> {
+		debug_assert_eq!(params.meta.threshold, params.key_share.threshold);
+		debug_assert_eq!(params.meta.self_node_id == params.meta.master_node_id, requester_signature.is_some());
+
+		use key_server_cluster::generation_session::{check_cluster_nodes, check_threshold};
+
+		// check that common_point and encrypted_point are already set
+		if params.key_share.common_point.is_none() || params.key_share.encrypted_point.is_none() {
+			return Err(Error::NotStartedSessionId);
+		}
+
+		// check nodes and threshold
+		let nodes = params.key_share.id_numbers.keys().cloned().collect();
+		check_cluster_nodes(&params.meta.self_node_id, &nodes)?;
+		check_threshold(params.key_share.threshold, &nodes)?;
+
+		let consensus_transport = ReEncryptionConsensusTransport {
+			id: params.meta.id.clone(),
+			access_key: params.access_key.clone(),
+			nonce: params.nonce,
+			key_storage: params.key_storage.clone(),
+			cluster: params.cluster.clone(),
+		};
+
+		Ok(SessionImpl {
+			core: SessionCore {
+				meta: params.meta.clone(),
+				access_key: params.access_key,
+				key_share: params.key_share,
+				key_storage: params.key_storage,
+				cluster: params.cluster,
+				nonce: params.nonce,
+				completed: Condvar::new(),
+				listeners: SessionEventListeners::default(),
+			},
+			data: Mutex::new(SessionData {
+				consensus_session: match requester_signature {
+					Some(requester_signature) => ConsensusSession::new_on_master(ConsensusSessionParams {
+						meta: params.meta,
+						acl_storage: params.acl_storage.clone(),
+						consensus_transport: consensus_transport,
+					}, requester_signature)?,
+					None => ConsensusSession::new_on_slave(ConsensusSessionParams {
+						meta: params.meta,
+						acl_storage: params.acl_storage.clone(),
+						consensus_transport: consensus_transport,
+					})?,
+				},
+				target: None,
+				key_version: None,
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node id.
+	#[cfg(test)]
+	pub fn node(&self) -> &NodeId {
+		&self.core.meta.self_node_id
+	}
+
+	/// Get this session access key.
+	#[cfg(test)]
+	pub fn access_key(&self) -> &Secret {
+		&self.core.access_key
+	}
+
+	/// Get session state.
+	#[cfg(test)]
+	pub fn state(&self) -> ConsensusSessionState {
+		self.data.lock().consensus_session.state()
+	}
+
+	/// Get re-encrypted secret
+	#[cfg(test)]
+	pub fn reencrypted_secret(&self) -> Option<Result<ReEncryptedDocumentKey, Error>> {
+		self.data.lock().result.clone()
+	}
+
+	/// Register session events listener.
+	pub fn add_listener(&self, listener: Arc<SessionEventListener>) {
+		self.core.listeners.add(listener);
+	}
+
+	/// Initialize re-encryption session on master node.
+	pub fn initialize(&self, target_public: Public, target_signature: Signature) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		self.core.check_reencryption_target(data.consensus_session.requester()?, &target_public, &target_signature)?;
+		let key_version = self.core.latest_key_version()?;
+		data.target = Some((target_public.clone(), target_signature.clone()));
+		data.key_version = Some(key_version.clone());
+		data.consensus_session.initialize(self.core.key_share.id_numbers.keys().cloned().collect())?;
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Initialized);
+
+		if data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished {
+			self.core.listeners.notify(&self.core.meta.id, SessionEvent::Confirmed);
+			self.core.disseminate_jobs(&mut data.consensus_session, target_public, target_signature, key_version)?;
+
+			debug_assert!(data.consensus_session.state() == ConsensusSessionState::Finished);
+			data.result = Some(Ok(data.consensus_session.result()?));
+			self.core.listeners.notify(&self.core.meta.id, SessionEvent::Finished);
+			self.core.completed.notify_all();
+		}
+
+		Ok(())
+	}
+
+	/// Process re-encryption message.
+	pub fn process_message(&self, sender: &NodeId, message: &ReEncryptionMessage) -> Result<(), Error> {
+		if self.core.nonce != message.session_nonce() {
+			return Err(Error::ReplayProtection);
+		}
+
+		match message {
+			&ReEncryptionMessage::ReEncryptionConsensusMessage(ref message) =>
+				self.on_consensus_message(sender, message),
+			&ReEncryptionMessage::RequestPartialReEncryption(ref message) =>
+				self.on_partial_reencryption_requested(sender, message),
+			&ReEncryptionMessage::PartialReEncryption(ref message) =>
+				self.on_partial_reencryption(sender, message),
+			&ReEncryptionMessage::ReEncryptionSessionError(ref message) =>
+				self.on_session_error(sender, message),
+			&ReEncryptionMessage::ReEncryptionSessionCompleted(ref message) =>
+				self.on_session_completed(sender, message),
+		}
+	}
+
+	/// When consensus-related message is received.
+	pub fn on_consensus_message(&self, sender: &NodeId, message: &ReEncryptionConsensusMessage) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+
+		let mut data = self.data.lock();
+		let is_waiting_for_initialization = data.consensus_session.state() == ConsensusSessionState::WaitingForInitialization;
+		let is_establishing_consensus = data.consensus_session.state() == ConsensusSessionState::EstablishingConsensus;
+		let consensus_message = self.core.filter_consensus_message(data.key_version.as_ref(), &message.message)?;
+		data.consensus_session.on_consensus_message(&sender, &consensus_message)?;
+
+		if is_waiting_for_initialization && data.consensus_session.state() != ConsensusSessionState::WaitingForInitialization {
+			self.core.listeners.notify(&self.core.meta.id, SessionEvent::Initialized);
+		}
+
+		let is_consensus_established = data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished;
+		if self.core.meta.self_node_id != self.core.meta.master_node_id || !is_establishing_consensus || !is_consensus_established {
+			return Ok(());
+		}
+
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Confirmed);
+
+		let (target_public, target_signature) = data.target.clone()
+			.expect("we are on master node; on master node target is filled in initialize(); on_consensus_message follows initialize (state check in consensus_session); qed");
+		let key_version = data.key_version.clone()
+			.expect("we are on master node; on master node key_version is filled in initialize(); on_consensus_message follows initialize (state check in consensus_session); qed");
+		self.core.disseminate_jobs(&mut data.consensus_session, target_public, target_signature, key_version)
+	}
+
+	/// When partial re-encryption is requested.
+	pub fn on_partial_reencryption_requested(&self, sender: &NodeId, message: &RequestPartialReEncryption) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		if sender != &self.core.meta.master_node_id {
+			return Err(Error::InvalidMessage);
+		}
+
+		// only requester is allowed to choose public key, which document key is re-encrypted with
+		let mut data = self.data.lock();
+		let target_public: Public = message.target_public.clone().into();
+		let target_signature: Signature = message.target_signature.clone().into();
+		self.core.check_reencryption_target(data.consensus_session.requester()?, &target_public, &target_signature)?;
+
+		let key_version: H256 = message.key_version.clone().into();
+		let key_share = self.core.key_storage.get_version(&self.core.meta.id, &key_version)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		let reencryption_job = ReEncryptionJob::new_on_slave(self.core.meta.self_node_id.clone(), key_share)?;
+		let reencryption_transport = self.core.reencryption_transport(key_version);
+
+		data.consensus_session.on_job_request(&sender, PartialReEncryptionRequest {
+			id: message.request_id.clone().into(),
+			target_public: target_public,
+			target_signature: target_signature,
+			other_nodes_ids: message.nodes.iter().cloned().map(Into::into).collect(),
+		}, reencryption_job, reencryption_transport)
+	}
+
+	/// When partial re-encryption is received.
+	pub fn on_partial_reencryption(&self, sender: &NodeId, message: &PartialReEncryption) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		let mut data = self.data.lock();
+		data.consensus_session.on_job_response(sender, PartialReEncryptionResponse {
+			request_id: message.request_id.clone().into(),
+			common_shadow: message.common_shadow.clone().into(),
+			reencryption_shadow: message.reencryption_shadow.clone().into(),
+		})?;
+
+		if data.consensus_session.state() != ConsensusSessionState::Finished {
+			return Ok(());
+		}
+
+		// send compeltion signal to all nodes, except for rejected nodes
+		for node in data.consensus_session.consensus_non_rejected_nodes() {
+			self.core.cluster.send(&node, Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionCompleted(ReEncryptionSessionCompleted {
+				session: self.core.meta.id.clone().into(),
+				sub_session: self.core.access_key.clone().into(),
+				session_nonce: self.core.nonce,
+			})))?;
+		}
+
+		data.result = Some(Ok(data.consensus_session.result()?));
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Finished);
+		self.core.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// When session is completed.
+	pub fn on_session_completed(&self, sender: &NodeId, message: &ReEncryptionSessionCompleted) -> Result<(), Error> {
+		debug_assert!(self.core.meta.id == *message.session);
+		debug_assert!(self.core.access_key == *message.sub_session);
+		debug_assert!(sender != &self.core.meta.self_node_id);
+
+		self.data.lock().consensus_session.on_session_completed(sender)?;
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Finished);
+
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: &NodeId, message: &ReEncryptionSessionError) -> Result<(), Error> {
+		self.process_node_error(Some(&sender), &Error::remote(message.code, message.error.clone()))
+	}
+
+	/// Process error from the other node.
+	fn process_node_error(&self, node: Option<&NodeId>, error: &Error) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		match {
+			match node {
+				Some(node) => data.consensus_session.on_node_error(node),
+				None => data.consensus_session.on_session_timeout(),
+			}
+		} {
+			Ok(false) => Ok(()),
+			Ok(true) => {
+				let (target_public, target_signature) = data.target.clone().expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when target.is_some(); qed");
+				let key_version = data.key_version.clone().expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when key_version.is_some(); qed");
+				let disseminate_result = self.core.disseminate_jobs(&mut data.consensus_session, target_public, target_signature, key_version);
+				match disseminate_result {
+					Ok(()) => Ok(()),
+					Err(err) => {
+						warn!("{}: re-encryption session failed with error: {:?} from {:?}", &self.core.meta.self_node_id, error, node);
+
+						data.result = Some(Err(err.clone()));
+						self.core.listeners.notify(&self.core.meta.id, SessionEvent::Failed(err.clone()));
+						self.core.completed.notify_all();
+						Err(err)
+					}
+				}
+			},
+			Err(err) => {
+				warn!("{}: re-encryption session failed with error: {:?} from {:?}", &self.core.meta.self_node_id, error, node);
+
+				data.result = Some(Err(err.clone()));
+				self.core.listeners.notify(&self.core.meta.id, SessionEvent::Failed(err.clone()));
+				self.core.completed.notify_all();
+				Err(err)
+			},
+		}
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.consensus_session.state() == ConsensusSessionState::Failed
+			|| data.consensus_session.state() == ConsensusSessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().consensus_session.state() == ConsensusSessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		// ignore error, only state matters
+		let _ = self.process_node_error(Some(node), &Error::NodeDisconnected);
+	}
+
+	fn on_session_timeout(&self) {
+		// ignore error, only state matters
+		let _ = self.process_node_error(None, &Error::NodeDisconnected);
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		let state = data.consensus_session.state();
+		if state == ConsensusSessionState::Failed || state == ConsensusSessionState::Finished {
+			return;
+		}
+
+		warn!("{}: re-encryption session has been cancelled", &self.core.meta.self_node_id);
+
+		// key storage is never modified by re-encryption session => nothing to restore
+		// do not bother processing send error, as we already processing error
+		let error = Message::ReEncryption(ReEncryptionMessage::ReEncryptionSessionError(ReEncryptionSessionError {
+			session: self.core.meta.id.clone().into(),
+			sub_session: self.core.access_key.clone().into(),
+			session_nonce: self.core.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.core.meta.self_node_id == self.core.meta.master_node_id {
+			self.core.cluster.broadcast(error)
+		} else {
+			self.core.cluster.send(&self.core.meta.master_node_id, error)
+		};
+
+		data.consensus_session.on_session_cancelled();
+		data.result = Some(Err(Error::SessionCancelled));
+		self.core.listeners.notify(&self.core.meta.id, SessionEvent::Failed(Error::SessionCancelled));
+		self.core.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn wait(&self) -> Result<ReEncryptedDocumentKey, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			self.core.completed.wait(&mut data);
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+impl SessionCore {
+	pub fn reencryption_transport(&self, key_version: H256) -> ReEncryptionJobTransport {
+		ReEncryptionJobTransport {
+			id: self.meta.id.clone(),
+			access_key: self.access_key.clone(),
+			nonce: self.nonce,
+			key_version: key_version,
+			cluster: self.cluster.clone()
+		}
+	}
+
+	pub fn latest_key_version(&self) -> Result<H256, Error> {
+		self.key_storage.versions(&self.meta.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?
+			.into_iter()
+			.nth(0)
+			.ok_or(Error::KeyStorage("key share has no versions".into()))
+	}
+
+	pub fn filter_consensus_message(&self, key_version: Option<&H256>, message: &ConsensusMessage) -> Result<ConsensusMessage, Error> {
+		match *message {
+			// on master node: nodes, which do not have the negotiated key version, are treated as rejecting consensus
+			ConsensusMessage::ConfirmConsensusInitialization(ref message) if self.meta.self_node_id == self.meta.master_node_id => {
+				let key_version = key_version.ok_or(Error::InvalidStateForRequest)?;
+				Ok(ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+					is_confirmed: message.is_confirmed && message.key_versions.iter().any(|v| &**v == key_version),
+					key_versions: message.key_versions.clone(),
+				}))
+			},
+			_ => Ok(message.clone()),
+		}
+	}
+
+	pub fn check_reencryption_target(&self, requester: &Public, target_public: &Public, target_signature: &Signature) -> Result<(), Error> {
+		let target_hash = math::compute_reencryption_target_hash(&self.meta.id, target_public);
+		match recover(target_signature, &target_hash)? == *requester {
+			true => Ok(()),
+			false => Err(Error::AccessDenied),
+		}
+	}
+
+	pub fn disseminate_jobs(&self, consensus_session: &mut ReEncryptionConsensusSession, target_public: Public, target_signature: Signature, key_version: H256) -> Result<(), Error> {
+		let reencryption_job = ReEncryptionJob::new_on_master(self.meta.self_node_id.clone(), self.key_share.clone(), target_public, target_signature)?;
+		consensus_session.disseminate_jobs(reencryption_job, self.reencryption_transport(key_version))
+	}
+}
+
+impl JobTransport for ReEncryptionConsensusTransport {
+	type PartialJobRequest=Signature;
+	type PartialJobResponse=bool;
+
+	fn send_partial_request(&self, node: &NodeId, request: Signature) -> Result<(), Error> {
+		self.cluster.send(node, Message::ReEncryption(ReEncryptionMessage::ReEncryptionConsensusMessage(ReEncryptionConsensusMessage {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			message: ConsensusMessage::InitializeConsensusSession(InitializeConsensusSession {
+				requestor_signature: request.into(),
+			})
+		})))
+	}
+
+	fn send_partial_response(&self, node: &NodeId, response: bool) -> Result<(), Error> {
+		let key_versions = self.key_storage.versions(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		self.cluster.send(node, Message::ReEncryption(ReEncryptionMessage::ReEncryptionConsensusMessage(ReEncryptionConsensusMessage {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			message: ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+				is_confirmed: response,
+				key_versions: key_versions.into_iter().map(Into::into).collect(),
+			})
+		})))
+	}
+}
+
+impl JobTransport for ReEncryptionJobTransport {
+	type PartialJobRequest=PartialReEncryptionRequest;
+	type PartialJobResponse=PartialReEncryptionResponse;
+
+	fn send_partial_request(&self, node: &NodeId, request: PartialReEncryptionRequest) -> Result<(), Error> {
+		self.cluster.send(node, Message::ReEncryption(ReEncryptionMessage::RequestPartialReEncryption(RequestPartialReEncryption {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			request_id: request.id.into(),
+			target_public: request.target_public.into(),
+			target_signature: request.target_signature.into(),
+			nodes: request.other_nodes_ids.into_iter().map(Into::into).collect(),
+			key_version: self.key_version.clone().into(),
+		})))
+	}
+
+	fn send_partial_response(&self, node: &NodeId, response: PartialReEncryptionResponse) -> Result<(), Error> {
+		self.cluster.send(node, Message::ReEncryption(ReEncryptionMessage::PartialReEncryption(PartialReEncryption {
+			session: self.id.clone().into(),
+			sub_session: self.access_key.clone().into(),
+			session_nonce: self.nonce,
+			request_id: response.request_id.into(),
+			common_shadow: response.common_shadow.into(),
+			reencryption_shadow: response.reencryption_shadow.into(),
+		})))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use acl_storage::DummyAclStorage;
+	use ethkey::{self, KeyPair, Random, Generator, Public, Secret, Signature};
+	use key_server_cluster::{NodeId, DocumentKeyShare, SessionId, Error, SessionMeta, KeyStorage, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::re_encryption_session::{SessionImpl, SessionParams};
+	use key_server_cluster::message::{self, Message};
+	use key_server_cluster::math;
+	use key_server_cluster::jobs::consensus_session::ConsensusSessionState;
+
+	const SECRET_PLAIN: &'static str = "d2b57ae7619e070af0af6bc8c703c0cd27814c54d5d6a999cacac0da34ede279ca0d9216e85991029e54e2f0c92ee0bd30237725fa765cbdbfc4529489864c5f";
+
+	fn prepare_reencryption_sessions() -> (KeyPair, Vec<Arc<DummyCluster>>, Vec<Arc<DummyAclStorage>>, Vec<SessionImpl>) {
+		// prepare encrypted data + cluster configuration for scheme 4-of-5
+		let session_id = SessionId::default();
+		let access_key = Random.generate().unwrap().secret().clone();
+		let secret_shares: Vec<Secret> = vec![
+			"834cb736f02d9c968dfaf0c37658a1d86ff140554fc8b59c9fdad5a8cf810eec".parse().unwrap(),
+			"5a3c1d90fafafa66bb808bcc464354a98b05e6b2c95b5f609d4511cdd1b17a0b".parse().unwrap(),
+			"71bf61e7848e08e3a8486c308ce521bdacfebcf9116a0151447eb301f3a2d0e9".parse().unwrap(),
+			"80c0e5e2bea66fa9b2e07f7ce09630a9563e8242446d5ee63221feb09c4338f4".parse().unwrap(),
+			"c06546b5669877ba579ca437a5602e89425c53808c708d44ccd6afcaa4610fad".parse().unwrap(),
+		];
+		let id_numbers: Vec<(NodeId, Secret)> = vec![
+			("b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".into(),
+				"281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c".parse().unwrap()),
+			("1395568277679f7f583ab7c0992da35f26cde57149ee70e524e49bdae62db3e18eb96122501e7cbb798b784395d7bb5a499edead0706638ad056d886e56cf8fb".into(),
+				"00125d85a05e5e63e214cb60fe63f132eec8a103aa29266b7e6e6c5b7597230b".parse().unwrap()),
+			("99e82b163b062d55a64085bacfd407bb55f194ba5fb7a1af9c34b84435455520f1372e0e650a4f91aed0058cb823f62146ccb5599c8d13372c300dea866b69fc".into(),
+				"f43ac0fba42a5b6ed95707d2244659e89ba877b1c9b82c0d0a9dcf834e80fc62".parse().unwrap()),
+			("7e05df9dd077ec21ed4bc45c9fe9e0a43d65fa4be540630de615ced5e95cf5c3003035eb713317237d7667feeeb64335525158f5f7411f67aca9645169ea554c".into(),
+				"5a324938dfb2516800487d25ab7289ba8ec38811f77c3df602e4e65e3c9acd9f".parse().unwrap()),
+			("321977760d1d8e15b047a309e4c7fe6f355c10bb5a06c68472b676926427f69f229024fa2692c10da167d14cdc77eb95d0fce68af0a0f704f0d3db36baa83bb2".into(),
+				"12cf422d50002d04e52bd4906fd7f5f235f051ca36abfe37e061f8da248008d8".parse().unwrap()),
+		];
+		let common_point: Public = "6962be696e1bcbba8e64cc7fddf140f854835354b5804f3bb95ae5a2799130371b589a131bd39699ac7174ccb35fc4342dab05331202209582fc8f3a40916ab0".into();
+		let encrypted_point: Public = "b07031982bde9890e12eff154765f03c56c3ab646ad47431db5dd2d742a9297679c4c65b998557f8008469afd0c43d40b6c5f6c6a1c7354875da4115237ed87a".into();
+		let encrypted_datas: Vec<_> = (0..5).map(|i| DocumentKeyShare {
+			author: Public::default(),
+			threshold: 3,
+			id_numbers: id_numbers.clone().into_iter().collect(),
+			secret_share: secret_shares[i].clone(),
+			common_point: Some(common_point.clone()),
+			encrypted_point: Some(encrypted_point.clone()),
+			refresh_id: None,
+			metadata: Default::default(),
+		}).collect();
+		let key_storages: Vec<_> = (0..5).map(|i| {
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			key_storage.insert(session_id.clone(), encrypted_datas[i].clone()).unwrap();
+			key_storage
+		}).collect();
+		let acl_storages: Vec<_> = (0..5).map(|_| Arc::new(DummyAclStorage::default())).collect();
+		let clusters: Vec<_> = (0..5).map(|i| {
+			let cluster = Arc::new(DummyCluster::new(id_numbers.iter().nth(i).clone().unwrap().0));
+			for id_number in &id_numbers {
+				cluster.add_node(id_number.0.clone());
+			}
+			cluster
+		}).collect();
+		let requester = Random.generate().unwrap();
+		let signature = Some(ethkey::sign(requester.secret(), &SessionId::default()).unwrap());
+		let sessions: Vec<_> = (0..5).map(|i| SessionImpl::new(SessionParams {
+			meta: SessionMeta {
+				id: session_id.clone(),
+				self_node_id: id_numbers.iter().nth(i).clone().unwrap().0,
+				master_node_id: id_numbers.iter().nth(0).clone().unwrap().0,
+				threshold: encrypted_datas[i].threshold,
+			},
+			access_key: access_key.clone(),
+			key_share: encrypted_datas[i].clone(),
+			key_storage: key_storages[i].clone(),
+			acl_storage: acl_storages[i].clone(),
+			cluster: clusters[i].clone(),
+			nonce: 0,
+		}, if i == 0 { signature.clone() } else { None }).unwrap()).collect();
+
+		(requester, clusters, acl_storages, sessions)
+	}
+
+	fn do_messages_exchange(clusters: &[Arc<DummyCluster>], sessions: &[SessionImpl]) -> Result<(), Error> {
+		do_messages_exchange_until(clusters, sessions, |_, _, _| false)
+	}
+
+	fn do_messages_exchange_until<F>(clusters: &[Arc<DummyCluster>], sessions: &[SessionImpl], mut cond: F) -> Result<(), Error> where F: FnMut(&NodeId, &NodeId, &Message) -> bool {
+		while let Some((from, to, message)) = clusters.iter().filter_map(|c| c.take_message().map(|(to, msg)| (c.node(), to, msg))).next() {
+			let session = &sessions[sessions.iter().position(|s| s.node() == &to).unwrap()];
+			if cond(&from, &to, &message) {
+				break;
+			}
+
+			match message {
+				Message::ReEncryption(message) => session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn sign_target(requester: &KeyPair, target_public: &Public) -> Signature {
+		ethkey::sign(requester.secret(), &math::compute_reencryption_target_hash(&SessionId::default(), target_public)).unwrap()
+	}
+
+	#[test]
+	fn complete_reencryption_session() {
+		let (requester, clusters, _, sessions) = prepare_reencryption_sessions();
+
+		// now let's try to re-encrypt document key with target public key
+		let target = Random.generate().unwrap();
+		sessions[0].initialize(target.public().clone(), sign_target(&requester, target.public())).unwrap();
+
+		do_messages_exchange(&clusters, &sessions).unwrap();
+
+		// now check that:
+		// 1) 5 of 5 sessions are in Finished state
+		assert_eq!(sessions.iter().filter(|s| s.state() == ConsensusSessionState::Finished).count(), 5);
+		// 2) 1 session has re-encrypted key value
+		assert!(sessions.iter().skip(1).all(|s| s.reencrypted_secret().is_none()));
+		// 3) re-encrypted key can only be decrypted with target secret
+		let secret_plain: Public = SECRET_PLAIN.into();
+		let reencrypted_secret = sessions[0].reencrypted_secret().unwrap().unwrap();
+		assert!(reencrypted_secret.encrypted_point != secret_plain);
+		assert!(math::decrypt_with_joint_secret(&reencrypted_secret.encrypted_point, &reencrypted_secret.common_point, requester.secret()).unwrap() != secret_plain);
+		assert_eq!(math::decrypt_with_joint_secret(&reencrypted_secret.encrypted_point, &reencrypted_secret.common_point, target.secret()).unwrap(), secret_plain);
+	}
+
+	#[test]
+	fn reencryption_fails_to_initialize_if_target_is_not_signed_by_requester() {
+		let (_, _, _, sessions) = prepare_reencryption_sessions();
+		let target = Random.generate().unwrap();
+		assert_eq!(sessions[0].initialize(target.public().clone(), sign_target(&target, target.public())), Err(Error::AccessDenied));
+	}
+
+	#[test]
+	fn fails_to_partial_reencrypt_if_target_is_not_signed_by_requester() {
+		let (_, _, _, sessions) = prepare_reencryption_sessions();
+		let requester = Random.generate().unwrap();
+		assert_eq!(sessions[1].on_consensus_message(sessions[0].node(), &message::ReEncryptionConsensusMessage {
+				session: SessionId::default().into(),
+				sub_session: sessions[0].access_key().clone().into(),
+				session_nonce: 0,
+				message: message::ConsensusMessage::InitializeConsensusSession(message::InitializeConsensusSession {
+					requestor_signature: ethkey::sign(requester.secret(), &SessionId::default()).unwrap().into(),
+				}),
+		}).unwrap(), ());
+
+		// master node has replaced target public key with its own key
+		let target = Random.generate().unwrap();
+		assert_eq!(sessions[1].on_partial_reencryption_requested(sessions[0].node(), &message::RequestPartialReEncryption {
+			session: SessionId::default().into(),
+			sub_session: sessions[0].access_key().clone().into(),
+			session_nonce: 0,
+			request_id: Random.generate().unwrap().secret().clone().into(),
+			target_public: target.public().clone().into(),
+			target_signature: sign_target(&requester, Random.generate().unwrap().public()).into(),
+			nodes: sessions.iter().map(|s| s.node().clone().into()).take(4).collect(),
+			key_version: sessions[1].core.key_share.version().into(),
+		}).unwrap_err(), Error::AccessDenied);
+	}
+
+	#[test]
+	fn fails_to_partial_reencrypt_if_requested_by_slave() {
+		let (_, _, _, sessions) = prepare_reencryption_sessions();
+		let requester = Random.generate().unwrap();
+		assert_eq!(sessions[1].on_consensus_message(sessions[0].node(), &message::ReEncryptionConsensusMessage {
+				session: SessionId::default().into(),
+				sub_session: sessions[0].access_key().clone().into(),
+				session_nonce: 0,
+				message: message::ConsensusMessage::InitializeConsensusSession(message::InitializeConsensusSession {
+					requestor_signature: ethkey::sign(requester.secret(), &SessionId::default()).unwrap().into(),
+				}),
+		}).unwrap(), ());
+
+		let target = Random.generate().unwrap();
+		assert_eq!(sessions[1].on_partial_reencryption_requested(sessions[2].node(), &message::RequestPartialReEncryption {
+			session: SessionId::default().into(),
+			sub_session: sessions[0].access_key().clone().into(),
+			session_nonce: 0,
+			request_id: Random.generate().unwrap().secret().clone().into(),
+			target_public: target.public().clone().into(),
+			target_signature: sign_target(&requester, target.public()).into(),
+			nodes: sessions.iter().map(|s| s.node().clone().into()).take(4).collect(),
+			key_version: sessions[1].core.key_share.version().into(),
+		}).unwrap_err(), Error::InvalidMessage);
+	}
+
+	#[test]
+	fn failed_reencryption_session() {
+		let (requester, clusters, acl_storages, sessions) = prepare_reencryption_sessions();
+
+		// now let's try to do a re-encryption
+		let target = Random.generate().unwrap();
+		sessions[0].initialize(target.public().clone(), sign_target(&requester, target.public())).unwrap();
+
+		// we need 4 out of 5 nodes to agree to do a re-encryption
+		// let's say that 2 of these nodes are disagree
+		acl_storages[1].prohibit(requester.public().clone(), SessionId::default());
+		acl_storages[2].prohibit(requester.public().clone(), SessionId::default());
+
+		assert_eq!(do_messages_exchange(&clusters, &sessions).unwrap_err(), Error::ConsensusUnreachable);
+
+		// check that 3 nodes have failed state
+		assert_eq!(sessions[0].state(), ConsensusSessionState::Failed);
+		assert_eq!(sessions.iter().filter(|s| s.state() == ConsensusSessionState::Failed).count(), 3);
+	}
+
+	#[test]
+	fn reencryption_message_fails_when_nonce_is_wrong() {
+		let (_, _, _, sessions) = prepare_reencryption_sessions();
+		assert_eq!(sessions[1].process_message(sessions[0].node(), &message::ReEncryptionMessage::ReEncryptionSessionCompleted(
+			message::ReEncryptionSessionCompleted {
+				session: SessionId::default().into(),
+				sub_session: sessions[0].access_key().clone().into(),
+				session_nonce: 10,
+			}
+		)), Err(Error::ReplayProtection));
+	}
+}
@@ -0,0 +1,143 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time;
+use std::collections::BTreeMap;
+use parking_lot::Mutex;
+use key_server_cluster::NodeId;
+
+/// Delay before the first reconnect attempt (milliseconds).
+const MIN_RECONNECT_DELAY: u64 = 200;
+/// Max delay between reconnect attempts (milliseconds).
+const MAX_RECONNECT_DELAY: u64 = 60_000;
+
+/// Reconnect attempts scheduler. Delay between attempts to connect to the same node is doubled after
+/// every attempt (up to MAX_RECONNECT_DELAY) && is reset when connection is established.
+#[derive(Default)]
+pub struct ReconnectBackoff {
+	/// Reconnect state of every node, we are not connected to.
+	nodes: Mutex<BTreeMap<NodeId, NodeBackoff>>,
+}
+
+/// Reconnect state of single node.
+struct NodeBackoff {
+	/// Delay before next attempt (milliseconds).
+	delay: u64,
+	/// Time, when next attempt is allowed.
+	next_attempt: time::Instant,
+}
+
+impl ReconnectBackoff {
+	/// Remember that connection to the node has been lost. Next attempt is allowed after MIN_RECONNECT_DELAY.
+	pub fn on_disconnected(&self, node: &NodeId) {
+		self.on_disconnected_at(node, time::Instant::now())
+	}
+
+	/// Remember that connection to the node has been established.
+	pub fn on_connected(&self, node: &NodeId) {
+		self.nodes.lock().remove(node);
+	}
+
+	/// Check if we could try to connect to the node now. If attempt is allowed, delay before the next attempt is doubled.
+	pub fn begin_attempt(&self, node: &NodeId) -> bool {
+		self.begin_attempt_at(node, time::Instant::now())
+	}
+
+	/// Get delay until the nearest allowed attempt. None if there are no nodes to reconnect to.
+	pub fn next_attempt_delay(&self) -> Option<time::Duration> {
+		self.next_attempt_delay_at(time::Instant::now())
+	}
+
+	fn on_disconnected_at(&self, node: &NodeId, now: time::Instant) {
+		self.nodes.lock().insert(node.clone(), NodeBackoff {
+			delay: MIN_RECONNECT_DELAY,
+			next_attempt: now + time::Duration::from_millis(MIN_RECONNECT_DELAY),
+		});
+	}
+
+	fn begin_attempt_at(&self, node: &NodeId, now: time::Instant) -> bool {
+		let mut nodes = self.nodes.lock();
+		let backoff = nodes.entry(node.clone()).or_insert_with(|| NodeBackoff {
+			delay: MIN_RECONNECT_DELAY,
+			next_attempt: now,
+		});
+		if now < backoff.next_attempt {
+			return false;
+		}
+
+		backoff.next_attempt = now + time::Duration::from_millis(backoff.delay);
+		backoff.delay = ::std::cmp::min(backoff.delay * 2, MAX_RECONNECT_DELAY);
+		true
+	}
+
+	fn next_attempt_delay_at(&self, now: time::Instant) -> Option<time::Duration> {
+		self.nodes.lock().values()
+			.map(|backoff| if backoff.next_attempt > now { backoff.next_attempt - now } else { time::Duration::from_millis(0) })
+			.min()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time;
+	use ethkey::{Random, Generator};
+	use super::{ReconnectBackoff, MIN_RECONNECT_DELAY, MAX_RECONNECT_DELAY};
+
+	#[test]
+	fn delay_between_attempts_is_doubled() {
+		let node = Random.generate().unwrap().public().clone();
+		let backoff = ReconnectBackoff::default();
+		let now = time::Instant::now();
+
+		// first attempt is allowed immediately
+		assert!(backoff.begin_attempt_at(&node, now));
+		assert!(!backoff.begin_attempt_at(&node, now));
+		assert_eq!(backoff.next_attempt_delay_at(now), Some(time::Duration::from_millis(MIN_RECONNECT_DELAY)));
+
+		// next attempt is allowed after MIN_RECONNECT_DELAY, the one after it - after 2 * MIN_RECONNECT_DELAY
+		let now = now + time::Duration::from_millis(MIN_RECONNECT_DELAY);
+		assert!(backoff.begin_attempt_at(&node, now));
+		assert!(!backoff.begin_attempt_at(&node, now + time::Duration::from_millis(MIN_RECONNECT_DELAY)));
+		assert!(backoff.begin_attempt_at(&node, now + time::Duration::from_millis(2 * MIN_RECONNECT_DELAY)));
+	}
+
+	#[test]
+	fn delay_between_attempts_is_limited() {
+		let node = Random.generate().unwrap().public().clone();
+		let backoff = ReconnectBackoff::default();
+		let mut now = time::Instant::now();
+		for _ in 0..20 {
+			assert!(backoff.begin_attempt_at(&node, now));
+			now = now + time::Duration::from_millis(MAX_RECONNECT_DELAY);
+		}
+	}
+
+	#[test]
+	fn delay_is_reset_when_connected() {
+		let node = Random.generate().unwrap().public().clone();
+		let backoff = ReconnectBackoff::default();
+		let now = time::Instant::now();
+		assert!(backoff.begin_attempt_at(&node, now));
+
+		backoff.on_connected(&node);
+		assert_eq!(backoff.next_attempt_delay_at(now), None);
+
+		// when connection is lost, we wait for MIN_RECONNECT_DELAY before reconnecting
+		backoff.on_disconnected_at(&node, now);
+		assert!(!backoff.begin_attempt_at(&node, now));
+		assert!(backoff.begin_attempt_at(&node, now + time::Duration::from_millis(MIN_RECONNECT_DELAY)));
+	}
+}
@@ -0,0 +1,706 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use ethkey::{self, Public, Signature};
+use key_server_cluster::{Error, NodeId, SessionMeta, AclStorage, DocumentKeyShare, NodeKeyPair, AttestedServerKeyPublic};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::math;
+use key_server_cluster::message::{Message, ServerKeyRetrievalMessage, InitializeServerKeyRetrievalSession, ServerKeyRetrievalPublicShare,
+	RequestServerKeyAttestation, ServerKeyAttestation, ServerKeyRetrievalSessionError};
+
+/// Server key retrieval session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get server key retrieval session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed. Returns public portion of server key, attested by key holders.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<AttestedServerKeyPublic, Error>;
+}
+
+/// Server key retrieval session.
+/// Restores public portion of previously generated server key && collects attestations of key holders, so that
+/// the requester does not have to trust the single key server.
+/// Brief overview:
+/// 1) initialization: master node checks that requester is allowed to access the key && asks every connected key holder
+///   to send public portion of its key share
+/// 2) every key holder checks that requester is allowed to access the key && sends public portion of its key share back
+/// 3) master node restores joint public from all received public shares (there must be at least threshold + 1 of them)
+///   && sends these public shares to every key holder, asking it to attest the joint public
+/// 4) every key holder checks that its own public share is used && restores the same joint public. Then it signs
+///   keccak(key_id | joint_public) with its node key && sends signature back to the master node
+/// The session is read-only: key storage is never modified.
+pub struct SessionImpl {
+	/// Session metadata. Session id is the id of the server key.
+	meta: SessionMeta,
+	/// Key share.
+	key_share: DocumentKeyShare,
+	/// ACL storage.
+	acl_storage: Arc<AclStorage>,
+	/// This node key pair. Is used to sign attestations.
+	self_key_pair: Arc<NodeKeyPair>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// ACL storage.
+	pub acl_storage: Arc<AclStorage>,
+	/// This node key pair.
+	pub self_key_pair: Arc<NodeKeyPair>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of server key retrieval session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// === Values, filled on master node ===
+	/// Key holders, participating in the session (including this node).
+	participants: BTreeSet<NodeId>,
+	/// Public portions of key shares, received from key holders (including this node).
+	public_shares: BTreeMap<NodeId, Public>,
+	/// Attestations, received from key holders (including this node).
+	attestations: BTreeMap<NodeId, Signature>,
+	/// Restored joint public.
+	joint_public: Option<Public>,
+	/// === Values, filled on all nodes ===
+	/// Server key retrieval session result.
+	result: Option<Result<AttestedServerKeyPublic, Error>>,
+}
+
+/// Server key retrieval session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	// === Initialization states ===
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for every participant to send public portion of its key share.
+	WaitingForPublicShares,
+	/// Slave node waits for attestation request from master node.
+	WaitingForAttestationRequest,
+	/// Master node waits for every participant to attest the joint public.
+	WaitingForAttestations,
+
+	// === Final states of the session ===
+	/// Joint public is attested.
+	Finished,
+	/// Failed to attest joint public.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new server key retrieval session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		if !params.key_share.id_numbers.contains_key(&params.meta.self_node_id)
+			|| !params.key_share.id_numbers.contains_key(&params.meta.master_node_id) {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+
+		Ok(SessionImpl {
+			meta: params.meta,
+			key_share: params.key_share,
+			acl_storage: params.acl_storage,
+			self_key_pair: params.self_key_pair,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				participants: BTreeSet::new(),
+				public_shares: BTreeMap::new(),
+				attestations: BTreeMap::new(),
+				joint_public: None,
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	pub fn initialize(&self, requestor_signature: Signature, connected_nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		self.check_access(&requestor_signature)?;
+
+		// at least threshold + 1 connected key holders are required to restore joint public
+		let participants: BTreeSet<_> = self.key_share.id_numbers.keys()
+			.filter(|n| *n == self.node() || connected_nodes.contains(n))
+			.cloned()
+			.collect();
+		if participants.len() < self.key_share.threshold + 1 {
+			return Err(Error::ConsensusUnreachable);
+		}
+
+		// update state
+		data.public_shares.insert(self.node().clone(), math::compute_public_share(&self.key_share.secret_share)?);
+		data.participants = participants;
+		data.state = SessionState::WaitingForPublicShares;
+
+		// start initialization
+		for node in data.participants.iter().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(InitializeServerKeyRetrievalSession {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				requestor_signature: requestor_signature.clone().into(),
+			})))?;
+		}
+
+		self.try_request_attestations(&mut *data)
+	}
+
+	/// Process server key retrieval message.
+	pub fn process_message(&self, sender: &NodeId, message: &ServerKeyRetrievalMessage) -> Result<(), Error> {
+		match message {
+			&ServerKeyRetrievalMessage::InitializeServerKeyRetrievalSession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&ServerKeyRetrievalMessage::ServerKeyRetrievalPublicShare(ref message) =>
+				self.on_public_share(sender.clone(), message),
+			&ServerKeyRetrievalMessage::RequestServerKeyAttestation(ref message) =>
+				self.on_attestation_request(sender.clone(), message),
+			&ServerKeyRetrievalMessage::ServerKeyAttestation(ref message) =>
+				self.on_attestation(sender.clone(), message),
+			&ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+		}
+	}
+
+	/// When session initialization message is received.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeServerKeyRetrievalSession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		self.check_access(&message.requestor_signature.clone().into())?;
+
+		// update state
+		data.state = SessionState::WaitingForAttestationRequest;
+
+		// send public portion of key share back to master node
+		self.cluster.send(&sender, Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalPublicShare(ServerKeyRetrievalPublicShare {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			public_share: math::compute_public_share(&self.key_share.secret_share)?.into(),
+		})))
+	}
+
+	/// When public portion of key share is received.
+	pub fn on_public_share(&self, sender: NodeId, message: &ServerKeyRetrievalPublicShare) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForPublicShares {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.participants.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.public_shares.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.public_shares.insert(sender, message.public_share.clone().into());
+
+		self.try_request_attestations(&mut *data)
+	}
+
+	/// When attestation request is received.
+	pub fn on_attestation_request(&self, sender: NodeId, message: &RequestServerKeyAttestation) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForAttestationRequest {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// joint public must be restored from public shares of key holders && our own public share must be used
+		let public_shares: BTreeMap<NodeId, Public> = message.public_shares.iter()
+			.map(|(n, p)| (n.clone().into(), p.clone().into()))
+			.collect();
+		if public_shares.keys().any(|n| !self.key_share.id_numbers.contains_key(n)) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if public_shares.get(self.node()) != Some(&math::compute_public_share(&self.key_share.secret_share)?) {
+			return Err(Error::InvalidMessage);
+		}
+
+		let joint_public = self.compute_joint_public(&public_shares)?;
+		let signature = self.attest(&joint_public)?;
+		self.cluster.send(&sender, Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyAttestation(ServerKeyAttestation {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			signature: signature.clone().into(),
+		})))?;
+
+		// update state
+		let mut attestations = BTreeMap::new();
+		attestations.insert(self.node().clone(), signature);
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(AttestedServerKeyPublic {
+			public: joint_public,
+			attestations: attestations,
+		}));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// When attestation is received.
+	pub fn on_attestation(&self, sender: NodeId, message: &ServerKeyAttestation) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForAttestations {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.participants.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.attestations.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		// attestation must be signed by the sender node key
+		let signature: Signature = message.signature.clone().into();
+		let joint_public = data.joint_public.clone()
+			.expect("joint_public is filled before state is changed to WaitingForAttestations; qed");
+		let attestation_hash = math::compute_server_key_attestation_hash(&self.meta.id, &joint_public);
+		if !ethkey::verify_public(&sender, &signature, &attestation_hash)? {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.attestations.insert(sender, signature);
+
+		self.try_complete(&mut *data);
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &ServerKeyRetrievalSessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: server key retrieval session failed with error: {} from {}", self.node(), message.error, sender);
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Check that requester is the author of the key or is allowed to access the key.
+	fn check_access(&self, requestor_signature: &Signature) -> Result<(), Error> {
+		let requestor_public = ethkey::recover(requestor_signature, &self.meta.id)?;
+		if self.key_share.author == requestor_public {
+			return Ok(());
+		}
+
+		match self.acl_storage.check(&requestor_public, &self.meta.id) {
+			Ok(true) => Ok(()),
+			_ => Err(Error::AccessDenied),
+		}
+	}
+
+	/// Ask every participant to attest joint public, if public shares from all participants are received.
+	fn try_request_attestations(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::WaitingForPublicShares || data.public_shares.len() != data.participants.len() {
+			return Ok(());
+		}
+
+		let joint_public = self.compute_joint_public(&data.public_shares)?;
+		let signature = self.attest(&joint_public)?;
+		data.attestations.insert(self.node().clone(), signature);
+		data.joint_public = Some(joint_public);
+		data.state = SessionState::WaitingForAttestations;
+
+		for node in data.participants.iter().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::RequestServerKeyAttestation(RequestServerKeyAttestation {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				public_shares: data.public_shares.iter().map(|(n, p)| (n.clone().into(), p.clone().into())).collect(),
+			})))?;
+		}
+
+		self.try_complete(data);
+		Ok(())
+	}
+
+	/// Complete the session, if attestations from all participants are received.
+	fn try_complete(&self, data: &mut SessionData) {
+		if data.state != SessionState::WaitingForAttestations || data.attestations.len() != data.participants.len() {
+			return;
+		}
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(AttestedServerKeyPublic {
+			public: data.joint_public.clone()
+				.expect("joint_public is filled before state is changed to WaitingForAttestations; qed"),
+			attestations: data.attestations.clone(),
+		}));
+		self.completed.notify_all();
+	}
+
+	/// Restore joint public from public portions of key shares.
+	fn compute_joint_public(&self, public_shares: &BTreeMap<NodeId, Public>) -> Result<Public, Error> {
+		let threshold = self.key_share.threshold;
+		if public_shares.len() < threshold + 1 {
+			return Err(Error::ConsensusUnreachable);
+		}
+
+		let id_numbers: Vec<_> = public_shares.keys().map(|n| &self.key_share.id_numbers[n]).collect();
+		let public_shares: Vec<_> = public_shares.values().collect();
+		let joint_public = math::compute_joint_public_from_shares(&public_shares, &id_numbers)?;
+
+		// any threshold + 1 shares must lead to the same public => otherwise some node has sent wrong share
+		if public_shares.len() > threshold + 1
+			&& math::compute_joint_public_from_shares(&public_shares[..threshold + 1], &id_numbers[..threshold + 1])? != joint_public {
+			return Err(Error::InvalidMessage);
+		}
+
+		Ok(joint_public)
+	}
+
+	/// Sign joint public with this node key.
+	fn attest(&self, joint_public: &Public) -> Result<Signature, Error> {
+		let attestation_hash = math::compute_server_key_attestation_hash(&self.meta.id, joint_public);
+		self.self_key_pair.sign(&attestation_hash).map_err(Into::into)
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		// every participant is required to complete the session
+		if *node != self.meta.master_node_id && !data.participants.contains(node) {
+			return;
+		}
+
+		warn!("{}: server key retrieval session failed because {} connection has timeouted", self.node(), node);
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		warn!("{}: server key retrieval session failed with timeout", self.node());
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: server key retrieval session has been cancelled", self.node());
+
+		// do not bother processing send error, as we already processing error
+		let error = Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::ServerKeyRetrievalSessionError(ServerKeyRetrievalSessionError {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.meta.self_node_id == self.meta.master_node_id {
+			self.cluster.broadcast(error)
+		} else {
+			self.cluster.send(&self.meta.master_node_id, error)
+		};
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<AttestedServerKeyPublic, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::{BTreeSet, BTreeMap};
+	use ethkey::{self, Random, Generator, KeyPair, Secret};
+	use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, DocumentKeyShare, DummyAclStorage, PlainNodeKeyPair};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, ServerKeyRetrievalMessage, RequestServerKeyAttestation};
+	use super::{SessionImpl, SessionParams, SessionState, Session};
+
+	struct Node {
+		cluster: Arc<DummyCluster>,
+		session: SessionImpl,
+	}
+
+	fn prepare_nodes(threshold: usize, num_nodes: usize, acl_storage: Arc<DummyAclStorage>) -> (Vec<Secret>, KeyPair, Vec<Node>) {
+		let author = Random.generate().unwrap();
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let key_pairs: BTreeMap<NodeId, KeyPair> = (0..num_nodes)
+			.map(|_| Random.generate().unwrap())
+			.map(|key_pair| (key_pair.public().clone(), key_pair))
+			.collect();
+		let id_numbers: BTreeMap<NodeId, Secret> = key_pairs.keys()
+			.map(|node_id| (node_id.clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		let master_node_id = id_numbers.keys().nth(0).cloned().unwrap();
+		let nodes = id_numbers.iter().map(|(node_id, id_number)| {
+			let key_share = DocumentKeyShare {
+				author: author.public().clone(),
+				threshold: threshold,
+				id_numbers: id_numbers.clone(),
+				secret_share: math::compute_polynom(&polynom, id_number).unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			};
+
+			let cluster = Arc::new(DummyCluster::new(node_id.clone()));
+			for node in id_numbers.keys() {
+				cluster.add_node(node.clone());
+			}
+
+			let session = SessionImpl::new(SessionParams {
+				meta: SessionMeta {
+					id: SessionId::default(),
+					master_node_id: master_node_id.clone(),
+					self_node_id: node_id.clone(),
+					threshold: threshold,
+				},
+				key_share: key_share,
+				acl_storage: acl_storage.clone(),
+				self_key_pair: Arc::new(PlainNodeKeyPair::new(key_pairs[node_id].clone())),
+				cluster: cluster.clone(),
+				nonce: 0,
+			}).unwrap();
+
+			Node {
+				cluster: cluster,
+				session: session,
+			}
+		}).collect();
+		(polynom, author, nodes)
+	}
+
+	fn all_nodes(nodes: &[Node]) -> BTreeSet<NodeId> {
+		nodes.iter().map(|n| n.session.node().clone()).collect()
+	}
+
+	fn do_messages_exchange_until<F>(nodes: &[Node], mut cond: F) -> Result<(), Error> where F: FnMut(&NodeId, &NodeId, &Message) -> bool {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			if cond(&from, &to, &message) {
+				break;
+			}
+
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::ServerKeyRetrieval(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn server_key_public_is_attested_by_all_key_holders() {
+		let (polynom, author, nodes) = prepare_nodes(1, 3, Arc::new(DummyAclStorage::default()));
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+
+		// every attestation is signed by the key holder && is verifiable by the requester
+		let joint_public = math::compute_public_share(&polynom[0]).unwrap();
+		let result = nodes[0].session.wait(None).unwrap();
+		let attestation_hash = math::compute_server_key_attestation_hash(&SessionId::default(), &joint_public);
+		assert_eq!(result.public, joint_public);
+		assert_eq!(result.attestations.keys().cloned().collect::<BTreeSet<_>>(), all_nodes(&nodes));
+		assert!(result.attestations.iter().all(|(n, s)| ethkey::verify_public(n, s, &attestation_hash).unwrap()));
+	}
+
+	#[test]
+	fn server_key_public_is_attested_by_connected_key_holders() {
+		let (polynom, author, nodes) = prepare_nodes(1, 3, Arc::new(DummyAclStorage::default()));
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		let mut connected_nodes = all_nodes(&nodes);
+		connected_nodes.remove(nodes[2].session.node());
+		nodes[0].session.initialize(signature, connected_nodes.clone()).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+
+		let result = nodes[0].session.wait(None).unwrap();
+		assert_eq!(result.public, math::compute_public_share(&polynom[0]).unwrap());
+		assert_eq!(result.attestations.keys().cloned().collect::<BTreeSet<_>>(), connected_nodes);
+		assert_eq!(nodes[2].session.state(), SessionState::WaitingForInitialization);
+	}
+
+	#[test]
+	fn server_key_retrieval_fails_if_not_enough_key_holders_are_connected() {
+		let (_, author, nodes) = prepare_nodes(1, 3, Arc::new(DummyAclStorage::default()));
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		let connected_nodes = vec![nodes[0].session.node().clone()].into_iter().collect();
+		assert_eq!(nodes[0].session.initialize(signature, connected_nodes), Err(Error::ConsensusUnreachable));
+	}
+
+	#[test]
+	fn server_key_retrieval_fails_if_requester_is_not_allowed_to_access_key() {
+		let acl_storage = Arc::new(DummyAclStorage::default());
+		let requester = Random.generate().unwrap();
+		acl_storage.prohibit(requester.public().clone(), SessionId::default());
+		let (_, _, nodes) = prepare_nodes(1, 3, acl_storage);
+		let signature = ethkey::sign(requester.secret(), &SessionId::default()).unwrap();
+
+		assert_eq!(nodes[0].session.initialize(signature, all_nodes(&nodes)), Err(Error::AccessDenied));
+	}
+
+	#[test]
+	fn server_key_retrieval_works_for_requester_on_acl() {
+		let (_, _, nodes) = prepare_nodes(1, 3, Arc::new(DummyAclStorage::default()));
+		let signature = ethkey::sign(Random.generate().unwrap().secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes[0].session.wait(None).is_ok());
+	}
+
+	#[test]
+	fn key_holder_refuses_to_attest_if_its_public_share_is_not_used() {
+		let (_, author, nodes) = prepare_nodes(1, 3, Arc::new(DummyAclStorage::default()));
+		let signature = ethkey::sign(author.secret(), &SessionId::default()).unwrap();
+
+		nodes[0].session.initialize(signature, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::ServerKeyRetrieval(ServerKeyRetrievalMessage::RequestServerKeyAttestation(_)) => true,
+			_ => false,
+		}).unwrap();
+
+		// master replaces public share of the slave node
+		let mut public_shares = BTreeMap::new();
+		for node in &nodes {
+			public_shares.insert(node.session.node().clone().into(), Random.generate().unwrap().public().clone().into());
+		}
+		assert_eq!(nodes[1].session.on_attestation_request(nodes[0].session.node().clone(), &RequestServerKeyAttestation {
+			session: SessionId::default().into(),
+			session_nonce: 0,
+			public_shares: public_shares,
+		}), Err(Error::InvalidMessage));
+	}
+}
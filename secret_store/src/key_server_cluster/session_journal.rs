@@ -0,0 +1,235 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Journal of cluster sessions. Every message, sent or processed by the session on this node, is recorded
+//! together with the session start && removal, so that failed sessions could be reconstructed afterwards.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write, ErrorKind};
+use std::path::PathBuf;
+use parking_lot::Mutex;
+use serde_json;
+use audit_log::unix_timestamp;
+use key_server_cluster::{Error, NodeId, SessionId, SessionOutcome, SerializablePublic};
+use key_server_cluster::decryption_session::DecryptionSessionId;
+use key_server_cluster::message::Message;
+
+/// Key of session in the sessions container, which could be journaled. Records of all sessions, working with
+/// the same key, are stored together.
+pub trait JournalSessionId {
+	/// Get id of the key, which session is working with.
+	fn journal_session_id(&self) -> &SessionId;
+}
+
+/// Single journal record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalRecord {
+	/// Time of the event (seconds since unix epoch).
+	pub timestamp: u64,
+	/// Journaled event.
+	pub event: JournalEvent,
+}
+
+/// Journaled session event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JournalEvent {
+	/// Session of given kind has been created on this node.
+	Started {
+		/// Session kind.
+		kind: String,
+		/// Session master node.
+		master: SerializablePublic,
+	},
+	/// Session message has been sent to other node.
+	Sent {
+		/// Receiver node.
+		to: SerializablePublic,
+		/// Message type.
+		message: String,
+		/// Session nonce.
+		nonce: u64,
+	},
+	/// Session message, received from other node, has been passed to the session.
+	Received {
+		/// Sender node.
+		from: SerializablePublic,
+		/// Message type.
+		message: String,
+		/// Session nonce.
+		nonce: u64,
+	},
+	/// Session of given kind has been removed from this node.
+	Removed {
+		/// Session kind.
+		kind: String,
+		/// Session outcome.
+		outcome: String,
+	},
+}
+
+/// File-based session journal. Records of every session are stored in a separate file, named by the session id.
+/// Every record is stored as a single-line JSON object.
+pub struct SessionJournal {
+	/// Path to the journal directory.
+	path: PathBuf,
+	/// Lock, which is held while writing record, so that records are never interleaved.
+	lock: Mutex<()>,
+}
+
+impl SessionJournal {
+	/// Open (create if not exists) journal directory.
+	pub fn new(path: &str) -> Result<Self, Error> {
+		let path = PathBuf::from(path);
+		fs::create_dir_all(&path)
+			.map_err(|e| Error::Io(format!("error opening session journal: {}", e)))?;
+		Ok(SessionJournal {
+			path: path,
+			lock: Mutex::new(()),
+		})
+	}
+
+	/// When session has been created on this node.
+	pub fn on_session_started(&self, session_id: &SessionId, kind: &str, master: &NodeId) {
+		self.append(session_id, JournalEvent::Started {
+			kind: kind.into(),
+			master: master.clone().into(),
+		});
+	}
+
+	/// When session has been removed from this node.
+	pub fn on_session_removed(&self, session_id: &SessionId, kind: &str, outcome: SessionOutcome) {
+		self.append(session_id, JournalEvent::Removed {
+			kind: kind.into(),
+			outcome: format!("{:?}", outcome),
+		});
+	}
+
+	/// When session message has been sent to other node.
+	pub fn on_message_sent(&self, to: &NodeId, message: &Message) {
+		if let (Some(session_id), Some(nonce)) = (message.session_id(), message.session_nonce()) {
+			self.append(session_id, JournalEvent::Sent {
+				to: to.clone().into(),
+				message: message.to_string(),
+				nonce: nonce,
+			});
+		}
+	}
+
+	/// When session message, received from other node, is passed to the session.
+	pub fn on_message_received(&self, from: &NodeId, message: &Message) {
+		if let (Some(session_id), Some(nonce)) = (message.session_id(), message.session_nonce()) {
+			self.append(session_id, JournalEvent::Received {
+				from: from.clone().into(),
+				message: message.to_string(),
+				nonce: nonce,
+			});
+		}
+	}
+
+	/// Read all records of sessions with given id.
+	pub fn records(&self, session_id: &SessionId) -> Result<Vec<JournalRecord>, Error> {
+		// hold the lock, so that partially written records are not read
+		let _lock = self.lock.lock();
+		let file = match File::open(self.session_path(session_id)) {
+			Ok(file) => file,
+			Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(e) => return Err(Error::Io(format!("error opening session journal: {}", e))),
+		};
+
+		let mut records = Vec::new();
+		for line in BufReader::new(file).lines() {
+			let line = line.map_err(|e| Error::Io(format!("error reading session journal: {}", e)))?;
+			if line.is_empty() {
+				continue;
+			}
+
+			records.push(serde_json::from_str(&line).map_err(|e| Error::Serde(e.to_string()))?);
+		}
+		Ok(records)
+	}
+
+	/// Append record to the journal of given session. Journal errors never affect sessions => they're only logged.
+	fn append(&self, session_id: &SessionId, event: JournalEvent) {
+		if let Err(err) = self.try_append(session_id, event) {
+			warn!(target: "secretstore_net", "failed to write session {} journal: {}", session_id, err);
+		}
+	}
+
+	fn try_append(&self, session_id: &SessionId, event: JournalEvent) -> Result<(), Error> {
+		let mut line = serde_json::to_vec(&JournalRecord {
+			timestamp: unix_timestamp(),
+			event: event,
+		}).map_err(|e| Error::Serde(e.to_string()))?;
+		line.push(b'\n');
+
+		let _lock = self.lock.lock();
+		OpenOptions::new().create(true).append(true).open(self.session_path(session_id))
+			.and_then(|mut file| file.write_all(&line).and_then(|_| file.flush()))
+			.map_err(|e| Error::Io(format!("error writing session journal: {}", e)))
+	}
+
+	fn session_path(&self, session_id: &SessionId) -> PathBuf {
+		self.path.join(format!("{:?}.journal", session_id))
+	}
+}
+
+impl JournalSessionId for SessionId {
+	fn journal_session_id(&self) -> &SessionId {
+		self
+	}
+}
+
+impl JournalSessionId for DecryptionSessionId {
+	fn journal_session_id(&self) -> &SessionId {
+		&self.id
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use devtools::RandomTempPath;
+	use ethkey::{Random, Generator};
+	use key_server_cluster::{SessionId, SessionOutcome};
+	use key_server_cluster::message::{self, Message, ShareMoveMessage, ClusterMessage};
+	use super::{SessionJournal, JournalEvent};
+
+	#[test]
+	fn session_journal_works() {
+		let path = RandomTempPath::create_dir();
+		let journal = SessionJournal::new(path.as_path().join("journal").to_str().unwrap()).unwrap();
+		let node = Random.generate().unwrap().public().clone();
+		let session_id = SessionId::from(1);
+		let message = Message::ShareMove(ShareMoveMessage::ShareMoveCompleted(message::ShareMoveCompleted {
+			session: session_id.clone().into(),
+			session_nonce: 10,
+		}));
+
+		journal.on_session_started(&session_id, "share_move", &node);
+		journal.on_message_received(&node, &message);
+		journal.on_message_sent(&node, &message);
+		journal.on_message_sent(&node, &Message::Cluster(ClusterMessage::KeepAlive(message::KeepAlive {})));
+		journal.on_session_removed(&session_id, "share_move", SessionOutcome::Failed);
+
+		let events: Vec<_> = journal.records(&session_id).unwrap().into_iter().map(|r| r.event).collect();
+		assert_eq!(events, vec![
+			JournalEvent::Started { kind: "share_move".into(), master: node.clone().into() },
+			JournalEvent::Received { from: node.clone().into(), message: "ShareMove.ShareMoveCompleted".into(), nonce: 10 },
+			JournalEvent::Sent { to: node.clone().into(), message: "ShareMove.ShareMoveCompleted".into(), nonce: 10 },
+			JournalEvent::Removed { kind: "share_move".into(), outcome: "Failed".into() },
+		]);
+		assert_eq!(journal.records(&SessionId::from(2)).unwrap(), vec![]);
+	}
+}
@@ -0,0 +1,284 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use ethkey::{Public, Secret};
+use bigint::hash::H256;
+use key_server_cluster::{Error, NodeId, DocumentKeyShare};
+use key_server_cluster::math;
+
+/// Key share of single node, gathered for the audit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditedKeyShare {
+	/// Author of the key.
+	pub author: Public,
+	/// Decryption threshold.
+	pub threshold: usize,
+	/// Nodes ids numbers.
+	pub id_numbers: BTreeMap<NodeId, Secret>,
+	/// Node secret share.
+	pub secret_share: Secret,
+	/// Common (shared) encryption point.
+	pub common_point: Option<Public>,
+	/// Encrypted point.
+	pub encrypted_point: Option<Public>,
+}
+
+/// Inconsistency, found in the key share of single node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShareInconsistency {
+	/// Node threshold differs from the threshold of the majority.
+	Threshold(usize),
+	/// Node author differs from the author of the majority.
+	Author(Public),
+	/// Node common point differs from the common point of the majority.
+	CommonPoint(Option<Public>),
+	/// Node encrypted point differs from the encrypted point of the majority.
+	EncryptedPoint(Option<Public>),
+	/// Node set of key holders differs from the set of the majority.
+	IdNumbersSet(BTreeSet<NodeId>),
+	/// Node has no id_number for given key holder.
+	MissingIdNumber(NodeId),
+	/// Node id_number for given key holder differs from the id_number of the majority.
+	IdNumberMismatch(NodeId),
+	/// Node secret share does not belong to the polynom, shared by the rest of nodes.
+	SecretShare,
+}
+
+/// Result of cluster-wide key shares audit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterShareReport {
+	/// Threshold of the majority.
+	pub threshold: usize,
+	/// Inconsistencies, found in every audited node key share.
+	pub inconsistencies: BTreeMap<NodeId, Vec<ShareInconsistency>>,
+	/// True if at least threshold + 1 audited shares are consistent with each other.
+	pub is_reconstructable: bool,
+}
+
+impl ClusterShareReport {
+	/// True if all audited key shares are consistent.
+	pub fn is_consistent(&self) -> bool {
+		self.is_reconstructable && self.inconsistencies.values().all(|i| i.is_empty())
+	}
+}
+
+/// Check that key shares of the same key, gathered from every node (out-of-band), form a coherent set.
+/// Every value is compared against the value, reported by the majority of nodes.
+pub fn check_cluster_shares(shares: &BTreeMap<NodeId, AuditedKeyShare>) -> Result<ClusterShareReport, Error> {
+	if shares.is_empty() {
+		return Err(Error::InvalidNodesCount);
+	}
+
+	let mut inconsistencies: BTreeMap<NodeId, Vec<ShareInconsistency>> = shares.keys().map(|n| (n.clone(), Vec::new())).collect();
+
+	// check values that must be identical on all nodes
+	let threshold = majority(shares.values().map(|s| s.threshold));
+	let author = majority(shares.values().map(|s| s.author.clone()));
+	let common_point = majority(shares.values().map(|s| s.common_point.clone()));
+	let encrypted_point = majority(shares.values().map(|s| s.encrypted_point.clone()));
+	let id_numbers_set = majority(shares.values().map(|s| s.id_numbers.keys().cloned().collect::<BTreeSet<_>>()));
+	for (node, share) in shares {
+		let node_inconsistencies = inconsistencies.get_mut(node).expect("inconsistencies are initialized for every node; qed");
+		if share.threshold != threshold {
+			node_inconsistencies.push(ShareInconsistency::Threshold(share.threshold));
+		}
+		if share.author != author {
+			node_inconsistencies.push(ShareInconsistency::Author(share.author.clone()));
+		}
+		if share.common_point != common_point {
+			node_inconsistencies.push(ShareInconsistency::CommonPoint(share.common_point.clone()));
+		}
+		if share.encrypted_point != encrypted_point {
+			node_inconsistencies.push(ShareInconsistency::EncryptedPoint(share.encrypted_point.clone()));
+		}
+		let node_id_numbers_set: BTreeSet<_> = share.id_numbers.keys().cloned().collect();
+		if node_id_numbers_set != id_numbers_set {
+			node_inconsistencies.push(ShareInconsistency::IdNumbersSet(node_id_numbers_set));
+		}
+	}
+
+	// check that id_number of every audited node is known && the same in all views
+	let mut id_numbers = BTreeMap::new();
+	for node in shares.keys() {
+		let node_id_numbers: Vec<H256> = shares.values().filter_map(|s| s.id_numbers.get(node).map(|id| (**id).clone())).collect();
+		if node_id_numbers.is_empty() {
+			continue;
+		}
+
+		let id_number: Secret = majority(node_id_numbers.into_iter()).into();
+		for (viewer, share) in shares {
+			let viewer_inconsistencies = inconsistencies.get_mut(viewer).expect("inconsistencies are initialized for every node; qed");
+			match share.id_numbers.get(node) {
+				None => viewer_inconsistencies.push(ShareInconsistency::MissingIdNumber(node.clone())),
+				Some(viewer_id_number) if *viewer_id_number != id_number =>
+					viewer_inconsistencies.push(ShareInconsistency::IdNumberMismatch(node.clone())),
+				Some(_) => (),
+			}
+		}
+		id_numbers.insert(node.clone(), id_number);
+	}
+
+	// check that shares are points of the same polynom => secret is reconstructable
+	let candidates: Vec<_> = shares.iter()
+		.filter(|&(node, share)| share.threshold == threshold && id_numbers.contains_key(node))
+		.map(|(node, share)| (node.clone(), id_numbers[node].clone(), share.secret_share.clone()))
+		.collect();
+	let is_reconstructable = match find_share_outliers(threshold, &candidates) {
+		Some(outliers) => {
+			for outlier in &outliers {
+				inconsistencies.get_mut(outlier).expect("inconsistencies are initialized for every node; qed")
+					.push(ShareInconsistency::SecretShare);
+			}
+			candidates.len() - outliers.len() >= threshold + 1
+		},
+		None => false,
+	};
+
+	Ok(ClusterShareReport {
+		threshold: threshold,
+		inconsistencies: inconsistencies,
+		is_reconstructable: is_reconstructable,
+	})
+}
+
+impl<'a> From<&'a DocumentKeyShare> for AuditedKeyShare {
+	fn from(share: &'a DocumentKeyShare) -> Self {
+		AuditedKeyShare {
+			author: share.author.clone(),
+			threshold: share.threshold,
+			id_numbers: share.id_numbers.clone(),
+			secret_share: share.secret_share.clone(),
+			common_point: share.common_point.clone(),
+			encrypted_point: share.encrypted_point.clone(),
+		}
+	}
+}
+
+/// Find value, reported by the most of nodes. The least value is selected if there are several such values.
+fn majority<T: Ord, I: Iterator<Item=T>>(values: I) -> T {
+	let mut counts = BTreeMap::new();
+	for value in values {
+		*counts.entry(value).or_insert(0) += 1;
+	}
+
+	let max_count = counts.values().cloned().max().expect("majority is called for non-empty set of values; qed");
+	counts.into_iter().find(|&(_, count)| count == max_count).map(|(value, _)| value)
+		.expect("max_count is taken from counts; qed")
+}
+
+/// Find nodes, which shares are not on the polynom, shared by the most of other nodes.
+/// Every window of threshold + 1 consequent nodes is tried as a reference set and the one with the least outliers is selected.
+/// Returns None if there are not enough shares to interpolate.
+fn find_share_outliers(threshold: usize, shares: &[(NodeId, Secret, Secret)]) -> Option<BTreeSet<NodeId>> {
+	if shares.len() < threshold + 1 {
+		return None;
+	}
+
+	let mut best_outliers: Option<BTreeSet<NodeId>> = None;
+	for base_start in 0..shares.len() {
+		let base: Vec<_> = (0..threshold + 1).map(|i| &shares[(base_start + i) % shares.len()]).collect();
+		let reference = match interpolate_secret(base.iter().cloned()) {
+			Ok(reference) => reference,
+			Err(_) => continue,
+		};
+
+		let outliers: BTreeSet<_> = shares.iter()
+			.filter(|share| !base.iter().any(|b| b.0 == share.0))
+			.filter(|share| {
+				let subset = base.iter().cloned().take(threshold).chain(::std::iter::once(*share));
+				interpolate_secret(subset).map(|secret| secret != reference).unwrap_or(true)
+			})
+			.map(|share| share.0.clone())
+			.collect();
+		if best_outliers.as_ref().map(|best| outliers.len() < best.len()).unwrap_or(true) {
+			best_outliers = Some(outliers);
+		}
+	}
+
+	// if every reference set is broken (i.e. duplicate id_numbers), treat all shares as invalid
+	Some(best_outliers.unwrap_or_else(|| shares.iter().map(|s| s.0.clone()).collect()))
+}
+
+/// Interpolate (up to sign, which only depends on the number of shares) joint secret from given shares.
+fn interpolate_secret<'a, I>(shares: I) -> Result<Secret, Error> where I: Iterator<Item=&'a (NodeId, Secret, Secret)> + Clone {
+	let shadows = shares.clone()
+		.map(|&(ref node, ref id_number, ref secret_share)| math::compute_node_shadow(secret_share, id_number, shares.clone()
+			.filter(|&&(ref other_node, _, _)| other_node != node)
+			.map(|&(_, ref other_id_number, _)| other_id_number)))
+		.collect::<Result<Vec<_>, _>>()?;
+	math::compute_secret_sum(shadows.iter())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+	use ethkey::{Random, Generator};
+	use key_server_cluster::{NodeId, DocumentKeyShare};
+	use key_server_cluster::math;
+	use super::{AuditedKeyShare, check_cluster_shares, ShareInconsistency};
+
+	fn prepare_shares(threshold: usize, num_nodes: usize) -> BTreeMap<NodeId, AuditedKeyShare> {
+		let author = Random.generate().unwrap().public().clone();
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let id_numbers: BTreeMap<_, _> = (0..num_nodes)
+			.map(|_| (Random.generate().unwrap().public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		id_numbers.iter().map(|(node, id_number)| (node.clone(), AuditedKeyShare::from(&DocumentKeyShare {
+			author: author.clone(),
+			threshold: threshold,
+			id_numbers: id_numbers.clone(),
+			secret_share: math::compute_polynom(&polynom, id_number).unwrap(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		}))).collect()
+	}
+
+	#[test]
+	fn consistent_shares_pass_audit() {
+		let shares = prepare_shares(1, 4);
+		let report = check_cluster_shares(&shares).unwrap();
+		assert!(report.is_consistent());
+		assert_eq!(report.threshold, 1);
+	}
+
+	#[test]
+	fn divergent_id_numbers_are_flagged() {
+		let mut shares = prepare_shares(1, 4);
+		let nodes: Vec<_> = shares.keys().cloned().collect();
+		shares.get_mut(&nodes[2]).unwrap().id_numbers.insert(nodes[0].clone(), math::generate_random_scalar().unwrap());
+
+		let report = check_cluster_shares(&shares).unwrap();
+		assert!(!report.is_consistent());
+		assert!(report.is_reconstructable);
+		assert_eq!(report.inconsistencies[&nodes[2]], vec![ShareInconsistency::IdNumberMismatch(nodes[0].clone())]);
+		assert!(report.inconsistencies.iter().filter(|&(n, _)| n != &nodes[2]).all(|(_, i)| i.is_empty()));
+	}
+
+	#[test]
+	fn divergent_secret_share_is_flagged() {
+		let mut shares = prepare_shares(1, 4);
+		let nodes: Vec<_> = shares.keys().cloned().collect();
+		shares.get_mut(&nodes[1]).unwrap().secret_share = math::generate_random_scalar().unwrap();
+
+		let report = check_cluster_shares(&shares).unwrap();
+		assert!(report.is_reconstructable);
+		assert_eq!(report.inconsistencies[&nodes[1]], vec![ShareInconsistency::SecretShare]);
+		assert!(report.inconsistencies.iter().filter(|&(n, _)| n != &nodes[1]).all(|(_, i)| i.is_empty()));
+	}
+}
@@ -0,0 +1,848 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use ethkey::{self, Secret, Signature};
+use ethcrypto::ecies::{encrypt_single_message, decrypt_single_message};
+use hash::keccak;
+use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, NodeKeyPair};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::message::{Message, ShareBootstrapMessage, BootstrapKeyShare, InitializeShareBootstrapSession,
+	ShareBootstrapKeySharesSaved, CommitShareBootstrap, ShareBootstrapCommitted, ShareBootstrapSessionError};
+
+/// Max number of keys, which are moved to the bootstrapped node by single share bootstrap session.
+const BATCH_KEYS: usize = 16;
+/// Max total number of key holders in single share bootstrap batch. All key shares of the batch
+/// are sent in single message, so its size is limited.
+const BATCH_ID_NUMBERS: usize = 128;
+
+/// Share bootstrap session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get share bootstrap session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed. Returns ids of keys, which shares have been moved to the new node.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<BTreeSet<SessionId>, Error>;
+}
+
+/// Share bootstrap session.
+/// Moves a batch of master node key shares to the new (i.e. cold-standby) node, which is not yet holding these keys.
+/// Brief overview:
+/// 1) initialization: master node selects keys of the batch, which shares could be moved (the new node is not yet
+///   holding the key && all other key holders are connected) && sends all selected key shares to the new node
+///   in a single message. Secret shares are encrypted with the key, agreed by master node && the new node
+/// 2) the new node checks that the session is authorized by operator of master node, saves all key shares
+///   && reports it back to master node
+/// 3) master node asks every other key holder to replace master node with the new node in key shares of the batch
+///   (the id number of every share is left untouched, so the shares stay valid)
+/// 4) when all key holders have reported, master node soft-removes its key shares of the batch
+/// The session is never rolled back. Instead, every node ignores changes, which it has already saved
+/// => the same batch could be moved again after the session has failed.
+pub struct SessionImpl {
+	/// Session metadata.
+	meta: SessionMeta,
+	/// This node key pair.
+	self_key_pair: Arc<NodeKeyPair>,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// This node key pair.
+	pub self_key_pair: Arc<NodeKeyPair>,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of share bootstrap session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// === Values, filled on master node ===
+	/// The new node.
+	new_node: Option<NodeId>,
+	/// Signature of master node operator.
+	admin_signature: Option<Signature>,
+	/// Key shares, which are moved to the new node.
+	key_shares: BTreeMap<SessionId, DocumentKeyShare>,
+	/// Key holders, which must replace master node with the new node.
+	commit_nodes: BTreeSet<NodeId>,
+	/// Key holders, which have replaced master node with the new node.
+	committed_nodes: BTreeSet<NodeId>,
+	/// === Values, filled on all nodes ===
+	/// Share bootstrap session result.
+	result: Option<Result<BTreeSet<SessionId>, Error>>,
+}
+
+/// Share bootstrap session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for the new node to save key shares.
+	WaitingForKeySharesReport,
+	/// Master node waits for every key holder to report that the new node is saved.
+	WaitingForCommitReports,
+	/// Key shares are moved.
+	Finished,
+	/// Failed to move key shares.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new share bootstrap session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		Ok(SessionImpl {
+			meta: params.meta,
+			self_key_pair: params.self_key_pair,
+			key_storage: params.key_storage,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				new_node: None,
+				admin_signature: None,
+				key_shares: BTreeMap::new(),
+				commit_nodes: BTreeSet::new(),
+				committed_nodes: BTreeSet::new(),
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	pub fn initialize(&self, admin_signature: Signature, new_node: NodeId, keys: BTreeSet<SessionId>, connected_nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if &new_node == self.node() {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+		if !connected_nodes.contains(&new_node) {
+			return Err(Error::NodeDisconnected);
+		}
+		self.check_admin_signature(&admin_signature)?;
+
+		// share could only be moved to the node, which is not yet holding the key
+		// && every key holder must learn about the new node => all of them must be connected
+		for key_id in keys {
+			if !self.key_storage.contains(&key_id) {
+				continue;
+			}
+
+			let key_share = self.key_storage.get(&key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+			if !key_share.id_numbers.contains_key(self.node()) || key_share.id_numbers.contains_key(&new_node)
+				|| key_share.id_numbers.keys().any(|n| !connected_nodes.contains(n)) {
+				continue;
+			}
+
+			data.key_shares.insert(key_id, key_share);
+		}
+
+		// update state
+		data.new_node = Some(new_node.clone());
+		data.admin_signature = Some(admin_signature.clone());
+		if data.key_shares.is_empty() {
+			data.state = SessionState::Finished;
+			data.result = Some(Ok(BTreeSet::new()));
+			self.completed.notify_all();
+			return Ok(());
+		}
+
+		// send key shares to the new node
+		let encryption_key = self.self_key_pair.compute_shared_key(&new_node)?;
+		let key_shares = data.key_shares.iter()
+			.map(|(key_id, key_share)| Ok(BootstrapKeyShare {
+				key_id: key_id.clone().into(),
+				author: key_share.author.clone().into(),
+				threshold: key_share.threshold,
+				id_numbers: moved_id_numbers(key_share.id_numbers.clone(), self.node(), &new_node)?.into_iter()
+					.map(|(k, v)| (k.into(), v.into()))
+					.collect(),
+				encrypted_secret_share: encrypt_single_message(encryption_key.public(), &**key_share.secret_share)?.into(),
+				common_point: key_share.common_point.clone().map(Into::into),
+				encrypted_point: key_share.encrypted_point.clone().map(Into::into),
+				refresh_id: key_share.refresh_id.clone().map(Into::into),
+			}))
+			.collect::<Result<Vec<_>, Error>>()?;
+		data.state = SessionState::WaitingForKeySharesReport;
+		self.cluster.send(&new_node, Message::ShareBootstrap(ShareBootstrapMessage::InitializeShareBootstrapSession(InitializeShareBootstrapSession {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			admin_signature: admin_signature.into(),
+			key_shares: key_shares,
+		})))
+	}
+
+	/// Process share bootstrap message.
+	pub fn process_message(&self, sender: &NodeId, message: &ShareBootstrapMessage) -> Result<(), Error> {
+		match message {
+			&ShareBootstrapMessage::InitializeShareBootstrapSession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&ShareBootstrapMessage::ShareBootstrapKeySharesSaved(ref message) =>
+				self.on_key_shares_saved(sender.clone(), message),
+			&ShareBootstrapMessage::CommitShareBootstrap(ref message) =>
+				self.on_commit(sender.clone(), message),
+			&ShareBootstrapMessage::ShareBootstrapCommitted(ref message) =>
+				self.on_committed(sender.clone(), message),
+			&ShareBootstrapMessage::ShareBootstrapSessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+		}
+	}
+
+	/// When key shares are received by the new node.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeShareBootstrapSession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		self.check_admin_signature(&message.admin_signature.clone().into())?;
+		if message.key_shares.is_empty() {
+			return Err(Error::InvalidMessage);
+		}
+
+		// check && decrypt all key shares before saving any of them
+		let decryption_key = self.self_key_pair.compute_shared_key(&sender)?;
+		let mut key_shares = BTreeMap::new();
+		for key_share in &message.key_shares {
+			let key_id: SessionId = key_share.key_id.clone().into();
+			let id_numbers: BTreeMap<NodeId, Secret> = key_share.id_numbers.iter()
+				.map(|(k, v)| (k.clone().into(), v.clone().into()))
+				.collect();
+			if !id_numbers.contains_key(self.node()) || id_numbers.contains_key(&sender)
+				|| key_share.threshold >= id_numbers.len() {
+				return Err(Error::InvalidMessage);
+			}
+			if self.key_storage.is_tombstoned(&key_id) {
+				return Err(Error::KeyDeleted);
+			}
+
+			let secret_share = decrypt_single_message(decryption_key.secret(), &key_share.encrypted_secret_share)?;
+			let key_share = DocumentKeyShare {
+				author: key_share.author.clone().into(),
+				threshold: key_share.threshold,
+				id_numbers: id_numbers,
+				secret_share: Secret::from_unsafe_slice(&secret_share)?,
+				common_point: key_share.common_point.clone().map(Into::into),
+				encrypted_point: key_share.encrypted_point.clone().map(Into::into),
+				refresh_id: key_share.refresh_id.clone().map(Into::into),
+				metadata: Default::default(),
+			};
+
+			// key share could be already saved by the previous attempt to move this batch
+			if self.key_storage.contains(&key_id) {
+				let stored_key_share = self.key_storage.get(&key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+				if stored_key_share.id_numbers != key_share.id_numbers || stored_key_share.secret_share != key_share.secret_share {
+					return Err(Error::InvalidNodesConfiguration);
+				}
+			}
+
+			if key_shares.insert(key_id, key_share).is_some() {
+				return Err(Error::InvalidMessage);
+			}
+		}
+
+		// save key shares && report back to master node
+		let keys = key_shares.keys().cloned().collect();
+		for (key_id, key_share) in key_shares {
+			if !self.key_storage.contains(&key_id) {
+				self.key_storage.insert(key_id, key_share).map_err(|e| Error::KeyStorage(e.into()))?;
+			}
+		}
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(keys));
+		self.completed.notify_all();
+
+		self.cluster.send(&sender, Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapKeySharesSaved(ShareBootstrapKeySharesSaved {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When the new node reports that key shares are saved.
+	pub fn on_key_shares_saved(&self, sender: NodeId, message: &ShareBootstrapKeySharesSaved) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForKeySharesReport {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if data.new_node.as_ref() != Some(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// ask every other key holder to replace master node with the new node in its key shares
+		let mut holders_keys: BTreeMap<NodeId, BTreeSet<SessionId>> = BTreeMap::new();
+		for (key_id, key_share) in &data.key_shares {
+			for node in key_share.id_numbers.keys().filter(|n| *n != self.node()) {
+				holders_keys.entry(node.clone()).or_insert_with(Default::default).insert(key_id.clone());
+			}
+		}
+
+		let admin_signature = data.admin_signature.clone().expect("admin_signature is filled in initialize on master node; qed");
+		data.commit_nodes = holders_keys.keys().cloned().collect();
+		data.state = SessionState::WaitingForCommitReports;
+		for (node, keys) in holders_keys {
+			self.cluster.send(&node, Message::ShareBootstrap(ShareBootstrapMessage::CommitShareBootstrap(CommitShareBootstrap {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				admin_signature: admin_signature.clone().into(),
+				new_node: sender.clone().into(),
+				keys: keys.into_iter().map(Into::into).collect(),
+			})))?;
+		}
+
+		self.try_complete(&mut *data);
+		Ok(())
+	}
+
+	/// When commit request is received by key holder.
+	pub fn on_commit(&self, sender: NodeId, message: &CommitShareBootstrap) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		self.check_admin_signature(&message.admin_signature.clone().into())?;
+		let new_node: NodeId = message.new_node.clone().into();
+		if &new_node == self.node() || message.keys.is_empty() {
+			return Err(Error::InvalidMessage);
+		}
+
+		// check all key shares before updating any of them
+		let mut key_shares = Vec::new();
+		for key_id in &message.keys {
+			let key_id: SessionId = key_id.clone().into();
+			let mut key_share = self.key_storage.get(&key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+
+			// master node could be already replaced by the previous attempt to move this batch
+			if !key_share.id_numbers.contains_key(&sender) && key_share.id_numbers.contains_key(&new_node) {
+				continue;
+			}
+
+			key_share.id_numbers = moved_id_numbers(key_share.id_numbers, &sender, &new_node)?;
+			key_shares.push((key_id, key_share));
+		}
+
+		// replace master node with the new node && report back to master node
+		for (key_id, key_share) in key_shares {
+			self.key_storage.update(key_id, key_share).map_err(|e| Error::KeyStorage(e.into()))?;
+		}
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(message.keys.iter().cloned().map(Into::into).collect()));
+		self.completed.notify_all();
+
+		self.cluster.send(&sender, Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapCommitted(ShareBootstrapCommitted {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When key holder reports that master node is replaced with the new node.
+	pub fn on_committed(&self, sender: NodeId, message: &ShareBootstrapCommitted) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForCommitReports {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.commit_nodes.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.committed_nodes.contains(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.committed_nodes.insert(sender);
+
+		self.try_complete(&mut *data);
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &ShareBootstrapSessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: share bootstrap session failed with error: {} from {}", self.node(), message.error, sender);
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return Ok(());
+		}
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Soft-remove key shares of master node, if all key holders have replaced it with the new node.
+	fn try_complete(&self, data: &mut SessionData) {
+		if data.state != SessionState::WaitingForCommitReports || data.committed_nodes != data.commit_nodes {
+			return;
+		}
+
+		data.state = SessionState::Finished;
+		data.result = Some(data.key_shares.keys()
+			.map(|key_id| self.key_storage.soft_remove(key_id).map(|_| key_id.clone()))
+			.collect::<Result<BTreeSet<_>, _>>()
+			.map_err(|e| Error::KeyStorage(e.into())));
+		self.completed.notify_all();
+	}
+
+	/// Check that the session is authorized by operator of master node.
+	fn check_admin_signature(&self, signature: &Signature) -> Result<(), Error> {
+		let master = &self.meta.master_node_id;
+		match ethkey::verify_public(master, signature, &keccak(&**master))? {
+			true => Ok(()),
+			false => Err(Error::AccessDenied),
+		}
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+/// Compute id of share bootstrap session, which is moving shares of given keys.
+pub fn batch_session_id<'a, I: IntoIterator<Item=&'a SessionId>>(keys: I) -> SessionId {
+	let mut keys_ids = Vec::new();
+	for key_id in keys {
+		keys_ids.extend_from_slice(&**key_id);
+	}
+	keccak(&keys_ids)
+}
+
+/// Select the next batch of keys (following given key), which shares could be moved by single share bootstrap session.
+/// Returns empty batch if there are no more keys.
+pub fn select_batch(key_storage: &KeyStorage, after: Option<&SessionId>) -> Result<BTreeSet<SessionId>, Error> {
+	let mut batch = BTreeSet::new();
+	let mut batch_id_numbers = 0;
+	for key in key_storage.iter(after) {
+		let (key_id, key_share) = key.map_err(|e| Error::KeyStorage(e.into()))?;
+		if !batch.is_empty() && (batch.len() == BATCH_KEYS
+			|| batch_id_numbers + key_share.id_numbers.len() > BATCH_ID_NUMBERS) {
+			break;
+		}
+
+		batch_id_numbers += key_share.id_numbers.len();
+		batch.insert(key_id);
+	}
+
+	Ok(batch)
+}
+
+/// Replace master node with the new node, keeping id number of the moved share.
+fn moved_id_numbers(mut id_numbers: BTreeMap<NodeId, Secret>, master: &NodeId, new_node: &NodeId) -> Result<BTreeMap<NodeId, Secret>, Error> {
+	if id_numbers.contains_key(new_node) {
+		return Err(Error::InvalidNodesConfiguration);
+	}
+
+	let id_number = id_numbers.remove(master).ok_or(Error::InvalidNodesConfiguration)?;
+	id_numbers.insert(new_node.clone(), id_number);
+	Ok(id_numbers)
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return;
+		}
+
+		// slave nodes only care about master node && master node cares about the new node && all holders of moved keys
+		let is_master = self.meta.self_node_id == self.meta.master_node_id;
+		if (!is_master && node != &self.meta.master_node_id)
+			|| (is_master && data.new_node.as_ref() != Some(node)
+				&& !data.key_shares.values().any(|ks| ks.id_numbers.contains_key(node))) {
+			return;
+		}
+
+		warn!("{}: share bootstrap session failed because {} connection has timeouted", self.node(), node);
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return;
+		}
+
+		warn!("{}: share bootstrap session failed with timeout", self.node());
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: share bootstrap session has been cancelled", self.node());
+
+		// there are no changes to undo on slave nodes => only master node must learn about cancellation
+		if self.meta.self_node_id != self.meta.master_node_id {
+			// do not bother processing send error, as we already processing error
+			let _ = self.cluster.send(&self.meta.master_node_id, Message::ShareBootstrap(ShareBootstrapMessage::ShareBootstrapSessionError(ShareBootstrapSessionError {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				error: Error::SessionCancelled.into(),
+				code: Some(Error::SessionCancelled.code().into()),
+			})));
+		}
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<BTreeSet<SessionId>, Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::{BTreeSet, BTreeMap};
+	use ethkey::{self, Random, Generator, KeyPair, Secret, Signature};
+	use hash::keccak;
+	use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, DummyKeyStorage, PlainNodeKeyPair};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::cluster_sessions::ClusterSession;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, ShareBootstrapMessage};
+	use super::{SessionImpl, SessionParams, SessionState, Session};
+
+	struct Node {
+		key_pair: KeyPair,
+		cluster: Arc<DummyCluster>,
+		key_storage: Arc<DummyKeyStorage>,
+		session: SessionImpl,
+	}
+
+	fn create_session(key_pair: &KeyPair, master_node_id: &NodeId, cluster: Arc<DummyCluster>, key_storage: Arc<DummyKeyStorage>, nonce: u64) -> SessionImpl {
+		SessionImpl::new(SessionParams {
+			meta: SessionMeta {
+				id: SessionId::default(),
+				master_node_id: master_node_id.clone(),
+				self_node_id: key_pair.public().clone(),
+				threshold: 0,
+			},
+			self_key_pair: Arc::new(PlainNodeKeyPair::new(key_pair.clone())),
+			key_storage: key_storage,
+			cluster: cluster,
+			nonce: nonce,
+		}).unwrap()
+	}
+
+	fn generate_key_share(key_id: SessionId, threshold: usize, holders: &[&Node]) {
+		let author = Random.generate().unwrap();
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let id_numbers: BTreeMap<NodeId, Secret> = holders.iter()
+			.map(|n| (n.key_pair.public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		for holder in holders {
+			holder.key_storage.insert(key_id.clone(), DocumentKeyShare {
+				author: author.public().clone(),
+				threshold: threshold,
+				id_numbers: id_numbers.clone(),
+				secret_share: math::compute_polynom(&polynom, &id_numbers[holder.key_pair.public()]).unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			}).unwrap();
+		}
+	}
+
+	/// Prepare key holders (the first one is master) && the new node (the last node).
+	/// The first key is held by all key holders && the second key is held by master node && the first key holder.
+	fn prepare_nodes(num_nodes: usize) -> Vec<Node> {
+		let key_pairs: Vec<_> = (0..num_nodes + 1).map(|_| Random.generate().unwrap()).collect();
+		let master_node_id = key_pairs[0].public().clone();
+		let nodes: Vec<_> = key_pairs.iter().map(|key_pair| {
+			let cluster = Arc::new(DummyCluster::new(key_pair.public().clone()));
+			for node in &key_pairs {
+				cluster.add_node(node.public().clone());
+			}
+
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			Node {
+				key_pair: key_pair.clone(),
+				cluster: cluster.clone(),
+				key_storage: key_storage.clone(),
+				session: create_session(key_pair, &master_node_id, cluster, key_storage, 0),
+			}
+		}).collect();
+
+		generate_key_share(SessionId::from(1), 1, &nodes[0..num_nodes].iter().collect::<Vec<_>>());
+		generate_key_share(SessionId::from(2), 1, &[&nodes[0], &nodes[1]]);
+		nodes
+	}
+
+	fn restart_sessions(nodes: Vec<Node>, nonce: u64) -> Vec<Node> {
+		let master_node_id = nodes[0].key_pair.public().clone();
+		nodes.into_iter().map(|node| Node {
+			session: create_session(&node.key_pair, &master_node_id, node.cluster.clone(), node.key_storage.clone(), nonce),
+			key_pair: node.key_pair,
+			cluster: node.cluster,
+			key_storage: node.key_storage,
+		}).collect()
+	}
+
+	fn admin_signature(nodes: &[Node]) -> Signature {
+		ethkey::sign(nodes[0].key_pair.secret(), &keccak(&**nodes[0].key_pair.public())).unwrap()
+	}
+
+	fn all_keys() -> BTreeSet<SessionId> {
+		vec![SessionId::from(1), SessionId::from(2)].into_iter().collect()
+	}
+
+	fn all_nodes(nodes: &[Node]) -> BTreeSet<NodeId> {
+		nodes.iter().map(|n| n.session.node().clone()).collect()
+	}
+
+	fn do_messages_exchange_until<F>(nodes: &[Node], mut cond: F) -> Result<(), Error> where F: FnMut(&NodeId, &NodeId, &Message) -> bool {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			if cond(&from, &to, &message) {
+				break;
+			}
+
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::ShareBootstrap(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn key_shares_are_moved_to_new_node() {
+		let nodes = prepare_nodes(3);
+		let key1 = SessionId::from(1);
+		let old_share1 = nodes[0].key_storage.get(&key1).unwrap();
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(admin_signature(&nodes), new_node.clone(), all_keys(), all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+		assert_eq!(nodes[0].session.wait(None), Ok(all_keys()));
+
+		// master node has no key shares && the new node has master' key shares
+		assert!(all_keys().iter().all(|k| !nodes[0].key_storage.contains(k)));
+		let new_share1 = nodes[3].key_storage.get(&key1).unwrap();
+		assert_eq!(new_share1.secret_share, old_share1.secret_share);
+		assert_eq!(new_share1.id_numbers[&new_node], old_share1.id_numbers[nodes[0].session.node()]);
+
+		// all key holders are aware of the new node
+		assert_eq!(nodes[1].key_storage.get(&key1).unwrap().id_numbers, new_share1.id_numbers);
+		assert_eq!(nodes[2].key_storage.get(&key1).unwrap().id_numbers, new_share1.id_numbers);
+		assert_eq!(nodes[1].key_storage.get(&SessionId::from(2)).unwrap().id_numbers,
+			nodes[3].key_storage.get(&SessionId::from(2)).unwrap().id_numbers);
+		assert!(!nodes[2].key_storage.contains(&SessionId::from(2)));
+
+		// master node keeps the moved shares until these are purged
+		nodes[0].key_storage.restore_removed(&key1).unwrap();
+		assert_eq!(nodes[0].key_storage.get(&key1), Ok(old_share1));
+	}
+
+	#[test]
+	fn keys_with_disconnected_holders_are_skipped() {
+		let nodes = prepare_nodes(3);
+		let new_node = nodes[3].session.node().clone();
+		let disconnected_node = nodes[2].session.node().clone();
+		let connected_nodes = all_nodes(&nodes).into_iter().filter(|n| n != &disconnected_node).collect();
+
+		nodes[0].session.initialize(admin_signature(&nodes), new_node, all_keys(), connected_nodes).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert_eq!(nodes[0].session.wait(None), Ok(vec![SessionId::from(2)].into_iter().collect()));
+		assert!(nodes[0].key_storage.contains(&SessionId::from(1)));
+		assert!(!nodes[3].key_storage.contains(&SessionId::from(1)));
+	}
+
+	#[test]
+	fn session_completes_when_there_are_no_keys_to_move() {
+		let nodes = prepare_nodes(3);
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(admin_signature(&nodes), new_node, vec![SessionId::from(3)].into_iter().collect(), all_nodes(&nodes)).unwrap();
+		assert_eq!(nodes[0].session.wait(None), Ok(BTreeSet::new()));
+		assert!(nodes[0].cluster.take_message().is_none());
+	}
+
+	#[test]
+	fn new_node_refuses_key_shares_when_admin_signature_is_wrong() {
+		let nodes = prepare_nodes(3);
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(admin_signature(&nodes), new_node, all_keys(), all_nodes(&nodes)).unwrap();
+		let (_, message) = nodes[0].cluster.take_message().unwrap();
+		let mut message = match message {
+			Message::ShareBootstrap(ShareBootstrapMessage::InitializeShareBootstrapSession(message)) => message,
+			_ => unreachable!(),
+		};
+		message.admin_signature = ethkey::sign(Random.generate().unwrap().secret(), &keccak(&**nodes[0].key_pair.public())).unwrap().into();
+		assert_eq!(nodes[3].session.process_message(nodes[0].session.node(),
+			&ShareBootstrapMessage::InitializeShareBootstrapSession(message)), Err(Error::AccessDenied));
+		assert!(!nodes[3].key_storage.contains(&SessionId::from(1)));
+	}
+
+	#[test]
+	fn failed_batch_is_completed_when_retried() {
+		let nodes = prepare_nodes(3);
+		let new_node = nodes[3].session.node().clone();
+		let lost_commit_node = nodes[2].session.node().clone();
+
+		// commit request to the second key holder is lost, while the new node && the first key holder save their changes
+		nodes[0].session.initialize(admin_signature(&nodes), new_node.clone(), all_keys(), all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, to, message| match *message {
+			Message::ShareBootstrap(ShareBootstrapMessage::CommitShareBootstrap(_)) => to == &lost_commit_node,
+			_ => false,
+		}).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		nodes[0].session.on_node_timeout(&lost_commit_node);
+		assert_eq!(nodes[0].session.wait(None), Err(Error::NodeDisconnected));
+		assert!(nodes[0].key_storage.contains(&SessionId::from(1)));
+
+		// the same batch is moved again
+		let nodes = restart_sessions(nodes, 1);
+		nodes[0].session.initialize(admin_signature(&nodes), new_node.clone(), all_keys(), all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert_eq!(nodes[0].session.wait(None), Ok(all_keys()));
+		let new_share1 = nodes[3].key_storage.get(&SessionId::from(1)).unwrap();
+		assert!(nodes[1..3].iter().all(|n| n.key_storage.get(&SessionId::from(1)).unwrap().id_numbers == new_share1.id_numbers));
+	}
+
+	#[test]
+	fn share_bootstrap_message_fails_when_nonce_is_wrong() {
+		let nodes = prepare_nodes(3);
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(admin_signature(&nodes), new_node, all_keys(), all_nodes(&nodes)).unwrap();
+		let (_, message) = nodes[0].cluster.take_message().unwrap();
+		let mut message = match message {
+			Message::ShareBootstrap(ShareBootstrapMessage::InitializeShareBootstrapSession(message)) => message,
+			_ => unreachable!(),
+		};
+		message.session_nonce = 10;
+		assert_eq!(nodes[3].session.process_message(nodes[0].session.node(),
+			&ShareBootstrapMessage::InitializeShareBootstrapSession(message)), Err(Error::ReplayProtection));
+	}
+}
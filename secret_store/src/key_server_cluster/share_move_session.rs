@@ -0,0 +1,1090 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use serde_json;
+use bigint::hash::H256;
+use bytes::Bytes;
+use hash::keccak;
+use ethkey::Secret;
+use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::message::{Message, ShareMoveMessage, InitializeShareMoveSession, ConfirmShareMoveInitialization,
+	ShareMoveData, ShareMoveDataChunk, CommitShareMove, ShareMoveCompleted, ShareMoveSessionError, ShareMoveRollback};
+
+/// Serialized ShareMoveData message, which is larger than SHARE_MOVE_DATA_CHUNK_SIZE bytes, is sent in chunks of
+/// this size. Chunk is hex-encoded on the wire => it fits into a single message together with the envelope.
+pub const SHARE_MOVE_DATA_CHUNK_SIZE: usize = 16 * 1024;
+/// Max number of chunks of ShareMoveData message, which the new owner of the share is ready to reassemble.
+const MAX_SHARE_MOVE_DATA_CHUNKS: usize = 1024;
+
+/// Share move session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get share move session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error>;
+}
+
+/// Share move session.
+/// Moves key share of the master node to the node, which is not yet holding the key.
+/// Brief overview:
+/// 1) initialization: master node checks that all other key holders && the new owner of the share are connected
+///   && asks every one of them to confirm the move
+/// 2) every key holder checks that the new owner is not yet holding the key && confirms the move; the new owner checks
+///   that it is the target of the move && confirms it
+/// 3) when all nodes have confirmed the move, master node sends its key share to the new owner (large key share is
+///   sent in chunks, which are reassembled && checked against the hash of the whole share by the new owner)
+/// 4) the new owner saves the key share && reports it back to the master node
+/// 5) master node asks every other key holder to replace master node with the new owner in the key share (the id number
+///   of the share is left untouched, so the share stays valid)
+/// 6) when all key holders have reported, master node soft-removes its key share (it is kept in the key storage
+///   until purged, so that the key share could be restored if the move has been made by mistake)
+/// Master node keeps its key share until all key holders have replaced it with the new owner.
+/// If session fails on master node before that, master node asks every participant to roll back the move, so that
+/// the new owner removes the received key share && key holders return to the pre-session id numbers.
+pub struct SessionImpl {
+	/// Session metadata. Session id is the id of moved key.
+	meta: SessionMeta,
+	/// Key share. None on the new owner of the share.
+	key_share: Option<DocumentKeyShare>,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Key share. None on the new owner of the share.
+	pub key_share: Option<DocumentKeyShare>,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of share move session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// === Values, filled on all nodes ===
+	/// The new owner of the moved share.
+	new_node: Option<NodeId>,
+	/// === Values, filled on master node ===
+	/// Nodes, which have confirmed the move (including this node).
+	confirmed_nodes: BTreeSet<NodeId>,
+	/// Nodes, which have reported completion of the move.
+	completed_nodes: BTreeSet<NodeId>,
+	/// === Values, filled on the new owner ===
+	/// Chunks of the moved key share, received so far.
+	chunks: Option<ReceivedChunks>,
+	/// === Values, filled on all nodes ===
+	/// Share move session result.
+	result: Option<Result<(), Error>>,
+}
+
+/// Chunks of ShareMoveData message, received by the new owner of the share.
+#[derive(Debug)]
+struct ReceivedChunks {
+	/// Total number of chunks.
+	count: usize,
+	/// Hash of the whole serialized message.
+	payload_hash: H256,
+	/// Received chunks.
+	chunks: BTreeMap<usize, Bytes>,
+}
+
+/// Share move session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	// === Initialization states ===
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for every key holder && the new owner to confirm the move.
+	WaitingForConfirmations,
+	/// The new owner waits for the key share from master node.
+	WaitingForKeyShare,
+	/// Key holder waits for commit request from master node.
+	WaitingForCommit,
+	/// Master node waits for the new owner to save the key share.
+	WaitingForNewNodeReport,
+	/// Master node waits for every key holder to report that the new owner is saved.
+	WaitingForCommitReports,
+
+	// === Final states of the session ===
+	/// Key share is moved.
+	Finished,
+	/// Failed to move key share.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new share move session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		match params.key_share {
+			Some(ref key_share) => if !key_share.id_numbers.contains_key(&params.meta.self_node_id)
+				|| !key_share.id_numbers.contains_key(&params.meta.master_node_id) {
+				return Err(Error::InvalidNodesConfiguration);
+			},
+			// master node is moving its own share => it must have one
+			None => if params.meta.self_node_id == params.meta.master_node_id {
+				return Err(Error::InvalidNodesConfiguration);
+			},
+		}
+
+		Ok(SessionImpl {
+			meta: params.meta,
+			key_share: params.key_share,
+			key_storage: params.key_storage,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				new_node: None,
+				confirmed_nodes: BTreeSet::new(),
+				completed_nodes: BTreeSet::new(),
+				chunks: None,
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	pub fn initialize(&self, new_node: NodeId, connected_nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		// check that key share has not been modified since session has been created
+		// metadata is local to this node && is updated on every key access => only key material is compared
+		let key_share = self.key_share.as_ref().expect("key_share is checked in constructor on master node; qed");
+		let actual_key_share = self.key_storage.get(&self.meta.id).map_err(|e| Error::KeyStorage(e.into()))?;
+		if key_share.version() != actual_key_share.version()
+			|| key_share.threshold != actual_key_share.threshold
+			|| key_share.secret_share != actual_key_share.secret_share
+			|| key_share.common_point != actual_key_share.common_point
+			|| key_share.encrypted_point != actual_key_share.encrypted_point {
+			return Err(Error::StaleKeyShare);
+		}
+
+		// share could only be moved to the node, which is not yet holding the key
+		if key_share.id_numbers.contains_key(&new_node) {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+
+		// every key holder must learn about the new owner => all of them must be connected
+		if !connected_nodes.contains(&new_node)
+			|| key_share.id_numbers.keys().any(|n| n != self.node() && !connected_nodes.contains(n)) {
+			return Err(Error::NodeDisconnected);
+		}
+
+		// update state
+		data.new_node = Some(new_node.clone());
+		data.confirmed_nodes.insert(self.node().clone());
+		data.state = SessionState::WaitingForConfirmations;
+
+		// start initialization
+		for node in key_share.id_numbers.keys().filter(|n| *n != self.node()).chain(::std::iter::once(&new_node)) {
+			self.cluster.send(node, Message::ShareMove(ShareMoveMessage::InitializeShareMoveSession(InitializeShareMoveSession {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				new_node: new_node.clone().into(),
+			})))?;
+		}
+
+		Ok(())
+	}
+
+	/// Process share move message.
+	pub fn process_message(&self, sender: &NodeId, message: &ShareMoveMessage) -> Result<(), Error> {
+		match message {
+			&ShareMoveMessage::InitializeShareMoveSession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&ShareMoveMessage::ConfirmShareMoveInitialization(ref message) =>
+				self.on_confirm_initialization(sender.clone(), message),
+			&ShareMoveMessage::ShareMoveData(ref message) =>
+				self.on_share_move_data(sender.clone(), message),
+			&ShareMoveMessage::CommitShareMove(ref message) =>
+				self.on_commit(sender.clone(), message),
+			&ShareMoveMessage::ShareMoveCompleted(ref message) =>
+				self.on_share_move_completed(sender.clone(), message),
+			&ShareMoveMessage::ShareMoveSessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+			&ShareMoveMessage::ShareMoveRollback(ref message) =>
+				self.on_rollback(sender.clone(), message),
+			&ShareMoveMessage::ShareMoveDataChunk(ref message) =>
+				self.on_share_move_data_chunk(sender.clone(), message),
+		}
+	}
+
+	/// When session initialization message is received.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeShareMoveSession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		let new_node: NodeId = message.new_node.clone().into();
+		data.state = match self.key_share.as_ref() {
+			Some(key_share) => {
+				if key_share.id_numbers.contains_key(&new_node) {
+					return Err(Error::InvalidNodesConfiguration);
+				}
+				SessionState::WaitingForCommit
+			},
+			None => {
+				if &new_node != self.node() {
+					return Err(Error::InvalidNodesConfiguration);
+				}
+				SessionState::WaitingForKeyShare
+			},
+		};
+		data.new_node = Some(new_node);
+
+		// confirm move
+		self.cluster.send(&sender, Message::ShareMove(ShareMoveMessage::ConfirmShareMoveInitialization(ConfirmShareMoveInitialization {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When session initialization confirmation message is received.
+	pub fn on_confirm_initialization(&self, sender: NodeId, message: &ConfirmShareMoveInitialization) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForConfirmations {
+			return Err(Error::InvalidStateForRequest);
+		}
+		let key_share = self.key_share.as_ref().expect("key_share is checked in constructor on master node; qed");
+		if !key_share.id_numbers.contains_key(&sender) && data.new_node.as_ref() != Some(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.confirmed_nodes.contains(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.confirmed_nodes.insert(sender);
+
+		// wait until all key holders && the new owner have confirmed the move
+		if data.confirmed_nodes.len() != key_share.id_numbers.len() + 1 {
+			return Ok(());
+		}
+
+		// send key share to the new owner
+		let new_node = data.new_node.clone().expect("new_node is filled in initialize on master node; qed");
+		data.state = SessionState::WaitingForNewNodeReport;
+		self.send_share_move_data(&new_node, ShareMoveData {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			author: key_share.author.clone().into(),
+			threshold: key_share.threshold,
+			id_numbers: self.moved_id_numbers(key_share.id_numbers.clone(), &new_node)?.into_iter()
+				.map(|(k, v)| (k.into(), v.into()))
+				.collect(),
+			secret_share: key_share.secret_share.clone().into(),
+			common_point: key_share.common_point.clone().map(Into::into),
+			encrypted_point: key_share.encrypted_point.clone().map(Into::into),
+			refresh_id: key_share.refresh_id.clone().map(Into::into),
+		})
+	}
+
+	/// When moved key share is received.
+	pub fn on_share_move_data(&self, sender: NodeId, message: &ShareMoveData) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForKeyShare || data.chunks.is_some() {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		self.save_moved_share(&mut *data, sender, message)
+	}
+
+	/// When chunk of moved key share is received.
+	pub fn on_share_move_data_chunk(&self, sender: NodeId, message: &ShareMoveDataChunk) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForKeyShare {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if message.count < 2 || message.count > MAX_SHARE_MOVE_DATA_CHUNKS || message.index >= message.count
+			|| message.data.is_empty() || message.data.len() > SHARE_MOVE_DATA_CHUNK_SIZE {
+			return Err(Error::InvalidMessage);
+		}
+
+		// all chunks must belong to the same message
+		if data.chunks.is_none() {
+			data.chunks = Some(ReceivedChunks {
+				count: message.count,
+				payload_hash: message.payload_hash.clone().into(),
+				chunks: BTreeMap::new(),
+			});
+		}
+		let payload = {
+			let chunks = data.chunks.as_mut().expect("filled above; qed");
+			if chunks.count != message.count || chunks.payload_hash != *message.payload_hash
+				|| chunks.chunks.contains_key(&message.index) {
+				return Err(Error::InvalidMessage);
+			}
+
+			chunks.chunks.insert(message.index, message.data.clone().into());
+			if chunks.chunks.len() != chunks.count {
+				return Ok(());
+			}
+
+			let payload: Bytes = chunks.chunks.values().flat_map(|chunk| chunk.iter().cloned()).collect();
+			if keccak(&payload) != chunks.payload_hash {
+				return Err(Error::InvalidMessage);
+			}
+			payload
+		};
+
+		let message: ShareMoveData = serde_json::from_slice(&payload).map_err(|e| Error::Serde(e.to_string()))?;
+		if self.meta.id != *message.session {
+			return Err(Error::InvalidMessage);
+		}
+		self.check_nonce(message.session_nonce)?;
+
+		self.save_moved_share(&mut *data, sender, &message)
+	}
+
+	/// Save moved key share on the new owner && report back to master node.
+	fn save_moved_share(&self, data: &mut SessionData, sender: NodeId, message: &ShareMoveData) -> Result<(), Error> {
+		let id_numbers: BTreeMap<NodeId, Secret> = message.id_numbers.iter()
+			.map(|(k, v)| (k.clone().into(), v.clone().into()))
+			.collect();
+		if !id_numbers.contains_key(self.node()) || id_numbers.contains_key(&sender)
+			|| message.threshold >= id_numbers.len() {
+			return Err(Error::InvalidMessage);
+		}
+
+		// save key share && report back to master node
+		self.key_storage.insert(self.meta.id.clone(), DocumentKeyShare {
+			author: message.author.clone().into(),
+			threshold: message.threshold,
+			id_numbers: id_numbers,
+			secret_share: message.secret_share.clone().into(),
+			common_point: message.common_point.clone().map(Into::into),
+			encrypted_point: message.encrypted_point.clone().map(Into::into),
+			refresh_id: message.refresh_id.clone().map(Into::into),
+			metadata: Default::default(),
+		}).map_err(|e| Error::KeyStorage(e.into()))?;
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(()));
+		self.completed.notify_all();
+
+		self.cluster.send(&sender, Message::ShareMove(ShareMoveMessage::ShareMoveCompleted(ShareMoveCompleted {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When commit request is received.
+	pub fn on_commit(&self, sender: NodeId, message: &CommitShareMove) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForCommit {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// replace master node with the new owner && report back to master node
+		let new_node = data.new_node.clone().expect("new_node is filled in on_initialize_session on key holders; qed");
+		let mut key_share = self.key_storage.get(&self.meta.id).map_err(|e| Error::KeyStorage(e.into()))?;
+		key_share.id_numbers = self.moved_id_numbers(key_share.id_numbers, &new_node)?;
+		self.key_storage.update(self.meta.id.clone(), key_share).map_err(|e| Error::KeyStorage(e.into()))?;
+
+		data.state = SessionState::Finished;
+		data.result = Some(Ok(()));
+		self.completed.notify_all();
+
+		self.cluster.send(&sender, Message::ShareMove(ShareMoveMessage::ShareMoveCompleted(ShareMoveCompleted {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When share move completion is reported.
+	pub fn on_share_move_completed(&self, sender: NodeId, message: &ShareMoveCompleted) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id {
+			return Err(Error::InvalidStateForRequest);
+		}
+		let key_share = self.key_share.as_ref().expect("key_share is checked in constructor on master node; qed");
+		match data.state {
+			// the new owner has saved the key share => ask other key holders to replace master node with the new owner
+			SessionState::WaitingForNewNodeReport => {
+				if data.new_node.as_ref() != Some(&sender) {
+					return Err(Error::InvalidNodeForRequest);
+				}
+
+				data.completed_nodes.insert(sender);
+				data.state = SessionState::WaitingForCommitReports;
+				for node in key_share.id_numbers.keys().filter(|n| *n != self.node()) {
+					self.cluster.send(node, Message::ShareMove(ShareMoveMessage::CommitShareMove(CommitShareMove {
+						session: self.meta.id.clone().into(),
+						session_nonce: self.nonce,
+					})))?;
+				}
+			},
+			SessionState::WaitingForCommitReports => {
+				if !key_share.id_numbers.contains_key(&sender) {
+					return Err(Error::InvalidNodeForRequest);
+				}
+				if data.completed_nodes.contains(&sender) {
+					return Err(Error::InvalidMessage);
+				}
+
+				data.completed_nodes.insert(sender);
+			},
+			_ => return Err(Error::InvalidStateForRequest),
+		}
+
+		self.try_complete(&mut *data);
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &ShareMoveSessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: share move session failed with error: {} from {}", self.node(), message.error, sender);
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return Ok(());
+		}
+
+		data.state = SessionState::Failed;
+		let error = Error::remote(message.code, message.error.clone());
+		data.result = Some(Err(error.clone()));
+		self.completed.notify_all();
+		self.broadcast_rollback(&*data, error);
+
+		Ok(())
+	}
+
+	/// When master node asks to undo the move.
+	pub fn on_rollback(&self, sender: NodeId, message: &ShareMoveRollback) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.state == SessionState::Failed {
+			return Ok(());
+		}
+
+		// changes are possibly already saved (even if session is finished on this node) => undo them
+		warn!("{}: share move session is rolled back with error: {} from {}", self.node(), message.error, sender);
+		rollback(&*self.key_storage, self.node(), &sender, &self.meta.id, message.id_numbers.iter()
+			.map(|(k, v)| (k.clone().into(), v.clone().into()))
+			.collect())?;
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Send moved key share to the new owner. Key share is split into chunks if it is too large to be sent in a single message.
+	fn send_share_move_data(&self, new_node: &NodeId, message: ShareMoveData) -> Result<(), Error> {
+		let payload = serde_json::to_vec(&message).map_err(|e| Error::Serde(e.to_string()))?;
+		if payload.len() <= SHARE_MOVE_DATA_CHUNK_SIZE {
+			return self.cluster.send(new_node, Message::ShareMove(ShareMoveMessage::ShareMoveData(message)));
+		}
+
+		let payload_hash = keccak(&payload);
+		let count = (payload.len() + SHARE_MOVE_DATA_CHUNK_SIZE - 1) / SHARE_MOVE_DATA_CHUNK_SIZE;
+		for (index, chunk) in payload.chunks(SHARE_MOVE_DATA_CHUNK_SIZE).enumerate() {
+			self.cluster.send(new_node, Message::ShareMove(ShareMoveMessage::ShareMoveDataChunk(ShareMoveDataChunk {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				index: index,
+				count: count,
+				payload_hash: payload_hash.clone().into(),
+				data: chunk.to_vec().into(),
+			})))?;
+		}
+
+		Ok(())
+	}
+
+	/// Ask all participants to undo the move. Does nothing on slave nodes.
+	fn broadcast_rollback(&self, data: &SessionData, error: Error) {
+		let key_share = match self.key_share.as_ref() {
+			Some(key_share) if self.meta.self_node_id == self.meta.master_node_id => key_share,
+			_ => return,
+		};
+
+		// there could be no participants with saved changes, but it is cheaper to roll back everywhere than to track them
+		for node in key_share.id_numbers.keys().filter(|n| *n != self.node()).chain(data.new_node.iter()) {
+			// do not bother processing send error, as we already processing error
+			let _ = self.cluster.send(node, Message::ShareMove(ShareMoveMessage::ShareMoveRollback(ShareMoveRollback {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				id_numbers: key_share.id_numbers.iter()
+					.map(|(k, v)| (k.clone().into(), v.clone().into()))
+					.collect(),
+				error: error.clone().into(),
+				code: Some(error.code().into()),
+			})));
+		}
+	}
+
+	/// Soft-remove key share of master node, if all key holders have replaced it with the new owner.
+	fn try_complete(&self, data: &mut SessionData) {
+		let key_share = self.key_share.as_ref().expect("try_complete is only called on master node; key_share is checked in constructor on master node; qed");
+		if data.state != SessionState::WaitingForCommitReports
+			|| data.completed_nodes.len() != key_share.id_numbers.len() {
+			return;
+		}
+
+		data.state = SessionState::Finished;
+		data.result = Some(self.key_storage.soft_remove(&self.meta.id).map_err(|e| Error::KeyStorage(e.into())));
+		self.completed.notify_all();
+	}
+
+	/// Replace master node with the new owner, keeping id number of the moved share.
+	fn moved_id_numbers(&self, mut id_numbers: BTreeMap<NodeId, Secret>, new_node: &NodeId) -> Result<BTreeMap<NodeId, Secret>, Error> {
+		let id_number = id_numbers.remove(&self.meta.master_node_id).ok_or(Error::InvalidNodesConfiguration)?;
+		id_numbers.insert(new_node.clone(), id_number);
+		Ok(id_numbers)
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+/// Undo the move of master node key share on this node. Could be called when the session is already completed && forgotten.
+/// Only changes, made by the share move session, started by given master node, are undone:
+/// 1) the new owner of the share removes its key share
+/// 2) key holder replaces the new owner with master node in the key share
+/// 3) node, which has not saved any changes, is left untouched
+pub fn rollback(key_storage: &KeyStorage, self_node_id: &NodeId, master_node_id: &NodeId, key_id: &SessionId, id_numbers: BTreeMap<NodeId, Secret>) -> Result<(), Error> {
+	if !key_storage.contains(key_id) {
+		return Ok(());
+	}
+
+	let mut key_share = key_storage.get(key_id).map_err(|e| Error::KeyStorage(e.into()))?;
+	if key_share.id_numbers == id_numbers {
+		return Ok(());
+	}
+
+	// the only change, allowed to be undone, is the replacement of master node with the new owner (keeping the id number)
+	let master_id_number = id_numbers.get(master_node_id).ok_or(Error::InvalidMessage)?;
+	let new_node = key_share.id_numbers.iter()
+		.find(|&(_, id_number)| id_number == master_id_number)
+		.map(|(node, _)| node.clone())
+		.ok_or(Error::InvalidMessage)?;
+	let mut moved_id_numbers = id_numbers.clone();
+	moved_id_numbers.remove(master_node_id);
+	moved_id_numbers.insert(new_node.clone(), master_id_number.clone());
+	if moved_id_numbers != key_share.id_numbers {
+		return Err(Error::InvalidMessage);
+	}
+
+	if &new_node == self_node_id {
+		key_storage.remove(key_id).map_err(|e| Error::KeyStorage(e.into()))
+	} else {
+		key_share.id_numbers = id_numbers;
+		key_storage.update(key_id.clone(), key_share).map_err(|e| Error::KeyStorage(e.into()))
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return;
+		}
+
+		// slave nodes only care about master node && master node cares about all nodes, participating in the move
+		let is_master = self.meta.self_node_id == self.meta.master_node_id;
+		if (!is_master && node != &self.meta.master_node_id)
+			|| (is_master && data.new_node.as_ref() != Some(node)
+				&& !self.key_share.as_ref().map(|ks| ks.id_numbers.contains_key(node)).unwrap_or(false)) {
+			return;
+		}
+
+		warn!("{}: share move session failed because {} connection has timeouted", self.node(), node);
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+		self.broadcast_rollback(&*data, Error::NodeDisconnected);
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		if data.state == SessionState::Finished || data.state == SessionState::Failed {
+			return;
+		}
+
+		warn!("{}: share move session failed with timeout", self.node());
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+		self.broadcast_rollback(&*data, Error::NodeDisconnected);
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: share move session has been cancelled", self.node());
+
+		if self.meta.self_node_id == self.meta.master_node_id {
+			self.broadcast_rollback(&*data, Error::SessionCancelled);
+		} else {
+			// do not bother processing send error, as we already processing error
+			let _ = self.cluster.send(&self.meta.master_node_id, Message::ShareMove(ShareMoveMessage::ShareMoveSessionError(ShareMoveSessionError {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				error: Error::SessionCancelled.into(),
+				code: Some(Error::SessionCancelled.code().into()),
+			})));
+		}
+
+		data.state = SessionState::Failed;
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::{BTreeSet, BTreeMap};
+	use ethkey::{Random, Generator, Secret};
+	use key_server_cluster::{Error, ErrorCode, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, KeyMetadata, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::cluster_sessions::ClusterSession;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, ShareMoveMessage, ShareMoveSessionError};
+	use super::{SessionImpl, SessionParams, SessionState, Session, rollback};
+
+	struct Node {
+		cluster: Arc<DummyCluster>,
+		key_storage: Arc<DummyKeyStorage>,
+		session: SessionImpl,
+	}
+
+	/// Prepare key holders (the first one is master) && the new owner of the moved share (the last node).
+	fn prepare_nodes(threshold: usize, num_nodes: usize) -> Vec<Node> {
+		let author = Random.generate().unwrap();
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let id_numbers: BTreeMap<NodeId, Secret> = (0..num_nodes)
+			.map(|_| (Random.generate().unwrap().public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		let new_node_id = Random.generate().unwrap().public().clone();
+		let master_node_id = id_numbers.keys().nth(0).cloned().unwrap();
+		let all_nodes: Vec<_> = id_numbers.keys().cloned().chain(::std::iter::once(new_node_id.clone())).collect();
+		all_nodes.iter().map(|node_id| {
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			let key_share = id_numbers.get(node_id).map(|id_number| DocumentKeyShare {
+				author: author.public().clone(),
+				threshold: threshold,
+				id_numbers: id_numbers.clone(),
+				secret_share: math::compute_polynom(&polynom, id_number).unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			});
+			if let Some(ref key_share) = key_share {
+				key_storage.insert(SessionId::default(), key_share.clone()).unwrap();
+			}
+
+			let cluster = Arc::new(DummyCluster::new(node_id.clone()));
+			for node in &all_nodes {
+				cluster.add_node(node.clone());
+			}
+
+			let session = SessionImpl::new(SessionParams {
+				meta: SessionMeta {
+					id: SessionId::default(),
+					master_node_id: master_node_id.clone(),
+					self_node_id: node_id.clone(),
+					threshold: threshold,
+				},
+				key_share: key_share,
+				key_storage: key_storage.clone(),
+				cluster: cluster.clone(),
+				nonce: 0,
+			}).unwrap();
+
+			Node {
+				cluster: cluster,
+				key_storage: key_storage,
+				session: session,
+			}
+		}).collect()
+	}
+
+	fn all_nodes(nodes: &[Node]) -> BTreeSet<NodeId> {
+		nodes.iter().map(|n| n.session.node().clone()).collect()
+	}
+
+	fn do_messages_exchange_until<F>(nodes: &[Node], mut cond: F) -> Result<(), Error> where F: FnMut(&NodeId, &NodeId, &Message) -> bool {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			if cond(&from, &to, &message) {
+				break;
+			}
+
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::ShareMove(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn share_is_moved_to_new_node() {
+		let nodes = prepare_nodes(1, 3);
+		let old_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(new_node.clone(), all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+		assert_eq!(nodes[0].session.wait(None), Ok(()));
+
+		// master node has no key share && the new owner has master' key share
+		assert!(!nodes[0].key_storage.contains(&SessionId::default()));
+		let new_share = nodes[3].key_storage.get(&SessionId::default()).unwrap();
+		assert_eq!(new_share.secret_share, old_share.secret_share);
+		assert_eq!(new_share.id_numbers[&new_node], old_share.id_numbers[nodes[0].session.node()]);
+
+		// all key holders are aware of the new owner
+		assert!(nodes.iter().skip(1).all(|n| n.key_storage.get(&SessionId::default()).unwrap().id_numbers == new_share.id_numbers));
+
+		// master node keeps the moved share until it is purged
+		nodes[0].key_storage.restore_removed(&SessionId::default()).unwrap();
+		assert_eq!(nodes[0].key_storage.get(&SessionId::default()), Ok(old_share));
+	}
+
+	#[test]
+	fn share_move_session_fails_to_initialize_if_key_share_is_stale() {
+		let nodes = prepare_nodes(1, 3);
+		let new_node = nodes[3].session.node().clone();
+
+		// key share is modified after session has been created
+		let mut key_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		key_share.common_point = Some(math::generate_random_point().unwrap());
+		key_share.encrypted_point = Some(math::generate_random_point().unwrap());
+		nodes[0].key_storage.update(SessionId::default(), key_share.clone()).unwrap();
+
+		assert_eq!(nodes[0].session.initialize(new_node, all_nodes(&nodes)), Err(Error::StaleKeyShare));
+		assert_eq!(nodes[0].session.state(), SessionState::WaitingForInitialization);
+		assert!(nodes[0].cluster.take_message().is_none());
+		assert_eq!(nodes[0].key_storage.get(&SessionId::default()), Ok(key_share));
+	}
+
+	#[test]
+	fn share_move_session_ignores_key_metadata_changes() {
+		let nodes = prepare_nodes(1, 3);
+		let new_node = nodes[3].session.node().clone();
+
+		// key metadata is modified after session has been created
+		nodes[0].key_storage.set_metadata(&SessionId::default(), KeyMetadata {
+			label: Some("label".into()),
+			last_accessed: Some(100),
+			..Default::default()
+		}).unwrap();
+
+		nodes[0].session.initialize(new_node, all_nodes(&nodes)).unwrap();
+		assert_eq!(nodes[0].session.state(), SessionState::WaitingForConfirmations);
+	}
+
+	#[test]
+	fn master_keeps_share_until_all_key_holders_have_committed() {
+		let nodes = prepare_nodes(1, 3);
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(new_node, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::ShareMove(ShareMoveMessage::CommitShareMove(_)) => true,
+			_ => false,
+		}).unwrap();
+
+		// the new owner has saved the share, but master still holds its copy
+		assert!(nodes[0].key_storage.contains(&SessionId::default()));
+		assert!(nodes[3].key_storage.contains(&SessionId::default()));
+		assert_eq!(nodes[0].session.state(), SessionState::WaitingForCommitReports);
+	}
+
+	#[test]
+	fn large_share_is_moved_in_chunks() {
+		let nodes = prepare_nodes(1, 100);
+		let old_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		let new_node = nodes[100].session.node().clone();
+
+		let mut chunks = 0;
+		nodes[0].session.initialize(new_node.clone(), all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::ShareMove(ShareMoveMessage::ShareMoveData(_)) => true,
+			Message::ShareMove(ShareMoveMessage::ShareMoveDataChunk(_)) => { chunks += 1; false },
+			_ => false,
+		}).unwrap();
+		assert!(chunks > 1);
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+
+		let new_share = nodes[100].key_storage.get(&SessionId::default()).unwrap();
+		assert_eq!(new_share.secret_share, old_share.secret_share);
+		assert_eq!(new_share.id_numbers[&new_node], old_share.id_numbers[nodes[0].session.node()]);
+	}
+
+	#[test]
+	fn share_move_fails_if_chunk_is_corrupted() {
+		let nodes = prepare_nodes(1, 100);
+		let new_node = nodes[100].session.node().clone();
+
+		let mut chunk = None;
+		nodes[0].session.initialize(new_node.clone(), all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::ShareMove(ShareMoveMessage::ShareMoveDataChunk(ref message)) => { chunk = Some(message.clone()); true },
+			_ => false,
+		}).unwrap();
+
+		// the corrupted chunk is accepted, but the reassembled share does not match its hash
+		let mut chunk = chunk.unwrap();
+		let mut data: Vec<u8> = chunk.data.clone().into();
+		data[0] ^= 1;
+		chunk.data = data.into();
+		nodes[100].session.process_message(nodes[0].session.node(), &ShareMoveMessage::ShareMoveDataChunk(chunk)).unwrap();
+		assert_eq!(do_messages_exchange_until(&nodes, |_, _, _| false), Err(Error::InvalidMessage));
+		assert!(!nodes[100].key_storage.contains(&SessionId::default()));
+	}
+
+	#[test]
+	fn share_move_fails_if_new_node_is_key_holder() {
+		let nodes = prepare_nodes(1, 3);
+		let key_holder = nodes[1].session.node().clone();
+
+		assert_eq!(nodes[0].session.initialize(key_holder, all_nodes(&nodes)), Err(Error::InvalidNodesConfiguration));
+	}
+
+	#[test]
+	fn share_move_fails_if_key_holder_is_disconnected() {
+		let nodes = prepare_nodes(1, 3);
+		let new_node = nodes[3].session.node().clone();
+		let disconnected_node = nodes[2].session.node().clone();
+		let connected_nodes = all_nodes(&nodes).into_iter().filter(|n| n != &disconnected_node).collect();
+
+		assert_eq!(nodes[0].session.initialize(new_node, connected_nodes), Err(Error::NodeDisconnected));
+	}
+
+	#[test]
+	fn share_move_fails_if_new_node_disconnects_before_saving_share() {
+		let nodes = prepare_nodes(1, 3);
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(new_node.clone(), all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::ShareMove(ShareMoveMessage::ShareMoveData(_)) => true,
+			_ => false,
+		}).unwrap();
+
+		nodes[0].session.on_node_timeout(&new_node);
+		assert_eq!(nodes[0].session.wait(None), Err(Error::NodeDisconnected));
+		assert!(nodes[0].key_storage.contains(&SessionId::default()));
+	}
+
+	#[test]
+	fn share_move_is_rolled_back_when_session_fails_after_partial_commit() {
+		let nodes = prepare_nodes(1, 3);
+		let old_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		let new_node = nodes[3].session.node().clone();
+		let lost_commit_node = nodes[1].session.node().clone();
+
+		// commit request to the first key holder is lost, while the second key holder && the new owner save their changes
+		nodes[0].session.initialize(new_node, all_nodes(&nodes)).unwrap();
+		do_messages_exchange_until(&nodes, |_, to, message| match *message {
+			Message::ShareMove(ShareMoveMessage::CommitShareMove(_)) => to == &lost_commit_node,
+			_ => false,
+		}).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert_eq!(nodes[2].session.state(), SessionState::Finished);
+		assert!(nodes[3].key_storage.contains(&SessionId::default()));
+
+		// session fails on master node => all nodes are returning to the pre-session state
+		nodes[0].session.on_session_error(lost_commit_node, &ShareMoveSessionError {
+			session: SessionId::default().into(),
+			session_nonce: 0,
+			error: "error".into(),
+			code: Some(ErrorCode::Storage.into()),
+		}).unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Failed));
+		assert!(nodes.iter().all(|n| n.session.wait(None) == Err(Error::Remote(ErrorCode::Storage, "error".into()))));
+		assert!(nodes.iter().take(3).all(|n| n.key_storage.get(&SessionId::default()).unwrap().id_numbers == old_share.id_numbers));
+		assert!(!nodes[3].key_storage.contains(&SessionId::default()));
+	}
+
+	#[test]
+	fn share_move_rollback_fails_if_it_does_not_undo_the_move() {
+		let nodes = prepare_nodes(1, 3);
+		let mut id_numbers = nodes[1].key_storage.get(&SessionId::default()).unwrap().id_numbers;
+		id_numbers.insert(nodes[1].session.node().clone(), math::generate_random_scalar().unwrap());
+
+		assert_eq!(rollback(&*nodes[1].key_storage, nodes[1].session.node(), nodes[0].session.node(), &SessionId::default(), id_numbers),
+			Err(Error::InvalidMessage));
+	}
+
+	#[test]
+	fn share_move_message_fails_when_nonce_is_wrong() {
+		let nodes = prepare_nodes(1, 3);
+		let new_node = nodes[3].session.node().clone();
+
+		nodes[0].session.initialize(new_node, all_nodes(&nodes)).unwrap();
+		let (_, message) = nodes[0].cluster.take_message().unwrap();
+		let mut message = match message {
+			Message::ShareMove(ShareMoveMessage::InitializeShareMoveSession(message)) => message,
+			_ => unreachable!(),
+		};
+		message.session_nonce = 10;
+		assert_eq!(nodes[1].session.process_message(nodes[0].session.node(),
+			&ShareMoveMessage::InitializeShareMoveSession(message)), Err(Error::ReplayProtection));
+	}
+}
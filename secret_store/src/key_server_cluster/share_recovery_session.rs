@@ -0,0 +1,891 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use bigint::hash::H256;
+use ethkey::{Public, Secret};
+use key_server_cluster::{Error, NodeId, SessionMeta, KeyStorage, DocumentKeyShare};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::math;
+use key_server_cluster::message::{Message, ShareRecoveryMessage, InitializeShareRecoverySession, ConfirmShareRecoveryInitialization,
+	RequestShareRecoveryContribution, ShareRecoveryBlindingShare, ShareRecoveryContribution, ShareRecoverySessionError, ShareRecoveryKeyData};
+
+/// Share recovery session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get share recovery session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error>;
+}
+
+/// Share recovery session.
+/// Is used to re-derive secret share of the node, which local copy has been corrupted or lost.
+/// Brief overview:
+/// 1) initialization: master node (the one which is recovering its share) selects threshold + 1 contributing nodes.
+/// If master node has lost the key share completely, every connected node is asked to contribute && to send key data (all but the secret share).
+/// Connected nodes, which are not holding the key, report that they have no key share && are not contributing
+/// 2) every contributing node i computes its contribution: c_i = share_i * multiplication((x_master - x_j) / (x_i - x_j)) for every j != i
+/// 3) every contributing node sends random blinding value r_ij to every other contributing node j
+/// 4) every contributing node sends blinded contribution c_i - sum(r_ij) + sum(r_ji) to the master node
+/// 5) master node computes its secret share as sum of all blinded contributions && saves it (together with received key data, if lost) to the key storage
+/// Blinding is required, because otherwise master node could compute secret share of every contributing node.
+pub struct SessionImpl {
+	/// Session metadata.
+	meta: SessionMeta,
+	/// Key share. Is None on master node, which has lost its key share, && on nodes, which are not holding the key.
+	key_share: Option<DocumentKeyShare>,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Key share. Is None on master node, which has lost its key share, && on nodes, which are not holding the key.
+	pub key_share: Option<DocumentKeyShare>,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of share recovery session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// Nodes, contributing to the share recovery.
+	nodes: BTreeSet<NodeId>,
+	/// === Values, filled on master node ===
+	/// Nodes, which have confirmed session initialization.
+	confirmed_nodes: BTreeSet<NodeId>,
+	/// Key data, received from contributing nodes (when master node has lost its key share).
+	key_data: Option<KeyData>,
+	/// Connected nodes, which have reported that they're not holding the key (when master node has lost its key share).
+	non_holders: BTreeSet<NodeId>,
+	/// Blinded contributions, received from contributing nodes.
+	contributions: BTreeMap<NodeId, Secret>,
+	/// === Values, filled on contributing nodes ===
+	/// Sum of blinding values, sent to other contributing nodes.
+	sent_blinding: Option<Secret>,
+	/// Blinding values, received from other contributing nodes.
+	received_blindings: BTreeMap<NodeId, Secret>,
+	/// Share recovery session result.
+	result: Option<Result<(), Error>>,
+}
+
+/// Key data, which is the same on all key holders.
+#[derive(Debug, Clone, PartialEq)]
+struct KeyData {
+	/// Key author.
+	author: Public,
+	/// Decryption threshold.
+	threshold: usize,
+	/// Nodes ids numbers.
+	id_numbers: BTreeMap<NodeId, Secret>,
+	/// Common (shared) encryption point.
+	common_point: Option<Public>,
+	/// Encrypted point.
+	encrypted_point: Option<Public>,
+	/// Id of the last share refresh.
+	refresh_id: Option<H256>,
+}
+
+/// Share recovery session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	// === Initialization states ===
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for every contributing node to confirm initialization.
+	WaitingForInitializationConfirm,
+
+	// === Contribution states ===
+	/// Contributing node waits for contribution request from master node.
+	WaitingForContributionRequest,
+	/// Contributing node waits for blinding values from other contributing nodes.
+	WaitingForBlindings,
+	/// Master node waits for contributions from every contributing node.
+	WaitingForContributions,
+
+	// === Final states of the session ===
+	/// Share is recovered (on master node) or contribution is sent (on contributing node).
+	Finished,
+	/// Failed to recover share.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new share recovery session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		// master node could have lost its key share && other nodes could be not holding the key at all
+		if let Some(ref key_share) = params.key_share {
+			if !key_share.id_numbers.contains_key(&params.meta.self_node_id)
+				|| !key_share.id_numbers.contains_key(&params.meta.master_node_id) {
+				return Err(Error::InvalidNodesConfiguration);
+			}
+		}
+
+		Ok(SessionImpl {
+			meta: params.meta,
+			key_share: params.key_share,
+			key_storage: params.key_storage,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				nodes: BTreeSet::new(),
+				confirmed_nodes: BTreeSet::new(),
+				key_data: None,
+				non_holders: BTreeSet::new(),
+				contributions: BTreeMap::new(),
+				sent_blinding: None,
+				received_blindings: BTreeMap::new(),
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	/// Connected nodes are asked to contribute only if master node has lost its key share.
+	pub fn initialize(&self, connected_nodes: BTreeSet<NodeId>) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		// select contributing nodes
+		let nodes: BTreeSet<_> = match self.key_share {
+			Some(ref key_share) => key_share.id_numbers.keys()
+				.filter(|n| *n != self.node())
+				.take(key_share.threshold + 1)
+				.cloned()
+				.collect(),
+			None => connected_nodes.into_iter()
+				.filter(|n| n != self.node())
+				.collect(),
+		};
+		let is_enough_nodes = match self.key_share {
+			Some(ref key_share) => nodes.len() == key_share.threshold + 1,
+			None => !nodes.is_empty(),
+		};
+		if !is_enough_nodes {
+			return Err(Error::ConsensusUnreachable);
+		}
+
+		// update state
+		data.state = SessionState::WaitingForInitializationConfirm;
+		data.nodes = nodes.clone();
+
+		// start initialization
+		for node in &nodes {
+			self.cluster.send(node, Message::ShareRecovery(ShareRecoveryMessage::InitializeShareRecoverySession(InitializeShareRecoverySession {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				nodes: nodes.iter().cloned().map(Into::into).collect(),
+				is_key_data_requested: self.key_share.is_none(),
+			})))?;
+		}
+
+		Ok(())
+	}
+
+	/// Process share recovery message.
+	pub fn process_message(&self, sender: &NodeId, message: &ShareRecoveryMessage) -> Result<(), Error> {
+		match message {
+			&ShareRecoveryMessage::InitializeShareRecoverySession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&ShareRecoveryMessage::ConfirmShareRecoveryInitialization(ref message) =>
+				self.on_confirm_initialization(sender.clone(), message),
+			&ShareRecoveryMessage::RequestShareRecoveryContribution(ref message) =>
+				self.on_contribution_request(sender.clone(), message),
+			&ShareRecoveryMessage::ShareRecoveryBlindingShare(ref message) =>
+				self.on_blinding_share(sender.clone(), message),
+			&ShareRecoveryMessage::ShareRecoveryContribution(ref message) =>
+				self.on_contribution(sender.clone(), message),
+			&ShareRecoveryMessage::ShareRecoverySessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+		}
+	}
+
+	/// When session initialization message is received.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeShareRecoverySession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// node, which is not holding the key, could only be asked to contribute when master node has lost its key share
+		// => it reports that it has no key share && is not contributing
+		let mut nodes: BTreeSet<NodeId> = message.nodes.iter().cloned().map(Into::into).collect();
+		if self.key_share.is_none() {
+			if !message.is_key_data_requested || !nodes.contains(self.node()) {
+				return Err(Error::InvalidMessage);
+			}
+
+			data.state = SessionState::Finished;
+			data.result = Some(Ok(()));
+			self.completed.notify_all();
+
+			return self.cluster.send(&sender, Message::ShareRecovery(ShareRecoveryMessage::ConfirmShareRecoveryInitialization(ConfirmShareRecoveryInitialization {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				key_data: None,
+			})));
+		}
+
+		// key data is re-read, because self.key_share only holds data of connected nodes
+		let key_data = match message.is_key_data_requested {
+			true => {
+				let key_share = self.key_storage.get(&self.meta.id)
+					.map_err(|e| Error::KeyStorage(e.into()))?;
+
+				// every connected node has been asked to contribute => only key holders are contributing
+				nodes = nodes.into_iter().filter(|n| key_share.id_numbers.contains_key(n)).collect();
+
+				Some(ShareRecoveryKeyData {
+					author: key_share.author.into(),
+					threshold: key_share.threshold,
+					id_numbers: key_share.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+					common_point: key_share.common_point.map(Into::into),
+					encrypted_point: key_share.encrypted_point.map(Into::into),
+					refresh_id: key_share.refresh_id.map(Into::into),
+				})
+			},
+			false => None,
+		};
+
+		// check contributing nodes: master node can not contribute to its own share
+		let key_share = self.contributor_key_share()?;
+		if nodes.len() < key_share.threshold + 1 || !nodes.contains(self.node()) || nodes.contains(&sender)
+			|| nodes.iter().any(|n| !key_share.id_numbers.contains_key(n)) {
+			return Err(Error::InvalidMessage);
+		}
+
+		// update state
+		data.state = SessionState::WaitingForContributionRequest;
+		data.nodes = nodes;
+
+		// send confirmation back to master node
+		self.cluster.send(&sender, Message::ShareRecovery(ShareRecoveryMessage::ConfirmShareRecoveryInitialization(ConfirmShareRecoveryInitialization {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			key_data: key_data,
+		})))
+	}
+
+	/// When session initialization confirmation message is received.
+	pub fn on_confirm_initialization(&self, sender: NodeId, message: &ConfirmShareRecoveryInitialization) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitializationConfirm {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.nodes.contains(&sender) || data.confirmed_nodes.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// when master node has lost its key share, every contributing node must report the same key data
+		// && every node, which is not holding the key, is excluded from contributing nodes
+		if self.key_share.is_none() {
+			match message.key_data.clone() {
+				Some(key_data) => {
+					let key_data = KeyData {
+						author: key_data.author.into(),
+						threshold: key_data.threshold,
+						id_numbers: key_data.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+						common_point: key_data.common_point.map(Into::into),
+						encrypted_point: key_data.encrypted_point.map(Into::into),
+						refresh_id: key_data.refresh_id.map(Into::into),
+					};
+					if data.key_data.as_ref().map(|d| d != &key_data).unwrap_or(false) {
+						return Err(Error::InvalidMessage);
+					}
+					if !key_data.id_numbers.contains_key(self.node()) || !key_data.id_numbers.contains_key(&sender)
+						|| data.non_holders.iter().any(|n| key_data.id_numbers.contains_key(n)) {
+						return Err(Error::InvalidMessage);
+					}
+					data.key_data = Some(key_data);
+				},
+				None => {
+					if data.key_data.as_ref().map(|d| d.id_numbers.contains_key(&sender)).unwrap_or(false) {
+						return Err(Error::InvalidMessage);
+					}
+					data.nodes.remove(&sender);
+					data.non_holders.insert(sender.clone());
+				},
+			}
+		}
+
+		// check if all nodes have confirmed initialization
+		if !data.non_holders.contains(&sender) {
+			data.confirmed_nodes.insert(sender);
+		}
+		if data.confirmed_nodes != data.nodes {
+			return Ok(());
+		}
+
+		// check that there are enough key holders to recover the share
+		if self.key_share.is_none() {
+			let threshold = data.key_data.as_ref().map(|d| d.threshold).ok_or(Error::ConsensusUnreachable)?;
+			if data.nodes.len() < threshold + 1 {
+				return Err(Error::ConsensusUnreachable);
+			}
+		}
+
+		// update state
+		data.state = SessionState::WaitingForContributions;
+
+		// ask every contributing node to compute its contribution
+		for node in &data.nodes {
+			self.cluster.send(node, Message::ShareRecovery(ShareRecoveryMessage::RequestShareRecoveryContribution(RequestShareRecoveryContribution {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+			})))?;
+		}
+
+		Ok(())
+	}
+
+	/// When contribution request is received.
+	pub fn on_contribution_request(&self, sender: NodeId, message: &RequestShareRecoveryContribution) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForContributionRequest {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// send random blinding value to every other contributing node
+		let mut sent_blinding: Option<Secret> = None;
+		for node in data.nodes.iter().filter(|n| *n != self.node()) {
+			let blinding = math::generate_random_scalar()?;
+			sent_blinding = Some(match sent_blinding.take() {
+				Some(mut sent_blinding) => {
+					sent_blinding.add(&blinding)?;
+					sent_blinding
+				},
+				None => blinding.clone(),
+			});
+
+			self.cluster.send(node, Message::ShareRecovery(ShareRecoveryMessage::ShareRecoveryBlindingShare(ShareRecoveryBlindingShare {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				blinding: blinding.into(),
+			})))?;
+		}
+
+		// update state
+		data.state = SessionState::WaitingForBlindings;
+		data.sent_blinding = sent_blinding;
+
+		self.try_send_contribution(&mut *data)
+	}
+
+	/// When blinding value is received from other contributing node.
+	pub fn on_blinding_share(&self, sender: NodeId, message: &ShareRecoveryBlindingShare) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForContributionRequest && data.state != SessionState::WaitingForBlindings {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.nodes.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.received_blindings.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.received_blindings.insert(sender, message.blinding.clone().into());
+
+		self.try_send_contribution(&mut *data)
+	}
+
+	/// When blinded contribution is received.
+	pub fn on_contribution(&self, sender: NodeId, message: &ShareRecoveryContribution) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForContributions {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !data.nodes.contains(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.contributions.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		// check if all nodes have sent their contributions
+		data.contributions.insert(sender, message.contribution.clone().into());
+		if data.contributions.len() != data.nodes.len() {
+			return Ok(());
+		}
+
+		// blinding values are cancelled out in the sum
+		let secret_share = math::compute_secret_sum(data.contributions.values())?;
+
+		// save recovered share
+		match data.key_data.take() {
+			// key share is re-read, because self.key_share only holds data of connected nodes
+			None => {
+				let mut key_share = self.key_storage.get(&self.meta.id)
+					.map_err(|e| Error::KeyStorage(e.into()))?;
+				key_share.secret_share = secret_share;
+				self.key_storage.update(self.meta.id.clone(), key_share)
+			},
+			Some(key_data) => self.key_storage.insert(self.meta.id.clone(), DocumentKeyShare {
+				author: key_data.author,
+				threshold: key_data.threshold,
+				id_numbers: key_data.id_numbers,
+				secret_share: secret_share,
+				common_point: key_data.common_point,
+				encrypted_point: key_data.encrypted_point,
+				refresh_id: key_data.refresh_id,
+				metadata: Default::default(),
+			}),
+		}.map_err(|e| Error::KeyStorage(e.into()))?;
+
+		// update state
+		data.state = SessionState::Finished;
+		data.clear_secrets();
+		data.result = Some(Ok(()));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &ShareRecoverySessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: share recovery session failed with error: {} from {}", self.node(), message.error, sender);
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Send blinded contribution to master node, if all blinding values are received.
+	fn try_send_contribution(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::WaitingForBlindings || data.received_blindings.len() + 1 != data.nodes.len() {
+			return Ok(());
+		}
+
+		// compute contribution
+		let key_share = self.contributor_key_share()?;
+		let self_id_number = &key_share.id_numbers[self.node()];
+		let master_id_number = &key_share.id_numbers[&self.meta.master_node_id];
+		let mut contribution = math::compute_share_recovery_contribution(&key_share.secret_share, self_id_number, master_id_number,
+			data.nodes.iter().filter(|n| *n != self.node()).map(|n| &key_share.id_numbers[n]))?;
+
+		// blind contribution
+		if let Some(ref sent_blinding) = data.sent_blinding {
+			contribution.sub(sent_blinding)?;
+		}
+		for received_blinding in data.received_blindings.values() {
+			contribution.add(received_blinding)?;
+		}
+
+		// update state
+		data.state = SessionState::Finished;
+		data.clear_secrets();
+		data.result = Some(Ok(()));
+		self.completed.notify_all();
+
+		self.cluster.send(&self.meta.master_node_id, Message::ShareRecovery(ShareRecoveryMessage::ShareRecoveryContribution(ShareRecoveryContribution {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			contribution: contribution.into(),
+		})))
+	}
+
+	/// Get key share of contributing node.
+	fn contributor_key_share(&self) -> Result<&DocumentKeyShare, Error> {
+		self.key_share.as_ref().ok_or(Error::InvalidNodeForRequest)
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+impl SessionData {
+	/// Forget contributions && blinding values, which are not required once the session is completed.
+	fn clear_secrets(&mut self) {
+		self.contributions.clear();
+		self.sent_blinding = None;
+		self.received_blindings.clear();
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		// only master && contributing nodes are required to complete the session
+		if node != &self.meta.master_node_id && !data.nodes.contains(node) {
+			return;
+		}
+
+		warn!("{}: share recovery session failed because {} connection has timeouted", self.node(), node);
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		warn!("{}: share recovery session failed with timeout", self.node());
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: share recovery session has been cancelled", self.node());
+
+		// key storage is only updated when session is finished => nothing to restore
+		// do not bother processing send error, as we already processing error
+		let error = Message::ShareRecovery(ShareRecoveryMessage::ShareRecoverySessionError(ShareRecoverySessionError {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.meta.self_node_id == self.meta.master_node_id {
+			self.cluster.broadcast(error)
+		} else {
+			self.cluster.send(&self.meta.master_node_id, error)
+		};
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::{BTreeSet, BTreeMap};
+	use ethkey::{Random, Generator, Secret};
+	use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, ShareRecoveryMessage, InitializeShareRecoverySession};
+	use super::{SessionImpl, SessionParams, SessionState, Session};
+
+	struct Node {
+		cluster: Arc<DummyCluster>,
+		key_storage: Arc<DummyKeyStorage>,
+		session: SessionImpl,
+	}
+
+	fn prepare_nodes(threshold: usize, num_nodes: usize) -> (Vec<Secret>, Vec<Node>) {
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let id_numbers: BTreeMap<NodeId, Secret> = (0..num_nodes)
+			.map(|_| (Random.generate().unwrap().public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		let master_node_id = id_numbers.keys().nth(0).cloned().unwrap();
+		let nodes = id_numbers.iter().map(|(node_id, id_number)| {
+			let key_share = DocumentKeyShare {
+				author: Default::default(),
+				threshold: threshold,
+				id_numbers: id_numbers.clone(),
+				secret_share: math::compute_polynom(&polynom, id_number).unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			};
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			key_storage.insert(SessionId::default(), key_share.clone()).unwrap();
+
+			let cluster = Arc::new(DummyCluster::new(node_id.clone()));
+			for node in id_numbers.keys() {
+				cluster.add_node(node.clone());
+			}
+
+			let session = SessionImpl::new(SessionParams {
+				meta: SessionMeta {
+					id: SessionId::default(),
+					master_node_id: master_node_id.clone(),
+					self_node_id: node_id.clone(),
+					threshold: threshold,
+				},
+				key_share: Some(key_share),
+				key_storage: key_storage.clone(),
+				cluster: cluster.clone(),
+				nonce: 0,
+			}).unwrap();
+
+			Node {
+				cluster: cluster,
+				key_storage: key_storage,
+				session: session,
+			}
+		}).collect();
+		(polynom, nodes)
+	}
+
+	fn do_messages_exchange(nodes: &[Node]) -> Result<(), Error> {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::ShareRecovery(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn corrupted_share_is_recovered_in_place() {
+		let (polynom, nodes) = prepare_nodes(2, 5);
+
+		// corrupt share of master node
+		let original_key_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		let mut corrupted_key_share = original_key_share.clone();
+		corrupted_key_share.secret_share = math::generate_random_scalar().unwrap();
+		nodes[0].key_storage.update(SessionId::default(), corrupted_key_share).unwrap();
+
+		// recover share
+		nodes[0].session.initialize(BTreeSet::new()).unwrap();
+		do_messages_exchange(&nodes).unwrap();
+		assert_eq!(nodes[0].session.state(), SessionState::Finished);
+		assert_eq!(nodes[0].session.wait(None), Ok(()));
+		assert_eq!(nodes[0].key_storage.get(&SessionId::default()).unwrap(), original_key_share);
+
+		// only threshold + 1 nodes have contributed to the recovery
+		assert_eq!(nodes.iter().skip(1).filter(|n| n.session.state() == SessionState::Finished).count(), 3);
+		assert_eq!(nodes.iter().skip(1).filter(|n| n.session.state() == SessionState::WaitingForInitialization).count(), 1);
+
+		// joint secret is still reconstructable from the set of shares, including recovered share
+		let shares: Vec<_> = nodes.iter().skip(2).take(2).chain(::std::iter::once(&nodes[0]))
+			.map(|n| (n.key_storage.get(&SessionId::default()).unwrap().id_numbers[n.session.node()].clone(),
+				n.key_storage.get(&SessionId::default()).unwrap().secret_share))
+			.collect();
+		let shadows: Vec<_> = shares.iter()
+			.map(|&(ref id_number, ref secret_share)| math::compute_node_shadow(secret_share, id_number,
+				shares.iter().filter(|&&(ref other_id_number, _)| other_id_number != id_number).map(|&(ref other_id_number, _)| other_id_number)).unwrap())
+			.collect();
+		// sign of the interpolated value depends on threshold parity only => it is positive for even threshold
+		assert_eq!(math::compute_secret_sum(shadows.iter()).unwrap(), polynom[0]);
+	}
+
+	#[test]
+	fn lost_share_is_recovered_with_key_data() {
+		let (_, mut nodes) = prepare_nodes(2, 4);
+
+		// master node has lost its key share completely
+		let original_key_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		let key_storage = Arc::new(DummyKeyStorage::default());
+		let session = SessionImpl::new(SessionParams {
+			meta: nodes[0].session.meta.clone(),
+			key_share: None,
+			key_storage: key_storage.clone(),
+			cluster: nodes[0].cluster.clone(),
+			nonce: 0,
+		}).unwrap();
+		nodes[0].session = session;
+		nodes[0].key_storage = key_storage;
+
+		// recover share && key data
+		let connected_nodes: BTreeSet<NodeId> = nodes.iter().map(|n| n.session.node().clone()).collect();
+		nodes[0].session.initialize(connected_nodes).unwrap();
+		do_messages_exchange(&nodes).unwrap();
+		assert_eq!(nodes[0].session.wait(None), Ok(()));
+		assert_eq!(nodes[0].key_storage.get(&SessionId::default()).unwrap(), original_key_share);
+		assert!(nodes.iter().skip(1).all(|n| n.session.state() == SessionState::Finished));
+	}
+
+	#[test]
+	fn lost_share_is_recovered_when_connected_node_is_not_holding_the_key() {
+		let (_, mut nodes) = prepare_nodes(1, 3);
+
+		// master node has lost its key share completely
+		let original_key_share = nodes[0].key_storage.get(&SessionId::default()).unwrap();
+		let key_storage = Arc::new(DummyKeyStorage::default());
+		let session = SessionImpl::new(SessionParams {
+			meta: nodes[0].session.meta.clone(),
+			key_share: None,
+			key_storage: key_storage.clone(),
+			cluster: nodes[0].cluster.clone(),
+			nonce: 0,
+		}).unwrap();
+		nodes[0].session = session;
+		nodes[0].key_storage = key_storage;
+
+		// master node is also connected to the node, which is not holding the key
+		let non_holder_id = Random.generate().unwrap().public().clone();
+		let non_holder_cluster = Arc::new(DummyCluster::new(non_holder_id.clone()));
+		let non_holder_key_storage = Arc::new(DummyKeyStorage::default());
+		let mut meta = nodes[0].session.meta.clone();
+		meta.self_node_id = non_holder_id;
+		let non_holder_session = SessionImpl::new(SessionParams {
+			meta: meta,
+			key_share: None,
+			key_storage: non_holder_key_storage.clone(),
+			cluster: non_holder_cluster.clone(),
+			nonce: 0,
+		}).unwrap();
+		nodes.push(Node {
+			cluster: non_holder_cluster,
+			key_storage: non_holder_key_storage,
+			session: non_holder_session,
+		});
+
+		// recover share && key data: node, which is not holding the key, is skipped
+		let connected_nodes: BTreeSet<NodeId> = nodes.iter().map(|n| n.session.node().clone()).collect();
+		nodes[0].session.initialize(connected_nodes).unwrap();
+		do_messages_exchange(&nodes).unwrap();
+		assert_eq!(nodes[0].session.wait(None), Ok(()));
+		assert_eq!(nodes[0].key_storage.get(&SessionId::default()).unwrap(), original_key_share);
+		assert!(nodes.iter().skip(1).all(|n| n.session.state() == SessionState::Finished));
+		assert!(!nodes[3].key_storage.contains(&SessionId::default()));
+	}
+
+	#[test]
+	fn share_recovery_fails_if_not_enough_nodes() {
+		let (_, nodes) = prepare_nodes(2, 3);
+		assert_eq!(nodes[0].session.initialize(BTreeSet::new()), Err(Error::ConsensusUnreachable));
+	}
+
+	#[test]
+	fn share_recovery_initialization_is_rejected_if_master_is_contributing() {
+		let (_, nodes) = prepare_nodes(1, 3);
+		let message = InitializeShareRecoverySession {
+			session: SessionId::default().into(),
+			session_nonce: 0,
+			nodes: nodes.iter().take(2).map(|n| n.session.node().clone().into()).collect(),
+			is_key_data_requested: false,
+		};
+		assert_eq!(nodes[1].session.process_message(nodes[0].session.node(),
+			&ShareRecoveryMessage::InitializeShareRecoverySession(message)), Err(Error::InvalidMessage));
+	}
+}
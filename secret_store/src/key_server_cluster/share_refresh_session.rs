@@ -0,0 +1,814 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::time;
+use std::sync::Arc;
+use parking_lot::{Condvar, Mutex};
+use byteorder::{BigEndian, ByteOrder};
+use bigint::hash::H256;
+use hash::keccak;
+use ethkey::Secret;
+use key_server_cluster::{Error, NodeId, SessionMeta, KeyStorage, DocumentKeyShare};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::math;
+use key_server_cluster::message::{Message, ShareRefreshMessage, InitializeShareRefreshSession, ConfirmShareRefreshInitialization,
+	RequestShareRefreshDeltas, ShareRefreshDelta, ConfirmShareRefresh, CommitShareRefresh, ShareRefreshCommitted, ShareRefreshSessionError};
+
+/// Share refresh session API.
+pub trait Session: Send + Sync + 'static {
+	/// Get share refresh session state.
+	fn state(&self) -> SessionState;
+	/// Wait until session is completed.
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error>;
+}
+
+/// Share refresh (proactive secret sharing) session.
+/// Re-randomizes secret shares of all key holders, so that joint secret is left the same, but shares, stolen
+/// before the refresh, can not be combined with shares, stolen after the refresh.
+/// Brief overview:
+/// 1) initialization: master node initializes the session on all other key holders
+/// 2) every key holder generates random polynom of threshold degree with zero constant term
+/// 3) every key holder sends value of its polynom at id number of every other key holder
+/// 4) every key holder computes refreshed share as sum of its old share and all received values && confirms this to master node
+/// 5) when all key holders have confirmed, master node asks every other key holder to save refreshed share && to report back
+/// 6) when all other key holders have reported, master node saves its own refreshed share
+/// Refreshed share is saved as the new version of the key share, so nodes, which have failed to commit, are still able
+/// to agree on the previous version.
+pub struct SessionImpl {
+	/// Session metadata.
+	meta: SessionMeta,
+	/// Key share.
+	key_share: DocumentKeyShare,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
+	/// Cluster which allows this node to send messages to other nodes in the cluster.
+	cluster: Arc<Cluster>,
+	/// Session nonce.
+	nonce: u64,
+	/// SessionImpl completion condvar.
+	completed: Condvar,
+	/// Mutable session data.
+	data: Mutex<SessionData>,
+}
+
+/// SessionImpl creation parameters
+pub struct SessionParams {
+	/// Session metadata.
+	pub meta: SessionMeta,
+	/// Key share.
+	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
+	/// Cluster
+	pub cluster: Arc<Cluster>,
+	/// Session nonce.
+	pub nonce: u64,
+}
+
+/// Mutable data of share refresh session.
+#[derive(Debug)]
+struct SessionData {
+	/// Current state of the session.
+	state: SessionState,
+	/// === Values, filled on master node ===
+	/// Nodes, which have confirmed session initialization.
+	initialized_nodes: BTreeSet<NodeId>,
+	/// Nodes, which have computed refreshed share.
+	refreshed_nodes: BTreeSet<NodeId>,
+	/// Nodes, which have saved refreshed share.
+	committed_nodes: BTreeSet<NodeId>,
+	/// === Values, filled on all nodes ===
+	/// Share deltas, received from other nodes (including this node).
+	deltas: BTreeMap<NodeId, Secret>,
+	/// Refreshed secret share.
+	refreshed_share: Option<Secret>,
+	/// Share refresh session result.
+	result: Option<Result<(), Error>>,
+}
+
+/// Share refresh session state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+	// === Initialization states ===
+	/// Every node starts in this state.
+	WaitingForInitialization,
+	/// Master node waits for every other node to confirm initialization.
+	WaitingForInitializationConfirm,
+	/// Slave node waits for deltas request from master node.
+	WaitingForDeltasRequest,
+
+	// === Refresh states ===
+	/// Node waits for deltas from every other node.
+	WaitingForDeltas,
+	/// Master node waits for every other node to compute refreshed share.
+	WaitingForRefreshConfirm,
+	/// Slave node waits for commit request from master node.
+	WaitingForCommit,
+	/// Master node waits for every other node to save refreshed share.
+	WaitingForCommitReports,
+
+	// === Final states of the session ===
+	/// Refreshed share is saved.
+	Finished,
+	/// Failed to refresh share. Key storage is left untouched.
+	Failed,
+}
+
+impl SessionImpl {
+	/// Create new share refresh session.
+	pub fn new(params: SessionParams) -> Result<Self, Error> {
+		if !params.key_share.id_numbers.contains_key(&params.meta.self_node_id)
+			|| !params.key_share.id_numbers.contains_key(&params.meta.master_node_id) {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+		// when threshold is 0, every share is equal to the joint secret => there's nothing to refresh
+		if params.key_share.threshold == 0 {
+			return Err(Error::InvalidThreshold);
+		}
+
+		Ok(SessionImpl {
+			meta: params.meta,
+			key_share: params.key_share,
+			key_storage: params.key_storage,
+			cluster: params.cluster,
+			nonce: params.nonce,
+			completed: Condvar::new(),
+			data: Mutex::new(SessionData {
+				state: SessionState::WaitingForInitialization,
+				initialized_nodes: BTreeSet::new(),
+				refreshed_nodes: BTreeSet::new(),
+				committed_nodes: BTreeSet::new(),
+				deltas: BTreeMap::new(),
+				refreshed_share: None,
+				result: None,
+			}),
+		})
+	}
+
+	/// Get this node Id.
+	pub fn node(&self) -> &NodeId {
+		&self.meta.self_node_id
+	}
+
+	/// Start new session initialization. This must be called on master node.
+	pub fn initialize(&self) -> Result<(), Error> {
+		debug_assert_eq!(self.meta.self_node_id, self.meta.master_node_id);
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+
+		// update state
+		data.state = SessionState::WaitingForInitializationConfirm;
+		data.initialized_nodes.insert(self.node().clone());
+
+		// start initialization
+		let nodes: BTreeSet<_> = self.key_share.id_numbers.keys().cloned().collect();
+		for node in nodes.iter().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::ShareRefresh(ShareRefreshMessage::InitializeShareRefreshSession(InitializeShareRefreshSession {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				nodes: nodes.iter().cloned().map(Into::into).collect(),
+			})))?;
+		}
+
+		Ok(())
+	}
+
+	/// Process share refresh message.
+	pub fn process_message(&self, sender: &NodeId, message: &ShareRefreshMessage) -> Result<(), Error> {
+		match message {
+			&ShareRefreshMessage::InitializeShareRefreshSession(ref message) =>
+				self.on_initialize_session(sender.clone(), message),
+			&ShareRefreshMessage::ConfirmShareRefreshInitialization(ref message) =>
+				self.on_confirm_initialization(sender.clone(), message),
+			&ShareRefreshMessage::RequestShareRefreshDeltas(ref message) =>
+				self.on_deltas_request(sender.clone(), message),
+			&ShareRefreshMessage::ShareRefreshDelta(ref message) =>
+				self.on_delta(sender.clone(), message),
+			&ShareRefreshMessage::ConfirmShareRefresh(ref message) =>
+				self.on_confirm_refresh(sender.clone(), message),
+			&ShareRefreshMessage::CommitShareRefresh(ref message) =>
+				self.on_commit(sender.clone(), message),
+			&ShareRefreshMessage::ShareRefreshCommitted(ref message) =>
+				self.on_committed(sender.clone(), message),
+			&ShareRefreshMessage::ShareRefreshSessionError(ref message) =>
+				self.on_session_error(sender.clone(), message),
+		}
+	}
+
+	/// When session initialization message is received.
+	pub fn on_initialize_session(&self, sender: NodeId, message: &InitializeShareRefreshSession) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitialization {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// every key holder must take part in refresh
+		let nodes: BTreeSet<NodeId> = message.nodes.iter().cloned().map(Into::into).collect();
+		if nodes.len() != self.key_share.id_numbers.len() || nodes.iter().any(|n| !self.key_share.id_numbers.contains_key(n)) {
+			return Err(Error::InvalidNodesConfiguration);
+		}
+
+		// update state
+		data.state = SessionState::WaitingForDeltasRequest;
+
+		// send confirmation back to master node
+		self.cluster.send(&sender, Message::ShareRefresh(ShareRefreshMessage::ConfirmShareRefreshInitialization(ConfirmShareRefreshInitialization {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When session initialization confirmation message is received.
+	pub fn on_confirm_initialization(&self, sender: NodeId, message: &ConfirmShareRefreshInitialization) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForInitializationConfirm {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !self.key_share.id_numbers.contains_key(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		// check if all nodes have confirmed initialization
+		data.initialized_nodes.insert(sender);
+		if data.initialized_nodes.len() != self.key_share.id_numbers.len() {
+			return Ok(());
+		}
+
+		// ask every other node to send deltas
+		for node in self.key_share.id_numbers.keys().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::ShareRefresh(ShareRefreshMessage::RequestShareRefreshDeltas(RequestShareRefreshDeltas {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+			})))?;
+		}
+
+		self.send_deltas(&mut *data)
+	}
+
+	/// When deltas request is received.
+	pub fn on_deltas_request(&self, sender: NodeId, message: &RequestShareRefreshDeltas) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForDeltasRequest {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		self.send_deltas(&mut *data)
+	}
+
+	/// When share delta is received from other node.
+	pub fn on_delta(&self, sender: NodeId, message: &ShareRefreshDelta) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForDeltasRequest && data.state != SessionState::WaitingForDeltas {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !self.key_share.id_numbers.contains_key(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.deltas.contains_key(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.deltas.insert(sender, message.delta.clone().into());
+
+		self.try_refresh_share(&mut *data)
+	}
+
+	/// When refresh confirmation is received.
+	pub fn on_confirm_refresh(&self, sender: NodeId, message: &ConfirmShareRefresh) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id
+			|| (data.state != SessionState::WaitingForDeltas && data.state != SessionState::WaitingForRefreshConfirm) {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !self.key_share.id_numbers.contains_key(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.refreshed_nodes.contains(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.refreshed_nodes.insert(sender);
+
+		self.try_commit(&mut *data)
+	}
+
+	/// When commit request is received.
+	pub fn on_commit(&self, sender: NodeId, message: &CommitShareRefresh) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if data.state != SessionState::WaitingForCommit {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if sender != self.meta.master_node_id {
+			return Err(Error::InvalidNodeForRequest);
+		}
+
+		self.save_refreshed_share(&mut *data)?;
+
+		self.cluster.send(&sender, Message::ShareRefresh(ShareRefreshMessage::ShareRefreshCommitted(ShareRefreshCommitted {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// When key holder reports that refreshed share is saved.
+	pub fn on_committed(&self, sender: NodeId, message: &ShareRefreshCommitted) -> Result<(), Error> {
+		debug_assert!(self.meta.id == *message.session);
+		debug_assert!(&sender != self.node());
+
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		// check state
+		if self.meta.self_node_id != self.meta.master_node_id || data.state != SessionState::WaitingForCommitReports {
+			return Err(Error::InvalidStateForRequest);
+		}
+		if !self.key_share.id_numbers.contains_key(&sender) {
+			return Err(Error::InvalidNodeForRequest);
+		}
+		if data.committed_nodes.contains(&sender) {
+			return Err(Error::InvalidMessage);
+		}
+
+		data.committed_nodes.insert(sender);
+
+		self.try_complete(&mut *data)
+	}
+
+	/// When error has occured on another node.
+	pub fn on_session_error(&self, sender: NodeId, message: &ShareRefreshSessionError) -> Result<(), Error> {
+		self.check_nonce(message.session_nonce)?;
+
+		let mut data = self.data.lock();
+
+		warn!("{}: share refresh session failed with error: {} from {}", self.node(), message.error, sender);
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::remote(message.code, message.error.clone())));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Generate random polynom with zero constant term && send its values to every other node.
+	fn send_deltas(&self, data: &mut SessionData) -> Result<(), Error> {
+		let polynom = math::generate_random_polynom(self.key_share.threshold - 1)?;
+		for (node, id_number) in self.key_share.id_numbers.iter().filter(|&(n, _)| n != self.node()) {
+			self.cluster.send(node, Message::ShareRefresh(ShareRefreshMessage::ShareRefreshDelta(ShareRefreshDelta {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+				delta: math::compute_share_refresh_delta(&polynom, id_number)?.into(),
+			})))?;
+		}
+
+		// update state
+		let self_id_number = &self.key_share.id_numbers[self.node()];
+		data.deltas.insert(self.node().clone(), math::compute_share_refresh_delta(&polynom, self_id_number)?);
+		data.state = SessionState::WaitingForDeltas;
+
+		self.try_refresh_share(data)
+	}
+
+	/// Compute refreshed share, if deltas from all nodes are received.
+	fn try_refresh_share(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::WaitingForDeltas || data.deltas.len() != self.key_share.id_numbers.len() {
+			return Ok(());
+		}
+
+		data.refreshed_share = Some(math::compute_secret_sum(data.deltas.values().chain(::std::iter::once(&self.key_share.secret_share)))?);
+
+		// master node waits for confirmations from all other nodes
+		if self.meta.self_node_id == self.meta.master_node_id {
+			data.state = SessionState::WaitingForRefreshConfirm;
+			data.refreshed_nodes.insert(self.node().clone());
+			return self.try_commit(data);
+		}
+
+		data.state = SessionState::WaitingForCommit;
+		self.cluster.send(&self.meta.master_node_id, Message::ShareRefresh(ShareRefreshMessage::ConfirmShareRefresh(ConfirmShareRefresh {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+		})))
+	}
+
+	/// Ask every node to save refreshed share, if all nodes have confirmed refresh.
+	fn try_commit(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::WaitingForRefreshConfirm || data.refreshed_nodes.len() != self.key_share.id_numbers.len() {
+			return Ok(());
+		}
+
+		data.state = SessionState::WaitingForCommitReports;
+		for node in self.key_share.id_numbers.keys().filter(|n| *n != self.node()) {
+			self.cluster.send(node, Message::ShareRefresh(ShareRefreshMessage::CommitShareRefresh(CommitShareRefresh {
+				session: self.meta.id.clone().into(),
+				session_nonce: self.nonce,
+			})))?;
+		}
+
+		self.try_complete(data)
+	}
+
+	/// Save refreshed share of master node, if all other nodes have saved their refreshed shares.
+	fn try_complete(&self, data: &mut SessionData) -> Result<(), Error> {
+		if data.state != SessionState::WaitingForCommitReports || data.committed_nodes.len() + 1 != self.key_share.id_numbers.len() {
+			return Ok(());
+		}
+
+		self.save_refreshed_share(data)
+	}
+
+	/// Save refreshed share to the key storage as the new key share version.
+	fn save_refreshed_share(&self, data: &mut SessionData) -> Result<(), Error> {
+		// check that key share has not been modified since session has been created
+		let mut key_share = self.key_storage.get(&self.meta.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		if key_share.secret_share != self.key_share.secret_share || key_share.version() != self.key_share.version() {
+			return Err(Error::StaleKeyShare);
+		}
+
+		key_share.secret_share = data.refreshed_share.clone()
+			.expect("save_refreshed_share is called after refreshed share is computed; qed");
+		key_share.refresh_id = Some(self.refresh_id());
+		self.key_storage.update(self.meta.id.clone(), key_share)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+
+		// update state
+		data.state = SessionState::Finished;
+		data.clear_secrets();
+		data.result = Some(Ok(()));
+		self.completed.notify_all();
+
+		Ok(())
+	}
+
+	/// Id of this share refresh. It is the same on all key holders && is different for every refresh of the same key,
+	/// so that refreshed shares are never mixed with shares of other versions.
+	fn refresh_id(&self) -> H256 {
+		let mut nonce = [0u8; 8];
+		BigEndian::write_u64(&mut nonce, self.nonce);
+
+		let mut buffer = Vec::with_capacity(32 + 64 + 8);
+		buffer.extend_from_slice(&*self.key_share.version());
+		buffer.extend_from_slice(&*self.meta.master_node_id);
+		buffer.extend_from_slice(&nonce);
+		keccak(&buffer)
+	}
+
+	/// Check session nonce.
+	fn check_nonce(&self, message_session_nonce: u64) -> Result<(), Error> {
+		match self.nonce == message_session_nonce {
+			true => Ok(()),
+			false => Err(Error::ReplayProtection),
+		}
+	}
+}
+
+impl SessionData {
+	/// Forget share deltas && refreshed share, which are not required once the session is completed.
+	fn clear_secrets(&mut self) {
+		self.deltas.clear();
+		self.refreshed_share = None;
+	}
+}
+
+impl ClusterSession for SessionImpl {
+	fn is_finished(&self) -> bool {
+		let data = self.data.lock();
+		data.state == SessionState::Failed
+			|| data.state == SessionState::Finished
+	}
+
+	fn is_failed(&self) -> bool {
+		self.data.lock().state == SessionState::Failed
+	}
+
+	fn on_node_timeout(&self, node: &NodeId) {
+		let mut data = self.data.lock();
+
+		// every key holder is required to complete the session
+		if !self.key_share.id_numbers.contains_key(node) {
+			return;
+		}
+
+		warn!("{}: share refresh session failed because {} connection has timeouted", self.node(), node);
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn on_session_timeout(&self) {
+		let mut data = self.data.lock();
+
+		warn!("{}: share refresh session failed with timeout", self.node());
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::NodeDisconnected));
+		self.completed.notify_all();
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		if data.state == SessionState::Failed || data.state == SessionState::Finished {
+			return;
+		}
+
+		warn!("{}: share refresh session has been cancelled", self.node());
+
+		// key storage is only updated when session is finished => nothing to restore
+		// do not bother processing send error, as we already processing error
+		let error = Message::ShareRefresh(ShareRefreshMessage::ShareRefreshSessionError(ShareRefreshSessionError {
+			session: self.meta.id.clone().into(),
+			session_nonce: self.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.meta.self_node_id == self.meta.master_node_id {
+			self.cluster.broadcast(error)
+		} else {
+			self.cluster.send(&self.meta.master_node_id, error)
+		};
+
+		data.state = SessionState::Failed;
+		data.clear_secrets();
+		data.result = Some(Err(Error::SessionCancelled));
+		self.completed.notify_all();
+	}
+}
+
+impl Session for SessionImpl {
+	fn state(&self) -> SessionState {
+		self.data.lock().state.clone()
+	}
+
+	fn wait(&self, timeout: Option<time::Duration>) -> Result<(), Error> {
+		let mut data = self.data.lock();
+		if !data.result.is_some() {
+			match timeout {
+				None => self.completed.wait(&mut data),
+				Some(timeout) => { self.completed.wait_for(&mut data, timeout); },
+			}
+		}
+
+		data.result.as_ref()
+			.expect("checked above or waited for completed; completed is only signaled when result.is_some(); qed")
+			.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::collections::BTreeMap;
+	use ethkey::{Random, Generator, Secret};
+	use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, KeyStorage, DocumentKeyShare, DummyKeyStorage};
+	use key_server_cluster::cluster::tests::DummyCluster;
+	use key_server_cluster::math;
+	use key_server_cluster::message::{Message, ShareRefreshMessage};
+	use super::{SessionImpl, SessionParams, SessionState, Session};
+
+	struct Node {
+		cluster: Arc<DummyCluster>,
+		key_storage: Arc<DummyKeyStorage>,
+		session: SessionImpl,
+	}
+
+	fn prepare_nodes(threshold: usize, num_nodes: usize) -> (Vec<Secret>, Vec<Node>) {
+		let polynom = math::generate_random_polynom(threshold).unwrap();
+		let id_numbers: BTreeMap<NodeId, Secret> = (0..num_nodes)
+			.map(|_| (Random.generate().unwrap().public().clone(), math::generate_random_scalar().unwrap()))
+			.collect();
+		let master_node_id = id_numbers.keys().nth(0).cloned().unwrap();
+		let nodes = id_numbers.iter().map(|(node_id, id_number)| {
+			let key_share = DocumentKeyShare {
+				author: Default::default(),
+				threshold: threshold,
+				id_numbers: id_numbers.clone(),
+				secret_share: math::compute_polynom(&polynom, id_number).unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			};
+			let key_storage = Arc::new(DummyKeyStorage::default());
+			key_storage.insert(SessionId::default(), key_share.clone()).unwrap();
+
+			let cluster = Arc::new(DummyCluster::new(node_id.clone()));
+			for node in id_numbers.keys() {
+				cluster.add_node(node.clone());
+			}
+
+			let session = SessionImpl::new(SessionParams {
+				meta: SessionMeta {
+					id: SessionId::default(),
+					master_node_id: master_node_id.clone(),
+					self_node_id: node_id.clone(),
+					threshold: threshold,
+				},
+				key_share: key_share,
+				key_storage: key_storage.clone(),
+				cluster: cluster.clone(),
+				nonce: 0,
+			}).unwrap();
+
+			Node {
+				cluster: cluster,
+				key_storage: key_storage,
+				session: session,
+			}
+		}).collect();
+		(polynom, nodes)
+	}
+
+	fn do_messages_exchange_until<F>(nodes: &[Node], mut cond: F) -> Result<(), Error> where F: FnMut(&NodeId, &NodeId, &Message) -> bool {
+		while let Some((from, to, message)) = nodes.iter().filter_map(|n| n.cluster.take_message().map(|(to, msg)| (n.cluster.node(), to, msg))).next() {
+			if cond(&from, &to, &message) {
+				break;
+			}
+
+			let node = nodes.iter().find(|n| n.session.node() == &to).unwrap();
+			match message {
+				Message::ShareRefresh(message) => node.session.process_message(&from, &message)?,
+				_ => unreachable!(),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn key_shares(nodes: &[Node]) -> Vec<DocumentKeyShare> {
+		nodes.iter().map(|n| n.key_storage.get(&SessionId::default()).unwrap()).collect()
+	}
+
+	#[test]
+	fn shares_are_refreshed_and_joint_secret_is_preserved() {
+		let (polynom, nodes) = prepare_nodes(2, 4);
+		let old_key_shares = key_shares(&nodes);
+
+		nodes[0].session.initialize().unwrap();
+		do_messages_exchange_until(&nodes, |_, _, _| false).unwrap();
+		assert!(nodes.iter().all(|n| n.session.state() == SessionState::Finished));
+		assert_eq!(nodes[0].session.wait(None), Ok(()));
+
+		// all shares are changed && saved as the same new version
+		let new_key_shares = key_shares(&nodes);
+		for (old_key_share, new_key_share) in old_key_shares.iter().zip(new_key_shares.iter()) {
+			assert!(old_key_share.secret_share != new_key_share.secret_share);
+			assert_eq!(old_key_share.id_numbers, new_key_share.id_numbers);
+			assert!(old_key_share.version() != new_key_share.version());
+			assert_eq!(new_key_share.version(), new_key_shares[0].version());
+		}
+
+		// joint secret is reconstructable from refreshed shares
+		let shares: Vec<_> = nodes.iter().zip(new_key_shares.iter()).skip(1)
+			.map(|(n, key_share)| (key_share.id_numbers[n.session.node()].clone(), key_share.secret_share.clone()))
+			.collect();
+		let shadows: Vec<_> = shares.iter()
+			.map(|&(ref id_number, ref secret_share)| math::compute_node_shadow(secret_share, id_number,
+				shares.iter().filter(|&&(ref other_id_number, _)| other_id_number != id_number).map(|&(ref other_id_number, _)| other_id_number)).unwrap())
+			.collect();
+		// sign of the interpolated value depends on threshold parity only => it is positive for even threshold
+		assert_eq!(math::compute_secret_sum(shadows.iter()).unwrap(), polynom[0]);
+	}
+
+	#[test]
+	fn shares_are_not_saved_until_commit() {
+		let (_, nodes) = prepare_nodes(1, 3);
+		let old_key_shares = key_shares(&nodes);
+
+		nodes[0].session.initialize().unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::ShareRefresh(ShareRefreshMessage::CommitShareRefresh(_)) => true,
+			_ => false,
+		}).unwrap();
+
+		// master waits for slaves to save their shares && slaves are waiting for commit
+		assert_eq!(old_key_shares, key_shares(&nodes));
+		assert_eq!(nodes[0].session.state(), SessionState::WaitingForCommitReports);
+		assert!(nodes.iter().skip(1).all(|n| n.session.state() == SessionState::WaitingForCommit));
+	}
+
+	#[test]
+	fn previous_version_is_kept_until_all_nodes_have_committed() {
+		let (_, nodes) = prepare_nodes(1, 3);
+		let old_key_shares = key_shares(&nodes);
+		let old_version = old_key_shares[0].version();
+
+		nodes[0].session.initialize().unwrap();
+		do_messages_exchange_until(&nodes, |_, _, message| match *message {
+			Message::ShareRefresh(ShareRefreshMessage::ShareRefreshCommitted(_)) => true,
+			_ => false,
+		}).unwrap();
+
+		// slaves have saved refreshed shares, but master has not received their reports yet
+		assert_eq!(nodes[0].key_storage.versions(&SessionId::default()).unwrap(), vec![old_version.clone()]);
+		assert_eq!(nodes[0].session.state(), SessionState::WaitingForCommitReports);
+		for (node, old_key_share) in nodes.iter().zip(old_key_shares.iter()).skip(1) {
+			let versions = node.key_storage.versions(&SessionId::default()).unwrap();
+			assert_eq!(versions.len(), 2);
+			assert!(versions[0] != old_version);
+			assert_eq!(versions[1], old_version);
+
+			// previous version is still common to all nodes && could be used by other sessions
+			assert_eq!(&node.key_storage.get_version(&SessionId::default(), &old_version).unwrap(), old_key_share);
+		}
+	}
+
+	#[test]
+	fn share_refresh_is_rejected_for_zero_threshold() {
+		let self_node_id = Random.generate().unwrap().public().clone();
+		let id_numbers: BTreeMap<_, _> = vec![(self_node_id.clone(), math::generate_random_scalar().unwrap())].into_iter().collect();
+		assert_eq!(SessionImpl::new(SessionParams {
+			meta: SessionMeta {
+				id: SessionId::default(),
+				master_node_id: self_node_id.clone(),
+				self_node_id: self_node_id.clone(),
+				threshold: 0,
+			},
+			key_share: DocumentKeyShare {
+				author: Default::default(),
+				threshold: 0,
+				id_numbers: id_numbers,
+				secret_share: math::generate_random_scalar().unwrap(),
+				common_point: None,
+				encrypted_point: None,
+				refresh_id: None,
+				metadata: Default::default(),
+			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
+			cluster: Arc::new(DummyCluster::new(self_node_id)),
+			nonce: 0,
+		}).err(), Some(Error::InvalidThreshold));
+	}
+}
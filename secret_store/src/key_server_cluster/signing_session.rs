@@ -19,7 +19,7 @@ use std::sync::Arc;
 use parking_lot::{Mutex, Condvar};
 use ethkey::{Public, Secret, Signature};
 use bigint::hash::H256;
-use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, AclStorage, DocumentKeyShare};
+use key_server_cluster::{Error, NodeId, SessionId, SessionMeta, AclStorage, KeyStorage, DocumentKeyShare};
 use key_server_cluster::cluster::{Cluster};
 use key_server_cluster::cluster_sessions::ClusterSession;
 use key_server_cluster::generation_session::{SessionImpl as GenerationSession, SessionParams as GenerationSessionParams,
@@ -44,6 +44,7 @@ pub trait Session: Send + Sync + 'static {
 /// Brief overview:
 /// 1) initialization: master node (which has received request for signing the message) requests all other nodes to sign the message
 /// 2) ACL check: all nodes which have received the request are querying ACL-contract to check if requestor has access to the private key
+/// 2.1) version negotiation: nodes, which do not have the latest key version of the master node, are treated as rejecting consensus
 /// 3) partial signing: every node which has succussfully checked access for the requestor do a partial signing
 /// 4) signing: master node receives all partial signatures of the secret and computes the signature
 pub struct SessionImpl {
@@ -61,6 +62,8 @@ struct SessionCore {
 	pub access_key: Secret,
 	/// Key share.
 	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
 	/// Cluster which allows this node to send messages to other nodes in the cluster.
 	pub cluster: Arc<Cluster>,
 	/// Session-level nonce.
@@ -78,6 +81,8 @@ struct SessionData {
 	pub state: SessionState,
 	/// Message hash.
 	pub message_hash: Option<H256>,
+	/// Key version, negotiated by master node.
+	pub key_version: Option<H256>,
 	/// Consensus-based signing session.
 	pub consensus_session: SigningConsensusSession,
 	/// Session key generation session.
@@ -106,6 +111,8 @@ pub struct SessionParams {
 	pub access_key: Secret,
 	/// Key share.
 	pub key_share: DocumentKeyShare,
+	/// Key storage.
+	pub key_storage: Arc<KeyStorage>,
 	/// ACL storage.
 	pub acl_storage: Arc<AclStorage>,
 	/// Cluster
@@ -122,6 +129,8 @@ struct SigningConsensusTransport {
 	access_key: Secret,
 	/// Session-level nonce.
 	nonce: u64,
+	/// Key storage.
+	key_storage: Arc<KeyStorage>,
 	/// Cluster.
 	cluster: Arc<Cluster>,
 }
@@ -146,6 +155,8 @@ struct SigningJobTransport {
 	access_key: Secret,
 	/// Session-level nonce.
 	nonce: u64,
+	/// Key version, used for signing.
+	key_version: H256,
 	/// Cluster.
 	cluster: Arc<Cluster>,
 }
@@ -167,6 +178,7 @@ impl SessionImpl {
 			id: params.meta.id.clone(),
 			access_key: params.access_key.clone(),
 			nonce: params.nonce,
+			key_storage: params.key_storage.clone(),
 			cluster: params.cluster.clone(),
 		};
 
@@ -175,6 +187,7 @@ impl SessionImpl {
 				meta: params.meta.clone(),
 				access_key: params.access_key,
 				key_share: params.key_share,
+				key_storage: params.key_storage,
 				cluster: params.cluster,
 				nonce: params.nonce,
 				completed: Condvar::new(),
@@ -182,6 +195,7 @@ impl SessionImpl {
 			data: Mutex::new(SessionData {
 				state: SessionState::ConsensusEstablishing,
 				message_hash: None,
+				key_version: None,
 				consensus_session: match requester_signature {
 					Some(requester_signature) => ConsensusSession::new_on_master(ConsensusSessionParams {
 						meta: params.meta,
@@ -209,7 +223,9 @@ impl SessionImpl {
 	/// Initialize signing session on master node.
 	pub fn initialize(&self, message_hash: H256) -> Result<(), Error> {
 		let mut data = self.data.lock();
+		let key_version = self.core.latest_key_version()?;
 		data.message_hash = Some(message_hash);
+		data.key_version = Some(key_version.clone());
 		data.consensus_session.initialize(self.core.key_share.id_numbers.keys().cloned().collect())?;
 
 		if data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished {
@@ -234,7 +250,7 @@ impl SessionImpl {
 			data.generation_session = Some(generation_session);
 			data.state = SessionState::SignatureComputing;
 
-			self.core.disseminate_jobs(&mut data.consensus_session, joint_public_and_secret.0, joint_public_and_secret.1, message_hash)?;
+			self.core.disseminate_jobs(&mut data.consensus_session, joint_public_and_secret.0, joint_public_and_secret.1, message_hash, key_version)?;
 
 			debug_assert!(data.consensus_session.state() == ConsensusSessionState::Finished);
 			data.result = Some(Ok(data.consensus_session.result()?));
@@ -274,7 +290,8 @@ impl SessionImpl {
 
 		let mut data = self.data.lock();
 		let is_establishing_consensus = data.consensus_session.state() == ConsensusSessionState::EstablishingConsensus;
-		data.consensus_session.on_consensus_message(&sender, &message.message)?;
+		let consensus_message = self.core.filter_consensus_message(data.key_version.as_ref(), &message.message)?;
+		data.consensus_session.on_consensus_message(&sender, &consensus_message)?;
 
 		let is_consensus_established = data.consensus_session.state() == ConsensusSessionState::ConsensusEstablished;
 		if self.core.meta.self_node_id != self.core.meta.master_node_id || !is_establishing_consensus || !is_consensus_established {
@@ -355,11 +372,13 @@ impl SessionImpl {
 
 		let message_hash = data.message_hash
 			.expect("we are on master node; on master node message_hash is filled in initialize(); on_generation_message follows initialize; qed");
+		let key_version = data.key_version.clone()
+			.expect("we are on master node; on master node key_version is filled in initialize(); on_generation_message follows initialize; qed");
 		let joint_public_and_secret = data.generation_session.as_ref()
 			.expect("session key is generated before signature is computed; we are in SignatureComputing state; qed")
 			.joint_public_and_secret()
 			.expect("session key is generated before signature is computed; we are in SignatureComputing state; qed")?;
-		self.core.disseminate_jobs(&mut data.consensus_session, joint_public_and_secret.0, joint_public_and_secret.1, message_hash)
+		self.core.disseminate_jobs(&mut data.consensus_session, joint_public_and_secret.0, joint_public_and_secret.1, message_hash, key_version)
 	}
 
 	/// When partial signature is requested.
@@ -381,8 +400,11 @@ impl SessionImpl {
 			.expect("session key is generated before signature is computed; we are in SignatureComputing state; qed")
 			.joint_public_and_secret()
 			.expect("session key is generated before signature is computed; we are in SignatureComputing state; qed")?;
-		let signing_job = SigningJob::new_on_slave(self.core.meta.self_node_id.clone(), self.core.key_share.clone(), joint_public_and_secret.0, joint_public_and_secret.1)?;
-		let signing_transport = self.core.signing_transport();
+		let key_version: H256 = message.key_version.clone().into();
+		let key_share = self.core.key_storage.get_version(&self.core.meta.id, &key_version)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
+		let signing_job = SigningJob::new_on_slave(self.core.meta.self_node_id.clone(), key_share, joint_public_and_secret.0, joint_public_and_secret.1)?;
+		let signing_transport = self.core.signing_transport(key_version);
 
 		data.consensus_session.on_job_request(sender, PartialSigningRequest {
 			id: message.request_id.clone().into(),
@@ -433,11 +455,11 @@ impl SessionImpl {
 
 	/// When error has occured on another node.
 	pub fn on_session_error(&self, sender: &NodeId, message: &SigningSessionError) -> Result<(), Error> {
-		self.process_node_error(Some(&sender), &message.error)
+		self.process_node_error(Some(&sender), &Error::remote(message.code, message.error.clone()))
 	}
 
 	/// Process error from the other node.
-	fn process_node_error(&self, node: Option<&NodeId>, error: &String) -> Result<(), Error> {
+	fn process_node_error(&self, node: Option<&NodeId>, error: &Error) -> Result<(), Error> {
 		let mut data = self.data.lock();
 		match {
 			match node {
@@ -449,11 +471,13 @@ impl SessionImpl {
 			Ok(true) => {
 				let message_hash = data.message_hash.as_ref().cloned()
 					.expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when message_hash.is_some(); qed");
+				let key_version = data.key_version.as_ref().cloned()
+					.expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when key_version.is_some(); qed");
 				let joint_public_and_secret = data.generation_session.as_ref()
 					.expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when message_hash.is_some(); qed")
 					.joint_public_and_secret()
 					.expect("on_node_error returned true; this means that jobs must be REsent; this means that jobs already have been sent; jobs are sent when message_hash.is_some(); qed")?;
-				let disseminate_result = self.core.disseminate_jobs(&mut data.consensus_session, joint_public_and_secret.0, joint_public_and_secret.1, message_hash);
+				let disseminate_result = self.core.disseminate_jobs(&mut data.consensus_session, joint_public_and_secret.0, joint_public_and_secret.1, message_hash, key_version);
 				match disseminate_result {
 					Ok(()) => Ok(()),
 					Err(err) => {
@@ -483,14 +507,47 @@ impl ClusterSession for SessionImpl {
 			|| data.consensus_session.state() == ConsensusSessionState::Finished
 	}
 
+	fn is_failed(&self) -> bool {
+		self.data.lock().consensus_session.state() == ConsensusSessionState::Failed
+	}
+
 	fn on_node_timeout(&self, node: &NodeId) {
 		// ignore error, only state matters
-		let _ = self.process_node_error(Some(node), &Error::NodeDisconnected.into());
+		let _ = self.process_node_error(Some(node), &Error::NodeDisconnected);
 	}
 
 	fn on_session_timeout(&self) {
 		// ignore error, only state matters
-		let _ = self.process_node_error(None, &Error::NodeDisconnected.into());
+		let _ = self.process_node_error(None, &Error::NodeDisconnected);
+	}
+
+	fn cancel(&self) {
+		let mut data = self.data.lock();
+		let state = data.consensus_session.state();
+		if state == ConsensusSessionState::Failed || state == ConsensusSessionState::Finished {
+			return;
+		}
+
+		warn!("{}: signing session has been cancelled", &self.core.meta.self_node_id);
+
+		// key storage is never modified by signing session => nothing to restore
+		// do not bother processing send error, as we already processing error
+		let error = Message::Signing(SigningMessage::SigningSessionError(SigningSessionError {
+			session: self.core.meta.id.clone().into(),
+			sub_session: self.core.access_key.clone().into(),
+			session_nonce: self.core.nonce,
+			error: Error::SessionCancelled.into(),
+			code: Some(Error::SessionCancelled.code().into()),
+		}));
+		let _ = if self.core.meta.self_node_id == self.core.meta.master_node_id {
+			self.core.cluster.broadcast(error)
+		} else {
+			self.core.cluster.send(&self.core.meta.master_node_id, error)
+		};
+
+		data.consensus_session.on_session_cancelled();
+		data.result = Some(Err(Error::SessionCancelled));
+		self.core.completed.notify_all();
 	}
 }
 
@@ -534,21 +591,56 @@ impl Cluster for SessionKeyGenerationTransport {
 		debug_assert!(self.other_nodes_ids.contains(to));
 		self.cluster.send(to, self.map_message(message)?)
 	}
+
+	fn nodes(&self) -> BTreeSet<NodeId> {
+		self.cluster.nodes()
+	}
+
+	fn traffic(&self) -> (u64, u64) {
+		self.cluster.traffic()
+	}
+
+	fn on_message_received(&self, size: usize) -> Result<(), Error> {
+		self.cluster.on_message_received(size)
+	}
 }
 
 impl SessionCore {
-	pub fn signing_transport(&self) -> SigningJobTransport {
+	pub fn signing_transport(&self, key_version: H256) -> SigningJobTransport {
 		SigningJobTransport {
 			id: self.meta.id.clone(),
 			access_key: self.access_key.clone(),
 			nonce: self.nonce,
+			key_version: key_version,
 			cluster: self.cluster.clone()
 		}
 	}
 
-	pub fn disseminate_jobs(&self, consensus_session: &mut SigningConsensusSession, session_public: Public, session_secret_share: Secret, message_hash: H256) -> Result<(), Error> {
+	pub fn latest_key_version(&self) -> Result<H256, Error> {
+		self.key_storage.versions(&self.meta.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?
+			.into_iter()
+			.nth(0)
+			.ok_or(Error::KeyStorage("key share has no versions".into()))
+	}
+
+	pub fn filter_consensus_message(&self, key_version: Option<&H256>, message: &ConsensusMessage) -> Result<ConsensusMessage, Error> {
+		match *message {
+			// on master node: nodes, which do not have the negotiated key version, are treated as rejecting consensus
+			ConsensusMessage::ConfirmConsensusInitialization(ref message) if self.meta.self_node_id == self.meta.master_node_id => {
+				let key_version = key_version.ok_or(Error::InvalidStateForRequest)?;
+				Ok(ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
+					is_confirmed: message.is_confirmed && message.key_versions.iter().any(|v| &**v == key_version),
+					key_versions: message.key_versions.clone(),
+				}))
+			},
+			_ => Ok(message.clone()),
+		}
+	}
+
+	pub fn disseminate_jobs(&self, consensus_session: &mut SigningConsensusSession, session_public: Public, session_secret_share: Secret, message_hash: H256, key_version: H256) -> Result<(), Error> {
 		let signing_job = SigningJob::new_on_master(self.meta.self_node_id.clone(), self.key_share.clone(), session_public, session_secret_share, message_hash)?;
-		consensus_session.disseminate_jobs(signing_job, self.signing_transport())
+		consensus_session.disseminate_jobs(signing_job, self.signing_transport(key_version))
 	}
 }
 
@@ -568,12 +660,15 @@ impl JobTransport for SigningConsensusTransport {
 	}
 
 	fn send_partial_response(&self, node: &NodeId, response: bool) -> Result<(), Error> {
+		let key_versions = self.key_storage.versions(&self.id)
+			.map_err(|e| Error::KeyStorage(e.into()))?;
 		self.cluster.send(node, Message::Signing(SigningMessage::SigningConsensusMessage(SigningConsensusMessage {
 			session: self.id.clone().into(),
 			sub_session: self.access_key.clone().into(),
 			session_nonce: self.nonce,
 			message: ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 				is_confirmed: response,
+				key_versions: key_versions.into_iter().map(Into::into).collect(),
 			})
 		})))
 	}
@@ -591,6 +686,7 @@ impl JobTransport for SigningJobTransport {
 			request_id: request.id.into(),
 			message_hash: request.message_hash.into(),
 			nodes: request.other_nodes_ids.into_iter().map(Into::into).collect(),
+			key_version: self.key_version.clone().into(),
 		})))
 	}
 
@@ -613,7 +709,7 @@ mod tests {
 	use bigint::hash::H256;
 	use ethkey::{self, Random, Generator, Public, Secret, KeyPair};
 	use acl_storage::DummyAclStorage;
-	use key_server_cluster::{NodeId, DocumentKeyShare, SessionId, SessionMeta, Error, KeyStorage};
+	use key_server_cluster::{NodeId, DocumentKeyShare, SessionId, SessionMeta, Error, KeyStorage, DummyKeyStorage};
 	use key_server_cluster::cluster::tests::DummyCluster;
 	use key_server_cluster::generation_session::{Session as GenerationSession};
 	use key_server_cluster::generation_session::tests::MessageLoop as KeyGenerationMessageLoop;
@@ -657,6 +753,7 @@ mod tests {
 					},
 					access_key: "834cb736f02d9c968dfaf0c37658a1d86ff140554fc8b59c9fdad5a8cf810eec".parse().unwrap(),
 					key_share: gl_node.key_storage.get(&session_id).unwrap(),
+					key_storage: gl_node.key_storage.clone(),
 					acl_storage: acl_storage,
 					cluster: cluster.clone(),
 					nonce: 0,
@@ -791,7 +888,10 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Some(Random.generate().unwrap().public().clone()),
 				encrypted_point: Some(Random.generate().unwrap().public().clone()),
+				refresh_id: None,
+				metadata: Default::default(),
 			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
 			nonce: 0,
@@ -822,7 +922,10 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Some(Random.generate().unwrap().public().clone()),
 				encrypted_point: Some(Random.generate().unwrap().public().clone()),
+				refresh_id: None,
+				metadata: Default::default(),
 			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
 			nonce: 0,
@@ -853,7 +956,10 @@ mod tests {
 				secret_share: Random.generate().unwrap().secret().clone(),
 				common_point: Some(Random.generate().unwrap().public().clone()),
 				encrypted_point: Some(Random.generate().unwrap().public().clone()),
+				refresh_id: None,
+				metadata: Default::default(),
 			},
+			key_storage: Arc::new(DummyKeyStorage::default()),
 			acl_storage: Arc::new(DummyAclStorage::default()),
 			cluster: Arc::new(DummyCluster::new(self_node_id.clone())),
 			nonce: 0,
@@ -898,6 +1004,7 @@ mod tests {
 			session_nonce: 0,
 			message: ConsensusMessage::ConfirmConsensusInitialization(ConfirmConsensusInitialization {
 				is_confirmed: true,
+				key_versions: Vec::new(),
 			}),
 		}), Err(Error::InvalidStateForRequest));
 	}
@@ -952,6 +1059,7 @@ mod tests {
 			request_id: Secret::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap().into(),
 			message_hash: H256::default().into(),
 			nodes: Default::default(),
+			key_version: H256::default().into(),
 		}), Err(Error::InvalidStateForRequest));
 	}
 
@@ -965,6 +1073,7 @@ mod tests {
 			request_id: Secret::from_str("0000000000000000000000000000000000000000000000000000000000000001").unwrap().into(),
 			message_hash: H256::default().into(),
 			nodes: Default::default(),
+			key_version: H256::default().into(),
 		}), Err(Error::InvalidMessage));
 	}
 
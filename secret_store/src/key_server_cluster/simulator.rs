@@ -0,0 +1,530 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! In-memory simulation of the key server cluster.
+//! All nodes of simulated cluster are living in the same process && are exchanging messages over the simulated
+//! network, where delivery latency of every link could be configured && messages could be dropped. Latency is
+//! measured in virtual ticks, so simulations are deterministic && do not depend on the wall-clock time.
+//! Every operation is driven by the single master node && is running until simulated network has no messages to
+//! deliver. If master session has not been completed by that time, it is failed with timeout (as in real cluster).
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use ethkey::{Random, Generator, Signature};
+use key_storage::MemoryKeyStorage;
+use key_server_cluster::{KeyStorage, SessionMeta};
+use key_server_cluster::cluster::Cluster;
+use key_server_cluster::cluster_sessions::ClusterSession;
+use key_server_cluster::message::{Message, EncryptionMessage};
+use key_server_cluster::generation_session::{Session as GenerationSession, SessionImpl as GenerationSessionImpl,
+	SessionParams as GenerationSessionParams};
+use key_server_cluster::encryption_session::{Session as EncryptionSession, SessionImpl as EncryptionSessionImpl,
+	SessionParams as EncryptionSessionParams};
+use key_server_cluster::decryption_session::{SessionImpl as DecryptionSessionImpl, SessionParams as DecryptionSessionParams};
+
+pub use ethkey::Public;
+pub use acl_storage::DummyAclStorage;
+pub use key_server_cluster::{Error, NodeId, SessionId, EncryptedDocumentKeyShadow};
+pub use key_server_cluster::math::{EncryptedSecret, generate_random_point, encrypt_secret};
+
+/// Filter of dropped messages. Returns true if message, sent from the first node to the second node, must be dropped.
+pub type DropFilter = Box<Fn(&NodeId, &NodeId) -> bool + Send + Sync>;
+
+/// Simulated network, connecting all nodes of simulated cluster.
+pub struct Network {
+	/// Network data.
+	data: Mutex<NetworkData>,
+}
+
+/// Simulated network data.
+struct NetworkData {
+	/// Current virtual time (in ticks).
+	now: u64,
+	/// Sequence number of the next sent message. Is used to preserve order of messages with the same delivery time.
+	next_message_seq: u64,
+	/// Messages, waiting for delivery, ordered by delivery time.
+	messages: BTreeMap<(u64, u64), (NodeId, NodeId, Message)>,
+	/// Latency of links without explicitly configured latency (in ticks).
+	default_latency: u64,
+	/// Explicitly configured latency of links (in ticks).
+	links_latency: BTreeMap<(NodeId, NodeId), u64>,
+	/// Filter of dropped messages.
+	drop_filter: Option<DropFilter>,
+	/// Number of delivered messages.
+	delivered_messages: usize,
+	/// Number of dropped messages.
+	dropped_messages: usize,
+}
+
+/// Cluster of the single simulated node.
+pub struct SimulatedCluster {
+	/// This node id.
+	self_node_id: NodeId,
+	/// All nodes of the simulated cluster.
+	nodes: BTreeSet<NodeId>,
+	/// Simulated network.
+	network: Arc<Network>,
+}
+
+/// Single node of the simulated cluster.
+struct SimulatedNode {
+	/// Node cluster.
+	cluster: Arc<SimulatedCluster>,
+	/// Node key storage.
+	key_storage: Arc<MemoryKeyStorage>,
+	/// Node ACL storage.
+	acl_storage: Arc<DummyAclStorage>,
+}
+
+/// Simulated key server cluster.
+pub struct Simulator {
+	/// Simulated network.
+	network: Arc<Network>,
+	/// All nodes of the simulated cluster.
+	nodes: BTreeMap<NodeId, SimulatedNode>,
+	/// Nonce of the next session.
+	next_session_nonce: Mutex<u64>,
+}
+
+/// Session, which could be run over simulated network.
+trait SimulatedSession: ClusterSession {
+	/// Process message, received from the given node.
+	fn on_message(&self, sender: &NodeId, message: &Message) -> Result<(), Error>;
+}
+
+impl Network {
+	/// Create new network without latency && drops.
+	fn new() -> Self {
+		Network {
+			data: Mutex::new(NetworkData {
+				now: 0,
+				next_message_seq: 0,
+				messages: BTreeMap::new(),
+				default_latency: 0,
+				links_latency: BTreeMap::new(),
+				drop_filter: None,
+				delivered_messages: 0,
+				dropped_messages: 0,
+			}),
+		}
+	}
+
+	/// Get current virtual time (in ticks).
+	pub fn now(&self) -> u64 {
+		self.data.lock().now
+	}
+
+	/// Get number of delivered messages.
+	pub fn delivered_messages(&self) -> usize {
+		self.data.lock().delivered_messages
+	}
+
+	/// Get number of dropped messages.
+	pub fn dropped_messages(&self) -> usize {
+		self.data.lock().dropped_messages
+	}
+
+	/// Set latency of all links without explicitly configured latency.
+	pub fn set_default_latency(&self, latency: u64) {
+		self.data.lock().default_latency = latency;
+	}
+
+	/// Set latency of the link from the first node to the second node.
+	pub fn set_link_latency(&self, from: NodeId, to: NodeId, latency: u64) {
+		self.data.lock().links_latency.insert((from, to), latency);
+	}
+
+	/// Set filter of dropped messages. Messages, sent before the filter is set, are not affected.
+	pub fn set_drop_filter<F>(&self, filter: F) where F: Fn(&NodeId, &NodeId) -> bool + Send + Sync + 'static {
+		self.data.lock().drop_filter = Some(Box::new(filter));
+	}
+
+	/// Remove filter of dropped messages.
+	pub fn reset_drop_filter(&self) {
+		self.data.lock().drop_filter = None;
+	}
+
+	/// Send message from the first node to the second node.
+	fn send(&self, from: &NodeId, to: &NodeId, message: Message) {
+		let mut data = self.data.lock();
+		if data.drop_filter.as_ref().map(|f| f(from, to)).unwrap_or(false) {
+			data.dropped_messages += 1;
+			return;
+		}
+
+		let latency = data.links_latency.get(&(from.clone(), to.clone())).cloned().unwrap_or(data.default_latency);
+		let delivery_time = data.now + latency;
+		let message_seq = data.next_message_seq;
+		data.next_message_seq += 1;
+		data.messages.insert((delivery_time, message_seq), (from.clone(), to.clone(), message));
+	}
+
+	/// Take message with the earliest delivery time && advance virtual time to this delivery time.
+	fn take_message(&self) -> Option<(NodeId, NodeId, Message)> {
+		let mut data = self.data.lock();
+		let key = match data.messages.keys().next() {
+			Some(key) => key.clone(),
+			None => return None,
+		};
+
+		data.now = key.0;
+		data.delivered_messages += 1;
+		data.messages.remove(&key)
+	}
+
+	/// Forget all messages, waiting for delivery.
+	fn clear(&self) {
+		self.data.lock().messages.clear();
+	}
+}
+
+impl SimulatedCluster {
+	/// Get this node id.
+	pub fn node(&self) -> &NodeId {
+		&self.self_node_id
+	}
+}
+
+impl Cluster for SimulatedCluster {
+	fn broadcast(&self, message: Message) -> Result<(), Error> {
+		for node in self.nodes.iter().filter(|n| *n != &self.self_node_id) {
+			self.network.send(&self.self_node_id, node, message.clone());
+		}
+		Ok(())
+	}
+
+	fn send(&self, to: &NodeId, message: Message) -> Result<(), Error> {
+		debug_assert!(&self.self_node_id != to);
+		if !self.nodes.contains(to) {
+			return Err(Error::NodeDisconnected);
+		}
+
+		self.network.send(&self.self_node_id, to, message);
+		Ok(())
+	}
+
+	fn nodes(&self) -> BTreeSet<NodeId> {
+		self.nodes.clone()
+	}
+
+	fn traffic(&self) -> (u64, u64) {
+		(0, 0)
+	}
+
+	fn on_message_received(&self, _size: usize) -> Result<(), Error> {
+		Ok(())
+	}
+}
+
+impl Simulator {
+	/// Create new simulated cluster of given number of nodes.
+	pub fn new(nodes_num: usize) -> Self {
+		let network = Arc::new(Network::new());
+		let nodes_ids: BTreeSet<_> = (0..nodes_num).map(|_| Random.generate().expect("generating random key pair never fails; qed").public().clone()).collect();
+		let nodes = nodes_ids.iter().map(|node_id| (node_id.clone(), SimulatedNode {
+			cluster: Arc::new(SimulatedCluster {
+				self_node_id: node_id.clone(),
+				nodes: nodes_ids.clone(),
+				network: network.clone(),
+			}),
+			key_storage: Arc::new(MemoryKeyStorage::default()),
+			acl_storage: Arc::new(DummyAclStorage::default()),
+		})).collect();
+
+		Simulator {
+			network: network,
+			nodes: nodes,
+			next_session_nonce: Mutex::new(0),
+		}
+	}
+
+	/// Get simulated network.
+	pub fn network(&self) -> &Arc<Network> {
+		&self.network
+	}
+
+	/// Get ids of all nodes of the simulated cluster.
+	pub fn nodes(&self) -> Vec<NodeId> {
+		self.nodes.keys().cloned().collect()
+	}
+
+	/// Get key storage of the given node.
+	pub fn key_storage(&self, node: &NodeId) -> Option<Arc<KeyStorage>> {
+		self.nodes.get(node).map(|n| n.key_storage.clone() as Arc<KeyStorage>)
+	}
+
+	/// Get ACL storage of the given node. All requesters have access to all keys, until prohibited.
+	pub fn acl_storage(&self, node: &NodeId) -> Option<Arc<DummyAclStorage>> {
+		self.nodes.get(node).map(|n| n.acl_storage.clone())
+	}
+
+	/// Generate server key on all nodes of the simulated cluster.
+	pub fn generate_server_key(&self, master: &NodeId, session_id: SessionId, author: Public, threshold: usize) -> Result<Public, Error> {
+		let nonce = self.next_session_nonce();
+		let sessions: BTreeMap<_, _> = self.nodes.iter().map(|(node_id, node)| (node_id.clone(), GenerationSessionImpl::new(GenerationSessionParams {
+			id: session_id.clone(),
+			self_node_id: node_id.clone(),
+			key_storage: Some(node.key_storage.clone()),
+			cluster: node.cluster.clone(),
+			nonce: Some(nonce),
+		}))).collect();
+
+		let master_session = sessions.get(master).ok_or(Error::InvalidNodeId)?;
+		self.network.clear();
+		master_session.initialize(author, threshold, self.nodes.keys().cloned().collect())?;
+		self.run_sessions(master, &sessions)?;
+
+		master_session.joint_public_and_secret()
+			.unwrap_or(Err(Error::NodeDisconnected))
+			.map(|(public, _)| public)
+	}
+
+	/// Store document key, encrypted with previously generated server key, on all key holders.
+	pub fn store_document_key(&self, master: &NodeId, session_id: SessionId, requester_signature: Signature, common_point: Public, encrypted_point: Public) -> Result<(), Error> {
+		if !self.nodes.contains_key(master) {
+			return Err(Error::InvalidNodeId);
+		}
+
+		let nonce = self.next_session_nonce();
+		let mut sessions = BTreeMap::new();
+		for (node_id, node) in self.nodes.iter() {
+			// nodes without key share are not participating in the session
+			let encrypted_data = match node.key_storage.get(&session_id) {
+				Ok(encrypted_data) => encrypted_data,
+				Err(_) => continue,
+			};
+			sessions.insert(node_id.clone(), EncryptionSessionImpl::new(EncryptionSessionParams {
+				id: session_id.clone(),
+				self_node_id: node_id.clone(),
+				encrypted_data: encrypted_data,
+				key_storage: node.key_storage.clone(),
+				cluster: node.cluster.clone(),
+				nonce: nonce,
+			})?);
+		}
+
+		let master_session = sessions.get(master).ok_or(Error::InvalidSessionId)?;
+		self.network.clear();
+		master_session.initialize(requester_signature, common_point, encrypted_point)?;
+		self.run_sessions(master, &sessions)?;
+
+		master_session.wait(None)
+	}
+
+	/// Restore previously stored document key (or its shadow, if is_shadow_decryption is true).
+	pub fn restore_document_key(&self, master: &NodeId, session_id: SessionId, requester_signature: Signature, is_shadow_decryption: bool) -> Result<EncryptedDocumentKeyShadow, Error> {
+		if !self.nodes.contains_key(master) {
+			return Err(Error::InvalidNodeId);
+		}
+
+		let nonce = self.next_session_nonce();
+		let access_key = Random.generate()?.secret().clone();
+		let mut sessions = BTreeMap::new();
+		for (node_id, node) in self.nodes.iter() {
+			// nodes without key share are not participating in the session
+			let key_share = match node.key_storage.get(&session_id) {
+				Ok(key_share) => key_share,
+				Err(_) => continue,
+			};
+			sessions.insert(node_id.clone(), DecryptionSessionImpl::new(DecryptionSessionParams {
+				meta: SessionMeta {
+					id: session_id.clone(),
+					master_node_id: master.clone(),
+					self_node_id: node_id.clone(),
+					threshold: key_share.threshold,
+				},
+				access_key: access_key.clone(),
+				key_share: key_share,
+				key_storage: node.key_storage.clone(),
+				acl_storage: node.acl_storage.clone(),
+				cluster: node.cluster.clone(),
+				nonce: nonce,
+			}, if node_id == master { Some(requester_signature.clone()) } else { None })?);
+		}
+
+		let master_session = sessions.get(master).ok_or(Error::InvalidSessionId)?;
+		self.network.clear();
+		master_session.initialize(is_shadow_decryption)?;
+		self.run_sessions(master, &sessions)?;
+
+		master_session.decrypted_secret()
+			.unwrap_or(Err(Error::NodeDisconnected))
+	}
+
+	/// Get nonce of the next session.
+	fn next_session_nonce(&self) -> u64 {
+		let mut next_session_nonce = self.next_session_nonce.lock();
+		*next_session_nonce += 1;
+		*next_session_nonce
+	}
+
+	/// Deliver messages of sessions until network has no messages to deliver. Fails master session with timeout, if it is not completed.
+	fn run_sessions<S: SimulatedSession>(&self, master: &NodeId, sessions: &BTreeMap<NodeId, S>) -> Result<(), Error> {
+		// messages, which are received too early && are waiting for processing
+		let mut deferred_messages: VecDeque<(NodeId, NodeId, Message)> = VecDeque::new();
+		while let Some(message) = self.network.take_message() {
+			let mut messages = VecDeque::new();
+			messages.push_back(message);
+			while let Some((from, to, message)) = messages.pop_front() {
+				// message, addressed to the node, which is not participating in the session, is lost
+				let session = match sessions.get(&to) {
+					Some(session) => session,
+					None => continue,
+				};
+
+				match session.on_message(&from, &message) {
+					Ok(()) => {
+						// retry messages, which have been received by this node too early
+						let (retried_messages, still_deferred_messages): (VecDeque<_>, VecDeque<_>) = deferred_messages.into_iter()
+							.partition(|m| m.1 == to);
+						deferred_messages = still_deferred_messages;
+						messages.extend(retried_messages);
+					},
+					Err(Error::TooEarlyForRequest) => deferred_messages.push_back((from, to, message)),
+					Err(err) => return Err(err),
+				}
+			}
+		}
+
+		let master_session = sessions.get(master).expect("checked by caller; qed");
+		if !master_session.is_finished() {
+			master_session.on_session_timeout();
+		}
+
+		Ok(())
+	}
+}
+
+impl SimulatedSession for GenerationSessionImpl {
+	fn on_message(&self, sender: &NodeId, message: &Message) -> Result<(), Error> {
+		match *message {
+			Message::Generation(ref message) => self.process_message(sender, message),
+			_ => Err(Error::InvalidMessage),
+		}
+	}
+}
+
+impl SimulatedSession for EncryptionSessionImpl {
+	fn on_message(&self, sender: &NodeId, message: &Message) -> Result<(), Error> {
+		match *message {
+			Message::Encryption(EncryptionMessage::InitializeEncryptionSession(ref message)) =>
+				self.on_initialize_session(sender.clone(), message),
+			Message::Encryption(EncryptionMessage::ConfirmEncryptionInitialization(ref message)) =>
+				self.on_confirm_initialization(sender.clone(), message),
+			Message::Encryption(EncryptionMessage::EncryptionSessionError(ref message)) =>
+				self.on_session_error(sender.clone(), message),
+			Message::Encryption(EncryptionMessage::RequestRekeyingPublicShare(ref message)) =>
+				self.on_request_rekeying_public_share(sender.clone(), message),
+			Message::Encryption(EncryptionMessage::RekeyingPublicShare(ref message)) =>
+				self.on_rekeying_public_share(sender.clone(), message),
+			Message::Encryption(EncryptionMessage::CommitRekeying(ref message)) =>
+				self.on_commit_rekeying(sender.clone(), message),
+			_ => Err(Error::InvalidMessage),
+		}
+	}
+}
+
+impl SimulatedSession for DecryptionSessionImpl {
+	fn on_message(&self, sender: &NodeId, message: &Message) -> Result<(), Error> {
+		match *message {
+			Message::Decryption(ref message) => self.process_message(sender, message),
+			_ => Err(Error::InvalidMessage),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ethkey::{self, Random, Generator};
+	use key_server_cluster::{Error, SessionId};
+	use key_server_cluster::math;
+	use super::Simulator;
+
+	fn generate_and_restore_document_key(simulator: &Simulator, session_id: SessionId, threshold: usize) -> Result<(), Error> {
+		let nodes = simulator.nodes();
+		let requester = Random.generate().unwrap();
+		let signature = ethkey::sign(requester.secret(), &session_id).unwrap();
+
+		let server_public = simulator.generate_server_key(&nodes[0], session_id.clone(), requester.public().clone(), threshold)?;
+		let document_key = math::generate_random_point().unwrap();
+		let encrypted_document_key = math::encrypt_secret(&document_key, &server_public).unwrap();
+		simulator.store_document_key(&nodes[1], session_id.clone(), signature.clone(),
+			encrypted_document_key.common_point, encrypted_document_key.encrypted_point)?;
+		let restored_document_key = simulator.restore_document_key(&nodes[nodes.len() - 1], session_id, signature, false)?;
+		assert_eq!(restored_document_key.decrypted_secret, document_key);
+		Ok(())
+	}
+
+	#[test]
+	fn simulated_cluster_restores_document_key() {
+		let simulator = Simulator::new(5);
+		generate_and_restore_document_key(&simulator, SessionId::from(42), 2).unwrap();
+		assert_eq!(simulator.network().now(), 0);
+		assert_eq!(simulator.network().dropped_messages(), 0);
+		assert!(simulator.network().delivered_messages() > 0);
+		for node in simulator.nodes() {
+			assert!(simulator.key_storage(&node).unwrap().contains(&SessionId::from(42)));
+		}
+	}
+
+	#[test]
+	fn simulated_cluster_restores_document_key_when_links_have_latency() {
+		let simulator = Simulator::new(5);
+		let nodes = simulator.nodes();
+		simulator.network().set_default_latency(10);
+		for (i, from) in nodes.iter().enumerate() {
+			for (j, to) in nodes.iter().enumerate() {
+				simulator.network().set_link_latency(from.clone(), to.clone(), ((i * 7 + j * 13) % 50) as u64);
+			}
+		}
+
+		generate_and_restore_document_key(&simulator, SessionId::from(42), 2).unwrap();
+		assert!(simulator.network().now() > 0);
+	}
+
+	#[test]
+	fn simulated_cluster_fails_when_messages_to_node_are_dropped() {
+		let simulator = Simulator::new(3);
+		let dropping_node = simulator.nodes()[2].clone();
+		simulator.network().set_drop_filter(move |_, to| to == &dropping_node);
+
+		assert_eq!(generate_and_restore_document_key(&simulator, SessionId::from(42), 1), Err(Error::NodeDisconnected));
+		assert!(simulator.network().dropped_messages() > 0);
+
+		// when network is recovered, cluster is working again
+		simulator.network().reset_drop_filter();
+		generate_and_restore_document_key(&simulator, SessionId::from(43), 1).unwrap();
+	}
+
+	#[test]
+	fn simulated_cluster_refuses_to_restore_document_key_to_prohibited_requester() {
+		let simulator = Simulator::new(3);
+		let nodes = simulator.nodes();
+		let session_id = SessionId::from(42);
+		let requester = Random.generate().unwrap();
+		let signature = ethkey::sign(requester.secret(), &session_id).unwrap();
+		let server_public = simulator.generate_server_key(&nodes[0], session_id.clone(), requester.public().clone(), 1).unwrap();
+		let encrypted_document_key = math::encrypt_secret(&math::generate_random_point().unwrap(), &server_public).unwrap();
+		simulator.store_document_key(&nodes[0], session_id.clone(), signature.clone(),
+			encrypted_document_key.common_point, encrypted_document_key.encrypted_point).unwrap();
+
+		for node in &nodes {
+			simulator.acl_storage(node).unwrap().prohibit(requester.public().clone(), session_id.clone());
+		}
+		assert!(simulator.restore_document_key(&nodes[0], session_id, signature, false).is_err());
+	}
+}
@@ -15,7 +15,7 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::sync::{Arc, Weak};
-use std::net::SocketAddr;
+use std::net::{SocketAddr, IpAddr, ToSocketAddrs};
 use std::collections::BTreeMap;
 use futures::{future, Future};
 use parking_lot::Mutex;
@@ -58,8 +58,8 @@ struct CachedContract {
 	client: Weak<Client>,
 	/// Contract address.
 	contract_addr: Option<Address>,
-	/// Active set of key servers.
-	key_servers: BTreeMap<Public, SocketAddr>,
+	/// Active set of key servers. Addresses are resolved when the set is read, so that hostnames are re-resolved on reconnect.
+	key_servers: BTreeMap<Public, NodeAddress>,
 }
 
 impl OnChainKeyServerSet {
@@ -99,13 +99,7 @@ impl CachedContract {
 		Ok(CachedContract {
 			client: Arc::downgrade(client),
 			contract_addr: None,
-			key_servers: key_servers.into_iter()
-				.map(|(p, addr)| {
-					let addr = format!("{}:{}", addr.address, addr.port).parse()
-						.map_err(|err| Error::Internal(format!("error parsing node address: {}", err)))?;
-					Ok((p, addr))
-				})
-				.collect::<Result<BTreeMap<_, _>, Error>>()?,
+			key_servers: key_servers,
 		})
 	}
 
@@ -142,7 +136,15 @@ impl CachedContract {
 	}
 
 	pub fn get(&self) -> BTreeMap<Public, SocketAddr> {
-		self.key_servers.clone()
+		self.key_servers.iter()
+			.filter_map(|(public, address)| match resolve_node_address(&address.address, address.port) {
+				Ok(address) => Some((public.clone(), address)),
+				Err(err) => {
+					warn!(target: "secretstore_net", "failed to resolve address of key server {}: {}", public, err);
+					None
+				},
+			})
+			.collect()
 	}
 
 	fn read_from_registry(&mut self, client: &Client, new_contract_address: Option<Address>) {
@@ -163,13 +165,13 @@ impl CachedContract {
 					.and_then(|p| if p.len() == 64 { Ok(Public::from_slice(&p)) } else { Err(format!("Invalid public length {}", p.len())) });
 				let key_server_ip = contract.get_key_server_address(
 					|a, d| future::done(client.call_contract(BlockId::Latest, a, d)), key_server).wait()
-					.and_then(|a| a.parse().map_err(|e| format!("Invalid ip address: {}", e)));
+					.and_then(|a| parse_node_address(&a));
 
 				// only add successfully parsed nodes
 				match (key_server_public, key_server_ip) {
 					(Ok(key_server_public), Ok(key_server_ip)) => { key_servers.insert(key_server_public, key_server_ip); },
 					(Err(public_err), _) => warn!(target: "secretstore_net", "received invalid public from key server set contract: {}", public_err),
-					(_, Err(ip_err)) => warn!(target: "secretstore_net", "received invalid IP from key server set contract: {}", ip_err),
+					(_, Err(ip_err)) => warn!(target: "secretstore_net", "received invalid address from key server set contract: {}", ip_err),
 				}
 			}
 			key_servers
@@ -179,12 +181,41 @@ impl CachedContract {
 	}
 }
 
+/// Parse node address in `host:port` form. IPv6 addresses must be enclosed in square brackets: `[::1]:8083`.
+pub fn parse_node_address(address: &str) -> Result<NodeAddress, String> {
+	let separator = address.rfind(':').ok_or_else(|| format!("Invalid node address: {}", address))?;
+	let (host, port) = (&address[..separator], &address[separator + 1..]);
+	let is_bracketed = host.starts_with('[') && host.ends_with(']');
+	let host = if is_bracketed { &host[1..host.len() - 1] } else { host };
+	if host.is_empty() || host.contains('[') || host.contains(']') || (host.contains(':') && !is_bracketed) {
+		return Err(format!("Invalid node address: {}", address));
+	}
+
+	Ok(NodeAddress {
+		address: host.into(),
+		port: port.parse().map_err(|e| format!("Invalid port in node address: {}. Error: {}", address, e))?,
+	})
+}
+
+/// Resolve node address. Address is either an IPv4/IPv6 address (IPv6 optionally enclosed in square brackets), or a hostname.
+pub fn resolve_node_address(address: &str, port: u16) -> Result<SocketAddr, Error> {
+	let ip_address = if address.starts_with('[') && address.ends_with(']') { &address[1..address.len() - 1] } else { address };
+	if let Ok(ip_address) = ip_address.parse::<IpAddr>() {
+		return Ok(SocketAddr::new(ip_address, port));
+	}
+
+	(address, port).to_socket_addrs()
+		.map_err(|err| Error::Internal(format!("error resolving node address {}: {}", address, err)))?
+		.next()
+		.ok_or_else(|| Error::Internal(format!("error resolving node address {}: no addresses found", address)))
+}
+
 #[cfg(test)]
 pub mod tests {
 	use std::collections::BTreeMap;
 	use std::net::SocketAddr;
 	use ethkey::Public;
-	use super::KeyServerSet;
+	use super::{KeyServerSet, parse_node_address, resolve_node_address};
 
 	#[derive(Default)]
 	pub struct MapKeyServerSet {
@@ -204,4 +235,27 @@ pub mod tests {
 			self.nodes.clone()
 		}
 	}
+
+	#[test]
+	fn node_address_is_parsed() {
+		let address = parse_node_address("127.0.0.1:8083").unwrap();
+		assert_eq!((address.address.as_str(), address.port), ("127.0.0.1", 8083));
+		let address = parse_node_address("[::1]:8083").unwrap();
+		assert_eq!((address.address.as_str(), address.port), ("::1", 8083));
+		let address = parse_node_address("ss-0.secretstore.svc.cluster.local:8083").unwrap();
+		assert_eq!((address.address.as_str(), address.port), ("ss-0.secretstore.svc.cluster.local", 8083));
+
+		assert!(parse_node_address("127.0.0.1").is_err());
+		assert!(parse_node_address("::1:8083").is_err());
+		assert!(parse_node_address(":8083").is_err());
+		assert!(parse_node_address("127.0.0.1:port").is_err());
+	}
+
+	#[test]
+	fn node_address_is_resolved() {
+		assert_eq!(resolve_node_address("127.0.0.1", 8083), Ok("127.0.0.1:8083".parse::<SocketAddr>().unwrap()));
+		assert_eq!(resolve_node_address("::1", 8083), Ok("[::1]:8083".parse::<SocketAddr>().unwrap()));
+		assert_eq!(resolve_node_address("[::1]", 8083), Ok("[::1]:8083".parse::<SocketAddr>().unwrap()));
+		assert!(resolve_node_address("localhost", 8083).unwrap().ip().is_loopback());
+	}
 }
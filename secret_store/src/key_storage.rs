@@ -14,16 +14,44 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::fs;
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use parking_lot::{Mutex, RwLock};
 use serde_json;
-use ethkey::{Secret, Public};
-use util::Database;
-use types::all::{Error, ServiceConfiguration, ServerKeyId, NodeId};
-use serialization::{SerializablePublic, SerializableSecret};
+use ethcrypto::ecies::{encrypt_single_message, decrypt_single_message};
+use ethkey::{Secret, Public, KeyPair};
+use bigint::hash::H256;
+use hash::keccak;
+use util::{Database, DBTransaction};
+use traits::NodeKeyPair;
+use audit_log::unix_timestamp;
+use types::all::{Error, ServerKeyId, NodeId, KeyBackupReport};
+use serialization::{SerializablePublic, SerializableSecret, SerializableH256};
 
 /// Key of version value.
 const DB_META_KEY_VERSION: &'static [u8; 7] = b"version";
+/// Prefix of maximal session nonce keys.
+const DB_SESSION_NONCE_PREFIX: &'static [u8; 6] = b"nonce:";
+/// Prefix of deleted keys tombstones.
+const DB_TOMBSTONE_PREFIX: &'static [u8; 10] = b"tombstone:";
+/// Prefix of removed key shares, which are kept until purged.
+const DB_REMOVED_PREFIX: &'static [u8; 8] = b"removed:";
+/// Current version of the database.
+const CURRENT_VERSION: u8 = 4;
+/// Name of the file, used by file key storage.
+const FILE_KEY_STORAGE_NAME: &'static str = "key_shares";
+/// Magic bytes, starting every key storage backup.
+const BACKUP_MAGIC: &'static [u8; 8] = b"SSBACKUP";
+/// Current version of the key storage backup format.
+const BACKUP_VERSION: u8 = 1;
+/// Max size of single encrypted key storage backup entry.
+const MAX_BACKUP_ENTRY_SIZE: u32 = 16 * 1024 * 1024;
+/// Max number of key share versions, kept by key storage. Older versions are removed when new version is committed.
+/// The previous version is kept, so that nodes, which have failed to commit the new version, could still agree on common version.
+const MAX_KEY_SHARE_VERSIONS: usize = 2;
 
 /// Encrypted key share, stored by key storage on the single key server.
 #[derive(Debug, Clone, PartialEq)]
@@ -40,23 +68,147 @@ pub struct DocumentKeyShare {
 	pub common_point: Option<Public>,
 	/// Encrypted point.
 	pub encrypted_point: Option<Public>,
+	/// Id of the share refresh, which has produced this key share. None if key shares have never been refreshed.
+	pub refresh_id: Option<H256>,
+	/// Key metadata.
+	pub metadata: KeyMetadata,
 }
 
+/// Optional key metadata, stored alongside the key share. Metadata is local to the key server && is never
+/// sent to other nodes => shares, received in admin sessions, come without metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyMetadata {
+	/// Time when the key has been generated (seconds since unix epoch).
+	pub created: Option<u64>,
+	/// Human-readable label, assigned by the key server operator.
+	pub label: Option<String>,
+	/// Requester, which has triggered key generation.
+	pub requester: Option<Public>,
+	/// Last time when the key has been used to serve request to this key server (seconds since unix epoch).
+	pub last_accessed: Option<u64>,
+}
+
+/// Iterator over latest versions of stored document encryption keys.
+pub type KeyStorageIterator<'a> = Box<Iterator<Item=Result<(ServerKeyId, DocumentKeyShare), Error>> + 'a>;
+
+/// Single entry of the key storage snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyStorageSnapshotEntry {
+	/// Document encryption key with all its versions, starting from the latest one.
+	Key(ServerKeyId, Vec<DocumentKeyShare>),
+	/// Soft-removed document encryption key with all its versions, paired with the time of removal (seconds since unix epoch).
+	RemovedKey(ServerKeyId, u64, Vec<DocumentKeyShare>),
+	/// Id of deleted document encryption key.
+	Tombstone(ServerKeyId),
+	/// Maximal nonce of sessions of given kind, started by given node.
+	SessionNonce(NodeId, String, u64),
+}
+
+/// Iterator over entries of consistent key storage snapshot.
+pub type KeyStorageSnapshot<'a> = Box<Iterator<Item=Result<KeyStorageSnapshotEntry, Error>> + 'a>;
+
 /// Document encryption keys storage
 pub trait KeyStorage: Send + Sync {
 	/// Insert document encryption key
 	fn insert(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error>;
-	/// Update document encryption key
+	/// Update document encryption key. The previous version of the key is kept, older versions are removed. Metadata of existing key is kept
 	fn update(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error>;
-	/// Get document encryption key
+	/// Get latest version of document encryption key
 	fn get(&self, document: &ServerKeyId) -> Result<DocumentKeyShare, Error>;
+	/// Get given version of document encryption key
+	fn get_version(&self, document: &ServerKeyId, version: &H256) -> Result<DocumentKeyShare, Error>;
+	/// Get all versions of document encryption key, starting from the latest one
+	fn versions(&self, document: &ServerKeyId) -> Result<Vec<H256>, Error>;
+	/// Replace metadata of document encryption key. Key share versions are not changed
+	fn set_metadata(&self, document: &ServerKeyId, metadata: KeyMetadata) -> Result<(), Error>;
+	/// Remove all versions of document encryption key
+	fn remove(&self, document: &ServerKeyId) -> Result<(), Error>;
+	/// Remove all versions of document encryption key, keeping them aside until purged, so that the key could be restored
+	fn soft_remove(&self, document: &ServerKeyId) -> Result<(), Error>;
+	/// Soft-remove all versions of document encryption key && remember that the key has been deleted
+	fn tombstone(&self, document: &ServerKeyId) -> Result<(), Error>;
+	/// Check if document encryption key has been deleted
+	fn is_tombstoned(&self, document: &ServerKeyId) -> bool;
+	/// Get ids of soft-removed document encryption keys, paired with the time of removal (seconds since unix epoch)
+	fn removed_documents(&self) -> Result<Vec<(ServerKeyId, u64)>, Error>;
+	/// Restore all versions of soft-removed document encryption key. Deleted key is not treated as deleted anymore
+	fn restore_removed(&self, document: &ServerKeyId) -> Result<(), Error>;
+	/// Purge soft-removed document encryption keys, which have been removed before given time (seconds since unix epoch).
+	/// Returns ids of purged keys
+	fn purge_removed(&self, removed_before: u64) -> Result<Vec<ServerKeyId>, Error>;
 	/// Check if storage contains document encryption key
 	fn contains(&self, document: &ServerKeyId) -> bool;
+	/// Get ids of all stored document encryption keys
+	fn documents(&self) -> Result<Vec<ServerKeyId>, Error>;
+	/// Iterate over latest versions of stored document encryption keys in ascending order of their ids,
+	/// starting after given id (or from the first key, if None)
+	fn iter<'a>(&'a self, after: Option<&ServerKeyId>) -> KeyStorageIterator<'a>;
+	/// Take consistent snapshot of the whole storage contents. Changes, made after the snapshot has been taken, are not
+	/// seen when iterating over the snapshot
+	fn snapshot<'a>(&'a self) -> KeyStorageSnapshot<'a>;
+	/// Get maximal nonce of sessions of given kind, started by given node
+	fn max_session_nonce(&self, node: &NodeId, session_kind: &str) -> Result<Option<u64>, Error>;
+	/// Set maximal nonce of sessions of given kind, started by given node
+	fn set_max_session_nonce(&self, node: &NodeId, session_kind: &str, nonce: u64) -> Result<(), Error>;
+	/// Write all snapshot entries to the empty storage at once. Either all entries are written, or none of them.
+	/// Fails if storage contains any keys, soft-removed keys, tombstones or session nonces
+	fn restore(&self, entries: Vec<KeyStorageSnapshotEntry>) -> Result<(), Error>;
 }
 
 /// Persistent document encryption keys storage
 pub struct PersistentKeyStorage {
 	db: Database,
+	/// Key pair, used to encrypt key shares at rest.
+	encryption_key: KeyPair,
+	/// Lock, which is held while key storage is modified, so that read-modify-write operations are never interleaved.
+	write_lock: Mutex<()>,
+}
+
+/// Document encryption keys storage, kept in the single encrypted file
+pub struct FileKeyStorage {
+	/// Path to the storage file.
+	path: PathBuf,
+	/// Key pair, used to encrypt storage file.
+	encryption_key: KeyPair,
+	/// Storage file contents.
+	data: RwLock<FileKeyStorageData>,
+}
+
+/// In-memory document encryption keys storage. All keys are lost when storage is dropped
+#[derive(Default)]
+pub struct MemoryKeyStorage {
+	keys: RwLock<HashMap<ServerKeyId, Vec<DocumentKeyShare>>>,
+	removed: RwLock<HashMap<ServerKeyId, (u64, Vec<DocumentKeyShare>)>>,
+	tombstones: RwLock<HashSet<ServerKeyId>>,
+	session_nonces: RwLock<HashMap<(NodeId, String), u64>>,
+}
+
+/// Contents of the file key storage.
+#[derive(Default, Clone)]
+struct FileKeyStorageData {
+	/// Key shares with all their versions.
+	keys: BTreeMap<ServerKeyId, SerializableDocumentKeyShareV3>,
+	/// Soft-removed key shares with all their versions.
+	removed: BTreeMap<ServerKeyId, SerializableRemovedKeyShare>,
+	/// Ids of deleted keys.
+	tombstones: BTreeSet<ServerKeyId>,
+	/// Maximal session nonces.
+	session_nonces: BTreeMap<(NodeId, String), u64>,
+}
+
+/// Contents of the file key storage, as it is stored in the file.
+#[derive(Serialize, Deserialize)]
+struct SerializableFileKeyStorageData {
+	/// Key shares with all their versions. V2 key shares are read as V3 key shares without metadata.
+	keys: Vec<(SerializableH256, SerializableDocumentKeyShareV3)>,
+	/// Soft-removed key shares with all their versions.
+	#[serde(default)]
+	removed: Vec<(SerializableH256, SerializableRemovedKeyShare)>,
+	/// Ids of deleted keys.
+	#[serde(default)]
+	tombstones: Vec<SerializableH256>,
+	/// Maximal session nonces.
+	session_nonces: Vec<(SerializablePublic, String, u64)>,
 }
 
 /// V0 of encrypted key share, as it is stored by key storage on the single key server.
@@ -91,152 +243,1298 @@ struct SerializableDocumentKeyShareV1 {
 	pub encrypted_point: Option<SerializablePublic>,
 }
 
+/// V2 of encrypted key share, as it is stored by key storage on the single key server.
+#[derive(Serialize, Deserialize)]
+struct SerializableDocumentKeyShareV2 {
+	/// Author of the entry.
+	pub author: SerializablePublic,
+	/// Decryption threshold (at least threshold + 1 nodes are required to decrypt data).
+	pub threshold: usize,
+	/// Common (shared) encryption point.
+	pub common_point: Option<SerializablePublic>,
+	/// Encrypted point.
+	pub encrypted_point: Option<SerializablePublic>,
+	/// Versions of key share, starting from the latest one.
+	pub versions: Vec<SerializableDocumentKeyShareVersionV2>,
+}
+
+/// V2 of single key share version, as it is stored by key storage on the single key server.
+#[derive(Clone, Serialize, Deserialize)]
+struct SerializableDocumentKeyShareVersionV2 {
+	/// Version hash.
+	pub hash: SerializableH256,
+	/// Nodes ids numbers.
+	pub id_numbers: BTreeMap<SerializablePublic, SerializableSecret>,
+	/// Node secret share.
+	pub secret_share: SerializableSecret,
+	/// Id of the share refresh, which has produced this key share version.
+	#[serde(default)]
+	pub refresh_id: Option<SerializableH256>,
+}
+
+/// V3 of encrypted key share, as it is stored by key storage on the single key server.
+#[derive(Clone, Serialize, Deserialize)]
+struct SerializableDocumentKeyShareV3 {
+	/// Author of the entry.
+	pub author: SerializablePublic,
+	/// Decryption threshold (at least threshold + 1 nodes are required to decrypt data).
+	pub threshold: usize,
+	/// Common (shared) encryption point.
+	pub common_point: Option<SerializablePublic>,
+	/// Encrypted point.
+	pub encrypted_point: Option<SerializablePublic>,
+	/// Versions of key share, starting from the latest one.
+	pub versions: Vec<SerializableDocumentKeyShareVersionV2>,
+	/// Key metadata.
+	#[serde(default)]
+	pub metadata: SerializableKeyMetadataV3,
+}
+
+/// V3 of key metadata, as it is stored by key storage on the single key server.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct SerializableKeyMetadataV3 {
+	/// Time when the key has been generated.
+	#[serde(default)]
+	pub created: Option<u64>,
+	/// Human-readable label.
+	#[serde(default)]
+	pub label: Option<String>,
+	/// Requester, which has triggered key generation.
+	#[serde(default)]
+	pub requester: Option<SerializablePublic>,
+	/// Last time when the key has been accessed.
+	#[serde(default)]
+	pub last_accessed: Option<u64>,
+}
+
+/// Soft-removed key share, as it is stored by key storage on the single key server until it is purged.
+#[derive(Clone, Serialize, Deserialize)]
+struct SerializableRemovedKeyShare {
+	/// Time when the key share has been removed (seconds since unix epoch).
+	pub removed: u64,
+	/// Removed key share with all its versions.
+	pub key: SerializableDocumentKeyShareV3,
+}
+
+/// Key storage snapshot entry, as it is written to the backup.
+#[derive(Serialize, Deserialize)]
+enum SerializableBackupEntry {
+	/// Key share with all its versions.
+	Key(SerializableH256, SerializableDocumentKeyShareV3),
+	/// Soft-removed key share with all its versions.
+	RemovedKey(SerializableH256, SerializableRemovedKeyShare),
+	/// Id of deleted key.
+	Tombstone(SerializableH256),
+	/// Maximal session nonce.
+	SessionNonce(SerializablePublic, String, u64),
+	/// Last entry of the backup, holding the number of preceding entries. Truncated backups are detected with this entry.
+	End(u64),
+}
+
+impl DocumentKeyShare {
+	/// Get version of the key share. Version is derived from the set of key holders && their id numbers
+	/// (and the id of the last share refresh, if any), so it must be computed over the key share, as it is stored in the key storage.
+	pub fn version(&self) -> H256 {
+		let mut buffer = Vec::with_capacity(self.id_numbers.len() * 96 + 32);
+		for (node, id_number) in &self.id_numbers {
+			buffer.extend_from_slice(&**node);
+			buffer.extend_from_slice(&***id_number);
+		}
+		if let Some(ref refresh_id) = self.refresh_id {
+			buffer.extend_from_slice(&**refresh_id);
+		}
+		keccak(&buffer)
+	}
+}
+
+impl KeyMetadata {
+	/// Metadata of the key, which has just been generated at the request of given requester.
+	pub fn generated(requester: Public) -> Self {
+		KeyMetadata {
+			created: Some(unix_timestamp()),
+			label: None,
+			requester: Some(requester),
+			last_accessed: None,
+		}
+	}
+}
+
 impl PersistentKeyStorage {
-	/// Create new persistent document encryption keys storage
-	pub fn new(config: &ServiceConfiguration) -> Result<Self, Error> {
-		let mut db_path = PathBuf::from(&config.data_path);
+	/// Open persistent document encryption keys storage, located in given secret store data directory
+	pub fn new(data_path: &str, self_key_pair: &NodeKeyPair) -> Result<Self, Error> {
+		let mut db_path = PathBuf::from(data_path);
 		db_path.push("db");
 		let db_path = db_path.to_str().ok_or(Error::Database("Invalid secretstore path".to_owned()))?;
 
+		let encryption_key = storage_encryption_key(self_key_pair)?;
 		let db = Database::open_default(&db_path).map_err(Error::Database)?;
-		let db = upgrade_db(db)?;
+		let db = upgrade_db(db, &encryption_key)?;
 
 		Ok(PersistentKeyStorage {
 			db: db,
+			encryption_key: encryption_key,
+			write_lock: Mutex::new(()),
 		})
 	}
+
+	/// Read serialized key share with all its versions.
+	fn read(&self, document: &ServerKeyId) -> Result<SerializableDocumentKeyShareV3, Error> {
+		self.db.get(None, document)
+			.map_err(Error::Database)?
+			.ok_or(Error::DocumentNotFound)
+			.and_then(|key| decrypt_key_share(&self.encryption_key, &key))
+	}
+
+	/// Write serialized key share with all its versions.
+	fn write(&self, document: ServerKeyId, key: SerializableDocumentKeyShareV3) -> Result<(), Error> {
+		let key = encrypt_key_share(&self.encryption_key, &key)?;
+		let mut batch = self.db.transaction();
+		batch.put(None, &document, &key);
+		self.db.write(batch).map_err(Error::Database)
+	}
+
+	/// Read soft-removed key share with all its versions.
+	fn read_removed(&self, document: &ServerKeyId) -> Result<SerializableRemovedKeyShare, Error> {
+		self.db.get(None, &removed_key(document))
+			.map_err(Error::Database)?
+			.ok_or(Error::DocumentNotFound)
+			.and_then(|key| decrypt_removed_key_share(&self.encryption_key, &key))
+	}
+
+	/// Move key share with all its versions aside, within given transaction. Does nothing if key share is not stored.
+	fn soft_remove_within(&self, batch: &mut DBTransaction, document: &ServerKeyId) -> Result<(), Error> {
+		let key = match self.read(document) {
+			Ok(key) => key,
+			Err(Error::DocumentNotFound) => return Ok(()),
+			Err(err) => return Err(err),
+		};
+
+		let removed_key_share = encrypt_removed_key_share(&self.encryption_key, &SerializableRemovedKeyShare {
+			removed: unix_timestamp(),
+			key: key,
+		})?;
+		batch.delete(None, document);
+		batch.put(None, &removed_key(document), &removed_key_share);
+		Ok(())
+	}
 }
 
-fn upgrade_db(db: Database) -> Result<Database, Error> {
+/// Check if key storage backend is available, i.e. keys could be read from it.
+pub fn is_key_storage_available(key_storage: &KeyStorage) -> bool {
+	match key_storage.get(&Default::default()) {
+		Ok(_) | Err(Error::DocumentNotFound) => true,
+		Err(_) => false,
+	}
+}
+
+/// Write consistent snapshot of key storage contents to the backup. Every backup entry is encrypted with the same key,
+/// which is used to encrypt key shares at rest => backup could only be restored by the key server with the same node key.
+pub fn backup_key_storage<W: Write>(key_storage: &KeyStorage, self_key_pair: &NodeKeyPair, target: &mut W) -> Result<KeyBackupReport, Error> {
+	let encryption_key = storage_encryption_key(self_key_pair)?;
+	target.write_all(&BACKUP_MAGIC[..])
+		.and_then(|_| target.write_u8(BACKUP_VERSION))
+		.map_err(|e| Error::Database(e.to_string()))?;
+
+	let mut report = KeyBackupReport::default();
+	let mut entries = 0;
+	for entry in key_storage.snapshot() {
+		let entry = match entry? {
+			KeyStorageSnapshotEntry::Key(document, versions) => {
+				report.keys += 1;
+				SerializableBackupEntry::Key(document.into(), SerializableDocumentKeyShareV3::from_key_share_versions(versions)?)
+			},
+			KeyStorageSnapshotEntry::RemovedKey(document, removed, versions) => {
+				report.removed_keys += 1;
+				SerializableBackupEntry::RemovedKey(document.into(), SerializableRemovedKeyShare {
+					removed: removed,
+					key: SerializableDocumentKeyShareV3::from_key_share_versions(versions)?,
+				})
+			},
+			KeyStorageSnapshotEntry::Tombstone(document) => {
+				report.tombstones += 1;
+				SerializableBackupEntry::Tombstone(document.into())
+			},
+			KeyStorageSnapshotEntry::SessionNonce(node, session_kind, nonce) =>
+				SerializableBackupEntry::SessionNonce(node.into(), session_kind, nonce),
+		};
+		write_backup_entry(target, &encryption_key, &entry)?;
+		entries += 1;
+	}
+
+	write_backup_entry(target, &encryption_key, &SerializableBackupEntry::End(entries))?;
+	target.flush().map_err(|e| Error::Database(e.to_string()))?;
+	Ok(report)
+}
+
+/// Restore key storage contents from the backup, written by `backup_key_storage`. Backup could only be restored to the empty
+/// key storage. Soft-removed keys are restored as if they have been removed at the time of restore. The whole backup is read
+/// && verified before it is written to the key storage => key storage is left empty if backup is truncated or corrupted.
+pub fn restore_key_storage<R: Read>(key_storage: &KeyStorage, self_key_pair: &NodeKeyPair, source: &mut R) -> Result<KeyBackupReport, Error> {
+	// storage could be modified while backup is read => emptiness is checked again when backup is written to the storage
+	if !key_storage.documents()?.is_empty() || !key_storage.removed_documents()?.is_empty() {
+		return Err(non_empty_key_storage_error());
+	}
+
+	let encryption_key = storage_encryption_key(self_key_pair)?;
+	let mut magic = [0u8; 8];
+	source.read_exact(&mut magic).map_err(|e| Error::Database(e.to_string()))?;
+	let version = source.read_u8().map_err(|e| Error::Database(e.to_string()))?;
+	if &magic != BACKUP_MAGIC || version != BACKUP_VERSION {
+		return Err(Error::Database(format!("unsupported key storage backup version: {}", version)));
+	}
+
+	let removed = unix_timestamp();
+	let mut report = KeyBackupReport::default();
+	let mut entries = Vec::new();
+	loop {
+		let entry = match read_backup_entry(source, &encryption_key)? {
+			SerializableBackupEntry::Key(document, key) => {
+				report.keys += 1;
+				KeyStorageSnapshotEntry::Key(document.into(), key.into_key_share_versions())
+			},
+			SerializableBackupEntry::RemovedKey(document, key) => {
+				report.removed_keys += 1;
+				KeyStorageSnapshotEntry::RemovedKey(document.into(), removed, key.key.into_key_share_versions())
+			},
+			SerializableBackupEntry::Tombstone(document) => {
+				report.tombstones += 1;
+				KeyStorageSnapshotEntry::Tombstone(document.into())
+			},
+			SerializableBackupEntry::SessionNonce(node, session_kind, nonce) =>
+				KeyStorageSnapshotEntry::SessionNonce(node.into(), session_kind, nonce),
+			SerializableBackupEntry::End(backup_entries) => {
+				if backup_entries != entries.len() as u64 {
+					return Err(Error::Database("key storage backup is corrupted".into()));
+				}
+
+				key_storage.restore(entries)?;
+				return Ok(report);
+			},
+		};
+		entries.push(entry);
+	}
+}
+
+/// Error, returned when backup is restored to non-empty key storage.
+fn non_empty_key_storage_error() -> Error {
+	Error::Database("key storage backup could only be restored to empty key storage".into())
+}
+
+/// Serialize, encrypt && write single backup entry.
+fn write_backup_entry<W: Write>(target: &mut W, encryption_key: &KeyPair, entry: &SerializableBackupEntry) -> Result<(), Error> {
+	let entry = serde_json::to_vec(entry).map_err(|e| Error::Database(e.to_string()))?;
+	let entry = encrypt_single_message(encryption_key.public(), &entry).map_err(|e| Error::Database(format!("{}", e)))?;
+	target.write_u32::<LittleEndian>(entry.len() as u32)
+		.and_then(|_| target.write_all(&entry))
+		.map_err(|e| Error::Database(e.to_string()))
+}
+
+/// Read, decrypt && deserialize single backup entry.
+fn read_backup_entry<R: Read>(source: &mut R, encryption_key: &KeyPair) -> Result<SerializableBackupEntry, Error> {
+	let entry_size = source.read_u32::<LittleEndian>().map_err(|e| Error::Database(e.to_string()))?;
+	if entry_size > MAX_BACKUP_ENTRY_SIZE {
+		return Err(Error::Database(format!("too large key storage backup entry: {}", entry_size)));
+	}
+
+	let mut entry = vec![0; entry_size as usize];
+	source.read_exact(&mut entry).map_err(|e| Error::Database(e.to_string()))?;
+	let entry = decrypt_single_message(encryption_key.secret(), &entry).map_err(|e| Error::Database(format!("{}", e)))?;
+	serde_json::from_slice(&entry).map_err(|e| Error::Database(e.to_string()))
+}
+
+/// Compute key pair, used to encrypt key shares at rest. This is the node key pair agreement with itself
+/// => it could only be computed by the owner of the node secret.
+fn storage_encryption_key(self_key_pair: &NodeKeyPair) -> Result<KeyPair, Error> {
+	self_key_pair.compute_shared_key(self_key_pair.public())
+		.map_err(|e| Error::Internal(format!("{}", e)))
+}
+
+/// Serialize && encrypt key share.
+fn encrypt_key_share(encryption_key: &KeyPair, key: &SerializableDocumentKeyShareV3) -> Result<Vec<u8>, Error> {
+	let key = serde_json::to_vec(key).map_err(|e| Error::Database(e.to_string()))?;
+	encrypt_single_message(encryption_key.public(), &key).map_err(|e| Error::Database(format!("{}", e)))
+}
+
+/// Decrypt && deserialize key share.
+fn decrypt_key_share(encryption_key: &KeyPair, key: &[u8]) -> Result<SerializableDocumentKeyShareV3, Error> {
+	let key = decrypt_single_message(encryption_key.secret(), key).map_err(|e| Error::Database(format!("{}", e)))?;
+	serde_json::from_slice::<SerializableDocumentKeyShareV3>(&key).map_err(|e| Error::Database(e.to_string()))
+}
+
+/// Serialize && encrypt soft-removed key share.
+fn encrypt_removed_key_share(encryption_key: &KeyPair, key: &SerializableRemovedKeyShare) -> Result<Vec<u8>, Error> {
+	let key = serde_json::to_vec(key).map_err(|e| Error::Database(e.to_string()))?;
+	encrypt_single_message(encryption_key.public(), &key).map_err(|e| Error::Database(format!("{}", e)))
+}
+
+/// Decrypt && deserialize soft-removed key share.
+fn decrypt_removed_key_share(encryption_key: &KeyPair, key: &[u8]) -> Result<SerializableRemovedKeyShare, Error> {
+	let key = decrypt_single_message(encryption_key.secret(), key).map_err(|e| Error::Database(format!("{}", e)))?;
+	serde_json::from_slice::<SerializableRemovedKeyShare>(&key).map_err(|e| Error::Database(e.to_string()))
+}
+
+fn upgrade_db(db: Database, encryption_key: &KeyPair) -> Result<Database, Error> {
 	let version = db.get(None, DB_META_KEY_VERSION).map_err(Error::Database)?;
 	let version = version.and_then(|v| v.get(0).cloned()).unwrap_or(0);
 	match version {
 		0 => {
 			let mut batch = db.transaction();
-			batch.put(None, DB_META_KEY_VERSION, &[1]);
+			batch.put(None, DB_META_KEY_VERSION, &[CURRENT_VERSION]);
 			for (db_key, db_value) in db.iter(None).into_iter().flat_map(|inner| inner) {
 				let v0_key = serde_json::from_slice::<SerializableDocumentKeyShareV0>(&db_value).map_err(|e| Error::Database(e.to_string()))?;
-				let v1_key = SerializableDocumentKeyShareV1 {
+				let v3_key: SerializableDocumentKeyShareV3 = DocumentKeyShare {
 					// author is used in separate generation + encrypt sessions.
 					// in v0 there have been only simultaneous GenEnc sessions.
-					author: Public::default().into(), 
+					author: Public::default(),
 					threshold: v0_key.threshold,
-					id_numbers: v0_key.id_numbers,
-					secret_share: v0_key.secret_share,
-					common_point: Some(v0_key.common_point),
-					encrypted_point: Some(v0_key.encrypted_point),
-				};
-				let db_value = serde_json::to_vec(&v1_key).map_err(|e| Error::Database(e.to_string()))?;
+					id_numbers: v0_key.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+					secret_share: v0_key.secret_share.into(),
+					common_point: Some(v0_key.common_point.into()),
+					encrypted_point: Some(v0_key.encrypted_point.into()),
+					refresh_id: None,
+					metadata: Default::default(),
+				}.into();
+				let db_value = encrypt_key_share(encryption_key, &v3_key)?;
+				batch.put(None, &*db_key, &*db_value);
+			}
+			db.write(batch).map_err(Error::Database)?;
+			Ok(db)
+		},
+		1 => {
+			let mut batch = db.transaction();
+			batch.put(None, DB_META_KEY_VERSION, &[CURRENT_VERSION]);
+			for (db_key, db_value) in db.iter(None).into_iter().flat_map(|inner| inner) {
+				// v1 database already has version key => skip it
+				if &*db_key == &DB_META_KEY_VERSION[..] {
+					continue;
+				}
+
+				let v1_key = serde_json::from_slice::<SerializableDocumentKeyShareV1>(&db_value).map_err(|e| Error::Database(e.to_string()))?;
+				let v3_key: SerializableDocumentKeyShareV3 = DocumentKeyShare {
+					author: v1_key.author.into(),
+					threshold: v1_key.threshold,
+					id_numbers: v1_key.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+					secret_share: v1_key.secret_share.into(),
+					common_point: v1_key.common_point.map(Into::into),
+					encrypted_point: v1_key.encrypted_point.map(Into::into),
+					refresh_id: None,
+					metadata: Default::default(),
+				}.into();
+				let db_value = encrypt_key_share(encryption_key, &v3_key)?;
 				batch.put(None, &*db_key, &*db_value);
 			}
 			db.write(batch).map_err(Error::Database)?;
 			Ok(db)
 		},
-		1 => Ok(db),
+		2 => {
+			let mut batch = db.transaction();
+			batch.put(None, DB_META_KEY_VERSION, &[CURRENT_VERSION]);
+			for (db_key, db_value) in db.iter(None).into_iter().flat_map(|inner| inner) {
+				// meta values are stored unencrypted
+				if &*db_key == &DB_META_KEY_VERSION[..] || db_key.starts_with(&DB_SESSION_NONCE_PREFIX[..]) {
+					continue;
+				}
+
+				let v2_key = serde_json::from_slice::<SerializableDocumentKeyShareV2>(&db_value).map_err(|e| Error::Database(e.to_string()))?;
+				let db_value = encrypt_key_share(encryption_key, &v2_key.into())?;
+				batch.put(None, &*db_key, &*db_value);
+			}
+			db.write(batch).map_err(Error::Database)?;
+			Ok(db)
+		},
+		3 => {
+			let mut batch = db.transaction();
+			batch.put(None, DB_META_KEY_VERSION, &[CURRENT_VERSION]);
+			for (db_key, db_value) in db.iter(None).into_iter().flat_map(|inner| inner) {
+				// only key shares are re-encoded
+				if db_key.len() != ServerKeyId::len() {
+					continue;
+				}
+
+				let v2_key = decrypt_single_message(encryption_key.secret(), &db_value).map_err(|e| Error::Database(format!("{}", e)))?;
+				let v2_key = serde_json::from_slice::<SerializableDocumentKeyShareV2>(&v2_key).map_err(|e| Error::Database(e.to_string()))?;
+				let db_value = encrypt_key_share(encryption_key, &v2_key.into())?;
+				batch.put(None, &*db_key, &*db_value);
+			}
+			db.write(batch).map_err(Error::Database)?;
+			Ok(db)
+		},
+		CURRENT_VERSION => Ok(db),
 		_ => Err(Error::Database(format!("unsupported SecretStore database version:? {}", version))),
 	}
 }
 
 impl KeyStorage for PersistentKeyStorage {
 	fn insert(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
-		let key: SerializableDocumentKeyShareV1 = key.into();
-		let key = serde_json::to_vec(&key).map_err(|e| Error::Database(e.to_string()))?;
+		let _write_lock = self.write_lock.lock();
+		self.write(document, key.into())
+	}
+
+	fn update(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
+		let previous_key = match self.read(&document) {
+			Ok(previous_key) => Some(previous_key),
+			Err(Error::DocumentNotFound) => None,
+			Err(err) => return Err(err),
+		};
+
+		self.write(document, merge_key_share_versions(previous_key, key))
+	}
+
+	fn get(&self, document: &ServerKeyId) -> Result<DocumentKeyShare, Error> {
+		self.read(document)
+			.and_then(|key| key.into_key_share(None))
+	}
+
+	fn get_version(&self, document: &ServerKeyId, version: &H256) -> Result<DocumentKeyShare, Error> {
+		self.read(document)
+			.and_then(|key| key.into_key_share(Some(version)))
+	}
+
+	fn versions(&self, document: &ServerKeyId) -> Result<Vec<H256>, Error> {
+		self.read(document)
+			.map(|key| key.versions.into_iter().map(|v| v.hash.into()).collect())
+	}
+
+	fn set_metadata(&self, document: &ServerKeyId, metadata: KeyMetadata) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
+		let mut key = self.read(document)?;
+		key.metadata = metadata.into();
+		self.write(document.clone(), key)
+	}
+
+	fn remove(&self, document: &ServerKeyId) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
 		let mut batch = self.db.transaction();
-		batch.put(None, &document, &key);
+		batch.delete(None, document);
 		self.db.write(batch).map_err(Error::Database)
 	}
 
+	fn soft_remove(&self, document: &ServerKeyId) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
+		let mut batch = self.db.transaction();
+		self.soft_remove_within(&mut batch, document)?;
+		self.db.write(batch).map_err(Error::Database)
+	}
+
+	fn tombstone(&self, document: &ServerKeyId) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
+		let mut batch = self.db.transaction();
+		self.soft_remove_within(&mut batch, document)?;
+		batch.put(None, &tombstone_key(document), &[]);
+		self.db.write(batch).map_err(Error::Database)
+	}
+
+	fn is_tombstoned(&self, document: &ServerKeyId) -> bool {
+		self.db.get(None, &tombstone_key(document))
+			.map(|k| k.is_some())
+			.unwrap_or(false)
+	}
+
+	fn removed_documents(&self) -> Result<Vec<(ServerKeyId, u64)>, Error> {
+		self.db.iter(None).into_iter().flat_map(|inner| inner)
+			.filter(|&(ref db_key, _)| db_key.starts_with(&DB_REMOVED_PREFIX[..]))
+			.map(|(db_key, db_value)| decrypt_removed_key_share(&self.encryption_key, &db_value)
+				.map(|key| (ServerKeyId::from_slice(&db_key[DB_REMOVED_PREFIX.len()..]), key.removed)))
+			.collect()
+	}
+
+	fn restore_removed(&self, document: &ServerKeyId) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
+		if self.contains(document) {
+			return Err(Error::Database("removed key share could not replace stored key share".into()));
+		}
+
+		let key = encrypt_key_share(&self.encryption_key, &self.read_removed(document)?.key)?;
+		let mut batch = self.db.transaction();
+		batch.put(None, document, &key);
+		batch.delete(None, &removed_key(document));
+		batch.delete(None, &tombstone_key(document));
+		self.db.write(batch).map_err(Error::Database)
+	}
+
+	fn purge_removed(&self, removed_before: u64) -> Result<Vec<ServerKeyId>, Error> {
+		let _write_lock = self.write_lock.lock();
+		let purged: Vec<_> = self.removed_documents()?.into_iter()
+			.filter(|&(_, removed)| removed < removed_before)
+			.map(|(document, _)| document)
+			.collect();
+		if purged.is_empty() {
+			return Ok(purged);
+		}
+
+		let mut batch = self.db.transaction();
+		for document in &purged {
+			batch.delete(None, &removed_key(document));
+		}
+		self.db.write(batch).map_err(Error::Database)?;
+		Ok(purged)
+	}
+
+	fn contains(&self, document: &ServerKeyId) -> bool {
+		self.db.get(None, document)
+			.map(|k| k.is_some())
+			.unwrap_or(false)
+	}
+
+	fn documents(&self) -> Result<Vec<ServerKeyId>, Error> {
+		// meta keys (version, session nonces, tombstones, removed key shares) are never of the key id length
+		Ok(self.db.iter(None).into_iter().flat_map(|inner| inner)
+			.filter(|&(ref db_key, _)| db_key.len() == ServerKeyId::len())
+			.map(|(db_key, _)| ServerKeyId::from_slice(&*db_key))
+			.collect())
+	}
+
+	fn iter<'a>(&'a self, after: Option<&ServerKeyId>) -> KeyStorageIterator<'a> {
+		// keys are read && decrypted one by one, so that the whole storage is never loaded into memory
+		let after = after.cloned();
+		Box::new(self.db.iter(None).into_iter().flat_map(|inner| inner)
+			.filter(|&(ref db_key, _)| db_key.len() == ServerKeyId::len())
+			.map(|(db_key, db_value)| (ServerKeyId::from_slice(&*db_key), db_value))
+			.filter(move |&(ref document, _)| after.as_ref().map(|after| document > after).unwrap_or(true))
+			.map(move |(document, db_value)| decrypt_key_share(&self.encryption_key, &db_value)
+				.and_then(|key| key.into_key_share(None))
+				.map(|key| (document, key))))
+	}
+
+	fn snapshot<'a>(&'a self) -> KeyStorageSnapshot<'a> {
+		// database iterator is working over implicit database snapshot => it doesn't see changes, made after it has been created
+		Box::new(self.db.iter(None).into_iter().flat_map(|inner| inner)
+			.filter_map(move |(db_key, db_value)| if db_key.len() == ServerKeyId::len() {
+				Some(decrypt_key_share(&self.encryption_key, &db_value)
+					.map(|key| KeyStorageSnapshotEntry::Key(ServerKeyId::from_slice(&*db_key), key.into_key_share_versions())))
+			} else if db_key.starts_with(&DB_REMOVED_PREFIX[..]) {
+				Some(decrypt_removed_key_share(&self.encryption_key, &db_value)
+					.map(|key| KeyStorageSnapshotEntry::RemovedKey(ServerKeyId::from_slice(&db_key[DB_REMOVED_PREFIX.len()..]),
+						key.removed, key.key.into_key_share_versions())))
+			} else if db_key.starts_with(&DB_TOMBSTONE_PREFIX[..]) {
+				Some(Ok(KeyStorageSnapshotEntry::Tombstone(ServerKeyId::from_slice(&db_key[DB_TOMBSTONE_PREFIX.len()..]))))
+			} else if db_key.starts_with(&DB_SESSION_NONCE_PREFIX[..]) {
+				let node_and_kind = &db_key[DB_SESSION_NONCE_PREFIX.len()..];
+				if node_and_kind.len() < NodeId::len() {
+					return Some(Err(Error::Database("invalid session nonce key".into())));
+				}
+
+				let session_kind = String::from_utf8(node_and_kind[NodeId::len()..].to_vec())
+					.map_err(|e| Error::Database(e.to_string()));
+				let nonce = serde_json::from_slice(&db_value)
+					.map_err(|e| Error::Database(e.to_string()));
+				Some(session_kind.and_then(|session_kind| nonce.map(|nonce|
+					KeyStorageSnapshotEntry::SessionNonce(NodeId::from_slice(&node_and_kind[..NodeId::len()]), session_kind, nonce))))
+			} else {
+				// version key
+				None
+			}))
+	}
+
+	fn max_session_nonce(&self, node: &NodeId, session_kind: &str) -> Result<Option<u64>, Error> {
+		match self.db.get(None, &session_nonce_key(node, session_kind)).map_err(Error::Database)? {
+			Some(nonce) => serde_json::from_slice(&nonce).map(Some).map_err(|e| Error::Database(e.to_string())),
+			None => Ok(None),
+		}
+	}
+
+	fn set_max_session_nonce(&self, node: &NodeId, session_kind: &str, nonce: u64) -> Result<(), Error> {
+		let nonce = serde_json::to_vec(&nonce).map_err(|e| Error::Database(e.to_string()))?;
+		let mut batch = self.db.transaction();
+		batch.put(None, &session_nonce_key(node, session_kind), &nonce);
+		self.db.write(batch).map_err(Error::Database)
+	}
+
+	fn restore(&self, entries: Vec<KeyStorageSnapshotEntry>) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
+		// the only entry of the empty database is its version
+		let is_empty = self.db.iter(None).into_iter().flat_map(|inner| inner)
+			.all(|(db_key, _)| &*db_key == &DB_META_KEY_VERSION[..]);
+		if !is_empty {
+			return Err(non_empty_key_storage_error());
+		}
+
+		let mut batch = self.db.transaction();
+		for entry in entries {
+			match entry {
+				KeyStorageSnapshotEntry::Key(document, versions) => {
+					let key = SerializableDocumentKeyShareV3::from_key_share_versions(versions)?;
+					batch.put(None, &document, &encrypt_key_share(&self.encryption_key, &key)?);
+				},
+				KeyStorageSnapshotEntry::RemovedKey(document, removed, versions) => {
+					let key = encrypt_removed_key_share(&self.encryption_key, &SerializableRemovedKeyShare {
+						removed: removed,
+						key: SerializableDocumentKeyShareV3::from_key_share_versions(versions)?,
+					})?;
+					batch.put(None, &removed_key(&document), &key);
+				},
+				KeyStorageSnapshotEntry::Tombstone(document) =>
+					batch.put(None, &tombstone_key(&document), &[]),
+				KeyStorageSnapshotEntry::SessionNonce(node, session_kind, nonce) => {
+					let nonce = serde_json::to_vec(&nonce).map_err(|e| Error::Database(e.to_string()))?;
+					batch.put(None, &session_nonce_key(&node, &session_kind), &nonce);
+				},
+			}
+		}
+		self.db.write(batch).map_err(Error::Database)
+	}
+}
+
+/// Database key of maximal session nonce.
+fn session_nonce_key(node: &NodeId, session_kind: &str) -> Vec<u8> {
+	let mut key = DB_SESSION_NONCE_PREFIX.to_vec();
+	key.extend_from_slice(&**node);
+	key.extend_from_slice(session_kind.as_bytes());
+	key
+}
+
+/// Database key of deleted key tombstone.
+fn tombstone_key(document: &ServerKeyId) -> Vec<u8> {
+	let mut key = DB_TOMBSTONE_PREFIX.to_vec();
+	key.extend_from_slice(&**document);
+	key
+}
+
+/// Database key of soft-removed key share.
+fn removed_key(document: &ServerKeyId) -> Vec<u8> {
+	let mut key = DB_REMOVED_PREFIX.to_vec();
+	key.extend_from_slice(&**document);
+	key
+}
+
+impl FileKeyStorage {
+	/// Open file document encryption keys storage, located in given secret store data directory
+	pub fn new(data_path: &str, self_key_pair: &NodeKeyPair) -> Result<Self, Error> {
+		let mut path = PathBuf::from(data_path);
+		path.push(FILE_KEY_STORAGE_NAME);
+
+		let encryption_key = storage_encryption_key(self_key_pair)?;
+		let data = match fs::File::open(&path) {
+			Ok(mut file) => {
+				let mut contents = Vec::new();
+				file.read_to_end(&mut contents).map_err(|e| Error::Database(e.to_string()))?;
+				let contents = decrypt_single_message(encryption_key.secret(), &contents).map_err(|e| Error::Database(format!("{}", e)))?;
+				serde_json::from_slice::<SerializableFileKeyStorageData>(&contents)
+					.map_err(|e| Error::Database(e.to_string()))?
+					.into()
+			},
+			Err(ref err) if err.kind() == io::ErrorKind::NotFound => FileKeyStorageData::default(),
+			Err(err) => return Err(Error::Database(err.to_string())),
+		};
+
+		Ok(FileKeyStorage {
+			path: path,
+			encryption_key: encryption_key,
+			data: RwLock::new(data),
+		})
+	}
+
+	/// Modify storage contents. Modified contents are written to the file before they're applied.
+	fn modify<F>(&self, f: F) -> Result<(), Error> where F: FnOnce(&mut FileKeyStorageData) {
+		self.try_modify(|data| {
+			f(data);
+			Ok(())
+		})
+	}
+
+	/// Modify storage contents && write them to the file. Nothing is changed if modification fails.
+	fn try_modify<F>(&self, f: F) -> Result<(), Error> where F: FnOnce(&mut FileKeyStorageData) -> Result<(), Error> {
+		let mut data = self.data.write();
+		let mut new_data = data.clone();
+		f(&mut new_data)?;
+
+		let contents = serde_json::to_vec(&SerializableFileKeyStorageData::from(new_data.clone())).map_err(|e| Error::Database(e.to_string()))?;
+		let contents = encrypt_single_message(self.encryption_key.public(), &contents).map_err(|e| Error::Database(format!("{}", e)))?;
+		self.write_file(&contents).map_err(|e| Error::Database(e.to_string()))?;
+
+		*data = new_data;
+		Ok(())
+	}
+
+	/// Replace storage file contents. New contents are written to the temporary file first,
+	/// so that storage file is never left partially written.
+	fn write_file(&self, contents: &[u8]) -> Result<(), io::Error> {
+		if let Some(dir) = self.path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+
+		let temp_path = self.path.with_extension("tmp");
+		{
+			let mut file = fs::File::create(&temp_path)?;
+			file.write_all(contents)?;
+			file.sync_all()?;
+		}
+		fs::rename(&temp_path, &self.path)
+	}
+
+	/// Read serialized key share with all its versions.
+	fn read(&self, document: &ServerKeyId) -> Result<SerializableDocumentKeyShareV3, Error> {
+		self.data.read().keys.get(document).cloned().ok_or(Error::DocumentNotFound)
+	}
+}
+
+impl KeyStorage for FileKeyStorage {
+	fn insert(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
+		self.modify(move |data| { data.keys.insert(document, key.into()); })
+	}
+
+	fn update(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
+		self.modify(move |data| {
+			let previous_key = data.keys.remove(&document);
+			data.keys.insert(document, merge_key_share_versions(previous_key, key));
+		})
+	}
+
+	fn get(&self, document: &ServerKeyId) -> Result<DocumentKeyShare, Error> {
+		self.read(document)
+			.and_then(|key| key.into_key_share(None))
+	}
+
+	fn get_version(&self, document: &ServerKeyId, version: &H256) -> Result<DocumentKeyShare, Error> {
+		self.read(document)
+			.and_then(|key| key.into_key_share(Some(version)))
+	}
+
+	fn versions(&self, document: &ServerKeyId) -> Result<Vec<H256>, Error> {
+		self.read(document)
+			.map(|key| key.versions.into_iter().map(|v| v.hash.into()).collect())
+	}
+
+	fn set_metadata(&self, document: &ServerKeyId, metadata: KeyMetadata) -> Result<(), Error> {
+		if !self.contains(document) {
+			return Err(Error::DocumentNotFound);
+		}
+
+		self.modify(move |data| if let Some(key) = data.keys.get_mut(document) {
+			key.metadata = metadata.into();
+		})
+	}
+
+	fn remove(&self, document: &ServerKeyId) -> Result<(), Error> {
+		self.modify(|data| { data.keys.remove(document); })
+	}
+
+	fn soft_remove(&self, document: &ServerKeyId) -> Result<(), Error> {
+		self.modify(|data| data.soft_remove(document))
+	}
+
+	fn tombstone(&self, document: &ServerKeyId) -> Result<(), Error> {
+		self.modify(|data| {
+			data.soft_remove(document);
+			data.tombstones.insert(document.clone());
+		})
+	}
+
+	fn is_tombstoned(&self, document: &ServerKeyId) -> bool {
+		self.data.read().tombstones.contains(document)
+	}
+
+	fn removed_documents(&self) -> Result<Vec<(ServerKeyId, u64)>, Error> {
+		Ok(self.data.read().removed.iter().map(|(document, key)| (document.clone(), key.removed)).collect())
+	}
+
+	fn restore_removed(&self, document: &ServerKeyId) -> Result<(), Error> {
+		{
+			let data = self.data.read();
+			if data.keys.contains_key(document) {
+				return Err(Error::Database("removed key share could not replace stored key share".into()));
+			}
+			if !data.removed.contains_key(document) {
+				return Err(Error::DocumentNotFound);
+			}
+		}
+
+		self.modify(|data| if let Some(key) = data.removed.remove(document) {
+			data.keys.insert(document.clone(), key.key);
+			data.tombstones.remove(document);
+		})
+	}
+
+	fn purge_removed(&self, removed_before: u64) -> Result<Vec<ServerKeyId>, Error> {
+		let purged: Vec<_> = self.data.read().removed.iter()
+			.filter(|&(_, key)| key.removed < removed_before)
+			.map(|(document, _)| document.clone())
+			.collect();
+		if purged.is_empty() {
+			return Ok(purged);
+		}
+
+		self.modify(|data| {
+			for document in &purged {
+				data.removed.remove(document);
+			}
+		})?;
+		Ok(purged)
+	}
+
+	fn contains(&self, document: &ServerKeyId) -> bool {
+		self.data.read().keys.contains_key(document)
+	}
+
+	fn documents(&self) -> Result<Vec<ServerKeyId>, Error> {
+		Ok(self.data.read().keys.keys().cloned().collect())
+	}
+
+	fn iter<'a>(&'a self, after: Option<&ServerKeyId>) -> KeyStorageIterator<'a> {
+		let documents = documents_after(self.data.read().keys.keys().cloned(), after);
+		Box::new(documents.into_iter().filter_map(move |document| match self.get(&document) {
+			Ok(key) => Some(Ok((document, key))),
+			// key has been removed since iteration has started
+			Err(Error::DocumentNotFound) => None,
+			Err(err) => Some(Err(err)),
+		}))
+	}
+
+	fn snapshot<'a>(&'a self) -> KeyStorageSnapshot<'a> {
+		// storage contents are already in memory => snapshot is the copy of contents
+		let data = self.data.read().clone();
+		let keys = data.keys.into_iter()
+			.map(|(document, key)| KeyStorageSnapshotEntry::Key(document, key.into_key_share_versions()));
+		let removed = data.removed.into_iter()
+			.map(|(document, key)| KeyStorageSnapshotEntry::RemovedKey(document, key.removed, key.key.into_key_share_versions()));
+		let tombstones = data.tombstones.into_iter()
+			.map(KeyStorageSnapshotEntry::Tombstone);
+		let session_nonces = data.session_nonces.into_iter()
+			.map(|((node, session_kind), nonce)| KeyStorageSnapshotEntry::SessionNonce(node, session_kind, nonce));
+		Box::new(keys.chain(removed).chain(tombstones).chain(session_nonces).map(Ok))
+	}
+
+	fn max_session_nonce(&self, node: &NodeId, session_kind: &str) -> Result<Option<u64>, Error> {
+		Ok(self.data.read().session_nonces.get(&(node.clone(), session_kind.to_owned())).cloned())
+	}
+
+	fn set_max_session_nonce(&self, node: &NodeId, session_kind: &str, nonce: u64) -> Result<(), Error> {
+		self.modify(|data| { data.session_nonces.insert((node.clone(), session_kind.to_owned()), nonce); })
+	}
+
+	fn restore(&self, entries: Vec<KeyStorageSnapshotEntry>) -> Result<(), Error> {
+		// entries are converted before modification, so that storage is never left partially restored
+		let mut restored = FileKeyStorageData::default();
+		for entry in entries {
+			match entry {
+				KeyStorageSnapshotEntry::Key(document, versions) => {
+					restored.keys.insert(document, SerializableDocumentKeyShareV3::from_key_share_versions(versions)?);
+				},
+				KeyStorageSnapshotEntry::RemovedKey(document, removed, versions) => {
+					restored.removed.insert(document, SerializableRemovedKeyShare {
+						removed: removed,
+						key: SerializableDocumentKeyShareV3::from_key_share_versions(versions)?,
+					});
+				},
+				KeyStorageSnapshotEntry::Tombstone(document) => {
+					restored.tombstones.insert(document);
+				},
+				KeyStorageSnapshotEntry::SessionNonce(node, session_kind, nonce) => {
+					restored.session_nonces.insert((node, session_kind), nonce);
+				},
+			}
+		}
+
+		self.try_modify(move |data| {
+			if !data.keys.is_empty() || !data.removed.is_empty() || !data.tombstones.is_empty() || !data.session_nonces.is_empty() {
+				return Err(non_empty_key_storage_error());
+			}
+
+			*data = restored;
+			Ok(())
+		})
+	}
+}
+
+impl KeyStorage for MemoryKeyStorage {
+	fn insert(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
+		self.keys.write().insert(document, vec![key]);
+		Ok(())
+	}
+
 	fn update(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
-		self.insert(document, key)
+		let mut keys = self.keys.write();
+		let versions = keys.entry(document).or_insert_with(Vec::new);
+		let mut key = key;
+		if let Some(previous_key) = versions.first() {
+			key.metadata = previous_key.metadata.clone();
+		}
+		let version = key.version();
+		versions.retain(|k| k.version() != version);
+		versions.insert(0, key);
+		versions.truncate(MAX_KEY_SHARE_VERSIONS);
+		Ok(())
+	}
+
+	fn get(&self, document: &ServerKeyId) -> Result<DocumentKeyShare, Error> {
+		self.keys.read().get(document).and_then(|v| v.first().cloned()).ok_or(Error::DocumentNotFound)
+	}
+
+	fn get_version(&self, document: &ServerKeyId, version: &H256) -> Result<DocumentKeyShare, Error> {
+		self.keys.read().get(document).and_then(|v| v.iter().find(|k| &k.version() == version).cloned()).ok_or(Error::DocumentNotFound)
+	}
+
+	fn versions(&self, document: &ServerKeyId) -> Result<Vec<H256>, Error> {
+		self.keys.read().get(document).map(|v| v.iter().map(|k| k.version()).collect()).ok_or(Error::DocumentNotFound)
+	}
+
+	fn set_metadata(&self, document: &ServerKeyId, metadata: KeyMetadata) -> Result<(), Error> {
+		let mut keys = self.keys.write();
+		let versions = keys.get_mut(document).ok_or(Error::DocumentNotFound)?;
+		for key in versions.iter_mut() {
+			key.metadata = metadata.clone();
+		}
+		Ok(())
+	}
+
+	fn remove(&self, document: &ServerKeyId) -> Result<(), Error> {
+		self.keys.write().remove(document);
+		Ok(())
+	}
+
+	fn soft_remove(&self, document: &ServerKeyId) -> Result<(), Error> {
+		if let Some(versions) = self.keys.write().remove(document) {
+			self.removed.write().insert(document.clone(), (unix_timestamp(), versions));
+		}
+		Ok(())
+	}
+
+	fn tombstone(&self, document: &ServerKeyId) -> Result<(), Error> {
+		self.soft_remove(document)?;
+		self.tombstones.write().insert(document.clone());
+		Ok(())
+	}
+
+	fn is_tombstoned(&self, document: &ServerKeyId) -> bool {
+		self.tombstones.read().contains(document)
+	}
+
+	fn removed_documents(&self) -> Result<Vec<(ServerKeyId, u64)>, Error> {
+		Ok(self.removed.read().iter().map(|(document, &(removed, _))| (document.clone(), removed)).collect())
+	}
+
+	fn restore_removed(&self, document: &ServerKeyId) -> Result<(), Error> {
+		let mut keys = self.keys.write();
+		if keys.contains_key(document) {
+			return Err(Error::Database("removed key share could not replace stored key share".into()));
+		}
+
+		let (_, versions) = self.removed.write().remove(document).ok_or(Error::DocumentNotFound)?;
+		keys.insert(document.clone(), versions);
+		self.tombstones.write().remove(document);
+		Ok(())
+	}
+
+	fn purge_removed(&self, removed_before: u64) -> Result<Vec<ServerKeyId>, Error> {
+		let mut removed = self.removed.write();
+		let purged: Vec<_> = removed.iter()
+			.filter(|&(_, &(removed_at, _))| removed_at < removed_before)
+			.map(|(document, _)| document.clone())
+			.collect();
+		for document in &purged {
+			removed.remove(document);
+		}
+		Ok(purged)
+	}
+
+	fn contains(&self, document: &ServerKeyId) -> bool {
+		self.keys.read().contains_key(document)
+	}
+
+	fn documents(&self) -> Result<Vec<ServerKeyId>, Error> {
+		Ok(self.keys.read().keys().cloned().collect())
+	}
+
+	fn iter<'a>(&'a self, after: Option<&ServerKeyId>) -> KeyStorageIterator<'a> {
+		let documents = documents_after(self.keys.read().keys().cloned(), after);
+		Box::new(documents.into_iter().filter_map(move |document| match self.get(&document) {
+			Ok(key) => Some(Ok((document, key))),
+			// key has been removed since iteration has started
+			Err(Error::DocumentNotFound) => None,
+			Err(err) => Some(Err(err)),
+		}))
+	}
+
+	fn snapshot<'a>(&'a self) -> KeyStorageSnapshot<'a> {
+		// all locks are held while copying, so that the copy is consistent
+		let keys = self.keys.read();
+		let removed = self.removed.read();
+		let tombstones = self.tombstones.read();
+		let session_nonces = self.session_nonces.read();
+		let entries: Vec<_> = keys.iter()
+			.map(|(document, versions)| KeyStorageSnapshotEntry::Key(document.clone(), versions.clone()))
+			.chain(removed.iter()
+				.map(|(document, &(removed, ref versions))| KeyStorageSnapshotEntry::RemovedKey(document.clone(), removed, versions.clone())))
+			.chain(tombstones.iter()
+				.map(|document| KeyStorageSnapshotEntry::Tombstone(document.clone())))
+			.chain(session_nonces.iter()
+				.map(|(&(ref node, ref session_kind), &nonce)| KeyStorageSnapshotEntry::SessionNonce(node.clone(), session_kind.clone(), nonce)))
+			.map(Ok)
+			.collect();
+		Box::new(entries.into_iter())
+	}
+
+	fn max_session_nonce(&self, node: &NodeId, session_kind: &str) -> Result<Option<u64>, Error> {
+		Ok(self.session_nonces.read().get(&(node.clone(), session_kind.to_owned())).cloned())
+	}
+
+	fn set_max_session_nonce(&self, node: &NodeId, session_kind: &str, nonce: u64) -> Result<(), Error> {
+		self.session_nonces.write().insert((node.clone(), session_kind.to_owned()), nonce);
+		Ok(())
+	}
+
+	fn restore(&self, entries: Vec<KeyStorageSnapshotEntry>) -> Result<(), Error> {
+		// all locks are held while restoring, so that partially restored storage is never seen
+		let mut keys = self.keys.write();
+		let mut removed = self.removed.write();
+		let mut tombstones = self.tombstones.write();
+		let mut session_nonces = self.session_nonces.write();
+		if !keys.is_empty() || !removed.is_empty() || !tombstones.is_empty() || !session_nonces.is_empty() {
+			return Err(non_empty_key_storage_error());
+		}
+
+		for entry in entries {
+			match entry {
+				KeyStorageSnapshotEntry::Key(document, versions) => {
+					keys.insert(document, versions);
+				},
+				KeyStorageSnapshotEntry::RemovedKey(document, removed_at, versions) => {
+					removed.insert(document, (removed_at, versions));
+				},
+				KeyStorageSnapshotEntry::Tombstone(document) => {
+					tombstones.insert(document);
+				},
+				KeyStorageSnapshotEntry::SessionNonce(node, session_kind, nonce) => {
+					session_nonces.insert((node, session_kind), nonce);
+				},
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Select ids of keys, which are greater than given id && sort them in ascending order.
+fn documents_after<I: Iterator<Item=ServerKeyId>>(documents: I, after: Option<&ServerKeyId>) -> Vec<ServerKeyId> {
+	let mut documents: Vec<_> = documents.filter(|document| after.map(|after| document > after).unwrap_or(true)).collect();
+	documents.sort();
+	documents
+}
+
+/// Add new key share version to previous versions of the same key. Key share of the same version is replaced,
+/// all other versions (and metadata of the previous key) are kept.
+fn merge_key_share_versions(previous_key: Option<SerializableDocumentKeyShareV3>, key: DocumentKeyShare) -> SerializableDocumentKeyShareV3 {
+	let mut key: SerializableDocumentKeyShareV3 = key.into();
+	if let Some(ref previous_key) = previous_key {
+		key.metadata = previous_key.metadata.clone();
+	}
+	let mut previous_versions = previous_key.map(|k| k.versions).unwrap_or_default();
+	previous_versions.retain(|v| key.versions.iter().all(|nv| *nv.hash != *v.hash));
+	key.versions.extend(previous_versions);
+	key.versions.truncate(MAX_KEY_SHARE_VERSIONS);
+	key
+}
+
+impl FileKeyStorageData {
+	/// Move key share with all its versions aside. Does nothing if key share is not stored.
+	fn soft_remove(&mut self, document: &ServerKeyId) {
+		if let Some(key) = self.keys.remove(document) {
+			self.removed.insert(document.clone(), SerializableRemovedKeyShare {
+				removed: unix_timestamp(),
+				key: key,
+			});
+		}
+	}
+}
+
+impl From<SerializableFileKeyStorageData> for FileKeyStorageData {
+	fn from(data: SerializableFileKeyStorageData) -> Self {
+		FileKeyStorageData {
+			keys: data.keys.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+			removed: data.removed.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+			tombstones: data.tombstones.into_iter().map(Into::into).collect(),
+			session_nonces: data.session_nonces.into_iter().map(|(n, k, v)| ((n.into(), k), v)).collect(),
+		}
+	}
+}
+
+impl From<FileKeyStorageData> for SerializableFileKeyStorageData {
+	fn from(data: FileKeyStorageData) -> Self {
+		SerializableFileKeyStorageData {
+			keys: data.keys.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+			removed: data.removed.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+			tombstones: data.tombstones.into_iter().map(Into::into).collect(),
+			session_nonces: data.session_nonces.into_iter().map(|((n, k), v)| (n.into(), k, v)).collect(),
+		}
+	}
+}
+
+impl SerializableDocumentKeyShareV3 {
+	/// Get key share of given version (or the latest version, if None).
+	fn into_key_share(self, version: Option<&H256>) -> Result<DocumentKeyShare, Error> {
+		let author = self.author;
+		let threshold = self.threshold;
+		let common_point = self.common_point;
+		let encrypted_point = self.encrypted_point;
+		let metadata = self.metadata;
+		let key_version = match version {
+			Some(version) => self.versions.into_iter().find(|v| &*v.hash == version).ok_or(Error::DocumentNotFound)?,
+			None => self.versions.into_iter().nth(0).ok_or(Error::Database("key share without versions".into()))?,
+		};
+
+		Ok(DocumentKeyShare {
+			author: author.into(),
+			threshold: threshold,
+			id_numbers: key_version.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+			secret_share: key_version.secret_share.into(),
+			common_point: common_point.map(Into::into),
+			encrypted_point: encrypted_point.map(Into::into),
+			refresh_id: key_version.refresh_id.map(Into::into),
+			metadata: metadata.into(),
+		})
 	}
 
-	fn get(&self, document: &ServerKeyId) -> Result<DocumentKeyShare, Error> {
-		self.db.get(None, document)
-			.map_err(Error::Database)?
-			.ok_or(Error::DocumentNotFound)
-			.map(|key| key.into_vec())
-			.and_then(|key| serde_json::from_slice::<SerializableDocumentKeyShareV1>(&key).map_err(|e| Error::Database(e.to_string())))
-			.map(Into::into)
+	/// Get all versions of key share, starting from the latest one.
+	fn into_key_share_versions(self) -> Vec<DocumentKeyShare> {
+		let author: Public = self.author.into();
+		let threshold = self.threshold;
+		let common_point: Option<Public> = self.common_point.map(Into::into);
+		let encrypted_point: Option<Public> = self.encrypted_point.map(Into::into);
+		let metadata: KeyMetadata = self.metadata.into();
+		self.versions.into_iter()
+			.map(|key_version| DocumentKeyShare {
+				author: author.clone(),
+				threshold: threshold,
+				id_numbers: key_version.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+				secret_share: key_version.secret_share.into(),
+				common_point: common_point.clone(),
+				encrypted_point: encrypted_point.clone(),
+				refresh_id: key_version.refresh_id.map(Into::into),
+				metadata: metadata.clone(),
+			})
+			.collect()
 	}
 
-	fn contains(&self, document: &ServerKeyId) -> bool {
-		self.db.get(None, document)
-			.map(|k| k.is_some())
-			.unwrap_or(false)
+	/// Create key share from all its versions, starting from the latest one.
+	fn from_key_share_versions(versions: Vec<DocumentKeyShare>) -> Result<Self, Error> {
+		let mut versions = versions.into_iter();
+		let mut key: SerializableDocumentKeyShareV3 = versions.next()
+			.ok_or(Error::Database("key share without versions".into()))?
+			.into();
+		for version in versions {
+			key.versions.extend(SerializableDocumentKeyShareV3::from(version).versions);
+		}
+		Ok(key)
 	}
 }
 
-impl From<DocumentKeyShare> for SerializableDocumentKeyShareV1 {
+impl From<DocumentKeyShare> for SerializableDocumentKeyShareV3 {
 	fn from(key: DocumentKeyShare) -> Self {
-		SerializableDocumentKeyShareV1 {
+		let version = key.version();
+		SerializableDocumentKeyShareV3 {
 			author: key.author.into(),
 			threshold: key.threshold,
-			id_numbers: key.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
-			secret_share: key.secret_share.into(),
 			common_point: key.common_point.map(Into::into),
 			encrypted_point: key.encrypted_point.map(Into::into),
+			versions: vec![SerializableDocumentKeyShareVersionV2 {
+				hash: version.into(),
+				id_numbers: key.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+				secret_share: key.secret_share.into(),
+				refresh_id: key.refresh_id.map(Into::into),
+			}],
+			metadata: key.metadata.into(),
 		}
 	}
 }
 
-impl From<SerializableDocumentKeyShareV1> for DocumentKeyShare {
-	fn from(key: SerializableDocumentKeyShareV1) -> Self {
-		DocumentKeyShare {
-			author: key.author.into(),
+impl From<SerializableDocumentKeyShareV2> for SerializableDocumentKeyShareV3 {
+	fn from(key: SerializableDocumentKeyShareV2) -> Self {
+		SerializableDocumentKeyShareV3 {
+			author: key.author,
 			threshold: key.threshold,
-			id_numbers: key.id_numbers.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
-			secret_share: key.secret_share.into(),
-			common_point: key.common_point.map(Into::into),
-			encrypted_point: key.encrypted_point.map(Into::into),
+			common_point: key.common_point,
+			encrypted_point: key.encrypted_point,
+			versions: key.versions,
+			metadata: Default::default(),
+		}
+	}
+}
+
+impl From<KeyMetadata> for SerializableKeyMetadataV3 {
+	fn from(metadata: KeyMetadata) -> Self {
+		SerializableKeyMetadataV3 {
+			created: metadata.created,
+			label: metadata.label,
+			requester: metadata.requester.map(Into::into),
+			last_accessed: metadata.last_accessed,
+		}
+	}
+}
+
+impl From<SerializableKeyMetadataV3> for KeyMetadata {
+	fn from(metadata: SerializableKeyMetadataV3) -> Self {
+		KeyMetadata {
+			created: metadata.created,
+			label: metadata.label,
+			requester: metadata.requester.map(Into::into),
+			last_accessed: metadata.last_accessed,
 		}
 	}
 }
 
 #[cfg(test)]
 pub mod tests {
-	use std::collections::{BTreeMap, HashMap};
-	use parking_lot::RwLock;
+	use std::collections::BTreeMap;
 	use serde_json;
+	use parking_lot::Mutex;
+	use bigint::hash::H256;
 	use devtools::RandomTempPath;
-	use ethkey::{Random, Generator, Public, Secret};
+	use ethkey::{Random, Generator, Public, Secret, KeyPair};
 	use util::Database;
-	use types::all::{Error, NodeAddress, ServiceConfiguration, ClusterConfiguration, ServerKeyId};
-	use super::{DB_META_KEY_VERSION, KeyStorage, PersistentKeyStorage, DocumentKeyShare,
-		SerializableDocumentKeyShareV0, SerializableDocumentKeyShareV1, upgrade_db};
+	use audit_log::unix_timestamp;
+	use node_key_pair::PlainNodeKeyPair;
+	use types::all::{Error, NodeAddress, NodeId, ServiceConfiguration, ClusterConfiguration, ServerKeyId, KeyStorageBackend};
+	use ethcrypto::ecies::encrypt_single_message;
+	use super::{DB_META_KEY_VERSION, CURRENT_VERSION, KeyStorage, PersistentKeyStorage, FileKeyStorage, DocumentKeyShare, KeyMetadata,
+		KeyStorageSnapshotEntry, SerializableDocumentKeyShareV0, SerializableDocumentKeyShareV1, SerializableDocumentKeyShareV2,
+		SerializableDocumentKeyShareV3, SerializableBackupEntry, BACKUP_MAGIC, upgrade_db, storage_encryption_key, decrypt_key_share,
+		write_backup_entry, backup_key_storage, restore_key_storage};
 
-	/// In-memory document encryption keys storage
-	#[derive(Default)]
-	pub struct DummyKeyStorage {
-		keys: RwLock<HashMap<ServerKeyId, DocumentKeyShare>>,
+	fn open_key_storage(db: Database, self_key_pair: &KeyPair) -> Result<PersistentKeyStorage, Error> {
+		let encryption_key = storage_encryption_key(&PlainNodeKeyPair::new(self_key_pair.clone()))?;
+		Ok(PersistentKeyStorage {
+			db: upgrade_db(db, &encryption_key)?,
+			encryption_key: encryption_key,
+			write_lock: Mutex::new(()),
+		})
 	}
 
-	impl KeyStorage for DummyKeyStorage {
-		fn insert(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
-			self.keys.write().insert(document, key);
-			Ok(())
-		}
-
-		fn update(&self, document: ServerKeyId, key: DocumentKeyShare) -> Result<(), Error> {
-			self.keys.write().insert(document, key);
-			Ok(())
-		}
-
-		fn get(&self, document: &ServerKeyId) -> Result<DocumentKeyShare, Error> {
-			self.keys.read().get(document).cloned().ok_or(Error::DocumentNotFound)
-		}
-
-		fn contains(&self, document: &ServerKeyId) -> bool {
-			self.keys.read().contains_key(document)
+	fn serializable_key_share_v2(key: DocumentKeyShare) -> SerializableDocumentKeyShareV2 {
+		let key = SerializableDocumentKeyShareV3::from(key);
+		SerializableDocumentKeyShareV2 {
+			author: key.author,
+			threshold: key.threshold,
+			common_point: key.common_point,
+			encrypted_point: key.encrypted_point,
+			versions: key.versions,
 		}
 	}
 
+	/// In-memory document encryption keys storage
+	pub use super::MemoryKeyStorage as DummyKeyStorage;
+
 	#[test]
 	fn persistent_key_storage() {
 		let path = RandomTempPath::create_dir();
+		let self_key_pair = PlainNodeKeyPair::new(Random.generate().unwrap());
 		let config = ServiceConfiguration {
 			listener_address: None,
+			service_contract_address: None,
 			acl_check_enabled: true,
+			acl_file: None,
+			acl_service: None,
+			acl_cache_ttl: None,
+			audit_log: None,
+			health_check_enabled: false,
+			http_api_keys: None,
+			http_cors: None,
+			http_hosts: None,
+			http_tls_certificate: None,
+			http_tls_private_key: None,
+			http_tls_client_ca: None,
+			rekeyings_per_minute: None,
 			data_path: path.as_str().to_owned(),
+			key_storage: KeyStorageBackend::Database,
 			cluster_config: ClusterConfiguration {
 				threads: 1,
 				listener_address: NodeAddress {
@@ -245,6 +1543,19 @@ pub mod tests {
 				},
 				nodes: BTreeMap::new(),
 				allow_connecting_to_higher_nodes: false,
+				rate_limits: Default::default(),
+				peer_rate_limits: Default::default(),
+				deterministic_nodes_selection: false,
+				read_only_nodes: Vec::new(),
+				timeouts: Default::default(),
+				external_address: None,
+				upnp_enabled: false,
+				admin_messages_share: None,
+				removed_keys_retention: None,
+				decryption_cache_ttl: None,
+				max_session_traffic: None,
+				session_journal: None,
+				backup_path: None,
 			},
 		};
 		
@@ -258,6 +1569,8 @@ pub mod tests {
 			secret_share: Random.generate().unwrap().secret().clone(),
 			common_point: Some(Random.generate().unwrap().public().clone()),
 			encrypted_point: Some(Random.generate().unwrap().public().clone()),
+			refresh_id: None,
+			metadata: Default::default(),
 		};
 		let key2 = ServerKeyId::from(2);
 		let value2 = DocumentKeyShare {
@@ -269,10 +1582,12 @@ pub mod tests {
 			secret_share: Random.generate().unwrap().secret().clone(),
 			common_point: Some(Random.generate().unwrap().public().clone()),
 			encrypted_point: Some(Random.generate().unwrap().public().clone()),
+			refresh_id: None,
+			metadata: Default::default(),
 		};
 		let key3 = ServerKeyId::from(3);
 
-		let key_storage = PersistentKeyStorage::new(&config).unwrap();
+		let key_storage = PersistentKeyStorage::new(&config.data_path, &self_key_pair).unwrap();
 		key_storage.insert(key1.clone(), value1.clone()).unwrap();
 		key_storage.insert(key2.clone(), value2.clone()).unwrap();
 		assert_eq!(key_storage.get(&key1), Ok(value1.clone()));
@@ -280,14 +1595,143 @@ pub mod tests {
 		assert_eq!(key_storage.get(&key3), Err(Error::DocumentNotFound));
 		drop(key_storage);
 
-		let key_storage = PersistentKeyStorage::new(&config).unwrap();
+		let key_storage = PersistentKeyStorage::new(&config.data_path, &self_key_pair).unwrap();
 		assert_eq!(key_storage.get(&key1), Ok(value1));
 		assert_eq!(key_storage.get(&key2), Ok(value2));
 		assert_eq!(key_storage.get(&key3), Err(Error::DocumentNotFound));
+		key_storage.set_max_session_nonce(&Public::default(), "generation", 10).unwrap();
+		assert_eq!(key_storage.documents(), Ok(vec![key1.clone(), key2.clone()]));
+
+		key_storage.remove(&key2).unwrap();
+		assert!(key_storage.contains(&key1));
+		assert!(!key_storage.contains(&key2));
+		assert_eq!(key_storage.get(&key2), Err(Error::DocumentNotFound));
+		assert_eq!(key_storage.documents(), Ok(vec![key1.clone()]));
+	}
+
+	#[test]
+	fn persistent_key_storage_keeps_versions() {
+		let path = RandomTempPath::create_dir();
+		let self_key_pair = Random.generate().unwrap();
+		let db = Database::open_default(path.as_str()).unwrap();
+		let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+
+		let key = ServerKeyId::from(1);
+		let value1 = DocumentKeyShare {
+			author: Public::default(),
+			threshold: 1,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone()),
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone()),
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+		key_storage.insert(key.clone(), value1.clone()).unwrap();
+
+		// update with the same set of key holders replaces the version
+		let mut value2 = value1.clone();
+		value2.secret_share = Random.generate().unwrap().secret().clone();
+		key_storage.update(key.clone(), value2.clone()).unwrap();
+		assert_eq!(key_storage.versions(&key), Ok(vec![value2.version()]));
+
+		// update with the other set of key holders adds new version
+		let mut value3 = value2.clone();
+		value3.id_numbers.insert(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone());
+		key_storage.update(key.clone(), value3.clone()).unwrap();
+		assert_eq!(key_storage.versions(&key), Ok(vec![value3.version(), value2.version()]));
+		assert_eq!(key_storage.get(&key), Ok(value3.clone()));
+		assert_eq!(key_storage.get_version(&key, &value2.version()), Ok(value2.clone()));
+		assert_eq!(key_storage.get_version(&key, &H256::from(7)), Err(Error::DocumentNotFound));
+
+		// the oldest version is removed when there are too many versions
+		let mut value4 = value3.clone();
+		value4.id_numbers.insert(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone());
+		key_storage.update(key.clone(), value4.clone()).unwrap();
+		assert_eq!(key_storage.versions(&key), Ok(vec![value4.version(), value3.version()]));
+		assert_eq!(key_storage.get_version(&key, &value2.version()), Err(Error::DocumentNotFound));
+
+		// refreshed share of the same set of key holders adds new version
+		let mut value5 = value4.clone();
+		value5.secret_share = Random.generate().unwrap().secret().clone();
+		value5.refresh_id = Some(H256::from(1));
+		key_storage.update(key.clone(), value5.clone()).unwrap();
+		assert_eq!(key_storage.versions(&key), Ok(vec![value5.version(), value4.version()]));
+		assert_eq!(key_storage.get(&key), Ok(value5.clone()));
+
+		// insert forgets all previous versions
+		key_storage.insert(key.clone(), value1.clone()).unwrap();
+		assert_eq!(key_storage.versions(&key), Ok(vec![value1.version()]));
+	}
+
+	#[test]
+	fn persistent_key_storage_keeps_session_nonces() {
+		let path = RandomTempPath::create_dir();
+		let self_key_pair = Random.generate().unwrap();
+		let node1: NodeId = Random.generate().unwrap().public().clone();
+		let node2: NodeId = Random.generate().unwrap().public().clone();
+
+		{
+			let db = Database::open_default(path.as_str()).unwrap();
+			let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+			assert_eq!(key_storage.max_session_nonce(&node1, "decryption"), Ok(None));
+			key_storage.set_max_session_nonce(&node1, "decryption", 10).unwrap();
+			key_storage.set_max_session_nonce(&node1, "signing", 20).unwrap();
+			key_storage.set_max_session_nonce(&node2, "decryption", 30).unwrap();
+		}
+
+		let db = Database::open_default(path.as_str()).unwrap();
+		let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+		assert_eq!(key_storage.max_session_nonce(&node1, "decryption"), Ok(Some(10)));
+		assert_eq!(key_storage.max_session_nonce(&node1, "signing"), Ok(Some(20)));
+		assert_eq!(key_storage.max_session_nonce(&node2, "decryption"), Ok(Some(30)));
+		assert_eq!(key_storage.max_session_nonce(&node2, "signing"), Ok(None));
+	}
+
+	#[test]
+	fn persistent_key_storage_keeps_tombstones() {
+		let path = RandomTempPath::create_dir();
+		let self_key_pair = Random.generate().unwrap();
+		let key_id = ServerKeyId::from(1);
+		let value = DocumentKeyShare {
+			author: Public::default(),
+			threshold: 0,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+
+		{
+			let db = Database::open_default(path.as_str()).unwrap();
+			let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+			key_storage.insert(key_id.clone(), value).unwrap();
+			assert!(!key_storage.is_tombstoned(&key_id));
+			key_storage.tombstone(&key_id).unwrap();
+		}
+
+		let db = Database::open_default(path.as_str()).unwrap();
+		let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+		assert!(!key_storage.contains(&key_id));
+		assert!(key_storage.is_tombstoned(&key_id));
+		assert_eq!(key_storage.documents(), Ok(vec![]));
+
+		// deleted key share is kept until purged
+		assert_eq!(key_storage.removed_documents().map(|documents| documents.len()), Ok(1));
+		key_storage.restore_removed(&key_id).unwrap();
+		assert!(key_storage.contains(&key_id));
+		assert!(!key_storage.is_tombstoned(&key_id));
 	}
 
 	#[test]
-	fn upgrade_db_0_to_1() {
+	fn upgrade_db_from_0() {
 		let db_path = RandomTempPath::create_dir();
 		let db = Database::open_default(db_path.as_str()).unwrap();
 
@@ -309,19 +1753,492 @@ pub mod tests {
 		}
 
 		// upgrade database
-		let db = upgrade_db(db).unwrap();
+		let key_storage = open_key_storage(db, &Random.generate().unwrap()).unwrap();
 
 		// check upgrade
-		assert_eq!(db.get(None, DB_META_KEY_VERSION).unwrap().unwrap()[0], 1);
-		let key = serde_json::from_slice::<SerializableDocumentKeyShareV1>(&db.get(None, &[7]).unwrap().map(|key| key.to_vec()).unwrap()).unwrap();
+		assert_eq!(key_storage.db.get(None, DB_META_KEY_VERSION).unwrap().unwrap()[0], CURRENT_VERSION);
+		let key = decrypt_key_share(&key_storage.encryption_key, &key_storage.db.get(None, &[7]).unwrap().unwrap()).unwrap();
 		assert_eq!(Public::default(), key.author.clone().into());
 		assert_eq!(777, key.threshold);
+		assert_eq!(Some("99e82b163b062d55a64085bacfd407bb55f194ba5fb7a1af9c34b84435455520f1372e0e650a4f91aed0058cb823f62146ccb5599c8d13372c300dea866b69fc".parse::<Public>().unwrap()), key.common_point.clone().map(Into::into));
+		assert_eq!(Some("7e05df9dd077ec21ed4bc45c9fe9e0a43d65fa4be540630de615ced5e95cf5c3003035eb713317237d7667feeeb64335525158f5f7411f67aca9645169ea554c".parse::<Public>().unwrap()), key.encrypted_point.clone().map(Into::into));
+
+		assert_eq!(key.versions.len(), 1);
 		assert_eq!(vec![(
 			"b486d3840218837b035c66196ecb15e6b067ca20101e11bd5e626288ab6806ecc70b8307012626bd512bad1559112d11d21025cef48cc7a1d2f3976da08f36c8".parse::<Public>().unwrap(),
 			"281b6bf43cb86d0dc7b98e1b7def4a80f3ce16d28d2308f934f116767306f06c".parse::<Secret>().unwrap(),
-		)], key.id_numbers.clone().into_iter().map(|(k, v)| (k.into(), v.into())).collect::<Vec<(Public, Secret)>>());
-		assert_eq!("00125d85a05e5e63e214cb60fe63f132eec8a103aa29266b7e6e6c5b7597230b".parse::<Secret>().unwrap(), key.secret_share.into());
-		assert_eq!(Some("99e82b163b062d55a64085bacfd407bb55f194ba5fb7a1af9c34b84435455520f1372e0e650a4f91aed0058cb823f62146ccb5599c8d13372c300dea866b69fc".parse::<Public>().unwrap()), key.common_point.clone().map(Into::into));
-		assert_eq!(Some("7e05df9dd077ec21ed4bc45c9fe9e0a43d65fa4be540630de615ced5e95cf5c3003035eb713317237d7667feeeb64335525158f5f7411f67aca9645169ea554c".parse::<Public>().unwrap()), key.encrypted_point.clone().map(Into::into));
+		)], key.versions[0].id_numbers.clone().into_iter().map(|(k, v)| (k.into(), v.into())).collect::<Vec<(Public, Secret)>>());
+		assert_eq!("00125d85a05e5e63e214cb60fe63f132eec8a103aa29266b7e6e6c5b7597230b".parse::<Secret>().unwrap(), key.versions[0].secret_share.clone().into());
+	}
+
+	#[test]
+	fn upgrade_db_from_1() {
+		let db_path = RandomTempPath::create_dir();
+		let db = Database::open_default(db_path.as_str()).unwrap();
+
+		// prepare v1 database
+		let v1_key = DocumentKeyShare {
+			author: Random.generate().unwrap().public().clone(),
+			threshold: 777,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: Some(Random.generate().unwrap().public().clone()),
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+		let key_id = ServerKeyId::from(7);
+		{
+			let key = serde_json::to_vec(&SerializableDocumentKeyShareV1 {
+				author: v1_key.author.clone().into(),
+				threshold: v1_key.threshold,
+				id_numbers: v1_key.id_numbers.clone().into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+				secret_share: v1_key.secret_share.clone().into(),
+				common_point: v1_key.common_point.clone().map(Into::into),
+				encrypted_point: None,
+			}).unwrap();
+			let mut batch = db.transaction();
+			batch.put(None, DB_META_KEY_VERSION, &[1]);
+			batch.put(None, &key_id, &key);
+			db.write(batch).unwrap();
+		}
+
+		// upgrade database
+		let key_storage = open_key_storage(db, &Random.generate().unwrap()).unwrap();
+
+		// check upgrade
+		assert_eq!(key_storage.db.get(None, DB_META_KEY_VERSION).unwrap().unwrap()[0], CURRENT_VERSION);
+		assert_eq!(key_storage.get(&key_id), Ok(v1_key.clone()));
+		assert_eq!(key_storage.versions(&key_id), Ok(vec![v1_key.version()]));
+	}
+
+	#[test]
+	fn upgrade_db_from_2() {
+		let db_path = RandomTempPath::create_dir();
+		let db = Database::open_default(db_path.as_str()).unwrap();
+		let node: NodeId = Random.generate().unwrap().public().clone();
+
+		// prepare v2 database
+		let v2_key = DocumentKeyShare {
+			author: Random.generate().unwrap().public().clone(),
+			threshold: 777,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+		let key_id = ServerKeyId::from(7);
+		{
+			let key = serde_json::to_vec(&serializable_key_share_v2(v2_key.clone())).unwrap();
+			let mut batch = db.transaction();
+			batch.put(None, DB_META_KEY_VERSION, &[2]);
+			batch.put(None, &key_id, &key);
+			db.write(batch).unwrap();
+		}
+		let key_storage = PersistentKeyStorage {
+			db: db,
+			encryption_key: Random.generate().unwrap(),
+			write_lock: Mutex::new(()),
+		};
+		key_storage.set_max_session_nonce(&node, "generation", 10).unwrap();
+		let db = key_storage.db;
+
+		// upgrade database
+		let key_storage = open_key_storage(db, &Random.generate().unwrap()).unwrap();
+
+		// check upgrade
+		assert_eq!(key_storage.db.get(None, DB_META_KEY_VERSION).unwrap().unwrap()[0], CURRENT_VERSION);
+		assert!(serde_json::from_slice::<SerializableDocumentKeyShareV2>(&key_storage.db.get(None, &key_id).unwrap().unwrap()).is_err());
+		assert_eq!(key_storage.get(&key_id), Ok(v2_key));
+		assert_eq!(key_storage.max_session_nonce(&node, "generation"), Ok(Some(10)));
+	}
+
+	#[test]
+	fn upgrade_db_from_3() {
+		let db_path = RandomTempPath::create_dir();
+		let db = Database::open_default(db_path.as_str()).unwrap();
+		let self_key_pair = Random.generate().unwrap();
+		let node: NodeId = Random.generate().unwrap().public().clone();
+
+		// prepare v3 database
+		let v3_key = DocumentKeyShare {
+			author: Random.generate().unwrap().public().clone(),
+			threshold: 777,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+		let key_id = ServerKeyId::from(7);
+		{
+			let encryption_key = storage_encryption_key(&PlainNodeKeyPair::new(self_key_pair.clone())).unwrap();
+			let key = serde_json::to_vec(&serializable_key_share_v2(v3_key.clone())).unwrap();
+			let key = encrypt_single_message(encryption_key.public(), &key).unwrap();
+			let mut batch = db.transaction();
+			batch.put(None, DB_META_KEY_VERSION, &[3]);
+			batch.put(None, &key_id, &key);
+			db.write(batch).unwrap();
+		}
+		let key_storage = PersistentKeyStorage {
+			db: db,
+			encryption_key: Random.generate().unwrap(),
+			write_lock: Mutex::new(()),
+		};
+		key_storage.set_max_session_nonce(&node, "generation", 10).unwrap();
+		key_storage.tombstone(&ServerKeyId::from(8)).unwrap();
+		let db = key_storage.db;
+
+		// upgrade database
+		let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+
+		// check upgrade
+		assert_eq!(key_storage.db.get(None, DB_META_KEY_VERSION).unwrap().unwrap()[0], CURRENT_VERSION);
+		assert_eq!(key_storage.get(&key_id), Ok(v3_key));
+		assert_eq!(key_storage.max_session_nonce(&node, "generation"), Ok(Some(10)));
+		assert!(key_storage.is_tombstoned(&ServerKeyId::from(8)));
+	}
+
+	#[test]
+	fn key_storage_keeps_metadata() {
+		let path = RandomTempPath::create_dir();
+		let self_key_pair = Random.generate().unwrap();
+		let key_id = ServerKeyId::from(1);
+		let mut key = DocumentKeyShare {
+			author: Public::default(),
+			threshold: 0,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: KeyMetadata::generated(Random.generate().unwrap().public().clone()),
+		};
+
+		{
+			let db = Database::open_default(path.as_str()).unwrap();
+			let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+			assert_eq!(key_storage.set_metadata(&key_id, Default::default()), Err(Error::DocumentNotFound));
+			key_storage.insert(key_id.clone(), key.clone()).unwrap();
+
+			// metadata is replaced without changing key share versions
+			key.metadata.label = Some("label".into());
+			key.metadata.last_accessed = Some(100);
+			key_storage.set_metadata(&key_id, key.metadata.clone()).unwrap();
+			assert_eq!(key_storage.versions(&key_id), Ok(vec![key.version()]));
+
+			// metadata is kept when key share is updated
+			let mut updated_key = key.clone();
+			updated_key.secret_share = Random.generate().unwrap().secret().clone();
+			updated_key.metadata = Default::default();
+			key_storage.update(key_id.clone(), updated_key.clone()).unwrap();
+			key.secret_share = updated_key.secret_share;
+		}
+
+		let db = Database::open_default(path.as_str()).unwrap();
+		let key_storage = open_key_storage(db, &self_key_pair).unwrap();
+		assert_eq!(key_storage.get(&key_id), Ok(key));
+	}
+
+	fn check_key_storage_iterator(key_storage: &KeyStorage) {
+		let keys: Vec<_> = (0..5).map(|i| (ServerKeyId::from(5 - i as u64), DocumentKeyShare {
+			author: Public::default(),
+			threshold: i,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		})).collect();
+		for &(ref key_id, ref key) in &keys {
+			key_storage.insert(key_id.clone(), key.clone()).unwrap();
+		}
+		key_storage.set_max_session_nonce(&Public::default(), "generation", 10).unwrap();
+		key_storage.tombstone(&ServerKeyId::from(10)).unwrap();
+
+		// keys are iterated in ascending order of their ids
+		let mut sorted_keys = keys.clone();
+		sorted_keys.reverse();
+		assert_eq!(key_storage.iter(None).collect::<Result<Vec<_>, _>>(), Ok(sorted_keys.clone()));
+		assert_eq!(key_storage.iter(Some(&ServerKeyId::from(3))).collect::<Result<Vec<_>, _>>(), Ok(sorted_keys[3..].to_vec()));
+		assert_eq!(key_storage.iter(Some(&ServerKeyId::from(5))).count(), 0);
+	}
+
+	#[test]
+	fn key_storages_iterate_keys() {
+		let path = RandomTempPath::create_dir();
+		let db = Database::open_default(path.as_str()).unwrap();
+		check_key_storage_iterator(&open_key_storage(db, &Random.generate().unwrap()).unwrap());
+
+		let path = RandomTempPath::create_dir();
+		check_key_storage_iterator(&FileKeyStorage::new(path.as_str(), &PlainNodeKeyPair::new(Random.generate().unwrap())).unwrap());
+
+		check_key_storage_iterator(&DummyKeyStorage::default());
+	}
+
+	fn check_key_storage_keeps_removed_keys(key_storage: &KeyStorage) {
+		let key = DocumentKeyShare {
+			author: Public::default(),
+			threshold: 0,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+		key_storage.insert(ServerKeyId::from(1), key.clone()).unwrap();
+		key_storage.insert(ServerKeyId::from(2), key.clone()).unwrap();
+		key_storage.soft_remove(&ServerKeyId::from(1)).unwrap();
+		key_storage.tombstone(&ServerKeyId::from(2)).unwrap();
+		key_storage.soft_remove(&ServerKeyId::from(3)).unwrap();
+
+		// removed keys are not stored anymore, but could be restored
+		assert_eq!(key_storage.documents(), Ok(vec![]));
+		let mut removed: Vec<_> = key_storage.removed_documents().unwrap().into_iter().map(|(document, _)| document).collect();
+		removed.sort();
+		assert_eq!(removed, vec![ServerKeyId::from(1), ServerKeyId::from(2)]);
+		key_storage.restore_removed(&ServerKeyId::from(2)).unwrap();
+		assert_eq!(key_storage.get(&ServerKeyId::from(2)), Ok(key.clone()));
+		assert!(!key_storage.is_tombstoned(&ServerKeyId::from(2)));
+		assert_eq!(key_storage.restore_removed(&ServerKeyId::from(2)), Err(Error::DocumentNotFound));
+
+		// removed key share never replaces stored key share
+		key_storage.insert(ServerKeyId::from(1), key.clone()).unwrap();
+		assert!(key_storage.restore_removed(&ServerKeyId::from(1)).is_err());
+		key_storage.remove(&ServerKeyId::from(1)).unwrap();
+
+		// only keys, removed before given time, are purged
+		assert_eq!(key_storage.purge_removed(0), Ok(vec![]));
+		assert_eq!(key_storage.purge_removed(unix_timestamp() + 1), Ok(vec![ServerKeyId::from(1)]));
+		assert_eq!(key_storage.removed_documents(), Ok(vec![]));
+		assert_eq!(key_storage.restore_removed(&ServerKeyId::from(1)), Err(Error::DocumentNotFound));
+	}
+
+	#[test]
+	fn key_storages_keep_removed_keys() {
+		let path = RandomTempPath::create_dir();
+		let db = Database::open_default(path.as_str()).unwrap();
+		check_key_storage_keeps_removed_keys(&open_key_storage(db, &Random.generate().unwrap()).unwrap());
+
+		let path = RandomTempPath::create_dir();
+		check_key_storage_keeps_removed_keys(&FileKeyStorage::new(path.as_str(), &PlainNodeKeyPair::new(Random.generate().unwrap())).unwrap());
+
+		check_key_storage_keeps_removed_keys(&DummyKeyStorage::default());
+	}
+
+	fn sorted_snapshot(key_storage: &KeyStorage) -> Vec<KeyStorageSnapshotEntry> {
+		let mut snapshot: Vec<_> = key_storage.snapshot().collect::<Result<Vec<_>, _>>().unwrap().into_iter()
+			// removal time is reset when restoring from backup
+			.map(|entry| match entry {
+				KeyStorageSnapshotEntry::RemovedKey(document, _, versions) => KeyStorageSnapshotEntry::RemovedKey(document, 0, versions),
+				entry => entry,
+			})
+			.collect();
+		snapshot.sort_by_key(|entry| format!("{:?}", entry));
+		snapshot
+	}
+
+	fn check_key_storage_backup(key_storage: &KeyStorage, restored_key_storage: &KeyStorage, self_key_pair: &KeyPair) {
+		let key = DocumentKeyShare {
+			author: Public::default(),
+			threshold: 0,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: KeyMetadata::generated(Random.generate().unwrap().public().clone()),
+		};
+		let mut new_key = key.clone();
+		new_key.id_numbers.insert(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone());
+		key_storage.insert(ServerKeyId::from(1), key.clone()).unwrap();
+		key_storage.update(ServerKeyId::from(1), new_key.clone()).unwrap();
+		key_storage.insert(ServerKeyId::from(2), key.clone()).unwrap();
+		key_storage.tombstone(&ServerKeyId::from(2)).unwrap();
+		key_storage.insert(ServerKeyId::from(3), key.clone()).unwrap();
+		key_storage.soft_remove(&ServerKeyId::from(3)).unwrap();
+		key_storage.insert(ServerKeyId::from(3), new_key.clone()).unwrap();
+		key_storage.set_max_session_nonce(&Public::default(), "generation", 10).unwrap();
+
+		// snapshot contains all storage entries
+		let snapshot = sorted_snapshot(key_storage);
+		assert_eq!(snapshot.len(), 6);
+		assert!(snapshot.contains(&KeyStorageSnapshotEntry::Key(ServerKeyId::from(1), vec![new_key.clone(), key.clone()])));
+		assert!(snapshot.contains(&KeyStorageSnapshotEntry::RemovedKey(ServerKeyId::from(2), 0, vec![key.clone()])));
+		assert!(snapshot.contains(&KeyStorageSnapshotEntry::Tombstone(ServerKeyId::from(2))));
+		assert!(snapshot.contains(&KeyStorageSnapshotEntry::SessionNonce(Public::default(), "generation".into(), 10)));
+
+		// backup is restored with the same node key only
+		let self_key_pair = PlainNodeKeyPair::new(self_key_pair.clone());
+		let mut backup = Vec::new();
+		let report = backup_key_storage(key_storage, &self_key_pair, &mut backup).unwrap();
+		assert_eq!((report.keys, report.removed_keys, report.tombstones), (2, 2, 1));
+		let other_key_pair = PlainNodeKeyPair::new(Random.generate().unwrap());
+		assert!(restore_key_storage(restored_key_storage, &other_key_pair, &mut &backup[..]).is_err());
+		assert!(sorted_snapshot(restored_key_storage).is_empty());
+
+		// nothing is restored from truncated or corrupted backup
+		assert!(restore_key_storage(restored_key_storage, &self_key_pair, &mut &backup[..backup.len() - 1]).is_err());
+		assert!(sorted_snapshot(restored_key_storage).is_empty());
+		let mut corrupted_backup = backup.clone();
+		let corrupted_byte = corrupted_backup.len() - 16;
+		corrupted_backup[corrupted_byte] ^= 0xff;
+		assert!(restore_key_storage(restored_key_storage, &self_key_pair, &mut &corrupted_backup[..]).is_err());
+		assert!(sorted_snapshot(restored_key_storage).is_empty());
+		let encryption_key = storage_encryption_key(&self_key_pair).unwrap();
+		let mut miscounted_backup = backup[..BACKUP_MAGIC.len() + 1].to_vec();
+		write_backup_entry(&mut miscounted_backup, &encryption_key, &SerializableBackupEntry::Key(ServerKeyId::from(4).into(), key.clone().into())).unwrap();
+		write_backup_entry(&mut miscounted_backup, &encryption_key, &SerializableBackupEntry::End(2)).unwrap();
+		assert!(restore_key_storage(restored_key_storage, &self_key_pair, &mut &miscounted_backup[..]).is_err());
+		assert!(sorted_snapshot(restored_key_storage).is_empty());
+
+		assert_eq!(restore_key_storage(restored_key_storage, &self_key_pair, &mut &backup[..]), Ok(report));
+		assert_eq!(sorted_snapshot(restored_key_storage), snapshot);
+
+		// backup is never restored to non-empty storage
+		assert!(restore_key_storage(restored_key_storage, &self_key_pair, &mut &backup[..]).is_err());
+		assert!(restored_key_storage.restore(Vec::new()).is_err());
+	}
+
+	fn check_restore_to_non_empty_key_storage(key_storage_with_nonce: &KeyStorage, key_storage_with_tombstone: &KeyStorage) {
+		key_storage_with_nonce.set_max_session_nonce(&Public::default(), "generation", 10).unwrap();
+		assert!(key_storage_with_nonce.restore(vec![KeyStorageSnapshotEntry::Tombstone(ServerKeyId::from(1))]).is_err());
+		assert!(!key_storage_with_nonce.is_tombstoned(&ServerKeyId::from(1)));
+
+		key_storage_with_tombstone.tombstone(&ServerKeyId::from(1)).unwrap();
+		assert!(key_storage_with_tombstone.restore(vec![KeyStorageSnapshotEntry::SessionNonce(Public::default(), "generation".into(), 10)]).is_err());
+		assert_eq!(key_storage_with_tombstone.max_session_nonce(&Public::default(), "generation"), Ok(None));
+	}
+
+	#[test]
+	fn key_storages_are_backed_up_and_restored() {
+		let self_key_pair = Random.generate().unwrap();
+		let path = RandomTempPath::create_dir();
+		let restored_path = RandomTempPath::create_dir();
+		let db = Database::open_default(path.as_str()).unwrap();
+		let restored_db = Database::open_default(restored_path.as_str()).unwrap();
+		check_key_storage_backup(&open_key_storage(db, &self_key_pair).unwrap(),
+			&open_key_storage(restored_db, &self_key_pair).unwrap(), &self_key_pair);
+
+		let path = RandomTempPath::create_dir();
+		let restored_path = RandomTempPath::create_dir();
+		let node_key_pair = PlainNodeKeyPair::new(self_key_pair.clone());
+		check_key_storage_backup(&FileKeyStorage::new(path.as_str(), &node_key_pair).unwrap(),
+			&FileKeyStorage::new(restored_path.as_str(), &node_key_pair).unwrap(), &self_key_pair);
+
+		check_key_storage_backup(&DummyKeyStorage::default(), &DummyKeyStorage::default(), &self_key_pair);
+	}
+
+	#[test]
+	fn backup_is_not_restored_to_key_storage_with_tombstones_or_session_nonces() {
+		let self_key_pair = Random.generate().unwrap();
+		let path = RandomTempPath::create_dir();
+		let other_path = RandomTempPath::create_dir();
+		check_restore_to_non_empty_key_storage(&open_key_storage(Database::open_default(path.as_str()).unwrap(), &self_key_pair).unwrap(),
+			&open_key_storage(Database::open_default(other_path.as_str()).unwrap(), &self_key_pair).unwrap());
+
+		let path = RandomTempPath::create_dir();
+		let other_path = RandomTempPath::create_dir();
+		let node_key_pair = PlainNodeKeyPair::new(self_key_pair.clone());
+		check_restore_to_non_empty_key_storage(&FileKeyStorage::new(path.as_str(), &node_key_pair).unwrap(),
+			&FileKeyStorage::new(other_path.as_str(), &node_key_pair).unwrap());
+
+		check_restore_to_non_empty_key_storage(&DummyKeyStorage::default(), &DummyKeyStorage::default());
+	}
+
+	#[test]
+	fn key_shares_are_not_readable_with_other_node_key() {
+		let path = RandomTempPath::create_dir();
+		let key_id = ServerKeyId::from(1);
+		let key = DocumentKeyShare {
+			author: Public::default(),
+			threshold: 0,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone())
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+
+		{
+			let db = Database::open_default(path.as_str()).unwrap();
+			let key_storage = open_key_storage(db, &Random.generate().unwrap()).unwrap();
+			key_storage.insert(key_id.clone(), key).unwrap();
+		}
+
+		let db = Database::open_default(path.as_str()).unwrap();
+		let key_storage = open_key_storage(db, &Random.generate().unwrap()).unwrap();
+		assert!(key_storage.contains(&key_id));
+		assert!(key_storage.get(&key_id).is_err());
+	}
+
+	#[test]
+	fn file_key_storage() {
+		let path = RandomTempPath::create_dir();
+		let self_key_pair = PlainNodeKeyPair::new(Random.generate().unwrap());
+		let node: NodeId = Random.generate().unwrap().public().clone();
+
+		let key_id = ServerKeyId::from(1);
+		let value1 = DocumentKeyShare {
+			author: Public::default(),
+			threshold: 1,
+			id_numbers: vec![
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone()),
+				(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone()),
+			].into_iter().collect(),
+			secret_share: Random.generate().unwrap().secret().clone(),
+			common_point: None,
+			encrypted_point: None,
+			refresh_id: None,
+			metadata: Default::default(),
+		};
+		let mut value2 = value1.clone();
+		value2.id_numbers.insert(Random.generate().unwrap().public().clone(), Random.generate().unwrap().secret().clone());
+
+		{
+			let key_storage = FileKeyStorage::new(path.as_str(), &self_key_pair).unwrap();
+			assert_eq!(key_storage.documents(), Ok(vec![]));
+			key_storage.insert(key_id.clone(), value1.clone()).unwrap();
+			key_storage.update(key_id.clone(), value2.clone()).unwrap();
+			key_storage.insert(ServerKeyId::from(2), value1.clone()).unwrap();
+			key_storage.remove(&ServerKeyId::from(2)).unwrap();
+			key_storage.insert(ServerKeyId::from(3), value1.clone()).unwrap();
+			key_storage.tombstone(&ServerKeyId::from(3)).unwrap();
+			key_storage.set_max_session_nonce(&node, "generation", 10).unwrap();
+		}
+
+		let key_storage = FileKeyStorage::new(path.as_str(), &self_key_pair).unwrap();
+		assert_eq!(key_storage.documents(), Ok(vec![key_id.clone()]));
+		assert_eq!(key_storage.get(&key_id), Ok(value2.clone()));
+		assert_eq!(key_storage.get_version(&key_id, &value1.version()), Ok(value1.clone()));
+		assert_eq!(key_storage.versions(&key_id), Ok(vec![value2.version(), value1.version()]));
+		assert_eq!(key_storage.max_session_nonce(&node, "generation"), Ok(Some(10)));
+		assert!(!key_storage.is_tombstoned(&ServerKeyId::from(2)));
+		assert!(key_storage.is_tombstoned(&ServerKeyId::from(3)));
+
+		// storage file could not be read without node key
+		assert!(FileKeyStorage::new(path.as_str(), &PlainNodeKeyPair::new(Random.generate().unwrap())).is_err());
 	}
 }
@@ -21,6 +21,7 @@ extern crate log;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate hyper;
+extern crate openssl;
 #[macro_use]
 extern crate lazy_static;
 extern crate parking_lot;
@@ -47,6 +48,7 @@ extern crate ethcrypto;
 extern crate ethkey;
 extern crate native_contracts;
 extern crate hash;
+extern crate igd;
 
 mod key_server_cluster;
 mod types;
@@ -57,33 +59,84 @@ mod traits {
 }
 
 mod acl_storage;
+mod acl_rekeying;
+mod audit_log;
+mod decryption_cache;
 mod http_listener;
+mod http_tls;
+mod listener;
 mod key_server;
 mod key_storage;
 mod serialization;
 mod key_server_set;
+mod nat;
 mod node_key_pair;
+mod peer_filter;
+mod service_contract_listener;
 
 use std::sync::Arc;
 use ethcore::client::Client;
 
 pub use types::all::{ServerKeyId, EncryptedDocumentKey, RequestSignature, Public,
-	Error, NodeAddress, ServiceConfiguration, ClusterConfiguration};
+	Error, NodeAddress, ContractAddress, ServiceConfiguration, ClusterConfiguration, KeyStorageBackend, SessionsRateLimits,
+	ClusterTimeouts, PeerRateLimits, ErrorCode, KeyBackupReport, AdminRequestSignature};
 pub use traits::{NodeKeyPair, KeyServer};
+pub use acl_storage::{AclStorage, HttpAclStorage};
 pub use self::node_key_pair::{PlainNodeKeyPair, KeyStoreNodeKeyPair};
+pub use key_server_cluster::{KeyStorage, AuditedKeyShare, ClusterShareReport, ShareInconsistency, check_cluster_shares};
+pub use key_storage::{KeyStorageSnapshot, KeyStorageSnapshotEntry, backup_key_storage, restore_key_storage};
+#[cfg(feature = "test-helpers")]
+pub use key_server_cluster::simulator;
+#[cfg(feature = "fault-injection")]
+pub use key_server_cluster::fault_injection;
+
+/// Open key shares storage of given node, located in given secret store data directory
+pub fn open_key_storage(backend: &KeyStorageBackend, data_path: &str, self_key_pair: &NodeKeyPair) -> Result<Arc<KeyStorage>, Error> {
+	let key_storage: Arc<KeyStorage> = match *backend {
+		KeyStorageBackend::Database => Arc::new(key_storage::PersistentKeyStorage::new(data_path, self_key_pair)?),
+		KeyStorageBackend::File => Arc::new(key_storage::FileKeyStorage::new(data_path, self_key_pair)?),
+		KeyStorageBackend::Memory => Arc::new(key_storage::MemoryKeyStorage::default()),
+	};
+	Ok(key_storage)
+}
 
 /// Start new key server instance
 pub fn start(client: Arc<Client>, self_key_pair: Arc<NodeKeyPair>, config: ServiceConfiguration) -> Result<Box<KeyServer>, Error> {
-	use std::sync::Arc;
-
-	let acl_storage: Arc<acl_storage::AclStorage> = if config.acl_check_enabled {
-			acl_storage::OnChainAclStorage::new(&client)
-		} else {
-			Arc::new(acl_storage::DummyAclStorage::default())
+	let acl_storage: Arc<AclStorage> = match (config.acl_check_enabled, config.acl_file.as_ref(), config.acl_service.as_ref()) {
+			(true, Some(acl_file), _) => Arc::new(acl_storage::FileAclStorage::new(acl_file)?),
+			(true, None, Some(acl_service)) => Arc::new(HttpAclStorage::new(acl_service.clone(), config.acl_cache_ttl)),
+			(true, None, None) => acl_storage::OnChainAclStorage::new(&client),
+			(false, _, _) => Arc::new(acl_storage::DummyAclStorage::default()),
 		};
+	start_with_acl_storage(client, self_key_pair, config, acl_storage)
+}
+
+/// Start new key server instance, which is using given ACL storage to check access to keys.
+/// ACL-related options of the configuration are ignored.
+pub fn start_with_acl_storage(client: Arc<Client>, self_key_pair: Arc<NodeKeyPair>, config: ServiceConfiguration, acl_storage: Arc<AclStorage>) -> Result<Box<KeyServer>, Error> {
 	let key_server_set = key_server_set::OnChainKeyServerSet::new(&client, config.cluster_config.nodes.clone())?;
-	let key_storage = Arc::new(key_storage::PersistentKeyStorage::new(&config)?);
-	let key_server = key_server::KeyServerImpl::new(&config.cluster_config, key_server_set, self_key_pair, acl_storage, key_storage)?;
-	let listener = http_listener::KeyServerHttpListener::start(config.listener_address, key_server)?;
+	let key_storage = open_key_storage(&config.key_storage, &config.data_path, &*self_key_pair)?;
+	let audit_log: Option<Arc<audit_log::AuditLog>> = match config.audit_log {
+		Some(ref audit_log) => Some(Arc::new(audit_log::FileAuditLog::new(audit_log)?)),
+		None => None,
+	};
+	let peer_filter = Arc::new(peer_filter::FilePeerFilter::new(&config.data_path)?);
+	let key_server = Arc::new(key_server::KeyServerImpl::new(&config.cluster_config, key_server_set.clone(), self_key_pair.clone(), acl_storage.clone(), key_storage, peer_filter, audit_log)?);
+	let acl_rekeying = config.rekeyings_per_minute.map(|rekeyings_per_minute|
+		acl_rekeying::AclRekeying::new(acl_storage, key_server.cluster(), self_key_pair.clone(), key_server_set.clone(), rekeyings_per_minute));
+	let http_access_policy = http_listener::HttpAccessPolicy::new(config.http_api_keys.as_ref().map(|p| p.as_str()), config.http_cors, config.http_hosts)?;
+	let http_tls_server = match (config.http_tls_certificate, config.http_tls_private_key) {
+		(Some(certificate), Some(private_key)) => Some(http_tls::TlsServer::new(&http_tls::HttpTlsConfiguration {
+			certificate: certificate,
+			private_key: private_key,
+			client_ca: config.http_tls_client_ca,
+		})?),
+		(None, None) => None,
+		_ => return Err(Error::Internal("both certificate and private key are required to enable TLS".into())),
+	};
+	let http_listener = http_listener::KeyServerHttpListener::start(config.listener_address, config.health_check_enabled, http_access_policy, http_tls_server, key_server.clone())?;
+	let contract_listener = config.service_contract_address.map(|service_contract_address|
+		service_contract_listener::ServiceContractListener::new(&client, service_contract_address, key_server.clone(), self_key_pair, key_server_set));
+	let listener = listener::Listener::new(key_server, http_listener, contract_listener, acl_rekeying);
 	Ok(Box::new(listener))
 }
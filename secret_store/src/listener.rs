@@ -0,0 +1,193 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use bigint::hash::H256;
+use acl_rekeying::AclRekeying;
+use http_listener::KeyServerHttpListener;
+use service_contract_listener::ServiceContractListener;
+use traits::{ServerKeyGenerator, DocumentKeyServer, MessageSigner, AuditLogReader, MetricsReader, AdministrationServer, KeyServer};
+use types::all::{Error, Public, NodeId, MessageHash, EncryptedMessageSignature, RequestSignature, ServerKeyId,
+	EncryptedDocumentKey, EncryptedDocumentKeyShadow, EncryptedDocumentKeyWithEntropy, DocumentKeyShadows, ReEncryptedDocumentKey,
+	AuditRecord, ClusterHealth, DrainReport, BootstrapReport, PeerLists, KeyList, RemovedKeyInfo, AttestedServerKeyPublic,
+	ExportedKeyShare, KeyExportReport, KeyBackupReport, AdminRequestSignature};
+
+/// Key server, accepting requests from all configured listeners.
+pub struct Listener {
+	/// Key server.
+	key_server: Arc<KeyServer>,
+	/// HTTP requests listener.
+	_http: KeyServerHttpListener,
+	/// Service contract requests listener.
+	_contract: Option<Arc<ServiceContractListener>>,
+	/// ACL re-keying policy.
+	_acl_rekeying: Option<AclRekeying>,
+}
+
+impl Listener {
+	/// Create new listener.
+	pub fn new(key_server: Arc<KeyServer>, http: KeyServerHttpListener, contract: Option<Arc<ServiceContractListener>>, acl_rekeying: Option<AclRekeying>) -> Self {
+		Listener {
+			key_server: key_server,
+			_http: http,
+			_contract: contract,
+			_acl_rekeying: acl_rekeying,
+		}
+	}
+}
+
+impl KeyServer for Listener {}
+
+impl ServerKeyGenerator for Listener {
+	fn generate_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<Public, Error> {
+		self.key_server.generate_key(key_id, signature, threshold)
+	}
+
+	fn derive_key(&self, parent_key_id: &ServerKeyId, signature: &RequestSignature, derivation_path: &H256) -> Result<Public, Error> {
+		self.key_server.derive_key(parent_key_id, signature, derivation_path)
+	}
+
+	fn delete_key(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<BTreeSet<NodeId>, Error> {
+		self.key_server.delete_key(key_id, signature)
+	}
+
+	fn restore_key_public(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<AttestedServerKeyPublic, Error> {
+		self.key_server.restore_key_public(key_id, signature)
+	}
+}
+
+impl DocumentKeyServer for Listener {
+	fn store_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, common_point: Public, encrypted_document_key: Public) -> Result<(), Error> {
+		self.key_server.store_document_key(key_id, signature, common_point, encrypted_document_key)
+	}
+
+	fn generate_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<EncryptedDocumentKey, Error> {
+		self.key_server.generate_document_key(key_id, signature, threshold)
+	}
+
+	fn generate_document_key_with_entropy(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize, requester_entropy: Public) -> Result<EncryptedDocumentKeyWithEntropy, Error> {
+		self.key_server.generate_document_key_with_entropy(key_id, signature, threshold, requester_entropy)
+	}
+
+	fn restore_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<EncryptedDocumentKey, Error> {
+		self.key_server.restore_document_key(key_id, signature)
+	}
+
+	fn restore_document_key_shadow(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<EncryptedDocumentKeyShadow, Error> {
+		self.key_server.restore_document_key_shadow(key_id, signature)
+	}
+
+	fn restore_document_key_shadows(&self, requests: Vec<(ServerKeyId, RequestSignature)>) -> Result<DocumentKeyShadows, Error> {
+		self.key_server.restore_document_key_shadows(requests)
+	}
+
+	fn reencrypt_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, target_public: &Public, target_signature: &RequestSignature) -> Result<ReEncryptedDocumentKey, Error> {
+		self.key_server.reencrypt_document_key(key_id, signature, target_public, target_signature)
+	}
+}
+
+impl MessageSigner for Listener {
+	fn sign_message(&self, key_id: &ServerKeyId, signature: &RequestSignature, message: MessageHash) -> Result<EncryptedMessageSignature, Error> {
+		self.key_server.sign_message(key_id, signature, message)
+	}
+
+	fn sign_message_ecdsa(&self, key_id: &ServerKeyId, signature: &RequestSignature, message: MessageHash) -> Result<EncryptedMessageSignature, Error> {
+		self.key_server.sign_message_ecdsa(key_id, signature, message)
+	}
+}
+
+impl AuditLogReader for Listener {
+	fn key_audit_log(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<Vec<AuditRecord>, Error> {
+		self.key_server.key_audit_log(key_id, signature)
+	}
+}
+
+impl MetricsReader for Listener {
+	fn metrics(&self) -> Result<String, Error> {
+		self.key_server.metrics()
+	}
+
+	fn health(&self) -> Result<ClusterHealth, Error> {
+		self.key_server.health()
+	}
+}
+
+impl AdministrationServer for Listener {
+	fn drain(&self, signature: &AdminRequestSignature, targets: BTreeSet<NodeId>) -> Result<DrainReport, Error> {
+		self.key_server.drain(signature, targets)
+	}
+
+	fn drain_report(&self, signature: &AdminRequestSignature, id: u64) -> Result<DrainReport, Error> {
+		self.key_server.drain_report(signature, id)
+	}
+
+	fn cancel_drain(&self, signature: &AdminRequestSignature, id: u64) -> Result<DrainReport, Error> {
+		self.key_server.cancel_drain(signature, id)
+	}
+
+	fn peer_lists(&self, signature: &AdminRequestSignature) -> Result<PeerLists, Error> {
+		self.key_server.peer_lists(signature)
+	}
+
+	fn allow_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error> {
+		self.key_server.allow_peer(signature, node)
+	}
+
+	fn deny_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error> {
+		self.key_server.deny_peer(signature, node)
+	}
+
+	fn forget_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error> {
+		self.key_server.forget_peer(signature, node)
+	}
+
+	fn set_key_label(&self, signature: &AdminRequestSignature, key_id: &ServerKeyId, label: Option<String>) -> Result<(), Error> {
+		self.key_server.set_key_label(signature, key_id, label)
+	}
+
+	fn list_keys(&self, signature: &AdminRequestSignature, after: Option<ServerKeyId>, limit: usize) -> Result<KeyList, Error> {
+		self.key_server.list_keys(signature, after, limit)
+	}
+
+	fn list_removed_keys(&self, signature: &AdminRequestSignature) -> Result<Vec<RemovedKeyInfo>, Error> {
+		self.key_server.list_removed_keys(signature)
+	}
+
+	fn restore_removed_key(&self, signature: &AdminRequestSignature, key_id: &ServerKeyId) -> Result<(), Error> {
+		self.key_server.restore_removed_key(signature, key_id)
+	}
+
+	fn bootstrap_node(&self, signature: &AdminRequestSignature, new_node: NodeId, after: Option<ServerKeyId>) -> Result<BootstrapReport, Error> {
+		self.key_server.bootstrap_node(signature, new_node, after)
+	}
+
+	fn export_keys(&self, signature: &AdminRequestSignature, keys: BTreeSet<ServerKeyId>, targets: BTreeSet<NodeId>, threshold: usize) -> Result<KeyExportReport, Error> {
+		self.key_server.export_keys(signature, keys, targets, threshold)
+	}
+
+	fn import_key_share(&self, signature: &AdminRequestSignature, key_share: ExportedKeyShare) -> Result<(), Error> {
+		self.key_server.import_key_share(signature, key_share)
+	}
+
+	fn backup_keys(&self, signature: &AdminRequestSignature, file_name: String) -> Result<KeyBackupReport, Error> {
+		self.key_server.backup_keys(signature, file_name)
+	}
+
+	fn restore_keys(&self, signature: &AdminRequestSignature, file_name: String) -> Result<KeyBackupReport, Error> {
+		self.key_server.restore_keys(signature, file_name)
+	}
+}
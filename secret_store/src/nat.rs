@@ -0,0 +1,60 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+use std::net::{SocketAddr, SocketAddrV4};
+use igd::{PortMappingProtocol, search_gateway_from_timeout};
+
+/// Gateway search timeout (seconds).
+const GATEWAY_SEARCH_TIMEOUT: u64 = 5;
+
+/// Map cluster listener port on the gateway using UPnP. Returns external address of the listener.
+/// Only IPv4 listeners, bound to the specific interface, could be mapped.
+pub fn map_external_address(local_address: &SocketAddr) -> Option<SocketAddr> {
+	let local_address = match *local_address {
+		SocketAddr::V4(ref local_address) if !local_address.ip().is_unspecified() => local_address.clone(),
+		_ => {
+			warn!(target: "secretstore_net", "UPnP port mapping requires cluster listener to be bound to the IPv4 interface address. Listener address: {}", local_address);
+			return None;
+		},
+	};
+
+	let gateway = match search_gateway_from_timeout(local_address.ip().clone(), Duration::from_secs(GATEWAY_SEARCH_TIMEOUT)) {
+		Ok(gateway) => gateway,
+		Err(err) => {
+			warn!(target: "secretstore_net", "UPnP gateway search has failed: {}", err);
+			return None;
+		},
+	};
+	let external_ip = match gateway.get_external_ip() {
+		Ok(external_ip) => external_ip,
+		Err(err) => {
+			warn!(target: "secretstore_net", "UPnP external IP request has failed: {}", err);
+			return None;
+		},
+	};
+	match gateway.add_any_port(PortMappingProtocol::TCP, local_address, 0, "Parity SecretStore/TCP") {
+		Ok(external_port) => {
+			let external_address = SocketAddr::V4(SocketAddrV4::new(external_ip, external_port));
+			info!(target: "secretstore_net", "Cluster listener {} is mapped to external address {}", local_address, external_address);
+			Some(external_address)
+		},
+		Err(err) => {
+			warn!(target: "secretstore_net", "UPnP port mapping has failed: {}", err);
+			None
+		},
+	}
+}
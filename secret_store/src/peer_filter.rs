@@ -0,0 +1,221 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::collections::BTreeSet;
+use parking_lot::{Mutex, RwLock};
+use serde_json;
+use serialization::SerializablePeerLists;
+use types::all::{Error, NodeId, PeerLists};
+
+/// Name of the peer lists file in the secret store data directory.
+const PEER_LISTS_FILE_NAME: &'static str = "peer_lists.json";
+
+/// Allow/deny lists of cluster connections. Nodes still must be the part of the key server set to connect.
+pub trait PeerFilter: Send + Sync {
+	/// Check if connection with given node is allowed.
+	fn is_allowed(&self, node: &NodeId) -> bool;
+	/// Get current lists.
+	fn lists(&self) -> PeerLists;
+	/// Add node to the allow list (removing it from the deny list).
+	fn allow(&self, node: NodeId) -> Result<(), Error>;
+	/// Add node to the deny list (removing it from the allow list).
+	fn deny(&self, node: NodeId) -> Result<(), Error>;
+	/// Remove node from both lists.
+	fn forget(&self, node: &NodeId) -> Result<(), Error>;
+}
+
+/// In-memory peer filter. Lists are lost on restart.
+#[derive(Default)]
+pub struct MemoryPeerFilter {
+	/// Nodes, which are allowed to connect.
+	allowed: RwLock<BTreeSet<NodeId>>,
+	/// Nodes, which are never allowed to connect.
+	denied: RwLock<BTreeSet<NodeId>>,
+}
+
+/// File-based peer filter. Lists are written to the file on every change.
+pub struct FilePeerFilter {
+	/// Path to the lists file.
+	path: PathBuf,
+	/// Actual lists.
+	filter: MemoryPeerFilter,
+	/// Is held while lists are changed && written to the file.
+	write_lock: Mutex<()>,
+}
+
+impl MemoryPeerFilter {
+	fn from_lists(lists: PeerLists) -> Self {
+		MemoryPeerFilter {
+			allowed: RwLock::new(lists.allowed.into_iter().collect()),
+			denied: RwLock::new(lists.denied.into_iter().collect()),
+		}
+	}
+}
+
+impl PeerFilter for MemoryPeerFilter {
+	fn is_allowed(&self, node: &NodeId) -> bool {
+		if self.denied.read().contains(node) {
+			return false;
+		}
+
+		let allowed = self.allowed.read();
+		allowed.is_empty() || allowed.contains(node)
+	}
+
+	fn lists(&self) -> PeerLists {
+		PeerLists {
+			allowed: self.allowed.read().iter().cloned().collect(),
+			denied: self.denied.read().iter().cloned().collect(),
+		}
+	}
+
+	fn allow(&self, node: NodeId) -> Result<(), Error> {
+		self.denied.write().remove(&node);
+		self.allowed.write().insert(node);
+		Ok(())
+	}
+
+	fn deny(&self, node: NodeId) -> Result<(), Error> {
+		self.allowed.write().remove(&node);
+		self.denied.write().insert(node);
+		Ok(())
+	}
+
+	fn forget(&self, node: &NodeId) -> Result<(), Error> {
+		self.allowed.write().remove(node);
+		self.denied.write().remove(node);
+		Ok(())
+	}
+}
+
+impl FilePeerFilter {
+	/// Open (create if not exists) peer lists file, located in given secret store data directory.
+	pub fn new(data_path: &str) -> Result<Self, Error> {
+		let mut path = PathBuf::from(data_path);
+		fs::create_dir_all(&path)
+			.map_err(|e| Error::Database(format!("error creating peer lists directory: {}", e)))?;
+		path.push(PEER_LISTS_FILE_NAME);
+
+		let lists = if path.exists() {
+			let file = fs::File::open(&path)
+				.map_err(|e| Error::Database(format!("error opening peer lists: {}", e)))?;
+			serde_json::from_reader::<_, SerializablePeerLists>(file)?.into()
+		} else {
+			PeerLists::default()
+		};
+
+		Ok(FilePeerFilter {
+			path: path,
+			filter: MemoryPeerFilter::from_lists(lists),
+			write_lock: Mutex::new(()),
+		})
+	}
+
+	/// Change lists && write them to the file.
+	fn update<F>(&self, update: F) -> Result<(), Error> where F: FnOnce(&MemoryPeerFilter) -> Result<(), Error> {
+		let _write_lock = self.write_lock.lock();
+		update(&self.filter)?;
+
+		// write to temporary file first, so that lists are never partially written
+		let lists = serde_json::to_vec(&SerializablePeerLists::from(self.filter.lists()))?;
+		let tmp_path = self.path.with_extension("json.tmp");
+		fs::File::create(&tmp_path)
+			.and_then(|mut file| file.write_all(&lists).and_then(|_| file.sync_all()))
+			.and_then(|_| fs::rename(&tmp_path, &self.path))
+			.map_err(|e| Error::Database(format!("error writing peer lists: {}", e)))
+	}
+}
+
+impl PeerFilter for FilePeerFilter {
+	fn is_allowed(&self, node: &NodeId) -> bool {
+		self.filter.is_allowed(node)
+	}
+
+	fn lists(&self) -> PeerLists {
+		self.filter.lists()
+	}
+
+	fn allow(&self, node: NodeId) -> Result<(), Error> {
+		self.update(move |filter| filter.allow(node))
+	}
+
+	fn deny(&self, node: NodeId) -> Result<(), Error> {
+		self.update(move |filter| filter.deny(node))
+	}
+
+	fn forget(&self, node: &NodeId) -> Result<(), Error> {
+		self.update(|filter| filter.forget(node))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use devtools::RandomTempPath;
+	use ethkey::{Random, Generator};
+	use types::all::PeerLists;
+	use super::{PeerFilter, MemoryPeerFilter, FilePeerFilter};
+
+	#[test]
+	fn memory_peer_filter_works() {
+		let node1 = Random.generate().unwrap().public().clone();
+		let node2 = Random.generate().unwrap().public().clone();
+		let filter = MemoryPeerFilter::default();
+
+		// everyone is allowed by default
+		assert!(filter.is_allowed(&node1));
+		assert!(filter.is_allowed(&node2));
+
+		// denied node is not allowed
+		filter.deny(node1.clone()).unwrap();
+		assert!(!filter.is_allowed(&node1));
+		assert!(filter.is_allowed(&node2));
+
+		// when allow list is not empty, only allowed nodes are allowed
+		filter.allow(node1.clone()).unwrap();
+		assert!(filter.is_allowed(&node1));
+		assert!(!filter.is_allowed(&node2));
+		assert_eq!(filter.lists(), PeerLists { allowed: vec![node1.clone()], denied: vec![] });
+
+		// forgotten node is removed from both lists
+		filter.forget(&node1).unwrap();
+		assert!(filter.is_allowed(&node1));
+		assert!(filter.is_allowed(&node2));
+		assert_eq!(filter.lists(), PeerLists::default());
+	}
+
+	#[test]
+	fn file_peer_filter_works() {
+		let path = RandomTempPath::create_dir();
+		let data_path = path.as_str().to_owned();
+		let node1 = Random.generate().unwrap().public().clone();
+		let node2 = Random.generate().unwrap().public().clone();
+
+		{
+			let filter = FilePeerFilter::new(&data_path).unwrap();
+			filter.deny(node1.clone()).unwrap();
+			filter.allow(node2.clone()).unwrap();
+		}
+
+		// lists are preserved when file is reopened
+		let filter = FilePeerFilter::new(&data_path).unwrap();
+		assert!(!filter.is_allowed(&node1));
+		assert!(filter.is_allowed(&node2));
+		assert_eq!(filter.lists(), PeerLists { allowed: vec![node2.clone()], denied: vec![node1.clone()] });
+	}
+}
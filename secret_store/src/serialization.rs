@@ -15,6 +15,7 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::fmt;
+use std::collections::BTreeMap;
 use std::cmp::{Ord, PartialOrd, Ordering};
 use std::ops::Deref;
 use rustc_hex::{ToHex, FromHex};
@@ -23,6 +24,8 @@ use serde::de::{Visitor, Error as SerdeError};
 use ethkey::{Public, Secret, Signature};
 use bigint::hash::H256;
 use bytes::Bytes;
+use types::all::{AuditOperation, AuditRecord, PeerHealth, ClusterHealth, DrainReport, BootstrapReport, PeerLists, KeyInfo, KeyList, RemovedKeyInfo, KeyBackupReport,
+	AttestedServerKeyPublic, ExportedKeyShare, ExportedShareContribution, KeyExportReport, ErrorCode};
 
 /// Serializable message hash.
 pub type SerializableMessageHash = SerializableH256;
@@ -36,6 +39,512 @@ pub struct SerializableEncryptedDocumentKeyShadow {
 	pub common_point: SerializablePublic,
 	/// If shadow decryption was requested: shadow decryption coefficients, encrypted with requestor public.
 	pub decrypt_shadows: Vec<SerializableBytes>,
+	/// Encrypted point of the document key.
+	pub encrypted_point: SerializablePublic,
+	/// Proofs of nodes decryption shares, encrypted with requestor public.
+	pub decrypt_proofs: Vec<SerializableBytes>,
+}
+
+/// Serializable document key, generated using requester entropy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableEncryptedDocumentKeyWithEntropy {
+	/// Document key, encrypted with requester public key.
+	pub encrypted_key: SerializableBytes,
+	/// Server entropy point.
+	pub server_entropy: SerializablePublic,
+}
+
+/// Serializable server key public, attested by key holders.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableAttestedServerKeyPublic {
+	/// Public portion of server key.
+	pub public: SerializablePublic,
+	/// Attestations of key holders.
+	pub attestations: Vec<SerializableServerKeyAttestation>,
+}
+
+/// Serializable attestation of server key public.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableServerKeyAttestation {
+	/// Key holder.
+	pub node: SerializablePublic,
+	/// keccak(key_id | public), signed with key holder node key.
+	pub signature: SerializableSignature,
+}
+
+/// Serializable request of single document key shadow from the batch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableDocumentKeyShadowRequest {
+	/// Server key id.
+	pub key_id: SerializableH256,
+	/// Server key id, signed by requester.
+	pub signature: SerializableSignature,
+}
+
+/// Serializable result of single document key shadow retrieval from the batch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableDocumentKeyShadowResult {
+	/// Server key id.
+	pub key_id: SerializableH256,
+	/// Document key shadow. None if retrieval has failed.
+	pub shadow: Option<SerializableEncryptedDocumentKeyShadow>,
+	/// Retrieval error.
+	pub error: Option<String>,
+}
+
+/// Serializable re-encryption result.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableReEncryptedDocumentKey {
+	/// Shared common point, computed for target public key.
+	pub common_point: SerializablePublic,
+	/// Document key, encrypted with target public key.
+	pub encrypted_point: SerializablePublic,
+}
+
+/// Serializable operation, requested from the key server.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SerializableAuditOperation {
+	GenerateServerKey,
+	StoreDocumentKey,
+	GenerateDocumentKey,
+	RestoreDocumentKey,
+	RestoreDocumentKeyShadow,
+	ReEncryptDocumentKey,
+	SignMessage,
+	SignMessageEcdsa,
+	DeriveServerKey,
+	DeleteServerKey,
+	RestoreServerKeyPublic,
+}
+
+/// Serializable audit log record.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableAuditRecord {
+	/// Requested operation.
+	pub operation: SerializableAuditOperation,
+	/// Server key id.
+	pub key_id: SerializableH256,
+	/// Requester public.
+	pub requester: Option<SerializablePublic>,
+	/// Nodes, connected to this node when operation has been started.
+	pub nodes: Vec<SerializablePublic>,
+	/// Operation start time (seconds since unix epoch).
+	pub started: u64,
+	/// Operation finish time (seconds since unix epoch).
+	pub finished: u64,
+	/// Operation error.
+	pub error: Option<String>,
+}
+
+/// Serializable health of other key server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializablePeerHealth {
+	/// Key server id.
+	pub node: SerializablePublic,
+	/// Is connection to the key server established.
+	pub is_connected: bool,
+	/// Time (seconds since unix epoch), when the last session with this key server has completed successfully.
+	pub last_session: Option<u64>,
+	/// Difference between key server clock && this node clock (in seconds).
+	pub clock_skew: Option<i64>,
+}
+
+/// Serializable health of the key server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableClusterHealth {
+	/// All other key servers.
+	pub peers: Vec<SerializablePeerHealth>,
+	/// Is key storage available.
+	pub is_key_storage_available: bool,
+	/// Is this key server reachable through its external address.
+	pub is_externally_reachable: Option<bool>,
+}
+
+/// Serializable result of key server drain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableDrainReport {
+	/// Id of the drain.
+	pub id: u64,
+	/// Keys, which shares have been moved to other key servers.
+	pub moved_keys: Vec<SerializableH256>,
+	/// Keys, which shares have failed to move.
+	pub failed_keys: Vec<SerializableH256>,
+	/// Error, which has stopped the drain.
+	pub error: Option<String>,
+	/// Is the drain completed.
+	pub is_completed: bool,
+	/// Has the drain been cancelled.
+	pub is_cancelled: bool,
+	/// Is it safe to shutdown the key server.
+	pub is_safe_to_shutdown: bool,
+}
+
+/// Serializable result of key server bootstrap.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableBootstrapReport {
+	/// Keys, which shares have been moved to the new key server.
+	pub moved_keys: Vec<SerializableH256>,
+	/// Keys, which shares have not been moved.
+	pub skipped_keys: Vec<SerializableH256>,
+	/// Error, which has stopped the bootstrap.
+	pub error: Option<String>,
+	/// Id of the key, after which the bootstrap must be resumed.
+	pub next: Option<SerializableH256>,
+}
+
+/// Serializable peer access lists.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SerializablePeerLists {
+	/// Nodes, which are allowed to connect.
+	pub allowed: Vec<SerializablePublic>,
+	/// Nodes, which are never allowed to connect.
+	pub denied: Vec<SerializablePublic>,
+}
+
+/// Serializable key, stored by key server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableKeyInfo {
+	/// Server key id.
+	pub key_id: SerializableH256,
+	/// Key threshold.
+	pub threshold: usize,
+	/// Nodes, holding shares of the key.
+	pub holders: Vec<SerializablePublic>,
+	/// Time when the key has been generated (seconds since unix epoch).
+	pub created: Option<u64>,
+	/// Human-readable label of the key.
+	pub label: Option<String>,
+	/// Requester, which has triggered key generation.
+	pub requester: Option<SerializablePublic>,
+	/// Last time when the key has been accessed (seconds since unix epoch).
+	pub last_accessed: Option<u64>,
+}
+
+/// Serializable page of keys, stored by key server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableKeyList {
+	/// Keys, in ascending order of their ids.
+	pub keys: Vec<SerializableKeyInfo>,
+	/// Id of the last key from this page, if there are more keys to list.
+	pub next: Option<SerializableH256>,
+}
+
+/// Serializable key, removed from key server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableRemovedKeyInfo {
+	/// Server key id.
+	pub key_id: SerializableH256,
+	/// Time when the key has been removed (seconds since unix epoch).
+	pub removed: u64,
+	/// True if the key has been deleted.
+	pub is_deleted: bool,
+}
+
+/// Serializable result of key storage backup (or restore).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableKeyBackupReport {
+	/// Number of keys, written to (or restored from) the backup.
+	pub keys: usize,
+	/// Number of removed keys, written to (or restored from) the backup.
+	pub removed_keys: usize,
+	/// Number of deleted keys tombstones, written to (or restored from) the backup.
+	pub tombstones: usize,
+}
+
+/// Serializable keys export request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableKeyExportRequest {
+	/// Keys to export.
+	pub keys: Vec<SerializableH256>,
+	/// Nodes of the target cluster.
+	pub targets: Vec<SerializablePublic>,
+}
+
+/// Serializable key share, exported for the node of other cluster.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableExportedKeyShare {
+	/// Server key id.
+	pub key_id: SerializableH256,
+	/// Target node, which must import this key share.
+	pub node: SerializablePublic,
+	/// Public portion of server key.
+	pub public: SerializablePublic,
+	/// Key author.
+	pub author: SerializablePublic,
+	/// Key threshold in the target cluster.
+	pub threshold: usize,
+	/// Id numbers of all target nodes.
+	pub id_numbers: BTreeMap<SerializablePublic, SerializableH256>,
+	/// Common (shared) encryption point.
+	pub common_point: Option<SerializablePublic>,
+	/// Encrypted point.
+	pub encrypted_point: Option<SerializablePublic>,
+	/// Contributions of key holders of the source cluster.
+	pub contributions: Vec<SerializableExportedShareContribution>,
+}
+
+/// Serializable contribution of key holder to the exported key share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableExportedShareContribution {
+	/// Key holder, which has computed the contribution.
+	pub node: SerializablePublic,
+	/// Commitments to coefficients of key holder polynom.
+	pub commitments: Vec<SerializablePublic>,
+	/// Encrypted value of key holder polynom at id number of the target node.
+	pub encrypted_sub_share: SerializableBytes,
+}
+
+/// Serializable result of keys export.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializableKeyExportReport {
+	/// Key shares for target nodes.
+	pub key_shares: Vec<SerializableExportedKeyShare>,
+	/// Keys, which have failed to export.
+	pub failed_keys: Vec<SerializableH256>,
+}
+
+impl From<AuditRecord> for SerializableAuditRecord {
+	fn from(record: AuditRecord) -> Self {
+		SerializableAuditRecord {
+			operation: record.operation.into(),
+			key_id: record.key_id.into(),
+			requester: record.requester.map(Into::into),
+			nodes: record.nodes.into_iter().map(Into::into).collect(),
+			started: record.started,
+			finished: record.finished,
+			error: record.error,
+		}
+	}
+}
+
+impl From<SerializableAuditRecord> for AuditRecord {
+	fn from(record: SerializableAuditRecord) -> Self {
+		AuditRecord {
+			operation: record.operation.into(),
+			key_id: record.key_id.into(),
+			requester: record.requester.map(Into::into),
+			nodes: record.nodes.into_iter().map(Into::into).collect(),
+			started: record.started,
+			finished: record.finished,
+			error: record.error,
+		}
+	}
+}
+
+impl From<PeerHealth> for SerializablePeerHealth {
+	fn from(health: PeerHealth) -> Self {
+		SerializablePeerHealth {
+			node: health.node.into(),
+			is_connected: health.is_connected,
+			last_session: health.last_session,
+			clock_skew: health.clock_skew,
+		}
+	}
+}
+
+impl From<ClusterHealth> for SerializableClusterHealth {
+	fn from(health: ClusterHealth) -> Self {
+		SerializableClusterHealth {
+			peers: health.peers.into_iter().map(Into::into).collect(),
+			is_key_storage_available: health.is_key_storage_available,
+			is_externally_reachable: health.is_externally_reachable,
+		}
+	}
+}
+
+impl From<DrainReport> for SerializableDrainReport {
+	fn from(report: DrainReport) -> Self {
+		SerializableDrainReport {
+			id: report.id,
+			moved_keys: report.moved_keys.into_iter().map(Into::into).collect(),
+			failed_keys: report.failed_keys.into_iter().map(Into::into).collect(),
+			error: report.error,
+			is_completed: report.is_completed,
+			is_cancelled: report.is_cancelled,
+			is_safe_to_shutdown: report.is_safe_to_shutdown,
+		}
+	}
+}
+
+impl From<BootstrapReport> for SerializableBootstrapReport {
+	fn from(report: BootstrapReport) -> Self {
+		SerializableBootstrapReport {
+			moved_keys: report.moved_keys.into_iter().map(Into::into).collect(),
+			skipped_keys: report.skipped_keys.into_iter().map(Into::into).collect(),
+			error: report.error,
+			next: report.next.map(Into::into),
+		}
+	}
+}
+
+impl From<PeerLists> for SerializablePeerLists {
+	fn from(lists: PeerLists) -> Self {
+		SerializablePeerLists {
+			allowed: lists.allowed.into_iter().map(Into::into).collect(),
+			denied: lists.denied.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+impl From<SerializablePeerLists> for PeerLists {
+	fn from(lists: SerializablePeerLists) -> Self {
+		PeerLists {
+			allowed: lists.allowed.into_iter().map(Into::into).collect(),
+			denied: lists.denied.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+impl From<KeyInfo> for SerializableKeyInfo {
+	fn from(key: KeyInfo) -> Self {
+		SerializableKeyInfo {
+			key_id: key.key_id.into(),
+			threshold: key.threshold,
+			holders: key.holders.into_iter().map(Into::into).collect(),
+			created: key.created,
+			label: key.label,
+			requester: key.requester.map(Into::into),
+			last_accessed: key.last_accessed,
+		}
+	}
+}
+
+impl From<KeyList> for SerializableKeyList {
+	fn from(list: KeyList) -> Self {
+		SerializableKeyList {
+			keys: list.keys.into_iter().map(Into::into).collect(),
+			next: list.next.map(Into::into),
+		}
+	}
+}
+
+impl From<RemovedKeyInfo> for SerializableRemovedKeyInfo {
+	fn from(key: RemovedKeyInfo) -> Self {
+		SerializableRemovedKeyInfo {
+			key_id: key.key_id.into(),
+			removed: key.removed,
+			is_deleted: key.is_deleted,
+		}
+	}
+}
+
+impl From<KeyBackupReport> for SerializableKeyBackupReport {
+	fn from(report: KeyBackupReport) -> Self {
+		SerializableKeyBackupReport {
+			keys: report.keys,
+			removed_keys: report.removed_keys,
+			tombstones: report.tombstones,
+		}
+	}
+}
+
+impl From<AttestedServerKeyPublic> for SerializableAttestedServerKeyPublic {
+	fn from(public: AttestedServerKeyPublic) -> Self {
+		SerializableAttestedServerKeyPublic {
+			public: public.public.into(),
+			attestations: public.attestations.into_iter().map(|(node, signature)| SerializableServerKeyAttestation {
+				node: node.into(),
+				signature: signature.into(),
+			}).collect(),
+		}
+	}
+}
+
+impl From<ExportedKeyShare> for SerializableExportedKeyShare {
+	fn from(key_share: ExportedKeyShare) -> Self {
+		SerializableExportedKeyShare {
+			key_id: key_share.key_id.into(),
+			node: key_share.node.into(),
+			public: key_share.public.into(),
+			author: key_share.author.into(),
+			threshold: key_share.threshold,
+			id_numbers: key_share.id_numbers.into_iter().map(|(node, id_number)| (node.into(), id_number.into())).collect(),
+			common_point: key_share.common_point.map(Into::into),
+			encrypted_point: key_share.encrypted_point.map(Into::into),
+			contributions: key_share.contributions.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+impl From<SerializableExportedKeyShare> for ExportedKeyShare {
+	fn from(key_share: SerializableExportedKeyShare) -> Self {
+		ExportedKeyShare {
+			key_id: key_share.key_id.into(),
+			node: key_share.node.into(),
+			public: key_share.public.into(),
+			author: key_share.author.into(),
+			threshold: key_share.threshold,
+			id_numbers: key_share.id_numbers.into_iter().map(|(node, id_number)| (node.into(), id_number.into())).collect(),
+			common_point: key_share.common_point.map(Into::into),
+			encrypted_point: key_share.encrypted_point.map(Into::into),
+			contributions: key_share.contributions.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+impl From<ExportedShareContribution> for SerializableExportedShareContribution {
+	fn from(contribution: ExportedShareContribution) -> Self {
+		SerializableExportedShareContribution {
+			node: contribution.node.into(),
+			commitments: contribution.commitments.into_iter().map(Into::into).collect(),
+			encrypted_sub_share: contribution.encrypted_sub_share.into(),
+		}
+	}
+}
+
+impl From<SerializableExportedShareContribution> for ExportedShareContribution {
+	fn from(contribution: SerializableExportedShareContribution) -> Self {
+		ExportedShareContribution {
+			node: contribution.node.into(),
+			commitments: contribution.commitments.into_iter().map(Into::into).collect(),
+			encrypted_sub_share: contribution.encrypted_sub_share.into(),
+		}
+	}
+}
+
+impl From<KeyExportReport> for SerializableKeyExportReport {
+	fn from(report: KeyExportReport) -> Self {
+		SerializableKeyExportReport {
+			key_shares: report.key_shares.into_iter().map(Into::into).collect(),
+			failed_keys: report.failed_keys.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+impl From<AuditOperation> for SerializableAuditOperation {
+	fn from(operation: AuditOperation) -> Self {
+		match operation {
+			AuditOperation::GenerateServerKey => SerializableAuditOperation::GenerateServerKey,
+			AuditOperation::StoreDocumentKey => SerializableAuditOperation::StoreDocumentKey,
+			AuditOperation::GenerateDocumentKey => SerializableAuditOperation::GenerateDocumentKey,
+			AuditOperation::RestoreDocumentKey => SerializableAuditOperation::RestoreDocumentKey,
+			AuditOperation::RestoreDocumentKeyShadow => SerializableAuditOperation::RestoreDocumentKeyShadow,
+			AuditOperation::ReEncryptDocumentKey => SerializableAuditOperation::ReEncryptDocumentKey,
+			AuditOperation::SignMessage => SerializableAuditOperation::SignMessage,
+			AuditOperation::SignMessageEcdsa => SerializableAuditOperation::SignMessageEcdsa,
+			AuditOperation::DeriveServerKey => SerializableAuditOperation::DeriveServerKey,
+			AuditOperation::DeleteServerKey => SerializableAuditOperation::DeleteServerKey,
+			AuditOperation::RestoreServerKeyPublic => SerializableAuditOperation::RestoreServerKeyPublic,
+		}
+	}
+}
+
+impl From<SerializableAuditOperation> for AuditOperation {
+	fn from(operation: SerializableAuditOperation) -> Self {
+		match operation {
+			SerializableAuditOperation::GenerateServerKey => AuditOperation::GenerateServerKey,
+			SerializableAuditOperation::StoreDocumentKey => AuditOperation::StoreDocumentKey,
+			SerializableAuditOperation::GenerateDocumentKey => AuditOperation::GenerateDocumentKey,
+			SerializableAuditOperation::RestoreDocumentKey => AuditOperation::RestoreDocumentKey,
+			SerializableAuditOperation::RestoreDocumentKeyShadow => AuditOperation::RestoreDocumentKeyShadow,
+			SerializableAuditOperation::ReEncryptDocumentKey => AuditOperation::ReEncryptDocumentKey,
+			SerializableAuditOperation::SignMessage => AuditOperation::SignMessage,
+			SerializableAuditOperation::SignMessageEcdsa => AuditOperation::SignMessageEcdsa,
+			SerializableAuditOperation::DeriveServerKey => AuditOperation::DeriveServerKey,
+			SerializableAuditOperation::DeleteServerKey => AuditOperation::DeleteServerKey,
+			SerializableAuditOperation::RestoreServerKeyPublic => AuditOperation::RestoreServerKeyPublic,
+		}
+	}
 }
 
 /// Serializable Bytes.
@@ -344,10 +853,64 @@ impl<'a> Deserialize<'a> for SerializablePublic {
 	}
 }
 
+/// Serializable session error code. Codes, unknown to this node, are deserialized as ErrorCode::Unknown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SerializableErrorCode(pub ErrorCode);
+
+impl From<ErrorCode> for SerializableErrorCode {
+	fn from(code: ErrorCode) -> Self {
+		SerializableErrorCode(code)
+	}
+}
+
+impl Into<ErrorCode> for SerializableErrorCode {
+	fn into(self) -> ErrorCode {
+		self.0
+	}
+}
+
+impl Serialize for SerializableErrorCode {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+		serializer.serialize_str(match self.0 {
+			ErrorCode::Unknown => "Unknown",
+			ErrorCode::AccessDenied => "AccessDenied",
+			ErrorCode::InvalidRequest => "InvalidRequest",
+			ErrorCode::InvalidState => "InvalidState",
+			ErrorCode::Unavailable => "Unavailable",
+			ErrorCode::InvalidMessage => "InvalidMessage",
+			ErrorCode::Storage => "Storage",
+			ErrorCode::StaleKey => "StaleKey",
+			ErrorCode::ConsensusUnreachable => "ConsensusUnreachable",
+			ErrorCode::Cancelled => "Cancelled",
+			ErrorCode::Internal => "Internal",
+		})
+	}
+}
+
+impl<'a> Deserialize<'a> for SerializableErrorCode {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'a> {
+		let s = String::deserialize(deserializer)?;
+		Ok(SerializableErrorCode(match s.as_ref() {
+			"AccessDenied" => ErrorCode::AccessDenied,
+			"InvalidRequest" => ErrorCode::InvalidRequest,
+			"InvalidState" => ErrorCode::InvalidState,
+			"Unavailable" => ErrorCode::Unavailable,
+			"InvalidMessage" => ErrorCode::InvalidMessage,
+			"Storage" => ErrorCode::Storage,
+			"StaleKey" => ErrorCode::StaleKey,
+			"ConsensusUnreachable" => ErrorCode::ConsensusUnreachable,
+			"Cancelled" => ErrorCode::Cancelled,
+			"Internal" => ErrorCode::Internal,
+			_ => ErrorCode::Unknown,
+		}))
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use serde_json;
-	use super::{SerializableBytes, SerializablePublic};
+	use types::all::ErrorCode;
+	use super::{SerializableBytes, SerializablePublic, SerializableErrorCode};
 
 	#[test]
 	fn serialize_and_deserialize_bytes() {
@@ -366,4 +929,16 @@ mod tests {
 		let public_deserialized: SerializablePublic = serde_json::from_str(&public_serialized).unwrap();
 		assert_eq!(public_deserialized, public);
 	}
+
+	#[test]
+	fn serialize_and_deserialize_error_code() {
+		let code = SerializableErrorCode(ErrorCode::Storage);
+		let code_serialized = serde_json::to_string(&code).unwrap();
+		assert_eq!(&code_serialized, r#""Storage""#);
+		let code_deserialized: SerializableErrorCode = serde_json::from_str(&code_serialized).unwrap();
+		assert_eq!(code_deserialized, code);
+
+		let code_deserialized: SerializableErrorCode = serde_json::from_str(r#""CodeFromNewerNode""#).unwrap();
+		assert_eq!(code_deserialized, SerializableErrorCode(ErrorCode::Unknown));
+	}
 }
@@ -0,0 +1,370 @@
+// Copyright 2015-2017 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Weak};
+use std::thread;
+use futures::{future, Future};
+use parking_lot::{RwLock, Mutex, Condvar};
+use ethcore::filter::Filter;
+use ethcore::log_entry::LogEntry;
+use ethcore::client::{Client, BlockChainClient, BlockId, ChainNotify};
+use native_contracts::SecretStoreService;
+use hash::keccak;
+use bigint::hash::{H256, H520};
+use bigint::prelude::U256;
+use util::Address;
+use bytes::Bytes;
+use acl_storage::is_synced;
+use key_server_set::KeyServerSet;
+use traits::{NodeKeyPair, KeyServer};
+use types::all::{Public, ServerKeyId, RequestSignature, ContractAddress};
+
+/// Name of the service contract in the registry.
+const SERVICE_CONTRACT_REGISTRY_NAME: &'static str = "secretstore_service";
+
+/// Server key generation has been requested.
+const SERVER_KEY_REQUESTED_EVENT_NAME: &'static [u8] = &*b"ServerKeyRequested(bytes32,bytes,uint256)";
+/// Document key store has been requested.
+const DOCUMENT_KEY_STORE_REQUESTED_EVENT_NAME: &'static [u8] = &*b"DocumentKeyStoreRequested(bytes32,bytes,bytes,bytes)";
+
+/// Max threshold, which could be requested using service contract.
+const MAX_THRESHOLD: usize = 0xFFFF;
+
+lazy_static! {
+	static ref SERVER_KEY_REQUESTED_EVENT_NAME_HASH: H256 = keccak(SERVER_KEY_REQUESTED_EVENT_NAME);
+	static ref DOCUMENT_KEY_STORE_REQUESTED_EVENT_NAME_HASH: H256 = keccak(DOCUMENT_KEY_STORE_REQUESTED_EVENT_NAME);
+}
+
+/// Service contract listener. Watches the service contract for server key generation && document key store
+/// requests, runs corresponding sessions and publishes results back to the contract.
+/// Every request is processed by the single key server of the current set, selected by the server key id.
+/// Transactions are signed by the engine signer, so it must be a key server account, known to the contract.
+pub struct ServiceContractListener {
+	/// Shared listener data.
+	data: Arc<ServiceContractListenerData>,
+	/// Service thread handle.
+	service_handle: Option<thread::JoinHandle<()>>,
+}
+
+/// Service contract listener data.
+struct ServiceContractListenerData {
+	/// Blockchain client.
+	client: Weak<Client>,
+	/// Service contract address source.
+	contract_address: ContractAddress,
+	/// Service contract at actual address.
+	contract: RwLock<Option<SecretStoreService>>,
+	/// Key server.
+	key_server: Arc<KeyServer>,
+	/// This node key pair.
+	self_key_pair: Arc<NodeKeyPair>,
+	/// Key servers set.
+	key_server_set: Arc<KeyServerSet>,
+	/// Service tasks queue.
+	tasks_queue: TasksQueue,
+}
+
+/// Service tasks queue.
+#[derive(Default)]
+struct TasksQueue {
+	/// Service event.
+	service_event: Condvar,
+	/// Service tasks.
+	service_tasks: Mutex<VecDeque<ServiceTask>>,
+}
+
+/// Service task.
+#[derive(Debug, Clone, PartialEq)]
+enum ServiceTask {
+	/// Generate server key (server_key_id, author signature, threshold).
+	GenerateServerKey(ServerKeyId, RequestSignature, usize),
+	/// Store document key (server_key_id, author signature, common point, encrypted point).
+	StoreDocumentKey(ServerKeyId, RequestSignature, Public, Public),
+	/// Shutdown listener.
+	Shutdown,
+}
+
+impl ServiceContractListener {
+	pub fn new(client: &Arc<Client>, contract_address: ContractAddress, key_server: Arc<KeyServer>, self_key_pair: Arc<NodeKeyPair>, key_server_set: Arc<KeyServerSet>) -> Arc<ServiceContractListener> {
+		let data = Arc::new(ServiceContractListenerData {
+			client: Arc::downgrade(client),
+			contract_address: contract_address,
+			contract: RwLock::new(None),
+			key_server: key_server,
+			self_key_pair: self_key_pair,
+			key_server_set: key_server_set,
+			tasks_queue: TasksQueue::default(),
+		});
+		data.update_contract(&**client);
+
+		let service_thread_data = data.clone();
+		let service_handle = thread::spawn(move || ServiceContractListener::run_service_thread(service_thread_data));
+		let contract = Arc::new(ServiceContractListener {
+			data: data,
+			service_handle: Some(service_handle),
+		});
+		client.add_notify(contract.clone());
+		contract
+	}
+
+	fn run_service_thread(data: Arc<ServiceContractListenerData>) {
+		loop {
+			match data.tasks_queue.wait() {
+				ServiceTask::Shutdown => break,
+				task => data.process_service_task(task),
+			}
+		}
+	}
+}
+
+impl ChainNotify for ServiceContractListener {
+	fn new_blocks(&self, _imported: Vec<H256>, _invalid: Vec<H256>, enacted: Vec<H256>, _retracted: Vec<H256>, _sealed: Vec<H256>, _proposed: Vec<Bytes>, _duration: u64) {
+		if enacted.is_empty() {
+			return;
+		}
+
+		let client = match self.data.client.upgrade() {
+			Some(client) => client,
+			None => return,
+		};
+		// requests from blocks, imported during sync, are most probably already processed
+		if !is_synced(&*client) {
+			return;
+		}
+
+		self.data.update_contract(&*client);
+		for task in self.data.read_service_tasks(&*client, enacted) {
+			self.data.tasks_queue.push(task);
+		}
+	}
+}
+
+impl Drop for ServiceContractListener {
+	fn drop(&mut self) {
+		if let Some(service_handle) = self.service_handle.take() {
+			self.data.tasks_queue.push(ServiceTask::Shutdown);
+			// ignore error as we are already closing
+			let _ = service_handle.join();
+		}
+	}
+}
+
+impl ServiceContractListenerData {
+	/// Update service contract address.
+	fn update_contract(&self, client: &Client) {
+		let contract_address = match self.contract_address {
+			ContractAddress::Registry => client.registry_address(SERVICE_CONTRACT_REGISTRY_NAME.to_owned()),
+			ContractAddress::Address(ref address) => Some(address.clone()),
+		};
+
+		let mut contract = self.contract.write();
+		if contract.as_ref().map(|c| c.address.clone()) != contract_address {
+			trace!(target: "secretstore", "{}: configuring for service contract from {:?}", self.self_key_pair.public(), contract_address);
+			*contract = contract_address.map(SecretStoreService::new);
+		}
+	}
+
+	/// Read service tasks, which must be processed by this key server, from given blocks.
+	fn read_service_tasks(&self, client: &Client, blocks: Vec<H256>) -> Vec<ServiceTask> {
+		let contract = match *self.contract.read() {
+			Some(ref contract) => contract.clone(),
+			None => return Vec::new(),
+		};
+
+		let key_servers = self.key_server_set.get().into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+		blocks.into_iter()
+			.flat_map(|block_hash| client.logs(Filter {
+				from_block: BlockId::Hash(block_hash.clone()),
+				to_block: BlockId::Hash(block_hash),
+				address: Some(vec![contract.address.clone()]),
+				topics: vec![
+					Some(vec![*SERVER_KEY_REQUESTED_EVENT_NAME_HASH, *DOCUMENT_KEY_STORE_REQUESTED_EVENT_NAME_HASH]),
+					None,
+					None,
+					None,
+				],
+				limit: None,
+			}))
+			.filter_map(|log| {
+				let task = parse_service_task(&contract, &log.entry);
+				if task.is_none() {
+					warn!(target: "secretstore", "{}: ignoring invalid service contract request in transaction {}",
+						self.self_key_pair.public(), log.transaction_hash);
+				}
+				task
+			})
+			.filter(|task| match *task {
+				ServiceTask::GenerateServerKey(ref server_key_id, _, _) | ServiceTask::StoreDocumentKey(ref server_key_id, _, _, _) =>
+					is_processed_by_this_key_server(&key_servers, self.self_key_pair.public(), server_key_id),
+				ServiceTask::Shutdown => false,
+			})
+			.collect()
+	}
+
+	/// Run session for given service task && publish its result.
+	fn process_service_task(&self, task: ServiceTask) {
+		match task {
+			ServiceTask::GenerateServerKey(server_key_id, signature, threshold) => {
+				let result = match self.key_server.generate_key(&server_key_id, &signature, threshold) {
+					Ok(server_key) => self.publish(|contract, client| contract.server_key_generated(|a, d| transact(client, a, d),
+						server_key_id.clone(), server_key.to_vec()).wait()),
+					Err(error) => {
+						warn!(target: "secretstore", "{}: server key {} generation, requested by service contract, has failed with: {}",
+							self.self_key_pair.public(), server_key_id, error);
+						self.publish(|contract, client| contract.server_key_generation_error(|a, d| transact(client, a, d),
+							server_key_id.clone()).wait())
+					},
+				};
+				if let Err(error) = result {
+					warn!(target: "secretstore", "{}: failed to publish server key {} generation result: {}",
+						self.self_key_pair.public(), server_key_id, error);
+				}
+			},
+			ServiceTask::StoreDocumentKey(server_key_id, signature, common_point, encrypted_point) => {
+				let result = match self.key_server.store_document_key(&server_key_id, &signature, common_point, encrypted_point) {
+					Ok(()) => self.publish(|contract, client| contract.document_key_stored(|a, d| transact(client, a, d),
+						server_key_id.clone()).wait()),
+					Err(error) => {
+						warn!(target: "secretstore", "{}: document key {} store, requested by service contract, has failed with: {}",
+							self.self_key_pair.public(), server_key_id, error);
+						self.publish(|contract, client| contract.document_key_store_error(|a, d| transact(client, a, d),
+							server_key_id.clone()).wait())
+					},
+				};
+				if let Err(error) = result {
+					warn!(target: "secretstore", "{}: failed to publish document key {} store result: {}",
+						self.self_key_pair.public(), server_key_id, error);
+				}
+			},
+			ServiceTask::Shutdown => unreachable!("Shutdown task is processed by service thread; qed"),
+		}
+	}
+
+	/// Publish result to the service contract.
+	fn publish<F>(&self, publish: F) -> Result<(), String> where F: FnOnce(&SecretStoreService, &Client) -> Result<(), String> {
+		let client = self.client.upgrade().ok_or("client is required to publish result".to_owned())?;
+		let contract = self.contract.read().clone().ok_or("service contract is not configured".to_owned())?;
+		publish(&contract, &*client)
+	}
+}
+
+impl TasksQueue {
+	/// Push new task to the queue.
+	fn push(&self, task: ServiceTask) {
+		let mut service_tasks = self.service_tasks.lock();
+		service_tasks.push_back(task);
+		self.service_event.notify_all();
+	}
+
+	/// Wait for new task.
+	fn wait(&self) -> ServiceTask {
+		let mut service_tasks = self.service_tasks.lock();
+		while service_tasks.is_empty() {
+			self.service_event.wait(&mut service_tasks);
+		}
+
+		service_tasks.pop_front()
+			.expect("loop above is only exited when there are tasks in the queue; qed")
+	}
+}
+
+/// Send transaction to the service contract.
+fn transact(client: &Client, contract_address: Address, data: Bytes) -> future::FutureResult<Bytes, String> {
+	future::done(client.transact_contract(contract_address, data)
+		.map(|_| Vec::new())
+		.map_err(|e| format!("{}", e)))
+}
+
+/// Parse service task from the service contract log entry.
+fn parse_service_task(contract: &SecretStoreService, log: &LogEntry) -> Option<ServiceTask> {
+	let event_name = match log.topics.first() {
+		Some(topic) if *topic == *SERVER_KEY_REQUESTED_EVENT_NAME_HASH => "ServerKeyRequested",
+		Some(topic) if *topic == *DOCUMENT_KEY_STORE_REQUESTED_EVENT_NAME_HASH => "DocumentKeyStoreRequested",
+		_ => return None,
+	};
+	let event = SecretStoreService::contract(contract)
+		.event(event_name.into())
+		.expect("service contract is known ahead of time to have both events; qed");
+	let topics = log.topics.iter().map(|t| t.0.clone()).collect();
+	let params = match event.decode_log(topics, log.data.clone()) {
+		Ok(params) => params.into_iter().map(|p| p.value).collect::<Vec<_>>(),
+		Err(_) => return None,
+	};
+
+	let server_key_id = params.get(0).and_then(|p| p.clone().to_fixed_bytes())
+		.and_then(|id| if id.len() == 32 { Some(ServerKeyId::from_slice(&id)) } else { None });
+	let signature = params.get(1).and_then(|p| p.clone().to_bytes())
+		.and_then(|s| if s.len() == 65 { Some(H520::from_slice(&s).into()) } else { None });
+	match (event_name, server_key_id, signature) {
+		("ServerKeyRequested", Some(server_key_id), Some(signature)) => params.get(2)
+			.and_then(|p| p.clone().to_uint())
+			.map(|t| U256::from(&t[..]))
+			.and_then(|t| if t <= U256::from(MAX_THRESHOLD) { Some(t.low_u64() as usize) } else { None })
+			.map(|threshold| ServiceTask::GenerateServerKey(server_key_id, signature, threshold)),
+		("DocumentKeyStoreRequested", Some(server_key_id), Some(signature)) => {
+			let common_point = params.get(2).and_then(|p| p.clone().to_bytes())
+				.and_then(|p| if p.len() == 64 { Some(Public::from_slice(&p)) } else { None });
+			let encrypted_point = params.get(3).and_then(|p| p.clone().to_bytes())
+				.and_then(|p| if p.len() == 64 { Some(Public::from_slice(&p)) } else { None });
+			match (common_point, encrypted_point) {
+				(Some(common_point), Some(encrypted_point)) =>
+					Some(ServiceTask::StoreDocumentKey(server_key_id, signature, common_point, encrypted_point)),
+				_ => None,
+			}
+		},
+		_ => None,
+	}
+}
+
+/// Check if request with given server key id must be processed by this key server.
+/// Server key ids space is split among key servers, so that every request is processed by the single key server.
+pub fn is_processed_by_this_key_server(key_servers: &[Public], self_public: &Public, server_key_id: &ServerKeyId) -> bool {
+	let this_server_index = match key_servers.iter().position(|k| k == self_public) {
+		Some(index) => index,
+		None => return false,
+	};
+
+	let server_key_id = U256::from(&server_key_id[..]);
+	(server_key_id % U256::from(key_servers.len())).low_u64() as usize == this_server_index
+}
+
+#[cfg(test)]
+mod tests {
+	use ethkey::{Random, Generator};
+	use types::all::ServerKeyId;
+	use super::is_processed_by_this_key_server;
+
+	#[test]
+	fn request_is_processed_by_single_key_server() {
+		let key_servers: Vec<_> = (0..5).map(|_| Random.generate().unwrap().public().clone()).collect();
+		for i in 0..32u64 {
+			let server_key_id: ServerKeyId = i.into();
+			assert_eq!(key_servers.iter()
+				.filter(|key_server| is_processed_by_this_key_server(&key_servers, key_server, &server_key_id))
+				.count(), 1);
+		}
+	}
+
+	#[test]
+	fn request_is_not_processed_by_unknown_key_server() {
+		let key_servers: Vec<_> = (0..3).map(|_| Random.generate().unwrap().public().clone()).collect();
+		let unknown_key_server = Random.generate().unwrap().public().clone();
+		for i in 0..32u64 {
+			assert!(!is_processed_by_this_key_server(&key_servers, &unknown_key_server, &i.into()));
+		}
+	}
+}
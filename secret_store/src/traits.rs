@@ -14,10 +14,13 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeSet;
 use ethkey::{KeyPair, Signature, Error as EthKeyError};
 use bigint::hash::H256;
-use types::all::{Error, Public, ServerKeyId, MessageHash, EncryptedMessageSignature, RequestSignature, EncryptedDocumentKey,
-	EncryptedDocumentKeyShadow};
+use types::all::{Error, Public, NodeId, ServerKeyId, MessageHash, EncryptedMessageSignature, RequestSignature, EncryptedDocumentKey,
+	EncryptedDocumentKeyShadow, EncryptedDocumentKeyWithEntropy, DocumentKeyShadows, ReEncryptedDocumentKey, AuditRecord,
+	ClusterHealth, DrainReport, BootstrapReport, PeerLists, KeyList, RemovedKeyInfo, AttestedServerKeyPublic, ExportedKeyShare,
+	KeyExportReport, KeyBackupReport, AdminRequestSignature};
 
 /// Node key pair.
 pub trait NodeKeyPair: Send + Sync {
@@ -37,6 +40,25 @@ pub trait ServerKeyGenerator {
 	/// `threshold + 1` is the minimal number of nodes, required to restore private key.
 	/// Result is a public portion of SK.
 	fn generate_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<Public, Error>;
+	/// Derive new SK from previously generated SK.
+	/// `parent_key_id` is identifier of previously generated SK.
+	/// `signature` is id of derived SK (keccak(parent_key_id | derivation_path)), signed with caller public key.
+	///   Caller must be the same as in the `generate_key` call of the parent SK.
+	/// `derivation_path` is the caller-provided derivation path. Different paths lead to different SKs.
+	/// Result is a public portion of derived SK.
+	fn derive_key(&self, parent_key_id: &ServerKeyId, signature: &RequestSignature, derivation_path: &H256) -> Result<Public, Error>;
+	/// Delete previously generated SK (and DK, if stored) from all key servers.
+	/// `key_id` is identifier of previously generated SK.
+	/// `signature` is `key_id`, signed with caller public key. Caller must be the same as in the `generate_key` call.
+	/// Deleted SK could not be generated or restored again.
+	/// Result is a set of key servers, which have failed to delete their shares of SK.
+	fn delete_key(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<BTreeSet<NodeId>, Error>;
+	/// Restore public portion of previously generated SK.
+	/// `key_id` is identifier of previously generated SK.
+	/// `signature` is `key_id`, signed with caller public key. Caller must be the author of SK or be on ACL for this function to succeed.
+	/// Result is a public portion of SK && signatures of keccak(key_id | public), made by (at least threshold + 1) key holders
+	/// with their node keys. Caller could check these signatures instead of trusting the single key server.
+	fn restore_key_public(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<AttestedServerKeyPublic, Error>;
 }
 
 /// Document key (DK) server.
@@ -55,6 +77,11 @@ pub trait DocumentKeyServer: ServerKeyGenerator {
 	/// `threshold + 1` is the minimal number of nodes, required to restore private key.
 	/// Result is a DK, encrypted with caller public key.
 	fn generate_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize) -> Result<EncryptedDocumentKey, Error>;
+	/// Generate and store both SK and DK. Unlike `generate_document_key`, DK is not solely a function of key server randomness.
+	/// `requester_entropy` is `r * T`, where `T` is generation point and `r` is random scalar, known only to the caller.
+	/// DK is computed as `s * (r * T)`, where `s` is random scalar, chosen by the key server.
+	/// Result is a DK, encrypted with caller public key, and the `s * T` point. Caller could check that DK is equal to `r * (s * T)`.
+	fn generate_document_key_with_entropy(&self, key_id: &ServerKeyId, signature: &RequestSignature, threshold: usize, requester_entropy: Public) -> Result<EncryptedDocumentKeyWithEntropy, Error>;
 	/// Restore previously stored DK.
 	/// DK is decrypted on the key server (which might be considered unsafe), and then encrypted with caller public key.
 	/// `key_id` is identifier of previously generated SK.
@@ -67,8 +94,24 @@ pub trait DocumentKeyServer: ServerKeyGenerator {
 	/// 2) calculate decrypt_shadows_sum = sum of all secrets from (1)
 	/// 3) calculate decrypt_shadow_point: decrypt_shadows_sum * result.common_point
 	/// 4) calculate decrypted_secret: result.decrypted_secret + decrypt_shadow_point
+	/// To verify decrypted DK on client:
+	/// 1) use requestor secret key to decrypt nodes decryption proofs from result.decrypt_proofs
+	/// 2) check proofs against SK public, result.common_point && result.encrypted_point (see `math::verify_decryption_proofs`)
 	/// Result is a DK shadow.
 	fn restore_document_key_shadow(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<EncryptedDocumentKeyShadow, Error>;
+	/// Restore multiple previously stored DKs shadows.
+	/// `requests` are pairs of SK identifier and this identifier, signed with caller public key. All requests must be signed by the same caller.
+	/// Decryption sessions are pipelined, so that the next DKs are restored while the results for previous DKs are consumed.
+	/// Result is an iterator over DKs shadows (see `restore_document_key_shadow`), yielded in the order of requests.
+	fn restore_document_key_shadows(&self, requests: Vec<(ServerKeyId, RequestSignature)>) -> Result<DocumentKeyShadows, Error>;
+	/// Re-encrypt previously stored DK with target public key. DK is never revealed to any of key servers.
+	/// `key_id` is identifier of previously generated SK.
+	/// `signature` is key_id, signed with caller public key. Caller must be on ACL for this function to succeed.
+	/// `target_public` is the public key, which DK must be re-encrypted with.
+	/// `target_signature` is keccak(key_id | target_public), signed with caller public key.
+	/// To decrypt DK on client: decrypted_secret = result.encrypted_point - target_secret * result.common_point.
+	/// Result is a DK, encrypted with target public key.
+	fn reencrypt_document_key(&self, key_id: &ServerKeyId, signature: &RequestSignature, target_public: &Public, target_signature: &RequestSignature) -> Result<ReEncryptedDocumentKey, Error>;
 }
 
 /// Message signer.
@@ -79,10 +122,109 @@ pub trait MessageSigner: ServerKeyGenerator {
 	/// `message` is the message to be signed.
 	/// Result is a signed message, encrypted with caller public key.
 	fn sign_message(&self, key_id: &ServerKeyId, signature: &RequestSignature, message: MessageHash) -> Result<EncryptedMessageSignature, Error>;
+	/// Sign message with previously generated SK, using ECDSA.
+	/// `key_id` is the caller-provided identifier of generated SK.
+	/// `signature` is `key_id`, signed with caller public key.
+	/// `message` is the message to be signed.
+	/// Result is a 65-bytes (r, s, v) ECDSA signature, encrypted with caller public key.
+	fn sign_message_ecdsa(&self, key_id: &ServerKeyId, signature: &RequestSignature, message: MessageHash) -> Result<EncryptedMessageSignature, Error>;
 }
 
+/// Audit log reader.
+pub trait AuditLogReader {
+	/// Read audit log records of given key.
+	/// `key_id` is identifier of previously generated SK.
+	/// `signature` is key_id, signed with caller public key. Caller must be on ACL for this function to succeed.
+	/// Result is the list of all operations with given key, requested from this key server.
+	fn key_audit_log(&self, key_id: &ServerKeyId, signature: &RequestSignature) -> Result<Vec<AuditRecord>, Error>;
+}
+
+/// Key server metrics && health reader.
+pub trait MetricsReader {
+	/// Read metrics of this key server && its view of the cluster.
+	/// Result is the metrics in Prometheus text exposition format.
+	fn metrics(&self) -> Result<String, Error>;
+	/// Read health of this key server && its view of the cluster.
+	fn health(&self) -> Result<ClusterHealth, Error>;
+}
+
+/// Key server administration. Every administrative request must be signed by the key server operator (i.e. with
+/// the key server key) within ADMIN_REQUEST_WINDOW seconds before it is processed. Every signed request is only accepted once.
+pub trait AdministrationServer {
+	/// Start draining this key server before shutdown: stop accepting new sessions, wait until in-flight sessions are completed
+	/// && move key shares of this key server to other key servers. The drain continues in background after this call.
+	/// `signature` is the request signature of this key server operator. Only key server operator is allowed to drain it.
+	/// `targets` are key servers, which key shares are moved to. Every key share is moved to the target, which is not
+	///   yet holding the key. Key shares are left untouched if `targets` are empty.
+	/// New sessions are refused by this key server after this call, even if it has failed, until the drain is cancelled.
+	/// Result is the initial report, which id is used to request drain progress.
+	fn drain(&self, signature: &AdminRequestSignature, targets: BTreeSet<NodeId>) -> Result<DrainReport, Error>;
+	/// Get progress of the drain with given id. Only the latest drain could be requested.
+	/// `signature` is the request signature of this key server operator.
+	/// Result is the report, telling whether it is safe to shutdown this key server.
+	fn drain_report(&self, signature: &AdminRequestSignature, id: u64) -> Result<DrainReport, Error>;
+	/// Cancel the drain with given id (either running, or completed) && accept new sessions again. Key shares, which are
+	/// not yet moved, are left on this key server. Only the latest drain could be cancelled.
+	/// `signature` is the request signature of this key server operator.
+	fn cancel_drain(&self, signature: &AdminRequestSignature, id: u64) -> Result<DrainReport, Error>;
+	/// Get allow/deny lists of cluster connections of this key server.
+	/// `signature` is the request signature of this key server operator.
+	fn peer_lists(&self, signature: &AdminRequestSignature) -> Result<PeerLists, Error>;
+	/// Allow cluster connections with given node. If allow list is not empty, connections with all other nodes are refused.
+	/// `signature` is the request signature of this key server operator.
+	fn allow_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error>;
+	/// Deny cluster connections with given node. Existing connection with this node is closed immediately.
+	/// `signature` is the request signature of this key server operator.
+	fn deny_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error>;
+	/// Remove given node from both allow && deny lists.
+	/// `signature` is the request signature of this key server operator.
+	fn forget_peer(&self, signature: &AdminRequestSignature, node: NodeId) -> Result<(), Error>;
+	/// Set human-readable label of the key, stored by this key server. Label is removed if `label` is None.
+	/// `signature` is the request signature of this key server operator.
+	fn set_key_label(&self, signature: &AdminRequestSignature, key_id: &ServerKeyId, label: Option<String>) -> Result<(), Error>;
+	/// List keys, stored by this key server, in ascending order of their ids.
+	/// `signature` is the request signature of this key server operator.
+	/// `after` is the id of the key, after which listing starts (`next` of the previous page). Listing starts from the first key if None.
+	/// `limit` is max number of keys in the result.
+	fn list_keys(&self, signature: &AdminRequestSignature, after: Option<ServerKeyId>, limit: usize) -> Result<KeyList, Error>;
+	/// List keys, removed from this key server by share move && key deletion sessions, which are not yet purged.
+	/// `signature` is the request signature of this key server operator.
+	fn list_removed_keys(&self, signature: &AdminRequestSignature) -> Result<Vec<RemovedKeyInfo>, Error>;
+	/// Restore key share, removed from this key server by share move or key deletion session. Deleted key is not treated as deleted anymore.
+	/// Only the local key share is restored: key holders, which have changed their shares since removal, must be restored separately.
+	/// `signature` is the request signature of this key server operator.
+	fn restore_removed_key(&self, signature: &AdminRequestSignature, key_id: &ServerKeyId) -> Result<(), Error>;
+	/// Bootstrap new (i.e. cold-standby) key server: move key shares of this key server to the `new_node`, which is not yet holding these keys.
+	/// Key shares are moved in batches, in ascending order of key ids. Secret shares are encrypted with the key, agreed by this
+	/// key server && the `new_node`.
+	/// `signature` is the request signature of this key server operator, or of the operator of the connected key server, which
+	///   keys are moved. In the latter case, request is forwarded to that key server. Admin sessions are signed by the key server,
+	///   which keys are moved. This signature is checked by the `new_node` && other key holders. Every batch is forwarded
+	///   separately => forwarded bootstrap is stopped after ADMIN_REQUEST_WINDOW && must be resumed with the new request.
+	/// `after` is the id of the key, after which bootstrap starts (`next` of the previous report). Bootstrap starts from the first key if None.
+	fn bootstrap_node(&self, signature: &AdminRequestSignature, new_node: NodeId, after: Option<ServerKeyId>) -> Result<BootstrapReport, Error>;
+	/// Export keys to other key server cluster: re-share every key among `targets` nodes with given `threshold`. Every exported key share
+	/// holds values, encrypted with the key, agreed by contributing key holder && the target node, so that the key shares could be
+	/// delivered to target nodes by untrusted party. Keys, which have failed to export, are listed in the report.
+	/// `signature` is the request signature of this key server operator, or of the operator of the connected key server, which
+	///   exports the keys. In the latter case, request is forwarded to that key server. Admin sessions are signed by the key server,
+	///   which exports the keys. This signature is checked by other contributing key holders.
+	fn export_keys(&self, signature: &AdminRequestSignature, keys: BTreeSet<ServerKeyId>, targets: BTreeSet<NodeId>, threshold: usize) -> Result<KeyExportReport, Error>;
+	/// Import key share, exported from other key server cluster for this key server.
+	/// `signature` is the request signature of this key server operator.
+	fn import_key_share(&self, signature: &AdminRequestSignature, key_share: ExportedKeyShare) -> Result<(), Error>;
+	/// Write consistent snapshot of key storage of this key server to the backup file. Backup is encrypted with the key,
+	/// derived from this key server key => it could only be restored by the key server with the same key.
+	/// `signature` is the request signature of this key server operator.
+	/// `file_name` is the name of the backup file within backup directory of this key server. Existing file is replaced.
+	fn backup_keys(&self, signature: &AdminRequestSignature, file_name: String) -> Result<KeyBackupReport, Error>;
+	/// Restore key storage of this key server from the backup file, written by `backup_keys`. Key storage must be empty.
+	/// `signature` is the request signature of this key server operator.
+	/// `file_name` is the name of the backup file within backup directory of this key server.
+	fn restore_keys(&self, signature: &AdminRequestSignature, file_name: String) -> Result<KeyBackupReport, Error>;
+}
 
 /// Key server.
 #[ipc(client_ident="RemoteKeyServer")]
-pub trait KeyServer: DocumentKeyServer + MessageSigner + Send + Sync {
+pub trait KeyServer: DocumentKeyServer + MessageSigner + AuditLogReader + MetricsReader + AdministrationServer + Send + Sync {
 }
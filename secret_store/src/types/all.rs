@@ -52,8 +52,40 @@ pub enum Error {
 	Serde(String),
 	/// Database-related error
 	Database(String),
+	/// Too many sessions have been requested
+	RateLimited,
 	/// Internal error
 	Internal(String),
+	/// Cluster session has failed with given error code
+	Cluster(ErrorCode, String),
+}
+
+/// Category of cluster session error, which is sent to other nodes along with error text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[binary]
+pub enum ErrorCode {
+	/// Error is not categorized (i.e. it has been received from older node).
+	Unknown,
+	/// Access to the key is denied.
+	AccessDenied,
+	/// Request parameters are invalid. Retrying the same request is useless.
+	InvalidRequest,
+	/// Session is in invalid state for the request. Session must be restarted.
+	InvalidState,
+	/// Node is temporarily unable to process request. Request could be retried later.
+	Unavailable,
+	/// Invalid message has been received.
+	InvalidMessage,
+	/// Key storage has failed.
+	Storage,
+	/// Key share is outdated or deleted.
+	StaleKey,
+	/// Consensus is unreachable.
+	ConsensusUnreachable,
+	/// Session has been cancelled.
+	Cancelled,
+	/// Internal error.
+	Internal,
 }
 
 /// Secret store configuration
@@ -66,16 +98,67 @@ pub struct NodeAddress {
 	pub port: u16,
 }
 
+/// Key shares storage backend
+#[derive(Debug, Clone, PartialEq)]
+#[binary]
+pub enum KeyStorageBackend {
+	/// Key shares are stored in the database, located in the data directory.
+	Database,
+	/// Key shares are stored in the single encrypted file, located in the data directory.
+	File,
+	/// Key shares are kept in memory only. They are lost on restart. Useful for tests only.
+	Memory,
+}
+
+/// Contract address.
+#[derive(Debug, Clone, PartialEq)]
+#[binary]
+pub enum ContractAddress {
+	/// Address is read from registry.
+	Registry,
+	/// Address is specified.
+	Address(ethkey::Address),
+}
+
 /// Secret store configuration
 #[derive(Debug)]
 #[binary]
 pub struct ServiceConfiguration {
 	/// HTTP listener address. If None, HTTP API is disabled.
 	pub listener_address: Option<NodeAddress>,
+	/// Service contract address. If None, service contract is not watched.
+	pub service_contract_address: Option<ContractAddress>,
 	/// Is ACL check enabled. If false, everyone has access to all keys. Useful for tests only.
 	pub acl_check_enabled: bool,
+	/// Path to the ACL file. If None, ACL is read from the on-chain contract.
+	pub acl_file: Option<String>,
+	/// URL of the HTTP policy service, which is asked for access decisions. Ignored if ACL file is set.
+	/// If None, ACL is read from the on-chain contract.
+	pub acl_service: Option<String>,
+	/// Time (in seconds), during which decisions of HTTP policy service are cached. None means default value.
+	pub acl_cache_ttl: Option<u64>,
+	/// Path to the audit log file. If None, audit log is disabled.
+	pub audit_log: Option<String>,
+	/// Is unauthenticated health-check endpoint of HTTP listener enabled.
+	pub health_check_enabled: bool,
+	/// Path to the file with HTTP API keys (one key per line). If None, HTTP requests are not authenticated.
+	pub http_api_keys: Option<String>,
+	/// Origins, which are allowed to make cross-origin HTTP requests. If None, cross-origin requests are refused.
+	pub http_cors: Option<Vec<String>>,
+	/// Allowed values of the Host header of HTTP requests. If None, any Host header is accepted.
+	pub http_hosts: Option<Vec<String>>,
+	/// Path to the PEM file with HTTP listener TLS certificate (chain). If None, TLS is disabled.
+	pub http_tls_certificate: Option<String>,
+	/// Path to the PEM file with HTTP listener TLS private key. If None, TLS is disabled.
+	pub http_tls_private_key: Option<String>,
+	/// Path to the PEM file with CA certificates, used to verify HTTP clients certificates. If None, client certificates are not required.
+	pub http_tls_client_ca: Option<String>,
+	/// Max number of document re-keyings after ACL revocation, started within a minute. If None, documents are not re-keyed.
+	pub rekeyings_per_minute: Option<usize>,
 	/// Data directory path for secret store
 	pub data_path: String,
+	/// Key shares storage backend.
+	pub key_storage: KeyStorageBackend,
 	/// Cluster configuration.
 	pub cluster_config: ClusterConfiguration,
 }
@@ -93,6 +176,362 @@ pub struct ClusterConfiguration {
 	/// Allow outbound connections to 'higher' nodes.
 	/// This is useful for tests, but slower a bit for production.
 	pub allow_connecting_to_higher_nodes: bool,
+	/// Limits of decryption && signing sessions, started by this node.
+	pub rate_limits: SessionsRateLimits,
+	/// Limits of messages, received from every other node.
+	pub peer_rate_limits: PeerRateLimits,
+	/// Select nodes for decryption sessions in their natural order, instead of preferring nodes with lowest latency && load.
+	pub deterministic_nodes_selection: bool,
+	/// Read-only nodes. These nodes are holding key shares and are participating in sessions,
+	/// but are not allowed to start share administration (recovery && refresh) sessions.
+	pub read_only_nodes: Vec<ethkey::Public>,
+	/// Timeouts of sessions && session messages.
+	pub timeouts: ClusterTimeouts,
+	/// Address, which is used by other nodes to connect to this node, if it differs from the listener address (i.e. when behind NAT).
+	pub external_address: Option<NodeAddress>,
+	/// Map listener port on the gateway using UPnP, if external address is not set.
+	pub upnp_enabled: bool,
+	/// Max share (in percents) of admin sessions messages in processed messages, while user sessions are running.
+	/// None if not limited.
+	pub admin_messages_share: Option<usize>,
+	/// Time (in seconds), during which key shares, removed by share move && key deletion sessions, are kept in the key storage.
+	/// None means default value.
+	pub removed_keys_retention: Option<u64>,
+	/// Time (in seconds), during which shadow decryption results are cached by this node && returned to the same requester
+	/// without starting new decryption session. None if results are not cached.
+	pub decryption_cache_ttl: Option<u64>,
+	/// Max number of bytes, sent && received by single session on this node. Session is aborted when it exceeds this limit.
+	/// None if not limited.
+	pub max_session_traffic: Option<u64>,
+	/// Path to the directory, where messages && state transitions of every session are journaled. None if journal is disabled.
+	pub session_journal: Option<String>,
+	/// Path to the directory, where key storage backups are written to && restored from. None if backups are disabled.
+	pub backup_path: Option<String>,
+}
+
+/// Timeouts (in seconds) of cluster sessions && session messages. None means default value.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[binary]
+pub struct ClusterTimeouts {
+	/// Generation session is stalled if no messages have been received within this interval.
+	pub generation_idle_timeout: Option<u64>,
+	/// Encryption session is stalled if no messages have been received within this interval.
+	pub encryption_idle_timeout: Option<u64>,
+	/// Decryption session is stalled if no messages have been received within this interval.
+	pub decryption_idle_timeout: Option<u64>,
+	/// Signing session is stalled if no messages have been received within this interval.
+	pub signing_idle_timeout: Option<u64>,
+	/// Share recovery session is stalled if no messages have been received within this interval.
+	pub share_recovery_idle_timeout: Option<u64>,
+	/// Share refresh session is stalled if no messages have been received within this interval.
+	pub share_refresh_idle_timeout: Option<u64>,
+	/// Session of any kind is stalled if it is not completed within this interval.
+	pub total_timeout: Option<u64>,
+	/// Max number of retries of session message, which can not be processed yet (i.e. while sessions are paused).
+	/// None means that message is retried until it is processed.
+	pub message_retries: Option<usize>,
+}
+
+/// Limits of requester-initiated (decryption && signing) sessions, started by this node. None means no limit.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[binary]
+pub struct SessionsRateLimits {
+	/// Max number of sessions, started by single requester within a minute.
+	pub requester_sessions_per_minute: Option<usize>,
+	/// Max number of concurrent sessions, started by single requester.
+	pub requester_concurrent_sessions: Option<usize>,
+	/// Max number of sessions, started within a minute.
+	pub sessions_per_minute: Option<usize>,
+	/// Max number of concurrent sessions.
+	pub concurrent_sessions: Option<usize>,
+}
+
+/// Per-node limits (messages per second) of messages, received from other key servers. When node exceeds any limit,
+/// it is disconnected for the throttle time. None means default value.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[binary]
+pub struct PeerRateLimits {
+	/// Max number of messages, which are starting new sessions on this node.
+	pub session_initializations: Option<u64>,
+	/// Max number of session error messages.
+	pub session_errors: Option<u64>,
+	/// Max number of other session messages.
+	pub session_messages: Option<u64>,
+	/// Max number of cluster-level messages.
+	pub cluster_messages: Option<u64>,
+	/// Time (in seconds), during which throttled node is disconnected.
+	pub throttle_time: Option<u64>,
+}
+
+/// Operation, requested from the key server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[binary]
+pub enum AuditOperation {
+	/// Server key generation.
+	GenerateServerKey,
+	/// Server key derivation.
+	DeriveServerKey,
+	/// Server key deletion.
+	DeleteServerKey,
+	/// Attested server key public retrieval.
+	RestoreServerKeyPublic,
+	/// Storing externally generated document key.
+	StoreDocumentKey,
+	/// Server && document key generation.
+	GenerateDocumentKey,
+	/// Document key retrieval.
+	RestoreDocumentKey,
+	/// Document key shadow retrieval.
+	RestoreDocumentKeyShadow,
+	/// Document key re-encryption.
+	ReEncryptDocumentKey,
+	/// Message signing.
+	SignMessage,
+	/// Message signing, using ECDSA.
+	SignMessageEcdsa,
+}
+
+/// Audit log record: single operation, requested from this key server.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct AuditRecord {
+	/// Requested operation.
+	pub operation: AuditOperation,
+	/// Server key id.
+	pub key_id: ServerKeyId,
+	/// Requester public. None if it can not be recovered from request signature.
+	pub requester: Option<ethkey::Public>,
+	/// Nodes, connected to this node when operation has been started. Session participants are selected from these nodes.
+	pub nodes: Vec<NodeId>,
+	/// Operation start time (seconds since unix epoch).
+	pub started: u64,
+	/// Operation finish time (seconds since unix epoch).
+	pub finished: u64,
+	/// Operation error. None if operation has succeeded.
+	pub error: Option<String>,
+}
+
+/// Health of other key server, as seen by this node.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct PeerHealth {
+	/// Key server id.
+	pub node: NodeId,
+	/// Is connection to the key server established.
+	pub is_connected: bool,
+	/// Time (seconds since unix epoch), when the last session with this key server has completed successfully.
+	pub last_session: Option<u64>,
+	/// Difference between key server clock && this node clock (in seconds), measured on last keep alive response.
+	pub clock_skew: Option<i64>,
+}
+
+/// Health of this key server && its view of the cluster.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct ClusterHealth {
+	/// All other key servers.
+	pub peers: Vec<PeerHealth>,
+	/// Is key storage available.
+	pub is_key_storage_available: bool,
+	/// Has the last reachability self-check connected to this node through its external address. None if external address is unknown
+	/// or it has not been checked yet.
+	pub is_externally_reachable: Option<bool>,
+}
+
+/// Signature of key server administrative request.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct AdminRequestSignature {
+	/// HTTP method of the request.
+	pub method: String,
+	/// Path of the request.
+	pub endpoint: String,
+	/// Time (seconds since unix epoch), when the request has been signed.
+	pub timestamp: u64,
+	/// Request nonce. Every request of the operator, signed at the same time, must have unique nonce.
+	pub nonce: u64,
+	/// keccak of the request body. Requests without body have keccak of the empty body.
+	pub body_hash: bigint::hash::H256,
+	/// keccak("{method} {endpoint} {timestamp} {nonce} {body_hash}"), signed with key server key.
+	pub signature: RequestSignature,
+}
+
+/// Progress of key server drain.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[binary]
+pub struct DrainReport {
+	/// Id of the drain, which is used to request its progress.
+	pub id: u64,
+	/// Keys, which shares have been moved to other key servers.
+	pub moved_keys: Vec<ServerKeyId>,
+	/// Keys, which shares have failed to move && are still held by this key server.
+	pub failed_keys: Vec<ServerKeyId>,
+	/// Error, which has stopped the drain. None if all key shares have been processed.
+	pub error: Option<String>,
+	/// Is the drain completed?
+	pub is_completed: bool,
+	/// Has the drain been cancelled? Key server accepts new sessions again after the drain is cancelled.
+	pub is_cancelled: bool,
+	/// Is it safe to shutdown this key server (i.e. the drain is completed, there are no active sessions
+	/// && all requested share moves have succeeded)?
+	pub is_safe_to_shutdown: bool,
+}
+
+/// Result of bootstrapping new key server with key shares of this key server.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[binary]
+pub struct BootstrapReport {
+	/// Keys, which shares have been moved to the new key server.
+	pub moved_keys: Vec<ServerKeyId>,
+	/// Keys, which shares have not been moved, because the new key server is already holding the key
+	/// or some of key holders are disconnected.
+	pub skipped_keys: Vec<ServerKeyId>,
+	/// Error, which has stopped the bootstrap. None if all key shares have been processed.
+	pub error: Option<String>,
+	/// Id of the key, after which the bootstrap must be resumed, if it has been stopped by error.
+	/// None means that it must be resumed from the first key.
+	pub next: Option<ServerKeyId>,
+}
+
+/// Key share, exported from this cluster for single node of the target cluster.
+/// Only the target node is able to decrypt contributions of key holders && compute its secret share.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct ExportedKeyShare {
+	/// Server key id.
+	pub key_id: ServerKeyId,
+	/// Target node, which must import this key share.
+	pub node: NodeId,
+	/// Public portion of server key.
+	pub public: ethkey::Public,
+	/// Key author.
+	pub author: ethkey::Public,
+	/// Key threshold in the target cluster.
+	pub threshold: usize,
+	/// Id numbers of all target nodes.
+	pub id_numbers: BTreeMap<NodeId, bigint::hash::H256>,
+	/// Common (shared) encryption point. None if document key is not yet stored.
+	pub common_point: Option<ethkey::Public>,
+	/// Encrypted point. None if document key is not yet stored.
+	pub encrypted_point: Option<ethkey::Public>,
+	/// Contributions of key holders of this cluster.
+	pub contributions: Vec<ExportedShareContribution>,
+}
+
+/// Contribution of single key holder to the key share of the target node.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct ExportedShareContribution {
+	/// Key holder, which has computed the contribution.
+	pub node: NodeId,
+	/// Commitments to coefficients of key holder polynom: coeff * G.
+	pub commitments: Vec<ethkey::Public>,
+	/// Value of key holder polynom at id number of the target node, encrypted with the key, agreed by key holder && the target node.
+	pub encrypted_sub_share: Vec<u8>,
+}
+
+/// Result of exporting keys to other cluster.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[binary]
+pub struct KeyExportReport {
+	/// Key shares for target nodes. Every key share must be imported by its target node.
+	pub key_shares: Vec<ExportedKeyShare>,
+	/// Keys, which have failed to export.
+	pub failed_keys: Vec<ServerKeyId>,
+}
+
+/// Peer access lists of key server.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[binary]
+pub struct PeerLists {
+	/// Nodes, which are allowed to connect. If not empty, connections with all other nodes are refused.
+	pub allowed: Vec<NodeId>,
+	/// Nodes, which are never allowed to connect.
+	pub denied: Vec<NodeId>,
+}
+
+/// Key, stored by key server.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct KeyInfo {
+	/// Server key id.
+	pub key_id: ServerKeyId,
+	/// Key threshold.
+	pub threshold: usize,
+	/// Nodes, holding shares of the key.
+	pub holders: Vec<NodeId>,
+	/// Time when the key has been generated (seconds since unix epoch).
+	pub created: Option<u64>,
+	/// Human-readable label of the key.
+	pub label: Option<String>,
+	/// Requester, which has triggered key generation.
+	pub requester: Option<Public>,
+	/// Last time when the key has been used to serve request to this key server (seconds since unix epoch).
+	pub last_accessed: Option<u64>,
+}
+
+/// Single page of keys, stored by key server.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct KeyList {
+	/// Keys, in ascending order of their ids.
+	pub keys: Vec<KeyInfo>,
+	/// Id of the last key from this page, if there are more keys to list. Next page starts after this key.
+	pub next: Option<ServerKeyId>,
+}
+
+/// Key, removed from key server by share move or key deletion session. It is kept until purged.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct RemovedKeyInfo {
+	/// Server key id.
+	pub key_id: ServerKeyId,
+	/// Time when the key has been removed (seconds since unix epoch).
+	pub removed: u64,
+	/// True if the key has been deleted (i.e. the key with the same id could not be generated again).
+	pub is_deleted: bool,
+}
+
+/// Key storage backup (or restore) report.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[binary]
+pub struct KeyBackupReport {
+	/// Number of keys, written to (or restored from) the backup.
+	pub keys: usize,
+	/// Number of removed keys, which are not yet purged, written to (or restored from) the backup.
+	pub removed_keys: usize,
+	/// Number of deleted keys tombstones, written to (or restored from) the backup.
+	pub tombstones: usize,
+}
+
+/// Public portion of server key, attested by key holders.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct AttestedServerKeyPublic {
+	/// Public portion of server key.
+	pub public: ethkey::Public,
+	/// Signatures of keccak(key_id | public), made by key holders with their node keys. There are at least threshold + 1 of them.
+	pub attestations: BTreeMap<NodeId, ethkey::Signature>,
+}
+
+/// Document key, re-encrypted with public key of other requester.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct ReEncryptedDocumentKey {
+	/// Common point of re-encrypted document key.
+	pub common_point: ethkey::Public,
+	/// Encrypted point of re-encrypted document key. Can only be decrypted using secret of target requester.
+	pub encrypted_point: ethkey::Public,
+}
+
+/// Document key, generated using requester entropy.
+#[derive(Clone, Debug, PartialEq)]
+#[binary]
+pub struct EncryptedDocumentKeyWithEntropy {
+	/// Document key, encrypted with requester public key.
+	pub encrypted_key: Vec<u8>,
+	/// Server entropy point `s * T`. Document key is equal to `r * (s * T)`, where `r * T` is requester entropy point.
+	pub server_entropy: ethkey::Public,
 }
 
 /// Shadow decryption result.
@@ -105,8 +544,15 @@ pub struct EncryptedDocumentKeyShadow {
 	pub common_point: Option<ethkey::Public>,
 	/// If shadow decryption was requested: shadow decryption coefficients, encrypted with requestor public.
 	pub decrypt_shadows: Option<Vec<Vec<u8>>>,
+	/// If shadow decryption was requested: encrypted point of the document key.
+	pub encrypted_point: Option<ethkey::Public>,
+	/// If shadow decryption was requested: proofs of nodes decryption shares, encrypted with requestor public.
+	pub decrypt_proofs: Option<Vec<Vec<u8>>>,
 }
 
+/// Results of batch shadow decryption. Every item is yielded as soon as it is available, in the order of requests.
+pub type DocumentKeyShadows = Box<Iterator<Item=(ServerKeyId, Result<EncryptedDocumentKeyShadow, Error>)> + Send>;
+
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
 		match *self {
@@ -115,7 +561,9 @@ impl fmt::Display for Error {
 			Error::DocumentNotFound => write!(f, "Document not found"),
 			Error::Serde(ref msg) => write!(f, "Serialization error: {}", msg),
 			Error::Database(ref msg) => write!(f, "Database error: {}", msg),
+			Error::RateLimited => write!(f, "Too many sessions requested"),
 			Error::Internal(ref msg) => write!(f, "Internal error: {}", msg),
+			Error::Cluster(ref code, ref msg) => write!(f, "Session error ({:?}): {}", code, msg),
 		}
 	}
 }
@@ -135,8 +583,10 @@ impl From<ethkey::Error> for Error {
 impl From<key_server_cluster::Error> for Error {
 	fn from(err: key_server_cluster::Error) -> Self {
 		match err {
-			key_server_cluster::Error::AccessDenied => Error::AccessDenied,
-			_ => Error::Internal(err.into()),
+			key_server_cluster::Error::AccessDenied
+				| key_server_cluster::Error::Remote(ErrorCode::AccessDenied, _) => Error::AccessDenied,
+			key_server_cluster::Error::RateLimited => Error::RateLimited,
+			_ => Error::Cluster(err.code(), err.into()),
 		}
 	}
 }